@@ -6,14 +6,19 @@
 
 use floem::{
     event::EventListener,
+    keyboard::ModifiersState,
     reactive::{ReadSignal, RwSignal, Scope},
     style::CursorStyle,
     view::View,
     views::{container, label, list, stack, svg, Decorators},
 };
 
+use lapce_core::{command::FocusCommand, mode::Mode};
+
 use crate::{
+    command::{CommandExecuted, CommandKind, LapceCommand},
     config::{color::LapceColor, icon::LapceIcons, LapceConfig},
+    keypress::{condition::Condition, KeyPressFocus},
     window_tab::CommonData,
 };
 
@@ -37,6 +42,9 @@ pub struct AlertBoxData {
     pub title: RwSignal<String>,
     pub msg: RwSignal<String>,
     pub buttons: RwSignal<Vec<AlertButton>>,
+    /// The button chosen with the keyboard, where the one after the last of
+    /// `buttons` is Cancel.
+    pub selected: RwSignal<usize>,
     pub config: ReadSignal<Arc<LapceConfig>>,
 }
 
@@ -47,9 +55,70 @@ pub fn new(cx: Scope, common: CommonData) -> Self {
             title: cx.create_rw_signal("".to_string()),
             msg: cx.create_rw_signal("".to_string()),
             buttons: cx.create_rw_signal(Vec::new()),
+            selected: cx.create_rw_signal(0),
             config: common.config,
         }
     }
+
+    fn select(&self) {
+        let selected = self.selected.get_untracked();
+        let button = self
+            .buttons
+            .with_untracked(|buttons| buttons.get(selected).cloned());
+        if let Some(button) = button {
+            (button.action)();
+        } else {
+            self.active.set(false);
+        }
+    }
+}
+
+impl KeyPressFocus for AlertBoxData {
+    fn get_mode(&self) -> Mode {
+        Mode::Normal
+    }
+
+    fn check_condition(&self, condition: Condition) -> bool {
+        matches!(condition, Condition::ListFocus | Condition::ModalFocus)
+    }
+
+    fn run_command(
+        &self,
+        command: &LapceCommand,
+        _count: Option<usize>,
+        _mods: ModifiersState,
+    ) -> CommandExecuted {
+        if let CommandKind::Focus(cmd) = &command.kind {
+            // There's a Cancel button after the given ones
+            let len = self.buttons.with_untracked(|buttons| buttons.len()) + 1;
+            match cmd {
+                FocusCommand::ModalClose => {
+                    self.active.set(false);
+                }
+                FocusCommand::ListNext => {
+                    self.selected.update(|selected| {
+                        *selected = (*selected + 1) % len;
+                    });
+                }
+                FocusCommand::ListPrevious => {
+                    self.selected.update(|selected| {
+                        *selected = (*selected + len - 1) % len;
+                    });
+                }
+                FocusCommand::ListSelect => {
+                    self.select();
+                }
+                _ => {}
+            }
+        }
+        CommandExecuted::Yes
+    }
+
+    fn receive_char(&self, _c: &str) {}
+
+    fn focus_only(&self) -> bool {
+        true
+    }
 }
 
 pub fn alert_box(alert_data: AlertBoxData) -> impl View {
@@ -58,6 +127,7 @@ pub fn alert_box(alert_data: AlertBoxData) -> impl View {
     let title = alert_data.title;
     let msg = alert_data.msg;
     let buttons = alert_data.buttons;
+    let selected = alert_data.selected;
     let button_id = AtomicU64::new(0);
 
     container(|| {
@@ -80,12 +150,12 @@ pub fn alert_box(alert_data: AlertBoxData) -> impl View {
                     label(move || msg.get())
                         .style(move |s| s.width_pct(100.0).margin_top_px(10.0)),
                     list(
-                        move || buttons.get(),
+                        move || buttons.get().into_iter().enumerate(),
                         move |_button| {
                             button_id
                                 .fetch_add(1, std::sync::atomic::Ordering::Relaxed)
                         },
-                        move |button| {
+                        move |(i, button)| {
                             label(move || button.text.clone())
                                 .on_click(move |_| {
                                     (button.action)();
@@ -106,6 +176,11 @@ pub fn alert_box(alert_data: AlertBoxData) -> impl View {
                                             *config
                                                 .get_color(LapceColor::LAPCE_BORDER),
                                         )
+                                        .apply_if(selected.get() == i, |s| {
+                                            s.background(*config.get_color(
+                                                LapceColor::PANEL_HOVERED_BACKGROUND,
+                                            ))
+                                        })
                                 })
                                 .hover_style(move |s| {
                                     s.cursor(CursorStyle::Pointer).background(
@@ -139,6 +214,15 @@ pub fn alert_box(alert_data: AlertBoxData) -> impl View {
                                 .border_color(
                                     *config.get_color(LapceColor::LAPCE_BORDER),
                                 )
+                                .apply_if(
+                                    selected.get()
+                                        >= buttons.with(|buttons| buttons.len()),
+                                    |s| {
+                                        s.background(*config.get_color(
+                                            LapceColor::PANEL_HOVERED_BACKGROUND,
+                                        ))
+                                    },
+                                )
                         })
                         .hover_style(move |s| {
                             s.cursor(CursorStyle::Pointer).background(
@@ -0,0 +1,181 @@
+use std::path::{Path, PathBuf};
+
+use lapce_core::language::LapceLanguage;
+
+/// The folders in or next to the one of a file that its counterpart can be
+/// in, like the `tests` next to `src`
+const SIBLING_DIRS: &[&str] = &[
+    "src",
+    "include",
+    "inc",
+    "lib",
+    "test",
+    "tests",
+    "spec",
+    "__tests__",
+];
+
+/// The pairs of patterns of the files of `language` that are each other's
+/// counterparts, like a header and its source or a module and its tests,
+/// with `{name}` standing for the same name in both.
+pub fn default_alternates(language: LapceLanguage) -> Vec<(String, String)> {
+    let pairs: &[(&str, &str)] = match language {
+        LapceLanguage::C | LapceLanguage::Cpp => &[
+            ("{name}.h", "{name}.c"),
+            ("{name}.h", "{name}.cpp"),
+            ("{name}.h", "{name}.cc"),
+            ("{name}.h", "{name}.cxx"),
+            ("{name}.hpp", "{name}.cpp"),
+            ("{name}.hh", "{name}.cc"),
+        ],
+        LapceLanguage::Rust => &[
+            ("src/{name}.rs", "tests/{name}.rs"),
+            ("{name}.rs", "{name}_test.rs"),
+            ("{name}.rs", "{name}_tests.rs"),
+        ],
+        LapceLanguage::Go => &[("{name}.go", "{name}_test.go")],
+        LapceLanguage::Python => &[
+            ("{name}.py", "test_{name}.py"),
+            ("{name}.py", "{name}_test.py"),
+        ],
+        LapceLanguage::Javascript
+        | LapceLanguage::Jsx
+        | LapceLanguage::Typescript
+        | LapceLanguage::Tsx => &[
+            ("{name}.ts", "{name}.test.ts"),
+            ("{name}.ts", "{name}.spec.ts"),
+            ("{name}.tsx", "{name}.test.tsx"),
+            ("{name}.js", "{name}.test.js"),
+            ("{name}.js", "{name}.spec.js"),
+            ("{name}.jsx", "{name}.test.jsx"),
+        ],
+        LapceLanguage::Ruby => &[
+            ("{name}.rb", "{name}_spec.rb"),
+            ("{name}.rb", "{name}_test.rb"),
+        ],
+        LapceLanguage::Elixir => &[("{name}.ex", "{name}_test.exs")],
+        LapceLanguage::Java => &[("{name}.java", "{name}Test.java")],
+        LapceLanguage::Kotlin => &[("{name}.kt", "{name}Test.kt")],
+        LapceLanguage::Ocaml | LapceLanguage::OcamlInterface => {
+            &[("{name}.ml", "{name}.mli")]
+        }
+        _ => &[],
+    };
+    pairs
+        .iter()
+        .map(|(a, b)| (a.to_string(), b.to_string()))
+        .collect()
+}
+
+/// The paths the counterpart of `path` can be at by the patterns of `pairs`,
+/// which go both ways, the likeliest first: the ones of the more specific
+/// patterns, and the ones in the folder the patterns point to before the ones
+/// in the folders around it.
+pub fn alternate_candidates(
+    path: &Path,
+    pairs: &[(String, String)],
+) -> Vec<PathBuf> {
+    let mut matches = Vec::new();
+    for (a, b) in pairs {
+        for (from, to) in [(a, b), (b, a)] {
+            if let Some((base, name)) = match_pattern(path, from) {
+                matches.push((from.len(), base, to.replace("{name}", name)));
+            }
+        }
+    }
+    matches.sort_by(|(a, ..), (b, ..)| b.cmp(a));
+
+    let mut candidates = matches
+        .iter()
+        .map(|(_, base, counterpart)| base.join(counterpart))
+        .collect::<Vec<_>>();
+    for (_, base, counterpart) in &matches {
+        for dir in SIBLING_DIRS {
+            candidates.push(base.join(dir).join(counterpart));
+            if base.file_name() != Some(dir.as_ref()) {
+                if let Some(parent) = base.parent() {
+                    candidates.push(parent.join(dir).join(counterpart));
+                }
+            }
+        }
+    }
+
+    let mut seen = Vec::new();
+    candidates.retain(|candidate| {
+        if candidate == path || seen.contains(candidate) {
+            return false;
+        }
+        seen.push(candidate.clone());
+        true
+    });
+    candidates
+}
+
+/// The folder the end of `path` is in and the name `pattern` gives it, when
+/// the end of `path` is of `pattern`, which is a file name or a path of a few
+/// folders and a file name.
+fn match_pattern<'a>(path: &'a Path, pattern: &str) -> Option<(PathBuf, &'a str)> {
+    let (prefix, suffix) = pattern.split_once("{name}")?;
+    let depth = pattern.split('/').count();
+    let base = path.ancestors().nth(depth)?;
+    let end = path.strip_prefix(base).ok()?.to_str()?;
+    // The end is compared with the pattern with `/` between its folders
+    // whatever the platform
+    let end_len = end.len();
+    let prefix_len = prefix.len();
+    if end_len < prefix_len + suffix.len() {
+        return None;
+    }
+    let normalized = end.replace('\\', "/");
+    if !normalized.starts_with(prefix) || !normalized.ends_with(suffix) {
+        return None;
+    }
+    let name = &end[prefix_len..end_len - suffix.len()];
+    if name.is_empty() || name.contains(['/', '\\']) {
+        return None;
+    }
+    Some((base.to_path_buf(), name))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use lapce_core::language::LapceLanguage;
+
+    use super::{alternate_candidates, default_alternates};
+
+    fn candidates(path: &str, language: LapceLanguage) -> Vec<PathBuf> {
+        alternate_candidates(Path::new(path), &default_alternates(language))
+    }
+
+    #[test]
+    fn test_alternate_candidates() {
+        let found = candidates("/p/src/foo.h", LapceLanguage::C);
+        assert_eq!(found[0], Path::new("/p/src/foo.c"));
+        assert_eq!(found[1], Path::new("/p/src/foo.cpp"));
+        assert!(found.contains(&PathBuf::from("/p/include/foo.c")));
+
+        let found = candidates("/p/src/foo.cpp", LapceLanguage::Cpp);
+        assert_eq!(found[0], Path::new("/p/src/foo.h"));
+        assert_eq!(found[1], Path::new("/p/src/foo.hpp"));
+        assert!(found.contains(&PathBuf::from("/p/include/foo.h")));
+
+        let found = candidates("/p/src/foo.rs", LapceLanguage::Rust);
+        assert_eq!(found[0], Path::new("/p/tests/foo.rs"));
+        assert_eq!(found[1], Path::new("/p/src/foo_test.rs"));
+        assert!(!found.contains(&PathBuf::from("/p/src/foo.rs")));
+
+        let found = candidates("/p/tests/foo.rs", LapceLanguage::Rust);
+        assert_eq!(found[0], Path::new("/p/src/foo.rs"));
+
+        let found = candidates("/p/src/foo_test.rs", LapceLanguage::Rust);
+        assert_eq!(found[0], Path::new("/p/src/foo.rs"));
+
+        let found = candidates("/p/pkg/a_test.go", LapceLanguage::Go);
+        assert_eq!(found[0], Path::new("/p/pkg/a.go"));
+
+        assert!(candidates("/p/README.md", LapceLanguage::Markdown).is_empty());
+        assert!(candidates("/p/src/.rs", LapceLanguage::Rust).is_empty());
+    }
+}
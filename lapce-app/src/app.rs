@@ -5,13 +5,18 @@
     ops::Range,
     process::Stdio,
     rc::Rc,
-    sync::{atomic::AtomicU64, Arc},
+    sync::{
+        atomic::{AtomicBool, AtomicU64},
+        Arc,
+    },
+    time::Duration,
 };
 
 use anyhow::{anyhow, Result};
 use clap::Parser;
 use crossbeam_channel::Sender;
 use floem::{
+    action::{exec_after, TimerToken},
     cosmic_text::{Style as FontStyle, Weight},
     event::{Event, EventListener},
     ext_event::create_signal_from_channel,
@@ -36,17 +41,20 @@
     },
     window::{WindowConfig, WindowId},
 };
+use is_terminal::IsTerminal;
 use lapce_core::{
+    color_literal::hsv_to_rgb,
     command::{EditCommand, FocusCommand},
     directory::Directory,
     meta,
+    register::Clipboard,
 };
 use lapce_rpc::{
     core::{CoreMessage, CoreNotification},
     file::PathObject,
     RpcMessage,
 };
-use lsp_types::{CompletionItemKind, MessageType, ShowMessageParams};
+use lsp_types::{CompletionItemKind, MessageType};
 use notify::Watcher;
 use serde::{Deserialize, Serialize};
 use tracing::{error, metadata::LevelFilter, trace};
@@ -54,16 +62,19 @@
 
 use crate::{
     about, alert,
-    code_action::CodeActionStatus,
+    color_picker::ColorPickerData,
     command::{
         CommandKind, InternalCommand, LapceCommand, LapceWorkbenchCommand,
         WindowCommand,
     },
+    completion::{documentation_content, CompletionStatus},
     config::{
         color::LapceColor, icon::LapceIcons, watcher::ConfigWatcher, LapceConfig,
     },
+    crash,
     db::LapceDb,
     debug::RunDebugMode,
+    doc::{Document, SystemClipboard},
     editor::{
         diff::{diff_show_more_section_view, DiffEditorData},
         location::{EditorLocation, EditorPosition},
@@ -71,11 +82,14 @@
         EditorData,
     },
     editor_tab::{EditorTabChild, EditorTabData},
+    first_run,
     focus_text::focus_text,
     id::{DiffEditorId, EditorId, EditorTabId, SplitId},
+    job::{self, spawn_job},
     keymap::keymap_view,
     keypress::keymap::KeyMap,
     listener::Listener,
+    logging::{init_logging, log_targets, parse_log_level},
     main_split::{
         MainSplitData, SplitContent, SplitData, SplitDirection, SplitMoveDirection,
     },
@@ -85,14 +99,16 @@
         PaletteData, PaletteStatus,
     },
     panel::{position::PanelContainerPosition, view::panel_container_view},
+    perf,
     plugin::PluginData,
     settings::settings_view,
-    status::status,
+    status::{language_server_log, status},
+    stdin::{read_stdin, StdinText, STDIN_ARG},
     text_input::text_input,
     title::title,
     update::ReleaseInfo,
-    window::{TabsInfo, WindowData, WindowInfo},
-    window_tab::{Focus, WindowTabData},
+    window::{TabsInfo, WindowData, WindowInfo, WindowSetup},
+    window_tab::{Focus, WindowMessage, WindowTabData},
     workspace::{LapceWorkspace, LapceWorkspaceType},
 };
 
@@ -102,16 +118,30 @@
 #[derive(Debug)]
 struct Cli {
     /// Launch new window even if Lapce is already running
-    #[clap(short, long, action)]
+    #[clap(short, long, alias = "new-window", action)]
     new: bool,
-    /// Don't return instantly when opened in a terminal
+    /// Don't return until the window is closed, in a new instance, so that
+    /// Lapce can be the editor of other programs, like `git commit`
     #[clap(short, long, action)]
     wait: bool,
+    /// Set on the instance started in the background, so that it doesn't
+    /// start another
+    #[clap(long, hide = true, action)]
+    detached: bool,
+    /// Read stdin into a new untitled file if it isn't a terminal,
+    /// as `-` given as a path does
+    #[clap(long, action)]
+    stdin: bool,
+    /// The level of the messages to log, one of off, error, warn, info,
+    /// debug or trace, instead of the one of the `core.log-level` setting
+    #[clap(long, value_name = "LEVEL", value_parser = parse_log_level)]
+    log_level: Option<LevelFilter>,
 
     /// Paths to file(s) and/or folder(s) to open.
     /// When path is a file (that exists or not),
     /// it accepts `path:line:column` syntax
-    /// to specify line and column at which it should open the file
+    /// to specify line and column at which it should open the file.
+    /// `-` reads stdin into a new untitled file
     #[clap(value_parser = lapce_proxy::cli::parse_file_line_column)]
     #[clap(value_hint = clap::ValueHint::AnyPath)]
     paths: Vec<PathObject>,
@@ -138,10 +168,15 @@ pub struct AppData {
     pub window_scale: RwSignal<f64>,
     pub app_command: Listener<AppCommand>,
     pub app_terminated: RwSignal<bool>,
+    /// Whether Lapce was started with `--wait`, by a program waiting for it
+    /// to exit, which it does once its window is closed. Its window isn't
+    /// kept as the session to restore.
+    pub wait: bool,
     /// The latest release information
     pub latest_release: RwSignal<Arc<Option<ReleaseInfo>>>,
     pub watcher: Arc<notify::RecommendedWatcher>,
     pub tracing_handle: Handle<Targets>,
+    pub scope: Scope,
 }
 
 impl AppData {
@@ -152,6 +187,13 @@ pub fn reload_config(&self) {
         }
     }
 
+    /// Log the messages of `level` and the ones more severe from now on.
+    pub fn set_log_level(&self, level: LevelFilter) {
+        let _ = self
+            .tracing_handle
+            .modify(|targets| *targets = log_targets(level));
+    }
+
     pub fn active_window_tab(&self) -> Option<Rc<WindowTabData>> {
         if let Some(window) = self.active_window() {
             return window.active_window_tab();
@@ -168,6 +210,7 @@ fn active_window(&self) -> Option<WindowData> {
             .or_else(|| windows.iter().next().map(|(_, window)| window.clone()))
     }
 
+    /// Open a new window, once what it's opened with is read in a job.
     pub fn new_window(&self) {
         let config = self
             .active_window()
@@ -190,23 +233,29 @@ pub fn new_window(&self) {
         } else {
             config
         };
+        let info = WindowInfo {
+            size: Size::ZERO,
+            pos: Point::ZERO,
+            maximised: false,
+            tabs: TabsInfo {
+                active_tab: 0,
+                workspaces: vec![LapceWorkspace::default()],
+            },
+        };
+        let db: Arc<LapceDb> = use_context().unwrap();
         let app_data = self.clone();
-        floem::new_window(
-            move |window_id| {
-                app_data.app_view(
-                    window_id,
-                    WindowInfo {
-                        size: Size::ZERO,
-                        pos: Point::ZERO,
-                        maximised: false,
-                        tabs: TabsInfo {
-                            active_tab: 0,
-                            workspaces: vec![LapceWorkspace::default()],
-                        },
-                    },
-                )
+        spawn_job(
+            self.scope,
+            move || {
+                let setup = WindowSetup::load(&db, &info);
+                (info, setup)
+            },
+            move |(info, setup)| {
+                floem::new_window(
+                    move |window_id| app_data.app_view(window_id, info, setup),
+                    Some(config),
+                );
             },
-            Some(config),
         );
     }
 
@@ -225,6 +274,16 @@ pub fn run_app_command(&self, cmd: AppCommand) {
                     .try_update(|windows| windows.remove(&window_id))
                     .unwrap();
                 if let Some(window_data) = window_data {
+                    if self.wait && self.windows.with_untracked(|w| w.is_empty()) {
+                        // The event loop ends with its last window, so `launch`
+                        // returns and the program that's waiting, like git,
+                        // goes on
+                        window_data.window_tabs.with_untracked(|window_tabs| {
+                            for (_, window_tab) in window_tabs {
+                                window_tab.proxy.shutdown();
+                            }
+                        });
+                    }
                     window_data.scope.dispose();
                 }
                 let db: Arc<LapceDb> = use_context().unwrap();
@@ -234,7 +293,7 @@ pub fn run_app_command(&self, cmd: AppCommand) {
                 floem::close_window(window_id);
             }
             AppCommand::NewWindow => {
-                println!("run new window");
+                trace!("run new window");
                 self.new_window();
             }
             AppCommand::WindowGotFocus(window_id) => {
@@ -301,9 +360,10 @@ fn create_windows(
                 } else {
                     config
                 };
+                let setup = WindowSetup::load(&db, &info);
                 let app_data = self.clone();
                 app = app.window(
-                    move |window_id| app_data.app_view(window_id, info),
+                    move |window_id| app_data.app_view(window_id, info, setup),
                     Some(config),
                 );
             }
@@ -318,9 +378,10 @@ fn create_windows(
                     } else {
                         config
                     };
+                    let setup = WindowSetup::load(&db, &info);
                     let app_data = self.clone();
                     app = app.window(
-                        move |window_id| app_data.app_view(window_id, info),
+                        move |window_id| app_data.app_view(window_id, info, setup),
                         Some(config),
                     );
                 }
@@ -347,9 +408,10 @@ fn create_windows(
             } else {
                 config
             };
+            let setup = WindowSetup::load(&db, &info);
             let app_data = self.clone();
             app = app.window(
-                move |window_id| app_data.app_view(window_id, info),
+                move |window_id| app_data.app_view(window_id, info, setup),
                 Some(config),
             );
         }
@@ -389,10 +451,16 @@ fn create_windows(
         app
     }
 
-    fn app_view(&self, window_id: WindowId, info: WindowInfo) -> impl View {
+    fn app_view(
+        &self,
+        window_id: WindowId,
+        info: WindowInfo,
+        setup: WindowSetup,
+    ) -> impl View {
         let window_data = WindowData::new(
             window_id,
             info,
+            setup,
             self.window_scale,
             self.latest_release.read_only(),
             self.app_command,
@@ -448,7 +516,7 @@ fn app_view(&self, window_id: WindowId, info: WindowInfo) -> impl View {
             true
         })
         .on_event(EventListener::WindowClosed, move |_| {
-            println!("window closed");
+            trace!("window closed");
             app_command.send(AppCommand::WindowClosed(window_id));
             true
         })
@@ -1505,7 +1573,11 @@ fn split_list(
                     }
                 }
             })
-            .style(move |s| s.flex_grow(1.0).flex_basis(Dimension::Points(1.0)))
+            .style(move |s| {
+                let weight = split.with(|split| split.weight(&content));
+                s.flex_grow(weight as f32)
+                    .flex_basis(Dimension::Points(1.0))
+            })
     };
     container_box(move || {
         Box::new(
@@ -1677,6 +1749,19 @@ fn palette_item(
                 .to_string();
             // let (folder, _) = create_signal(cx.scope, folder);
             let folder_len = folder.len();
+            // References show the line they're on
+            let line = match &item.content {
+                PaletteItemContent::Reference {
+                    location, preview, ..
+                } => match &location.position {
+                    Some(EditorPosition::Position(position)) => {
+                        format!(":{}  {preview}", position.line + 1)
+                    }
+                    _ => String::new(),
+                },
+                _ => String::new(),
+            };
+            let has_line = !line.is_empty();
 
             let file_name_indices = item
                 .indices
@@ -1724,7 +1809,17 @@ fn palette_item(
                                     *config.get().get_color(LapceColor::EDITOR_FOCUS)
                                 },
                             )
-                            .style(|s| s.margin_right_px(6.0).max_width_pct(100.0)),
+                            .style(move |s| {
+                                s.margin_right_px(6.0)
+                                    .max_width_pct(100.0)
+                                    .apply_if(has_line, |s| s.margin_right_px(0.0))
+                            }),
+                            label(move || line.clone()).style(move |s| {
+                                s.margin_right_px(6.0)
+                                    .min_width_px(0.0)
+                                    .text_ellipsis()
+                                    .apply_if(!has_line, |s| s.hide())
+                            }),
                             focus_text(
                                 move || folder.clone(),
                                 move || folder_indices.clone(),
@@ -1838,22 +1933,20 @@ fn palette_item(
             kind,
             name,
             location,
-            ..
+            container_name,
         } => {
             let text = name.to_string();
             let kind = *kind;
 
-            let path = location.path.clone();
-            let full_path = location.path.clone();
-            let path = if let Some(workspace_path) = workspace.path.as_ref() {
-                path.strip_prefix(workspace_path)
-                    .unwrap_or(&full_path)
-                    .to_path_buf()
-            } else {
-                path
-            };
+            let path = workspace.display_path(&location.path);
 
-            let hint = path.to_string_lossy().to_string();
+            // The container first, as it follows the name in the filter text
+            let hint = match container_name {
+                Some(container_name) => {
+                    format!("{container_name} {}", path.to_string_lossy())
+                }
+                None => path.to_string_lossy().to_string(),
+            };
             let text_indices: Vec<usize> = item
                 .indices
                 .iter()
@@ -2078,11 +2171,15 @@ fn palette_item(
             })
         }
         PaletteItemContent::Line { .. }
+        | PaletteItemContent::Keymap { .. }
+        | PaletteItemContent::KeymapConflict { .. }
         | PaletteItemContent::Workspace { .. }
         | PaletteItemContent::SshHost { .. }
         | PaletteItemContent::Language { .. }
         | PaletteItemContent::ColorTheme { .. }
         | PaletteItemContent::SCMReference { .. }
+        | PaletteItemContent::Task { .. }
+        | PaletteItemContent::CreateFile { .. }
         | PaletteItemContent::IconTheme { .. } => {
             let text = item.filter_text;
             let indices = item.indices;
@@ -2344,79 +2441,108 @@ fn palette(window_tab_data: Rc<WindowTabData>) -> impl View {
 }
 
 fn window_message_view(
-    messages: RwSignal<Vec<(String, ShowMessageParams)>>,
+    messages: RwSignal<Vec<WindowMessage>>,
     config: ReadSignal<Arc<LapceConfig>>,
 ) -> impl View {
-    let view_fn =
-        move |(i, (title, message)): (usize, (String, ShowMessageParams))| {
-            stack(|| {
-                (
-                    svg(move || {
-                        if let MessageType::ERROR = message.typ {
-                            config.get().ui_svg(LapceIcons::ERROR)
-                        } else {
-                            config.get().ui_svg(LapceIcons::WARNING)
-                        }
+    let view_fn = move |(i, message): (usize, WindowMessage)| {
+        let WindowMessage {
+            title,
+            message,
+            count,
+            details,
+            ..
+        } = message;
+        let has_details = details.is_some();
+        stack(|| {
+            (
+                svg(move || {
+                    config.get().ui_svg(match message.typ {
+                        MessageType::ERROR => LapceIcons::ERROR,
+                        MessageType::WARNING => LapceIcons::WARNING,
+                        _ => LapceIcons::INFO,
                     })
-                    .style(move |s| {
-                        let config = config.get();
-                        let size = config.ui.icon_size() as f32;
-                        let color = if let MessageType::ERROR = message.typ {
-                            config.get_color(LapceColor::LAPCE_ERROR)
-                        } else {
-                            config.get_color(LapceColor::LAPCE_WARN)
-                        };
-                        s.min_width_px(size)
-                            .size_px(size, size)
-                            .margin_right_px(10.0)
-                            .margin_top_px(4.0)
-                            .color(*color)
-                    }),
-                    stack(|| {
-                        (
-                            label(move || title.clone()).style(|s| {
-                                s.min_width_px(0.0)
-                                    .line_height(1.6)
-                                    .font_weight(Weight::BOLD)
-                            }),
-                            label(move || message.message.clone()).style(|s| {
-                                s.min_width_px(0.0)
-                                    .line_height(1.6)
-                                    .margin_top_px(5.0)
+                })
+                .style(move |s| {
+                    let config = config.get();
+                    let size = config.ui.icon_size() as f32;
+                    let color = config.get_color(match message.typ {
+                        MessageType::ERROR => LapceColor::LAPCE_ERROR,
+                        MessageType::WARNING => LapceColor::LAPCE_WARN,
+                        _ => LapceColor::LAPCE_ICON_ACTIVE,
+                    });
+                    s.min_width_px(size)
+                        .size_px(size, size)
+                        .margin_right_px(10.0)
+                        .margin_top_px(4.0)
+                        .color(*color)
+                }),
+                stack(|| {
+                    (
+                        label(move || {
+                            if count > 1 {
+                                format!("{title} ({count})")
+                            } else {
+                                title.clone()
+                            }
+                        })
+                        .style(|s| {
+                            s.min_width_px(0.0)
+                                .line_height(1.6)
+                                .font_weight(Weight::BOLD)
+                        }),
+                        label(move || message.message.clone()).style(|s| {
+                            s.min_width_px(0.0).line_height(1.6).margin_top_px(5.0)
+                        }),
+                        label(|| "Copy Details".to_string())
+                            .on_click(move |_| {
+                                if let Some(details) = &details {
+                                    SystemClipboard::new().put_string(details);
+                                }
+                                true
+                            })
+                            .style(move |s| {
+                                s.margin_top_px(5.0)
+                                    .cursor(CursorStyle::Pointer)
+                                    .color(
+                                        *config
+                                            .get()
+                                            .get_color(LapceColor::EDITOR_LINK),
+                                    )
+                                    .apply_if(!has_details, |s| s.hide())
                             }),
-                        )
-                    })
-                    .style(move |s| {
-                        s.flex_col()
-                            .min_width_px(0.0)
-                            .flex_basis_px(0.0)
-                            .flex_grow(1.0)
-                    }),
-                    clickable_icon(
-                        || LapceIcons::CLOSE,
-                        move || {
-                            messages.update(|messages| {
-                                messages.remove(i);
-                            });
-                        },
-                        || false,
-                        || false,
-                        config,
                     )
-                    .style(|s| s.margin_left_px(6.0)),
+                })
+                .style(move |s| {
+                    s.flex_col()
+                        .min_width_px(0.0)
+                        .flex_basis_px(0.0)
+                        .flex_grow(1.0)
+                }),
+                clickable_icon(
+                    || LapceIcons::CLOSE,
+                    move || {
+                        messages.update(|messages| {
+                            messages.remove(i);
+                        });
+                    },
+                    || false,
+                    || false,
+                    config,
                 )
-            })
-            .style(move |s| {
-                let config = config.get();
-                s.width_pct(100.0)
-                    .items_start()
-                    .padding_px(10.0)
-                    .border(1.0)
-                    .border_radius(6.0)
-                    .border_color(*config.get_color(LapceColor::LAPCE_BORDER))
-                    .background(*config.get_color(LapceColor::PANEL_BACKGROUND))
-            })
-        };
+                .style(|s| s.margin_left_px(6.0)),
+            )
+        })
+        .style(move |s| {
+            let config = config.get();
+            s.width_pct(100.0)
+                .items_start()
+                .padding_px(10.0)
+                .border(1.0)
+                .border_radius(6.0)
+                .border_color(*config.get_color(LapceColor::LAPCE_BORDER))
+                .background(*config.get_color(LapceColor::PANEL_BACKGROUND))
+        })
+    };
 
     let id = AtomicU64::new(0);
     container(|| {
@@ -2436,7 +2562,7 @@ fn window_message_view(
     .style(|s| s.absolute().size_pct(100.0, 100.0).justify_end())
 }
 
-struct VectorItems<V>(im::Vector<V>);
+pub struct VectorItems<V>(pub im::Vector<V>);
 
 impl<V: Clone + 'static> VirtualListVector<(usize, V)> for VectorItems<V> {
     type ItemIterator = Box<dyn Iterator<Item = (usize, V)>>;
@@ -2477,6 +2603,33 @@ fn completion_kind_to_str(kind: CompletionItemKind) -> &'static str {
     }
 }
 
+/// A piece of the markdown shown in the hover and the completion
+/// documentation.
+fn markdown_content_view(
+    content: MarkdownContent,
+    max_width: f32,
+    config: ReadSignal<Arc<LapceConfig>>,
+) -> impl View {
+    match content {
+        MarkdownContent::Text(text_layout) => container_box(|| {
+            Box::new(
+                rich_text(move || text_layout.clone())
+                    .style(move |s| s.max_width_px(max_width)),
+            )
+        })
+        .style(|s| s.max_width_pct(100.0)),
+        MarkdownContent::Image { .. } => container_box(|| Box::new(empty())),
+        MarkdownContent::Separator => container_box(|| {
+            Box::new(empty().style(move |s| {
+                s.width_pct(100.0)
+                    .margin_vert_px(5.0)
+                    .height_px(1.0)
+                    .background(*config.get().get_color(LapceColor::LAPCE_BORDER))
+            }))
+        }),
+    }
+}
+
 fn hover(window_tab_data: Rc<WindowTabData>) -> impl View {
     let hover_data = window_tab_data.common.hover.clone();
     let config = window_tab_data.common.config;
@@ -2487,26 +2640,7 @@ fn hover(window_tab_data: Rc<WindowTabData>) -> impl View {
         list(
             move || hover_data.content.get(),
             move |_| id.fetch_add(1, std::sync::atomic::Ordering::Relaxed),
-            move |content| match content {
-                MarkdownContent::Text(text_layout) => container_box(|| {
-                    Box::new(
-                        rich_text(move || text_layout.clone())
-                            .style(|s| s.max_width_px(600.0)),
-                    )
-                })
-                .style(|s| s.max_width_pct(100.0)),
-                MarkdownContent::Image { .. } => container_box(|| Box::new(empty())),
-                MarkdownContent::Separator => container_box(|| {
-                    Box::new(empty().style(move |s| {
-                        s.width_pct(100.0)
-                            .margin_vert_px(5.0)
-                            .height_px(1.0)
-                            .background(
-                                *config.get().get_color(LapceColor::LAPCE_BORDER),
-                            )
-                    }))
-                }),
-            },
+            move |content| markdown_content_view(content, 600.0, config),
         )
         .style(|s| s.flex_col().padding_horiz_px(10.0).padding_vert_px(5.0))
     })
@@ -2536,6 +2670,46 @@ fn hover(window_tab_data: Rc<WindowTabData>) -> impl View {
     })
 }
 
+fn signature(window_tab_data: Rc<WindowTabData>) -> impl View {
+    let signature_data = window_tab_data.common.signature.clone();
+    let config = window_tab_data.common.config;
+    let id = AtomicU64::new(0);
+    let layout_rect = window_tab_data.common.signature.layout_rect;
+
+    scroll(|| {
+        list(
+            move || signature_data.content.get(),
+            move |_| id.fetch_add(1, std::sync::atomic::Ordering::Relaxed),
+            move |content| markdown_content_view(content, 600.0, config),
+        )
+        .style(|s| s.flex_col().padding_horiz_px(10.0).padding_vert_px(5.0))
+    })
+    .on_resize(move |rect| {
+        layout_rect.set(rect);
+    })
+    .on_event(EventListener::PointerMove, |_| true)
+    .style(move |s| {
+        let active = window_tab_data.common.signature.active.get();
+        if !active {
+            s.hide()
+        } else {
+            let config = config.get();
+            if let Some(origin) = window_tab_data.signature_origin() {
+                s.absolute()
+                    .margin_left_px(origin.x as f32)
+                    .margin_top_px(origin.y as f32)
+                    .max_height_px(200.0)
+                    .border(1.0)
+                    .border_radius(6.0)
+                    .border_color(*config.get_color(LapceColor::LAPCE_BORDER))
+                    .background(*config.get_color(LapceColor::PANEL_BACKGROUND))
+            } else {
+                s.hide()
+            }
+        }
+    })
+}
+
 fn completion(window_tab_data: Rc<WindowTabData>) -> impl View {
     let completion_data = window_tab_data.common.completion;
     let config = window_tab_data.common.config;
@@ -2554,12 +2728,40 @@ fn completion(window_tab_data: Rc<WindowTabData>) -> impl View {
                 stack(|| {
                     (
                         container(move || {
-                            label(move || {
-                                item.item
-                                    .kind
-                                    .map(completion_kind_to_str)
-                                    .unwrap_or("")
-                                    .to_string()
+                            let kind = item.item.kind;
+                            let has_icon = move || {
+                                kind.and_then(|kind| {
+                                    config.get().completion_svg(kind)
+                                })
+                                .is_some()
+                            };
+                            stack(|| {
+                                (
+                                    svg(move || {
+                                        kind.and_then(|kind| {
+                                            config.get().completion_svg(kind)
+                                        })
+                                        .unwrap_or_default()
+                                    })
+                                    .style(
+                                        move |s| {
+                                            let size =
+                                                config.get().ui.icon_size() as f32;
+                                            s.size_px(size, size)
+                                                .apply_if(!has_icon(), |s| s.hide())
+                                        },
+                                    ),
+                                    label(move || {
+                                        kind.map(completion_kind_to_str)
+                                            .unwrap_or("")
+                                            .to_string()
+                                    })
+                                    .style(
+                                        move |s| {
+                                            s.apply_if(has_icon(), |s| s.hide())
+                                        },
+                                    ),
+                                )
                             })
                             .style(move |s| {
                                 s.width_pct(100.0)
@@ -2633,7 +2835,9 @@ fn completion(window_tab_data: Rc<WindowTabData>) -> impl View {
     .on_event(EventListener::PointerMove, |_| true)
     .style(move |s| {
         let config = config.get();
-        let origin = window_tab_data.completion_origin();
+        let Some(origin) = window_tab_data.completion_origin() else {
+            return s.hide();
+        };
         s.position(Position::Absolute)
             .width_px(400.0)
             .max_height_px(400.0)
@@ -2646,6 +2850,63 @@ fn completion(window_tab_data: Rc<WindowTabData>) -> impl View {
     })
 }
 
+/// The documentation of the active completion item, next to the list.
+fn completion_documentation(window_tab_data: Rc<WindowTabData>) -> impl View {
+    let completion_data = window_tab_data.common.completion;
+    let config = window_tab_data.common.config;
+    let active = completion_data.with_untracked(|c| c.active);
+    let active_item = move || {
+        completion_data.with(|c| {
+            active.track();
+            (c.status != CompletionStatus::Inactive)
+                .then(|| c.current_item().map(|item| item.item.clone()))
+                .flatten()
+        })
+    };
+    let id = AtomicU64::new(0);
+
+    scroll(move || {
+        list(
+            move || {
+                active_item()
+                    .map(|item| documentation_content(&item, &config.get()))
+                    .unwrap_or_default()
+            },
+            move |_| id.fetch_add(1, std::sync::atomic::Ordering::Relaxed),
+            move |content| markdown_content_view(content, 400.0, config),
+        )
+        .style(|s| s.flex_col().padding_horiz_px(10.0).padding_vert_px(5.0))
+    })
+    .on_event(EventListener::PointerMove, |_| true)
+    .style(move |s| {
+        let has_documentation = matches!(
+            active_item(),
+            Some(item) if item.detail.is_some() || item.documentation.is_some()
+        );
+        if !has_documentation {
+            return s.hide();
+        }
+        let config = config.get();
+        let Some(origin) = window_tab_data.completion_origin() else {
+            return s.hide();
+        };
+        let tab_width = window_tab_data.layout_rect.get().width();
+        // Next to the list, or before it if there's no room after
+        let width = 400.0;
+        let mut x = origin.x + 400.0 + 5.0;
+        if x + width > tab_width {
+            x = (origin.x - width - 5.0).max(0.0);
+        }
+        s.absolute()
+            .width_px(width as f32)
+            .max_height_px(400.0)
+            .margin_left_px(x as f32)
+            .margin_top_px(origin.y as f32)
+            .background(*config.get_color(LapceColor::COMPLETION_BACKGROUND))
+            .border_radius(6.0)
+    })
+}
+
 fn code_action(window_tab_data: Rc<WindowTabData>) -> impl View {
     let config = window_tab_data.common.config;
     let code_action = window_tab_data.code_action;
@@ -2704,18 +2965,17 @@ fn code_action(window_tab_data: Rc<WindowTabData>) -> impl View {
     })
     .on_event(EventListener::PointerMove, |_| true)
     .style(move |s| {
-        let origin = window_tab_data.code_action_origin();
-        s.display(match status.get() {
-            CodeActionStatus::Inactive => Display::None,
-            CodeActionStatus::Active => Display::Flex,
-        })
-        .position(Position::Absolute)
-        .width_px(400.0)
-        .max_height_px(400.0)
-        .margin_left_px(origin.x as f32)
-        .margin_top_px(origin.y as f32)
-        .background(*config.get().get_color(LapceColor::COMPLETION_BACKGROUND))
-        .border_radius(6.0)
+        status.track();
+        let Some(origin) = window_tab_data.code_action_origin() else {
+            return s.hide();
+        };
+        s.position(Position::Absolute)
+            .width_px(400.0)
+            .max_height_px(400.0)
+            .margin_left_px(origin.x as f32)
+            .margin_top_px(origin.y as f32)
+            .background(*config.get().get_color(LapceColor::COMPLETION_BACKGROUND))
+            .border_radius(6.0)
     })
 }
 
@@ -2745,9 +3005,10 @@ fn rename(window_tab_data: Rc<WindowTabData>) -> impl View {
     .on_event(EventListener::PointerMove, |_| true)
     .on_event(EventListener::PointerDown, |_| true)
     .style(move |s| {
-        let origin = window_tab_data.rename_origin();
+        let Some(origin) = window_tab_data.rename_origin() else {
+            return s.hide();
+        };
         s.position(Position::Absolute)
-            .apply_if(!active.get(), |s| s.hide())
             .margin_left_px(origin.x as f32)
             .margin_top_px(origin.y as f32)
             .background(*config.get().get_color(LapceColor::PANEL_BACKGROUND))
@@ -2756,6 +3017,260 @@ fn rename(window_tab_data: Rc<WindowTabData>) -> impl View {
     })
 }
 
+/// The width of the bars of the color picker
+const COLOR_SLIDER_WIDTH: f64 = 150.0;
+
+/// A bar of the color picker, filled as far as the hue, the saturation or the
+/// value is, which sets it to where it's clicked or dragged to.
+fn color_slider(
+    picker: ColorPickerData,
+    name: &'static str,
+    channel: usize,
+) -> impl View {
+    let config = picker.common.config;
+    let hsv = picker.hsv;
+    let max = if channel == 0 { 360.0 } else { 1.0 };
+    let dragging = create_rw_signal(false);
+    let set = move |x: f64| {
+        let value = (x / COLOR_SLIDER_WIDTH).clamp(0.0, 1.0) * max;
+        picker.set_hsv(|hsv| match channel {
+            0 => hsv.0 = value,
+            1 => hsv.1 = value,
+            _ => hsv.2 = value,
+        });
+    };
+    let drag = set.clone();
+
+    let bar = container(|| {
+        empty().style(move |s| {
+            let (hue, saturation, value) = hsv.get();
+            let fraction = [hue, saturation, value][channel] / max;
+            // The bar of the hue is in the hue alone
+            let [r, g, b] = if channel == 0 {
+                hsv_to_rgb(hue, 1.0, 1.0)
+            } else {
+                hsv_to_rgb(hue, saturation, value)
+            };
+            s.height_pct(100.0)
+                .width_px((fraction * COLOR_SLIDER_WIDTH) as f32)
+                .background(Color::rgb8(r, g, b))
+        })
+    })
+    .style(move |s| {
+        let config = config.get();
+        s.width_px(COLOR_SLIDER_WIDTH as f32)
+            .height_px(12.0)
+            .border(1.0)
+            .border_radius(3.0)
+            .border_color(*config.get_color(LapceColor::LAPCE_BORDER))
+            .background(*config.get_color(LapceColor::EDITOR_BACKGROUND))
+            .cursor(CursorStyle::Pointer)
+    });
+    let id = bar.id();
+    let bar = bar
+        .on_event(EventListener::PointerDown, move |event| {
+            if let Event::PointerDown(pointer_event) = event {
+                id.request_active();
+                dragging.set(true);
+                set(pointer_event.pos.x);
+            }
+            true
+        })
+        .on_event(EventListener::PointerMove, move |event| {
+            if let Event::PointerMove(pointer_event) = event {
+                if dragging.get_untracked() {
+                    drag(pointer_event.pos.x);
+                }
+            }
+            true
+        })
+        .on_event(EventListener::PointerUp, move |_| {
+            dragging.set(false);
+            true
+        });
+
+    stack(|| {
+        (
+            label(move || name.to_string()).style(|s| s.width_px(16.0)),
+            bar,
+        )
+    })
+    .style(|s| s.items_center().padding_vert_px(2.0))
+}
+
+/// The color picker of a color literal, with bars for the hue, the saturation
+/// and the value, a hex field, and a swatch of the picked color that rewrites
+/// the literal in it when it's clicked
+fn color_picker(window_tab_data: Rc<WindowTabData>) -> impl View {
+    let picker = window_tab_data.color_picker.clone();
+    let editor = picker.editor.clone();
+    let active = picker.active;
+    let layout_rect = picker.layout_rect;
+    let config = window_tab_data.common.config;
+    let swatch = picker.clone();
+    let confirm = picker.clone();
+
+    container(|| {
+        stack(|| {
+            (
+                color_slider(picker.clone(), "H", 0),
+                color_slider(picker.clone(), "S", 1),
+                color_slider(picker, "V", 2),
+                stack(|| {
+                    (
+                        container(move || {
+                            text_input(editor, move || active.get())
+                                .style(|s| s.width_px(100.0))
+                        })
+                        .style(move |s| {
+                            let config = config.get();
+                            s.font_family(config.editor.font_family.clone())
+                                .font_size(config.editor.font_size() as f32)
+                                .border(1.0)
+                                .border_radius(6.0)
+                                .border_color(
+                                    *config.get_color(LapceColor::LAPCE_BORDER),
+                                )
+                                .background(
+                                    *config.get_color(LapceColor::EDITOR_BACKGROUND),
+                                )
+                        }),
+                        empty()
+                            .on_click(move |_| {
+                                confirm.confirm();
+                                true
+                            })
+                            .style(move |s| {
+                                let [r, g, b] = swatch.rgb();
+                                s.size_px(24.0, 24.0)
+                                    .margin_left_px(16.0)
+                                    .border(1.0)
+                                    .border_radius(6.0)
+                                    .border_color(
+                                        *config
+                                            .get()
+                                            .get_color(LapceColor::LAPCE_BORDER),
+                                    )
+                                    .background(Color::rgb8(r, g, b))
+                                    .cursor(CursorStyle::Pointer)
+                            }),
+                    )
+                })
+                .style(|s| s.items_center().margin_top_px(4.0)),
+            )
+        })
+        .style(|s| s.flex_col())
+    })
+    .on_resize(move |rect| {
+        layout_rect.set(rect);
+    })
+    .on_event(EventListener::PointerMove, |_| true)
+    .on_event(EventListener::PointerDown, |_| true)
+    .style(move |s| {
+        let Some(origin) = window_tab_data.color_picker_origin() else {
+            return s.hide();
+        };
+        s.position(Position::Absolute)
+            .margin_left_px(origin.x as f32)
+            .margin_top_px(origin.y as f32)
+            .background(*config.get().get_color(LapceColor::PANEL_BACKGROUND))
+            .color(*config.get().get_color(LapceColor::PANEL_FOREGROUND))
+            .border_radius(6.0)
+            .padding_px(6.0)
+    })
+}
+
+/// How often the timing overlay catches up with the timings
+const TIMING_OVERLAY_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Show the latest timings until the overlay is hidden.
+fn refresh_timings(
+    shown: RwSignal<bool>,
+    text: RwSignal<String>,
+    timer: RwSignal<TimerToken>,
+) {
+    text.set(perf::overlay_text());
+    let token = exec_after(TIMING_OVERLAY_INTERVAL, move |token| {
+        if timer.try_get_untracked() == Some(token) && shown.get_untracked() {
+            refresh_timings(shown, text, timer);
+        }
+    });
+    timer.set(token);
+}
+
+/// The frames per second and the latest timing of each phase of handling an
+/// edit, in the bottom right corner
+fn timing_overlay(window_tab_data: Rc<WindowTabData>) -> impl View {
+    let shown = window_tab_data.timing_overlay;
+    let config = window_tab_data.common.config;
+    let text = create_rw_signal(String::new());
+    let timer = create_rw_signal(TimerToken::INVALID);
+    create_effect(move |_| {
+        if shown.get() {
+            refresh_timings(shown, text, timer);
+        } else {
+            timer.set(TimerToken::INVALID);
+        }
+    });
+
+    stack(|| {
+        (
+            perf::frame_counter(),
+            label(move || text.get()).style(move |s| {
+                let config = config.get();
+                s.font_family(config.editor.font_family.clone())
+                    .padding_px(6.0)
+                    .border(1.0)
+                    .border_radius(6.0)
+                    .border_color(*config.get_color(LapceColor::LAPCE_BORDER))
+                    .background(*config.get_color(LapceColor::PANEL_BACKGROUND))
+                    .color(*config.get_color(LapceColor::PANEL_FOREGROUND))
+            }),
+        )
+    })
+    .style(move |s| {
+        s.absolute()
+            .size_pct(100.0, 100.0)
+            .flex_col()
+            .justify_end()
+            .items_end()
+            .padding_right_px(10.0)
+            .padding_bottom_px(config.get().ui.status_height() as f32 + 4.0)
+            .apply_if(!shown.get(), |s| s.hide())
+    })
+}
+
+/// The keys of the resize mode, shown at the top while it's on
+fn resize_mode_hint(window_tab_data: Rc<WindowTabData>) -> impl View {
+    let resize_mode = window_tab_data.resize_mode;
+    let config = window_tab_data.common.config;
+    container(|| {
+        label(|| {
+            "Resizing: \u{2190} \u{2192} or h l for the width, \u{2191} \u{2193} or \
+             k j for the height, Shift for larger steps, Enter or Esc when done"
+                .to_string()
+        })
+        .style(move |s| {
+            let config = config.get();
+            s.padding_horiz_px(10.0)
+                .padding_vert_px(6.0)
+                .border(1.0)
+                .border_radius(6.0)
+                .border_color(*config.get_color(LapceColor::LAPCE_BORDER))
+                .background(*config.get_color(LapceColor::PANEL_BACKGROUND))
+                .color(*config.get_color(LapceColor::PANEL_FOREGROUND))
+        })
+    })
+    .style(move |s| {
+        s.absolute()
+            .size_pct(100.0, 100.0)
+            .flex_col()
+            .items_center()
+            .padding_top_px(config.get().ui.header_height() as f32 + 10.0)
+            .apply_if(!resize_mode.get(), |s| s.hide())
+    })
+}
+
 fn window_tab(window_tab_data: Rc<WindowTabData>) -> impl View {
     let source_control = window_tab_data.source_control.clone();
     let window_origin = window_tab_data.common.window_origin;
@@ -2801,13 +3316,19 @@ fn window_tab(window_tab_data: Rc<WindowTabData>) -> impl View {
                 window_origin.set(point);
             })
             .style(|s| s.size_pct(100.0, 100.0).flex_col()),
+            language_server_log(window_tab_data.clone()),
             completion(window_tab_data.clone()),
+            completion_documentation(window_tab_data.clone()),
+            signature(window_tab_data.clone()),
             hover(window_tab_data.clone()),
             code_action(window_tab_data.clone()),
             rename(window_tab_data.clone()),
+            color_picker(window_tab_data.clone()),
             palette(window_tab_data.clone()),
             about::about_popup(window_tab_data.clone()),
             alert::alert_box(window_tab_data.alert_data.clone()),
+            timing_overlay(window_tab_data.clone()),
+            resize_mode_hint(window_tab_data.clone()),
         )
     })
     .on_cleanup(move || {
@@ -3144,12 +3665,8 @@ fn window(window_data: WindowData) -> impl View {
     .window_title(move || {
         let active = active();
         let window_tabs = window_tabs.get();
-        let workspace = window_tabs
-            .get(active)
-            .or_else(|| window_tabs.last())
-            .and_then(|(_, window_tab)| window_tab.workspace.display());
-        match workspace {
-            Some(workspace) => format!("{workspace} - Lapce"),
+        match window_tabs.get(active).or_else(|| window_tabs.last()) {
+            Some((_, window_tab)) => window_tab.title(),
             None => "Lapce".to_string(),
         }
     })
@@ -3289,43 +3806,15 @@ fn window(window_data: WindowData) -> impl View {
     .style(|s| s.size_pct(100.0, 100.0))
 }
 
-#[inline(always)]
-fn logging() -> Handle<Targets> {
-    use tracing_subscriber::{filter, fmt, prelude::*, reload};
-
-    let file_appender = tracing_appender::rolling::Builder::new()
-        .max_log_files(10)
-        .rotation(tracing_appender::rolling::Rotation::DAILY)
-        .filename_prefix("lapce")
-        .filename_suffix("log")
-        .build(Directory::logs_directory().expect("Failed to obtain log directory"))
-        .expect("Couldn't create rolling appender");
-    let (log_file, _guard) = tracing_appender::non_blocking(file_appender);
-    let log_file_filter_targets = filter::Targets::new()
-        .with_target("lapce_app", LevelFilter::DEBUG)
-        .with_target("lapce_proxy", LevelFilter::DEBUG);
-    let (log_file_filter, reload_handle) =
-        reload::Subscriber::new(log_file_filter_targets);
-    let file_layer = tracing_subscriber::fmt::subscriber()
-        .with_ansi(false)
-        .with_writer(log_file)
-        .with_filter(log_file_filter);
-
-    let console_filter_targets = std::env::var("LAPCE_LOG")
-        .unwrap_or_default()
-        .parse::<filter::Targets>()
-        .unwrap_or_default();
-
-    tracing_subscriber::registry()
-        .with(file_layer)
-        .with(fmt::Subscriber::default().with_filter(console_filter_targets))
-        .init();
-
-    reload_handle
-}
-
 pub fn launch() {
-    let reload_handle = logging();
+    // `-` isn't a path to open, but has what's piped to Lapce opened
+    let args = std::env::args().collect::<Vec<_>>();
+    let dash = args.iter().skip(1).any(|arg| arg == STDIN_ARG);
+    let cli = Cli::parse_from(args.iter().filter(|arg| *arg != STDIN_ARG));
+    let open_stdin = dash || (cli.stdin && !std::io::stdin().is_terminal());
+
+    let (reload_handle, _log_guard) =
+        init_logging(cli.log_level.unwrap_or(LevelFilter::DEBUG));
 
     // if PWD is not set, then we are not being launched via a terminal
     #[cfg(any(target_os = "macos", target_os = "linux"))]
@@ -3333,13 +3822,11 @@ pub fn launch() {
         load_shell_env();
     }
 
-    let cli = Cli::parse();
-
     // small hack to unblock terminal if launched from it
     // launch it as a separate process that waits
-    if !cli.wait {
+    if !cli.wait && !cli.detached {
         let mut args = std::env::args().collect::<Vec<_>>();
-        args.push("--wait".to_string());
+        args.push("--detached".to_string());
         let mut cmd = std::process::Command::new(&args[0]);
         #[cfg(target_os = "windows")]
         cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
@@ -3354,20 +3841,29 @@ pub fn launch() {
         return;
     }
 
-    if !cli.new {
+    // What's read from stdin can't be passed on to a running instance, and
+    // what waits for the window to close needs its own
+    if !cli.new && !open_stdin && !cli.wait {
         if let Ok(socket) = get_socket() {
-            if let Err(e) = try_open_in_existing_process(socket, &cli.paths) {
-                error!("failed to open path(s): {e}");
-            };
-            return;
+            match try_open_in_existing_process(socket, &cli.paths) {
+                Ok(()) => return,
+                // The socket can be left over from an instance that crashed,
+                // or the instance doesn't respond, so start a new one instead
+                Err(e) => error!("failed to open path(s) in running instance: {e}"),
+            }
         }
     }
 
     #[cfg(feature = "updater")]
     crate::update::cleanup();
 
+    crash::install_panic_hook();
+    let crash_report = crash::take_crash_report();
+
     let _ = lapce_proxy::register_lapce_path();
-    let db = Arc::new(LapceDb::new().unwrap());
+    // Before anything reads the settings or watches their files
+    let config_directory = first_run::setup_config_directory();
+    let db = Arc::new(LapceDb::new());
     let scope = Scope::new();
     provide_context(db.clone());
 
@@ -3396,14 +3892,36 @@ pub fn launch() {
         active_window: scope.create_rw_signal(WindowId::from(0)),
         window_scale,
         app_terminated: scope.create_rw_signal(false),
+        wait: cli.wait,
         watcher: Arc::new(watcher),
         latest_release,
         app_command,
         tracing_handle: reload_handle,
+        scope,
     };
 
     let app = app_data.create_windows(db.clone(), cli.paths);
 
+    // The log level follows the setting, unless it was given to Lapce
+    if cli.log_level.is_none() {
+        let app_data = app_data.clone();
+        create_effect(move |last: Option<Option<String>>| {
+            let level = app_data.windows.with(|windows| {
+                windows.values().next().map(|window| {
+                    window.config.with(|config| config.core.log_level.clone())
+                })
+            });
+            if level != last.flatten() {
+                match level.as_deref().map(parse_log_level) {
+                    Some(Ok(level)) => app_data.set_log_level(level),
+                    Some(Err(e)) => error!("{e}"),
+                    None => {}
+                }
+            }
+            level
+        });
+    }
+
     {
         let app_data = app_data.clone();
         let notification = create_signal_from_channel(rx);
@@ -3414,6 +3932,78 @@ pub fn launch() {
         });
     }
 
+    {
+        // Reload so that an "auto" color theme follows the OS appearance. The
+        // OS is only polled while the color theme is "auto".
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        let notification = create_signal_from_channel(rx);
+        let follow_os = Arc::new(AtomicBool::new(false));
+        {
+            let app_data = app_data.clone();
+            create_effect(move |_| {
+                if notification.get().is_some() {
+                    app_data.reload_config();
+                }
+            });
+        }
+        let poller = {
+            let follow_os = follow_os.clone();
+            std::thread::spawn(move || {
+                let mut prefers_dark = None;
+                loop {
+                    if !follow_os.load(std::sync::atomic::Ordering::Relaxed) {
+                        prefers_dark = None;
+                        std::thread::park();
+                        continue;
+                    }
+                    let new = crate::config::os_theme::os_prefers_dark();
+                    if prefers_dark.is_some()
+                        && prefers_dark != Some(new)
+                        && tx.send(new).is_err()
+                    {
+                        return;
+                    }
+                    prefers_dark = Some(new);
+                    std::thread::sleep(std::time::Duration::from_secs(3));
+                }
+            })
+        };
+        let app_data = app_data.clone();
+        create_effect(move |_| {
+            let auto = app_data.windows.with(|windows| {
+                windows.values().next().map(|window| {
+                    window.config.with(|config| {
+                        config.core.color_theme.eq_ignore_ascii_case("auto")
+                    })
+                })
+            });
+            let auto = auto.unwrap_or(false);
+            if follow_os.swap(auto, std::sync::atomic::Ordering::Relaxed) != auto {
+                poller.thread().unpark();
+            }
+        });
+    }
+
+    {
+        // Save the session now and then, so that little is lost if Lapce
+        // doesn't get to save it on exit
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        let notification = create_signal_from_channel(rx);
+        let app_data = app_data.clone();
+        let db = db.clone();
+        create_effect(move |_| {
+            if notification.get().is_some() {
+                let _ = db.save_app(&app_data);
+            }
+        });
+        std::thread::spawn(move || loop {
+            std::thread::sleep(std::time::Duration::from_secs(60));
+            if tx.send(()).is_err() {
+                return;
+            }
+        });
+    }
+
     #[cfg(feature = "updater")]
     {
         let (tx, rx) = crossbeam_channel::bounded(1);
@@ -3432,7 +4022,8 @@ pub fn launch() {
         });
     }
 
-    {
+    // The running instance is the one the paths opened later go to
+    if !cli.wait {
         let (tx, rx) = crossbeam_channel::bounded(1);
         let notification = create_signal_from_channel(rx);
         let app_data = app_data.clone();
@@ -3448,6 +4039,58 @@ pub fn launch() {
         });
     }
 
+    {
+        // Shown once, in the first window there is
+        let app_data = app_data.clone();
+        create_effect(move |shown: Option<bool>| {
+            if shown == Some(true) {
+                return true;
+            }
+            app_data.windows.with(|_| ());
+            let Some(window_tab) = app_data.active_window_tab() else {
+                return false;
+            };
+            window_tab.show_config_directory(&config_directory);
+            if let Some(report) = &crash_report {
+                window_tab.show_crash_report(report);
+            }
+            true
+        });
+    }
+
+    if open_stdin {
+        // Show what's read from stdin as it comes, so that long output can
+        // be looked at before it ends
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let notification = create_signal_from_channel(rx);
+        let text = StdinText::default();
+        let app_data = app_data.clone();
+        {
+            let text = text.clone();
+            create_effect(move |doc: Option<Option<RwSignal<Document>>>| {
+                // Until there's a window to show it in, it's kept for later
+                app_data.windows.with(|_| ());
+                notification.with(|_| ());
+                let mut doc = doc.flatten();
+                if doc.is_none() {
+                    doc = app_data.active_window_tab().and_then(|window_tab| {
+                        window_tab.main_split.new_read_only_file()
+                    });
+                }
+                if let Some(doc) = doc {
+                    let text = text.take();
+                    if !text.is_empty() {
+                        doc.update(|doc| doc.append(&text));
+                    }
+                }
+                doc
+            });
+        }
+        std::thread::spawn(move || {
+            read_stdin(text, tx);
+        });
+    }
+
     {
         let app_data = app_data.clone();
         app_data.app_command.listen(move |command| {
@@ -3455,10 +4098,14 @@ pub fn launch() {
         });
     }
 
+    // What's done from now on is done while the user types
+    job::mark_ui_thread();
+
     app.on_event(move |event| match event {
         floem::AppEvent::WillTerminate => {
             app_data.app_terminated.set(true);
-            let _ = db.insert_app(app_data.clone());
+            db.save_app_on_exit(&app_data);
+            crash::clear_crash();
         }
         floem::AppEvent::Reopen {
             has_visible_windows,
@@ -3544,6 +4191,11 @@ pub fn try_open_in_existing_process(
 fn listen_local_socket(tx: Sender<CoreNotification>) -> Result<()> {
     let local_socket = Directory::local_socket()
         .ok_or_else(|| anyhow!("can't get local socket folder"))?;
+    // An instance that didn't answer in time still owns a socket that accepts
+    // connections, which is only removed when it's left over from a crash
+    if get_socket().is_ok() {
+        return Err(anyhow!("local socket is used by another instance"));
+    }
     let _ = std::fs::remove_file(&local_socket);
     let socket =
         interprocess::local_socket::LocalSocketListener::bind(local_socket)?;
@@ -7,7 +7,7 @@
 };
 use lapce_core::{command::FocusCommand, mode::Mode, movement::Movement};
 use lapce_rpc::plugin::PluginId;
-use lsp_types::CodeActionOrCommand;
+use lsp_types::{CodeActionKind, CodeActionOrCommand};
 
 use crate::{
     command::{CommandExecuted, CommandKind, InternalCommand},
@@ -38,6 +38,35 @@ pub fn title(&self) -> &str {
     }
 }
 
+/// Whether `kind` is `parent` or one of its sub-kinds, like
+/// `source.organizeImports.rust` is of `source.organizeImports`.
+pub fn is_kind_of(kind: &CodeActionKind, parent: &CodeActionKind) -> bool {
+    let (kind, parent) = (kind.as_str(), parent.as_str());
+    kind.strip_prefix(parent)
+        .map(|rest| rest.is_empty() || rest.starts_with('.'))
+        .unwrap_or(false)
+}
+
+/// Where the action goes in the list: the quick fixes first, the preferred
+/// one of them leading, then the other actions and last the bare commands.
+fn action_rank(action: &CodeActionOrCommand) -> usize {
+    match action {
+        CodeActionOrCommand::CodeAction(action) => {
+            let quick_fix = action
+                .kind
+                .as_ref()
+                .map(|kind| is_kind_of(kind, &CodeActionKind::QUICKFIX))
+                .unwrap_or(false);
+            match (quick_fix, action.is_preferred == Some(true)) {
+                (true, true) => 0,
+                (true, false) => 1,
+                (false, _) => 2,
+            }
+        }
+        CodeActionOrCommand::Command(_) => 3,
+    }
+}
+
 #[derive(Clone)]
 pub struct CodeActionData {
     pub status: RwSignal<CodeActionStatus>,
@@ -174,11 +203,12 @@ pub fn show(
         self.offset = offset;
         self.mouse_click = mouse_click;
         self.request_id += 1;
-        self.items = code_actions
-            .1
-            .iter()
+        let mut actions = code_actions.1.clone();
+        actions.sort_by_key(action_rank);
+        self.items = actions
+            .into_iter()
             .map(|code_action| ScoredCodeActionItem {
-                item: code_action.clone(),
+                item: code_action,
                 plugin_id: code_actions.0,
                 score: 0,
                 indices: Vec::new(),
@@ -230,3 +260,52 @@ fn run_focus_command(&self, cmd: &FocusCommand) -> CommandExecuted {
         CommandExecuted::Yes
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{CodeAction, CodeActionKind, CodeActionOrCommand, Command};
+
+    use super::{action_rank, is_kind_of};
+
+    fn action(kind: CodeActionKind, is_preferred: bool) -> CodeActionOrCommand {
+        CodeActionOrCommand::CodeAction(CodeAction {
+            title: kind.as_str().to_string(),
+            kind: Some(kind),
+            is_preferred: Some(is_preferred),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_is_kind_of() {
+        let organize = CodeActionKind::SOURCE_ORGANIZE_IMPORTS;
+        assert!(is_kind_of(&organize, &CodeActionKind::SOURCE));
+        assert!(is_kind_of(&organize, &organize));
+        assert!(is_kind_of(
+            &CodeActionKind::from("source.organizeImports.rust"),
+            &organize
+        ));
+        assert!(!is_kind_of(&CodeActionKind::SOURCE, &organize));
+        assert!(!is_kind_of(
+            &CodeActionKind::from("sourcery"),
+            &CodeActionKind::SOURCE
+        ));
+    }
+
+    #[test]
+    fn test_quick_fixes_first() {
+        let mut actions = vec![
+            CodeActionOrCommand::Command(Command {
+                title: "command".to_string(),
+                command: "command".to_string(),
+                arguments: None,
+            }),
+            action(CodeActionKind::REFACTOR, true),
+            action(CodeActionKind::QUICKFIX, false),
+            action(CodeActionKind::QUICKFIX, true),
+        ];
+        actions.sort_by_key(action_rank);
+        let ranks = actions.iter().map(action_rank).collect::<Vec<_>>();
+        assert_eq!(ranks, vec![0, 1, 2, 3]);
+    }
+}
@@ -0,0 +1,183 @@
+use floem::{
+    keyboard::ModifiersState,
+    peniko::kurbo::Rect,
+    reactive::{RwSignal, Scope},
+};
+use lapce_core::{
+    color_literal::{color_literals, hsv_to_rgb, rgb_to_hsv, ColorLiteral},
+    command::FocusCommand,
+    mode::Mode,
+    selection::Selection,
+};
+use lapce_xi_rope::Rope;
+
+use crate::{
+    command::{CommandExecuted, CommandKind, LapceCommand},
+    editor::EditorData,
+    id::EditorId,
+    keypress::{condition::Condition, KeyPressFocus},
+    window_tab::{CommonData, Focus},
+};
+
+/// The popup that changes the color of a color literal, from a hue, a
+/// saturation and a value or from a hex color typed in it
+#[derive(Clone)]
+pub struct ColorPickerData {
+    pub active: RwSignal<bool>,
+    /// The hex field
+    pub editor: EditorData,
+    /// The editor the literal is in, and the literal at its offsets in the
+    /// document
+    pub target: RwSignal<Option<(EditorData, ColorLiteral)>>,
+    /// The hue from 0 to 360, and the saturation and the value from 0 to 1
+    pub hsv: RwSignal<(f64, f64, f64)>,
+    pub layout_rect: RwSignal<Rect>,
+    pub common: CommonData,
+}
+
+impl KeyPressFocus for ColorPickerData {
+    fn get_mode(&self) -> Mode {
+        Mode::Insert
+    }
+
+    fn check_condition(&self, condition: Condition) -> bool {
+        matches!(
+            condition,
+            Condition::ColorPickerFocus | Condition::ModalFocus
+        )
+    }
+
+    fn run_command(
+        &self,
+        command: &LapceCommand,
+        count: Option<usize>,
+        mods: ModifiersState,
+    ) -> CommandExecuted {
+        match &command.kind {
+            CommandKind::Workbench(_) => {}
+            CommandKind::Focus(cmd) => {
+                self.run_focus_command(cmd);
+            }
+            CommandKind::Edit(_)
+            | CommandKind::Move(_)
+            | CommandKind::MultiSelection(_) => {
+                self.editor.run_command(command, count, mods);
+                self.hex_changed();
+            }
+            CommandKind::MotionMode(_) => {}
+        }
+        CommandExecuted::Yes
+    }
+
+    fn receive_char(&self, c: &str) {
+        self.editor.receive_char(c);
+        self.hex_changed();
+    }
+}
+
+impl ColorPickerData {
+    pub fn new(cx: Scope, common: CommonData) -> Self {
+        let active = cx.create_rw_signal(false);
+        let target = cx.create_rw_signal(None);
+        let hsv = cx.create_rw_signal((0.0, 0.0, 0.0));
+        let layout_rect = cx.create_rw_signal(Rect::ZERO);
+        let editor = EditorData::new_local(cx, EditorId::next(), common.clone());
+        Self {
+            active,
+            editor,
+            target,
+            hsv,
+            layout_rect,
+            common,
+        }
+    }
+
+    pub fn start(&self, editor: EditorData, literal: ColorLiteral) {
+        let [r, g, b, _] = literal.rgba;
+        self.hsv.set(rgb_to_hsv([r, g, b]));
+        self.target.set(Some((editor, literal)));
+        self.write_hex();
+        self.active.set(true);
+        self.common.focus.set(Focus::ColorPicker);
+    }
+
+    /// The color that's picked.
+    pub fn rgb(&self) -> [u8; 3] {
+        let (hue, saturation, value) = self.hsv.get();
+        hsv_to_rgb(hue, saturation, value)
+    }
+
+    /// Change one of the hue, the saturation or the value, with the hex field
+    /// following it.
+    pub fn set_hsv(&self, f: impl FnOnce(&mut (f64, f64, f64))) {
+        self.hsv.update(f);
+        self.write_hex();
+    }
+
+    /// Put the picked color in the hex field, selected to be typed over.
+    fn write_hex(&self) {
+        let (hue, saturation, value) = self.hsv.get_untracked();
+        let [r, g, b] = hsv_to_rgb(hue, saturation, value);
+        let hex = format!("#{r:02x}{g:02x}{b:02x}");
+        self.editor
+            .view
+            .doc
+            .update(|doc| doc.reload(Rope::from(&hex), true));
+        self.editor
+            .cursor
+            .update(|cursor| cursor.set_insert(Selection::region(0, hex.len())));
+    }
+
+    /// Pick the color of the hex field when it's a whole color.
+    fn hex_changed(&self) {
+        let hex = self
+            .editor
+            .view
+            .doc
+            .with_untracked(|doc| doc.buffer().to_string());
+        let hex = hex.trim();
+        let hex = if hex.starts_with('#') {
+            hex.to_string()
+        } else {
+            format!("#{hex}")
+        };
+        if let Some(literal) = color_literals(&hex)
+            .into_iter()
+            .find(|literal| literal.range() == (0..hex.len()))
+        {
+            let [r, g, b, _] = literal.rgba;
+            self.hsv.set(rgb_to_hsv([r, g, b]));
+        }
+    }
+
+    fn run_focus_command(&self, cmd: &FocusCommand) -> CommandExecuted {
+        match cmd {
+            FocusCommand::ModalClose => {
+                self.cancel();
+            }
+            FocusCommand::ConfirmColorPicker => {
+                self.confirm();
+            }
+            _ => return CommandExecuted::No,
+        }
+        CommandExecuted::Yes
+    }
+
+    pub fn cancel(&self) {
+        self.active.set(false);
+        self.target.set(None);
+        if let Focus::ColorPicker = self.common.focus.get_untracked() {
+            self.common.focus.set(Focus::Workbench);
+        }
+    }
+
+    /// Rewrite the literal in the picked color, in the format it's written in.
+    pub fn confirm(&self) {
+        let (hue, saturation, value) = self.hsv.get_untracked();
+        let rgb = hsv_to_rgb(hue, saturation, value);
+        if let Some((editor, literal)) = self.target.get_untracked() {
+            editor.rewrite_color_literal(&literal, rgb);
+        }
+        self.cancel();
+    }
+}
@@ -2,14 +2,22 @@
 
 use floem::{keyboard::ModifiersState, peniko::kurbo::Vec2, reactive::RwSignal};
 use indexmap::IndexMap;
-use lapce_core::command::{
-    EditCommand, FocusCommand, MotionModeCommand, MoveCommand, MultiSelectionCommand,
+use lapce_core::{
+    color_literal::ColorLiteral,
+    command::{
+        EditCommand, FocusCommand, MotionModeCommand, MoveCommand,
+        MultiSelectionCommand,
+    },
+    language::LapceLanguage,
 };
 use lapce_rpc::{
     dap_types::RunDebugConfig, plugin::PluginId, proxy::ProxyStatus,
     terminal::TermId,
 };
-use lsp_types::{CodeActionOrCommand, Position, WorkspaceEdit};
+use lapce_xi_rope::Rope;
+use lsp_types::{
+    CodeActionOrCommand, Position, ShowMessageParams, TextEdit, WorkspaceEdit,
+};
 use serde_json::Value;
 use strum::{EnumMessage, IntoEnumIterator};
 use strum_macros::{Display, EnumIter, EnumMessage, EnumString, IntoStaticStr};
@@ -20,8 +28,10 @@
     doc::Document,
     editor::location::EditorLocation,
     editor_tab::EditorTabChild,
-    id::EditorTabId,
+    id::{EditorId, EditorTabId},
     main_split::{SplitDirection, SplitMoveDirection},
+    palette::kind::PaletteKind,
+    task::TaskConfig,
     workspace::LapceWorkspace,
 };
 
@@ -63,6 +73,19 @@ pub fn str(&self) -> &'static str {
             CommandKind::MultiSelection(cmd) => cmd.into(),
         }
     }
+
+    /// Whether running the command again from the command history does what
+    /// it did, which isn't so for the ones that undo, or that run others.
+    pub fn is_repeatable(&self) -> bool {
+        !matches!(
+            self,
+            CommandKind::Edit(EditCommand::Undo | EditCommand::Redo)
+                | CommandKind::Workbench(
+                    LapceWorkbenchCommand::RepeatLastCommand
+                        | LapceWorkbenchCommand::PaletteCommand
+                )
+        )
+    }
 }
 
 #[derive(PartialEq, Eq)]
@@ -161,6 +184,42 @@ pub enum LapceWorkbenchCommand {
     #[strum(message = "Reveal Active File in File Explorer")]
     RevealActiveFileInFileExplorer,
 
+    #[strum(serialize = "reveal_active_file_in_panel")]
+    #[strum(message = "Reveal Active File in Explorer Panel")]
+    RevealActiveFileInPanel,
+
+    #[strum(serialize = "toggle_file_explorer_follow")]
+    #[strum(message = "Toggle File Explorer Following the Active File")]
+    ToggleFileExplorerFollow,
+
+    #[strum(serialize = "open_changes")]
+    #[strum(message = "Open Changes")]
+    OpenChanges,
+
+    #[strum(serialize = "compare_with_saved")]
+    #[strum(message = "Compare with Saved")]
+    CompareWithSaved,
+
+    #[strum(serialize = "revert_file")]
+    #[strum(message = "Revert File")]
+    RevertFile,
+
+    #[strum(serialize = "toggle_diff_ignore_whitespace")]
+    #[strum(message = "Toggle Diff Ignore Whitespace")]
+    ToggleDiffIgnoreWhitespace,
+
+    #[strum(serialize = "alternate_file")]
+    #[strum(message = "Alternate File")]
+    AlternateFile,
+
+    #[strum(serialize = "rename_file")]
+    #[strum(message = "Rename File")]
+    RenameFile,
+
+    #[strum(serialize = "resize_mode")]
+    #[strum(message = "Resize Mode")]
+    ResizeMode,
+
     #[strum(serialize = "change_color_theme")]
     #[strum(message = "Change Color Theme")]
     ChangeColorTheme,
@@ -177,6 +236,14 @@ pub enum LapceWorkbenchCommand {
     #[strum(message = "Open Settings File")]
     OpenSettingsFile,
 
+    #[strum(serialize = "open_user_settings_file")]
+    #[strum(message = "Open User Settings File")]
+    OpenUserSettingsFile,
+
+    #[strum(serialize = "open_workspace_settings_file")]
+    #[strum(message = "Open Workspace Settings File")]
+    OpenWorkspaceSettingsFile,
+
     #[strum(serialize = "open_settings_directory")]
     #[strum(message = "Open Settings Directory")]
     OpenSettingsDirectory,
@@ -197,6 +264,18 @@ pub enum LapceWorkbenchCommand {
     #[strum(message = "Open Logs Directory")]
     OpenLogsDirectory,
 
+    #[strum(serialize = "log_open_editors")]
+    #[strum(message = "Log Open Editors")]
+    LogOpenEditors,
+
+    #[strum(serialize = "toggle_timing_overlay")]
+    #[strum(message = "Toggle Timing Overlay")]
+    ToggleTimingOverlay,
+
+    #[strum(serialize = "save_timings")]
+    #[strum(message = "Save Timings to a File")]
+    SaveTimings,
+
     #[strum(serialize = "open_proxy_directory")]
     #[strum(message = "Open Proxy Directory")]
     OpenProxyDirectory,
@@ -221,6 +300,18 @@ pub enum LapceWorkbenchCommand {
     #[strum(message = "Reset Zoom")]
     ZoomReset,
 
+    #[strum(serialize = "ui_zoom_in")]
+    #[strum(message = "Zoom In the UI")]
+    UiZoomIn,
+
+    #[strum(serialize = "ui_zoom_out")]
+    #[strum(message = "Zoom Out the UI")]
+    UiZoomOut,
+
+    #[strum(serialize = "ui_zoom_reset")]
+    #[strum(message = "Reset the UI Zoom")]
+    UiZoomReset,
+
     #[strum(serialize = "close_window_tab")]
     #[strum(message = "Close Current Window Tab")]
     CloseWindowTab,
@@ -257,6 +348,10 @@ pub enum LapceWorkbenchCommand {
     #[strum(message = "Reload Window")]
     ReloadWindow,
 
+    #[strum(serialize = "manage_workspace_trust")]
+    #[strum(message = "Manage Workspace Trust")]
+    ManageWorkspaceTrust,
+
     #[strum(message = "New Window")]
     #[strum(serialize = "new_window")]
     NewWindow,
@@ -269,6 +364,38 @@ pub enum LapceWorkbenchCommand {
     #[strum(serialize = "new_file")]
     NewFile,
 
+    #[strum(message = "Toggle Read-Only")]
+    #[strum(serialize = "toggle_read_only")]
+    ToggleReadOnly,
+
+    #[strum(message = "Toggle ANSI Colors")]
+    #[strum(serialize = "toggle_ansi_colors")]
+    ToggleAnsiColors,
+
+    #[strum(message = "Toggle Spell Check")]
+    #[strum(serialize = "toggle_spell_check")]
+    ToggleSpellCheck,
+
+    #[strum(message = "Toggle Whitespace")]
+    #[strum(serialize = "toggle_whitespace")]
+    ToggleWhitespace,
+
+    #[strum(message = "Toggle Line Numbers")]
+    #[strum(serialize = "toggle_line_numbers")]
+    ToggleLineNumbers,
+
+    #[strum(message = "Link Scrolling with the Editor Next to It")]
+    #[strum(serialize = "link_scroll")]
+    LinkScroll,
+
+    #[strum(message = "Unlink Scrolling")]
+    #[strum(serialize = "unlink_scroll")]
+    UnlinkScroll,
+
+    #[strum(message = "Reopen Closed Editor")]
+    #[strum(serialize = "reopen_closed_editor")]
+    ReopenClosedEditor,
+
     #[strum(serialize = "connect_ssh_host")]
     #[strum(message = "Connect to SSH Host")]
     ConnectSshHost,
@@ -301,6 +428,14 @@ pub enum LapceWorkbenchCommand {
     #[strum(serialize = "palette.command")]
     PaletteCommand,
 
+    #[strum(message = "Keyboard Shortcuts Reference")]
+    #[strum(serialize = "palette.keymap")]
+    PaletteKeymap,
+
+    #[strum(message = "Repeat Last Command")]
+    #[strum(serialize = "repeat_last_command")]
+    RepeatLastCommand,
+
     #[strum(message = "Open Recent Workspace")]
     #[strum(serialize = "palette.workspace")]
     PaletteWorkspace,
@@ -325,6 +460,18 @@ pub enum LapceWorkbenchCommand {
     #[strum(serialize = "palette.run_and_debug_stop")]
     RunAndDebugStop,
 
+    #[strum(message = "Run Task")]
+    #[strum(serialize = "palette.task")]
+    PaletteTask,
+
+    #[strum(message = "Rerun Last Task")]
+    #[strum(serialize = "rerun_last_task")]
+    RerunLastTask,
+
+    #[strum(message = "Kill Running Tasks")]
+    #[strum(serialize = "kill_tasks")]
+    KillTasks,
+
     #[strum(serialize = "source_control.checkout_reference")]
     CheckoutReference,
 
@@ -381,6 +528,10 @@ pub enum LapceWorkbenchCommand {
     #[strum(serialize = "toggle_search_focus")]
     ToggleSearchFocus,
 
+    #[strum(message = "Toggle Outline Focus")]
+    #[strum(serialize = "toggle_outline_focus")]
+    ToggleOutlineFocus,
+
     // Visual toggle commands
     #[strum(serialize = "toggle_terminal_visual")]
     ToggleTerminalVisual,
@@ -403,9 +554,33 @@ pub enum LapceWorkbenchCommand {
     #[strum(serialize = "toggle_search_visual")]
     ToggleSearchVisual,
 
+    #[strum(serialize = "toggle_outline_visual")]
+    ToggleOutlineVisual,
+
+    #[strum(message = "Toggle Edit Preview")]
+    #[strum(serialize = "toggle_edit_preview_visual")]
+    ToggleEditPreviewVisual,
+
+    #[strum(message = "Toggle Tasks")]
+    #[strum(serialize = "toggle_tasks_visual")]
+    ToggleTasksVisual,
+
     #[strum(serialize = "focus_editor")]
+    #[strum(message = "Focus Editor")]
     FocusEditor,
 
+    #[strum(serialize = "focus_left_panel")]
+    #[strum(message = "Focus Left Panel")]
+    FocusLeftPanel,
+
+    #[strum(serialize = "focus_bottom_panel")]
+    #[strum(message = "Focus Bottom Panel")]
+    FocusBottomPanel,
+
+    #[strum(serialize = "focus_right_panel")]
+    #[strum(message = "Focus Right Panel")]
+    FocusRightPanel,
+
     #[strum(serialize = "focus_terminal")]
     FocusTerminal,
 
@@ -455,6 +630,10 @@ pub enum LapceWorkbenchCommand {
     #[strum(message = "Toggle Inlay Hints")]
     ToggleInlayHints,
 
+    #[strum(serialize = "toggle_inline_blame")]
+    #[strum(message = "Toggle Inline Git Blame")]
+    ToggleInlineBlame,
+
     #[strum(serialize = "restart_to_update")]
     RestartToUpdate,
 
@@ -507,6 +686,10 @@ pub enum InternalCommand {
     OpenFile {
         path: PathBuf,
     },
+    /// Create the file, with the folders it's in, and open it
+    CreateFile {
+        path: PathBuf,
+    },
     OpenFileInNewTab {
         path: PathBuf,
     },
@@ -523,6 +706,11 @@ pub enum InternalCommand {
     PaletteReferences {
         references: Vec<EditorLocation>,
     },
+    /// Run the palette with `input` already typed in after its prefix
+    PaletteWithInput {
+        kind: PaletteKind,
+        input: String,
+    },
     SaveJumpLocation {
         path: PathBuf,
         offset: usize,
@@ -567,19 +755,35 @@ pub enum InternalCommand {
         plugin_id: PluginId,
         action: CodeActionOrCommand,
     },
-    ApplyWorkspaceEdit {
+    ApplyRenameEdit {
         edit: WorkspaceEdit,
     },
+    /// A file or folder was renamed from Lapce, with the edits the language
+    /// servers made for it
+    PathRenamed {
+        from: PathBuf,
+        to: PathBuf,
+        edits: Vec<WorkspaceEdit>,
+    },
     RunAndDebug {
         mode: RunDebugMode,
         config: RunDebugConfig,
     },
+    RunTask {
+        task: TaskConfig,
+    },
     StartRename {
         path: PathBuf,
         placeholder: String,
         start: usize,
         position: Position,
     },
+    /// Show the color picker for a color literal of an editor, at its offsets
+    /// in the document
+    ShowColorPicker {
+        editor_id: EditorId,
+        literal: ColorLiteral,
+    },
     Search {
         pattern: Option<String>,
     },
@@ -628,9 +832,31 @@ pub enum InternalCommand {
         buttons: Vec<AlertButton>,
     },
     HideAlert,
+    ShowMessage {
+        title: String,
+        message: ShowMessageParams,
+    },
+    /// Show the edits of the files in the edit preview, to pick the ones to
+    /// apply, along with the `failures` of the files that couldn't be edited
+    PreviewWorkspaceEdit {
+        title: &'static str,
+        files: Vec<(PathBuf, Option<i32>, Vec<TextEdit>)>,
+        failures: Vec<String>,
+    },
     SaveScratchDoc {
         doc: RwSignal<Document>,
     },
+    /// Open `text` in a new untitled file beside the editor tab
+    /// `editor_tab_id`
+    OpenScratchBeside {
+        editor_tab_id: EditorTabId,
+        text: Rope,
+        language: LapceLanguage,
+    },
+    OpenFileBeside {
+        editor_tab_id: EditorTabId,
+        path: PathBuf,
+    },
     UpdateProxyStatus {
         status: ProxyStatus,
     },
@@ -1,20 +1,28 @@
 use std::{borrow::Cow, path::PathBuf, str::FromStr, sync::Arc};
 
 use floem::{
+    ext_event::create_ext_action,
     peniko::kurbo::Rect,
     reactive::{ReadSignal, RwSignal, Scope},
 };
 use lapce_core::{buffer::rope_text::RopeText, movement::Movement};
-use lapce_rpc::{plugin::PluginId, proxy::ProxyRpcHandler};
+use lapce_rpc::{
+    plugin::PluginId,
+    proxy::{ProxyResponse, ProxyRpcHandler},
+};
 use lsp_types::{
-    CompletionItem, CompletionResponse, CompletionTextEdit, InsertTextFormat,
-    Position,
+    CompletionItem, CompletionResponse, CompletionTextEdit, Documentation,
+    InsertTextFormat, MarkupContent, MarkupKind, Position,
 };
 use nucleo::Utf32Str;
 
 use crate::{
-    config::LapceConfig, doc::Document, editor::view_data::EditorViewData,
-    id::EditorId, snippet::Snippet,
+    config::LapceConfig,
+    doc::Document,
+    editor::view_data::EditorViewData,
+    id::EditorId,
+    markdown::{from_plaintext, parse_markdown, MarkdownContent},
+    snippet::Snippet,
 };
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -31,6 +39,9 @@ pub struct ScoredCompletionItem {
     pub score: u32,
     pub label_score: u32,
     pub indices: Vec<usize>,
+    /// Whether the item was resolved, filling in what the server left out of
+    /// the list, like the documentation
+    pub resolved: bool,
 }
 
 #[derive(Clone)]
@@ -114,6 +125,7 @@ pub fn receive(
                 score: 0,
                 label_score: 0,
                 indices: Vec::new(),
+                resolved: false,
             })
             .collect();
         self.input_items.insert(input.to_string(), items);
@@ -128,10 +140,17 @@ pub fn request(
         path: PathBuf,
         input: String,
         position: Position,
+        trigger_character: Option<String>,
     ) {
         self.latest_editor_id = Some(editor_id);
         self.input_items.insert(input.clone(), im::Vector::new());
-        proxy_rpc.completion(self.request_id, path, input, position);
+        proxy_rpc.completion(
+            self.request_id,
+            path,
+            input,
+            position,
+            trigger_character,
+        );
     }
 
     /// Close the completion, clearing all the data.
@@ -295,6 +314,21 @@ pub fn current_item(&self) -> Option<&ScoredCompletionItem> {
         self.filtered_items.get(self.active.get_untracked())
     }
 
+    /// Replace `item` with what resolving it gave, wherever it's listed.
+    pub fn set_resolved(&mut self, item: &CompletionItem, resolved: CompletionItem) {
+        let items = self
+            .input_items
+            .iter_mut()
+            .flat_map(|(_, items)| items.iter_mut())
+            .chain(self.filtered_items.iter_mut());
+        for scored in items {
+            if &scored.item == item {
+                scored.item = resolved.clone();
+                scored.resolved = true;
+            }
+        }
+    }
+
     /// Update the completion lens of the document with the active completion item.  
     pub fn update_document_completion(
         &self,
@@ -342,6 +376,79 @@ pub fn update_document_completion(
     }
 }
 
+/// Resolve the active item whenever another one becomes active, for the
+/// documentation that servers leave out of the list.
+pub fn resolve_active_item(
+    cx: Scope,
+    completion: RwSignal<CompletionData>,
+    proxy: ProxyRpcHandler,
+) {
+    cx.create_effect(move |_| {
+        let active = completion.with(|c| {
+            c.active.track();
+            (c.status != CompletionStatus::Inactive)
+                .then(|| c.current_item().cloned())
+                .flatten()
+                .map(|item| (c.request_id, item))
+        });
+        let Some((request_id, item)) = active else {
+            return;
+        };
+        if item.resolved || item.item.documentation.is_some() {
+            return;
+        }
+
+        let original = item.item.clone();
+        let send = create_ext_action(cx, move |resolved: Option<CompletionItem>| {
+            completion.update(|c| {
+                if c.request_id == request_id {
+                    // Servers that can't resolve are asked only once
+                    let resolved = resolved.unwrap_or_else(|| original.clone());
+                    c.set_resolved(&original, resolved);
+                }
+            });
+        });
+        proxy.completion_resolve(item.plugin_id, item.item, move |result| {
+            let resolved = match result {
+                Ok(ProxyResponse::CompletionResolveResponse { item }) => Some(*item),
+                _ => None,
+            };
+            send(resolved);
+        });
+    });
+}
+
+/// The documentation of `item` shown next to the completion list, with its
+/// detail, like the signature, first.
+pub fn documentation_content(
+    item: &CompletionItem,
+    config: &LapceConfig,
+) -> Vec<MarkdownContent> {
+    let mut content = Vec::new();
+    if let Some(detail) = item.detail.as_ref().filter(|d| !d.trim().is_empty()) {
+        content.extend(from_plaintext(detail, 1.5, config));
+    }
+    let documentation = match &item.documentation {
+        Some(Documentation::String(text)) => from_plaintext(text, 1.5, config),
+        Some(Documentation::MarkupContent(MarkupContent {
+            kind: MarkupKind::PlainText,
+            value,
+        })) => from_plaintext(value, 1.5, config),
+        Some(Documentation::MarkupContent(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value,
+        })) => parse_markdown(value, 1.5, config),
+        None => Vec::new(),
+    };
+    if !documentation.is_empty() {
+        if !content.is_empty() {
+            content.push(MarkdownContent::Separator);
+        }
+        content.extend(documentation);
+    }
+    content
+}
+
 /// Clear the current completion lens. Only `update`s if there is a completion lens.
 pub fn clear_completion_lens(doc: RwSignal<Document>) {
     let has_completion = doc.with_untracked(|doc| doc.completion_lens().is_some());
@@ -1,53 +1,86 @@
 use std::{
     collections::HashMap,
+    io::Write,
     path::{Path, PathBuf},
     sync::Arc,
 };
 
+use crossbeam_channel::Sender;
 use floem::peniko::Color;
 use itertools::Itertools;
-use lapce_core::directory::Directory;
+use lapce_core::{
+    directory::Directory, language::LapceLanguage,
+    language_config::LanguageConfigOverrides,
+};
 use lapce_proxy::plugin::wasi::find_all_volts;
-use lapce_rpc::plugin::VoltID;
+use lapce_rpc::plugin::{LanguageServerConfig, VoltID};
 use lsp_types::{CompletionItemKind, SymbolKind};
 use once_cell::sync::Lazy;
 use parking_lot::RwLock;
 use serde::Deserialize;
+use tracing::error;
 
 use self::{
     color::LapceColor,
-    color_theme::{ColorThemeConfig, ThemeColor, ThemeColorPreference},
+    color_theme::{
+        ColorThemeConfig, SyntaxFontStyle, ThemeColor, ThemeColorPreference,
+        ThemeOverridesConfig,
+    },
     core::CoreConfig,
     editor::EditorConfig,
+    files::FilesConfig,
     icon::LapceIcons,
     icon_theme::IconThemeConfig,
+    search::SearchConfig,
     svg::SvgStore,
     terminal::TerminalConfig,
     ui::UIConfig,
 };
-use crate::workspace::{LapceWorkspace, LapceWorkspaceType};
+use crate::{
+    job::assert_off_ui_thread,
+    logging::LOG_LEVELS,
+    trust::{restrict_settings, WorkspaceTrust},
+    workspace::{LapceWorkspace, LapceWorkspaceType},
+};
 
 pub mod color;
 pub mod color_theme;
 pub mod core;
 pub mod editor;
+pub mod files;
 pub mod icon;
 pub mod icon_theme;
+pub mod os_theme;
+pub mod search;
 pub mod svg;
 pub mod terminal;
 pub mod ui;
 pub mod watcher;
 
 pub const LOGO: &str = include_str!("../../extra/images/logo.svg");
-const DEFAULT_SETTINGS: &str = include_str!("../../defaults/settings.toml");
+pub const DEFAULT_SETTINGS: &str = include_str!("../../defaults/settings.toml");
 const DEFAULT_LIGHT_THEME: &str = include_str!("../../defaults/light-theme.toml");
-const DEFAULT_DARK_THEME: &str = include_str!("../../defaults/dark-theme.toml");
+pub const DEFAULT_DARK_THEME: &str = include_str!("../../defaults/dark-theme.toml");
 const DEFAULT_ICON_THEME: &str = include_str!("../../defaults/icon-theme.toml");
+pub const DEFAULT_USER_KEYMAPS: &str =
+    include_str!("../../defaults/keymaps-user.toml");
 
 static DEFAULT_CONFIG: Lazy<config::Config> = Lazy::new(LapceConfig::default_config);
 static DEFAULT_LAPCE_CONFIG: Lazy<LapceConfig> =
     Lazy::new(LapceConfig::default_lapce_config);
 
+/// The edits of the settings file, as the parent table, the key and the value
+static SETTINGS_WRITER: Lazy<Sender<(String, String, toml_edit::Value)>> =
+    Lazy::new(|| {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        std::thread::spawn(move || {
+            for (parent, key, value) in rx {
+                LapceConfig::write_setting(&parent, &key, value);
+            }
+        });
+        tx
+    });
+
 /// Used for creating a `DropdownData` for a setting
 #[derive(Debug, Clone)]
 pub struct DropdownInfo {
@@ -67,6 +100,20 @@ pub struct LapceConfig {
     pub terminal: TerminalConfig,
     pub color_theme: ColorThemeConfig,
     pub icon_theme: IconThemeConfig,
+    #[serde(default)]
+    pub theme: ThemeOverridesConfig,
+    /// Per-language overrides of comment tokens, indentation, auto-closing
+    /// pairs and rulers, under `[languages.<name>]`
+    #[serde(default)]
+    pub languages: HashMap<String, LanguageConfigOverrides>,
+    /// Language servers to run without a plugin, under
+    /// `[language-servers.<language id>]`
+    #[serde(default)]
+    pub language_servers: HashMap<String, LanguageServerConfig>,
+    #[serde(default)]
+    pub files: FilesConfig,
+    #[serde(default)]
+    pub search: SearchConfig,
     #[serde(flatten)]
     pub plugins: HashMap<String, HashMap<String, serde_json::Value>>,
     #[serde(skip)]
@@ -90,19 +137,55 @@ pub struct LapceConfig {
     color_theme_list: im::Vector<String>,
     #[serde(skip)]
     icon_theme_list: im::Vector<String>,
+    /// The color theme files that failed to load.
+    #[serde(skip)]
+    pub color_theme_errors: Vec<String>,
+    /// Whether the settings of the workspace that run programs were used
+    #[serde(skip)]
+    pub workspace_trust: WorkspaceTrust,
+    /// The user's settings with the workspace's on top, as they were read
+    /// from their files, which a theme is put under when it's switched to
+    #[serde(skip)]
+    settings: config::Config,
+    /// Whether the OS was set to a dark appearance, for an `"auto"` color
+    /// theme
+    #[serde(skip)]
+    os_prefers_dark: Option<bool>,
+    /// The user's keymaps file and what's in it, which the keymaps are made
+    /// from
+    #[serde(skip)]
+    pub user_keymaps: Option<(PathBuf, String)>,
 }
 
 impl LapceConfig {
-    pub fn load(workspace: &LapceWorkspace, disabled_volts: &[VoltID]) -> Self {
-        let config = Self::merge_config(workspace, None, None);
+    pub fn load(
+        workspace: &LapceWorkspace,
+        disabled_volts: &[VoltID],
+        workspace_trust: WorkspaceTrust,
+    ) -> Self {
+        assert_off_ui_thread("Loading the settings");
+        let settings = Self::load_settings(workspace, workspace_trust);
+        let config = Self::merge_config(&settings, None, None);
         let mut lapce_config: LapceConfig = config
             .try_deserialize()
             .unwrap_or_else(|_| DEFAULT_LAPCE_CONFIG.clone());
+        lapce_config.workspace_trust = workspace_trust;
+        lapce_config.settings = settings;
+        // Asking the OS runs a command, so it's only done when it's used
+        if lapce_config.core.color_theme.eq_ignore_ascii_case("auto") {
+            lapce_config.os_prefers_dark = os_theme::os_prefers_dark();
+        }
+        lapce_config.user_keymaps = Self::keymaps_file().and_then(|path| {
+            let content = std::fs::read_to_string(&path).ok()?;
+            Some((path, content))
+        });
 
+        let mut color_theme_errors = Vec::new();
         lapce_config.available_color_themes =
-            Self::load_color_themes(disabled_volts);
+            Self::load_color_themes(disabled_volts, &mut color_theme_errors);
+        lapce_config.color_theme_errors = color_theme_errors;
         lapce_config.available_icon_themes = Self::load_icon_themes(disabled_volts);
-        lapce_config.resolve_theme(workspace);
+        lapce_config.resolve_theme();
 
         lapce_config.color_theme_list = lapce_config
             .available_color_themes
@@ -122,11 +205,46 @@ pub fn load(workspace: &LapceWorkspace, disabled_volts: &[VoltID]) -> Self {
 
         lapce_config.terminal.get_indexed_colors();
 
+        let svgs = lapce_config.icon_theme_svgs();
+        lapce_config.svg_store.write().load_svgs_on_disk(svgs);
+
         lapce_config
     }
 
-    fn merge_config(
+    /// The settings of the user's settings file, with the ones of the
+    /// workspace's on top.
+    fn load_settings(
         workspace: &LapceWorkspace,
+        workspace_trust: WorkspaceTrust,
+    ) -> config::Config {
+        let mut settings = config::Config::default();
+        if let Some(path) = Self::settings_file() {
+            settings = config::Config::builder()
+                .add_source(settings.clone())
+                .add_source(config::File::from(path.as_path()).required(false))
+                .build()
+                .unwrap_or_else(|_| settings.clone());
+        }
+
+        if let Some(path) = Self::workspace_settings_file(workspace) {
+            let builder = config::Config::builder().add_source(settings.clone());
+            let builder = if workspace_trust.is_trusted() {
+                builder
+                    .add_source(config::File::from(path.as_path()).required(false))
+            } else {
+                builder.add_source(config::File::from_str(
+                    &Self::restricted_settings(&path),
+                    config::FileFormat::Toml,
+                ))
+            };
+            settings = builder.build().unwrap_or_else(|_| settings.clone());
+        }
+
+        settings
+    }
+
+    fn merge_config(
+        settings: &config::Config,
         color_theme_config: Option<config::Config>,
         icon_theme_config: Option<config::Config>,
     ) -> config::Config {
@@ -147,33 +265,26 @@ fn merge_config(
                 .unwrap_or_else(|_| config.clone());
         }
 
-        if let Some(path) = Self::settings_file() {
-            config = config::Config::builder()
-                .add_source(config.clone())
-                .add_source(config::File::from(path.as_path()).required(false))
-                .build()
-                .unwrap_or_else(|_| config.clone());
-        }
+        config::Config::builder()
+            .add_source(config.clone())
+            .add_source(settings.clone())
+            .build()
+            .unwrap_or(config)
+    }
 
-        match workspace.kind {
-            LapceWorkspaceType::Local => {
-                if let Some(path) = workspace.path.as_ref() {
-                    let path = path.join("./.lapce/settings.toml");
-                    config = config::Config::builder()
-                        .add_source(config.clone())
-                        .add_source(
-                            config::File::from(path.as_path()).required(false),
-                        )
-                        .build()
-                        .unwrap_or_else(|_| config.clone());
-                }
-            }
-            LapceWorkspaceType::RemoteSSH(_) => {}
-            #[cfg(windows)]
-            LapceWorkspaceType::RemoteWSL => {}
+    /// The settings of the settings file at `path` that a workspace that isn't
+    /// trusted can set.
+    fn restricted_settings(path: &Path) -> String {
+        let Some(mut settings) = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| content.parse::<toml::Value>().ok())
+        else {
+            return String::new();
+        };
+        if let Some(settings) = settings.as_table_mut() {
+            restrict_settings(settings);
         }
-
-        config
+        toml::to_string(&settings).unwrap_or_default()
     }
 
     fn update_id(&mut self) {
@@ -200,11 +311,10 @@ fn default_lapce_config() -> LapceConfig {
         default_lapce_config
     }
 
-    fn resolve_theme(&mut self, workspace: &LapceWorkspace) {
+    fn resolve_theme(&mut self) {
         let mut default_lapce_config = DEFAULT_LAPCE_CONFIG.clone();
-        if let Some((_, color_theme_config)) = self
-            .available_color_themes
-            .get(&self.core.color_theme.to_lowercase())
+        if let Some((_, color_theme_config)) =
+            self.available_color_themes.get(&self.color_theme_key())
         {
             if let Ok(mut theme_lapce_config) = config::Config::builder()
                 .add_source(DEFAULT_CONFIG.clone())
@@ -212,6 +322,9 @@ fn resolve_theme(&mut self, workspace: &LapceWorkspace) {
                 .build()
                 .and_then(|theme| theme.try_deserialize::<LapceConfig>())
             {
+                theme_lapce_config
+                    .color_theme
+                    .report_invalid_colors(&default_lapce_config.color);
                 theme_lapce_config.resolve_colors(Some(&default_lapce_config));
                 default_lapce_config = theme_lapce_config;
             }
@@ -219,7 +332,7 @@ fn resolve_theme(&mut self, workspace: &LapceWorkspace) {
 
         let color_theme_config = self
             .available_color_themes
-            .get(&self.core.color_theme.to_lowercase())
+            .get(&self.color_theme_key())
             .map(|(_, config)| config);
 
         let icon_theme_config = self
@@ -234,7 +347,7 @@ fn resolve_theme(&mut self, workspace: &LapceWorkspace) {
 
         if color_theme_config.is_some() || icon_theme_config.is_some() {
             if let Ok(new) = Self::merge_config(
-                workspace,
+                &self.settings,
                 color_theme_config.cloned(),
                 icon_theme_config.cloned(),
             )
@@ -248,6 +361,7 @@ fn resolve_theme(&mut self, workspace: &LapceWorkspace) {
 
                 self.color_theme = new.color_theme;
                 self.icon_theme = new.icon_theme;
+                self.theme = new.theme;
                 if let Some(icon_theme_path) = icon_theme_path {
                     self.icon_theme.path =
                         icon_theme_path.clone().unwrap_or_default();
@@ -263,13 +377,18 @@ fn resolve_theme(&mut self, workspace: &LapceWorkspace) {
 
     fn load_color_themes(
         disabled_volts: &[VoltID],
+        errors: &mut Vec<String>,
     ) -> HashMap<String, (String, config::Config)> {
-        let mut themes = Self::load_local_themes().unwrap_or_default();
+        let mut themes = Self::load_local_themes(errors).unwrap_or_default();
 
-        for (key, theme) in Self::load_plugin_color_themes(disabled_volts) {
+        for (key, theme) in Self::load_plugin_color_themes(disabled_volts, errors) {
             themes.insert(key, theme);
         }
 
+        for err in errors.iter() {
+            error!("{err}");
+        }
+
         let (name, theme) =
             Self::load_color_theme_from_str(DEFAULT_LIGHT_THEME).unwrap();
         themes.insert(name.to_lowercase(), (name, theme));
@@ -280,39 +399,119 @@ fn load_color_themes(
         themes
     }
 
+    /// The key in `available_color_themes` of the theme to use, which for
+    /// `"auto"` is the light or dark theme matching the OS appearance.
+    fn color_theme_key(&self) -> String {
+        if self.core.color_theme.eq_ignore_ascii_case("auto") {
+            if self.os_prefers_dark.unwrap_or(true) {
+                self.core.dark_color_theme.to_lowercase()
+            } else {
+                self.core.light_color_theme.to_lowercase()
+            }
+        } else {
+            self.core.color_theme.to_lowercase()
+        }
+    }
+
     /// Set the active color theme.
     /// Note that this does not save the config.
-    pub fn set_color_theme(&mut self, workspace: &LapceWorkspace, theme: &str) {
+    pub fn set_color_theme(&mut self, theme: &str) {
         self.core.color_theme = theme.to_string();
-        self.resolve_theme(workspace);
+        self.resolve_theme();
+    }
+
+    /// Keep using the active color theme from `old` if it failed to load this
+    /// time, which happens when its file is saved while being edited.
+    pub fn keep_color_theme(&mut self, old: &LapceConfig) {
+        let key = self.color_theme_key();
+        if self.available_color_themes.contains_key(&key) {
+            return;
+        }
+        if let Some(theme) = old.available_color_themes.get(&key) {
+            self.available_color_themes.insert(key, theme.clone());
+            self.resolve_theme();
+        }
     }
 
     /// Set the active icon theme.  
     /// Note that this does not save the config.
-    pub fn set_icon_theme(&mut self, workspace: &LapceWorkspace, theme: &str) {
+    pub fn set_icon_theme(&mut self, theme: &str) {
         self.core.icon_theme = theme.to_string();
-        self.resolve_theme(workspace);
+        self.resolve_theme();
     }
 
     pub fn set_modal(&mut self, _workspace: &LapceWorkspace, modal: bool) {
         self.core.modal = modal;
     }
 
-    /// Get the color by the name from the current theme if it exists
-    /// Otherwise, get the color from the base them
-    /// # Panics
-    /// If the color was not able to be found in either theme, which may be indicative that
-    /// it is misspelled or needs to be added to the base-theme.
+    /// Get the color by the name from the current theme.
+    ///
+    /// The keys are namespaced by `.`, so if the theme doesn't have the color a
+    /// parent key is tried, e.g. `panel.current.foreground.dim` falls back to
+    /// `panel.current.foreground` and then `panel.current`. If no parent matches
+    /// either, the color comes from the default theme, and lastly the editor
+    /// foreground, so this never fails.
     pub fn get_color(&self, name: &str) -> &Color {
-        self.color
-            .ui
-            .get(name)
-            .unwrap_or_else(|| panic!("Key not found: {name}"))
+        if let Some(color) = Self::get_namespaced_color(&self.color.ui, name) {
+            return color;
+        }
+
+        let default_ui = &DEFAULT_LAPCE_CONFIG.color.ui;
+        if let Some(color) = default_ui.get(name) {
+            return color;
+        }
+
+        error!("color key not found: {name}");
+        default_ui
+            .get(LapceColor::EDITOR_FOREGROUND)
+            .unwrap_or(&Color::BLACK)
+    }
+
+    fn get_namespaced_color<'a>(
+        colors: &'a HashMap<String, Color>,
+        name: &str,
+    ) -> Option<&'a Color> {
+        let mut key = name;
+        loop {
+            if let Some(color) = colors.get(key) {
+                return Some(color);
+            }
+            key = key.rsplit_once('.')?.0;
+        }
+    }
+
+    /// The user's `[languages.<name>]` overrides of `language`, which can be
+    /// named by its id or its name, in any case.
+    pub fn language_overrides(
+        &self,
+        language: LapceLanguage,
+    ) -> Option<&LanguageConfigOverrides> {
+        self.languages
+            .iter()
+            .find(|(name, _)| {
+                name.eq_ignore_ascii_case(language.as_ref())
+                    || name.eq_ignore_ascii_case(language.name())
+            })
+            .map(|(_, overrides)| overrides)
     }
 
-    /// Retrieve a color value whose key starts with "style."
+    /// Retrieve the color of a syntax scope. Scopes are namespaced by `.`, and
+    /// the longest prefix the theme has a color for is used, so
+    /// `keyword.control` falls back to `keyword`.
     pub fn get_style_color(&self, name: &str) -> Option<&Color> {
-        self.color.syntax.get(name)
+        Self::get_namespaced_color(&self.color.syntax, name)
+    }
+
+    /// Retrieve the font style of a syntax scope, matched like
+    /// [`LapceConfig::get_style_color`].
+    pub fn get_style_font(&self, name: &str) -> Option<SyntaxFontStyle> {
+        let mut key = name;
+        loop {
+            if let Some(style) = self.color.syntax_style.get(key) {
+                return Some(*style);
+            }
+            key = key.rsplit_once('.')?.0;
+        }
     }
 
     pub fn completion_color(
@@ -353,6 +552,10 @@ fn resolve_colors(&mut self, default_config: Option<&LapceConfig>) {
             &self.color.base,
             default_config.map(|c| &c.color.syntax),
         );
+        self.color.syntax_style = self
+            .color_theme
+            .resolve_syntax_style(default_config.map(|c| &c.color.syntax_style));
+        self.theme.apply(&mut self.color);
 
         let fg = self.get_color(LapceColor::EDITOR_FOREGROUND);
         let bg = self.get_color(LapceColor::EDITOR_BACKGROUND);
@@ -367,31 +570,48 @@ fn resolve_colors(&mut self, default_config: Option<&LapceConfig>) {
         };
     }
 
-    fn load_local_themes() -> Option<HashMap<String, (String, config::Config)>> {
+    fn load_local_themes(
+        errors: &mut Vec<String>,
+    ) -> Option<HashMap<String, (String, config::Config)>> {
         let themes_folder = Directory::themes_directory()?;
-        let themes: HashMap<String, (String, config::Config)> =
-            std::fs::read_dir(themes_folder)
-                .ok()?
-                .filter_map(|entry| {
-                    entry
-                        .ok()
-                        .and_then(|entry| Self::load_color_theme(&entry.path()))
-                })
-                .collect();
+        let mut themes = HashMap::new();
+        for entry in std::fs::read_dir(themes_folder).ok()?.flatten() {
+            match Self::load_color_theme(&entry.path()) {
+                Ok(Some((key, theme))) => {
+                    themes.insert(key, theme);
+                }
+                Ok(None) => {}
+                Err(err) => errors.push(err),
+            }
+        }
         Some(themes)
     }
 
-    fn load_color_theme(path: &Path) -> Option<(String, (String, config::Config))> {
+    /// Load the color theme at `path`, the error describes why the file isn't
+    /// a valid theme, for toml errors it includes the line and column.
+    fn load_color_theme(
+        path: &Path,
+    ) -> Result<Option<(String, (String, config::Config))>, String> {
         if !path.is_file() {
-            return None;
+            return Ok(None);
         }
         let config = config::Config::builder()
             .add_source(config::File::from(path))
             .build()
-            .ok()?;
-        let table = config.get_table("color-theme").ok()?;
-        let name = table.get("name")?.to_string();
-        Some((name.to_lowercase(), (name, config)))
+            .map_err(|err| format!("failed to load color theme {path:?}: {err}"))?;
+        // A theme that's all commented out, like the one written on the first
+        // run, isn't one yet
+        if config.collect().map_or(false, |values| values.is_empty()) {
+            return Ok(None);
+        }
+        let table = config.get_table("color-theme").map_err(|err| {
+            format!("color theme {path:?} has no [color-theme] table: {err}")
+        })?;
+        let name = table
+            .get("name")
+            .map(|name| name.to_string())
+            .ok_or_else(|| format!("color theme {path:?} has no name"))?;
+        Ok(Some((name.to_lowercase(), (name, config))))
     }
 
     fn load_color_theme_from_str(s: &str) -> Option<(String, config::Config)> {
@@ -434,6 +654,7 @@ fn load_icon_theme_from_str(s: &str) -> Option<(String, config::Config)> {
 
     fn load_plugin_color_themes(
         disabled_volts: &[VoltID],
+        errors: &mut Vec<String>,
     ) -> HashMap<String, (String, config::Config)> {
         let mut themes: HashMap<String, (String, config::Config)> = HashMap::new();
         for meta in find_all_volts() {
@@ -442,10 +663,12 @@ fn load_plugin_color_themes(
             }
             if let Some(plugin_themes) = meta.color_themes.as_ref() {
                 for theme_path in plugin_themes {
-                    if let Some((key, theme)) =
-                        Self::load_color_theme(&PathBuf::from(theme_path))
-                    {
-                        themes.insert(key, theme);
+                    match Self::load_color_theme(&PathBuf::from(theme_path)) {
+                        Ok(Some((key, theme))) => {
+                            themes.insert(key, theme);
+                        }
+                        Ok(None) => {}
+                        Err(err) => errors.push(err),
                     }
                 }
             }
@@ -493,6 +716,21 @@ fn load_icon_theme(
         ))
     }
 
+    /// The settings file of `workspace`, whose settings take precedence over
+    /// the user's in it. Only the local workspaces have one.
+    pub fn workspace_settings_file(workspace: &LapceWorkspace) -> Option<PathBuf> {
+        match workspace.kind {
+            LapceWorkspaceType::Local => Some(
+                workspace
+                    .path
+                    .as_ref()?
+                    .join(".lapce")
+                    .join("settings.toml"),
+            ),
+            _ => None,
+        }
+    }
+
     pub fn settings_file() -> Option<PathBuf> {
         let path = Directory::config_directory()?.join("settings.toml");
 
@@ -510,19 +748,50 @@ pub fn keymaps_file() -> Option<PathBuf> {
         let path = Directory::config_directory()?.join("keymaps.toml");
 
         if !path.exists() {
-            let _ = std::fs::OpenOptions::new()
+            if let Ok(mut file) = std::fs::OpenOptions::new()
                 .create_new(true)
                 .write(true)
-                .open(&path);
+                .open(&path)
+            {
+                let _ = file.write_all(DEFAULT_USER_KEYMAPS.as_bytes());
+            }
         }
 
         Some(path)
     }
 
+    /// The files of the icons of the icon themes on disk, which are read
+    /// along with the settings rather than when they're painted.
+    fn icon_theme_svgs(&self) -> Vec<PathBuf> {
+        let mut themes = vec![self.icon_theme.clone()];
+        for (_, config, path) in self.available_icon_themes.values() {
+            let Some(path) = path else {
+                continue;
+            };
+            if let Ok(mut theme) = config.get::<IconThemeConfig>("icon-theme") {
+                theme.path = path.clone();
+                themes.push(theme);
+            }
+        }
+        themes
+            .iter()
+            .filter(|theme| !theme.path.as_os_str().is_empty())
+            .flat_map(|theme| {
+                theme
+                    .ui
+                    .values()
+                    .chain(theme.foldername.values())
+                    .chain(theme.filename.values())
+                    .chain(theme.extension.values())
+                    .map(|icon| theme.path.join(icon))
+            })
+            .collect()
+    }
+
     pub fn ui_svg(&self, icon: &'static str) -> String {
         let svg = self.icon_theme.ui.get(icon).and_then(|path| {
             let path = self.icon_theme.path.join(path);
-            self.svg_store.write().get_svg_on_disk(&path)
+            self.svg_store.read().get_svg_on_disk(&path)
         });
 
         svg.unwrap_or_else(|| {
@@ -535,7 +804,7 @@ pub fn file_svg(&self, path: &Path) -> (String, Option<&Color>) {
         let svg = self
             .icon_theme
             .resolve_path_to_icon(path)
-            .and_then(|p| self.svg_store.write().get_svg_on_disk(&p));
+            .and_then(|p| self.svg_store.read().get_svg_on_disk(&p));
         if let Some(svg) = svg {
             let color = if self.icon_theme.use_editor_color.unwrap_or(false) {
                 Some(self.get_color(LapceColor::LAPCE_ICON_ACTIVE))
@@ -581,6 +850,41 @@ pub fn symbol_svg(&self, kind: &SymbolKind) -> Option<String> {
         Some(self.ui_svg(kind_str))
     }
 
+    pub fn completion_svg(&self, kind: CompletionItemKind) -> Option<String> {
+        let kind_str = match kind {
+            CompletionItemKind::CLASS => LapceIcons::COMPLETION_ITEM_KIND_CLASS,
+            CompletionItemKind::CONSTANT => {
+                LapceIcons::COMPLETION_ITEM_KIND_CONSTANT
+            }
+            CompletionItemKind::ENUM_MEMBER => {
+                LapceIcons::COMPLETION_ITEM_KIND_ENUM_MEMBER
+            }
+            CompletionItemKind::ENUM => LapceIcons::COMPLETION_ITEM_KIND_ENUM,
+            CompletionItemKind::FIELD => LapceIcons::COMPLETION_ITEM_KIND_FIELD,
+            CompletionItemKind::FUNCTION => {
+                LapceIcons::COMPLETION_ITEM_KIND_FUNCTION
+            }
+            CompletionItemKind::INTERFACE => {
+                LapceIcons::COMPLETION_ITEM_KIND_INTERFACE
+            }
+            CompletionItemKind::KEYWORD => LapceIcons::COMPLETION_ITEM_KIND_KEYWORD,
+            CompletionItemKind::METHOD => LapceIcons::COMPLETION_ITEM_KIND_METHOD,
+            CompletionItemKind::MODULE => LapceIcons::COMPLETION_ITEM_KIND_MODULE,
+            CompletionItemKind::PROPERTY => {
+                LapceIcons::COMPLETION_ITEM_KIND_PROPERTY
+            }
+            CompletionItemKind::SNIPPET => LapceIcons::COMPLETION_ITEM_KIND_SNIPPET,
+            CompletionItemKind::TEXT => LapceIcons::COMPLETION_ITEM_KIND_STRING,
+            CompletionItemKind::STRUCT => LapceIcons::COMPLETION_ITEM_KIND_STRUCT,
+            CompletionItemKind::VARIABLE => {
+                LapceIcons::COMPLETION_ITEM_KIND_VARIABLE
+            }
+            _ => return None,
+        };
+
+        Some(self.ui_svg(kind_str))
+    }
+
     pub fn logo_svg(&self) -> String {
         self.svg_store.read().logo_svg()
     }
@@ -779,6 +1083,15 @@ pub fn get_dropdown_info(&self, kind: &str, key: &str) -> Option<DropdownInfo> {
                     .unwrap_or(0),
                 items: self.icon_theme_list.clone(),
             }),
+            ("core", "log-level") => Some(DropdownInfo {
+                active_index: LOG_LEVELS
+                    .iter()
+                    .position(|level| {
+                        level.eq_ignore_ascii_case(&self.core.log_level)
+                    })
+                    .unwrap_or(0),
+                items: LOG_LEVELS.iter().map(|level| level.to_string()).collect(),
+            }),
             _ => None,
         }
     }
@@ -792,11 +1105,17 @@ fn get_file_table() -> Option<toml_edit::Document> {
 
     /// Update the config file with the given edit.  
     /// This should be called whenever the configuration is changed, so that it is persisted.
-    pub fn update_file(
+    /// The edits are written in order by a thread of their own.
+    pub fn update_file(parent: &str, key: &str, value: toml_edit::Value) {
+        let _ = SETTINGS_WRITER.send((parent.to_string(), key.to_string(), value));
+    }
+
+    fn write_setting(
         parent: &str,
         key: &str,
         value: toml_edit::Value,
     ) -> Option<()> {
+        assert_off_ui_thread("Writing the settings");
         let mut main_table = Self::get_file_table().unwrap_or_default();
 
         // Find the container table
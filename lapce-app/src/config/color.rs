@@ -62,6 +62,9 @@ impl LapceColor {
     pub const EDITOR_LINK: &str = "editor.link";
     pub const EDITOR_VISIBLE_WHITESPACE: &str = "editor.visible_whitespace";
     pub const EDITOR_INDENT_GUIDE: &str = "editor.indent_guide";
+    pub const EDITOR_RULER: &str = "editor.ruler";
+    pub const EDITOR_SPELLING: &str = "editor.spelling";
+    pub const EDITOR_ERROR_FLASH: &str = "editor.error_flash";
     pub const EDITOR_DRAG_DROP_BACKGROUND: &str = "editor.drag_drop_background";
     pub const EDITOR_STICKY_HEADER_BACKGROUND: &str =
         "editor.sticky_header_background";
@@ -71,6 +74,9 @@ impl LapceColor {
     pub const INLAY_HINT_FOREGROUND: &str = "inlay_hint.foreground";
     pub const INLAY_HINT_BACKGROUND: &str = "inlay_hint.background";
 
+    pub const JUMP_LABEL_FOREGROUND: &str = "jump_label.foreground";
+    pub const JUMP_LABEL_BACKGROUND: &str = "jump_label.background";
+
     pub const ERROR_LENS_ERROR_FOREGROUND: &str = "error_lens.error.foreground";
     pub const ERROR_LENS_ERROR_BACKGROUND: &str = "error_lens.error.background";
     pub const ERROR_LENS_WARNING_FOREGROUND: &str = "error_lens.warning.foreground";
@@ -153,4 +159,140 @@ impl LapceColor {
         "status.modal.terminal.foreground";
 
     pub const MARKDOWN_BLOCKQUOTE: &'static str = "markdown.blockquote";
+
+    /// Every color key above, which the default theme needs a value for as
+    /// the other themes fall back to it
+    pub const ALL: &[&str] = &[
+        Self::LAPCE_WARN,
+        Self::LAPCE_ERROR,
+        Self::LAPCE_DROPDOWN_SHADOW,
+        Self::LAPCE_BORDER,
+        Self::LAPCE_SCROLL_BAR,
+        Self::LAPCE_BUTTON_PRIMARY_BACKGROUND,
+        Self::LAPCE_BUTTON_PRIMARY_FOREGROUND,
+        Self::LAPCE_TAB_ACTIVE_BACKGROUND,
+        Self::LAPCE_TAB_ACTIVE_FOREGROUND,
+        Self::LAPCE_TAB_ACTIVE_UNDERLINE,
+        Self::LAPCE_TAB_INACTIVE_BACKGROUND,
+        Self::LAPCE_TAB_INACTIVE_FOREGROUND,
+        Self::LAPCE_TAB_INACTIVE_UNDERLINE,
+        Self::LAPCE_TAB_SEPARATOR,
+        Self::LAPCE_ICON_ACTIVE,
+        Self::LAPCE_ICON_INACTIVE,
+        Self::LAPCE_REMOTE_ICON,
+        Self::LAPCE_REMOTE_LOCAL,
+        Self::LAPCE_REMOTE_CONNECTED,
+        Self::LAPCE_REMOTE_CONNECTING,
+        Self::LAPCE_REMOTE_DISCONNECTED,
+        Self::LAPCE_PLUGIN_NAME,
+        Self::LAPCE_PLUGIN_DESCRIPTION,
+        Self::LAPCE_PLUGIN_AUTHOR,
+        Self::EDITOR_BACKGROUND,
+        Self::EDITOR_FOREGROUND,
+        Self::EDITOR_DIM,
+        Self::EDITOR_FOCUS,
+        Self::EDITOR_CARET,
+        Self::EDITOR_SELECTION,
+        Self::EDITOR_CURRENT_LINE,
+        Self::EDITOR_LINK,
+        Self::EDITOR_VISIBLE_WHITESPACE,
+        Self::EDITOR_INDENT_GUIDE,
+        Self::EDITOR_RULER,
+        Self::EDITOR_SPELLING,
+        Self::EDITOR_ERROR_FLASH,
+        Self::EDITOR_DRAG_DROP_BACKGROUND,
+        Self::EDITOR_STICKY_HEADER_BACKGROUND,
+        Self::EDITOR_DRAG_DROP_TAB_BACKGROUND,
+        Self::INLAY_HINT_FOREGROUND,
+        Self::INLAY_HINT_BACKGROUND,
+        Self::JUMP_LABEL_FOREGROUND,
+        Self::JUMP_LABEL_BACKGROUND,
+        Self::ERROR_LENS_ERROR_FOREGROUND,
+        Self::ERROR_LENS_ERROR_BACKGROUND,
+        Self::ERROR_LENS_WARNING_FOREGROUND,
+        Self::ERROR_LENS_WARNING_BACKGROUND,
+        Self::ERROR_LENS_OTHER_FOREGROUND,
+        Self::ERROR_LENS_OTHER_BACKGROUND,
+        Self::COMPLETION_LENS_FOREGROUND,
+        Self::SOURCE_CONTROL_ADDED,
+        Self::SOURCE_CONTROL_REMOVED,
+        Self::SOURCE_CONTROL_MODIFIED,
+        Self::TERMINAL_CURSOR,
+        Self::TERMINAL_BACKGROUND,
+        Self::TERMINAL_FOREGROUND,
+        Self::TERMINAL_RED,
+        Self::TERMINAL_BLUE,
+        Self::TERMINAL_GREEN,
+        Self::TERMINAL_YELLOW,
+        Self::TERMINAL_BLACK,
+        Self::TERMINAL_WHITE,
+        Self::TERMINAL_CYAN,
+        Self::TERMINAL_MAGENTA,
+        Self::TERMINAL_BRIGHT_RED,
+        Self::TERMINAL_BRIGHT_BLUE,
+        Self::TERMINAL_BRIGHT_GREEN,
+        Self::TERMINAL_BRIGHT_YELLOW,
+        Self::TERMINAL_BRIGHT_BLACK,
+        Self::TERMINAL_BRIGHT_WHITE,
+        Self::TERMINAL_BRIGHT_CYAN,
+        Self::TERMINAL_BRIGHT_MAGENTA,
+        Self::PALETTE_BACKGROUND,
+        Self::PALETTE_FOREGROUND,
+        Self::PALETTE_CURRENT_BACKGROUND,
+        Self::PALETTE_CURRENT_FOREGROUND,
+        Self::COMPLETION_BACKGROUND,
+        Self::COMPLETION_CURRENT,
+        Self::HOVER_BACKGROUND,
+        Self::ACTIVITY_BACKGROUND,
+        Self::ACTIVITY_CURRENT,
+        Self::DEBUG_BREAKPOINT,
+        Self::DEBUG_BREAKPOINT_HOVER,
+        Self::PANEL_BACKGROUND,
+        Self::PANEL_FOREGROUND,
+        Self::PANEL_FOREGROUND_DIM,
+        Self::PANEL_CURRENT_BACKGROUND,
+        Self::PANEL_CURRENT_FOREGROUND,
+        Self::PANEL_CURRENT_FOREGROUND_DIM,
+        Self::PANEL_HOVERED_BACKGROUND,
+        Self::PANEL_HOVERED_ACTIVE_BACKGROUND,
+        Self::PANEL_HOVERED_FOREGROUND,
+        Self::PANEL_HOVERED_FOREGROUND_DIM,
+        Self::STATUS_BACKGROUND,
+        Self::STATUS_FOREGROUND,
+        Self::STATUS_MODAL_NORMAL_BACKGROUND,
+        Self::STATUS_MODAL_NORMAL_FOREGROUND,
+        Self::STATUS_MODAL_INSERT_BACKGROUND,
+        Self::STATUS_MODAL_INSERT_FOREGROUND,
+        Self::STATUS_MODAL_VISUAL_BACKGROUND,
+        Self::STATUS_MODAL_VISUAL_FOREGROUND,
+        Self::STATUS_MODAL_TERMINAL_BACKGROUND,
+        Self::STATUS_MODAL_TERMINAL_FOREGROUND,
+        Self::MARKDOWN_BLOCKQUOTE,
+    ];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LapceColor;
+
+    const DEFAULT_SETTINGS: &str = include_str!("../../../defaults/settings.toml");
+
+    /// Every color key that is used through `LapceColor` needs a value in the
+    /// default theme, which is what the other themes fall back to.
+    #[test]
+    fn test_default_theme_has_all_colors() {
+        let settings: toml::Value = toml::from_str(DEFAULT_SETTINGS).unwrap();
+        let ui = settings
+            .get("color-theme")
+            .and_then(|theme| theme.get("ui"))
+            .and_then(|ui| ui.as_table())
+            .unwrap();
+
+        for key in LapceColor::ALL {
+            assert!(
+                ui.contains_key(*key),
+                "{key} is missing in the default theme"
+            );
+        }
+    }
 }
@@ -3,6 +3,7 @@
 use floem::peniko::Color;
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
+use tracing::warn;
 
 #[derive(Debug, Clone, Default)]
 pub enum ThemeColorPreference {
@@ -72,11 +73,35 @@ pub fn get(&self, name: &str) -> Option<&Color> {
     }
 }
 
+/// The font style of a syntax scope, in addition to its color.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyntaxFontStyle {
+    pub italic: bool,
+    pub bold: bool,
+}
+
+impl SyntaxFontStyle {
+    /// Parse a style like `"italic"`, `"bold"` or `"bold italic"`.
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut style = SyntaxFontStyle::default();
+        for part in s.split_whitespace() {
+            match part {
+                "italic" => style.italic = true,
+                "bold" => style.bold = true,
+                "normal" => {}
+                _ => return None,
+            }
+        }
+        Some(style)
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct ThemeColor {
     pub color_preference: ThemeColorPreference,
     pub base: ThemeBaseColor,
     pub syntax: HashMap<String, Color>,
+    pub syntax_style: HashMap<String, SyntaxFontStyle>,
     pub ui: HashMap<String, Color>,
 }
 
@@ -140,10 +165,51 @@ pub struct ColorThemeConfig {
     pub high_contrast: Option<bool>,
     pub base: ThemeBaseConfig,
     pub syntax: IndexMap<String, String>,
+    /// The font styles of the syntax scopes, which are matched the same way as
+    /// the colors are.
+    #[serde(default)]
+    pub syntax_style: IndexMap<String, String>,
     pub ui: IndexMap<String, String>,
 }
 
 impl ColorThemeConfig {
+    /// Warn about the colors of the theme which can't be parsed, so they fall
+    /// back to the default theme, and about the ui keys the default theme
+    /// doesn't have, which are most likely misspelled.
+    pub(super) fn report_invalid_colors(&self, default: &ThemeColor) {
+        for name in default.base.keys() {
+            let value = self.base.get(name).map(|v| v.as_str()).unwrap_or("");
+            if Color::parse(value).is_none() {
+                warn!(
+                    "color theme {}: invalid color {value:?} for base.{name}",
+                    self.name
+                );
+            }
+        }
+
+        for (section, colors) in [("syntax", &self.syntax), ("ui", &self.ui)] {
+            for (key, value) in colors {
+                let valid = if let Some(stripped) = value.strip_prefix('$') {
+                    default.base.get(stripped).is_some()
+                } else {
+                    Color::parse(value).is_some()
+                };
+                if !valid {
+                    warn!(
+                        "color theme {}: invalid color {value:?} for {section}.{key}",
+                        self.name
+                    );
+                }
+            }
+        }
+
+        for key in self.ui.keys() {
+            if !default.ui.contains_key(key) {
+                warn!("color theme {}: unknown key ui.{key}", self.name);
+            }
+        }
+    }
+
     fn resolve_color(
         colors: &IndexMap<String, String>,
         base: &ThemeBaseColor,
@@ -177,6 +243,27 @@ pub(super) fn resolve_ui_color(
         Self::resolve_color(&self.ui, base, default)
     }
 
+    pub(super) fn resolve_syntax_style(
+        &self,
+        default: Option<&HashMap<String, SyntaxFontStyle>>,
+    ) -> HashMap<String, SyntaxFontStyle> {
+        let mut styles = default.cloned().unwrap_or_default();
+        for (name, style) in &self.syntax_style {
+            match SyntaxFontStyle::parse(style) {
+                Some(style) => {
+                    styles.insert(name.to_string(), style);
+                }
+                None => {
+                    warn!(
+                        "color theme {}: invalid font style {style:?} for syntax-style.{name}",
+                        self.name
+                    );
+                }
+            }
+        }
+        styles
+    }
+
     pub(super) fn resolve_syntax_color(
         &self,
         base: &ThemeBaseColor,
@@ -185,3 +272,37 @@ pub(super) fn resolve_syntax_color(
         Self::resolve_color(&self.syntax, base, default)
     }
 }
+
+/// The `[theme]` section of the settings file.
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct ThemeOverridesConfig {
+    /// Colors that replace the active theme's, keyed by ui key or syntax scope.
+    #[serde(default)]
+    pub overrides: IndexMap<String, String>,
+}
+
+impl ThemeOverridesConfig {
+    /// Apply the overrides on top of the resolved theme colors. A key is a ui
+    /// color if the theme has it, and a syntax scope otherwise. Values which
+    /// aren't valid colors are reported and skipped.
+    pub(super) fn apply(&self, color: &mut ThemeColor) {
+        for (key, value) in &self.overrides {
+            let parsed = if let Some(stripped) = value.strip_prefix('$') {
+                color.base.get(stripped).cloned()
+            } else {
+                Color::parse(value)
+            };
+            let Some(parsed) = parsed else {
+                warn!("invalid color {value:?} for theme.overrides.{key}");
+                continue;
+            };
+
+            if color.ui.contains_key(key) {
+                color.ui.insert(key.to_string(), parsed);
+            } else {
+                color.syntax.insert(key.to_string(), parsed);
+            }
+        }
+    }
+}
@@ -6,12 +6,30 @@
 pub struct CoreConfig {
     #[field_names(desc = "Enable modal editing (Vim like)")]
     pub modal: bool,
-    #[field_names(desc = "Set the color theme of Lapce")]
+    #[field_names(
+        desc = "Set the color theme of Lapce, \"auto\" follows the OS light/dark appearance"
+    )]
     pub color_theme: String,
+    #[field_names(desc = "The color theme used by \"auto\" when the OS is light")]
+    pub light_color_theme: String,
+    #[field_names(desc = "The color theme used by \"auto\" when the OS is dark")]
+    pub dark_color_theme: String,
     #[field_names(desc = "Set the icon theme of Lapce")]
     pub icon_theme: String,
     #[field_names(
         desc = "Enable customised titlebar and disable OS native one (Linux, BSD, Windows)"
     )]
     pub custom_titlebar: bool,
+    #[field_names(
+        desc = "How long in milliseconds to wait for the next key of a key sequence before running the shorter binding. Set to 0 to wait indefinitely."
+    )]
+    pub key_sequence_timeout: u64,
+    #[field_names(
+        desc = "The key that `leader` stands for in keymaps, which are used in normal and visual mode"
+    )]
+    pub leader: String,
+    #[field_names(
+        desc = "The level of the messages written to the log: off, error, warn, info, debug or trace. `--log-level` takes precedence over it"
+    )]
+    pub log_level: String,
 }
@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+
+use floem::cosmic_text::FamilyOwned;
 use serde::{Deserialize, Serialize};
 use structdesc::FieldNames;
 
@@ -29,6 +32,10 @@ pub struct EditorConfig {
         desc = "If enabled, when you input a tab character, it will insert indent that's detected based on your files."
     )]
     pub smart_tab: bool,
+    #[field_names(
+        desc = "If enabled, shifting lines with the indent and outdent commands and operators shifts blank lines too. It can be set for a language under [languages] too"
+    )]
+    pub shift_blank_lines: bool,
     #[field_names(desc = "Set the tab width")]
     pub tab_width: usize,
     #[field_names(desc = "If opened editors are shown in a tab")]
@@ -45,6 +52,10 @@ pub struct EditorConfig {
         desc = "Show code context like functions and classes at the top of editor when scroll"
     )]
     pub sticky_header: bool,
+    #[field_names(desc = "If the minimap is shown on the right of the editor")]
+    pub show_minimap: bool,
+    #[field_names(desc = "Set the width of the minimap")]
+    pub minimap_width: usize,
     #[field_names(
         desc = "If the editor should show the documentation of the current completion item"
     )]
@@ -69,6 +80,10 @@ pub struct EditorConfig {
         desc = "If modal mode should have relative line numbers (though, not in insert mode)"
     )]
     pub modal_mode_relative_line_numbers: bool,
+    #[field_names(
+        desc = "Whether the editor shows line numbers in the gutter. It can be changed for a single editor with the Toggle Line Numbers command."
+    )]
+    pub show_line_numbers: bool,
     #[field_names(
         desc = "Whether it should format the document on save (if there is an available formatter)"
     )]
@@ -77,6 +92,11 @@ pub struct EditorConfig {
     #[field_names(desc = "If matching brackets are highlighted")]
     pub highlight_matching_brackets: bool,
 
+    #[field_names(
+        desc = "If bracket pairs are colored by how deeply they are nested"
+    )]
+    pub bracket_pair_colorization: bool,
+
     #[field_names(desc = "If scope lines are highlighted")]
     pub highlight_scope_lines: bool,
 
@@ -105,6 +125,10 @@ pub struct EditorConfig {
         desc = "Set the error lens font size. If 0 it uses the inlay hint font size."
     )]
     pub error_lens_font_size: usize,
+    #[field_names(
+        desc = "If the author, date and summary of the commit that last changed the cursor's line are shown at its end"
+    )]
+    pub inline_blame: bool,
     #[field_names(
         desc = "If the editor should display the completion item as phantom text"
     )]
@@ -126,13 +150,17 @@ pub struct EditorConfig {
     )]
     pub multicursor_case_sensitive: bool,
     #[field_names(
-        desc = "Whether the multiple cursor selection only selects whole words."
+        desc = "Whether selecting the occurrences of a whole word with multiple cursors only selects them where they're whole words too."
     )]
     pub multicursor_whole_words: bool,
     #[field_names(
-        desc = "How the editor should render whitespace characters.\nOptions: none, all, boundary, trailing."
+        desc = "How the editor should render whitespace characters. It can be changed for a single editor with the Toggle Whitespace command.\nOptions: none, all, boundary, trailing."
     )]
     pub render_whitespace: String,
+    #[field_names(
+        desc = "How the editor makes it known that a command couldn't be done, like a motion at the end of the document or a search without matches.\nOptions: flash, beep, none."
+    )]
+    pub error_feedback: String,
     #[field_names(desc = "Whether the editor show indent guide.")]
     pub show_indent_guide: bool,
     #[field_names(
@@ -157,6 +185,59 @@ pub struct EditorConfig {
         desc = "Set the default number of visible lines above and below the diff block (-1 for infinite)"
     )]
     pub diff_context_lines: i32,
+    #[field_names(
+        desc = "If lines that only differ in whitespace are left unmarked in the gutter. The diff view has its own option, set with the Toggle Diff Ignore Whitespace command."
+    )]
+    pub gutter_diff_ignore_whitespace: bool,
+    #[field_names(
+        desc = "Set the multiplier for the distance the editor scrolls by on mouse wheel and trackpad movement"
+    )]
+    pub scroll_speed_modifier: f64,
+    #[field_names(
+        desc = "Set the number of lines the editor scrolls by for each step of a mouse wheel"
+    )]
+    pub wheel_scroll_lines: usize,
+    #[field_names(
+        desc = "Set the size in megabytes above which files are shown without syntax highlighting"
+    )]
+    pub max_syntax_file_size: usize,
+    #[field_names(
+        desc = "If the selected text is put in the primary selection, and a middle click pastes it (Linux only)"
+    )]
+    pub primary_selection: bool,
+    #[field_names(
+        desc = "If the line numbers are included when exporting or copying the document as HTML"
+    )]
+    pub html_line_numbers: bool,
+    #[field_names(
+        desc = "Assign languages to file names, extensions and globs over the built-in ones, like `h = \"cpp\"`, `Justfile = \"make\"` or `\"*.conf\" = \"toml\"`"
+    )]
+    #[serde(alias = "file-languages")]
+    pub file_associations: HashMap<String, String>,
+    #[field_names(
+        desc = "The columns to draw rulers at, like `[80, 100]`. A language can have its own under `[languages.<name>]`"
+    )]
+    pub rulers: Vec<usize>,
+    #[field_names(
+        desc = "The column the reflow operator (`gq`) wraps lines at. 0 uses the last ruler, or 80 if there's none"
+    )]
+    pub text_width: usize,
+    #[field_names(
+        desc = "Give git commit messages 50/72 rulers, and mark the subject or body lines that are too long"
+    )]
+    pub git_commit_mode: bool,
+    #[field_names(
+        desc = "Put the cursor back where it was when a file was last closed, rather than at the top, when it's opened again"
+    )]
+    pub restore_cursor_position: bool,
+    #[field_names(
+        desc = "Show the changes of a rename or code action that touches more than one file in the edit preview panel, to pick the ones to apply"
+    )]
+    pub preview_workspace_edits: bool,
+    #[field_names(
+        desc = "Check the spelling of the comments and strings of code, and of all the text of Markdown and plain text files. It can be set for a language under [languages] too, and toggled for a file with the Toggle Spell Check command"
+    )]
+    pub spell_check: bool,
 }
 
 impl EditorConfig {
@@ -164,6 +245,18 @@ pub fn font_size(&self) -> usize {
         self.font_size.max(6).min(32)
     }
 
+    /// The families of the editor font, ending with the generic monospace one,
+    /// so that a family that isn't installed falls back to a monospace font
+    /// rather than the proportional default.
+    pub fn font_family(&self) -> Vec<FamilyOwned> {
+        let mut family: Vec<FamilyOwned> =
+            FamilyOwned::parse_list(&self.font_family).collect();
+        if !family.contains(&FamilyOwned::Monospace) {
+            family.push(FamilyOwned::Monospace);
+        }
+        family
+    }
+
     pub fn line_height(&self) -> usize {
         const SCALE_OR_SIZE_LIMIT: f64 = 5.0;
 
@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+
+/// Settings of the files of the workspace, under `[files]`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct FilesConfig {
+    /// Globs of the files and folders left out of the file palette, like
+    /// `**/node_modules` or `*.min.js`, relative to the workspace
+    pub exclude: Vec<String>,
+    /// Sort file names ignoring case and with their numbers by value, so
+    /// that `file2` comes before `File10`, in the file explorer, the palette
+    /// and the search results, rather than by their characters
+    pub natural_sort: bool,
+    /// Move the files deleted in the file explorer to the trash, rather than
+    /// deleting them for good
+    pub delete_to_trash: bool,
+    /// Offer to create the counterpart of a file that Alternate File finds
+    /// none of, rather than looking for it in the file palette
+    pub alternate_create_if_missing: bool,
+}
+
+impl Default for FilesConfig {
+    fn default() -> Self {
+        Self {
+            exclude: Vec::new(),
+            natural_sort: true,
+            delete_to_trash: true,
+            alternate_create_if_missing: false,
+        }
+    }
+}
@@ -26,10 +26,14 @@ impl LapceIcons {
     pub const RUN_ERRORS: &str = "run_errors";
     pub const UNSAVED: &str = "unsaved";
     pub const WARNING: &str = "warning";
+    pub const INFO: &str = "info";
     pub const TERMINAL: &str = "terminal";
     pub const SETTINGS: &str = "settings";
     pub const LIGHTBULB: &str = "lightbulb";
     pub const EXTENSIONS: &str = "extensions";
+    pub const OUTLINE: &str = "outline";
+    pub const EDIT_PREVIEW: &str = "edit_preview";
+    pub const TASK: &str = "task";
     pub const KEYBOARD: &str = "keyboard";
     pub const BREADCRUMB_SEPARATOR: &str = "breadcrumb_separator";
 
@@ -88,6 +92,7 @@ impl LapceIcons {
     pub const SEARCH_CASE_SENSITIVE: &'static str = "search.case_sensitive";
     pub const SEARCH_WHOLE_WORD: &'static str = "search.whole_word";
     pub const SEARCH_REGEX: &'static str = "search.regex";
+    pub const SEARCH_PRESERVE_CASE: &'static str = "search.preserve_case";
     pub const SEARCH_REPLACE: &'static str = "search.replace";
     pub const SEARCH_REPLACE_ALL: &'static str = "search.replace_all";
 
@@ -0,0 +1,55 @@
+use std::process::Command;
+
+use crate::job::assert_off_ui_thread;
+
+/// Whether the OS is set to a dark appearance, `None` if it couldn't be found
+/// out, in which case the dark theme is used.
+///
+/// This asks the platform's own tooling rather than listening for a window
+/// notification, so it is polled to notice the user toggling it.
+pub fn os_prefers_dark() -> Option<bool> {
+    assert_off_ui_thread("Asking the OS for its appearance");
+    #[cfg(target_os = "macos")]
+    {
+        // The key only exists when the dark appearance is on
+        let output = Command::new("defaults")
+            .args(["read", "-g", "AppleInterfaceStyle"])
+            .output()
+            .ok()?;
+        let style = String::from_utf8_lossy(&output.stdout);
+        Some(style.trim().eq_ignore_ascii_case("dark"))
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+        let output = Command::new("reg")
+            .args([
+                "query",
+                r"HKCU\Software\Microsoft\Windows\CurrentVersion\Themes\Personalize",
+                "/v",
+                "AppsUseLightTheme",
+            ])
+            .creation_flags(CREATE_NO_WINDOW)
+            .output()
+            .ok()?;
+        let output = String::from_utf8_lossy(&output.stdout);
+        let value = output.split_whitespace().last()?;
+        Some(value == "0x0")
+    }
+
+    #[cfg(not(any(target_os = "macos", windows)))]
+    {
+        let output = Command::new("gsettings")
+            .args(["get", "org.gnome.desktop.interface", "color-scheme"])
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let scheme = String::from_utf8_lossy(&output.stdout);
+        Some(scheme.contains("dark"))
+    }
+}
@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+
+/// Settings of the search in the files of the workspace, under `[search]`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct SearchConfig {
+    /// Globs of the files and folders left out of the search, like
+    /// `**/fixtures`, relative to the workspace
+    pub exclude: Vec<String>,
+}
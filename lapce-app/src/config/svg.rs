@@ -52,12 +52,18 @@ pub fn get_default_svg(&mut self, name: &str) -> String {
         self.svgs.get(name).unwrap().clone()
     }
 
-    pub fn get_svg_on_disk(&mut self, path: &Path) -> Option<String> {
-        if !self.svgs_on_disk.contains_key(path) {
-            let svg = fs::read_to_string(path).ok();
-            self.svgs_on_disk.insert(path.to_path_buf(), svg);
+    /// Read the svgs at `paths`, which is done before they're painted, as
+    /// that's on the UI thread.
+    pub fn load_svgs_on_disk(&mut self, paths: impl IntoIterator<Item = PathBuf>) {
+        for path in paths {
+            if !self.svgs_on_disk.contains_key(&path) {
+                let svg = fs::read_to_string(&path).ok();
+                self.svgs_on_disk.insert(path, svg);
+            }
         }
+    }
 
-        self.svgs_on_disk.get(path).unwrap().clone()
+    pub fn get_svg_on_disk(&self, path: &Path) -> Option<String> {
+        self.svgs_on_disk.get(path).cloned().flatten()
     }
 }
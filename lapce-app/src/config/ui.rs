@@ -48,11 +48,44 @@ pub struct UIConfig {
 
     #[field_names(desc = "Set the line height for list items")]
     list_line_height: usize,
+
+    #[field_names(
+        desc = "Reveal the active file in the file explorer when switching to it"
+    )]
+    pub file_explorer_follow: bool,
+
+    #[field_names(desc = "Open the files created in the file explorer")]
+    pub file_explorer_open_created: bool,
+
+    #[field_names(
+        desc = "Scale the UI around the editors, like the panels, the palette, the tabs and the status bar, e.g. to 1.5 when sharing the screen"
+    )]
+    scale: f64,
 }
 
 impl UIConfig {
+    pub const MIN_SCALE: f64 = 0.5;
+    pub const MAX_SCALE: f64 = 3.0;
+
+    /// How much the UI is scaled, on top of the scale of the display
+    pub fn scale(&self) -> f64 {
+        Self::clamp_scale(self.scale)
+    }
+
+    /// `scale` within the scales the UI can have, rounded to a tenth
+    pub fn clamp_scale(scale: f64) -> f64 {
+        if !scale.is_finite() || scale <= 0.0 {
+            return 1.0;
+        }
+        (scale.max(Self::MIN_SCALE).min(Self::MAX_SCALE) * 10.0).round() / 10.0
+    }
+
+    fn scaled(&self, size: usize) -> usize {
+        (size as f64 * self.scale()).round() as usize
+    }
+
     pub fn font_size(&self) -> usize {
-        self.font_size.max(6).min(32)
+        self.scaled(self.font_size.max(6).min(32))
     }
 
     pub fn font_family(&self) -> Vec<FamilyOwned> {
@@ -61,19 +94,45 @@ pub fn font_family(&self) -> Vec<FamilyOwned> {
 
     pub fn header_height(&self) -> usize {
         let font_size = self.font_size();
-        self.header_height.max(font_size)
+        self.scaled(self.header_height).max(font_size)
     }
 
     pub fn icon_size(&self) -> usize {
         if self.icon_size == 0 {
             self.font_size()
         } else {
-            self.icon_size.max(6).min(32)
+            self.scaled(self.icon_size.max(6).min(32))
         }
     }
 
     pub fn status_height(&self) -> usize {
         let font_size = self.font_size();
-        self.status_height.max(font_size)
+        self.scaled(self.status_height).max(font_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UIConfig;
+
+    #[test]
+    fn test_scale() {
+        let config = UIConfig {
+            font_size: 13,
+            header_height: 36,
+            status_height: 25,
+            scale: 1.5,
+            ..Default::default()
+        };
+        assert_eq!(config.font_size(), 20);
+        assert_eq!(config.icon_size(), 20);
+        assert_eq!(config.header_height(), 54);
+        assert_eq!(config.status_height(), 38);
+
+        assert_eq!(UIConfig::clamp_scale(1.24), 1.2);
+        assert_eq!(UIConfig::clamp_scale(0.1), UIConfig::MIN_SCALE);
+        assert_eq!(UIConfig::clamp_scale(10.0), UIConfig::MAX_SCALE);
+        assert_eq!(UIConfig::clamp_scale(0.0), 1.0);
+        assert_eq!(UIConfig::clamp_scale(f64::NAN), 1.0);
     }
 }
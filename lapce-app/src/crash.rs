@@ -0,0 +1,321 @@
+//! Keeping the unsaved work when Lapce panics, and telling about the panic
+//! the next time it's started.
+//!
+//! The text of each document with unsaved changes is kept here as it's
+//! edited, which costs little as the text is shared with the document. The
+//! panic hook writes those texts to the backups folder, and the panic with its
+//! backtrace to the crash log, without touching the state of the UI, which can
+//! be halfway through an update. The next start opens the backups as unsaved
+//! changes, unless their file changed on disk since, and points at the log.
+//!
+//! Several instances of Lapce can run at once, so each one keeps its backups
+//! and its log apart, by its process id.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard, PoisonError, TryLockError},
+    time::{Duration, SystemTime},
+};
+
+use lapce_core::{directory::Directory, meta::VERSION};
+use lapce_rpc::buffer::BufferId;
+use lapce_xi_rope::Rope;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::job;
+
+/// The start and the end of the name of the file in the config directory the
+/// panic is written to, around the process id
+const CRASH_LOG: (&str, &str) = ("crash-", ".log");
+
+/// What the crash log is renamed to once it's been reported, so that it can
+/// still be read
+const LAST_CRASH_LOG: &str = "last-crash.log";
+
+/// The file in the backups folder that lists the backups
+const BACKUPS_INDEX: &str = "backups.json";
+
+/// The documents with unsaved changes, by their buffer
+static UNSAVED: Lazy<Mutex<HashMap<BufferId, Unsaved>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Clone)]
+struct Unsaved {
+    path: Option<PathBuf>,
+    text: Rope,
+}
+
+/// A document backed up as Lapce panicked
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Backup {
+    /// The file of the document, or `None` if it's untitled
+    path: Option<PathBuf>,
+    /// The file in the backups folder the text is in
+    file: String,
+    /// When the file of the document was last changed on disk as it was
+    /// backed up
+    #[serde(default)]
+    modified: Option<SystemTime>,
+}
+
+/// What's left of the last run of Lapce when it panicked
+pub struct CrashReport {
+    pub log: PathBuf,
+    /// The panic and its backtrace, for a bug report
+    pub details: String,
+    /// The text of the documents with unsaved changes, and their files
+    pub backups: Vec<(Option<PathBuf>, Rope)>,
+    /// The files whose backups are left out, as they changed on disk since
+    pub changed: Vec<PathBuf>,
+}
+
+/// The unsaved documents, even after a thread panicked while it had them
+/// locked, as what it was doing to them is done by then.
+fn unsaved() -> MutexGuard<'static, HashMap<BufferId, Unsaved>> {
+    UNSAVED.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Keep `text` as the one of the document of `buffer_id` to back up, unless
+/// it's `pristine`.
+pub fn track_unsaved(
+    buffer_id: BufferId,
+    path: Option<&Path>,
+    text: &Rope,
+    pristine: bool,
+) {
+    let mut unsaved = unsaved();
+    if pristine {
+        unsaved.remove(&buffer_id);
+    } else {
+        unsaved.insert(
+            buffer_id,
+            Unsaved {
+                path: path.map(Path::to_path_buf),
+                text: text.clone(),
+            },
+        );
+    }
+}
+
+/// Stop backing up the document of `buffer_id`, as it's closed.
+pub fn forget_unsaved(buffer_id: BufferId) {
+    unsaved().remove(&buffer_id);
+}
+
+/// Have a panic back up the unsaved documents and write the crash log before
+/// it's reported the usual way.
+pub fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let thread = std::thread::current();
+        let details = format!(
+            "Lapce {VERSION}, thread '{}' {info}\n\n{}\n",
+            thread.name().unwrap_or("unnamed"),
+            std::backtrace::Backtrace::force_capture(),
+        );
+        // A panic of another thread only ends what it was doing, while Lapce
+        // keeps running
+        if job::is_ui_thread() {
+            write_crash(&details);
+        }
+        default_hook(info);
+    }));
+}
+
+/// Write the backups and the crash log. A panic here would abort before the
+/// log is written, so nothing is unwrapped.
+fn write_crash(details: &str) {
+    let unsaved = locked_unsaved()
+        .map(|unsaved| unsaved.values().cloned().collect::<Vec<_>>())
+        .unwrap_or_default();
+    if !unsaved.is_empty() {
+        if let Some(dir) = backups_dir(std::process::id()) {
+            let _ = write_backups(&dir, &unsaved);
+        }
+    }
+    if let Some(dir) = Directory::config_directory() {
+        let _ = std::fs::write(dir.join(crash_log(std::process::id())), details);
+    }
+}
+
+/// Remove the backups and the crash log of this instance, as it's closed
+/// without a panic. Those of the other instances are left alone.
+pub fn clear_crash() {
+    unsaved().clear();
+    if let Some(dir) = backups_dir(std::process::id()) {
+        let _ = std::fs::remove_dir_all(dir);
+    }
+    if let Some(dir) = Directory::config_directory() {
+        let _ = std::fs::remove_file(dir.join(crash_log(std::process::id())));
+    }
+}
+
+/// The name of the crash log of the instance with the process id `id`
+fn crash_log(id: u32) -> String {
+    format!("{}{id}{}", CRASH_LOG.0, CRASH_LOG.1)
+}
+
+/// The process id of the instance that wrote the crash log `name`
+fn crash_log_id(name: &str) -> Option<u32> {
+    name.strip_prefix(CRASH_LOG.0)?
+        .strip_suffix(CRASH_LOG.1)?
+        .parse()
+        .ok()
+}
+
+/// The folder the backups of the instance with the process id `id` go to
+fn backups_dir(id: u32) -> Option<PathBuf> {
+    Directory::backups_directory().map(|dir| dir.join(id.to_string()))
+}
+
+/// The unsaved documents, unless they stay locked, which they do when the
+/// panic was while they were locked on the same thread.
+fn locked_unsaved() -> Option<MutexGuard<'static, HashMap<BufferId, Unsaved>>> {
+    for _ in 0..100 {
+        match UNSAVED.try_lock() {
+            Ok(unsaved) => return Some(unsaved),
+            Err(TryLockError::Poisoned(err)) => return Some(err.into_inner()),
+            Err(TryLockError::WouldBlock) => {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        }
+    }
+    None
+}
+
+fn write_backups(dir: &Path, unsaved: &[Unsaved]) -> anyhow::Result<()> {
+    // The backups of an earlier panic that weren't restored are replaced
+    let _ = std::fs::remove_dir_all(dir);
+    std::fs::create_dir_all(dir)?;
+    let mut backups = Vec::new();
+    for (i, unsaved) in unsaved.iter().enumerate() {
+        let file = format!("{i}.txt");
+        std::fs::write(dir.join(&file), unsaved.text.to_string())?;
+        backups.push(Backup {
+            path: unsaved.path.clone(),
+            file,
+            modified: unsaved.path.as_deref().and_then(modified),
+        });
+    }
+    std::fs::write(dir.join(BACKUPS_INDEX), serde_json::to_string(&backups)?)?;
+    Ok(())
+}
+
+/// What an instance left when it panicked, if one did. It's only reported
+/// once: the log is renamed and the backups are removed, so that another
+/// instance starting at the same time doesn't report it too.
+pub fn take_crash_report() -> Option<CrashReport> {
+    let dir = Directory::config_directory()?;
+    let (log, id) = std::fs::read_dir(&dir).ok()?.flatten().find_map(|entry| {
+        let id = crash_log_id(entry.file_name().to_str()?)?;
+        Some((entry.path(), id))
+    })?;
+    let last_log = dir.join(LAST_CRASH_LOG);
+    std::fs::rename(&log, &last_log).ok()?;
+    let details = std::fs::read_to_string(&last_log).ok()?;
+    let (backups, changed) = backups_dir(id)
+        .map(|dir| take_backups(&dir))
+        .unwrap_or_default();
+    Some(CrashReport {
+        log: last_log,
+        details,
+        backups,
+        changed,
+    })
+}
+
+/// The backups in `dir`, and the files left out as they changed on disk since
+/// they were backed up.
+fn take_backups(dir: &Path) -> (Vec<(Option<PathBuf>, Rope)>, Vec<PathBuf>) {
+    let backups = std::fs::read_to_string(dir.join(BACKUPS_INDEX))
+        .ok()
+        .and_then(|index| serde_json::from_str::<Vec<Backup>>(&index).ok())
+        .unwrap_or_default();
+    let mut changed = Vec::new();
+    let backups = backups
+        .into_iter()
+        .filter_map(|backup| {
+            if let Some(path) = backup.path.as_ref() {
+                if modified(path) != backup.modified {
+                    changed.push(path.clone());
+                    return None;
+                }
+            }
+            let text = std::fs::read_to_string(dir.join(&backup.file)).ok()?;
+            Some((backup.path, Rope::from(text)))
+        })
+        .collect();
+    let _ = std::fs::remove_dir_all(dir);
+    (backups, changed)
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use lapce_xi_rope::Rope;
+
+    use super::{crash_log, crash_log_id, take_backups, write_backups, Unsaved};
+
+    #[test]
+    fn test_crash_log_id() {
+        assert_eq!(crash_log_id(&crash_log(1234)), Some(1234));
+        assert_eq!(crash_log_id("crash.log"), None);
+        assert_eq!(crash_log_id("last-crash.log"), None);
+    }
+
+    #[test]
+    fn test_backups() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().join("backups");
+        let unsaved = vec![
+            Unsaved {
+                path: Some(PathBuf::from("/p/a.rs")),
+                text: Rope::from("fn main() {}\n"),
+            },
+            Unsaved {
+                path: None,
+                text: Rope::from("notes"),
+            },
+        ];
+        write_backups(&dir, &unsaved).unwrap();
+        let (backups, changed) = take_backups(&dir);
+        let backups = backups
+            .into_iter()
+            .map(|(path, text)| (path, text.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            backups,
+            vec![
+                (Some(PathBuf::from("/p/a.rs")), "fn main() {}\n".to_string()),
+                (None, "notes".to_string()),
+            ]
+        );
+        assert!(changed.is_empty());
+        assert!(!dir.exists());
+        assert!(take_backups(&dir).0.is_empty());
+    }
+
+    #[test]
+    fn test_backup_of_changed_file() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().join("backups");
+        let path = temp.path().join("changed.rs");
+        let unsaved = vec![Unsaved {
+            path: Some(path.clone()),
+            text: Rope::from("fn main() {}\n"),
+        }];
+        write_backups(&dir, &unsaved).unwrap();
+        // Written by something else after the panic
+        std::fs::write(&path, "").unwrap();
+        let (backups, changed) = take_backups(&dir);
+        assert!(backups.is_empty());
+        assert_eq!(changed, vec![path]);
+    }
+}
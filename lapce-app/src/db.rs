@@ -1,32 +1,62 @@
 use std::{
+    collections::HashMap,
     path::{Path, PathBuf},
     rc::Rc,
     sync::Arc,
+    time::Duration,
 };
 
 use anyhow::{anyhow, Result};
-use crossbeam_channel::{unbounded, Sender};
+use crossbeam_channel::{bounded, unbounded, Sender};
 use floem::peniko::kurbo::Vec2;
-use lapce_core::directory::Directory;
+use lapce_core::{
+    directory::Directory, language::LapceLanguage, path::renamed_path,
+};
 use lapce_rpc::plugin::VoltID;
+use tracing::warn;
 
 use crate::{
     app::{AppData, AppInfo},
     doc::DocInfo,
-    panel::{data::PanelOrder, kind::PanelKind, position::PanelPosition},
+    find::history::SearchHistory,
+    job::assert_off_ui_thread,
+    palette::command_history::CommandHistory,
+    panel::{
+        data::{default_panel_order, PanelOrder},
+        kind::PanelKind,
+        position::PanelPosition,
+    },
+    trust::{trust_path, WorkspaceTrust},
     window::{WindowData, WindowInfo},
     window_tab::WindowTabData,
     workspace::{LapceWorkspace, WorkspaceInfo},
 };
 
+/// The most files of a workspace whose position is remembered
+const MAX_DOC_INFOS: usize = 500;
+
+/// How long the saves are waited for to be written, like as Lapce exits
+pub const SAVE_TIMEOUT: Duration = Duration::from_secs(3);
+
 pub enum SaveEvent {
     App(AppInfo),
     Workspace(LapceWorkspace, WorkspaceInfo),
     RecentWorkspace(LapceWorkspace),
-    Doc(DocInfo),
+    Doc(LapceWorkspace, DocInfo),
+    /// The files of a workspace under the first path were renamed to be under
+    /// the second
+    DocsRenamed(LapceWorkspace, PathBuf, PathBuf),
+    /// The language the user chose for a file of a workspace, or `None` to
+    /// have it detected again
+    DocLanguage(LapceWorkspace, PathBuf, Option<LapceLanguage>),
     DisabledVolts(Vec<VoltID>),
     WorkspaceDisabledVolts(Arc<LapceWorkspace>, Vec<VoltID>),
     PanelOrder(PanelOrder),
+    CommandHistory(CommandHistory),
+    SearchHistory(SearchHistory),
+    Window(WindowInfo),
+    /// Sent back once the events before it are written
+    Flushed(Sender<()>),
 }
 
 #[derive(Clone)]
@@ -36,17 +66,20 @@ pub struct LapceDb {
 }
 
 impl LapceDb {
-    pub fn new() -> Result<Self> {
-        let path = Directory::config_directory()
-            .ok_or_else(|| anyhow!("can't get config directory"))?
-            .join("lapce.db");
+    /// Open the database in the config directory. When it can't be opened,
+    /// nothing is saved, and what's saved before isn't restored, so that Lapce
+    /// runs all the same.
+    pub fn new() -> Self {
         let (save_tx, save_rx) = unbounded();
 
-        let sled_db = sled::Config::default()
-            .path(path)
-            .flush_every_ms(None)
-            .open()
-            .ok();
+        let sled_db = Directory::config_directory().and_then(|dir| {
+            sled::Config::default()
+                .path(dir.join("lapce.db"))
+                .flush_every_ms(None)
+                .open()
+                .map_err(|err| warn!("Couldn't open the database: {err}"))
+                .ok()
+        });
 
         let db = Self { save_tx, sled_db };
         let local_db = db.clone();
@@ -63,8 +96,17 @@ pub fn new() -> Result<Self> {
                     SaveEvent::RecentWorkspace(workspace) => {
                         let _ = local_db.insert_recent_workspace(workspace);
                     }
-                    SaveEvent::Doc(info) => {
-                        let _ = local_db.insert_doc(&info);
+                    SaveEvent::Doc(workspace, info) => {
+                        let _ = local_db.insert_doc(&workspace, info);
+                    }
+                    SaveEvent::DocsRenamed(workspace, from, to) => {
+                        let _ = local_db.insert_renamed_docs(&workspace, &from, &to);
+                        let _ = local_db
+                            .insert_renamed_doc_languages(&workspace, &from, &to);
+                    }
+                    SaveEvent::DocLanguage(workspace, path, language) => {
+                        let _ =
+                            local_db.insert_doc_language(&workspace, path, language);
                     }
                     SaveEvent::DisabledVolts(volts) => {
                         let _ = local_db.insert_disabled_volts(volts);
@@ -76,10 +118,22 @@ pub fn new() -> Result<Self> {
                     SaveEvent::PanelOrder(order) => {
                         let _ = local_db.insert_panel_orders(&order);
                     }
+                    SaveEvent::CommandHistory(history) => {
+                        let _ = local_db.insert_command_history(&history);
+                    }
+                    SaveEvent::SearchHistory(history) => {
+                        let _ = local_db.insert_search_history(&history);
+                    }
+                    SaveEvent::Window(info) => {
+                        let _ = local_db.insert_window_info(info);
+                    }
+                    SaveEvent::Flushed(tx) => {
+                        let _ = tx.send(());
+                    }
                 }
             }
         });
-        Ok(db)
+        db
     }
 
     fn get_db(&self) -> Result<&sled::Db> {
@@ -116,7 +170,7 @@ pub fn insert_disabled_volts(&self, volts: Vec<VoltID>) -> Result<()> {
         let sled_db = self.get_db()?;
         let volts = serde_json::to_string(&volts)?;
         sled_db.insert(b"disabled_volts", volts.as_str())?;
-        sled_db.flush()?;
+        flush(sled_db)?;
         Ok(())
     }
 
@@ -128,7 +182,7 @@ pub fn insert_workspace_disabled_volts(
         let sled_db = self.get_db()?;
         let volts = serde_json::to_string(&volts)?;
         sled_db.insert(format!("disabled_volts:{workspace}"), volts.as_str())?;
-        sled_db.flush()?;
+        flush(sled_db)?;
         Ok(())
     }
 
@@ -145,6 +199,38 @@ pub fn get_workspace_disabled_volts(
         Ok(volts)
     }
 
+    /// Whether `workspace` was trusted or not, if it was decided.
+    pub fn get_workspace_trust(
+        &self,
+        workspace: &LapceWorkspace,
+    ) -> Result<WorkspaceTrust> {
+        let sled_db = self.get_db()?;
+        let key = workspace_trust_key(workspace)
+            .ok_or_else(|| anyhow!("workspace has no trust"))?;
+        let trust = sled_db
+            .get(key)?
+            .ok_or_else(|| anyhow!("can't find workspace trust"))?;
+        let trust = std::str::from_utf8(&trust)?;
+        let trust: WorkspaceTrust = serde_json::from_str(trust)?;
+        Ok(trust)
+    }
+
+    /// Keep whether `workspace` is trusted, right away, as the workspace is
+    /// opened again with it.
+    pub fn insert_workspace_trust(
+        &self,
+        workspace: &LapceWorkspace,
+        trust: WorkspaceTrust,
+    ) -> Result<()> {
+        let sled_db = self.get_db()?;
+        let key = workspace_trust_key(workspace)
+            .ok_or_else(|| anyhow!("workspace has no trust"))?;
+        let trust = serde_json::to_string(&trust)?;
+        sled_db.insert(key, trust.as_str())?;
+        flush(sled_db)?;
+        Ok(())
+    }
+
     pub fn recent_workspaces(&self) -> Result<Vec<LapceWorkspace>> {
         let sled_db = self.get_db()?;
         let workspaces = sled_db
@@ -164,12 +250,73 @@ pub fn update_recent_workspace(&self, workspace: &LapceWorkspace) -> Result<()>
         Ok(())
     }
 
-    fn insert_doc(&self, info: &DocInfo) -> Result<()> {
-        let key = format!("{}:{}", info.workspace, info.path.to_str().unwrap_or(""));
-        let info = serde_json::to_string(info)?;
+    fn insert_doc(&self, workspace: &LapceWorkspace, info: DocInfo) -> Result<()> {
+        let mut docs = self.get_doc_infos(workspace).unwrap_or_default();
+        remember_doc(&mut docs, info);
+        let docs = serde_json::to_string(&docs)?;
+        let sled_db = self.get_db()?;
+        sled_db.insert(doc_infos_key(workspace).as_str(), docs.as_str())?;
+        flush(sled_db)?;
+        Ok(())
+    }
+
+    fn insert_renamed_docs(
+        &self,
+        workspace: &LapceWorkspace,
+        from: &Path,
+        to: &Path,
+    ) -> Result<()> {
+        let mut docs = self.get_doc_infos(workspace)?;
+        if !rename_docs(&mut docs, from, to) {
+            return Ok(());
+        }
+        let docs = serde_json::to_string(&docs)?;
+        let sled_db = self.get_db()?;
+        sled_db.insert(doc_infos_key(workspace).as_str(), docs.as_str())?;
+        flush(sled_db)?;
+        Ok(())
+    }
+
+    fn insert_doc_language(
+        &self,
+        workspace: &LapceWorkspace,
+        path: PathBuf,
+        language: Option<LapceLanguage>,
+    ) -> Result<()> {
+        let mut languages = self.get_doc_languages(workspace).unwrap_or_default();
+        match language {
+            Some(language) => {
+                languages.insert(path, language.as_ref().to_string());
+            }
+            None => {
+                languages.remove(&path);
+            }
+        }
+        self.write_doc_languages(workspace, &languages)
+    }
+
+    fn insert_renamed_doc_languages(
+        &self,
+        workspace: &LapceWorkspace,
+        from: &Path,
+        to: &Path,
+    ) -> Result<()> {
+        let mut languages = self.get_doc_languages(workspace)?;
+        if !rename_doc_languages(&mut languages, from, to) {
+            return Ok(());
+        }
+        self.write_doc_languages(workspace, &languages)
+    }
+
+    fn write_doc_languages(
+        &self,
+        workspace: &LapceWorkspace,
+        languages: &HashMap<PathBuf, String>,
+    ) -> Result<()> {
+        let languages = serde_json::to_string(languages)?;
         let sled_db = self.get_db()?;
-        sled_db.insert(key.as_str(), info.as_str())?;
-        sled_db.flush()?;
+        sled_db.insert(doc_languages_key(workspace).as_str(), languages.as_str())?;
+        flush(sled_db)?;
         Ok(())
     }
 
@@ -201,7 +348,7 @@ fn insert_recent_workspace(&self, workspace: LapceWorkspace) -> Result<()> {
         let workspaces = serde_json::to_string(&workspaces)?;
 
         sled_db.insert("recent_workspaces", workspaces.as_str())?;
-        sled_db.flush()?;
+        flush(sled_db)?;
 
         Ok(())
     }
@@ -213,10 +360,13 @@ pub fn get_workspace_info(
         let workspace = workspace.to_string();
         let sled_db = self.get_db()?;
         let info = sled_db
-            .get(workspace)?
+            .get(&workspace)?
             .ok_or_else(|| anyhow!("can't find workspace info"))?;
         let info = std::str::from_utf8(&info)?;
-        let info: WorkspaceInfo = serde_json::from_str(info)?;
+        let info: WorkspaceInfo = serde_json::from_str(info).map_err(|err| {
+            warn!("Ignoring the saved state of workspace {workspace}: {err}");
+            err
+        })?;
         Ok(info)
     }
 
@@ -240,11 +390,14 @@ fn insert_workspace(
         let workspace_info = serde_json::to_string(info)?;
         let sled_db = self.get_db()?;
         sled_db.insert(workspace.as_str(), workspace_info.as_str())?;
-        sled_db.flush()?;
+        flush(sled_db)?;
         Ok(())
     }
 
     pub fn save_app(&self, data: &AppData) -> Result<()> {
+        if data.wait {
+            return Ok(());
+        }
         let windows = data.windows.get_untracked();
         for (_, window) in &windows {
             let _ = self.save_window(window.clone());
@@ -269,14 +422,19 @@ pub fn insert_app_info(&self, info: AppInfo) -> Result<()> {
         let info = serde_json::to_string(&info)?;
         let sled_db = self.get_db()?;
         sled_db.insert("app", info.as_str())?;
-        sled_db.flush()?;
+        flush(sled_db)?;
         Ok(())
     }
 
-    pub fn insert_app(&self, data: AppData) -> Result<()> {
+    /// Save the session as Lapce exits, waiting for it to be written.
+    pub fn save_app_on_exit(&self, data: &AppData) {
+        if data.wait {
+            return;
+        }
         let windows = data.windows.get_untracked();
         for (_, window) in &windows {
-            let _ = self.insert_window(window.clone());
+            let _ = self.save_window(window.clone());
+            let _ = self.save_tx.send(SaveEvent::Window(window.info()));
         }
         let info = AppInfo {
             windows: windows
@@ -284,11 +442,17 @@ pub fn insert_app(&self, data: AppData) -> Result<()> {
                 .map(|(_, window_data)| window_data.info())
                 .collect(),
         };
-        let info = serde_json::to_string(&info)?;
-        let sled_db = self.get_db()?;
-        sled_db.insert("app", info.as_str())?;
-        sled_db.flush()?;
-        Ok(())
+        let _ = self.save_tx.send(SaveEvent::App(info));
+        self.wait_saved();
+    }
+
+    /// Wait for what was saved so far to be written by the thread of the
+    /// database, for at most [`SAVE_TIMEOUT`].
+    pub fn wait_saved(&self) {
+        let (tx, rx) = bounded(1);
+        if self.save_tx.send(SaveEvent::Flushed(tx)).is_ok() {
+            let _ = rx.recv_timeout(SAVE_TIMEOUT);
+        }
     }
 
     pub fn get_app(&self) -> Result<AppInfo> {
@@ -318,25 +482,11 @@ pub fn save_window(&self, data: WindowData) -> Result<()> {
         Ok(())
     }
 
-    pub fn insert_window(&self, data: WindowData) -> Result<()> {
-        for (_, window_tab) in data.window_tabs.get_untracked().into_iter() {
-            let _ = self.insert_window_tab(window_tab);
-        }
-        let info = data.info();
+    fn insert_window_info(&self, info: WindowInfo) -> Result<()> {
         let info = serde_json::to_string(&info)?;
         let sled_db = self.get_db()?;
         sled_db.insert("window", info.as_str())?;
-        sled_db.flush()?;
-        Ok(())
-    }
-
-    pub fn insert_window_tab(&self, data: Rc<WindowTabData>) -> Result<()> {
-        let workspace = (*data.workspace).clone();
-        let workspace_info = data.workspace_info();
-
-        self.insert_workspace(&workspace, &workspace_info)?;
-        // self.insert_unsaved_buffer(main_split)?;
-
+        flush(sled_db)?;
         Ok(())
     }
 
@@ -348,12 +498,17 @@ pub fn get_panel_orders(&self) -> Result<PanelOrder> {
         let panel_orders = std::str::from_utf8(&panel_orders)?;
         let mut panel_orders: PanelOrder = serde_json::from_str(panel_orders)?;
 
+        // Panels added since the order was saved go where they are by default
+        let default_order = default_panel_order();
         use strum::IntoEnumIterator;
         for kind in PanelKind::iter() {
             if kind.position(&panel_orders).is_none() {
-                let panels = panel_orders
-                    .entry(PanelPosition::LeftTop)
-                    .or_insert_with(im::Vector::new);
+                let position = kind
+                    .position(&default_order)
+                    .map(|(_, position)| position)
+                    .unwrap_or(PanelPosition::LeftTop);
+                let panels =
+                    panel_orders.entry(position).or_insert_with(im::Vector::new);
                 panels.push_back(kind);
             }
         }
@@ -369,7 +524,51 @@ fn insert_panel_orders(&self, order: &PanelOrder) -> Result<()> {
         let info = serde_json::to_string(order)?;
         let sled_db = self.get_db()?;
         sled_db.insert("panel_orders", info.as_str())?;
-        sled_db.flush()?;
+        flush(sled_db)?;
+        Ok(())
+    }
+
+    pub fn get_command_history(&self) -> Result<CommandHistory> {
+        let sled_db = self.get_db()?;
+        let history = sled_db
+            .get("command_history")?
+            .ok_or_else(|| anyhow!("can't find command history"))?;
+        let history = std::str::from_utf8(&history)?;
+        let history: CommandHistory = serde_json::from_str(history)?;
+        Ok(history)
+    }
+
+    pub fn save_command_history(&self, history: CommandHistory) {
+        let _ = self.save_tx.send(SaveEvent::CommandHistory(history));
+    }
+
+    fn insert_command_history(&self, history: &CommandHistory) -> Result<()> {
+        let history = serde_json::to_string(history)?;
+        let sled_db = self.get_db()?;
+        sled_db.insert("command_history", history.as_str())?;
+        flush(sled_db)?;
+        Ok(())
+    }
+
+    pub fn get_search_history(&self) -> Result<SearchHistory> {
+        let sled_db = self.get_db()?;
+        let history = sled_db
+            .get("search_history")?
+            .ok_or_else(|| anyhow!("can't find search history"))?;
+        let history = std::str::from_utf8(&history)?;
+        let history: SearchHistory = serde_json::from_str(history)?;
+        Ok(history)
+    }
+
+    pub fn save_search_history(&self, history: SearchHistory) {
+        let _ = self.save_tx.send(SaveEvent::SearchHistory(history));
+    }
+
+    fn insert_search_history(&self, history: &SearchHistory) -> Result<()> {
+        let history = serde_json::to_string(history)?;
+        let sled_db = self.get_db()?;
+        sled_db.insert("search_history", history.as_str())?;
+        flush(sled_db)?;
         Ok(())
     }
 
@@ -377,16 +576,76 @@ pub fn save_doc_position(
         &self,
         workspace: &LapceWorkspace,
         path: PathBuf,
-        cursor_offset: usize,
+        (cursor_line, cursor_column): (usize, usize),
         scroll_offset: Vec2,
     ) {
         let info = DocInfo {
-            workspace: workspace.clone(),
             path,
             scroll_offset: (scroll_offset.x, scroll_offset.y),
-            cursor_offset,
+            cursor_line,
+            cursor_column,
         };
-        let _ = self.save_tx.send(SaveEvent::Doc(info));
+        let _ = self.save_tx.send(SaveEvent::Doc(workspace.clone(), info));
+    }
+
+    /// Keep the positions in the files of `workspace` under `from` for them
+    /// being under `to` now.
+    pub fn rename_docs(&self, workspace: &LapceWorkspace, from: &Path, to: &Path) {
+        let _ = self.save_tx.send(SaveEvent::DocsRenamed(
+            workspace.clone(),
+            from.to_path_buf(),
+            to.to_path_buf(),
+        ));
+    }
+
+    /// The positions in the files of `workspace` when they were last closed,
+    /// the latest last
+    fn get_doc_infos(&self, workspace: &LapceWorkspace) -> Result<Vec<DocInfo>> {
+        let sled_db = self.get_db()?;
+        let docs = sled_db
+            .get(doc_infos_key(workspace).as_str())?
+            .ok_or_else(|| anyhow!("can't find doc infos"))?;
+        let docs = std::str::from_utf8(&docs)?;
+        let docs: Vec<DocInfo> = serde_json::from_str(docs)?;
+        Ok(docs)
+    }
+
+    /// Remember `language` as the one of the file at `path`, which it's
+    /// opened in from then on whatever its path says, or forget the one chosen
+    /// before if `None`.
+    pub fn save_doc_language(
+        &self,
+        workspace: &LapceWorkspace,
+        path: PathBuf,
+        language: Option<LapceLanguage>,
+    ) {
+        let _ = self.save_tx.send(SaveEvent::DocLanguage(
+            workspace.clone(),
+            path,
+            language,
+        ));
+    }
+
+    fn get_doc_languages(
+        &self,
+        workspace: &LapceWorkspace,
+    ) -> Result<HashMap<PathBuf, String>> {
+        let sled_db = self.get_db()?;
+        let languages = sled_db
+            .get(doc_languages_key(workspace).as_str())?
+            .ok_or_else(|| anyhow!("can't find doc languages"))?;
+        let languages = std::str::from_utf8(&languages)?;
+        Ok(serde_json::from_str(languages)?)
+    }
+
+    /// The language the user chose for the file at `path`, if any
+    pub fn get_doc_language(
+        &self,
+        workspace: &LapceWorkspace,
+        path: &Path,
+    ) -> Option<LapceLanguage> {
+        let languages = self.get_doc_languages(workspace).ok()?;
+        LapceLanguage::from_name(languages.get(path)?)
     }
 
     pub fn get_doc_info(
@@ -394,13 +653,159 @@ pub fn get_doc_info(
         workspace: &LapceWorkspace,
         path: &Path,
     ) -> Result<DocInfo> {
-        let key = format!("{}:{}", workspace, path.to_str().unwrap_or(""));
-        let sled_db = self.get_db()?;
-        let info = sled_db
-            .get(key.as_str())?
-            .ok_or_else(|| anyhow!("can't find workspace info"))?;
-        let info = std::str::from_utf8(&info)?;
-        let info: DocInfo = serde_json::from_str(info)?;
-        Ok(info)
+        self.get_doc_infos(workspace)?
+            .into_iter()
+            .find(|info| info.path == path)
+            .ok_or_else(|| anyhow!("can't find doc info"))
+    }
+}
+
+/// Write what was inserted into `sled_db` to the disk.
+fn flush(sled_db: &sled::Db) -> Result<()> {
+    assert_off_ui_thread("Writing the database");
+    sled_db.flush()?;
+    Ok(())
+}
+
+fn workspace_trust_key(workspace: &LapceWorkspace) -> Option<String> {
+    let path = trust_path(workspace)?;
+    Some(format!("workspace_trust:{}", path.display()))
+}
+
+fn doc_infos_key(workspace: &LapceWorkspace) -> String {
+    format!("{workspace}:doc_infos")
+}
+
+fn doc_languages_key(workspace: &LapceWorkspace) -> String {
+    format!("{workspace}:doc_languages")
+}
+
+/// Put `info` last in `docs`, in place of the older one of its file, and
+/// forget the files closed longest ago beyond [`MAX_DOC_INFOS`].
+fn remember_doc(docs: &mut Vec<DocInfo>, info: DocInfo) {
+    docs.retain(|doc| doc.path != info.path);
+    docs.push(info);
+    if docs.len() > MAX_DOC_INFOS {
+        docs.drain(..docs.len() - MAX_DOC_INFOS);
+    }
+}
+
+/// Have the files of `docs` under `from` be under `to`, in place of the ones
+/// that were there. Whether any of them were.
+fn rename_docs(docs: &mut Vec<DocInfo>, from: &Path, to: &Path) -> bool {
+    if !docs.iter().any(|doc| doc.path.starts_with(from)) {
+        return false;
+    }
+    docs.retain(|doc| !doc.path.starts_with(to));
+    for doc in docs.iter_mut() {
+        if let Some(path) = renamed_path(&doc.path, from, to) {
+            doc.path = path;
+        }
+    }
+    true
+}
+
+/// Have the languages of the files under `from` be the ones of the files under
+/// `to`. Whether there were any.
+fn rename_doc_languages(
+    languages: &mut HashMap<PathBuf, String>,
+    from: &Path,
+    to: &Path,
+) -> bool {
+    if !languages.keys().any(|path| path.starts_with(from)) {
+        return false;
+    }
+    languages.retain(|path, _| !path.starts_with(to));
+    *languages = std::mem::take(languages)
+        .into_iter()
+        .map(|(path, language)| {
+            (renamed_path(&path, from, to).unwrap_or(path), language)
+        })
+        .collect();
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        path::{Path, PathBuf},
+    };
+
+    use super::{remember_doc, rename_doc_languages, rename_docs, MAX_DOC_INFOS};
+    use crate::doc::DocInfo;
+
+    fn info(path: &str, cursor_line: usize) -> DocInfo {
+        DocInfo {
+            path: PathBuf::from(path),
+            scroll_offset: (0.0, 0.0),
+            cursor_line,
+            cursor_column: 0,
+        }
+    }
+
+    #[test]
+    fn test_remember_doc() {
+        let mut docs = Vec::new();
+        remember_doc(&mut docs, info("a", 1));
+        remember_doc(&mut docs, info("b", 2));
+        remember_doc(&mut docs, info("a", 3));
+        assert_eq!(docs, vec![info("b", 2), info("a", 3)]);
+
+        for i in 0..MAX_DOC_INFOS - 1 {
+            remember_doc(&mut docs, info(&i.to_string(), i));
+        }
+        assert_eq!(docs.len(), MAX_DOC_INFOS);
+        assert!(!docs.iter().any(|doc| doc.path == PathBuf::from("b")));
+        assert_eq!(docs[0], info("a", 3));
+    }
+
+    #[test]
+    fn test_rename_docs() {
+        let mut docs = vec![info("/p/a.rs", 1), info("/p/src/b.rs", 2)];
+        assert!(!rename_docs(
+            &mut docs,
+            Path::new("/p/c.rs"),
+            Path::new("/p/d.rs")
+        ));
+
+        assert!(rename_docs(
+            &mut docs,
+            Path::new("/p/src"),
+            Path::new("/p/lib")
+        ));
+        assert_eq!(docs, vec![info("/p/a.rs", 1), info("/p/lib/b.rs", 2)]);
+
+        assert!(rename_docs(
+            &mut docs,
+            Path::new("/p/lib/b.rs"),
+            Path::new("/p/a.rs")
+        ));
+        assert_eq!(docs, vec![info("/p/a.rs", 2)]);
+    }
+
+    #[test]
+    fn test_rename_doc_languages() {
+        let mut languages = HashMap::from([
+            (PathBuf::from("/p/a.conf"), "Toml".to_string()),
+            (PathBuf::from("/p/src/b"), "Bash".to_string()),
+        ]);
+        assert!(!rename_doc_languages(
+            &mut languages,
+            Path::new("/p/c"),
+            Path::new("/p/d")
+        ));
+        assert!(rename_doc_languages(
+            &mut languages,
+            Path::new("/p/src"),
+            Path::new("/p/bin")
+        ));
+        assert_eq!(
+            languages,
+            HashMap::from([
+                (PathBuf::from("/p/a.conf"), "Toml".to_string()),
+                (PathBuf::from("/p/bin/b"), "Bash".to_string()),
+            ])
+        );
     }
 }
@@ -0,0 +1,178 @@
+use std::{
+    cell::{Cell, RefCell},
+    hash::Hash,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use floem::action::{exec_after, TimerToken};
+use indexmap::IndexMap;
+
+/// How long the edits have to pause before the work that can wait for them,
+/// like diffing against `HEAD`, is done.
+pub const SETTLE_DELAY: Duration = Duration::from_millis(200);
+
+/// Work of kinds `K`, which is merged by kind and due some time after it was
+/// last queued, so that a burst of edits leads to the work being done once.
+pub struct Coalescer<K, W> {
+    /// The work pending by kind, with when it's due
+    pending: IndexMap<K, (Instant, W)>,
+}
+
+impl<K: Copy + Eq + Hash, W> Default for Coalescer<K, W> {
+    fn default() -> Self {
+        Self {
+            pending: IndexMap::new(),
+        }
+    }
+}
+
+impl<K: Copy + Eq + Hash, W> Coalescer<K, W> {
+    /// Queue `work` of `kind`, due `delay` after `now`. It replaces the work
+    /// of `kind` already queued, and pushes it back.
+    pub fn push(&mut self, kind: K, work: W, now: Instant, delay: Duration) {
+        self.pending.insert(kind, (now + delay, work));
+    }
+
+    /// Take the work that's due at `now`, in the order its kind was first
+    /// queued.
+    pub fn take_due(&mut self, now: Instant) -> Vec<(K, W)> {
+        let due = self
+            .pending
+            .iter()
+            .filter(|(_, (at, _))| *at <= now)
+            .map(|(kind, _)| *kind)
+            .collect::<Vec<_>>();
+        due.into_iter()
+            .filter_map(|kind| {
+                let (_, work) = self.pending.shift_remove(&kind)?;
+                Some((kind, work))
+            })
+            .collect()
+    }
+
+    /// When the next pending work is due.
+    pub fn next_due(&self) -> Option<Instant> {
+        self.pending.values().map(|(at, _)| *at).min()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+/// Runs the work queued in a [`Coalescer`] on the UI thread once it's due,
+/// with a single timer for all of it.
+#[derive(Clone)]
+pub struct Debouncer<K> {
+    queue: Rc<RefCell<Coalescer<K, Box<dyn FnOnce()>>>>,
+    timer: Rc<Cell<TimerToken>>,
+}
+
+impl<K: Copy + Eq + Hash + 'static> Default for Debouncer<K> {
+    fn default() -> Self {
+        Self {
+            queue: Rc::new(RefCell::new(Coalescer::default())),
+            timer: Rc::new(Cell::new(TimerToken::INVALID)),
+        }
+    }
+}
+
+impl<K: Copy + Eq + Hash + 'static> Debouncer<K> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `work` once `delay` has passed without more work of `kind` being
+    /// scheduled, in place of the work of `kind` scheduled before. With no
+    /// delay, it runs on the next turn of the event loop, along with the rest
+    /// of the work scheduled during this one.
+    pub fn schedule(&self, kind: K, delay: Duration, work: impl FnOnce() + 'static) {
+        self.queue
+            .borrow_mut()
+            .push(kind, Box::new(work), Instant::now(), delay);
+        self.arm();
+    }
+
+    /// Set the timer for the next work that's due, replacing the last one.
+    fn arm(&self) {
+        let Some(due) = self.queue.borrow().next_due() else {
+            return;
+        };
+        let debouncer = self.clone();
+        let token = exec_after(
+            due.saturating_duration_since(Instant::now()),
+            move |token| {
+                if debouncer.timer.get() != token {
+                    return;
+                }
+                let due = debouncer.queue.borrow_mut().take_due(Instant::now());
+                for (_, work) in due {
+                    work();
+                }
+                debouncer.arm();
+            },
+        );
+        self.timer.set(token);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::Coalescer;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    enum Work {
+        Diff,
+        Update,
+    }
+
+    #[test]
+    fn test_coalesce_burst() {
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+        let delay = ms(200);
+        let mut coalescer = Coalescer::default();
+
+        // A key held down for a second, repeating every 30ms
+        for i in 0..34 {
+            let now = start + ms(30 * i);
+            assert!(coalescer.take_due(now).is_empty());
+            coalescer.push(Work::Diff, i, now, delay);
+        }
+        let last = start + ms(30 * 33);
+        assert_eq!(coalescer.next_due(), Some(last + delay));
+        assert!(coalescer.take_due(last + ms(199)).is_empty());
+
+        // Only the last of the burst is done
+        assert_eq!(coalescer.take_due(last + delay), vec![(Work::Diff, 33)]);
+        assert!(coalescer.is_empty());
+        assert_eq!(coalescer.next_due(), None);
+    }
+
+    #[test]
+    fn test_coalesce_kinds() {
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+        let mut coalescer = Coalescer::default();
+
+        // Work without a delay is batched until it's taken
+        coalescer.push(Work::Diff, "diff", start, ms(200));
+        coalescer.push(Work::Update, "update 1", start, Duration::ZERO);
+        coalescer.push(Work::Update, "update 2", start + ms(1), Duration::ZERO);
+        assert_eq!(coalescer.next_due(), Some(start + ms(1)));
+        assert_eq!(
+            coalescer.take_due(start + ms(1)),
+            vec![(Work::Update, "update 2")]
+        );
+
+        // The kinds are kept apart, and come in the order they were queued
+        coalescer.push(Work::Update, "update 3", start + ms(100), ms(50));
+        assert_eq!(
+            coalescer.take_due(start + ms(300)),
+            vec![(Work::Diff, "diff"), (Work::Update, "update 3")]
+        );
+    }
+}
@@ -14,7 +14,7 @@
 };
 use serde::{Deserialize, Serialize};
 
-const DEFAULT_RUN_TOML: &str = include_str!("../../defaults/run.toml");
+pub const DEFAULT_RUN_TOML: &str = include_str!("../../defaults/run.toml");
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum RunDebugMode {
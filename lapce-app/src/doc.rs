@@ -1,28 +1,36 @@
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
     collections::HashMap,
+    ops::{Range, RangeInclusive},
     path::PathBuf,
     rc::Rc,
     sync::{atomic, Arc},
 };
 
+#[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "openbsd"))]
+use clipboard::x11_clipboard::{Primary, X11ClipboardContext};
 use clipboard::{ClipboardContext, ClipboardProvider};
 use floem::{
-    cosmic_text::{Attrs, AttrsList, FamilyOwned, TextLayout},
+    cosmic_text::{Attrs, AttrsList, Style as FontStyle, TextLayout, Weight},
     ext_event::create_ext_action,
+    peniko::{kurbo::Vec2, Color},
     reactive::{ReadSignal, RwSignal, Scope},
 };
 use itertools::Itertools;
 use lapce_core::{
     buffer::{
-        diff::{rope_diff, DiffLines},
-        rope_text::RopeText,
+        diff::{rope_diff, shift_diff_lines, DiffLines, DiffOptions},
+        rope_text::{RopeText, RopeTextVal},
         Buffer, InvalLines,
     },
+    color_literal::{color_literals, ColorLiteral},
     command::EditCommand,
-    cursor::Cursor,
+    cursor::{Cursor, CursorMode},
     editor::{EditType, Editor},
+    indent::IndentStyle,
     language::LapceLanguage,
+    language_config::LanguageConfig,
+    mode::MotionMode,
     register::{Clipboard, Register},
     selection::{InsertDrift, Selection},
     style::line_styles,
@@ -32,6 +40,7 @@
     buffer::BufferId,
     plugin::PluginId,
     proxy::{ProxyResponse, ProxyRpcHandler},
+    source_control::BlameHunk,
     style::{LineStyle, LineStyles, Style},
 };
 use lapce_xi_rope::{
@@ -39,21 +48,67 @@
     Interval, Rope, RopeDelta, Transformer,
 };
 use lsp_types::{
-    CodeActionResponse, Diagnostic, DiagnosticSeverity, InlayHint, InlayHintLabel,
+    CodeActionResponse, Diagnostic, DiagnosticSeverity, FormattingOptions,
+    InlayHint, InlayHintLabel,
 };
 use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
 
-use self::phantom_text::{PhantomText, PhantomTextKind, PhantomTextLine};
+use self::{
+    ansi::AnsiText,
+    phantom_text::{PhantomText, PhantomTextKind, PhantomTextLine},
+    recent_edits::RecentEdits,
+    spell_check::SpellCheck,
+    syntax_worker::{SyntaxJob, SyntaxWorker},
+};
 use crate::{
     config::{color::LapceColor, LapceConfig},
-    editor::view_data::{LineExtraStyle, TextLayoutCache, TextLayoutLine},
-    find::{Find, FindProgress, FindResult},
+    crash,
+    debounce::{Debouncer, SETTLE_DELAY},
+    editor::{
+        minimap::{MinimapCache, MinimapLine},
+        view_data::{
+            LineExtraStyle, LineInval, RenderWhitespace, TextLayoutCache,
+            TextLayoutLine,
+        },
+    },
+    find::{Find, FindProgress, FindResult, MAX_FIND_COUNT},
     history::DocumentHistory,
-    workspace::LapceWorkspace,
+    id::EditorId,
+    perf::{self, Phase},
+    spell,
 };
 
+pub mod ansi;
+mod git_commit;
 pub mod phantom_text;
+mod recent_edits;
+mod spell_check;
+mod syntax_worker;
+
+/// The work on a document that's put off until its edits settle, and done
+/// once for a burst of them
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DocWork {
+    /// Diffing the buffer against `HEAD`
+    HeadDiff,
+    /// Blaming the buffer
+    Blame,
+    /// Requesting the semantic tokens and inlay hints
+    ProxyUpdate,
+    /// Requesting the symbols of the outline
+    Outline,
+    /// Checking the spelling of the edited lines
+    SpellCheck,
+}
+
+/// How many colors bracket pairs cycle through, as the `bracket.color1` and on
+/// syntax colors of the theme
+const BRACKET_COLORS: usize = 3;
+
+/// How many line invalidations a document remembers for views whose caches
+/// are behind, before they have to be cleared instead
+const MAX_LINE_INVALS: usize = 64;
 
 pub struct SystemClipboard {
     ctx: ClipboardContext,
@@ -83,9 +138,49 @@ fn put_string(&mut self, s: impl AsRef<str>) {
     }
 }
 
+/// The primary selection of X11, which holds the text selected last and is
+/// pasted with a middle click, apart from the clipboard. There's none on the
+/// other platforms, where it stays empty.
+pub struct PrimarySelection;
+
+#[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "openbsd"))]
+thread_local! {
+    /// Kept for the life of the app, as the selection we own is served from
+    /// its connection to the X server
+    static PRIMARY_SELECTION: RefCell<Option<X11ClipboardContext<Primary>>> =
+        RefCell::new(ClipboardProvider::new().ok());
+}
+
+#[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "openbsd"))]
+impl Clipboard for PrimarySelection {
+    fn get_string(&mut self) -> Option<String> {
+        PRIMARY_SELECTION.with(|ctx| ctx.borrow_mut().as_mut()?.get_contents().ok())
+    }
+
+    fn put_string(&mut self, s: impl AsRef<str>) {
+        PRIMARY_SELECTION.with(|ctx| {
+            if let Some(ctx) = ctx.borrow_mut().as_mut() {
+                let _ = ctx.set_contents(s.as_ref().to_string());
+            }
+        });
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "freebsd", target_os = "openbsd")))]
+impl Clipboard for PrimarySelection {
+    fn get_string(&mut self) -> Option<String> {
+        None
+    }
+
+    fn put_string(&mut self, _s: impl AsRef<str>) {}
+}
+
 #[derive(Clone, Debug)]
 pub struct DiagnosticData {
     pub expanded: RwSignal<bool>,
+    /// The document version the language server computed the diagnostics
+    /// for, if it said
+    pub version: RwSignal<Option<i32>>,
     pub diagnostics: RwSignal<im::Vector<EditorDiagnostic>>,
 }
 
@@ -95,12 +190,49 @@ pub struct EditorDiagnostic {
     pub diagnostic: Diagnostic,
 }
 
+/// The theme color of the squiggles and gutter markers of diagnostics with
+/// `severity`.
+pub fn diagnostic_color(severity: Option<DiagnosticSeverity>) -> &'static str {
+    match severity {
+        Some(DiagnosticSeverity::ERROR) => LapceColor::LAPCE_ERROR,
+        Some(DiagnosticSeverity::WARNING) | None => LapceColor::LAPCE_WARN,
+        Some(_) => LapceColor::ERROR_LENS_OTHER_FOREGROUND,
+    }
+}
+
+/// Whether the text highlighted as `scope` is written in words, which are
+/// spell checked: comments and plain strings.
+fn is_prose_scope(scope: &str) -> bool {
+    scope.starts_with("comment") || scope == "string"
+}
+
+/// Whether what's highlighted as `scope` is a name, which hex digits after a
+/// `#` can be too, like the id selectors of CSS.
+fn is_name_scope(scope: &str) -> bool {
+    [
+        "property",
+        "tag",
+        "attribute",
+        "type",
+        "variable",
+        "function",
+    ]
+    .iter()
+    .any(|name| scope.starts_with(name))
+}
+
 #[derive(Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct DocHistory {
     pub path: PathBuf,
     pub version: String,
 }
 
+impl DocHistory {
+    /// The version of a file as it's saved on disk, rather than one from
+    /// the source control
+    pub const SAVED: &'static str = "saved";
+}
+
 #[derive(Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum DocContent {
     /// A file at some location. This can be a remote path.
@@ -110,7 +242,14 @@ pub enum DocContent {
     /// A document of an old version in the source control
     History(DocHistory),
     /// A new file which doesn't exist in the file system
-    Scratch { id: BufferId, name: String },
+    Scratch {
+        id: BufferId,
+        name: String,
+        /// Whether it can't be edited until it's toggled, like what's read
+        /// from stdin
+        #[serde(default)]
+        read_only: bool,
+    },
 }
 
 impl DocContent {
@@ -127,7 +266,7 @@ pub fn read_only(&self) -> bool {
             DocContent::File(_) => false,
             DocContent::Local => false,
             DocContent::History(_) => true,
-            DocContent::Scratch { .. } => false,
+            DocContent::Scratch { read_only, .. } => *read_only,
         }
     }
 
@@ -141,12 +280,40 @@ pub fn path(&self) -> Option<&PathBuf> {
     }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// Where the cursor and the scroll were in a file when its editor was last
+/// closed, to go back there when the file is opened again
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct DocInfo {
-    pub workspace: LapceWorkspace,
     pub path: PathBuf,
     pub scroll_offset: (f64, f64),
-    pub cursor_offset: usize,
+    pub cursor_line: usize,
+    pub cursor_column: usize,
+}
+
+impl DocInfo {
+    /// The offset of the cursor in `buffer`, at the end of the file if it
+    /// has fewer lines now, or at the end of its line if that got shorter.
+    pub fn cursor_offset(&self, buffer: &Buffer) -> usize {
+        let last_line = buffer.last_line();
+        if self.cursor_line > last_line {
+            return buffer.len();
+        }
+        buffer.offset_of_line_col(self.cursor_line, self.cursor_column)
+    }
+
+    /// Whether the file still has the line the cursor was on, and so the
+    /// scroll position still makes sense
+    pub fn fits(&self, buffer: &Buffer) -> bool {
+        self.cursor_line <= buffer.last_line()
+    }
+}
+
+/// The cursor and scroll position an editor had on a document, so that it can go
+/// back to them after it has been switched to another document.
+#[derive(Clone)]
+pub struct EditorViewState {
+    pub cursor: Cursor,
+    pub scroll_offset: Vec2,
 }
 
 #[derive(Clone)]
@@ -162,10 +329,37 @@ pub struct Preedit {
 pub struct Document {
     pub scope: Scope,
     pub content: DocContent,
+    /// What tells the file of the document apart whatever name it's opened
+    /// under, from `MainSplitData::file_key`, worked out once when it's opened
+    pub file_key: Option<PathBuf>,
     pub buffer_id: BufferId,
     cache_rev: u64,
+    /// The line invalidations since the cache was last cleared, with the cache
+    /// revision each brought the document to
+    line_invals: Vec<(u64, LineInval)>,
+    /// The config id and language the buffer's language config was last
+    /// resolved for
+    language_config_key: Option<(u64, LapceLanguage)>,
+    /// The language the user chose for the document, over the detected one
+    pub language_override: Option<LapceLanguage>,
+    /// Whether the proxy was told the language of the file, as it's one the
+    /// user chose or associated with its path
+    language_chosen: bool,
     buffer: Buffer,
     syntax: Syntax,
+    /// The syntax edits made since the revision `syntax` was parsed at, or
+    /// `None` if the next parse has to start over.
+    syntax_edits: Option<Vec<SyntaxEdit>>,
+    /// The signal the document is stored in, which background syntax parses
+    /// hand their result back through. Documents without one parse inline.
+    signal: Option<RwSignal<Document>>,
+    syntax_worker: Option<Rc<SyntaxWorker>>,
+    /// Whether tree-sitter is turned off for the document, because its text is
+    /// over `editor.max-syntax-file-size`
+    syntax_disabled: bool,
+    /// The lines shown in the last editor to scroll, which background parses
+    /// highlight first
+    visible_lines: RefCell<Range<usize>>,
     line_styles: Rc<RefCell<LineStyles>>,
     /// Semantic highlighting information (which is provided by the LSP)
     semantic_styles: Option<Arc<Spans<Style>>>,
@@ -173,6 +367,9 @@ pub struct Document {
     pub inlay_hints: Option<Spans<InlayHint>>,
     /// The diagnostics for the document
     pub diagnostics: DiagnosticData,
+    /// The last edits, which diagnostics published for an older version are
+    /// shifted through
+    recent_edits: RecentEdits,
     /// Current completion lens text, if any.  
     /// This will be displayed even on views that are not focused.
     completion_lens: Option<String>,
@@ -182,14 +379,34 @@ pub struct Document {
     pub code_actions: im::HashMap<usize, Arc<(PluginId, CodeActionResponse)>>,
     /// Whether the buffer's content has been loaded/initialized into the buffer.
     loaded: bool,
+    /// Whether the file was deleted while the document was open, which keeps
+    /// its text to be saved again.
+    pub orphaned: bool,
+    /// The ANSI escape sequences left out of the text, when it's shown in the
+    /// colors they give, which the proxy's copy of the text keeps.
+    ansi: Option<AnsiText>,
+    /// The misspelled words, if the spelling is checked
+    spell: SpellCheck,
     /// Stores information about different versions of the document from source control.
     histories: RwSignal<im::HashMap<String, DocumentHistory>>,
     pub head_changes: RwSignal<im::Vector<DiffLines>>,
+    /// The git blame of the buffer, `None` until it has been retrieved for the
+    /// current content.
+    pub blame: RwSignal<Option<im::Vector<BlameHunk>>>,
+    /// The revision of the buffer the last blame was requested for
+    blame_rev: Rc<Cell<Option<u64>>>,
+    /// The work waiting for the edits to settle
+    pub work: Debouncer<DocWork>,
     /// The text layouts for the document. This may be shared with other views.
     text_layouts: Rc<RefCell<TextLayoutCache>>,
 
     /// A cache for the sticky headers which maps a line to the lines it should show in the header.
     pub sticky_headers: Rc<RefCell<HashMap<usize, Option<Vec<usize>>>>>,
+    /// The line summaries the minimap is painted from.
+    minimap: Rc<RefCell<MinimapCache>>,
+    /// The positions of the editors which have shown this document before
+    /// switching to another one.
+    view_states: Rc<RefCell<HashMap<EditorId, EditorViewState>>>,
     proxy: ProxyRpcHandler,
     config: ReadSignal<Arc<LapceConfig>>,
     find: Find,
@@ -212,19 +429,38 @@ pub fn new(
             buffer_id: BufferId::next(),
             buffer: Buffer::new(""),
             cache_rev: 0,
+            line_invals: Vec::new(),
+            file_key: None,
+            language_config_key: None,
+            language_override: None,
+            language_chosen: false,
             syntax,
+            syntax_edits: None,
+            signal: None,
+            syntax_worker: None,
+            syntax_disabled: false,
+            visible_lines: RefCell::new(0..0),
             line_styles: Rc::new(RefCell::new(HashMap::new())),
             semantic_styles: None,
             inlay_hints: None,
             diagnostics,
+            recent_edits: RecentEdits::default(),
             completion_lens: None,
             completion_pos: (0, 0),
             content: DocContent::File(path),
             loaded: false,
+            orphaned: false,
+            ansi: None,
+            spell: SpellCheck::default(),
             histories: cx.create_rw_signal(im::HashMap::new()),
             head_changes: cx.create_rw_signal(im::Vector::new()),
+            blame: cx.create_rw_signal(None),
+            blame_rev: Rc::new(Cell::new(None)),
+            work: Debouncer::new(),
             text_layouts: Rc::new(RefCell::new(TextLayoutCache::new())),
             sticky_headers: Rc::new(RefCell::new(HashMap::new())),
+            minimap: Rc::new(RefCell::new(MinimapCache::default())),
+            view_states: Rc::new(RefCell::new(HashMap::new())),
             code_actions: im::HashMap::new(),
             proxy,
             config,
@@ -245,21 +481,41 @@ pub fn new_local(
             buffer_id: BufferId::next(),
             buffer: Buffer::new(""),
             cache_rev: 0,
+            line_invals: Vec::new(),
+            file_key: None,
+            language_config_key: None,
+            language_override: None,
+            language_chosen: false,
             content: DocContent::Local,
             syntax: Syntax::plaintext(),
+            syntax_edits: None,
+            signal: None,
+            syntax_worker: None,
+            syntax_disabled: false,
+            visible_lines: RefCell::new(0..0),
             line_styles: Rc::new(RefCell::new(HashMap::new())),
             sticky_headers: Rc::new(RefCell::new(HashMap::new())),
+            minimap: Rc::new(RefCell::new(MinimapCache::default())),
+            view_states: Rc::new(RefCell::new(HashMap::new())),
             semantic_styles: None,
             inlay_hints: None,
             diagnostics: DiagnosticData {
                 expanded: cx.create_rw_signal(true),
+                version: cx.create_rw_signal(None),
                 diagnostics: cx.create_rw_signal(im::Vector::new()),
             },
+            recent_edits: RecentEdits::default(),
             completion_lens: None,
             completion_pos: (0, 0),
             loaded: true,
+            orphaned: false,
+            ansi: None,
+            spell: SpellCheck::default(),
             histories: cx.create_rw_signal(im::HashMap::new()),
             head_changes: cx.create_rw_signal(im::Vector::new()),
+            blame: cx.create_rw_signal(None),
+            blame_rev: Rc::new(Cell::new(None)),
+            work: Debouncer::new(),
             text_layouts: Rc::new(RefCell::new(TextLayoutCache::new())),
             code_actions: im::HashMap::new(),
             proxy,
@@ -283,21 +539,41 @@ pub fn new_content(
             buffer_id: BufferId::next(),
             buffer: Buffer::new(""),
             cache_rev: 0,
+            line_invals: Vec::new(),
+            file_key: None,
+            language_config_key: None,
+            language_override: None,
+            language_chosen: false,
             content,
             syntax: Syntax::plaintext(),
+            syntax_edits: None,
+            signal: None,
+            syntax_worker: None,
+            syntax_disabled: false,
+            visible_lines: RefCell::new(0..0),
             line_styles: Rc::new(RefCell::new(HashMap::new())),
             sticky_headers: Rc::new(RefCell::new(HashMap::new())),
+            minimap: Rc::new(RefCell::new(MinimapCache::default())),
+            view_states: Rc::new(RefCell::new(HashMap::new())),
             semantic_styles: None,
             inlay_hints: None,
             diagnostics: DiagnosticData {
                 expanded: cx.create_rw_signal(true),
+                version: cx.create_rw_signal(None),
                 diagnostics: cx.create_rw_signal(im::Vector::new()),
             },
+            recent_edits: RecentEdits::default(),
             completion_lens: None,
             completion_pos: (0, 0),
             loaded: true,
+            orphaned: false,
+            ansi: None,
+            spell: SpellCheck::default(),
             histories: cx.create_rw_signal(im::HashMap::new()),
             head_changes: cx.create_rw_signal(im::Vector::new()),
+            blame: cx.create_rw_signal(None),
+            blame_rev: Rc::new(Cell::new(None)),
+            work: Debouncer::new(),
             text_layouts: Rc::new(RefCell::new(TextLayoutCache::new())),
             code_actions: im::HashMap::new(),
             proxy,
@@ -326,21 +602,41 @@ pub fn new_hisotry(
             buffer_id: BufferId::next(),
             buffer: Buffer::new(""),
             cache_rev: 0,
+            line_invals: Vec::new(),
+            file_key: None,
+            language_config_key: None,
+            language_override: None,
+            language_chosen: false,
             content,
             syntax,
+            syntax_edits: None,
+            signal: None,
+            syntax_worker: None,
+            syntax_disabled: false,
+            visible_lines: RefCell::new(0..0),
             line_styles: Rc::new(RefCell::new(HashMap::new())),
             sticky_headers: Rc::new(RefCell::new(HashMap::new())),
+            minimap: Rc::new(RefCell::new(MinimapCache::default())),
+            view_states: Rc::new(RefCell::new(HashMap::new())),
             semantic_styles: None,
             inlay_hints: None,
             diagnostics: DiagnosticData {
                 expanded: cx.create_rw_signal(true),
+                version: cx.create_rw_signal(None),
                 diagnostics: cx.create_rw_signal(im::Vector::new()),
             },
+            recent_edits: RecentEdits::default(),
             completion_lens: None,
             completion_pos: (0, 0),
             loaded: true,
+            orphaned: false,
+            ansi: None,
+            spell: SpellCheck::default(),
             histories: cx.create_rw_signal(im::HashMap::new()),
             head_changes: cx.create_rw_signal(im::Vector::new()),
+            blame: cx.create_rw_signal(None),
+            blame_rev: Rc::new(Cell::new(None)),
+            work: Debouncer::new(),
             text_layouts: Rc::new(RefCell::new(TextLayoutCache::new())),
             code_actions: im::HashMap::new(),
             proxy,
@@ -351,6 +647,46 @@ pub fn new_hisotry(
         }
     }
 
+    /// Load the content of a history document, the file at `HEAD` or, for
+    /// [`DocHistory::SAVED`], the file as it's saved on disk.
+    pub fn load_history(doc: RwSignal<Document>) {
+        let Some((cx, proxy, history)) = doc.with_untracked(|doc| {
+            let DocContent::History(history) = &doc.content else {
+                return None;
+            };
+            Some((doc.scope, doc.proxy.clone(), history.clone()))
+        }) else {
+            return;
+        };
+        let send = create_ext_action(cx, move |content: Option<String>| {
+            if let Some(content) = content {
+                doc.update(|doc| doc.init_content(Rope::from(content)));
+            }
+        });
+        if history.version == DocHistory::SAVED {
+            proxy.read_files(vec![history.path], move |result| {
+                let content = match result {
+                    Ok(ProxyResponse::ReadFilesResponse { files }) => files
+                        .into_iter()
+                        .next()
+                        .and_then(|(_, content)| content.ok()),
+                    _ => None,
+                };
+                send(content);
+            });
+        } else {
+            proxy.get_buffer_head(history.path, move |result| {
+                let content = match result {
+                    Ok(ProxyResponse::BufferHeadResponse { content, .. }) => {
+                        Some(content)
+                    }
+                    _ => None,
+                };
+                send(content);
+            });
+        }
+    }
+
     pub fn buffer(&self) -> &Buffer {
         &self.buffer
     }
@@ -369,15 +705,328 @@ pub fn syntax(&self) -> &Syntax {
 
     pub fn set_syntax(&mut self, syntax: Syntax) {
         self.syntax = syntax;
-        if self.semantic_styles.is_none() {
-            self.clear_style_cache();
-        }
+        self.syntax_edits = None;
+        self.invalidate_syntax_styles();
         self.clear_sticky_headers_cache();
     }
 
     /// Set the syntax highlighting this document should use.
     pub fn set_language(&mut self, language: LapceLanguage) {
         self.syntax = Syntax::from_language(language);
+        self.syntax_edits = None;
+        self.update_spell_check();
+    }
+
+    /// Detect the language of the file from its name, or else its shebang
+    /// line, with the language the user chose for it and then the
+    /// `file-associations` setting taking precedence. Whether it changed.
+    pub fn detect_language(&mut self) -> bool {
+        let (chosen, language) = {
+            let path = match &self.content {
+                DocContent::File(path) => path,
+                DocContent::History(history) => &history.path,
+                DocContent::Local | DocContent::Scratch { .. } => return false,
+            };
+            let config = self.config.get_untracked();
+            let chosen = self.language_override.or_else(|| {
+                LapceLanguage::from_associations(
+                    path,
+                    &config.editor.file_associations,
+                )
+            });
+            let language = chosen.unwrap_or_else(|| {
+                LapceLanguage::detect(
+                    path,
+                    &self.buffer.line_content(0),
+                    &HashMap::new(),
+                )
+            });
+            (chosen, language)
+        };
+        // The language servers go by the path of the file unless told
+        // otherwise
+        if let DocContent::File(path) = &self.content {
+            if chosen.is_some() || self.language_chosen {
+                self.proxy.set_buffer_language(
+                    path.clone(),
+                    chosen.map(|language| language.lsp_id()),
+                );
+            }
+            self.language_chosen = chosen.is_some();
+        }
+        if language != self.syntax.language {
+            self.set_language(language);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Have the document be of `language` whatever its path says, or of the
+    /// detected one again if `None`.
+    pub fn set_language_override(&mut self, language: Option<LapceLanguage>) {
+        self.language_override = language;
+        match language {
+            Some(language) if language != self.syntax.language => {
+                self.set_language(language);
+            }
+            _ => {}
+        }
+        self.detect_language();
+        self.trigger_syntax_change(None);
+    }
+
+    /// Resolve the language config of the buffer from the built-in one of the
+    /// document's language and the user's `[languages]` overrides, unless
+    /// neither changed since it was last resolved.
+    fn update_language_config(&mut self) {
+        let config = self.config.get_untracked();
+        let language = self.syntax.language;
+        let key = (config.id, language);
+        if self.language_config_key == Some(key) {
+            return;
+        }
+        self.language_config_key = Some(key);
+
+        let mut language_config = LanguageConfig::new(language);
+        language_config.shift_blank_lines = config.editor.shift_blank_lines;
+        if let Some(overrides) = config.language_overrides(language) {
+            language_config.apply(overrides);
+        }
+        self.buffer.set_language_config(Arc::new(language_config));
+    }
+
+    /// The columns to draw rulers at, the ones set for the document's
+    /// language, or else the 50/72 of commit messages, or else the editor's.
+    pub fn rulers(&self, config: &LapceConfig) -> Vec<usize> {
+        let language = self.syntax.language;
+        if let Some(rulers) = config
+            .language_overrides(language)
+            .and_then(|overrides| overrides.rulers.clone())
+        {
+            rulers
+        } else if language == LapceLanguage::GitCommit
+            && config.editor.git_commit_mode
+        {
+            git_commit::RULERS.to_vec()
+        } else {
+            config.editor.rulers.clone()
+        }
+    }
+
+    /// The command set to format the document with when no language server
+    /// formats it.
+    pub fn formatter(&self, config: &LapceConfig) -> Option<String> {
+        config
+            .language_overrides(self.syntax.language)
+            .and_then(|overrides| overrides.formatter.clone())
+            .filter(|formatter| !formatter.trim().is_empty())
+    }
+
+    /// The options language servers format the document with, which follow
+    /// its indentation.
+    pub fn formatting_options(&self, config: &LapceConfig) -> FormattingOptions {
+        let (tab_size, insert_spaces) = match self.buffer.indent_style() {
+            IndentStyle::Tabs => (config.editor.tab_width as u32, false),
+            IndentStyle::Spaces(width) => (width as u32, true),
+        };
+        FormattingOptions {
+            tab_size,
+            insert_spaces,
+            ..Default::default()
+        }
+    }
+
+    /// The column the reflow operator wraps lines at: the text width set in
+    /// the config, or else the last ruler of the document, or else 80.
+    pub fn text_width(&self, config: &LapceConfig) -> usize {
+        if config.editor.text_width > 0 {
+            return config.editor.text_width;
+        }
+        self.rulers(config).into_iter().max().unwrap_or(80)
+    }
+
+    /// Whether the spelling is checked: as toggled for the document, or else
+    /// as set for its language, or else as the `editor.spell-check` setting
+    /// has it. Only files and untitled files are checked.
+    fn spell_check_enabled(&self) -> bool {
+        if !matches!(
+            self.content,
+            DocContent::File(_) | DocContent::Scratch { .. }
+        ) || self.syntax_disabled
+        {
+            return false;
+        }
+        if let Some(enabled) = self.spell.enabled {
+            return enabled;
+        }
+        let config = self.config.get_untracked();
+        config
+            .language_overrides(self.syntax.language)
+            .and_then(|overrides| overrides.spell_check)
+            .unwrap_or(config.editor.spell_check)
+    }
+
+    /// Whether the spell checking has to be turned on or off, as the settings,
+    /// the language or the switch of the document changed.
+    pub fn spell_check_changed(&self) -> bool {
+        self.spell_check_enabled() != self.spell.active
+    }
+
+    /// Start checking the spelling of the whole text, or stop and forget the
+    /// misspelled words, if the settings, the language or the switch of the
+    /// document turned it on or off.
+    pub fn update_spell_check(&mut self) {
+        if !self.spell_check_changed() {
+            return;
+        }
+        let enabled = !self.spell.active;
+        self.spell.active = enabled;
+        if enabled {
+            self.spell.mark_all(self.buffer.len());
+            self.schedule_spell_check();
+        } else {
+            self.spell.clear();
+            self.clear_text_cache();
+        }
+    }
+
+    /// Turn the spell checking of the document on or off, over the settings.
+    pub fn toggle_spell_check(&mut self) {
+        self.spell.enabled = Some(!self.spell.active);
+        self.update_spell_check();
+    }
+
+    /// Check the spelling of what changed once the edits settle.
+    fn schedule_spell_check(&self) {
+        let Some(signal) = self.signal else {
+            return;
+        };
+        if !self.spell.active || self.spell.dirty().is_none() {
+            return;
+        }
+        self.work
+            .schedule(DocWork::SpellCheck, SETTLE_DELAY, move || {
+                signal.try_update(|doc| doc.check_spelling());
+            });
+    }
+
+    /// Check the lines changed since the last check in the background: all of
+    /// their text in prose, and their comments and strings in code.
+    fn check_spelling(&mut self) {
+        let (Some(dirty), Some(signal)) = (self.spell.dirty(), self.signal) else {
+            return;
+        };
+        let prose = matches!(
+            self.syntax.language,
+            LapceLanguage::Plaintext | LapceLanguage::Markdown
+        );
+        // Where the comments and strings are is only known once the syntax
+        // tree caught up with the edits and is highlighted in full, which
+        // checks again when it's in
+        if !prose && (self.syntax_behind() || self.syntax.partial) {
+            return;
+        }
+
+        let start_line = self.buffer.line_of_offset(dirty.start);
+        let end_line = self.buffer.line_of_offset(dirty.end);
+        let start = self.buffer.offset_of_line(start_line);
+        let end = self.buffer.offset_of_line(end_line + 1);
+        let ranges = if prose {
+            vec![start..end]
+        } else if let Some(styles) = self.syntax.styles.as_ref() {
+            styles
+                .iter_chunks(start..end)
+                .filter(|(_, style)| {
+                    style
+                        .fg_color
+                        .as_deref()
+                        .map(is_prose_scope)
+                        .unwrap_or(false)
+                })
+                .map(|(iv, _)| iv.start().max(start)..iv.end().min(end))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let rev = self.rev();
+        let text = self.buffer.text().clone();
+        let send = create_ext_action(self.scope, move |words| {
+            signal.try_update(|doc| doc.set_misspellings(rev, start..end, words));
+        });
+        rayon::spawn(move || {
+            let dictionary = spell::dictionary();
+            let mut words = Vec::new();
+            for range in ranges {
+                let slice = text.slice_to_cow(range.clone());
+                words.extend(
+                    lapce_core::spell::misspellings(&dictionary, &slice)
+                        .into_iter()
+                        .map(|word| {
+                            range.start + word.start..range.start + word.end
+                        }),
+                );
+            }
+            send(words);
+        });
+    }
+
+    /// Take the misspelled `words` found in `range` by a check of the text at
+    /// `rev`, unless it changed since.
+    fn set_misspellings(
+        &mut self,
+        rev: u64,
+        range: Range<usize>,
+        words: Vec<Range<usize>>,
+    ) {
+        if rev != self.rev() || !self.spell.active {
+            return;
+        }
+        self.spell.set_checked(range.clone(), words);
+        let start_line = self.buffer.line_of_offset(range.start);
+        let end_line = self.buffer.line_of_offset(range.end);
+        self.invalidate_lines(LineInval::Lines(vec![start_line..end_line + 1]));
+    }
+
+    /// The misspelled word at `offset` and its range.
+    pub fn misspelling_at(&self, offset: usize) -> Option<(Range<usize>, String)> {
+        let range = self.spell.word_at(offset)?;
+        let word = self.buffer.slice_to_cow(range.clone()).to_string();
+        Some((range, word))
+    }
+
+    /// Stop taking `word` as misspelled, after it was added to the dictionary.
+    pub fn forget_misspelling(&mut self, word: &str) {
+        let word = word.to_lowercase();
+        let buffer = &self.buffer;
+        let forgotten = self.spell.forget(|range| {
+            buffer.slice_to_cow(range.clone()).to_lowercase() == word
+        });
+        let lines = forgotten
+            .into_iter()
+            .map(|range| {
+                let line = self.buffer.line_of_offset(range.start);
+                line..line + 1
+            })
+            .collect::<Vec<_>>();
+        if !lines.is_empty() {
+            self.invalidate_lines(LineInval::Lines(lines));
+        }
+    }
+
+    /// Parse the syntax of the document stored in `doc` in the background
+    /// from now on, rather than on the UI thread.
+    pub fn parse_in_background(doc: RwSignal<Document>) {
+        doc.update(|d| {
+            d.signal = Some(doc);
+            d.syntax_worker = Some(Rc::new(SyntaxWorker::new()));
+        });
+    }
+
+    /// Set the lines an editor of the document is showing.
+    pub fn set_visible_lines(&self, lines: Range<usize>) {
+        *self.visible_lines.borrow_mut() = lines;
     }
 
     pub fn find(&self) -> &Find {
@@ -392,8 +1041,15 @@ pub fn loaded(&self) -> bool {
     //// Initialize the content with some text, this marks the document as loaded.
     pub fn init_content(&mut self, content: Rope) {
         self.buffer.init_content(content);
-        self.buffer.detect_indent(&self.syntax);
+        self.recent_edits
+            .reset(self.buffer.rev(), self.buffer.text().clone());
+        self.detect_language();
+        self.update_language_config();
+        self.buffer.detect_indent();
         self.loaded = true;
+        self.update_spell_check();
+        self.spell.mark_all(self.buffer.len());
+        self.clear_minimap_cache();
         self.on_update(None);
         self.init_diagnostics();
         self.retrieve_head();
@@ -404,16 +1060,96 @@ pub fn init_content(&mut self, content: Rope) {
     pub fn reload(&mut self, content: Rope, set_pristine: bool) {
         // self.code_actions.clear();
         // self.inlay_hints = None;
+        if self.ansi.is_some() {
+            let raw_len = self.raw_len();
+            let (text, ansi) = AnsiText::parse(&content.to_string());
+            let raw_delta =
+                RopeDelta::simple_edit(Interval::new(0, raw_len), content, raw_len);
+            self.reload_shown(Rope::from(text), Some(ansi), raw_delta, set_pristine);
+            return;
+        }
         let delta = self.buffer.reload(content, set_pristine);
         self.apply_deltas(&[delta]);
     }
 
+    /// Whether the text is shown in the colors of its ANSI escape sequences,
+    /// rather than with them
+    pub fn ansi(&self) -> bool {
+        self.ansi.is_some()
+    }
+
+    /// Show the text in the colors of its ANSI escape sequences, or with the
+    /// sequences again. The text that's saved stays the same.
+    pub fn toggle_ansi(&mut self) {
+        let raw = self.raw_text();
+        let raw_len = raw.len();
+        let (text, ansi) = if self.ansi.is_some() {
+            (raw, None)
+        } else {
+            let (text, ansi) = AnsiText::parse(&raw);
+            (text, Some(ansi))
+        };
+        let raw_delta =
+            RopeDelta::simple_edit(Interval::new(0, 0), Rope::default(), raw_len);
+        let pristine = self.buffer.is_pristine();
+        self.reload_shown(Rope::from(text), ansi, raw_delta, pristine);
+    }
+
+    /// Replace the text shown, along with the escape sequences left out of
+    /// it, with `raw_delta` being the change of the text with them.
+    fn reload_shown(
+        &mut self,
+        text: Rope,
+        ansi: Option<AnsiText>,
+        raw_delta: RopeDelta,
+        set_pristine: bool,
+    ) {
+        let delta = self.buffer.reload(text, set_pristine);
+        self.ansi = ansi;
+        self.apply_deltas_as(&[delta], &[raw_delta]);
+    }
+
+    /// The text as it's saved, with the escape sequences that aren't shown
+    pub fn raw_text(&self) -> String {
+        let text = self.buffer.to_string();
+        match &self.ansi {
+            Some(ansi) => ansi.restore(&text),
+            None => text,
+        }
+    }
+
+    fn raw_len(&self) -> usize {
+        self.buffer.len() + self.ansi.as_ref().map_or(0, AnsiText::escapes_len)
+    }
+
+    /// Send the whole text to the proxy, for when it missed an edit.
+    pub fn resync_proxy(&self) {
+        if let DocContent::File(path) = &self.content {
+            self.proxy
+                .resync_buffer(path.clone(), self.raw_text(), self.rev());
+        }
+    }
+
     pub fn handle_file_changed(&mut self, content: Rope) {
         if self.buffer.is_pristine() {
             self.reload(content, true);
         }
     }
 
+    /// Add `text` to the end of the document, even when it's read-only, as
+    /// it's read from stdin. The language is guessed from the first text.
+    pub fn append(&mut self, text: &str) {
+        let end = self.buffer.len();
+        self.do_raw_edit(&[(Selection::caret(end), text)], EditType::Other);
+        self.buffer.set_pristine();
+        if end == 0 {
+            if let Some(language) = LapceLanguage::from_content(text) {
+                self.set_language(language);
+                self.trigger_syntax_change(None);
+            }
+        }
+    }
+
     pub fn do_insert(
         &mut self,
         cursor: &mut Cursor,
@@ -424,7 +1160,7 @@ pub fn do_insert(
             return Vec::new();
         }
 
-        let old_cursor = cursor.mode.clone();
+        self.update_language_config();
         let deltas = Editor::insert(
             cursor,
             &mut self.buffer,
@@ -432,9 +1168,6 @@ pub fn do_insert(
             &self.syntax,
             config.editor.auto_closing_matching_pairs,
         );
-        // Keep track of the change in the cursor mode for undo/redo
-        self.buffer.set_cursor_before(old_cursor);
-        self.buffer.set_cursor_after(cursor.mode.clone());
         self.apply_deltas(&deltas);
         deltas
     }
@@ -461,39 +1194,107 @@ pub fn do_edit(
             return Vec::new();
         }
 
+        self.update_language_config();
         let mut clipboard = SystemClipboard::new();
-        let old_cursor = cursor.mode.clone();
         let deltas = Editor::do_edit(
             cursor,
             &mut self.buffer,
             cmd,
-            &self.syntax,
             &mut clipboard,
             modal,
             register,
             smart_tab,
         );
+        self.apply_deltas(&deltas);
+        deltas
+    }
+
+    /// Apply an operator, like the `d` of `dw`, to the text from `start` to
+    /// `end`.
+    pub fn execute_motion_mode(
+        &mut self,
+        cursor: &mut Cursor,
+        motion_mode: MotionMode,
+        start: usize,
+        end: usize,
+        is_vertical: bool,
+        register: &mut Register,
+    ) -> Vec<(RopeDelta, InvalLines, SyntaxEdit)> {
+        self.update_language_config();
+        let deltas = Editor::execute_motion_mode(
+            cursor,
+            &mut self.buffer,
+            motion_mode,
+            start,
+            end,
+            is_vertical,
+            register,
+        );
+        self.apply_deltas(&deltas);
+        deltas
+    }
 
-        if !deltas.is_empty() {
-            self.buffer.set_cursor_before(old_cursor);
-            self.buffer.set_cursor_after(cursor.mode.clone());
+    /// Shift `lines` by `units` indents, to the left when `outdent`, leaving
+    /// the cursor at the start of the text of the first one.
+    pub fn shift_lines(
+        &mut self,
+        cursor: &mut Cursor,
+        lines: RangeInclusive<usize>,
+        units: usize,
+        outdent: bool,
+    ) -> Vec<(RopeDelta, InvalLines, SyntaxEdit)> {
+        if self.content.read_only() {
+            return Vec::new();
         }
 
+        self.update_language_config();
+        let first_line = *lines.start();
+        let deltas =
+            vec![Editor::shift_lines(&mut self.buffer, lines, units, outdent)];
+        let offset = self.buffer.first_non_blank_character_on_line(first_line);
+        cursor.mode = CursorMode::Normal(offset);
+        cursor.horiz = None;
         self.apply_deltas(&deltas);
         deltas
     }
 
     pub fn apply_deltas(&mut self, deltas: &[(RopeDelta, InvalLines, SyntaxEdit)]) {
+        let raw_deltas = deltas
+            .iter()
+            .map(|(delta, _, _)| match self.ansi.as_mut() {
+                Some(ansi) => ansi.apply_delta(delta),
+                None => delta.clone(),
+            })
+            .collect::<Vec<_>>();
+        self.apply_deltas_as(deltas, &raw_deltas);
+    }
+
+    /// Apply `deltas` of the shown text, which are `raw_deltas` of the text
+    /// the proxy has, with the escape sequences that aren't shown.
+    fn apply_deltas_as(
+        &mut self,
+        deltas: &[(RopeDelta, InvalLines, SyntaxEdit)],
+        raw_deltas: &[RopeDelta],
+    ) {
+        let _timer = perf::time(Phase::Edit);
         let rev = self.rev() - deltas.len() as u64;
-        for (i, (delta, _, _)) in deltas.iter().enumerate() {
+        for (i, (delta, inval_lines, _)) in deltas.iter().enumerate() {
+            self.invalidate_lines(LineInval::Edit(inval_lines.clone()));
+            self.update_head_changes(inval_lines);
+            self.update_view_states(delta);
             self.update_styles(delta);
             self.update_inlay_hints(delta);
             self.update_diagnostics(delta);
+            self.spell.apply_delta(delta);
+            self.recent_edits.push(delta.clone());
             self.update_completion_lens(delta);
             self.update_find_result(delta);
             if let DocContent::File(path) = &self.content {
-                self.proxy
-                    .update(path.clone(), delta.clone(), rev + i as u64 + 1);
+                self.proxy.update(
+                    path.clone(),
+                    raw_deltas[i].clone(),
+                    rev + i as u64 + 1,
+                );
             }
         }
 
@@ -514,14 +1315,35 @@ pub fn rev(&self) -> u64 {
         // self.find.borrow_mut().unset();
         // *self.find_progress.borrow_mut() = FindProgress::Started;
         // self.get_inlay_hints();
-        self.clear_style_cache();
+        // Edits have already invalidated the lines they touched
+        if edits.is_none() {
+            self.clear_style_cache();
+        }
         self.trigger_syntax_change(edits);
         self.clear_sticky_headers_cache();
         // self.clear_sticky_headers_cache();
         self.trigger_head_change();
+        self.clear_blame();
+        self.schedule_spell_check();
+        self.track_unsaved();
         // self.notify_special();
     }
 
+    /// Keep the text for a panic to back up while it has unsaved changes.
+    fn track_unsaved(&self) {
+        let path = match &self.content {
+            DocContent::File(path) => Some(path.as_path()),
+            DocContent::Scratch { .. } => None,
+            DocContent::Local | DocContent::History(_) => return,
+        };
+        crash::track_unsaved(
+            self.buffer_id,
+            path,
+            self.buffer.text(),
+            self.buffer.is_pristine(),
+        );
+    }
+
     /// Update the styles after an edit, so the highlights are at the correct positions.  
     /// This does not do a reparse of the document itself.
     fn update_styles(&mut self, delta: &RopeDelta) {
@@ -535,6 +1357,43 @@ fn update_styles(&mut self, delta: &RopeDelta) {
         self.syntax.lens.apply_delta(delta);
     }
 
+    /// Shift the git gutter marks along with an edit, until the diff against
+    /// `HEAD` catches up with it.
+    fn update_head_changes(&self, inval_lines: &InvalLines) {
+        if inval_lines.inval_count == inval_lines.new_count
+            || self
+                .head_changes
+                .with_untracked(|changes| changes.is_empty())
+        {
+            return;
+        }
+        self.head_changes.update(|changes| {
+            shift_diff_lines(
+                changes.iter_mut(),
+                inval_lines.start_line,
+                inval_lines.inval_count,
+                inval_lines.new_count,
+            );
+        });
+    }
+
+    /// Update the saved editor positions so they still point at the same text
+    /// after an edit, which might come from another editor.
+    fn update_view_states(&mut self, delta: &RopeDelta) {
+        for state in self.view_states.borrow_mut().values_mut() {
+            state.cursor.apply_delta(delta);
+        }
+    }
+
+    pub fn save_view_state(&self, editor_id: EditorId, state: EditorViewState) {
+        self.view_states.borrow_mut().insert(editor_id, state);
+    }
+
+    /// Take the position the editor had on this document, if any.
+    pub fn take_view_state(&self, editor_id: EditorId) -> Option<EditorViewState> {
+        self.view_states.borrow_mut().remove(&editor_id)
+    }
+
     /// Update the inlay hints so their positions are correct after an edit.
     fn update_inlay_hints(&mut self, delta: &RopeDelta) {
         if let Some(hints) = self.inlay_hints.as_mut() {
@@ -549,7 +1408,83 @@ pub fn trigger_syntax_change(
         let rev = self.buffer.rev();
         let text = self.buffer.text().clone();
 
-        self.syntax.parse(rev, text, edits.as_deref());
+        self.syntax_edits = match (self.syntax_edits.take(), edits) {
+            (Some(mut pending), Some(edits)) => {
+                pending.extend(edits);
+                Some(pending)
+            }
+            _ => None,
+        };
+
+        // Huge files would take too long to parse
+        let max_size = self
+            .config
+            .with_untracked(|config| config.editor.max_syntax_file_size);
+        if text.len() > max_size.saturating_mul(1024 * 1024) {
+            if self.syntax.layers.is_some() {
+                let mut syntax = Syntax::from_language(self.syntax.language);
+                syntax.layers = None;
+                self.set_syntax(syntax);
+            }
+            self.syntax_disabled = true;
+            return;
+        } else if self.syntax_disabled {
+            self.syntax_disabled = false;
+            self.set_language(self.syntax.language);
+        }
+
+        let (doc, worker) = match (self.signal, &self.syntax_worker) {
+            (Some(doc), Some(worker)) if self.syntax.layers.is_some() => {
+                (doc, worker.clone())
+            }
+            _ => {
+                self.syntax.parse(rev, text, self.syntax_edits.as_deref());
+                self.syntax_edits = Some(Vec::new());
+                self.invalidate_syntax_styles();
+                return;
+            }
+        };
+
+        let scope = self.scope;
+        let receive = move || {
+            create_ext_action(scope, move |syntax| {
+                doc.update(|doc| doc.receive_syntax(rev, syntax));
+            })
+        };
+        let visible = {
+            let lines = self.visible_lines.borrow();
+            self.buffer.offset_of_line(lines.start)
+                ..self.buffer.offset_of_line(lines.end)
+        };
+
+        // The parse carries on from the last finished one, reapplying every
+        // edit since, so each edit is parsed incrementally even if earlier
+        // parses were dropped.
+        worker.parse(SyntaxJob {
+            rev,
+            text,
+            syntax: self.syntax.clone(),
+            edits: self.syntax_edits.clone(),
+            visible,
+            on_partial: Box::new(receive()),
+            on_done: Box::new(receive()),
+        });
+    }
+
+    /// Take the result of a background parse, unless the buffer changed since
+    /// it started, in which case a newer parse is on its way. Partial results
+    /// are only taken until the complete one is in.
+    fn receive_syntax(&mut self, rev: u64, syntax: Syntax) {
+        if self.buffer.rev() != rev
+            || (self.syntax.rev == rev && !self.syntax.partial)
+        {
+            return;
+        }
+        self.set_syntax(syntax);
+        self.syntax_edits = Some(Vec::new());
+        if !self.syntax.partial {
+            self.schedule_spell_check();
+        }
     }
 
     fn clear_style_cache(&mut self) {
@@ -583,13 +1518,64 @@ pub fn clear_preedit(&mut self) {
     /// Inform any dependents on this document that they should clear any cached text.
     pub fn clear_text_cache(&mut self) {
         self.cache_rev += 1;
+        self.line_invals.clear();
         self.text_layouts.borrow_mut().clear(self.cache_rev);
     }
 
+    /// Drop the cached styles and text of only the lines `inval` made stale.
+    /// Views catch up on it through [`Document::line_invals_since`].
+    fn invalidate_lines(&mut self, inval: LineInval) {
+        self.cache_rev += 1;
+        inval.apply(&mut self.line_styles.borrow_mut());
+        match &inval {
+            LineInval::Edit(inval_lines) => {
+                self.minimap.borrow_mut().apply_inval_lines(inval_lines)
+            }
+            LineInval::Lines(lines) => {
+                self.minimap.borrow_mut().invalidate_lines(lines)
+            }
+        }
+        self.text_layouts
+            .borrow_mut()
+            .invalidate(&inval, self.cache_rev);
+
+        if self.line_invals.len() == MAX_LINE_INVALS {
+            self.line_invals.remove(0);
+        }
+        self.line_invals.push((self.cache_rev, inval));
+    }
+
+    /// The line invalidations that bring a cache at `cache_rev` up to date, or
+    /// `None` if it has to be cleared.
+    pub fn line_invals_since(&self, cache_rev: u64) -> Option<&[(u64, LineInval)]> {
+        let missed = self.cache_rev.checked_sub(cache_rev)? as usize;
+        let start = self.line_invals.len().checked_sub(missed)?;
+        Some(&self.line_invals[start..])
+    }
+
+    /// Drop the cached styles of the lines the last syntax parse highlighted
+    /// differently, unless they come from the semantic styles.
+    fn invalidate_syntax_styles(&mut self) {
+        if self.semantic_styles.is_some() {
+            return;
+        }
+        match self.syntax.changed_lines.clone() {
+            Some(lines) => self.invalidate_lines(LineInval::Lines(lines)),
+            None => {
+                self.clear_style_cache();
+                self.clear_minimap_cache();
+            }
+        }
+    }
+
     fn clear_sticky_headers_cache(&mut self) {
         self.sticky_headers.borrow_mut().clear();
     }
 
+    fn clear_minimap_cache(&mut self) {
+        self.minimap.borrow_mut().clear();
+    }
+
     /// Get the active style information, either the semantic styles or the
     /// tree-sitter syntax styles.
     fn styles(&self) -> Option<&Arc<Spans<Style>>> {
@@ -616,6 +1602,59 @@ pub fn line_style(&self, line: usize) -> Arc<Vec<LineStyle>> {
         self.line_styles.borrow().get(&line).cloned().unwrap()
     }
 
+    /// Whether the syntax tree is of an older revision of the text, while the
+    /// parse of the current one is on its way.
+    pub fn syntax_behind(&self) -> bool {
+        self.syntax.layers.is_some() && self.syntax.rev != self.buffer.rev()
+    }
+
+    /// Get the styles coloring the brackets on the line in cycling hues by how
+    /// deeply they are nested, and the ones without a pair as errors.
+    fn line_bracket_styles(&self, line: usize) -> Vec<LineStyle> {
+        if !self.config.get_untracked().editor.bracket_pair_colorization
+            || self.syntax_behind()
+        {
+            return Vec::new();
+        }
+
+        let start = self.buffer.offset_of_line(line);
+        let end = self.buffer.offset_of_line(line + 1);
+        self.syntax
+            .brackets(start..end)
+            .into_iter()
+            .map(|bracket| {
+                let scope = match bracket.depth {
+                    Some(depth) => {
+                        format!("bracket.color{}", depth % BRACKET_COLORS + 1)
+                    }
+                    None => "bracket.unpaired".to_string(),
+                };
+                LineStyle {
+                    start: bracket.offset - start,
+                    end: bracket.offset - start + 1,
+                    style: Style {
+                        fg_color: Some(scope),
+                    },
+                }
+            })
+            .collect()
+    }
+
+    /// Get the minimap summary of the line, which is cached until the line is
+    /// edited or the highlighting changes.
+    pub fn minimap_line(&self, line: usize) -> Arc<MinimapLine> {
+        if let Some(summary) = self.minimap.borrow().get(line) {
+            return summary;
+        }
+
+        let text = self.buffer.line_content(line);
+        let styles = self.line_style(line);
+        let tab_width = self.config.get_untracked().editor.tab_width;
+        let summary = Arc::new(MinimapLine::new(&text, &styles, tab_width));
+        self.minimap.borrow_mut().insert(line, summary.clone());
+        summary
+    }
+
     pub fn tigger_proxy_update(doc: RwSignal<Document>, proxy: &ProxyRpcHandler) {
         Self::get_inlay_hints(doc, proxy);
         Self::get_semantic_styles(doc, proxy);
@@ -644,6 +1683,7 @@ fn get_semantic_styles(doc: RwSignal<Document>, proxy: &ProxyRpcHandler) {
                 if doc.buffer.rev() == rev {
                     doc.semantic_styles = Some(styles);
                     doc.clear_style_cache();
+                    doc.clear_minimap_cache();
                 }
             })
         });
@@ -731,6 +1771,36 @@ fn get_inlay_hints(doc: RwSignal<Document>, proxy: &ProxyRpcHandler) {
         });
     }
 
+    /// The color literals of `line`, at its columns, in the languages whose
+    /// config shows swatches of them. The ones highlighted as names are left
+    /// out.
+    pub fn line_color_literals(&self, line: usize) -> Vec<ColorLiteral> {
+        if !self.buffer.language_config().color_swatches {
+            return Vec::new();
+        }
+        let literals = color_literals(&self.buffer.line_content(line));
+        if literals.is_empty() {
+            return literals;
+        }
+        let styles = self.line_style(line);
+        literals
+            .into_iter()
+            .filter(|literal| {
+                // The `#` is usually highlighted apart from the name after it
+                let col = literal.start + 1;
+                !styles.iter().any(|style| {
+                    style.start <= col
+                        && col < style.end
+                        && style
+                            .style
+                            .fg_color
+                            .as_deref()
+                            .map_or(false, is_name_scope)
+                })
+            })
+            .collect()
+    }
+
     /// Get the phantom text for a given line
     pub fn line_phantom_text(&self, line: usize) -> PhantomTextLine {
         let config = self.config.get_untracked();
@@ -862,6 +1932,19 @@ pub fn line_phantom_text(&self, line: usize) -> PhantomTextLine {
             text.push(completion_text);
         }
 
+        for literal in self.line_color_literals(line) {
+            let [r, g, b, _] = literal.rgba;
+            text.push(PhantomText {
+                kind: PhantomTextKind::ColorSwatch,
+                col: literal.start,
+                text: "\u{25a0} ".to_string(),
+                font_size: None,
+                fg: Some(Color::rgb8(r, g, b)),
+                bg: None,
+                under_line: None,
+            });
+        }
+
         if let Some(preedit) = self.preedit.as_ref() {
             let (ime_line, col) = self.buffer.offset_to_line_col(preedit.offset);
             if line == ime_line {
@@ -924,18 +2007,44 @@ fn update_diagnostics(&self, delta: &RopeDelta) {
     }
 
     /// init diagnostics offset ranges from lsp positions
+    ///
+    /// The positions are resolved against the text at the version the server
+    /// published them for, then shifted through the edits made since and
+    /// clamped to the buffer. Without a version, or one too old to have the
+    /// edits of, they are taken to be for the current text.
     pub fn init_diagnostics(&mut self) {
         self.clear_text_cache();
         self.clear_code_actions();
+        let version = self.diagnostics.version.get_untracked();
+        let since = version
+            .and_then(|version| u64::try_from(version).ok())
+            .and_then(|rev| self.recent_edits.since(rev));
+        let (text, deltas) = match since {
+            Some((text, deltas)) => (text, deltas.collect::<Vec<_>>()),
+            None => (self.buffer.text().clone(), Vec::new()),
+        };
+        let text = RopeTextVal::new(text);
+        let len = self.buffer.len();
         self.diagnostics.diagnostics.update(|diagnostics| {
             for diagnostic in diagnostics.iter_mut() {
-                let start = self
-                    .buffer()
-                    .offset_of_position(&diagnostic.diagnostic.range.start);
-                let end = self
-                    .buffer()
-                    .offset_of_position(&diagnostic.diagnostic.range.end);
+                let mut start =
+                    text.offset_of_position(&diagnostic.diagnostic.range.start);
+                let mut end =
+                    text.offset_of_position(&diagnostic.diagnostic.range.end);
+                for delta in &deltas {
+                    let mut transformer = Transformer::new(delta);
+                    start = transformer.transform(start, false);
+                    end = transformer.transform(end, true);
+                }
+                let start = start.min(len);
+                let end = end.clamp(start, len);
                 diagnostic.range = (start, end);
+                if !deltas.is_empty() {
+                    diagnostic.diagnostic.range.start =
+                        self.buffer.offset_to_position(start);
+                    diagnostic.diagnostic.range.end =
+                        self.buffer.offset_to_position(end);
+                }
             }
         });
     }
@@ -1041,8 +2150,30 @@ pub fn update_find(&self) {
             .progress
             .set(FindProgress::InProgress(Selection::new()));
 
+        // The first matches are sent on their own when there are many of them,
+        // so that the find bar can show there are more while the rest are
+        // counted
+        let find_result = self.find_result.clone();
+        let send_first =
+            create_ext_action(self.scope, move |occurrences: Selection| {
+                if find_result.find_rev.get_untracked() != find_rev {
+                    return;
+                }
+                let searched = occurrences
+                    .regions()
+                    .last()
+                    .map(|region| region.max())
+                    .unwrap_or(0);
+                find_result.occurrences.set(occurrences);
+                find_result
+                    .progress
+                    .set(FindProgress::InProgress(Selection::region(0, searched)));
+            });
         let find_result = self.find_result.clone();
         let send = create_ext_action(self.scope, move |occurrences| {
+            if find_result.find_rev.get_untracked() != find_rev {
+                return;
+            }
             find_result.occurrences.set(occurrences);
             find_result.progress.set(FindProgress::Ready);
         });
@@ -1060,6 +2191,25 @@ pub fn update_find(&self) {
                 case_matching,
                 whole_words,
                 true,
+                Some(MAX_FIND_COUNT),
+                &mut occurrences,
+            );
+            if occurrences.len() < MAX_FIND_COUNT {
+                send(occurrences);
+                return;
+            }
+            send_first(occurrences);
+
+            let mut occurrences = Selection::new();
+            Find::find(
+                &text,
+                &search,
+                0,
+                text.len(),
+                case_matching,
+                whole_words,
+                true,
+                None,
                 &mut occurrences,
             );
             send(occurrences);
@@ -1094,6 +2244,9 @@ pub fn sticky_headers(&self, line: usize) -> Option<Vec<usize>> {
     /// Retrieve the `head` version of the buffer
     pub fn retrieve_head(&self) {
         if let DocContent::File(path) = &self.content {
+            // `HEAD` might have moved, which changes the blame as well
+            self.clear_blame();
+
             let histories = self.histories;
 
             let send = {
@@ -1129,12 +2282,7 @@ pub fn retrieve_head(&self) {
     }
 
     pub fn trigger_head_change(&self) {
-        let history = if let Some(text) =
-            self.histories.with_untracked(|histories| {
-                histories
-                    .get("head")
-                    .map(|history| history.buffer.text().clone())
-            }) {
+        let history = if let Some(text) = self.head_text() {
             text
         } else {
             return;
@@ -1144,6 +2292,13 @@ pub fn trigger_head_change(&self) {
         let rev = self.rev();
         let left_rope = history;
         let right_rope = self.buffer().text().clone();
+        let options = DiffOptions {
+            ignore_whitespace: self
+                .config
+                .get_untracked()
+                .editor
+                .gutter_diff_ignore_whitespace,
+        };
 
         let send = {
             let atomic_rev = atomic_rev.clone();
@@ -1163,85 +2318,138 @@ pub fn trigger_head_change(&self) {
             })
         };
 
-        rayon::spawn(move || {
-            let changes =
-                rope_diff(left_rope, right_rope, rev, atomic_rev.clone(), None);
-            send(changes.map(im::Vector::from));
+        // Wait for a pause in typing, rather than diffing after every keystroke
+        self.work
+            .schedule(DocWork::HeadDiff, SETTLE_DELAY, move || {
+                if atomic_rev.load(atomic::Ordering::Acquire) != rev {
+                    return;
+                }
+                rayon::spawn(move || {
+                    let changes = rope_diff(
+                        left_rope,
+                        right_rope,
+                        rev,
+                        atomic_rev.clone(),
+                        None,
+                        options,
+                    );
+                    send(changes.map(im::Vector::from));
+                });
+            });
+    }
+
+    /// Blame the buffer in the background once it stops changing. The
+    /// result is dropped if the buffer changed again in the meantime.
+    pub fn retrieve_blame(&self) {
+        let path = if let DocContent::File(path) = &self.content {
+            path.clone()
+        } else {
+            return;
+        };
+        let rev = self.rev();
+        if self.blame_rev.get() == Some(rev) {
+            return;
+        }
+        self.blame_rev.set(Some(rev));
+
+        let atomic_rev = self.buffer().atomic_rev();
+        let send = {
+            let atomic_rev = atomic_rev.clone();
+            let blame = self.blame;
+            create_ext_action(self.scope, move |result| {
+                if atomic_rev.load(atomic::Ordering::Acquire) != rev {
+                    return;
+                }
+                // Files outside of a repository have nothing to show
+                let hunks =
+                    if let Ok(ProxyResponse::GitBlameResponse { hunks }) = result {
+                        hunks.into()
+                    } else {
+                        im::Vector::new()
+                    };
+                blame.set(Some(hunks));
+            })
+        };
+
+        let proxy = self.proxy.clone();
+        self.work.schedule(DocWork::Blame, SETTLE_DELAY, move || {
+            if atomic_rev.load(atomic::Ordering::Acquire) != rev {
+                return;
+            }
+            proxy.git_blame(path, move |result| {
+                send(result);
+            });
         });
     }
 
+    fn clear_blame(&self) {
+        self.blame_rev.set(None);
+        if self.blame.with_untracked(|blame| blame.is_some()) {
+            self.blame.set(None);
+        }
+    }
+
+    /// The text of the file at `HEAD`, once it has been retrieved.
+    pub fn head_text(&self) -> Option<Rope> {
+        self.histories.with_untracked(|histories| {
+            histories
+                .get("head")
+                .map(|history| history.buffer.text().clone())
+        })
+    }
+
     /// Create rendable whitespace layout by creating a new text layout
     /// with invisible spaces and special utf8 characters that display
     /// the different white space characters.
+    /// Every whitespace character is laid out, tagged with the least that
+    /// whitespace has to be rendered for it to be drawn, as each view of the
+    /// document can render it differently.
     fn new_whitespace_layout(
         line_content: &str,
         text_layout: &TextLayout,
         phantom: &PhantomTextLine,
-        config: &LapceConfig,
-    ) -> Option<Vec<(char, (f64, f64))>> {
-        let mut render_leading = false;
-        let mut render_boundary = false;
-        let mut render_between = false;
-
+    ) -> Vec<(char, (f64, f64), RenderWhitespace)> {
         // TODO: render whitespaces only on highlighted text
-        match config.editor.render_whitespace.as_str() {
-            "all" => {
-                render_leading = true;
-                render_boundary = true;
-                render_between = true;
-            }
-            "boundary" => {
-                render_leading = true;
-                render_boundary = true;
-            }
-            "trailing" => {} // All configs include rendering trailing whitespace
-            _ => return None,
-        }
-
         let mut whitespace_buffer = Vec::new();
-        let mut rendered_whitespaces: Vec<(char, (f64, f64))> = Vec::new();
+        let mut rendered_whitespaces = Vec::new();
         let mut char_found = false;
         let mut col = 0;
         for c in line_content.chars() {
             match c {
-                '\t' => {
+                '\t' | ' ' => {
                     let col_left = phantom.col_after(col, true);
                     let col_right = phantom.col_after(col + 1, false);
                     let x0 = text_layout.hit_position(col_left).point.x;
                     let x1 = text_layout.hit_position(col_right).point.x;
-                    whitespace_buffer.push(('\t', (x0, x1)));
-                }
-                ' ' => {
-                    let col_left = phantom.col_after(col, true);
-                    let col_right = phantom.col_after(col + 1, false);
-                    let x0 = text_layout.hit_position(col_left).point.x;
-                    let x1 = text_layout.hit_position(col_right).point.x;
-                    whitespace_buffer.push((' ', (x0, x1)));
+                    whitespace_buffer.push((c, (x0, x1)));
                 }
                 _ => {
-                    if (char_found && render_between)
-                        || (char_found
-                            && render_boundary
-                            && whitespace_buffer.len() > 1)
-                        || (!char_found && render_leading)
-                    {
-                        rendered_whitespaces.extend(whitespace_buffer.iter());
-                    }
-
+                    let render = if !char_found || whitespace_buffer.len() > 1 {
+                        RenderWhitespace::Boundary
+                    } else {
+                        RenderWhitespace::All
+                    };
+                    rendered_whitespaces.extend(
+                        whitespace_buffer.drain(..).map(|(c, x)| (c, x, render)),
+                    );
                     char_found = true;
-                    whitespace_buffer.clear();
                 }
             }
             col += c.len_utf8();
         }
-        rendered_whitespaces.extend(whitespace_buffer.iter());
+        rendered_whitespaces.extend(
+            whitespace_buffer
+                .into_iter()
+                .map(|(c, x)| (c, x, RenderWhitespace::Trailing)),
+        );
 
-        Some(rendered_whitespaces)
+        rendered_whitespaces
     }
 
     /// Create a new text layout for the given line.  
     /// Typically you should use [`Document::get_text_layout`] instead.
     fn new_text_layout(&self, line: usize, _font_size: usize) -> TextLayoutLine {
+        let _timer = perf::time(Phase::Layout);
         let config = self.config.get_untracked();
         let line_content_original = self.buffer.line_content(line);
 
@@ -1269,8 +2477,7 @@ fn new_text_layout(&self, line: usize, _font_size: usize) -> TextLayoutLine {
         let line_content = phantom_text.combine_with_text(line_content);
 
         let color = config.get_color(LapceColor::EDITOR_FOREGROUND);
-        let family: Vec<FamilyOwned> =
-            FamilyOwned::parse_list(&config.editor.font_family).collect();
+        let family = config.editor.font_family();
         let attrs = Attrs::new()
             .color(*color)
             .family(&family)
@@ -1279,13 +2486,52 @@ fn new_text_layout(&self, line: usize, _font_size: usize) -> TextLayoutLine {
 
         // Apply various styles to the line's text based on our semantic/syntax highlighting
         let styles = self.line_style(line);
-        for line_style in styles.iter() {
+        let bracket_styles = self.line_bracket_styles(line);
+        let git_commit_styles = if self.syntax.language == LapceLanguage::GitCommit
+            && config.editor.git_commit_mode
+        {
+            git_commit::line_styles(line, &self.buffer.line_content(line))
+        } else {
+            Vec::new()
+        };
+        for line_style in styles
+            .iter()
+            .chain(bracket_styles.iter())
+            .chain(git_commit_styles.iter())
+        {
             if let Some(fg_color) = line_style.style.fg_color.as_ref() {
+                let font_style = config.get_style_font(fg_color);
                 if let Some(fg_color) = config.get_style_color(fg_color) {
                     let start = phantom_text.col_at(line_style.start);
                     let end = phantom_text.col_at(line_style.end);
-                    attrs_list.add_span(start..end, attrs.color(*fg_color));
+                    let mut attrs = attrs.color(*fg_color);
+                    if let Some(font_style) = font_style {
+                        if font_style.italic {
+                            attrs = attrs.style(FontStyle::Italic);
+                        }
+                        if font_style.bold {
+                            attrs = attrs.weight(Weight::BOLD);
+                        }
+                    }
+                    attrs_list.add_span(start..end, attrs);
+                }
+            }
+        }
+
+        if let Some(ansi) = self.ansi.as_ref() {
+            let start = self.buffer.offset_of_line(line);
+            let end = self.buffer.offset_of_line(line + 1);
+            for (range, style) in ansi.styles(start..end) {
+                let mut attrs = attrs;
+                if let Some(fg) = style.fg {
+                    attrs = attrs.color(*config.get_color(fg));
                 }
+                if style.bold {
+                    attrs = attrs.weight(Weight::BOLD);
+                }
+                let start = phantom_text.col_at(range.start);
+                let end = phantom_text.col_at(range.end);
+                attrs_list.add_span(start..end, attrs);
             }
         }
 
@@ -1384,11 +2630,8 @@ fn new_text_layout(&self, line: usize, _font_size: usize) -> TextLayoutLine {
 
                     let x0 = text_layout.hit_position(start).point.x;
                     let x1 = text_layout.hit_position(end).point.x;
-                    let color_name = match diag.diagnostic.severity {
-                        Some(DiagnosticSeverity::ERROR) => LapceColor::LAPCE_ERROR,
-                        _ => LapceColor::LAPCE_WARN,
-                    };
-                    let color = *config.get_color(color_name);
+                    let color = *config
+                        .get_color(diagnostic_color(diag.diagnostic.severity));
                     extra_style.push(LineExtraStyle {
                         x: x0,
                         width: Some(x1 - x0),
@@ -1400,11 +2643,33 @@ fn new_text_layout(&self, line: usize, _font_size: usize) -> TextLayoutLine {
             }
         });
 
+        let line_start = self.buffer.offset_of_line(line);
+        let words = self
+            .spell
+            .words_in(line_start..self.buffer.offset_of_line(line + 1));
+        if !words.is_empty() {
+            let color = *config.get_color(LapceColor::EDITOR_SPELLING);
+            for word in words {
+                let start = phantom_text
+                    .col_after(word.start.saturating_sub(line_start), true);
+                let end = phantom_text
+                    .col_after(word.end.saturating_sub(line_start), false);
+                let x0 = text_layout.hit_position(start).point.x;
+                let x1 = text_layout.hit_position(end).point.x;
+                extra_style.push(LineExtraStyle {
+                    x: x0,
+                    width: Some(x1 - x0),
+                    bg_color: None,
+                    under_line: None,
+                    wave_line: Some(color),
+                });
+            }
+        }
+
         let whitespaces = Self::new_whitespace_layout(
             line_content_original,
             &text_layout,
             &phantom_text,
-            &config,
         );
 
         let indent_line = if line_content_original.trim().is_empty() {
@@ -1431,9 +2696,17 @@ fn new_text_layout(&self, line: usize, _font_size: usize) -> TextLayoutLine {
             extra_style,
             whitespaces,
             indent,
+            brackets_stale: config.editor.bracket_pair_colorization
+                && self.syntax_behind(),
         }
     }
 
+    /// Drop the cached layouts of the lines far from `lines`, once there are
+    /// too many of them.
+    pub fn evict_text_layouts(&self, lines: Range<usize>) {
+        self.text_layouts.borrow_mut().evict_far_from(lines);
+    }
+
     /// Get the text layout for the given line.  
     /// If the text layout is not cached, it will be created and cached.
     pub fn get_text_layout(
@@ -1452,6 +2725,7 @@ pub fn get_text_layout(
         }
 
         // Get whether there's an entry for this specific font size and line
+        // that is still good to use
         let cache_exists = self
             .text_layouts
             .borrow()
@@ -1459,7 +2733,9 @@ pub fn get_text_layout(
             .get(&font_size)
             .unwrap()
             .get(&line)
-            .is_some();
+            .map_or(false, |layout| {
+                !layout.brackets_stale || self.syntax_behind()
+            });
         // If there isn't an entry then we actually have to create it
         if !cache_exists {
             let text_layout = Arc::new(self.new_text_layout(line, font_size));
@@ -1486,3 +2762,33 @@ pub fn get_text_layout(
             .unwrap()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use lapce_core::buffer::Buffer;
+
+    use super::DocInfo;
+
+    fn info(cursor_line: usize, cursor_column: usize) -> DocInfo {
+        DocInfo {
+            path: PathBuf::from("/ws/main.rs"),
+            scroll_offset: (0.0, 0.0),
+            cursor_line,
+            cursor_column,
+        }
+    }
+
+    #[test]
+    fn test_doc_info_cursor_offset() {
+        let buffer = Buffer::new("fn main() {\n    let x = 1;\n}\n");
+        assert_eq!(info(1, 8).cursor_offset(&buffer), 20);
+        assert!(info(1, 8).fits(&buffer));
+        // The line got shorter
+        assert_eq!(info(2, 10).cursor_offset(&buffer), 28);
+        // The file lost lines
+        assert_eq!(info(40, 3).cursor_offset(&buffer), buffer.len());
+        assert!(!info(40, 3).fits(&buffer));
+    }
+}
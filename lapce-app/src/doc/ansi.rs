@@ -0,0 +1,371 @@
+use std::ops::Range;
+
+use lapce_xi_rope::{
+    spans::{Spans, SpansBuilder},
+    DeltaBuilder, DeltaElement, Interval, Rope, RopeDelta,
+};
+
+use crate::config::color::LapceColor;
+
+const COLORS: [&str; 8] = [
+    LapceColor::TERMINAL_BLACK,
+    LapceColor::TERMINAL_RED,
+    LapceColor::TERMINAL_GREEN,
+    LapceColor::TERMINAL_YELLOW,
+    LapceColor::TERMINAL_BLUE,
+    LapceColor::TERMINAL_MAGENTA,
+    LapceColor::TERMINAL_CYAN,
+    LapceColor::TERMINAL_WHITE,
+];
+
+const BRIGHT_COLORS: [&str; 8] = [
+    LapceColor::TERMINAL_BRIGHT_BLACK,
+    LapceColor::TERMINAL_BRIGHT_RED,
+    LapceColor::TERMINAL_BRIGHT_GREEN,
+    LapceColor::TERMINAL_BRIGHT_YELLOW,
+    LapceColor::TERMINAL_BRIGHT_BLUE,
+    LapceColor::TERMINAL_BRIGHT_MAGENTA,
+    LapceColor::TERMINAL_BRIGHT_CYAN,
+    LapceColor::TERMINAL_BRIGHT_WHITE,
+];
+
+/// How the SGR sequences, like `\x1b[1;31m`, say the text after them looks
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AnsiStyle {
+    /// The color of the terminal's palette, like [`LapceColor::TERMINAL_RED`]
+    pub fg: Option<&'static str>,
+    pub bold: bool,
+}
+
+impl AnsiStyle {
+    fn apply_sgr(&mut self, params: &str) {
+        let mut codes = params
+            .split([';', ':'])
+            .map(|code| code.parse::<usize>().unwrap_or(0));
+        while let Some(code) = codes.next() {
+            match code {
+                0 => *self = AnsiStyle::default(),
+                1 => self.bold = true,
+                22 => self.bold = false,
+                30..=37 => self.fg = Some(COLORS[code - 30]),
+                39 => self.fg = None,
+                90..=97 => self.fg = Some(BRIGHT_COLORS[code - 90]),
+                38 | 48 => {
+                    // Only the 16 colors of the palette can be shown
+                    let color = match codes.next() {
+                        Some(5) => match codes.next() {
+                            Some(i @ 0..=7) => Some(COLORS[i]),
+                            Some(i @ 8..=15) => Some(BRIGHT_COLORS[i - 8]),
+                            _ => None,
+                        },
+                        Some(2) => {
+                            codes.by_ref().take(3).for_each(drop);
+                            None
+                        }
+                        _ => None,
+                    };
+                    if code == 38 {
+                        self.fg = color;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// A CSI sequence found in a text
+struct Csi<'a> {
+    range: Range<usize>,
+    params: &'a str,
+    final_byte: u8,
+}
+
+/// The CSI sequences of `text`: `ESC [`, then parameter bytes, intermediate
+/// bytes and a final byte. An `ESC` that doesn't start one whole is left alone.
+fn csi_sequences(text: &str) -> impl Iterator<Item = Csi<'_>> {
+    let bytes = text.as_bytes();
+    let mut start = 0;
+    std::iter::from_fn(move || loop {
+        let esc = start + bytes[start..].iter().position(|b| *b == 0x1b)?;
+        start = esc + 1;
+        if bytes.get(esc + 1) != Some(&b'[') {
+            continue;
+        }
+        let params_start = esc + 2;
+        let mut i = params_start;
+        while matches!(bytes.get(i), Some(0x30..=0x3f)) {
+            i += 1;
+        }
+        let params_end = i;
+        while matches!(bytes.get(i), Some(0x20..=0x2f)) {
+            i += 1;
+        }
+        if let Some(&final_byte @ 0x40..=0x7e) = bytes.get(i) {
+            start = i + 1;
+            return Some(Csi {
+                range: esc..i + 1,
+                params: &text[params_start..params_end],
+                final_byte,
+            });
+        }
+    })
+}
+
+/// Whether `text` has ANSI escape sequences, like the colors of build output.
+pub fn has_escapes(text: &str) -> bool {
+    csi_sequences(text).next().is_some()
+}
+
+/// An escape sequence left out of the shown text
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Escape {
+    /// Where it is in the shown text
+    offset: usize,
+    sequence: String,
+}
+
+/// The ANSI escape sequences of a text shown without them, in their colors
+/// instead. The sequences follow the edits of the shown text, to be put back
+/// into the text that's saved.
+#[derive(Clone)]
+pub struct AnsiText {
+    escapes: Vec<Escape>,
+    styles: Spans<AnsiStyle>,
+}
+
+impl AnsiText {
+    /// The text to show of `text`, without its escape sequences, and them.
+    pub fn parse(text: &str) -> (String, AnsiText) {
+        let mut shown = String::with_capacity(text.len());
+        let mut escapes = Vec::new();
+        let mut spans = Vec::new();
+        let mut style = AnsiStyle::default();
+        let mut style_start = 0;
+        let mut last = 0;
+        for csi in csi_sequences(text) {
+            shown.push_str(&text[last..csi.range.start]);
+            last = csi.range.end;
+            escapes.push(Escape {
+                offset: shown.len(),
+                sequence: text[csi.range].to_string(),
+            });
+            if csi.final_byte == b'm' {
+                let mut new_style = style;
+                new_style.apply_sgr(csi.params);
+                if new_style != style {
+                    spans.push((style_start..shown.len(), style));
+                    style = new_style;
+                    style_start = shown.len();
+                }
+            }
+        }
+        shown.push_str(&text[last..]);
+        spans.push((style_start..shown.len(), style));
+
+        let mut styles = SpansBuilder::new(shown.len());
+        for (range, style) in spans {
+            if !range.is_empty() && style != AnsiStyle::default() {
+                styles.add_span(Interval::new(range.start, range.end), style);
+            }
+        }
+        let ansi = AnsiText {
+            escapes,
+            styles: styles.build(),
+        };
+        (shown, ansi)
+    }
+
+    /// The total length of the escape sequences
+    pub fn escapes_len(&self) -> usize {
+        self.escapes.iter().map(|e| e.sequence.len()).sum()
+    }
+
+    /// `text`, the shown text, with the escape sequences put back in.
+    pub fn restore(&self, text: &str) -> String {
+        let mut restored = String::with_capacity(text.len() + self.escapes_len());
+        let mut last = 0;
+        for escape in &self.escapes {
+            let offset = escape.offset.min(text.len());
+            restored.push_str(&text[last..offset]);
+            restored.push_str(&escape.sequence);
+            last = offset;
+        }
+        restored.push_str(&text[last..]);
+        restored
+    }
+
+    /// The offset in the text with the escape sequences of `offset` in the
+    /// shown one, after the sequences there or before them.
+    fn raw_offset(&self, offset: usize, after: bool) -> usize {
+        let escapes = self.escapes.iter().take_while(|e| {
+            if after {
+                e.offset <= offset
+            } else {
+                e.offset < offset
+            }
+        });
+        offset + escapes.map(|e| e.sequence.len()).sum::<usize>()
+    }
+
+    /// The styles of the shown text in `range`, relative to its start.
+    pub fn styles(&self, range: Range<usize>) -> Vec<(Range<usize>, AnsiStyle)> {
+        self.styles
+            .iter_chunks(range.clone())
+            .filter_map(|(iv, style)| {
+                let start = iv.start().max(range.start);
+                let end = iv.end().min(range.end);
+                (start < end)
+                    .then(|| (start - range.start..end - range.start, *style))
+            })
+            .collect()
+    }
+
+    /// Follow `delta`, an edit of the shown text, and return the same edit of
+    /// the text with the escape sequences. The text inserted at a sequence
+    /// goes after it, and the sequences within deleted text go with it.
+    pub fn apply_delta(&mut self, delta: &RopeDelta) -> RopeDelta {
+        let edits = replaced_ranges(delta);
+
+        let mut builder = DeltaBuilder::new(delta.base_len + self.escapes_len());
+        for (start, end, text) in &edits {
+            let raw_start = self.raw_offset(*start, true);
+            let raw_end = self.raw_offset(*end, false).max(raw_start);
+            builder.replace(Interval::new(raw_start, raw_end), Rope::from(text));
+        }
+
+        self.escapes.retain_mut(|escape| {
+            let offset = escape.offset;
+            let mut new_offset = offset;
+            for (start, end, text) in &edits {
+                if *start < offset && offset < *end {
+                    return false;
+                }
+                if *end < offset || (*end == offset && start < end) {
+                    new_offset = new_offset + text.len() - (end - start);
+                }
+            }
+            escape.offset = new_offset;
+            true
+        });
+        self.styles.apply_shape(delta);
+
+        builder.build()
+    }
+}
+
+/// The ranges of the old text `delta` replaces, with what it replaces them
+/// with, in order.
+fn replaced_ranges(delta: &RopeDelta) -> Vec<(usize, usize, String)> {
+    let mut edits = Vec::new();
+    let mut pending: Option<(usize, usize, String)> = None;
+    let mut offset = 0;
+    for el in delta.els.iter() {
+        match el {
+            DeltaElement::Copy(start, end) => {
+                if *start > offset {
+                    let edit =
+                        pending.get_or_insert((offset, offset, String::new()));
+                    edit.1 = *start;
+                }
+                edits.extend(pending.take());
+                offset = *end;
+            }
+            DeltaElement::Insert(node) => {
+                let edit = pending.get_or_insert((offset, offset, String::new()));
+                edit.2.push_str(&String::from(node));
+            }
+        }
+    }
+    if delta.base_len > offset {
+        let edit = pending.get_or_insert((offset, offset, String::new()));
+        edit.1 = delta.base_len;
+    }
+    edits.extend(pending.take());
+    edits
+}
+
+#[cfg(test)]
+mod tests {
+    use lapce_xi_rope::{Interval, Rope, RopeDelta};
+
+    use super::{has_escapes, AnsiStyle, AnsiText};
+    use crate::config::color::LapceColor;
+
+    const LOG: &str = "\x1b[1;31merror\x1b[0m: bad\n\x1b[32mok\x1b[39m\n";
+
+    #[test]
+    fn test_parse() {
+        assert!(has_escapes(LOG));
+        assert!(!has_escapes("plain \x1b text"));
+
+        let (shown, ansi) = AnsiText::parse(LOG);
+        assert_eq!(shown, "error: bad\nok\n");
+        assert_eq!(ansi.restore(&shown), LOG);
+        assert_eq!(
+            ansi.styles(0..11),
+            vec![(
+                0..5,
+                AnsiStyle {
+                    fg: Some(LapceColor::TERMINAL_RED),
+                    bold: true
+                }
+            )]
+        );
+        assert_eq!(
+            ansi.styles(11..14),
+            vec![(
+                0..2,
+                AnsiStyle {
+                    fg: Some(LapceColor::TERMINAL_GREEN),
+                    bold: false
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn test_malformed_sequences_pass_through() {
+        for text in ["a\x1b[31", "a\x1b[3\u{e9}m", "a\x1bxb", "\x1b"] {
+            let (shown, ansi) = AnsiText::parse(text);
+            assert_eq!(shown, text);
+            assert_eq!(ansi.restore(&shown), text);
+        }
+        let (shown, _) = AnsiText::parse("\x1b[\x1b[31mred");
+        assert_eq!(shown, "\x1b[red");
+    }
+
+    fn edit(
+        ansi: &mut AnsiText,
+        shown: &str,
+        raw: &str,
+        range: std::ops::Range<usize>,
+        text: &str,
+    ) -> (String, String) {
+        let delta = RopeDelta::simple_edit(
+            Interval::new(range.start, range.end),
+            Rope::from(text),
+            shown.len(),
+        );
+        let raw_delta = ansi.apply_delta(&delta);
+        let shown = String::from(delta.apply(&Rope::from(shown)));
+        let raw = String::from(raw_delta.apply(&Rope::from(raw)));
+        assert_eq!(ansi.restore(&shown), raw);
+        (shown, raw)
+    }
+
+    #[test]
+    fn test_edits_keep_the_sequences() {
+        let (shown, mut ansi) = AnsiText::parse(LOG);
+        // At the start of a colored word
+        let (shown, raw) = edit(&mut ansi, &shown, LOG, 0..0, "an ");
+        assert_eq!(raw, "\x1b[1;31man error\x1b[0m: bad\n\x1b[32mok\x1b[39m\n");
+        // Up to the end of one
+        let (shown, raw) = edit(&mut ansi, &shown, &raw, 6..8, "E");
+        assert_eq!(raw, "\x1b[1;31man errE\x1b[0m: bad\n\x1b[32mok\x1b[39m\n");
+        // Around the sequences, which go with the text
+        let (shown, raw) = edit(&mut ansi, &shown, &raw, 3..16, "");
+        assert_eq!(shown, "an k\n");
+        assert_eq!(raw, "\x1b[1;31man k\x1b[39m\n");
+    }
+}
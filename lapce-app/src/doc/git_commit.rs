@@ -0,0 +1,70 @@
+use lapce_rpc::style::{LineStyle, Style};
+
+/// The longest a commit's subject line should be
+pub const SUBJECT_LENGTH: usize = 50;
+/// The longest the lines of a commit's body should be
+pub const BODY_LENGTH: usize = 72;
+/// The columns of the rulers of a commit message
+pub const RULERS: [usize; 2] = [SUBJECT_LENGTH, BODY_LENGTH];
+
+/// The styles of a line of a git commit message: the lines git leaves out
+/// because they start with `#` are comments, and what goes past the length
+/// the subject line or a body line should have is marked.
+pub fn line_styles(line: usize, content: &str) -> Vec<LineStyle> {
+    let content = content.trim_end_matches(['\n', '\r']);
+    if content.starts_with('#') {
+        return vec![style(0, content.len(), "comment")];
+    }
+    let max = if line == 0 {
+        SUBJECT_LENGTH
+    } else {
+        BODY_LENGTH
+    };
+    match content.char_indices().nth(max) {
+        Some((start, _)) => vec![style(start, content.len(), "text.overflow")],
+        None => Vec::new(),
+    }
+}
+
+fn style(start: usize, end: usize, scope: &str) -> LineStyle {
+    LineStyle {
+        start,
+        end,
+        style: Style {
+            fg_color: Some(scope.to_string()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::line_styles;
+
+    fn spans(line: usize, content: &str) -> Vec<(usize, usize, String)> {
+        line_styles(line, content)
+            .into_iter()
+            .map(|s| (s.start, s.end, s.style.fg_color.unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_line_styles() {
+        let comment = "# Please enter the commit message\n";
+        assert_eq!(spans(3, comment), vec![(0, 33, "comment".to_string())]);
+
+        let subject = "a".repeat(50);
+        assert!(spans(0, &subject).is_empty());
+        let subject = format!("{}é!\n", "a".repeat(50));
+        assert_eq!(
+            spans(0, &subject),
+            vec![(50, 53, "text.overflow".to_string())]
+        );
+
+        let body = "b".repeat(72);
+        assert!(spans(2, &body).is_empty());
+        assert_eq!(
+            spans(2, &format!("{body}cd")),
+            vec![(72, 74, "text.overflow".to_string())]
+        );
+    }
+}
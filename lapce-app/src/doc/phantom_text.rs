@@ -27,6 +27,8 @@ pub enum PhantomTextKind {
     InlayHint,
     /// Error lens
     Diagnostic,
+    /// The swatch of a color literal
+    ColorSwatch,
 }
 
 /// Information about the phantom text on a specific line.  
@@ -0,0 +1,100 @@
+use std::collections::VecDeque;
+
+use lapce_xi_rope::{Rope, RopeDelta};
+
+/// How many edits back positions from a language server can be brought up to
+/// date
+const MAX_RECENT_EDITS: usize = 64;
+
+/// The last edits made to a document, so that positions a language server
+/// computed for an older revision of the text can be mapped onto the current
+/// one.
+#[derive(Clone, Default)]
+pub struct RecentEdits {
+    /// The revision of `base_text`
+    base_rev: u64,
+    /// The text before the first of `deltas`
+    base_text: Rope,
+    /// The edits since `base_rev`, each bumping the revision by one
+    deltas: VecDeque<RopeDelta>,
+}
+
+impl RecentEdits {
+    /// Start over from `text` at `rev`, forgetting the edits before it.
+    pub fn reset(&mut self, rev: u64, text: Rope) {
+        self.base_rev = rev;
+        self.base_text = text;
+        self.deltas.clear();
+    }
+
+    /// Record the edit that brought the text to the next revision.
+    pub fn push(&mut self, delta: RopeDelta) {
+        self.deltas.push_back(delta);
+        if self.deltas.len() > MAX_RECENT_EDITS {
+            if let Some(oldest) = self.deltas.pop_front() {
+                self.base_text = oldest.apply(&self.base_text);
+                self.base_rev += 1;
+            }
+        }
+    }
+
+    /// The text at `rev` and the edits made since, or `None` if `rev` is
+    /// older than the edits kept or newer than the text.
+    pub fn since(
+        &self,
+        rev: u64,
+    ) -> Option<(Rope, impl Iterator<Item = &RopeDelta> + '_)> {
+        let skip = rev.checked_sub(self.base_rev)? as usize;
+        if skip > self.deltas.len() {
+            return None;
+        }
+        let text = self
+            .deltas
+            .iter()
+            .take(skip)
+            .fold(self.base_text.clone(), |text, delta| delta.apply(&text));
+        Some((text, self.deltas.iter().skip(skip)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lapce_xi_rope::{Delta, Interval, Rope};
+
+    use super::*;
+
+    fn insert(text: &Rope, offset: usize, s: &str) -> RopeDelta {
+        Delta::simple_edit(Interval::new(offset, offset), Rope::from(s), text.len())
+    }
+
+    #[test]
+    fn test_since() {
+        let mut edits = RecentEdits::default();
+        let text = Rope::from("ab");
+        edits.reset(1, text.clone());
+
+        let delta = insert(&text, 1, "x");
+        let text = delta.apply(&text);
+        edits.push(delta);
+        let delta = insert(&text, 0, "y");
+        edits.push(delta);
+
+        let (old, deltas) = edits.since(2).unwrap();
+        assert_eq!(String::from(old), "axb");
+        assert_eq!(deltas.count(), 1);
+        assert_eq!(edits.since(3).unwrap().1.count(), 0);
+        assert!(edits.since(0).is_none());
+        assert!(edits.since(4).is_none());
+
+        let mut text = Rope::from("");
+        edits.reset(0, text.clone());
+        for i in 0..MAX_RECENT_EDITS + 2 {
+            let delta = insert(&text, i, "z");
+            text = delta.apply(&text);
+            edits.push(delta);
+        }
+        assert!(edits.since(1).is_none());
+        let (old, _) = edits.since(2).unwrap();
+        assert_eq!(String::from(old), "zz");
+    }
+}
@@ -0,0 +1,152 @@
+use std::ops::Range;
+
+use lapce_xi_rope::{RopeDelta, Transformer};
+
+/// The misspelled words of a document, and the part of its text that has to
+/// be checked again since it was edited.
+#[derive(Clone, Default)]
+pub struct SpellCheck {
+    /// The switch of the document, over the settings, if it was toggled
+    pub enabled: Option<bool>,
+    /// Whether the words are kept up to date with the text
+    pub active: bool,
+    /// The ranges of the misspelled words, in order
+    words: Vec<Range<usize>>,
+    /// The part of the text that changed since it was last checked
+    dirty: Option<Range<usize>>,
+}
+
+impl SpellCheck {
+    /// The misspelled words that are in `range`, even partly.
+    pub fn words_in(&self, range: Range<usize>) -> &[Range<usize>] {
+        let start = self.words.partition_point(|word| word.end <= range.start);
+        let end = self.words.partition_point(|word| word.start < range.end);
+        &self.words[start..end.max(start)]
+    }
+
+    /// The misspelled word around `offset`, which can be at its end.
+    pub fn word_at(&self, offset: usize) -> Option<Range<usize>> {
+        self.words
+            .iter()
+            .find(|word| word.start <= offset && offset <= word.end)
+            .cloned()
+    }
+
+    /// The part of the text that has to be checked, if any.
+    pub fn dirty(&self) -> Option<Range<usize>> {
+        self.dirty.clone()
+    }
+
+    /// Have the whole text, of `len`, checked again.
+    pub fn mark_all(&mut self, len: usize) {
+        self.dirty = Some(0..len);
+    }
+
+    /// Forget the misspelled words and what's left to check.
+    pub fn clear(&mut self) {
+        self.words.clear();
+        self.dirty = None;
+    }
+
+    /// Move the words along with an edit, dropping the ones it touched, which
+    /// are checked again with the text it inserted.
+    pub fn apply_delta(&mut self, delta: &RopeDelta) {
+        let (iv, new_len) = delta.summary();
+        self.words = std::mem::take(&mut self.words)
+            .into_iter()
+            .filter(|word| word.end < iv.start() || word.start > iv.end())
+            .map(|word| {
+                let mut transformer = Transformer::new(delta);
+                transformer.transform(word.start, false)
+                    ..transformer.transform(word.end, true)
+            })
+            .collect();
+
+        let inserted = iv.start()..iv.start() + new_len;
+        self.dirty = Some(match self.dirty.take() {
+            Some(dirty) => {
+                let mut transformer = Transformer::new(delta);
+                let start = transformer.transform(dirty.start, false);
+                let end = transformer.transform(dirty.end, true);
+                start.min(inserted.start)..end.max(inserted.end)
+            }
+            None => inserted,
+        });
+    }
+
+    /// Take the misspelled `words` of `range` from a check of the text as it
+    /// is, which covered all that was left to check.
+    pub fn set_checked(&mut self, range: Range<usize>, words: Vec<Range<usize>>) {
+        self.words
+            .retain(|word| word.end <= range.start || word.start >= range.end);
+        let at = self.words.partition_point(|word| word.start < range.start);
+        self.words.splice(at..at, words);
+        self.dirty = None;
+    }
+
+    /// Drop the misspelled words that `is_word` picks, like the ones just
+    /// added to the dictionary, and return their ranges.
+    pub fn forget(
+        &mut self,
+        mut is_word: impl FnMut(&Range<usize>) -> bool,
+    ) -> Vec<Range<usize>> {
+        let (forgotten, kept) = std::mem::take(&mut self.words)
+            .into_iter()
+            .partition(|word| is_word(word));
+        self.words = kept;
+        forgotten
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lapce_xi_rope::{Delta, Interval, Rope};
+
+    use super::*;
+
+    fn edit(len: usize, range: Range<usize>, s: &str) -> RopeDelta {
+        Delta::simple_edit(Interval::new(range.start, range.end), Rope::from(s), len)
+    }
+
+    #[test]
+    fn test_apply_delta() {
+        // "teh cat and teh dog"
+        let mut spell = SpellCheck::default();
+        spell.set_checked(0..19, vec![0..3, 12..15]);
+        assert!(spell.dirty().is_none());
+
+        // "teh cat a and teh dog": the words after the edit move along
+        spell.apply_delta(&edit(19, 8..8, "a "));
+        assert_eq!(spell.words_in(0..21), &[0..3, 14..17]);
+        assert_eq!(spell.dirty(), Some(8..10));
+
+        // "teh cat a and teh dogs": the word next to the edit is checked again
+        spell.apply_delta(&edit(21, 21..21, "s"));
+        assert_eq!(spell.words_in(0..22), &[0..3, 14..17]);
+        spell.apply_delta(&edit(22, 17..17, "x"));
+        assert_eq!(spell.words_in(0..23), &[0..3]);
+        assert_eq!(spell.dirty(), Some(8..23));
+
+        let mut spell = SpellCheck::default();
+        spell.set_checked(0..19, vec![0..3, 12..15]);
+        spell.apply_delta(&edit(19, 4..7, "bird"));
+        assert_eq!(spell.words_in(0..20), &[0..3, 13..16]);
+        assert_eq!(spell.word_at(16), Some(13..16));
+        assert_eq!(spell.dirty(), Some(4..8));
+    }
+
+    #[test]
+    fn test_set_checked() {
+        let mut spell = SpellCheck::default();
+        spell.set_checked(0..30, vec![0..3, 10..13, 20..23]);
+        spell.mark_all(30);
+        spell.set_checked(8..15, vec![9..11]);
+        assert_eq!(spell.words_in(0..30), &[0..3, 9..11, 20..23]);
+        assert_eq!(spell.words_in(10..21), &[9..11, 20..23]);
+        assert!(spell.dirty().is_none());
+
+        let forgotten = spell.forget(|word| word.start == 9);
+        assert_eq!(forgotten, vec![9..11]);
+        assert_eq!(spell.words_in(0..30), &[0..3, 20..23]);
+    }
+}
@@ -0,0 +1,115 @@
+use std::{
+    cell::Cell,
+    ops::Range,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::{Receiver, Sender};
+use lapce_core::syntax::{edit::SyntaxEdit, ParseBudget, Syntax};
+use lapce_xi_rope::Rope;
+use once_cell::sync::Lazy;
+
+use crate::perf::{self, Phase};
+
+/// How long a parse may take before the lines on screen are highlighted and
+/// shown ahead of the rest
+const PARSE_TIME_BUDGET: Duration = Duration::from_millis(30);
+
+/// The most threads syntax is parsed on, however many cores there are
+const MAX_SYNTAX_THREADS: usize = 4;
+
+/// The threads the syntax of every document is parsed on, which are shared so
+/// that opening many files doesn't start a thread for each.
+static SYNTAX_THREADS: Lazy<Sender<(SyntaxJob, Arc<AtomicUsize>)>> =
+    Lazy::new(|| {
+        let (jobs, receiver) = crossbeam_channel::unbounded();
+        let threads = std::thread::available_parallelism()
+            .map_or(1, |n| n.get().min(MAX_SYNTAX_THREADS));
+        for i in 0..threads {
+            let receiver = receiver.clone();
+            std::thread::Builder::new()
+                .name(format!("SyntaxWorker{i}"))
+                .spawn(move || SyntaxWorker::run(receiver))
+                .unwrap();
+        }
+        jobs
+    });
+
+/// A parse for the [`SyntaxWorker`] to do.
+pub struct SyntaxJob {
+    /// The buffer revision the parse is for
+    pub rev: u64,
+    pub text: Rope,
+    /// The last finished parse, which this one carries on from
+    pub syntax: Syntax,
+    /// The syntax edits since `syntax` was parsed
+    pub edits: Option<Vec<SyntaxEdit>>,
+    /// The byte range on screen, which a slow parse highlights first
+    pub visible: Range<usize>,
+    /// Receives the syntax with only the visible range highlighted, for
+    /// parses that run over the time budget
+    pub on_partial: Box<dyn FnOnce(Syntax) + Send>,
+    pub on_done: Box<dyn FnOnce(Syntax) + Send>,
+}
+
+/// The syntax parses of a document, which are done on the shared syntax
+/// threads. A new parse cancels the one in progress, which the buffer has moved
+/// on from.
+pub struct SyntaxWorker {
+    jobs: Sender<(SyntaxJob, Arc<AtomicUsize>)>,
+    /// The cancellation flag of the latest parse
+    cancellation_flag: Cell<Arc<AtomicUsize>>,
+}
+
+impl SyntaxWorker {
+    pub fn new() -> Self {
+        Self {
+            jobs: SYNTAX_THREADS.clone(),
+            cancellation_flag: Cell::new(Arc::new(AtomicUsize::new(0))),
+        }
+    }
+
+    pub fn parse(&self, job: SyntaxJob) {
+        let cancellation_flag = Arc::new(AtomicUsize::new(0));
+        self.cancellation_flag
+            .replace(cancellation_flag.clone())
+            .store(1, Ordering::Relaxed);
+        let _ = self.jobs.send((job, cancellation_flag));
+    }
+
+    fn run(jobs: Receiver<(SyntaxJob, Arc<AtomicUsize>)>) {
+        while let Ok((job, cancellation_flag)) = jobs.recv() {
+            // Only the latest parse of a document is still wanted, and it
+            // carries on from the same finished one as those before it
+            if cancellation_flag.load(Ordering::Relaxed) != 0 {
+                continue;
+            }
+
+            let mut syntax = job.syntax;
+            let budget = ParseBudget {
+                cancellation_flag: &cancellation_flag,
+                deadline: Instant::now() + PARSE_TIME_BUDGET,
+                priority: job.visible,
+                on_partial: job.on_partial,
+            };
+            let timer = perf::time(Phase::Highlight);
+            let parsed = syntax
+                .parse_within(job.rev, job.text, job.edits.as_deref(), Some(budget))
+                .is_ok();
+            drop(timer);
+            if parsed {
+                (job.on_done)(syntax);
+            }
+        }
+    }
+}
+
+impl Drop for SyntaxWorker {
+    fn drop(&mut self) {
+        self.cancellation_flag.get_mut().store(1, Ordering::Relaxed);
+    }
+}
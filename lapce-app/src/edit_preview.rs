@@ -0,0 +1,519 @@
+use std::{ops::Range, path::PathBuf};
+
+use floem::{
+    ext_event::create_ext_action,
+    reactive::{Memo, RwSignal, Scope},
+};
+use lapce_core::buffer::rope_text::{RopeText, RopeTextRef};
+use lapce_rpc::proxy::ProxyResponse;
+use lapce_xi_rope::Rope;
+use lsp_types::TextEdit;
+
+use crate::{main_split::MainSplitData, window_tab::CommonData};
+
+/// A run of lines changed by the edits of a file, as they are and as they
+/// would be once the edits are applied.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EditHunk {
+    /// The first line of the run
+    pub line: usize,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+    /// The edits that change the lines
+    pub edits: Vec<TextEdit>,
+}
+
+/// The edits of `text` gathered in hunks, with the edits touching the same
+/// lines in the same hunk.
+pub fn edit_hunks(text: &Rope, edits: &[TextEdit]) -> Result<Vec<EditHunk>, String> {
+    let rope_text = RopeTextRef::new(text);
+    let last_line = rope_text.last_line();
+    let mut edits = edits
+        .iter()
+        .map(|edit| {
+            // Positions past the end mean the file isn't what the edits were
+            // made for
+            if edit.range.end.line as usize > last_line {
+                return Err("it changed since the edits were made".to_string());
+            }
+            let start = rope_text.offset_of_position(&edit.range.start);
+            let end = rope_text.offset_of_position(&edit.range.end);
+            Ok((start..end.max(start), edit))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    edits.sort_by_key(|(range, _)| range.start);
+    if edits.windows(2).any(|pair| pair[0].0.end > pair[1].0.start) {
+        return Err("the edits overlap".to_string());
+    }
+
+    let mut hunks = Vec::new();
+    let mut group: Vec<(Range<usize>, &TextEdit)> = Vec::new();
+    let mut end_line = 0;
+    for (range, edit) in edits {
+        let start_line = rope_text.line_of_offset(range.start);
+        if !group.is_empty() && start_line > end_line {
+            hunks.push(edit_hunk(&rope_text, &group, end_line));
+            group.clear();
+        }
+        let range_end_line = rope_text.line_of_offset(range.end);
+        end_line = if group.is_empty() {
+            range_end_line
+        } else {
+            end_line.max(range_end_line)
+        };
+        group.push((range, edit));
+    }
+    if !group.is_empty() {
+        hunks.push(edit_hunk(&rope_text, &group, end_line));
+    }
+    Ok(hunks)
+}
+
+fn edit_hunk(
+    text: &RopeTextRef,
+    edits: &[(Range<usize>, &TextEdit)],
+    end_line: usize,
+) -> EditHunk {
+    let line = text.line_of_offset(edits[0].0.start);
+    let start = text.offset_of_line(line);
+    let end = text.offset_of_line(end_line + 1);
+    let mut after = String::new();
+    let mut last = start;
+    for (range, edit) in edits {
+        after.push_str(&text.slice_to_cow(last..range.start));
+        after.push_str(&edit.new_text);
+        last = range.end;
+    }
+    after.push_str(&text.slice_to_cow(last..end));
+    EditHunk {
+        line,
+        before: lines(&text.slice_to_cow(start..end)),
+        after: lines(&after),
+        edits: edits.iter().map(|(_, edit)| (*edit).clone()).collect(),
+    }
+}
+
+fn lines(text: &str) -> Vec<String> {
+    text.lines().map(|line| line.to_string()).collect()
+}
+
+/// Whether the lines of the hunk in `text` are still what they were when
+/// the hunk was worked out.
+pub fn hunk_matches(text: &Rope, hunk: &EditHunk) -> bool {
+    let rope_text = RopeTextRef::new(text);
+    let end_line = hunk.line + hunk.before.len();
+    if end_line > rope_text.last_line() + 1 {
+        return false;
+    }
+    let start = rope_text.offset_of_line(hunk.line);
+    let end = rope_text.offset_of_line(end_line);
+    lines(&rope_text.slice_to_cow(start..end)) == hunk.before
+}
+
+/// The hunks of the edits of a file, with the version of the document the
+/// edits were made for
+type FileHunks = (PathBuf, Option<i32>, Result<Vec<EditHunk>, String>);
+
+/// The hunks of the edits of each file, in the text of its document if it's
+/// open, or else in its content on disk out of `contents`.
+fn file_hunks(
+    files: Vec<(PathBuf, Option<i32>, Vec<TextEdit>, Option<Rope>)>,
+    contents: Vec<(PathBuf, Result<String, String>)>,
+) -> Vec<FileHunks> {
+    files
+        .into_iter()
+        .map(|(path, version, edits, text)| {
+            let text = match text {
+                Some(text) => Ok(text),
+                None => contents
+                    .iter()
+                    .find(|(p, _)| p == &path)
+                    .map(|(_, content)| content.clone())
+                    .unwrap_or_else(|| Err("it couldn't be read".to_string()))
+                    .map(|content| Rope::from(content.as_str())),
+            };
+            let hunks = text.and_then(|text| edit_hunks(&text, &edits));
+            (path, version, hunks)
+        })
+        .collect()
+}
+
+/// A file of the previewed edit.
+#[derive(Clone)]
+pub struct PreviewFile {
+    pub path: PathBuf,
+    /// The version of the document the edits were made for, if known
+    pub version: Option<i32>,
+    /// The hunks of the edits, or why they couldn't be worked out
+    pub hunks: Result<Vec<EditHunk>, String>,
+    /// The indexes of the hunks that are left out
+    pub excluded: RwSignal<im::HashSet<usize>>,
+}
+
+impl PreviewFile {
+    /// The hunks that are to be applied.
+    fn included_hunks(&self) -> Vec<EditHunk> {
+        let Ok(hunks) = self.hunks.as_ref() else {
+            return Vec::new();
+        };
+        let excluded = self.excluded.get_untracked();
+        hunks
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !excluded.contains(i))
+            .map(|(_, hunk)| hunk.clone())
+            .collect()
+    }
+}
+
+/// A row of the edit preview: a file, why its edits can't be applied, a hunk
+/// or a line of a hunk, which is either removed or added.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PreviewRow {
+    File(usize),
+    Error(usize),
+    Hunk(usize, usize),
+    Line {
+        file: usize,
+        hunk: usize,
+        added: bool,
+        index: usize,
+    },
+}
+
+/// The edits of a rename or code action that touch several files, shown
+/// before they're applied so that some of the hunks can be left out.
+#[derive(Clone)]
+pub struct EditPreviewData {
+    pub scope: Scope,
+    /// What the edits are from, like `Rename`
+    pub title: RwSignal<&'static str>,
+    pub files: RwSignal<im::Vector<PreviewFile>>,
+    /// The files that couldn't be edited at all
+    pub failures: RwSignal<Vec<String>>,
+    /// Whether the hunks are still being worked out
+    pub loading: RwSignal<bool>,
+    /// Bumped by every preview, so that a preview still being worked out
+    /// when another one starts is dropped, and the rows of the old files
+    /// aren't reused for the new ones
+    pub generation: RwSignal<usize>,
+    pub rows: Memo<im::Vector<PreviewRow>>,
+    pub main_split: MainSplitData,
+    pub common: CommonData,
+}
+
+impl EditPreviewData {
+    pub fn new(cx: Scope, main_split: MainSplitData, common: CommonData) -> Self {
+        let files: RwSignal<im::Vector<PreviewFile>> =
+            cx.create_rw_signal(im::Vector::new());
+        let rows = cx.create_memo(move |_| {
+            files.with(|files| {
+                let mut rows = im::Vector::new();
+                for (file, preview_file) in files.iter().enumerate() {
+                    rows.push_back(PreviewRow::File(file));
+                    let hunks = match preview_file.hunks.as_ref() {
+                        Ok(hunks) => hunks,
+                        Err(_) => {
+                            rows.push_back(PreviewRow::Error(file));
+                            continue;
+                        }
+                    };
+                    for (hunk, edit_hunk) in hunks.iter().enumerate() {
+                        rows.push_back(PreviewRow::Hunk(file, hunk));
+                        for (added, lines) in
+                            [(false, &edit_hunk.before), (true, &edit_hunk.after)]
+                        {
+                            for index in 0..lines.len() {
+                                rows.push_back(PreviewRow::Line {
+                                    file,
+                                    hunk,
+                                    added,
+                                    index,
+                                });
+                            }
+                        }
+                    }
+                }
+                rows
+            })
+        });
+        Self {
+            scope: cx,
+            title: cx.create_rw_signal(""),
+            files,
+            failures: cx.create_rw_signal(Vec::new()),
+            loading: cx.create_rw_signal(false),
+            generation: cx.create_rw_signal(0),
+            rows,
+            main_split,
+            common,
+        }
+    }
+
+    /// Work out the hunks of the edits of each file, from the open document
+    /// or else from the file on disk, without changing either.
+    pub fn preview(
+        &self,
+        title: &'static str,
+        files: Vec<(PathBuf, Option<i32>, Vec<TextEdit>)>,
+        failures: Vec<String>,
+    ) {
+        self.title.set(title);
+        self.failures.set(failures);
+        self.files.set(im::Vector::new());
+        self.loading.set(true);
+        self.generation.update(|generation| *generation += 1);
+        let generation = self.generation.get_untracked();
+
+        let docs = self.main_split.docs.get_untracked();
+        let files = files
+            .into_iter()
+            .map(|(path, version, edits)| {
+                let text = docs
+                    .get(&path)
+                    .filter(|doc| doc.with_untracked(|doc| doc.loaded()))
+                    .map(|doc| {
+                        doc.with_untracked(|doc| doc.buffer().text().clone())
+                    });
+                (path, version, edits, text)
+            })
+            .collect::<Vec<_>>();
+
+        let scope = self.scope;
+        let preview_files = self.files;
+        let loading = self.loading;
+        let generation_signal = self.generation;
+        let send = create_ext_action(self.scope, move |hunks: Vec<FileHunks>| {
+            if generation_signal.get_untracked() != generation {
+                return;
+            }
+            preview_files.set(
+                hunks
+                    .into_iter()
+                    .map(|(path, version, hunks)| PreviewFile {
+                        path,
+                        version,
+                        hunks,
+                        excluded: scope.create_rw_signal(im::HashSet::new()),
+                    })
+                    .collect(),
+            );
+            loading.set(false);
+        });
+        let on_disk = files
+            .iter()
+            .filter(|(_, _, _, text)| text.is_none())
+            .map(|(path, _, _, _)| path.clone())
+            .collect::<Vec<_>>();
+        if on_disk.is_empty() {
+            rayon::spawn(move || send(file_hunks(files, Vec::new())));
+            return;
+        }
+        self.common.proxy.read_files(on_disk, move |result| {
+            let contents = match result {
+                Ok(ProxyResponse::ReadFilesResponse { files }) => files,
+                _ => Vec::new(),
+            };
+            send(file_hunks(files, contents));
+        });
+    }
+
+    /// Leave out the hunk, or put it back in.
+    pub fn toggle_hunk(&self, file: usize, hunk: usize) {
+        let Some(preview_file) =
+            self.files.with_untracked(|files| files.get(file).cloned())
+        else {
+            return;
+        };
+        preview_file.excluded.update(|excluded| {
+            if excluded.remove(&hunk).is_none() {
+                excluded.insert(hunk);
+            }
+        });
+    }
+
+    /// Leave out all the hunks of the file if any is included, or else put
+    /// them all back in.
+    pub fn toggle_file(&self, file: usize) {
+        let Some(preview_file) =
+            self.files.with_untracked(|files| files.get(file).cloned())
+        else {
+            return;
+        };
+        let len = preview_file.hunks.as_ref().map(|h| h.len()).unwrap_or(0);
+        preview_file.excluded.update(|excluded| {
+            if excluded.len() < len {
+                *excluded = (0..len).collect();
+            } else {
+                excluded.clear();
+            }
+        });
+    }
+
+    pub fn discard(&self) {
+        self.generation.update(|generation| *generation += 1);
+        self.files.set(im::Vector::new());
+        self.failures.set(Vec::new());
+        self.loading.set(false);
+    }
+
+    /// Apply the hunks that are included. The lines of each hunk are checked
+    /// again first, and a file whose lines changed since the preview is left
+    /// alone.
+    pub fn apply(&self) {
+        let title = self.title.get_untracked();
+        let mut failures = self.failures.get_untracked();
+        let files = self
+            .files
+            .get_untracked()
+            .into_iter()
+            .filter_map(|file| {
+                if let Err(err) = file.hunks.as_ref() {
+                    failures.push(format!("{}: {err}", file.path.display()));
+                    return None;
+                }
+                let hunks = file.included_hunks();
+                (!hunks.is_empty()).then_some((file.path, file.version, hunks))
+            })
+            .collect::<Vec<_>>();
+        self.discard();
+        if files.is_empty() {
+            return;
+        }
+
+        let docs = self.main_split.docs.get_untracked();
+        let mut open = Vec::new();
+        let mut on_disk = Vec::new();
+        for (path, version, hunks) in files {
+            let text = docs
+                .get(&path)
+                .filter(|doc| doc.with_untracked(|doc| doc.loaded()))
+                .map(|doc| doc.with_untracked(|doc| doc.buffer().text().clone()));
+            match text {
+                Some(text) => open.push((path, version, hunks, text)),
+                None => on_disk.push((path, version, hunks)),
+            }
+        }
+        let mut checked = Vec::new();
+        for (path, version, hunks, text) in open {
+            match checked_edits(&text, hunks) {
+                Some(edits) => checked.push((path, version, edits)),
+                None => failures.push(changed_since_preview(&path)),
+            }
+        }
+
+        let main_split = self.main_split.clone();
+        if on_disk.is_empty() {
+            main_split.apply_file_edits(checked, failures, title, true);
+            return;
+        }
+
+        let send = create_ext_action(
+            self.scope,
+            move |(disk_checked, disk_failures): (
+                Vec<(PathBuf, Option<i32>, Vec<TextEdit>)>,
+                Vec<String>,
+            )| {
+                let mut checked = checked.clone();
+                checked.extend(disk_checked);
+                let mut failures = failures.clone();
+                failures.extend(disk_failures);
+                main_split.apply_file_edits(checked, failures, title, true);
+            },
+        );
+        let paths = on_disk.iter().map(|(path, _, _)| path.clone()).collect();
+        self.common.proxy.read_files(paths, move |result| {
+            let contents = match result {
+                Ok(ProxyResponse::ReadFilesResponse { files }) => files,
+                _ => Vec::new(),
+            };
+            let mut checked = Vec::new();
+            let mut failures = Vec::new();
+            for (path, version, hunks) in on_disk {
+                let text = contents
+                    .iter()
+                    .find(|(p, _)| p == &path)
+                    .and_then(|(_, content)| content.as_ref().ok())
+                    .map(|content| Rope::from(content.as_str()));
+                match text.and_then(|text| checked_edits(&text, hunks)) {
+                    Some(edits) => checked.push((path, version, edits)),
+                    None => failures.push(changed_since_preview(&path)),
+                }
+            }
+            send((checked, failures));
+        });
+    }
+}
+
+/// The edits of the hunks, if their lines in `text` are still the same.
+fn checked_edits(text: &Rope, hunks: Vec<EditHunk>) -> Option<Vec<TextEdit>> {
+    if !hunks.iter().all(|hunk| hunk_matches(text, hunk)) {
+        return None;
+    }
+    Some(hunks.into_iter().flat_map(|hunk| hunk.edits).collect())
+}
+
+fn changed_since_preview(path: &std::path::Path) -> String {
+    format!("{}: it changed since the preview", path.display())
+}
+
+#[cfg(test)]
+mod tests {
+    use lapce_xi_rope::Rope;
+    use lsp_types::{Position, Range, TextEdit};
+
+    use super::{edit_hunks, hunk_matches};
+
+    fn edit(line: u32, start: u32, end: u32, new_text: &str) -> TextEdit {
+        TextEdit {
+            range: Range::new(Position::new(line, start), Position::new(line, end)),
+            new_text: new_text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_edit_hunks() {
+        let text = Rope::from("let foo = 1;\nfoo + foo\n\nprint(foo)\n");
+        let edits = [
+            edit(3, 6, 9, "bar"),
+            edit(0, 4, 7, "bar"),
+            edit(1, 0, 3, "bar"),
+            edit(1, 6, 9, "bar"),
+        ];
+        let hunks = edit_hunks(&text, &edits).unwrap();
+        assert_eq!(hunks.len(), 3);
+        assert_eq!(hunks[0].line, 0);
+        assert_eq!(hunks[0].before, vec!["let foo = 1;"]);
+        assert_eq!(hunks[0].after, vec!["let bar = 1;"]);
+        assert_eq!(hunks[1].line, 1);
+        assert_eq!(hunks[1].before, vec!["foo + foo"]);
+        assert_eq!(hunks[1].after, vec!["bar + bar"]);
+        assert_eq!(hunks[1].edits.len(), 2);
+        assert_eq!(hunks[2].line, 3);
+        assert_eq!(hunks[2].after, vec!["print(bar)"]);
+
+        // An edit across lines makes a single hunk of them
+        let edits = [TextEdit {
+            range: Range::new(Position::new(0, 11), Position::new(1, 0)),
+            new_text: " ".to_string(),
+        }];
+        let hunks = edit_hunks(&text, &edits).unwrap();
+        assert_eq!(hunks[0].before, vec!["let foo = 1;", "foo + foo"]);
+        assert_eq!(hunks[0].after, vec!["let foo = 1 foo + foo"]);
+
+        assert!(edit_hunks(&text, &[edit(9, 0, 1, "")]).is_err());
+        assert!(edit_hunks(&text, &[edit(0, 0, 5, ""), edit(0, 2, 3, "")]).is_err());
+    }
+
+    #[test]
+    fn test_hunk_matches() {
+        let text = Rope::from("let foo = 1;\nfoo + foo\n");
+        let hunks = edit_hunks(&text, &[edit(1, 0, 3, "bar")]).unwrap();
+        assert!(hunk_matches(&text, &hunks[0]));
+        assert!(!hunk_matches(
+            &Rope::from("\nlet foo = 1;\nfoo + foo\n"),
+            &hunks[0]
+        ));
+        assert!(!hunk_matches(&Rope::from("let foo = 1;\n"), &hunks[0]));
+    }
+}
@@ -1,69 +1,124 @@
 use std::{
-    cmp::Ordering, collections::HashMap, str::FromStr, sync::Arc, time::Duration,
+    cmp::Ordering,
+    collections::HashMap,
+    path::PathBuf,
+    rc::Rc,
+    str::FromStr,
+    sync::{atomic::AtomicU64, Arc},
+    time::Duration,
 };
 
 use anyhow::Result;
 use floem::{
-    action::{exec_after, show_context_menu, TimerToken},
+    action::{exec_after, save_as, show_context_menu, TimerToken},
     ext_event::create_ext_action,
+    file::{FileDialogOptions, FileInfo},
     keyboard::ModifiersState,
     menu::{Menu, MenuItem},
     peniko::kurbo::{Point, Rect, Vec2},
     pointer::{PointerButton, PointerInputEvent, PointerMoveEvent},
     reactive::{use_context, ReadSignal, RwSignal, Scope},
 };
+use itertools::Itertools;
 use lapce_core::{
-    buffer::{diff::DiffLines, rope_text::RopeText, InvalLines},
-    command::{EditCommand, FocusCommand, MotionModeCommand, MultiSelectionCommand},
+    buffer::{
+        diff::{
+            diff_hunks, map_line, rope_diff, DiffHunk, DiffLines, DiffOptions,
+            LineDifference,
+        },
+        rope_text::{RopeText, RopeTextVal},
+        InvalLines,
+    },
+    color_literal::{color_literals, ColorLiteral},
+    command::{
+        EditCommand, FocusCommand, MotionModeCommand, MoveCommand,
+        MultiSelectionCommand,
+    },
     cursor::{Cursor, CursorMode},
     editor::EditType,
+    language::LapceLanguage,
     mode::{Mode, MotionMode},
+    motion::{self, OperatorAction},
     movement::Movement,
+    register::Clipboard,
+    replace::preserve_case,
     selection::{InsertDrift, Selection},
     syntax::edit::SyntaxEdit,
 };
-use lapce_rpc::{buffer::BufferId, plugin::PluginId, proxy::ProxyResponse};
+use lapce_rpc::{
+    buffer::BufferId, plugin::PluginId, proxy::ProxyResponse,
+    source_control::BlameCommit, RpcError,
+};
 use lapce_xi_rope::{Rope, RopeDelta, Transformer};
 use lsp_types::{
-    CompletionItem, CompletionTextEdit, GotoDefinitionResponse, HoverContents,
-    Location, MarkedString, MarkupKind, TextEdit,
+    CodeActionKind, CodeActionOrCommand, CompletionItem, CompletionTextEdit,
+    Diagnostic, GotoDefinitionResponse, HoverContents, Location, MarkedString,
+    MarkupKind, MessageType, ShowMessageParams, TextEdit,
 };
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    alert::AlertButton,
+    code_action::is_kind_of,
     command::{
         CommandExecuted, CommandKind, InternalCommand, LapceCommand,
         LapceWorkbenchCommand,
     },
     completion::{clear_completion_lens, CompletionStatus},
-    config::LapceConfig,
+    config::{color::LapceColor, LapceConfig},
     db::LapceDb,
-    doc::{DocContent, Document},
+    doc::{
+        DocContent, Document, EditorViewState, PrimarySelection, SystemClipboard,
+    },
     editor::location::{EditorLocation, EditorPosition},
     editor_tab::EditorTabChild,
+    feedback::{beep, ErrorFeedback, FLASH_DURATION},
+    find::{Find, FindProgress},
     id::{DiffEditorId, EditorId, EditorTabId},
+    jump::{label_targets, line_targets, match_label, Jump, JumpKind, JumpMatch},
     keypress::{condition::Condition, KeyPressFocus},
+    listener::Listener,
     main_split::{MainSplitData, SplitDirection, SplitMoveDirection},
     markdown::{
-        from_marked_string, from_plaintext, parse_markdown, MarkdownContent,
+        from_marked_string, from_plaintext, parse_markdown, parse_markdown_in,
+        MarkdownContent,
     },
     proxy::path_from_url,
     snippet::Snippet,
+    spell,
     window_tab::{CommonData, Focus, WindowTabData},
 };
 
 use self::{
+    blame::blame_hunk,
+    copy::{char_column, location_reference, relative_path, with_line_numbers},
+    html::{render_html, HtmlLine, HtmlTheme},
     view::{DiffSection, DiffSectionKind, LineInfo, ScreenLines},
-    view_data::{EditorViewData, EditorViewKind},
+    view_data::{EditorViewData, EditorViewKind, ViewOptions},
 };
 
+pub mod blame;
+pub mod copy;
 pub mod diff;
 pub mod gutter;
+pub mod html;
 pub mod location;
+pub mod minimap;
 pub mod movement;
 pub mod view;
 pub mod view_data;
 
+/// How long the typing has to settle for before the signature of the call is
+/// asked for
+const SIGNATURE_HELP_DELAY: Duration = Duration::from_millis(50);
+
+/// How long the cursor has to stay put before the code actions at it are
+/// asked for
+const CODE_ACTIONS_DELAY: Duration = Duration::from_millis(250);
+
+/// How long formatting may take before the file is saved without it
+const FORMAT_ON_SAVE_TIMEOUT: Duration = Duration::from_secs(1);
+
 #[derive(Clone, Debug)]
 pub enum InlineFindDirection {
     Left,
@@ -76,6 +131,9 @@ pub struct EditorInfo {
     // pub unsaved: Option<String>,
     pub offset: usize,
     pub scroll_offset: (f64, f64),
+    /// How the editor displays the document where it differs from the settings
+    #[serde(default)]
+    pub options: ViewOptions,
 }
 
 impl EditorInfo {
@@ -128,6 +186,7 @@ pub fn to_data(
                         let content = DocContent::Scratch {
                             id: BufferId::next(),
                             name: name.to_string(),
+                            read_only: false,
                         };
                         let doc = Document::new_content(
                             data.scope,
@@ -152,6 +211,7 @@ pub fn to_data(
                 )
             }
         };
+        editor_data.view.options.set(self.options);
         let editor_data = editor_data.scope.create_rw_signal(editor_data);
         data.editors.update(|editors| {
             editors.insert(editor_id, editor_data);
@@ -162,6 +222,23 @@ pub fn to_data(
 
 pub type SnippetIndex = Vec<(usize, (usize, usize))>;
 
+/// A shift of lines, kept for it to be repeated at the cursor
+#[derive(Clone, Debug, PartialEq)]
+pub enum LastShift {
+    /// An operator given a motion and its count, like `>ip`, or doubled
+    /// when there's no motion, like `>>`
+    Operator {
+        motion_mode: MotionMode,
+        movement: Option<(Movement, usize)>,
+    },
+    /// The `lines` lines of a visual selection shifted by `units` indents
+    Visual {
+        lines: usize,
+        units: usize,
+        outdent: bool,
+    },
+}
+
 #[derive(Clone)]
 pub struct EditorData {
     pub scope: Scope,
@@ -179,9 +256,18 @@ pub struct EditorData {
     pub last_movement: RwSignal<Movement>,
     pub inline_find: RwSignal<Option<InlineFindDirection>>,
     pub last_inline_find: RwSignal<Option<(InlineFindDirection, String)>>,
+    /// The jump whose labels are shown, if any
+    pub jump: RwSignal<Option<Jump>>,
+    /// The last shift of lines, which `.` repeats
+    pub last_shift: RwSignal<Option<LastShift>>,
     pub find_focus: RwSignal<bool>,
     pub active: RwSignal<bool>,
     pub sticky_header_height: RwSignal<f64>,
+    /// The timer of the code actions request waiting for the cursor to settle
+    pub code_actions_timer: RwSignal<TimerToken>,
+    /// The timer of the flash of the gutter, which is invalid when it isn't
+    /// flashed
+    pub error_flash: RwSignal<TimerToken>,
     pub common: CommonData,
 }
 
@@ -216,6 +302,7 @@ pub fn new(
         let view = EditorViewData::new(
             doc,
             cx.create_rw_signal(EditorViewKind::Normal),
+            cx.create_rw_signal(ViewOptions::default()),
             common.config,
         );
         {
@@ -243,9 +330,13 @@ pub fn new(
             last_movement: cx.create_rw_signal(Movement::Left),
             inline_find: cx.create_rw_signal(None),
             last_inline_find: cx.create_rw_signal(None),
+            jump: cx.create_rw_signal(None),
+            last_shift: cx.create_rw_signal(None),
             find_focus: cx.create_rw_signal(false),
             active: cx.create_rw_signal(false),
             sticky_header_height: cx.create_rw_signal(0.0),
+            code_actions_timer: cx.create_rw_signal(TimerToken::INVALID),
+            error_flash: cx.create_rw_signal(TimerToken::INVALID),
             common,
         }
     }
@@ -269,6 +360,7 @@ pub fn editor_info(&self, _data: &WindowTabData) -> EditorInfo {
             content: self.view.doc.get_untracked().content,
             offset,
             scroll_offset: (scroll_offset.x, scroll_offset.y),
+            options: self.view.options.get_untracked(),
         }
     }
 
@@ -314,13 +406,40 @@ pub fn copy(
             last_movement: cx.create_rw_signal(self.last_movement.get_untracked()),
             inline_find: cx.create_rw_signal(None),
             last_inline_find: cx.create_rw_signal(None),
+            jump: cx.create_rw_signal(None),
+            last_shift: cx.create_rw_signal(None),
             find_focus: cx.create_rw_signal(false),
             active: cx.create_rw_signal(false),
             sticky_header_height: cx.create_rw_signal(0.0),
+            code_actions_timer: cx.create_rw_signal(TimerToken::INVALID),
+            error_flash: cx.create_rw_signal(TimerToken::INVALID),
             common: self.common.clone(),
         }
     }
 
+    /// Make it known that a command couldn't be done, the way the
+    /// `error-feedback` setting says.
+    pub fn notify_error_feedback(&self) {
+        let setting = self
+            .common
+            .config
+            .with_untracked(|config| config.editor.error_feedback.clone());
+        match ErrorFeedback::from_setting(&setting) {
+            ErrorFeedback::Flash => {
+                let error_flash = self.error_flash;
+                let token = exec_after(FLASH_DURATION, move |token| {
+                    // Only the last flash ends it
+                    if error_flash.get_untracked() == token {
+                        error_flash.set(TimerToken::INVALID);
+                    }
+                });
+                error_flash.set(token);
+            }
+            ErrorFeedback::Beep => beep(),
+            ErrorFeedback::None => {}
+        }
+    }
+
     fn run_edit_command(&self, cmd: &EditCommand) -> CommandExecuted {
         let modal = self
             .common
@@ -338,17 +457,6 @@ fn run_edit_command(&self, cmd: &EditCommand) -> CommandExecuted {
         let mut cursor = self.cursor.get_untracked();
         let mut register = self.common.register.get_untracked();
 
-        let yank_data =
-            if let lapce_core::cursor::CursorMode::Visual { .. } = &cursor.mode {
-                Some(
-                    self.view
-                        .doc
-                        .with_untracked(|doc| cursor.yank(doc.buffer())),
-                )
-            } else {
-                None
-            };
-
         let deltas = self
             .view
             .doc
@@ -357,12 +465,6 @@ fn run_edit_command(&self, cmd: &EditCommand) -> CommandExecuted {
             })
             .unwrap();
 
-        if !deltas.is_empty() {
-            if let Some(data) = yank_data {
-                register.add_delete(data);
-            }
-        }
-
         self.cursor.set(cursor);
         self.common.register.set(register);
 
@@ -384,19 +486,77 @@ fn run_motion_mode_command(
         cmd: &MotionModeCommand,
         count: Option<usize>,
     ) -> CommandExecuted {
-        let count = count.unwrap_or(1);
-        let motion_mode = match cmd {
-            MotionModeCommand::MotionModeDelete => MotionMode::Delete { count },
-            MotionModeCommand::MotionModeIndent => MotionMode::Indent,
-            MotionModeCommand::MotionModeOutdent => MotionMode::Outdent,
-            MotionModeCommand::MotionModeYank => MotionMode::Yank { count },
+        let width = if let MotionModeCommand::MotionModeReflow = cmd {
+            let config = self.common.config.get_untracked();
+            self.view.doc.with_untracked(|doc| doc.text_width(&config))
+        } else {
+            0
         };
         let mut cursor = self.cursor.get_untracked();
         let mut register = self.common.register.get_untracked();
 
-        self.view.doc.update(|doc| {
-            movement::do_motion_mode(doc, &mut cursor, motion_mode, &mut register);
+        // In visual mode the selected lines are rewrapped or shifted right
+        // away, rather than waiting for a motion
+        let right_away = matches!(cursor.mode, CursorMode::Visual { .. })
+            && matches!(
+                cmd,
+                MotionModeCommand::MotionModeReflow
+                    | MotionModeCommand::MotionModeIndent
+                    | MotionModeCommand::MotionModeOutdent
+            );
+        let pending = cursor.motion_mode.clone();
+        let action = self.view.doc.with_untracked(|doc| {
+            motion::run_motion_mode(doc.buffer(), &mut cursor, cmd, count, width)
         });
+        let doubled = matches!(action, Some(OperatorAction::Edit(_)));
+        match action {
+            Some(OperatorAction::Edit(edit)) => {
+                self.view.doc.update(|doc| {
+                    doc.execute_motion_mode(
+                        &mut cursor,
+                        edit.motion_mode,
+                        edit.start,
+                        edit.end,
+                        edit.is_vertical,
+                        &mut register,
+                    );
+                });
+            }
+            Some(OperatorAction::Shift {
+                lines,
+                count,
+                outdent,
+            }) => {
+                let shifted = lines.end() - lines.start() + 1;
+                let done = self.view.doc.try_update(|doc| {
+                    doc.shift_lines(&mut cursor, lines, count, outdent)
+                });
+                if done.is_some() {
+                    self.last_shift.set(Some(LastShift::Visual {
+                        lines: shifted,
+                        units: count,
+                        outdent,
+                    }));
+                }
+            }
+            None => {}
+        }
+
+        if let (false, Some(pending)) = (right_away, pending) {
+            if !doubled {
+                // Another operator, like `dy`, drops the pending one
+                self.notify_error_feedback();
+            } else if matches!(
+                pending,
+                MotionMode::Indent { .. } | MotionMode::Outdent { .. }
+            ) {
+                // The operator doubled, like `>>`
+                self.last_shift.set(Some(LastShift::Operator {
+                    motion_mode: pending,
+                    movement: None,
+                }));
+            }
+        }
 
         self.cursor.set(cursor);
         self.common.register.set(register);
@@ -404,14 +564,86 @@ fn run_motion_mode_command(
         CommandExecuted::Yes
     }
 
+    /// Shift the lines at the cursor the way `shift` shifted others.
+    fn repeat_shift(&self, shift: LastShift) {
+        let mut cursor = self.cursor.get_untracked();
+        if !matches!(cursor.mode, CursorMode::Normal(_)) {
+            return;
+        }
+        match shift {
+            LastShift::Operator {
+                motion_mode,
+                movement: Some((movement, count)),
+            } => {
+                cursor.motion_mode = Some(motion_mode);
+                self.common.register.update(|register| {
+                    movement::move_cursor(
+                        &self.view,
+                        &mut cursor,
+                        &movement,
+                        count,
+                        false,
+                        register,
+                    )
+                });
+            }
+            LastShift::Operator {
+                motion_mode,
+                movement: None,
+            } => {
+                cursor.motion_mode = Some(motion_mode.clone());
+                self.common.register.update(|register| {
+                    self.view.doc.update(|doc| {
+                        movement::do_motion_mode(
+                            doc,
+                            &mut cursor,
+                            motion_mode,
+                            register,
+                        );
+                    });
+                });
+            }
+            LastShift::Visual {
+                lines,
+                units,
+                outdent,
+            } => {
+                self.view.doc.update(|doc| {
+                    let line = doc.buffer().line_of_offset(cursor.offset());
+                    doc.shift_lines(
+                        &mut cursor,
+                        line..=line + lines - 1,
+                        units,
+                        outdent,
+                    );
+                });
+            }
+        }
+        self.cursor.set(cursor);
+    }
+
     fn run_multi_selection_command(
         &self,
         cmd: &MultiSelectionCommand,
     ) -> CommandExecuted {
         let mut cursor = self.cursor.get_untracked();
-        movement::do_multi_selection(&self.view, &mut cursor, cmd);
+        let truncated = movement::do_multi_selection(&self.view, &mut cursor, cmd);
         self.cursor.set(cursor);
-        // self.cancel_signature();
+        if truncated {
+            self.common
+                .internal_command
+                .send(InternalCommand::ShowMessage {
+                    title: "Select All Occurrences".to_string(),
+                    message: ShowMessageParams {
+                        typ: MessageType::WARNING,
+                        message: format!(
+                            "Only the first {} occurrences were selected",
+                            movement::MAX_OCCURRENCE_CURSORS
+                        ),
+                    },
+                });
+        }
+        self.cancel_signature();
         self.cancel_completion();
         CommandExecuted::Yes
     }
@@ -442,6 +674,8 @@ fn run_move_command(
         self.last_movement.set(movement.clone());
 
         let mut cursor = self.cursor.get_untracked();
+        let pending = cursor.motion_mode.clone();
+        let offset = cursor.offset();
         self.common.register.update(|register| {
             movement::move_cursor(
                 &self.view,
@@ -452,6 +686,26 @@ fn run_move_command(
                 register,
             )
         });
+        // A counted motion that goes as far as it can isn't an error, only
+        // one that can't move at all
+        if count.is_some()
+            && pending.is_none()
+            && cursor.offset() == offset
+            && !movement.is_jump()
+        {
+            self.notify_error_feedback();
+        }
+        if let Some(
+            motion_mode @ (MotionMode::Indent { .. } | MotionMode::Outdent { .. }),
+        ) = pending
+        {
+            if cursor.motion_mode.is_none() {
+                self.last_shift.set(Some(LastShift::Operator {
+                    motion_mode,
+                    movement: Some((movement.clone(), count.unwrap_or(1))),
+                }));
+            }
+        }
 
         self.cursor.set(cursor);
 
@@ -471,6 +725,10 @@ fn run_move_command(
             })
         }
         self.cancel_completion();
+        // Only a signature already shown follows the cursor around
+        if self.common.signature.active.get_untracked() {
+            self.update_signature();
+        }
         CommandExecuted::Yes
     }
 
@@ -678,7 +936,7 @@ pub fn run_focus_command(
                         if last_placeholder {
                             *snippet = None;
                         }
-                        // self.update_signature();
+                        self.update_signature();
                         self.cancel_completion();
                     }
                 });
@@ -710,14 +968,91 @@ pub fn run_focus_command(
                                     cursor.set_insert(selection);
                                 });
                             }
-                            // self.update_signature();
+                            self.update_signature();
                             self.cancel_completion();
                         }
                     }
                 });
             }
             FocusCommand::GotoDefinition => {
-                self.go_to_definition();
+                self.go_to_definition(false);
+            }
+            FocusCommand::FormatDocument => {
+                self.format();
+            }
+            FocusCommand::OrganizeImports => {
+                self.run_source_action(
+                    CodeActionKind::SOURCE_ORGANIZE_IMPORTS,
+                    "Organize Imports",
+                );
+            }
+            FocusCommand::FixAll => {
+                self.run_source_action(
+                    CodeActionKind::from("source.fixAll"),
+                    "Fix All",
+                );
+            }
+            FocusCommand::ShowHover => {
+                let start_offset = self.view.doc.with_untracked(|doc| {
+                    let offset = self.cursor.with_untracked(|c| c.offset());
+                    doc.buffer().prev_code_boundary(offset)
+                });
+                self.update_hover(start_offset);
+            }
+            FocusCommand::GotoTypeDefinition => {
+                self.go_to_definition(true);
+            }
+            FocusCommand::FindReferences => {
+                self.find_references();
+            }
+            FocusCommand::NextDiff => {
+                self.jump_to_head_change(true);
+            }
+            FocusCommand::PreviousDiff => {
+                self.jump_to_head_change(false);
+            }
+            FocusCommand::RevertHunk => {
+                self.revert_hunk();
+            }
+            FocusCommand::CopyBlameCommitHash => {
+                if let Some(commit) = self.blame_commit() {
+                    let mut clipboard = SystemClipboard::new();
+                    clipboard.put_string(commit.id);
+                }
+            }
+            FocusCommand::ShowBlameCommit => {
+                self.show_blame_commit();
+            }
+            FocusCommand::ExportHtml => {
+                self.export_html();
+            }
+            FocusCommand::CopyAsHtml => {
+                let mut clipboard = SystemClipboard::new();
+                clipboard.put_string(self.render_html());
+            }
+            FocusCommand::CopyFilePath => {
+                self.copy_path(false);
+            }
+            FocusCommand::CopyRelativeFilePath => {
+                self.copy_path(true);
+            }
+            FocusCommand::CopyLocation => {
+                self.copy_location();
+            }
+            FocusCommand::CopyWithLineNumbers => {
+                self.copy_with_line_numbers();
+            }
+            FocusCommand::SelectionToScratch => {
+                self.selection_to_scratch(false);
+            }
+            FocusCommand::MoveSelectionToScratch => {
+                self.selection_to_scratch(true);
+            }
+            FocusCommand::ExtractSelectionToFile => {
+                self.extract_selection_to_file();
+            }
+            FocusCommand::GetCompletion => {
+                self.update_completion(true);
             }
             FocusCommand::ShowCodeActions => {
                 self.show_code_actions(false);
@@ -734,6 +1069,27 @@ pub fn run_focus_command(
             FocusCommand::Save => {
                 self.save(true, || {});
             }
+            // Like vim's `:wq` and `:q!`, which close the window, so that a
+            // program that waits for it, like `git commit`, goes on
+            FocusCommand::SaveAndExit => {
+                let workbench_command = self.common.workbench_command;
+                let close = move || {
+                    workbench_command.send(LapceWorkbenchCommand::CloseWindow)
+                };
+                let saved = self.view.doc.with_untracked(|doc| {
+                    doc.content.path().is_some() && doc.buffer().is_pristine()
+                });
+                if saved {
+                    close();
+                } else {
+                    self.save(true, close);
+                }
+            }
+            FocusCommand::ForceExit => {
+                self.common
+                    .workbench_command
+                    .send(LapceWorkbenchCommand::CloseWindow);
+            }
             FocusCommand::InlineFindLeft => {
                 self.inline_find.set(Some(InlineFindDirection::Left));
             }
@@ -745,6 +1101,20 @@ pub fn run_focus_command(
                     self.inline_find(direction, &c);
                 }
             }
+            FocusCommand::JumpToWord => {
+                self.start_jump(JumpKind::Word);
+            }
+            FocusCommand::JumpToChar => {
+                self.jump.set(Some(Jump::Char));
+            }
+            FocusCommand::JumpToLine => {
+                self.start_jump(JumpKind::LineStart);
+            }
+            FocusCommand::RepeatLastShift => {
+                if let Some(shift) = self.last_shift.get_untracked() {
+                    self.repeat_shift(shift);
+                }
+            }
             FocusCommand::Rename => {
                 self.rename();
             }
@@ -820,7 +1190,104 @@ fn inline_find(&self, direction: InlineFindDirection, c: &str) {
         }
     }
 
-    fn go_to_definition(&self) {
+    /// Label the targets of a jump of `kind` that are in view, or make it
+    /// known that there are none.
+    fn start_jump(&self, kind: JumpKind) {
+        let screen_lines = self.screen_lines();
+        let viewport = self.viewport.get_untracked();
+        let font_size = self
+            .common
+            .config
+            .with_untracked(|config| config.editor.font_size());
+        let cursor = self.cursor.with_untracked(|c| c.offset());
+        let mut offsets = Vec::new();
+        for line in screen_lines.lines {
+            let (line_start, content) = self.view.doc.with_untracked(|doc| {
+                let buffer = doc.buffer();
+                (
+                    buffer.offset_of_line(line),
+                    buffer.line_content(line).to_string(),
+                )
+            });
+            // Only the targets in view across are labelled
+            let phantom_text = self.view.line_phantom_text(line);
+            for col in line_targets(kind, &content) {
+                let col_after = phantom_text.col_after(col, false);
+                let x = self
+                    .view
+                    .line_point_of_line_col(line, col_after, font_size)
+                    .x;
+                if x >= viewport.x0 && x < viewport.x1 {
+                    offsets.push(line_start + col);
+                }
+            }
+        }
+        let targets = label_targets(&offsets, cursor);
+        if targets.is_empty() {
+            self.jump.set(None);
+            self.notify_error_feedback();
+        } else {
+            self.jump.set(Some(Jump::Labels {
+                targets,
+                typed: String::new(),
+            }));
+        }
+    }
+
+    /// Take `c` as the next key of the jump.
+    fn jump_receive_char(&self, jump: Jump, c: &str) {
+        match jump {
+            Jump::Char => {
+                let mut chars = c.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => self.start_jump(JumpKind::Char(c)),
+                    _ => self.jump.set(None),
+                }
+            }
+            Jump::Labels { targets, mut typed } => {
+                typed.push_str(c);
+                match match_label(&targets, &typed) {
+                    JumpMatch::Target(offset) => {
+                        self.jump.set(None);
+                        self.run_move_command(
+                            &lapce_core::movement::Movement::Offset(offset),
+                            None,
+                            ModifiersState::empty(),
+                        );
+                    }
+                    JumpMatch::Prefix => {
+                        self.jump.set(Some(Jump::Labels { targets, typed }));
+                    }
+                    JumpMatch::None => {
+                        self.jump.set(None);
+                        self.notify_error_feedback();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Start a definition or references request at `offset`, returning its
+    /// generation and a check of whether its response is still wanted, which
+    /// it isn't once the cursor moved or another request was made.
+    fn start_navigation_request(
+        &self,
+        offset: usize,
+    ) -> (usize, impl Fn() -> bool + Copy) {
+        let navigation_request = self.common.navigation_request;
+        navigation_request.update(|request| *request += 1);
+        let request = navigation_request.get_untracked();
+        let cursor = self.cursor.read_only();
+        let is_current = move || {
+            navigation_request.get_untracked() == request
+                && cursor.with_untracked(|c| c.offset()) == offset
+        };
+        (request, is_current)
+    }
+
+    /// Jump to the definition, or the type definition, of the symbol at the
+    /// cursor. On the definition already, its references are shown instead.
+    fn go_to_definition(&self, type_definition: bool) {
         let path = match self.view.doc.with_untracked(|doc| {
             if doc.loaded() {
                 doc.content.path().cloned()
@@ -840,185 +1307,691 @@ fn go_to_definition(&self) {
             (start_position, position)
         });
 
-        enum DefinitionOrReferece {
-            Location(EditorLocation),
-            References(Vec<Location>),
-        }
-
+        let (request_id, is_current) = self.start_navigation_request(offset);
         let internal_command = self.common.internal_command;
-        let cursor = self.cursor.read_only();
-        let send = create_ext_action(self.scope, move |d| {
-            let current_offset = cursor.with_untracked(|c| c.offset());
-            if current_offset != offset {
-                return;
-            }
-
-            match d {
-                DefinitionOrReferece::Location(location) => {
-                    internal_command
-                        .send(InternalCommand::JumpToLocation { location });
+        let (title, not_found) = if type_definition {
+            ("Go to Type Definition", "No type definition found")
+        } else {
+            ("Go to Definition", "No definition found")
+        };
+        let send = create_ext_action(
+            self.scope,
+            move |(locations, not_found): (Vec<Location>, &'static str)| {
+                if is_current() {
+                    show_locations(internal_command, title, not_found, locations);
                 }
-                DefinitionOrReferece::References(locations) => {
-                    internal_command.send(InternalCommand::PaletteReferences {
-                        references: locations
-                            .into_iter()
-                            .map(|l| EditorLocation {
-                                path: path_from_url(&l.uri),
-                                position: Some(EditorPosition::Position(
-                                    l.range.start,
-                                )),
-                                scroll_offset: None,
-                                ignore_unconfirmed: false,
-                                same_editor_tab: false,
-                            })
-                            .collect(),
+            },
+        );
+        let proxy = self.common.proxy.clone();
+        let on_definition = {
+            let path = path.clone();
+            move |definition: Option<GotoDefinitionResponse>| {
+                let locations =
+                    definition.map(definition_locations).unwrap_or_default();
+                let on_definition = !type_definition
+                    && locations.len() == 1
+                    && locations[0].range.start == start_position
+                    && path_from_url(&locations[0].uri) == path;
+                if on_definition {
+                    proxy.get_references(path, position, move |result| {
+                        let references = match result {
+                            Ok(ProxyResponse::GetReferencesResponse {
+                                references,
+                            }) => references,
+                            _ => Vec::new(),
+                        };
+                        send((references, "No references found"));
                     });
+                } else {
+                    send((locations, not_found));
                 }
             }
-        });
-        let proxy = self.common.proxy.clone();
-        self.common.proxy.get_definition(
-            offset,
-            path.clone(),
-            position,
-            move |result| {
-                if let Ok(ProxyResponse::GetDefinitionResponse {
-                    definition, ..
-                }) = result
-                {
-                    if let Some(location) = match definition {
-                        GotoDefinitionResponse::Scalar(location) => Some(location),
-                        GotoDefinitionResponse::Array(locations) => {
-                            if !locations.is_empty() {
-                                Some(locations[0].clone())
-                            } else {
-                                None
-                            }
-                        }
-                        GotoDefinitionResponse::Link(location_links) => {
-                            let location_link = location_links[0].clone();
-                            Some(Location {
-                                uri: location_link.target_uri,
-                                range: location_link.target_selection_range,
-                            })
-                        }
-                    } {
-                        if location.range.start == start_position {
-                            proxy.get_references(
-                                path.clone(),
-                                position,
-                                move |result| {
-                                    if let Ok(
-                                        ProxyResponse::GetReferencesResponse {
-                                            references,
-                                        },
-                                    ) = result
-                                    {
-                                        if references.is_empty() {
-                                            return;
-                                        }
-                                        if references.len() == 1 {
-                                            let location = &references[0];
-                                            send(DefinitionOrReferece::Location(
-                                                EditorLocation {
-                                                    path: path_from_url(
-                                                        &location.uri,
-                                                    ),
-                                                    position: Some(
-                                                        EditorPosition::Position(
-                                                            location.range.start,
-                                                        ),
-                                                    ),
-                                                    scroll_offset: None,
-                                                    ignore_unconfirmed: false,
-                                                    same_editor_tab: false,
-                                                },
-                                            ));
-                                        } else {
-                                            send(DefinitionOrReferece::References(
-                                                references,
-                                            ));
-                                        }
-                                    }
-                                },
-                            );
+        };
+        if type_definition {
+            self.common.proxy.get_type_definition(
+                request_id,
+                path,
+                position,
+                move |result| {
+                    on_definition(match result {
+                        Ok(ProxyResponse::GetTypeDefinition {
+                            definition, ..
+                        }) => Some(definition),
+                        _ => None,
+                    });
+                },
+            );
+        } else {
+            self.common.proxy.get_definition(
+                request_id,
+                path,
+                position,
+                move |result| {
+                    on_definition(match result {
+                        Ok(ProxyResponse::GetDefinitionResponse {
+                            definition,
+                            ..
+                        }) => Some(definition),
+                        _ => None,
+                    });
+                },
+            );
+        }
+    }
+
+    /// Move the cursor to the start of the next or previous change against
+    /// `HEAD`, or against the other side in a diff editor, wrapping around at
+    /// the end of the file.
+    fn jump_to_head_change(&self, forward: bool) {
+        let (starts, line) = self.view.doc.with_untracked(|doc| {
+            let starts = match self.view.kind.get_untracked() {
+                EditorViewKind::Diff(info) => diff_hunks(&info.changes)
+                    .into_iter()
+                    .map(|hunk| {
+                        if info.is_right {
+                            hunk.right.start
                         } else {
-                            let path = path_from_url(&location.uri);
-                            send(DefinitionOrReferece::Location(EditorLocation {
-                                path,
-                                position: Some(EditorPosition::Position(
-                                    location.range.start,
-                                )),
-                                scroll_offset: None,
-                                ignore_unconfirmed: false,
-                                same_editor_tab: false,
-                            }));
+                            hunk.left.start
                         }
-                    }
+                    })
+                    .collect::<Vec<_>>(),
+                EditorViewKind::Normal => {
+                    doc.head_changes.with_untracked(|changes| {
+                        diff_hunks(changes)
+                            .into_iter()
+                            .map(|hunk| hunk.right.start)
+                            .collect()
+                    })
                 }
-            },
-        );
-    }
+            };
+            let offset = self.cursor.with_untracked(|c| c.offset());
+            (starts, doc.buffer().line_of_offset(offset))
+        });
+        let lines = starts.into_iter().dedup();
+        let line = if forward {
+            lines
+                .clone()
+                .find(|l| *l > line)
+                .or_else(|| lines.clone().next())
+        } else {
+            lines
+                .clone()
+                .filter(|l| *l < line)
+                .last()
+                .or_else(|| lines.last())
+        };
+        let Some(line) = line else {
+            return;
+        };
 
-    fn page_move(&self, down: bool, mods: ModifiersState) {
-        let config = self.common.config.get_untracked();
-        let viewport = self.viewport.get_untracked();
-        let line_height = config.editor.line_height() as f64;
-        let lines = (viewport.height() / line_height / 2.0).round() as usize;
-        let distance = (lines as f64) * line_height;
-        self.scroll_delta
-            .set(Vec2::new(0.0, if down { distance } else { -distance }));
+        let offset = self.view.doc.with_untracked(|doc| {
+            doc.buffer().first_non_blank_character_on_line(line)
+        });
         self.run_move_command(
-            if down {
-                &lapce_core::movement::Movement::Down
-            } else {
-                &lapce_core::movement::Movement::Up
-            },
-            Some(lines),
-            mods,
+            &Movement::Offset(offset),
+            None,
+            ModifiersState::empty(),
         );
     }
 
-    fn scroll(&self, down: bool, count: usize, mods: ModifiersState) {
-        let config = self.common.config.get_untracked();
-        let viewport = self.viewport.get_untracked();
-        let line_height = config.editor.line_height() as f64;
-        let diff = line_height * count as f64;
-        let diff = if down { diff } else { -diff };
-
-        let offset = self.cursor.with_untracked(|cursor| cursor.offset());
-        let (line, _col) = self
-            .view
-            .doc
-            .with_untracked(|doc| doc.buffer().offset_to_line_col(offset));
-        let top = viewport.y0 + diff + self.sticky_header_height.get_untracked();
-        let bottom = viewport.y0 + diff + viewport.height();
-
-        let new_line = if (line + 1) as f64 * line_height + line_height > bottom {
-            let line = (bottom / line_height).floor() as usize;
-            if line > 2 {
-                line - 2
+    /// Put back the `HEAD` version of the change under the cursor.
+    fn revert_hunk(&self) {
+        let Some(head) = self.view.doc.with_untracked(|doc| {
+            if doc.content.read_only() {
+                None
             } else {
-                0
+                doc.head_text()
             }
-        } else if line as f64 * line_height - line_height < top {
-            let line = (top / line_height).ceil() as usize;
-            line + 1
-        } else {
-            line
+        }) else {
+            return;
+        };
+        let (hunks, line) = self.view.doc.with_untracked(|doc| {
+            let hunks = doc
+                .head_changes
+                .with_untracked(|changes| diff_hunks(changes));
+            let offset = self.cursor.with_untracked(|c| c.offset());
+            (hunks, doc.buffer().line_of_offset(offset))
+        });
+        // Removed lines are marked between the lines around them, so the
+        // cursor can be on either
+        let Some(hunk) = hunks.into_iter().find(|hunk| {
+            hunk.right.contains(&line)
+                || (hunk.right.is_empty()
+                    && (hunk.right.start == line || hunk.right.start == line + 1))
+        }) else {
+            return;
         };
 
-        self.scroll_delta.set(Vec2::new(0.0, diff));
-
-        match new_line.cmp(&line) {
-            Ordering::Greater => {
-                self.run_move_command(
-                    &lapce_core::movement::Movement::Down,
-                    Some(new_line - line),
-                    mods,
+        let head = RopeTextVal::new(head);
+        let text = head
+            .slice_to_cow(
+                head.offset_of_line(hunk.left.start)
+                    ..head.offset_of_line(hunk.left.end),
+            )
+            .to_string();
+        let (delta, inval_lines, edits) = self
+            .view
+            .doc
+            .try_update(|doc| {
+                let buffer = doc.buffer();
+                let selection = Selection::region(
+                    buffer.offset_of_line(hunk.right.start),
+                    buffer.offset_of_line(hunk.right.end),
                 );
-            }
-            Ordering::Less => {
+                doc.do_raw_edit(&[(selection, text.as_str())], EditType::Other)
+            })
+            .unwrap();
+        self.cursor.update(|cursor| {
+            cursor.apply_delta(&delta);
+        });
+        self.apply_deltas(&[(delta, inval_lines, edits)]);
+    }
+
+    /// The commit that last changed the cursor's line, if the document has
+    /// been blamed already.
+    fn blame_commit(&self) -> Option<BlameCommit> {
+        let offset = self.cursor.with_untracked(|c| c.offset());
+        self.view.doc.with_untracked(|doc| {
+            let line = doc.buffer().line_of_offset(offset);
+            doc.blame.with_untracked(|blame| {
+                blame_hunk(blame.as_ref()?, line)?.commit.clone()
+            })
+        })
+    }
+
+    /// Show the full message of the commit that last changed the cursor's
+    /// line in the hover.
+    fn show_blame_commit(&self) {
+        let Some(commit) = self.blame_commit() else {
+            return;
+        };
+        let config = self.common.config.get_untracked();
+        let mut content = parse_markdown(
+            &format!("**{}** `{}`", commit.author, commit.id),
+            1.5,
+            &config,
+        );
+        content.push(MarkdownContent::Separator);
+        content.extend(from_plaintext(commit.message.trim_end(), 1.5, &config));
+
+        let offset = self.cursor.with_untracked(|c| c.offset());
+        let hover_data = &self.common.hover;
+        hover_data.content.set(content);
+        hover_data.offset.set(offset);
+        hover_data.editor_id.set(self.editor_id);
+        hover_data.active.set(true);
+    }
+
+    /// The document, or the selection in it, as a standalone HTML page in the
+    /// colors of the theme, from the highlighting already done for the editor.
+    fn render_html(&self) -> String {
+        let config = self.common.config.get_untracked();
+        let cursor = self.cursor.get_untracked();
+        self.view.doc.with_untracked(|doc| {
+            let buffer = doc.buffer();
+            let selection = match &cursor.mode {
+                CursorMode::Normal(_) => None,
+                CursorMode::Visual { .. } => Some(cursor.edit_selection(buffer)),
+                CursorMode::Insert(selection) => {
+                    (!selection.is_caret()).then(|| selection.clone())
+                }
+            };
+            let (start, end) = match selection {
+                Some(selection) => (selection.min_offset(), selection.max_offset()),
+                None => (0, buffer.len()),
+            };
+            // A selection ending at the start of a line doesn't take it
+            let first_line = buffer.line_of_offset(start);
+            let last_line = buffer.line_of_offset(end.saturating_sub(1).max(start));
+
+            let texts = (first_line..=last_line)
+                .map(|line| {
+                    let line_start = buffer.offset_of_line(line);
+                    let text_start = start.max(line_start);
+                    let text_end =
+                        buffer.line_end_offset(line, true).min(end).max(text_start);
+                    (
+                        line,
+                        text_start - line_start,
+                        buffer.slice_to_cow(text_start..text_end),
+                    )
+                })
+                .collect::<Vec<_>>();
+            let lines = texts
+                .iter()
+                .map(|(line, shift, text)| {
+                    let spans = doc
+                        .line_style(*line)
+                        .iter()
+                        .filter(|style| style.end > *shift)
+                        .filter_map(|style| {
+                            let name = style.style.fg_color.as_ref()?;
+                            let color = config.get_style_color(name)?;
+                            Some((
+                                style.start.saturating_sub(*shift)
+                                    ..style.end - shift,
+                                *color,
+                                config.get_style_font(name),
+                            ))
+                        })
+                        .collect();
+                    HtmlLine {
+                        number: *line,
+                        text: text.as_ref(),
+                        spans,
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            let title = doc
+                .content
+                .path()
+                .and_then(|path| path.file_name())
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| "Untitled".to_string());
+            let theme = HtmlTheme {
+                foreground: *config.get_color(LapceColor::EDITOR_FOREGROUND),
+                background: *config.get_color(LapceColor::EDITOR_BACKGROUND),
+                line_numbers: config
+                    .editor
+                    .html_line_numbers
+                    .then(|| *config.get_color(LapceColor::EDITOR_DIM)),
+                font_family: &config.editor.font_family,
+            };
+            render_html(&title, &lines, &theme)
+        })
+    }
+
+    /// The path of the document to copy, relative to the workspace if
+    /// `relative`. An untitled document has its name copied instead, with a
+    /// message that it isn't saved.
+    fn path_to_copy(&self, relative: bool) -> Option<String> {
+        let content = self.view.doc.with_untracked(|doc| doc.content.clone());
+        let path = match content {
+            DocContent::File(path) => path,
+            DocContent::History(history) => history.path,
+            DocContent::Scratch { name, .. } => {
+                let message =
+                    format!("{name} isn't saved to a file, so its name was copied");
+                self.common
+                    .internal_command
+                    .send(InternalCommand::ShowMessage {
+                        title: "Copy File Path".to_string(),
+                        message: ShowMessageParams {
+                            typ: MessageType::INFO,
+                            message,
+                        },
+                    });
+                return Some(name);
+            }
+            DocContent::Local => return None,
+        };
+        let path = if relative {
+            relative_path(&path, self.common.workspace.path.as_deref())
+        } else {
+            &path
+        };
+        Some(path.to_string_lossy().into_owned())
+    }
+
+    fn copy_path(&self, relative: bool) {
+        if let Some(path) = self.path_to_copy(relative) {
+            let mut clipboard = SystemClipboard::new();
+            clipboard.put_string(path);
+        }
+    }
+
+    /// Copy a `path:line:column` reference to the cursor, with the path
+    /// relative to the workspace and the column counted in characters.
+    fn copy_location(&self) {
+        let Some(path) = self.path_to_copy(true) else {
+            return;
+        };
+        let offset = self.cursor.with_untracked(|cursor| cursor.offset());
+        let (line, column) = self.view.doc.with_untracked(|doc| {
+            let buffer = doc.buffer();
+            let (line, column) = buffer.offset_to_line_col(offset);
+            (line, char_column(&buffer.line_content(line), column))
+        });
+        let mut clipboard = SystemClipboard::new();
+        clipboard.put_string(location_reference(&path, line, column));
+    }
+
+    /// Copy the lines of the selection, or the line of the cursor, with their
+    /// line numbers in front.
+    fn copy_with_line_numbers(&self) {
+        let cursor = self.cursor.get_untracked();
+        let text = self.view.doc.with_untracked(|doc| {
+            let buffer = doc.buffer();
+            let selection = cursor.edit_selection(buffer);
+            let (start, end) = (selection.min_offset(), selection.max_offset());
+            // A selection ending at the start of a line doesn't take it
+            let first_line = buffer.line_of_offset(start);
+            let last_line = buffer.line_of_offset(end.saturating_sub(1).max(start));
+            let lines = (first_line..=last_line)
+                .map(|line| buffer.line_content(line))
+                .collect::<Vec<_>>();
+            with_line_numbers(
+                lines.iter().map(|line| line.trim_end_matches(['\r', '\n'])),
+                first_line,
+            )
+        });
+        let mut clipboard = SystemClipboard::new();
+        clipboard.put_string(text);
+    }
+
+    /// The selection and its text, the text of each of its regions on a line
+    /// of its own, when anything is selected.
+    fn selected_text(&self) -> Option<(Selection, String)> {
+        let cursor = self.cursor.get_untracked();
+        if cursor.is_normal() {
+            return None;
+        }
+        self.view.doc.with_untracked(|doc| {
+            let buffer = doc.buffer();
+            let selection = cursor.edit_selection(buffer);
+            let text = selection
+                .regions()
+                .iter()
+                .filter(|region| !region.is_caret())
+                .map(|region| buffer.slice_to_cow(region.min()..region.max()))
+                .join("\n");
+            (!text.is_empty()).then_some((selection, text))
+        })
+    }
+
+    /// Delete the text of `selection`, as a change that's undone on its own.
+    fn delete_selected(&self, selection: Selection) {
+        let mut cursor = self.cursor.get_untracked();
+        let (delta, inval_lines, edits) = self
+            .view
+            .doc
+            .try_update(|doc| {
+                let (delta, inval_lines, edits) =
+                    doc.do_raw_edit(&[(&selection, "")], EditType::DeleteSelection);
+                let selection =
+                    selection.apply_delta(&delta, true, InsertDrift::Default);
+                let old_cursor = cursor.mode.clone();
+                cursor.update_selection(doc.buffer(), selection);
+                doc.buffer_mut().set_cursor_before(old_cursor);
+                doc.buffer_mut().set_cursor_after(cursor.mode.clone());
+                (delta, inval_lines, edits)
+            })
+            .unwrap();
+        self.cursor.set(cursor);
+        self.apply_deltas(&[(delta, inval_lines, edits)]);
+    }
+
+    /// Open the selected text in a new untitled file beside the editor, in
+    /// the language of the document, taking it out of the document too when
+    /// `delete` is set.
+    fn selection_to_scratch(&self, delete: bool) {
+        let Some(editor_tab_id) = self.editor_tab_id else {
+            return;
+        };
+        let read_only = self.view.doc.with_untracked(|doc| doc.content.read_only());
+        let Some((selection, text)) =
+            self.selected_text().filter(|_| !(delete && read_only))
+        else {
+            self.notify_error_feedback();
+            return;
+        };
+        let language = self.view.doc.with_untracked(|doc| doc.syntax().language);
+        if delete {
+            self.delete_selected(selection);
+        }
+        self.common
+            .internal_command
+            .send(InternalCommand::OpenScratchBeside {
+                editor_tab_id,
+                text: Rope::from(text),
+                language,
+            });
+    }
+
+    /// Write the selected text to a new file, asking for its path, take it
+    /// out of the document and open the file beside the editor.
+    fn extract_selection_to_file(&self) {
+        let Some(editor_tab_id) = self.editor_tab_id else {
+            return;
+        };
+        let (read_only, rev) = self
+            .view
+            .doc
+            .with_untracked(|doc| (doc.content.read_only(), doc.rev()));
+        let Some((selection, text)) = self.selected_text().filter(|_| !read_only)
+        else {
+            self.notify_error_feedback();
+            return;
+        };
+
+        let editor = self.clone();
+        save_as(FileDialogOptions::new(), move |file: Option<FileInfo>| {
+            let Some(file) = file else {
+                return;
+            };
+            let path = file.path;
+            let editor = editor.clone();
+            let selection = selection.clone();
+            let send = create_ext_action(editor.scope, {
+                let editor = editor.clone();
+                let path = path.clone();
+                move |result: Result<ProxyResponse, RpcError>| {
+                    if let Err(err) = result {
+                        editor.common.internal_command.send(
+                            InternalCommand::ShowMessage {
+                                title: "Extract to New File".to_string(),
+                                message: ShowMessageParams {
+                                    typ: MessageType::ERROR,
+                                    message: err.message,
+                                },
+                            },
+                        );
+                        return;
+                    }
+                    // The selection is only taken out if the document wasn't
+                    // changed while the path was asked for
+                    if editor.view.doc.with_untracked(|doc| doc.rev()) == rev {
+                        editor.delete_selected(selection);
+                    }
+                    editor.common.internal_command.send(
+                        InternalCommand::OpenFileBeside {
+                            editor_tab_id,
+                            path,
+                        },
+                    );
+                }
+            });
+            editor.common.proxy.save_buffer_as(
+                BufferId::next(),
+                path,
+                0,
+                text.clone(),
+                move |result| {
+                    send(result);
+                },
+            );
+        });
+    }
+
+    /// Write the HTML of the document next to its file, as `<file>.html`, or
+    /// copy it when the document has no file. An existing `<file>.html` is
+    /// only replaced once the user confirms it.
+    fn export_html(&self) {
+        let html = self.render_html();
+        let path = self
+            .view
+            .doc
+            .with_untracked(|doc| doc.content.path().cloned());
+        let Some(path) = path.and_then(|path| {
+            let name = path.file_name()?.to_string_lossy().to_string();
+            Some(path.with_file_name(format!("{name}.html")))
+        }) else {
+            let mut clipboard = SystemClipboard::new();
+            clipboard.put_string(html);
+            return;
+        };
+        self.write_html(path, html, false);
+    }
+
+    fn write_html(&self, path: PathBuf, html: String, overwrite: bool) {
+        let editor = self.clone();
+        let internal_command = self.common.internal_command;
+        let send = create_ext_action(self.scope, {
+            let (path, html) = (path.clone(), html.clone());
+            move |result: Result<ProxyResponse, RpcError>| {
+                let message = match result {
+                    Ok(_) => ShowMessageParams {
+                        typ: MessageType::INFO,
+                        message: format!("Exported to {}", path.display()),
+                    },
+                    Err(err) if err.code == RpcError::ALREADY_EXISTS => {
+                        editor.confirm_html_overwrite(path, html);
+                        return;
+                    }
+                    Err(err) => ShowMessageParams {
+                        typ: MessageType::ERROR,
+                        message: err.message,
+                    },
+                };
+                internal_command.send(InternalCommand::ShowMessage {
+                    title: "Export as HTML".to_string(),
+                    message,
+                });
+            }
+        });
+        self.common
+            .proxy
+            .write_file(path, html, overwrite, move |result| {
+                send(result);
+            });
+    }
+
+    /// Ask whether to replace the HTML file at `path`, since there's one
+    /// already.
+    fn confirm_html_overwrite(&self, path: PathBuf, html: String) {
+        let editor = self.clone();
+        let internal_command = self.common.internal_command;
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        internal_command.send(InternalCommand::ShowAlert {
+            title: format!("{name} already exists"),
+            msg: "Do you want to replace it?".to_string(),
+            buttons: vec![AlertButton {
+                text: "Replace".to_string(),
+                action: Rc::new(move || {
+                    internal_command.send(InternalCommand::HideAlert);
+                    editor.write_html(path.clone(), html.clone(), true);
+                }),
+            }],
+        });
+    }
+
+    fn find_references(&self) {
+        let path = match self.view.doc.with_untracked(|doc| {
+            if doc.loaded() {
+                doc.content.path().cloned()
+            } else {
+                None
+            }
+        }) {
+            Some(path) => path,
+            None => return,
+        };
+
+        let offset = self.cursor.with_untracked(|c| c.offset());
+        let position = self
+            .view
+            .doc
+            .with_untracked(|doc| doc.buffer().offset_to_position(offset));
+
+        let (_, is_current) = self.start_navigation_request(offset);
+        let internal_command = self.common.internal_command;
+        let send =
+            create_ext_action(self.scope, move |references: Vec<Location>| {
+                if is_current() {
+                    show_locations(
+                        internal_command,
+                        "Find References",
+                        "No references found",
+                        references,
+                    );
+                }
+            });
+        self.common
+            .proxy
+            .get_references(path, position, move |result| {
+                let references = match result {
+                    Ok(ProxyResponse::GetReferencesResponse { references }) => {
+                        references
+                    }
+                    _ => Vec::new(),
+                };
+                send(references);
+            });
+    }
+
+    fn page_move(&self, down: bool, mods: ModifiersState) {
+        let config = self.common.config.get_untracked();
+        let viewport = self.viewport.get_untracked();
+        let line_height = config.editor.line_height() as f64;
+        let lines = (viewport.height() / line_height / 2.0).round() as usize;
+        let distance = (lines as f64) * line_height;
+        self.scroll_delta
+            .set(Vec2::new(0.0, if down { distance } else { -distance }));
+        self.run_move_command(
+            if down {
+                &lapce_core::movement::Movement::Down
+            } else {
+                &lapce_core::movement::Movement::Up
+            },
+            Some(lines),
+            mods,
+        );
+    }
+
+    fn scroll(&self, down: bool, count: usize, mods: ModifiersState) {
+        let config = self.common.config.get_untracked();
+        let viewport = self.viewport.get_untracked();
+        let line_height = config.editor.line_height() as f64;
+        let diff = line_height * count as f64;
+        let diff = if down { diff } else { -diff };
+
+        let offset = self.cursor.with_untracked(|cursor| cursor.offset());
+        let (line, _col) = self
+            .view
+            .doc
+            .with_untracked(|doc| doc.buffer().offset_to_line_col(offset));
+        let top = viewport.y0 + diff + self.sticky_header_height.get_untracked();
+        let bottom = viewport.y0 + diff + viewport.height();
+
+        let new_line = if (line + 1) as f64 * line_height + line_height > bottom {
+            let line = (bottom / line_height).floor() as usize;
+            if line > 2 {
+                line - 2
+            } else {
+                0
+            }
+        } else if line as f64 * line_height - line_height < top {
+            let line = (top / line_height).ceil() as usize;
+            line + 1
+        } else {
+            line
+        };
+
+        self.scroll_delta.set(Vec2::new(0.0, diff));
+
+        match new_line.cmp(&line) {
+            Ordering::Greater => {
+                self.run_move_command(
+                    &lapce_core::movement::Movement::Down,
+                    Some(new_line - line),
+                    mods,
+                );
+            }
+            Ordering::Less => {
                 self.run_move_command(
                     &lapce_core::movement::Movement::Up,
                     Some(line - new_line),
@@ -1036,7 +2009,7 @@ fn select_completion(&self) {
             .with_untracked(|c| c.current_item().cloned());
         self.cancel_completion();
         if let Some(item) = item {
-            if item.item.data.is_some() {
+            if item.item.data.is_some() && !item.resolved {
                 let editor = self.clone();
                 let (rev, path) = self
                     .view
@@ -1085,10 +2058,77 @@ pub fn cancel_completion(&self) {
         self.common.completion.update(|c| {
             c.cancel();
         });
+        self.common.proxy.cancel_completion();
 
         clear_completion_lens(self.view.doc);
     }
 
+    /// Ask for the signature of the call the cursor is in once the typing
+    /// settles. Typing `(` or `,` brings it up, it follows the arguments as
+    /// they're typed, and it goes away once the call is closed with `)`.
+    fn update_signature(&self) {
+        let show_signature = self
+            .common
+            .config
+            .with_untracked(|config| config.editor.show_signature);
+        if !show_signature || self.get_mode() != Mode::Insert {
+            self.cancel_signature();
+            return;
+        }
+
+        let signature = self.common.signature.clone();
+        let offset = self.cursor.with_untracked(|c| c.offset());
+        let (path, prev_char, call_start) = self.view.doc.with_untracked(|doc| {
+            let buffer = doc.buffer();
+            let prev_char = if offset > 0 {
+                buffer.char_at_offset(buffer.prev_grapheme_offset(offset, 1, 0))
+            } else {
+                None
+            };
+            (
+                doc.content.path().cloned(),
+                prev_char,
+                buffer.previous_unmatched(doc.syntax(), '(', offset),
+            )
+        });
+        let shown = signature.active.get_untracked()
+            && signature.editor_id.get_untracked() == self.editor_id;
+        let (Some(path), Some(call_start)) = (path, call_start) else {
+            self.cancel_signature();
+            return;
+        };
+        if prev_char == Some(')') {
+            self.cancel_signature();
+            return;
+        }
+        if !shown && !matches!(prev_char, Some('(' | ',')) {
+            return;
+        }
+
+        let request_id = signature.next_request();
+        signature.editor_id.set(self.editor_id);
+        signature.offset.set(call_start);
+        let position = self
+            .view
+            .doc
+            .with_untracked(|doc| doc.buffer().offset_to_position(offset));
+        let proxy = self.common.proxy.clone();
+        let timer = signature.timer;
+        let token = exec_after(SIGNATURE_HELP_DELAY, move |token| {
+            if timer.try_get_untracked() == Some(token) {
+                proxy.signature_help(request_id, path, position);
+            }
+        });
+        timer.set(token);
+    }
+
+    pub fn cancel_signature(&self) {
+        let signature = &self.common.signature;
+        if signature.editor_id.get_untracked() == self.editor_id {
+            signature.cancel();
+        }
+    }
+
     /// Update the displayed autocompletion box  
     /// Sends a request to the LSP for completion information
     fn update_completion(&self, display_if_empty_input: bool) {
@@ -1125,7 +2165,16 @@ fn update_completion(&self, display_if_empty_input: bool) {
             };
             (start_offset, input, char)
         });
-        if !display_if_empty_input && input.is_empty() && char != "." && char != ":"
+        // Typing a character that isn't part of a word, like `.`, asks the
+        // servers that listed it as a trigger character
+        let trigger_character = (input.is_empty()
+            && !display_if_empty_input
+            && char
+                .chars()
+                .all(|c| !c.is_alphanumeric() && !c.is_whitespace() && c != '_'))
+        .then(|| char.clone())
+        .filter(|char| !char.is_empty());
+        if !display_if_empty_input && input.is_empty() && trigger_character.is_none()
         {
             self.cancel_completion();
             return;
@@ -1152,6 +2201,7 @@ fn update_completion(&self, display_if_empty_input: bool) {
                         path.clone(),
                         "".to_string(),
                         start_pos,
+                        None,
                     );
                 }
 
@@ -1165,12 +2215,23 @@ fn update_completion(&self, display_if_empty_input: bool) {
                         path,
                         input,
                         position,
+                        None,
                     );
                 }
             });
             return;
         }
 
+        // The completion moved elsewhere, so the answers to the requests of
+        // the old one are of no use anymore
+        if self
+            .common
+            .completion
+            .with_untracked(|completion| completion.status)
+            != CompletionStatus::Inactive
+        {
+            self.common.proxy.cancel_completion();
+        }
         self.common.completion.update(|completion| {
             completion.path = path.clone();
             completion.offset = start_offset;
@@ -1188,6 +2249,7 @@ fn update_completion(&self, display_if_empty_input: bool) {
                 path.clone(),
                 "".to_string(),
                 start_pos,
+                trigger_character,
             );
 
             if !input.is_empty() {
@@ -1201,6 +2263,7 @@ fn update_completion(&self, display_if_empty_input: bool) {
                     path,
                     input,
                     position,
+                    None,
                 );
             }
         });
@@ -1236,44 +2299,45 @@ fn apply_completion_item(&self, item: &CompletionItem) -> Result<()> {
             .insert_text_format
             .unwrap_or(lsp_types::InsertTextFormat::PLAIN_TEXT);
         if let Some(edit) = &item.text_edit {
-            match edit {
-                CompletionTextEdit::Edit(edit) => {
-                    let offset = cursor.offset();
-                    let start_offset = doc.buffer().prev_code_boundary(offset);
-                    let end_offset = doc.buffer().next_code_boundary(offset);
-                    let edit_start =
-                        doc.buffer().offset_of_position(&edit.range.start);
-                    let edit_end = doc.buffer().offset_of_position(&edit.range.end);
+            // Accepting in the middle of a word replaces the rest of it too
+            let (range, new_text) = match edit {
+                CompletionTextEdit::Edit(edit) => (&edit.range, &edit.new_text),
+                CompletionTextEdit::InsertAndReplace(edit) => {
+                    (&edit.replace, &edit.new_text)
+                }
+            };
+            let offset = cursor.offset();
+            let start_offset = doc.buffer().prev_code_boundary(offset);
+            let end_offset = doc.buffer().next_code_boundary(offset);
+            let edit_start = doc.buffer().offset_of_position(&range.start);
+            let edit_end = doc.buffer().offset_of_position(&range.end);
 
-                    let selection = lapce_core::selection::Selection::region(
-                        start_offset.min(edit_start),
-                        end_offset.max(edit_end),
+            let selection = lapce_core::selection::Selection::region(
+                start_offset.min(edit_start),
+                end_offset.max(edit_end),
+            );
+            match text_format {
+                lsp_types::InsertTextFormat::PLAIN_TEXT => {
+                    self.do_edit(
+                        &selection,
+                        &[
+                            &[(selection.clone(), new_text.as_str())][..],
+                            &additional_edit[..],
+                        ]
+                        .concat(),
                     );
-                    match text_format {
-                        lsp_types::InsertTextFormat::PLAIN_TEXT => {
-                            self.do_edit(
-                                &selection,
-                                &[
-                                    &[(selection.clone(), edit.new_text.as_str())][..],
-                                    &additional_edit[..],
-                                ]
-                                .concat(),
-                            );
-                            return Ok(());
-                        }
-                        lsp_types::InsertTextFormat::SNIPPET => {
-                            self.completion_apply_snippet(
-                                &edit.new_text,
-                                &selection,
-                                additional_edit,
-                                start_offset,
-                            )?;
-                            return Ok(());
-                        }
-                        _ => {}
-                    }
+                    return Ok(());
+                }
+                lsp_types::InsertTextFormat::SNIPPET => {
+                    self.completion_apply_snippet(
+                        new_text,
+                        &selection,
+                        additional_edit,
+                        start_offset,
+                    )?;
+                    return Ok(());
                 }
-                CompletionTextEdit::InsertAndReplace(_) => (),
+                _ => {}
             }
         }
 
@@ -1282,6 +2346,17 @@ fn apply_completion_item(&self, item: &CompletionItem) -> Result<()> {
         let end_offset = doc.buffer().next_code_boundary(offset);
         let selection = Selection::region(start_offset, end_offset);
 
+        if let (Some(insert_text), lsp_types::InsertTextFormat::SNIPPET) =
+            (item.insert_text.as_deref(), text_format)
+        {
+            return self.completion_apply_snippet(
+                insert_text,
+                &selection,
+                additional_edit,
+                start_offset,
+            );
+        }
+
         self.do_edit(
             &selection,
             &[
@@ -1429,6 +2504,32 @@ pub fn do_text_edit(&self, edits: &[TextEdit]) {
         self.do_edit(&selection, &edits);
     }
 
+    /// Write `literal` again in the color `rgb`, as an edit that's undone on
+    /// its own, unless its text changed since it was found.
+    pub fn rewrite_color_literal(&self, literal: &ColorLiteral, rgb: [u8; 3]) {
+        let (selection, unchanged) = self.view.doc.with_untracked(|doc| {
+            let buffer = doc.buffer();
+            let selection = self.cursor.get_untracked().edit_selection(buffer);
+            let unchanged = literal.end <= buffer.len()
+                && color_literals(&buffer.slice_to_cow(literal.range()))
+                    .first()
+                    .map_or(false, |found| {
+                        found.end == literal.end - literal.start
+                            && found.rgba == literal.rgba
+                            && found.format == literal.format
+                    });
+            (selection, unchanged)
+        });
+        if !unchanged {
+            return;
+        }
+        let text = literal.with_rgb(rgb);
+        self.do_edit(
+            &selection,
+            &[(Selection::region(literal.start, literal.end), text.as_str())],
+        );
+    }
+
     fn apply_deltas(&self, deltas: &[(RopeDelta, InvalLines, SyntaxEdit)]) {
         if !deltas.is_empty() && !self.confirmed.get_untracked() {
             self.confirmed.set(true);
@@ -1438,7 +2539,7 @@ fn apply_deltas(&self, deltas: &[(RopeDelta, InvalLines, SyntaxEdit)]) {
             self.update_snippet_offset(delta);
             // self.update_breakpoints(delta);
         }
-        // self.update_signature();
+        self.update_signature();
     }
 
     fn update_snippet_offset(&self, delta: &RopeDelta) {
@@ -1474,13 +2575,23 @@ fn do_go_to_location(
             self.go_to_position(position, location.scroll_offset, edits);
         } else if let Some(edits) = edits.as_ref() {
             self.do_text_edit(edits);
-        } else {
+        } else if !self.restore_view_state()
+            && self
+                .common
+                .config
+                .with_untracked(|config| config.editor.restore_cursor_position)
+        {
             let db: Arc<LapceDb> = use_context().unwrap();
             if let Ok(info) = db.get_doc_info(&self.common.workspace, &location.path)
             {
+                let (offset, fits) = self.view.doc.with_untracked(|doc| {
+                    (info.cursor_offset(doc.buffer()), info.fits(doc.buffer()))
+                });
+                let scroll_offset = fits
+                    .then(|| Vec2::new(info.scroll_offset.0, info.scroll_offset.1));
                 self.go_to_position(
-                    EditorPosition::Offset(info.cursor_offset),
-                    Some(Vec2::new(info.scroll_offset.0, info.scroll_offset.1)),
+                    EditorPosition::Offset(offset),
+                    scroll_offset,
                     edits,
                 );
             }
@@ -1592,42 +2703,330 @@ pub fn get_code_actions(&self) {
                 doc.update(|doc| {
                     doc.code_actions.insert(offset, Arc::new(resp));
                 });
-            }
+            }
+        });
+
+        self.common.proxy.get_code_actions(
+            path,
+            position,
+            diagnostics,
+            None,
+            move |result| {
+                if let Ok(ProxyResponse::GetCodeActionsResponse {
+                    plugin_id,
+                    resp,
+                }) = result
+                {
+                    send((plugin_id, resp))
+                }
+            },
+        );
+    }
+
+    /// Ask for the code actions at the cursor once it has stayed put for a
+    /// moment.
+    pub fn schedule_code_actions(&self) {
+        let editor = self.clone();
+        let timer = self.code_actions_timer;
+        let token = exec_after(CODE_ACTIONS_DELAY, move |token| {
+            if timer.try_get_untracked() == Some(token) {
+                editor.get_code_actions();
+            }
+        });
+        timer.set(token);
+    }
+
+    /// Ask for the source actions of `kind`, like organizing the imports, and
+    /// run the one the server prefers, or else the first.
+    fn run_source_action(&self, kind: CodeActionKind, title: &'static str) {
+        let path = match self.view.doc.with_untracked(|doc| {
+            if doc.loaded() {
+                doc.content.path().cloned()
+            } else {
+                None
+            }
+        }) {
+            Some(path) => path,
+            None => return,
+        };
+
+        let offset = self.cursor.with_untracked(|c| c.offset());
+        let position = self
+            .view
+            .doc
+            .with_untracked(|doc| doc.buffer().offset_to_position(offset));
+        let only = vec![kind.clone()];
+        let internal_command = self.common.internal_command;
+        let send = create_ext_action(
+            self.scope,
+            move |result: Result<ProxyResponse, RpcError>| {
+                let message = match result {
+                    Ok(ProxyResponse::GetCodeActionsResponse {
+                        plugin_id,
+                        resp,
+                    }) => {
+                        let actions = resp
+                            .into_iter()
+                            .filter_map(|action| match action {
+                                CodeActionOrCommand::CodeAction(action)
+                                    if action
+                                        .kind
+                                        .as_ref()
+                                        .map(|k| is_kind_of(k, &kind))
+                                        .unwrap_or(false) =>
+                                {
+                                    Some(action)
+                                }
+                                _ => None,
+                            })
+                            .collect::<Vec<_>>();
+                        let action = actions
+                            .iter()
+                            .find(|action| action.is_preferred == Some(true))
+                            .or_else(|| actions.first());
+                        if let Some(action) = action {
+                            internal_command.send(InternalCommand::RunCodeAction {
+                                plugin_id,
+                                action: CodeActionOrCommand::CodeAction(
+                                    action.clone(),
+                                ),
+                            });
+                            return;
+                        }
+                        ShowMessageParams {
+                            typ: MessageType::INFO,
+                            message: "Nothing to do".to_string(),
+                        }
+                    }
+                    Ok(_) => return,
+                    Err(err) if err.code == RpcError::NOT_CAPABLE => {
+                        ShowMessageParams {
+                            typ: MessageType::INFO,
+                            message: "No code actions available".to_string(),
+                        }
+                    }
+                    Err(err) => ShowMessageParams {
+                        typ: MessageType::ERROR,
+                        message: err.message,
+                    },
+                };
+                internal_command.send(InternalCommand::ShowMessage {
+                    title: title.to_string(),
+                    message,
+                });
+            },
+        );
+        self.common.proxy.get_code_actions(
+            path,
+            position,
+            Vec::new(),
+            Some(only),
+            move |result| {
+                send(result);
+            },
+        );
+    }
+
+    pub fn show_code_actions(&self, mouse_click: bool) {
+        let offset = self.cursor.with_untracked(|c| c.offset());
+        let (code_actions, misspelling) = self.view.doc.with_untracked(|doc| {
+            (
+                doc.code_actions.get(&offset).cloned(),
+                doc.misspelling_at(offset),
+            )
+        });
+        // The fixes of a misspelled word come along with the language
+        // server's actions
+        let code_actions = match misspelling {
+            Some((range, word)) => {
+                let (plugin_id, mut actions) = code_actions
+                    .map(|code_actions| (code_actions.0, code_actions.1.clone()))
+                    .unwrap_or((PluginId(0), Vec::new()));
+                actions.extend(spell::code_actions(&word, range));
+                Some(Arc::new((plugin_id, actions)))
+            }
+            None => code_actions,
+        };
+        if let Some(code_actions) = code_actions {
+            if !code_actions.1.is_empty() {
+                self.common.internal_command.send(
+                    InternalCommand::ShowCodeActions {
+                        offset,
+                        mouse_click,
+                        code_actions,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Discard the unsaved changes of the file, once it's confirmed, by
+    /// changing the lines that differ from the file on disk back. It's an
+    /// edit like any other, so it can be undone.
+    pub fn revert_file(&self) {
+        let (rev, is_pristine, content) = self.view.doc.with_untracked(|doc| {
+            (doc.rev(), doc.buffer().is_pristine(), doc.content.clone())
+        });
+        let DocContent::File(path) = content else {
+            return;
+        };
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let internal_command = self.common.internal_command;
+        if is_pristine {
+            internal_command.send(InternalCommand::ShowMessage {
+                title: "Revert File".to_string(),
+                message: ShowMessageParams {
+                    typ: MessageType::INFO,
+                    message: format!("{name} has no unsaved changes"),
+                },
+            });
+            return;
+        }
+
+        let editor = self.clone();
+        let send =
+            create_ext_action(self.scope, move |result: Result<String, String>| {
+                let saved = match result {
+                    Ok(saved) => Rope::from(saved),
+                    Err(err) => {
+                        internal_command.send(InternalCommand::ShowMessage {
+                            title: "Revert File".to_string(),
+                            message: ShowMessageParams {
+                                typ: MessageType::ERROR,
+                                message: format!("Couldn't read {name}: {err}"),
+                            },
+                        });
+                        return;
+                    }
+                };
+                if editor.view.doc.with_untracked(|doc| doc.rev()) != rev {
+                    return;
+                }
+                let hunks = editor.diff_lines(&saved);
+                let lines = hunks
+                    .iter()
+                    .map(|hunk| hunk.left.len().max(hunk.right.len()))
+                    .sum::<usize>();
+                // Only line endings differ
+                if lines == 0 {
+                    editor.apply_revert(&saved, &hunks);
+                    return;
+                }
+                let revert = editor.clone();
+                let action = Rc::new(move || {
+                    internal_command.send(InternalCommand::HideAlert);
+                    if revert.view.doc.with_untracked(|doc| doc.rev()) == rev {
+                        revert.apply_revert(&saved, &hunks);
+                    }
+                });
+                internal_command.send(InternalCommand::ShowAlert {
+                    title: format!("Revert {name}?"),
+                    msg: format!(
+                        "{lines} {} from the saved file will be discarded. The \
+                         revert can be undone.",
+                        if lines == 1 {
+                            "line that differs"
+                        } else {
+                            "lines that differ"
+                        }
+                    ),
+                    buttons: vec![AlertButton {
+                        text: "Revert".to_string(),
+                        action,
+                    }],
+                });
+            });
+        self.common.proxy.read_files(vec![path], move |result| {
+            let saved = match result {
+                Ok(ProxyResponse::ReadFilesResponse { files }) => files
+                    .into_iter()
+                    .next()
+                    .map(|(_, content)| content)
+                    .unwrap_or_else(|| Err("it wasn't read".to_string())),
+                Ok(_) => Err("it wasn't read".to_string()),
+                Err(err) => Err(err.message),
+            };
+            send(saved);
         });
+    }
 
-        self.common.proxy.get_code_actions(
-            path,
-            position,
-            diagnostics,
-            move |result| {
-                if let Ok(ProxyResponse::GetCodeActionsResponse {
-                    plugin_id,
-                    resp,
-                }) = result
-                {
-                    send((plugin_id, resp))
-                }
-            },
-        );
+    /// Change the lines of the `hunks` back to the `saved` text, which is
+    /// then the one that's saved.
+    fn apply_revert(&self, saved: &Rope, hunks: &[DiffHunk]) {
+        self.replace_lines(saved, hunks);
+        self.view.doc.update(|doc| doc.buffer_mut().set_pristine());
     }
 
-    pub fn show_code_actions(&self, mouse_click: bool) {
-        let offset = self.cursor.with_untracked(|c| c.offset());
-        let code_actions = self
+    /// The hunks of lines that differ between the document and `new`.
+    fn diff_lines(&self, new: &Rope) -> Vec<DiffHunk> {
+        let text = self
             .view
             .doc
-            .with_untracked(|doc| doc.code_actions.get(&offset).cloned());
-        if let Some(code_actions) = code_actions {
-            if !code_actions.1.is_empty() {
-                self.common.internal_command.send(
-                    InternalCommand::ShowCodeActions {
-                        offset,
-                        mouse_click,
-                        code_actions,
-                    },
-                );
-            }
+            .with_untracked(|doc| doc.buffer().text().clone());
+        rope_diff(
+            text,
+            new.clone(),
+            0,
+            Arc::new(AtomicU64::new(0)),
+            None,
+            DiffOptions::default(),
+        )
+        .map(|changes| diff_hunks(&changes))
+        .unwrap_or_default()
+    }
+
+    /// Change the lines of the `hunks` to the ones of `new`, as one edit, with
+    /// the cursor kept on the line it was on as far as the lines are still
+    /// there.
+    fn replace_lines(&self, new: &Rope, hunks: &[DiffHunk]) {
+        let (selection, edits, line, col) = self.view.doc.with_untracked(|doc| {
+            let buffer = doc.buffer();
+            let new = RopeTextVal::new(new.clone());
+            let edits = hunks
+                .iter()
+                .map(|hunk| {
+                    let selection = Selection::region(
+                        buffer.offset_of_line(hunk.left.start),
+                        buffer.offset_of_line(hunk.left.end),
+                    );
+                    let text = new.slice_to_cow(
+                        new.offset_of_line(hunk.right.start)
+                            ..new.offset_of_line(hunk.right.end),
+                    );
+                    (selection, text.to_string())
+                })
+                .collect::<Vec<_>>();
+            let cursor = self.cursor.get_untracked();
+            let (line, col) = buffer.offset_to_line_col(cursor.offset());
+            (cursor.edit_selection(buffer), edits, line, col)
+        });
+        let edits = edits
+            .iter()
+            .map(|(selection, text)| (selection, text.as_str()))
+            .collect::<Vec<_>>();
+        if !edits.is_empty() {
+            self.do_edit(&selection, &edits);
         }
+        // What the lines don't tell apart, like their endings, is reloaded
+        // as a whole
+        let doc = self.view.doc;
+        if doc.with_untracked(|doc| {
+            doc.buffer().text().slice_to_cow(..) != new.slice_to_cow(..)
+        }) {
+            doc.update(|doc| doc.reload(new.clone(), false));
+        }
+
+        let offset = doc.with_untracked(|doc| {
+            let buffer = doc.buffer();
+            let line = map_line(hunks, line).min(buffer.last_line());
+            let col = col.min(buffer.line_end_col(line, true));
+            buffer.offset_of_line_col(line, col)
+        });
+        self.cursor
+            .update(|cursor| cursor.set_offset(offset, false, false));
     }
 
     fn do_save(&self, after_action: impl Fn() + 'static) {
@@ -1642,9 +3041,12 @@ fn do_save(&self, after_action: impl Fn() + 'static) {
                 let current_rev = doc.with_untracked(|doc| doc.rev());
                 if current_rev == rev {
                     doc.update(|doc| {
+                        doc.orphaned = false;
                         doc.buffer_mut().set_pristine();
                     });
                     after_action();
+                } else if doc.with_untracked(|doc| doc.orphaned) {
+                    doc.update(|doc| doc.orphaned = false);
                 }
             }
         });
@@ -1656,6 +3058,120 @@ fn do_save(&self, after_action: impl Fn() + 'static) {
         }
     }
 
+    /// Format the document, or what's selected of it, with the language
+    /// server, as a single undo step. Without a language server that formats
+    /// it, the whole document is formatted with the language's formatter
+    /// command, if it has one.
+    fn format(&self) {
+        let (path, rev, range) = self.view.doc.with_untracked(|doc| {
+            let buffer = doc.buffer();
+            let range = self.cursor.with_untracked(|cursor| {
+                let selection = match &cursor.mode {
+                    CursorMode::Visual { .. } => cursor.edit_selection(buffer),
+                    CursorMode::Insert(selection) if !selection.is_caret() => {
+                        selection.clone()
+                    }
+                    _ => return None,
+                };
+                Some(lsp_types::Range {
+                    start: buffer.offset_to_position(selection.min_offset()),
+                    end: buffer.offset_to_position(selection.max_offset()),
+                })
+            });
+            (doc.content.path().cloned(), doc.rev(), range)
+        });
+        let Some(path) = path else {
+            return;
+        };
+        let config = self.common.config.get_untracked();
+        let (formatter, options) = self.view.doc.with_untracked(|doc| {
+            (doc.formatter(&config), doc.formatting_options(&config))
+        });
+
+        let editor = self.clone();
+        let internal_command = self.common.internal_command;
+        let formatter_path = path.clone();
+        let send = create_ext_action(
+            self.scope,
+            move |result: Result<ProxyResponse, RpcError>| {
+                let message = match result {
+                    Ok(ProxyResponse::GetDocumentFormatting { edits }) => {
+                        // Edits made since make the formatting stale
+                        if editor.view.doc.with_untracked(|doc| doc.rev()) == rev {
+                            editor.do_text_edit(&edits);
+                        }
+                        return;
+                    }
+                    Ok(_) => return,
+                    Err(err) if err.code == RpcError::NOT_CAPABLE => {
+                        if let Some(command) = formatter {
+                            editor.format_with_command(formatter_path, command);
+                            return;
+                        }
+                        ShowMessageParams {
+                            typ: MessageType::INFO,
+                            message: "No formatter available".to_string(),
+                        }
+                    }
+                    Err(err) => ShowMessageParams {
+                        typ: MessageType::ERROR,
+                        message: err.message,
+                    },
+                };
+                internal_command.send(InternalCommand::ShowMessage {
+                    title: "Format Document".to_string(),
+                    message,
+                });
+            },
+        );
+        match range {
+            Some(range) => self.common.proxy.get_document_range_formatting(
+                path,
+                range,
+                options,
+                move |result| send(result),
+            ),
+            None => self.common.proxy.get_document_formatting(
+                path,
+                options,
+                move |result| send(result),
+            ),
+        }
+    }
+
+    /// Format the whole document with the formatter `command`, as a single
+    /// undo step, unless it's edited before the command is done.
+    fn format_with_command(&self, path: PathBuf, command: String) {
+        let rev = self.view.doc.with_untracked(|doc| doc.rev());
+        let editor = self.clone();
+        let internal_command = self.common.internal_command;
+        let send = create_ext_action(
+            self.scope,
+            move |result: Result<ProxyResponse, RpcError>| match result {
+                Ok(ProxyResponse::FormatWithCommandResponse { text }) => {
+                    if editor.view.doc.with_untracked(|doc| doc.rev()) == rev {
+                        let text = Rope::from(text);
+                        let hunks = editor.diff_lines(&text);
+                        editor.replace_lines(&text, &hunks);
+                    }
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    internal_command.send(InternalCommand::ShowMessage {
+                        title: "Format Document".to_string(),
+                        message: ShowMessageParams {
+                            typ: MessageType::ERROR,
+                            message: err.message,
+                        },
+                    });
+                }
+            },
+        );
+        self.common
+            .proxy
+            .format_with_command(path, command, move |result| send(result));
+    }
+
     pub fn save(
         &self,
         allow_formatting: bool,
@@ -1681,26 +3197,50 @@ pub fn save(
             let format_on_save = allow_formatting && config.editor.format_on_save;
             if format_on_save {
                 let editor = self.clone();
+                let internal_command = self.common.internal_command;
+                let file_name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
                 let send = create_ext_action(self.scope, move |result| {
-                    if let Ok(Ok(ProxyResponse::GetDocumentFormatting { edits })) =
-                        result
-                    {
-                        let current_rev =
-                            editor.view.doc.with_untracked(|doc| doc.rev());
-                        if current_rev == rev {
-                            editor.do_text_edit(&edits);
+                    match result {
+                        Ok(Ok(ProxyResponse::GetDocumentFormatting { edits })) => {
+                            let current_rev =
+                                editor.view.doc.with_untracked(|doc| doc.rev());
+                            if current_rev == rev {
+                                editor.do_text_edit(&edits);
+                            }
+                        }
+                        // A server that hangs doesn't keep the file from
+                        // being saved
+                        Err(_) => {
+                            internal_command.send(InternalCommand::ShowMessage {
+                                title: "Format on Save".to_string(),
+                                message: ShowMessageParams {
+                                    typ: MessageType::WARNING,
+                                    message: format!(
+                                        "Formatting {file_name} timed out, so it \
+                                         was saved without formatting"
+                                    ),
+                                },
+                            });
                         }
+                        _ => {}
                     }
                     editor.do_save(after_action);
                 });
 
+                let options = self
+                    .view
+                    .doc
+                    .with_untracked(|doc| doc.formatting_options(&config));
                 let (tx, rx) = crossbeam_channel::bounded(1);
                 let proxy = self.common.proxy.clone();
                 std::thread::spawn(move || {
-                    proxy.get_document_formatting(path, move |result| {
+                    proxy.get_document_formatting(path, options, move |result| {
                         let _ = tx.send(result);
                     });
-                    let result = rx.recv_timeout(std::time::Duration::from_secs(1));
+                    let result = rx.recv_timeout(FORMAT_ON_SAVE_TIMEOUT);
                     send(result);
                 });
             } else {
@@ -1721,6 +3261,7 @@ fn search_whole_word_forward(&self, mods: ModifiersState) {
         self.common.internal_command.send(InternalCommand::Search {
             pattern: Some(word),
         });
+        self.common.find.record_search();
         let next = self.common.find.next(buffer.text(), offset, false, true);
 
         if let Some((start, _end)) = next {
@@ -1729,10 +3270,49 @@ fn search_whole_word_forward(&self, mods: ModifiersState) {
                 None,
                 mods,
             );
+        } else {
+            self.notify_error_feedback();
+        }
+    }
+
+    /// Set the search input to the query searched for before or after the one
+    /// in it.
+    fn recall_search(&self, older: bool) {
+        let current = self.common.find.query().unwrap_or_default();
+        let query = self.common.find.history.try_update(|history| {
+            if older {
+                history.older(&current)
+            } else {
+                history.newer(&current)
+            }
+        });
+        if let Some(query) = query.flatten() {
+            self.common.internal_command.send(InternalCommand::Search {
+                pattern: Some(query),
+            });
         }
     }
 
+    /// Search for the latest query in the history when there's none, as after
+    /// a restart, and remember the current one.
+    fn record_search(&self) {
+        if self.common.find.query().is_none() {
+            let last = self
+                .common
+                .find
+                .history
+                .with_untracked(|history| history.last().map(|s| s.to_string()));
+            if last.is_some() {
+                self.common
+                    .internal_command
+                    .send(InternalCommand::Search { pattern: last });
+            }
+        }
+        self.common.find.record_search();
+    }
+
     fn search_forward(&self, mods: ModifiersState) {
+        self.record_search();
         let offset = self.cursor.with_untracked(|c| c.offset());
         let buffer = self.view.doc.with_untracked(|doc| doc.buffer().clone());
         let next = self.common.find.next(buffer.text(), offset, false, true);
@@ -1743,10 +3323,13 @@ fn search_forward(&self, mods: ModifiersState) {
                 None,
                 mods,
             );
+        } else {
+            self.notify_error_feedback();
         }
     }
 
     fn search_backward(&self, mods: ModifiersState) {
+        self.record_search();
         let offset = self.cursor.with_untracked(|c| c.offset());
         let buffer = self.view.doc.with_untracked(|doc| doc.buffer().clone());
         let next = self.common.find.next(buffer.text(), offset, true, true);
@@ -1757,6 +3340,8 @@ fn search_backward(&self, mods: ModifiersState) {
                 None,
                 mods,
             );
+        } else {
+            self.notify_error_feedback();
         }
     }
 
@@ -1767,7 +3352,8 @@ fn replace_next(&self, text: &str) {
 
         if let Some((start, end)) = next {
             let selection = Selection::region(start, end);
-            self.do_edit(&selection, &[(selection.clone(), text)]);
+            let text = self.replacement(buffer.text(), start, end, text);
+            self.do_edit(&selection, &[(selection.clone(), text.as_str())]);
         }
     }
 
@@ -1776,21 +3362,71 @@ fn replace_all(&self, text: &str) {
 
         self.view.update_find();
 
-        let edits: Vec<(Selection, &str)> = self
-            .view
-            .find_result()
-            .occurrences
-            .get_untracked()
+        let buffer = self.view.doc.with_untracked(|doc| doc.buffer().clone());
+        let find_result = self.view.find_result();
+        let occurrences = if find_result.progress.get_untracked()
+            == FindProgress::Ready
+        {
+            find_result.occurrences.get_untracked()
+        } else {
+            // The matches are still being found, and they all have to be
+            // replaced
+            let mut occurrences = Selection::new();
+            if let Some(search) = self.common.find.search_string.get_untracked() {
+                Find::find(
+                    buffer.text(),
+                    &search,
+                    0,
+                    buffer.len(),
+                    self.common.find.case_matching.get_untracked(),
+                    self.common.find.whole_words.get_untracked(),
+                    true,
+                    None,
+                    &mut occurrences,
+                );
+            }
+            occurrences
+        };
+        let replacements: Vec<(Selection, String)> = occurrences
             .regions()
             .iter()
-            .map(|region| (Selection::region(region.start, region.end), text))
+            .map(|region| {
+                let (start, end) = (region.start, region.end);
+                let text = self.replacement(buffer.text(), start, end, text);
+                (Selection::region(start, end), text)
+            })
+            .collect();
+        let edits: Vec<(&Selection, &str)> = replacements
+            .iter()
+            .map(|(selection, text)| (selection, text.as_str()))
             .collect();
         if !edits.is_empty() {
             self.do_edit(&Selection::caret(offset), &edits);
         }
     }
 
+    /// What replaces the match from `start` to `end`, in its case if the
+    /// replacement preserves it.
+    fn replacement(
+        &self,
+        text: &Rope,
+        start: usize,
+        end: usize,
+        new: &str,
+    ) -> String {
+        if self.common.find.preserve_case.get_untracked() {
+            preserve_case(&text.slice_to_cow(start..end), new)
+        } else {
+            new.to_string()
+        }
+    }
+
     pub fn save_doc_position(&self) {
+        // Editors out of the editor tabs, like the palette's preview, only show
+        // a file in passing
+        if self.editor_tab_id.is_none() && self.diff_editor_id.is_none() {
+            return;
+        }
         let path = match self.view.doc.with_untracked(|doc| {
             if doc.loaded() {
                 doc.content.path().cloned()
@@ -1803,15 +3439,41 @@ pub fn save_doc_position(&self) {
         };
 
         let cursor_offset = self.cursor.with_untracked(|c| c.offset());
+        let cursor = self
+            .view
+            .doc
+            .with_untracked(|doc| doc.buffer().offset_to_line_col(cursor_offset));
         let scroll_offset = self.viewport.with_untracked(|v| v.origin().to_vec2());
 
         let db: Arc<LapceDb> = use_context().unwrap();
-        db.save_doc_position(
-            &self.common.workspace,
-            path,
-            cursor_offset,
-            scroll_offset,
-        );
+        db.save_doc_position(&self.common.workspace, path, cursor, scroll_offset);
+    }
+
+    /// Remember the cursor and scroll position on the current doc, so they can
+    /// be restored when this editor shows the doc again.
+    pub fn save_view_state(&self) {
+        let state = EditorViewState {
+            cursor: self.cursor.get_untracked(),
+            scroll_offset: self.viewport.with_untracked(|v| v.origin().to_vec2()),
+        };
+        self.view
+            .doc
+            .with_untracked(|doc| doc.save_view_state(self.editor_id, state));
+    }
+
+    /// Restore the position this editor last had on the current doc, returns
+    /// false if there wasn't any.
+    fn restore_view_state(&self) -> bool {
+        let state = self
+            .view
+            .doc
+            .with_untracked(|doc| doc.take_view_state(self.editor_id));
+        let Some(state) = state else {
+            return false;
+        };
+        self.cursor.set(state.cursor);
+        self.scroll_to.set(Some(state.scroll_offset));
+        true
     }
 
     fn rename(&self) {
@@ -1835,8 +3497,35 @@ fn rename(&self) {
         let doc = self.view.doc;
         let internal_command = self.common.internal_command;
         let local_path = path.clone();
-        let send = create_ext_action(self.scope, move |result| {
-            if let Ok(ProxyResponse::PrepareRename { resp }) = result {
+        let send = create_ext_action(
+            self.scope,
+            move |result: Result<ProxyResponse, RpcError>| {
+                let cant_rename = || {
+                    internal_command.send(InternalCommand::ShowMessage {
+                        title: "Rename".to_string(),
+                        message: ShowMessageParams {
+                            typ: MessageType::INFO,
+                            message: "Nothing to rename at the cursor".to_string(),
+                        },
+                    });
+                };
+                let resp = match result {
+                    Ok(ProxyResponse::PrepareRename { resp }) => resp,
+                    // The server can't tell where renaming is possible, so the
+                    // word at the cursor is offered
+                    Err(err) if err.code == RpcError::NOT_CAPABLE => {
+                        lsp_types::PrepareRenameResponse::DefaultBehavior {
+                            default_behavior: true,
+                        }
+                    }
+                    // A null response, when the server says it can't be renamed
+                    Err(_) => {
+                        cant_rename();
+                        return;
+                    }
+                    Ok(_) => return,
+                };
+
                 if doc.with_untracked(|doc| doc.rev()) != rev {
                     return;
                 }
@@ -1881,14 +3570,18 @@ fn rename(&self) {
                         doc.buffer().slice_to_cow(start..end).to_string()
                     })
                 });
+                if placeholder.trim().is_empty() {
+                    cant_rename();
+                    return;
+                }
                 internal_command.send(InternalCommand::StartRename {
                     path: local_path.clone(),
                     placeholder,
                     start,
                     position,
                 });
-            }
-        });
+            },
+        );
         self.common
             .proxy
             .prepare_rename(path, position, move |result| {
@@ -1936,6 +3629,7 @@ pub fn word_at_cursor(&self) -> String {
     }
 
     pub fn clear_search(&self) {
+        self.common.find.record_search();
         self.common.find.visual.set(false);
         self.find_focus.set(false);
     }
@@ -1957,7 +3651,9 @@ fn search(&self) {
         self.common.find.replace_focus.set(false);
     }
 
-    pub fn pointer_down(&self, pointer_event: &PointerInputEvent) {
+    /// Handle a click in the editor, returning whether it ran a mouse binding
+    /// instead of the default behavior.
+    pub fn pointer_down(&self, pointer_event: &PointerInputEvent) -> bool {
         if let Some(editor_tab_id) = self.editor_tab_id {
             self.common
                 .internal_command
@@ -1967,6 +3663,23 @@ pub fn pointer_down(&self, pointer_event: &PointerInputEvent) {
             self.common.focus.set(Focus::Workbench);
             self.find_focus.set(false);
         }
+
+        let keypress = self.common.keypress.get_untracked();
+        if keypress.is_bound(pointer_event, self) {
+            // The command of a binding of a click runs at the clicked position
+            if let PointerButton::Primary
+            | PointerButton::Secondary
+            | PointerButton::Auxiliary = pointer_event.button
+            {
+                let mode = self.cursor.with_untracked(|c| c.get_mode());
+                let (offset, _) = self.view.offset_of_point(mode, pointer_event.pos);
+                self.cursor
+                    .update(|cursor| cursor.set_offset(offset, false, false));
+            }
+            keypress.key_down(pointer_event, self);
+            return true;
+        }
+
         match pointer_event.button {
             PointerButton::Primary => {
                 self.active.set(true);
@@ -1975,8 +3688,12 @@ pub fn pointer_down(&self, pointer_event: &PointerInputEvent) {
             PointerButton::Secondary => {
                 self.right_click(pointer_event);
             }
+            PointerButton::Auxiliary => {
+                self.middle_click(pointer_event);
+            }
             _ => {}
         }
+        false
     }
 
     fn left_click(&self, pointer_event: &PointerInputEvent) {
@@ -1995,6 +3712,15 @@ fn left_click(&self, pointer_event: &PointerInputEvent) {
     }
 
     fn single_click(&self, pointer_event: &PointerInputEvent) {
+        if let Some(literal) = self.view.color_swatch_at(pointer_event.pos) {
+            self.common
+                .internal_command
+                .send(InternalCommand::ShowColorPicker {
+                    editor_id: self.editor_id,
+                    literal,
+                });
+            return;
+        }
         let mode = self.cursor.with_untracked(|c| c.get_mode());
         let (new_offset, _) = self.view.offset_of_point(mode, pointer_event.pos);
         self.cursor.update(|cursor| {
@@ -2038,6 +3764,31 @@ fn triple_click(&self, pointer_event: &PointerInputEvent) {
         });
     }
 
+    /// Paste the primary selection where the middle click was, as a single
+    /// edit to undo.
+    fn middle_click(&self, pointer_event: &PointerInputEvent) {
+        let (enabled, read_only) = (
+            self.common
+                .config
+                .with_untracked(|config| config.editor.primary_selection),
+            self.view.doc.with_untracked(|doc| doc.content.read_only()),
+        );
+        if !enabled || read_only {
+            return;
+        }
+        let Some(text) = PrimarySelection.get_string() else {
+            return;
+        };
+        if text.is_empty() {
+            return;
+        }
+
+        let mode = self.cursor.with_untracked(|c| c.get_mode());
+        let (offset, _) = self.view.offset_of_point(mode, pointer_event.pos);
+        let selection = Selection::caret(offset);
+        self.do_edit(&selection, &[(selection.clone(), text.as_str())]);
+    }
+
     pub fn pointer_move(&self, pointer_event: &PointerMoveEvent) {
         let mode = self.cursor.with_untracked(|c| c.get_mode());
         let (offset, is_inside) = self.view.offset_of_point(mode, pointer_event.pos);
@@ -2069,6 +3820,8 @@ pub fn pointer_move(&self, pointer_event: &PointerMoveEvent) {
                     .doc
                     .with_untracked(|doc| doc.buffer().prev_code_boundary(offset));
 
+                // The mouse moved on from the hover being asked for
+                self.common.hover.next_request();
                 let editor = self.clone();
                 let mouse_hover_timer = self.common.mouse_hover_timer;
                 let timer_token =
@@ -2079,6 +3832,7 @@ pub fn pointer_move(&self, pointer_event: &PointerMoveEvent) {
                     });
                 mouse_hover_timer.set(timer_token);
             } else {
+                self.common.hover.next_request();
                 self.common.mouse_hover_timer.set(TimerToken::INVALID);
             }
         }
@@ -2100,46 +3854,77 @@ fn right_click(&self, pointer_event: &PointerInputEvent) {
             self.single_click(pointer_event);
         }
 
-        let is_file = self.view.doc.with_untracked(|doc| doc.content.is_file());
+        let (is_file, read_only) = self
+            .view
+            .doc
+            .with_untracked(|doc| (doc.content.is_file(), doc.content.read_only()));
         let mut menu = Menu::new("");
-        let cmds = if is_file {
-            vec![
+        let mut cmds = Vec::new();
+        if is_file {
+            cmds.extend([
                 Some(CommandKind::Focus(FocusCommand::GotoDefinition)),
                 Some(CommandKind::Focus(FocusCommand::GotoTypeDefinition)),
+                Some(CommandKind::Focus(FocusCommand::FindReferences)),
                 None,
                 Some(CommandKind::Focus(FocusCommand::Rename)),
                 None,
-                Some(CommandKind::Edit(EditCommand::ClipboardCut)),
-                Some(CommandKind::Edit(EditCommand::ClipboardCopy)),
-                Some(CommandKind::Edit(EditCommand::ClipboardPaste)),
-                None,
-                Some(CommandKind::Workbench(
-                    LapceWorkbenchCommand::PaletteCommand,
-                )),
-            ]
-        } else {
-            vec![
-                Some(CommandKind::Edit(EditCommand::ClipboardCut)),
-                Some(CommandKind::Edit(EditCommand::ClipboardCopy)),
-                Some(CommandKind::Edit(EditCommand::ClipboardPaste)),
+            ]);
+        }
+        cmds.extend([
+            Some(CommandKind::Edit(EditCommand::ClipboardCut)),
+            Some(CommandKind::Edit(EditCommand::ClipboardCopy)),
+            Some(CommandKind::Edit(EditCommand::ClipboardPaste)),
+            None,
+            Some(CommandKind::MultiSelection(
+                MultiSelectionCommand::SelectAll,
+            )),
+            None,
+            Some(CommandKind::Focus(FocusCommand::CopyFilePath)),
+            Some(CommandKind::Focus(FocusCommand::CopyRelativeFilePath)),
+            Some(CommandKind::Focus(FocusCommand::CopyLocation)),
+            Some(CommandKind::Focus(FocusCommand::CopyWithLineNumbers)),
+            None,
+        ]);
+        if self.selected_text().is_some() {
+            cmds.extend([
+                Some(CommandKind::Focus(FocusCommand::SelectionToScratch)),
+                Some(CommandKind::Focus(FocusCommand::MoveSelectionToScratch)),
+                Some(CommandKind::Focus(FocusCommand::ExtractSelectionToFile)),
                 None,
-                Some(CommandKind::Workbench(
-                    LapceWorkbenchCommand::PaletteCommand,
-                )),
-            ]
-        };
+            ]);
+        }
+        cmds.push(Some(CommandKind::Workbench(
+            LapceWorkbenchCommand::PaletteCommand,
+        )));
         let lapce_command = self.common.lapce_command;
         for cmd in cmds {
             if let Some(cmd) = cmd {
+                let changes_buffer = matches!(
+                    cmd,
+                    CommandKind::Edit(EditCommand::ClipboardCut)
+                        | CommandKind::Edit(EditCommand::ClipboardPaste)
+                        | CommandKind::Focus(FocusCommand::Rename)
+                        | CommandKind::Focus(FocusCommand::MoveSelectionToScratch)
+                        | CommandKind::Focus(FocusCommand::ExtractSelectionToFile)
+                );
+                let uses_selection = matches!(
+                    cmd,
+                    CommandKind::Edit(EditCommand::ClipboardCut)
+                        | CommandKind::Edit(EditCommand::ClipboardCopy)
+                );
+                let editor = self.clone();
                 menu = menu.entry(
-                    MenuItem::new(cmd.desc().unwrap_or_else(|| cmd.str())).action(
-                        move || {
+                    MenuItem::new(cmd.desc().unwrap_or_else(|| cmd.str()))
+                        .enabled(!(read_only && changes_buffer))
+                        .action(move || {
+                            if uses_selection {
+                                editor.select_word_if_caret();
+                            }
                             lapce_command.send(LapceCommand {
                                 kind: cmd.clone(),
                                 data: None,
                             })
-                        },
-                    ),
+                        }),
                 );
             } else {
                 menu = menu.separator();
@@ -2148,13 +3933,42 @@ fn right_click(&self, pointer_event: &PointerInputEvent) {
         show_context_menu(menu, None);
     }
 
-    fn update_hover(&self, offset: usize) {
-        let (path, position) = self.view.doc.with_untracked(|doc| {
-            (
-                doc.content.path().cloned(),
-                doc.buffer().offset_to_position(offset),
-            )
+    /// Select the word under the cursor when nothing is selected, so that
+    /// cutting or copying from the context menu takes that word.
+    fn select_word_if_caret(&self) {
+        let is_caret = self.view.doc.with_untracked(|doc| {
+            self.cursor
+                .with_untracked(|c| c.edit_selection(doc.buffer()).is_caret())
         });
+        if !is_caret {
+            return;
+        }
+        let offset = self.cursor.with_untracked(|c| c.offset());
+        let (start, end) = self.view.select_word(offset);
+        if start < end {
+            self.cursor
+                .update(|cursor| cursor.add_region(start, end, false, false));
+        }
+    }
+
+    fn update_hover(&self, offset: usize) {
+        let (path, position, language, diagnostics) =
+            self.view.doc.with_untracked(|doc| {
+                let diagnostics: Vec<Diagnostic> =
+                    doc.diagnostics.diagnostics.with_untracked(|diagnostics| {
+                        diagnostics
+                            .iter()
+                            .filter(|d| d.range.0 <= offset && offset <= d.range.1)
+                            .map(|d| d.diagnostic.clone())
+                            .collect()
+                    });
+                (
+                    doc.content.path().cloned(),
+                    doc.buffer().offset_to_position(offset),
+                    doc.syntax().language,
+                    diagnostics,
+                )
+            });
         let path = match path {
             Some(path) => path,
             None => return,
@@ -2162,18 +3976,33 @@ fn update_hover(&self, offset: usize) {
         let config = self.common.config;
         let hover_data = self.common.hover.clone();
         let editor_id = self.editor_id;
+        let request_id = hover_data.next_request();
         let send = create_ext_action(self.scope, move |resp| {
+            if hover_data.request_id.get_untracked() != request_id {
+                return;
+            }
+            let config = config.get_untracked();
+            // The diagnostics under the mouse come first, and show even when
+            // the server has nothing to say about the offset
+            let mut content = diagnostic_hover_content(&diagnostics, &config);
             if let Ok(ProxyResponse::HoverResponse { hover, .. }) = resp {
-                let content = parse_hover_resp(hover, &config.get_untracked());
+                if !content.is_empty() {
+                    content.push(MarkdownContent::Separator);
+                }
+                content.extend(parse_hover_resp(hover, language, &config));
+            }
+            if !content.is_empty() {
                 hover_data.content.set(content);
                 hover_data.offset.set(offset);
                 hover_data.editor_id.set(editor_id);
                 hover_data.active.set(true);
             }
         });
-        self.common.proxy.get_hover(0, path, position, |resp| {
-            send(resp);
-        });
+        self.common
+            .proxy
+            .get_hover(request_id, path, position, |resp| {
+                send(resp);
+            });
     }
 
     // reset the doc inside and move cursor back
@@ -2357,6 +4186,24 @@ pub fn screen_lines(&self) -> ScreenLines {
                                             y: visual_line * line_height,
                                         },
                                     );
+                                    let difference =
+                                        bothinfo.differences.iter().find(
+                                            |(line, _)| *line == actual_line - start,
+                                        );
+                                    if let Some((_, difference)) = difference {
+                                        diff_sections.push(DiffSection {
+                                            start_line: visual_line,
+                                            height: 1,
+                                            kind: match difference {
+                                                LineDifference::Whitespace => {
+                                                    DiffSectionKind::Whitespace
+                                                }
+                                                LineDifference::LineEnding => {
+                                                    DiffSectionKind::LineEnding
+                                                }
+                                            },
+                                        });
+                                    }
                                 }
                                 visual_line += 1;
                                 actual_line += 1;
@@ -2398,6 +4245,9 @@ fn check_condition(&self, condition: Condition) -> bool {
             Condition::ListFocus => self.has_completions(),
             Condition::CompletionFocus => self.has_completions(),
             Condition::InSnippet => self.snippet.with_untracked(|s| s.is_some()),
+            Condition::MotionMode => {
+                self.cursor.with_untracked(|c| c.motion_mode.is_some())
+            }
             Condition::EditorFocus => {
                 self.view.doc.with_untracked(|doc| !doc.content.is_local())
             }
@@ -2430,9 +4280,21 @@ fn run_command(
         count: Option<usize>,
         mods: ModifiersState,
     ) -> crate::command::CommandExecuted {
+        // The keys type the labels of a jump, and any other key leaves it
+        if self.jump.with_untracked(|jump| jump.is_some()) {
+            self.jump.set(None);
+            return CommandExecuted::Yes;
+        }
+
         if self.common.find.visual.get_untracked() && self.find_focus.get_untracked()
         {
             match &command.kind {
+                CommandKind::Move(cmd @ (MoveCommand::Up | MoveCommand::Down))
+                    if !self.common.find.replace_focus.get_untracked() =>
+                {
+                    self.recall_search(*cmd == MoveCommand::Up);
+                    return CommandExecuted::Yes;
+                }
                 CommandKind::Edit(_)
                 | CommandKind::Move(_)
                 | CommandKind::MultiSelection(_) => {
@@ -2487,6 +4349,7 @@ fn expect_char(&self) -> bool {
             false
         } else {
             self.inline_find.with_untracked(|f| f.is_some())
+                || self.jump.with_untracked(|jump| jump.is_some())
         }
     }
 
@@ -2503,6 +4366,8 @@ fn receive_char(&self, c: &str) {
                     InternalCommand::FindEditorReceiveChar { s: c.to_string() },
                 );
             }
+        } else if let Some(jump) = self.jump.get_untracked() {
+            self.jump_receive_char(jump, c);
         } else {
             // normal editor receive char
             if self.get_mode() == Mode::Insert {
@@ -2515,9 +4380,11 @@ fn receive_char(&self, c: &str) {
                     .unwrap();
                 self.cursor.set(cursor);
 
-                if !c
-                    .chars()
-                    .all(|c| c.is_whitespace() || c.is_ascii_whitespace())
+                // Pasted lines don't ask for completions
+                if !c.contains('\n')
+                    && !c
+                        .chars()
+                        .all(|c| c.is_whitespace() || c.is_ascii_whitespace())
                 {
                     self.update_completion(false);
                 } else {
@@ -2528,6 +4395,10 @@ fn receive_char(&self, c: &str) {
                 self.inline_find(direction.clone(), c);
                 self.last_inline_find.set(Some((direction, c.to_string())));
                 self.inline_find.set(None);
+            } else if self.cursor.with_untracked(|c| c.motion_mode.is_some()) {
+                // A key that's no motion drops the pending operator
+                self.cursor.update(|cursor| cursor.motion_mode = None);
+                self.notify_error_feedback();
             }
         }
     }
@@ -2603,13 +4474,34 @@ fn blink_cursor(
     }
 }
 
+/// The messages of the diagnostics at the hovered offset.
+fn diagnostic_hover_content(
+    diagnostics: &[Diagnostic],
+    config: &LapceConfig,
+) -> Vec<MarkdownContent> {
+    let entries = diagnostics.iter().map(|diagnostic| {
+        let message = match diagnostic.source.as_deref() {
+            Some(source) => format!("{} ({source})", diagnostic.message),
+            None => diagnostic.message.clone(),
+        };
+        from_plaintext(&message, 1.5, config)
+    });
+    itertools::Itertools::intersperse(entries, vec![MarkdownContent::Separator])
+        .flatten()
+        .collect()
+}
+
 fn parse_hover_resp(
     hover: lsp_types::Hover,
+    language: LapceLanguage,
     config: &LapceConfig,
 ) -> Vec<MarkdownContent> {
+    let language = Some(language);
     match hover.contents {
         HoverContents::Scalar(text) => match text {
-            MarkedString::String(text) => parse_markdown(&text, 1.5, config),
+            MarkedString::String(text) => {
+                parse_markdown_in(&text, 1.5, language, config)
+            }
             MarkedString::LanguageString(code) => parse_markdown(
                 &format!("```{}\n{}\n```", code.language, code.value),
                 1.5,
@@ -2619,7 +4511,12 @@ fn parse_hover_resp(
         HoverContents::Array(array) => {
             let entries = array
                 .into_iter()
-                .map(|t| from_marked_string(t, config))
+                .map(|t| match t {
+                    MarkedString::String(text) => {
+                        parse_markdown_in(&text, 1.5, language, config)
+                    }
+                    t => from_marked_string(t, config),
+                })
                 .rev();
 
             // TODO: It'd be nice to avoid this vec
@@ -2632,7 +4529,68 @@ fn parse_hover_resp(
         }
         HoverContents::Markup(content) => match content.kind {
             MarkupKind::PlainText => from_plaintext(&content.value, 1.5, config),
-            MarkupKind::Markdown => parse_markdown(&content.value, 1.5, config),
+            MarkupKind::Markdown => {
+                parse_markdown_in(&content.value, 1.5, language, config)
+            }
         },
     }
 }
+
+/// The locations of a definition response, taking the symbol's own range out
+/// of links.
+fn definition_locations(definition: GotoDefinitionResponse) -> Vec<Location> {
+    match definition {
+        GotoDefinitionResponse::Scalar(location) => vec![location],
+        GotoDefinitionResponse::Array(locations) => locations,
+        GotoDefinitionResponse::Link(links) => links
+            .into_iter()
+            .map(|link| Location {
+                uri: link.target_uri,
+                range: link.target_selection_range,
+            })
+            .collect(),
+    }
+}
+
+/// Jump to the only location, let the user pick one of several in the
+/// palette, grouped by file, or say there are none.
+fn show_locations(
+    internal_command: Listener<InternalCommand>,
+    title: &str,
+    not_found: &str,
+    mut locations: Vec<Location>,
+) {
+    locations.sort_by(|a, b| {
+        (a.uri.as_str(), a.range.start.line, a.range.start.character).cmp(&(
+            b.uri.as_str(),
+            b.range.start.line,
+            b.range.start.character,
+        ))
+    });
+    locations.dedup();
+    let mut locations = locations
+        .into_iter()
+        .map(|l| EditorLocation {
+            path: path_from_url(&l.uri),
+            position: Some(EditorPosition::Position(l.range.start)),
+            scroll_offset: None,
+            ignore_unconfirmed: false,
+            same_editor_tab: false,
+        })
+        .collect::<Vec<_>>();
+    match locations.len() {
+        0 => internal_command.send(InternalCommand::ShowMessage {
+            title: title.to_string(),
+            message: ShowMessageParams {
+                typ: MessageType::INFO,
+                message: not_found.to_string(),
+            },
+        }),
+        1 => internal_command.send(InternalCommand::JumpToLocation {
+            location: locations.remove(0),
+        }),
+        _ => internal_command.send(InternalCommand::PaletteReferences {
+            references: locations,
+        }),
+    }
+}
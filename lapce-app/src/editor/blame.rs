@@ -0,0 +1,105 @@
+use lapce_rpc::source_control::BlameHunk;
+
+/// The hunk of the blame that `line` is in.
+pub fn blame_hunk(hunks: &im::Vector<BlameHunk>, line: usize) -> Option<&BlameHunk> {
+    hunks
+        .iter()
+        .find(|hunk| hunk.start_line <= line && line < hunk.start_line + hunk.lines)
+}
+
+/// The text shown at the end of `line`, with `now` in seconds since the Unix
+/// epoch. `blame` is `None` while it is still being retrieved.
+pub fn inline_blame_text(
+    blame: Option<&im::Vector<BlameHunk>>,
+    line: usize,
+    now: i64,
+) -> Option<String> {
+    let Some(hunks) = blame else {
+        return Some("Loading blame…".to_string());
+    };
+    let hunk = blame_hunk(hunks, line)?;
+    let text = match &hunk.commit {
+        Some(commit) => format!(
+            "{}, {} • {}",
+            commit.author,
+            relative_time(now - commit.time),
+            commit.summary
+        ),
+        None => "Uncommitted changes".to_string(),
+    };
+    Some(text)
+}
+
+/// How long ago something happened `seconds` ago, in the largest unit that
+/// fits.
+fn relative_time(seconds: i64) -> String {
+    const UNITS: [(i64, &str); 6] = [
+        (60 * 60 * 24 * 365, "year"),
+        (60 * 60 * 24 * 30, "month"),
+        (60 * 60 * 24 * 7, "week"),
+        (60 * 60 * 24, "day"),
+        (60 * 60, "hour"),
+        (60, "minute"),
+    ];
+    for (unit, name) in UNITS {
+        let count = seconds / unit;
+        if count > 0 {
+            let plural = if count == 1 { "" } else { "s" };
+            return format!("{count} {name}{plural} ago");
+        }
+    }
+    "just now".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use lapce_rpc::source_control::{BlameCommit, BlameHunk};
+
+    use super::{inline_blame_text, relative_time};
+
+    #[test]
+    fn test_relative_time() {
+        assert_eq!(relative_time(-5), "just now");
+        assert_eq!(relative_time(59), "just now");
+        assert_eq!(relative_time(60), "1 minute ago");
+        assert_eq!(relative_time(60 * 60 * 5), "5 hours ago");
+        assert_eq!(relative_time(60 * 60 * 24 * 40), "1 month ago");
+        assert_eq!(relative_time(60 * 60 * 24 * 365 * 3), "3 years ago");
+    }
+
+    #[test]
+    fn test_inline_blame_text() {
+        let hunks = im::vector![
+            BlameHunk {
+                start_line: 0,
+                lines: 2,
+                commit: Some(BlameCommit {
+                    id: "0123456789".to_string(),
+                    author: "Ann".to_string(),
+                    time: 1000,
+                    summary: "Add things".to_string(),
+                    message: "Add things\n\nMore about them".to_string(),
+                }),
+            },
+            BlameHunk {
+                start_line: 2,
+                lines: 1,
+                commit: None,
+            },
+        ];
+        let now = 1000 + 60 * 60 * 24 * 2;
+        assert_eq!(
+            inline_blame_text(Some(&hunks), 1, now).as_deref(),
+            Some("Ann, 2 days ago • Add things")
+        );
+        assert_eq!(
+            inline_blame_text(Some(&hunks), 2, now).as_deref(),
+            Some("Uncommitted changes")
+        );
+        assert_eq!(inline_blame_text(Some(&hunks), 3, now), None);
+        assert_eq!(
+            inline_blame_text(None, 3, now).as_deref(),
+            Some("Loading blame…")
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use std::path::Path;
+
+/// `path` relative to `workspace` if it's in it, else as it is.
+pub fn relative_path<'a>(path: &'a Path, workspace: Option<&Path>) -> &'a Path {
+    workspace
+        .and_then(|workspace| path.strip_prefix(workspace).ok())
+        .unwrap_or(path)
+}
+
+/// A `path:line:column` reference, as compilers and grep print them, from the
+/// zero-based `line` and `column`.
+pub fn location_reference(path: &str, line: usize, column: usize) -> String {
+    format!("{path}:{}:{}", line + 1, column + 1)
+}
+
+/// The character column of the byte column `column` of `line`, which is what
+/// compilers and editors count columns in.
+pub fn char_column(line: &str, column: usize) -> usize {
+    line.get(..column)
+        .map_or(column, |before| before.chars().count())
+}
+
+/// `lines` each prefixed with its number, counting from the zero-based
+/// `first_line`, with the numbers lined up.
+pub fn with_line_numbers<'a>(
+    lines: impl IntoIterator<Item = &'a str>,
+    first_line: usize,
+) -> String {
+    let lines = lines.into_iter().collect::<Vec<_>>();
+    let width = (first_line + lines.len()).to_string().len();
+    let mut text = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            text.push('\n');
+        }
+        let number = first_line + i + 1;
+        if line.is_empty() {
+            text.push_str(&format!("{number:>width$} |"));
+        } else {
+            text.push_str(&format!("{number:>width$} | {line}"));
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{char_column, location_reference, relative_path, with_line_numbers};
+
+    #[test]
+    fn test_relative_path() {
+        let path = Path::new("/ws/src/main.rs");
+        assert_eq!(
+            relative_path(path, Some(Path::new("/ws"))),
+            Path::new("src/main.rs")
+        );
+        assert_eq!(relative_path(path, Some(Path::new("/other"))), path);
+        assert_eq!(relative_path(path, None), path);
+    }
+
+    #[test]
+    fn test_location_reference() {
+        assert_eq!(location_reference("src/main.rs", 0, 4), "src/main.rs:1:5");
+    }
+
+    #[test]
+    fn test_char_column() {
+        assert_eq!(char_column("let a = 1;", 4), 4);
+        assert_eq!(char_column("let é = 1;", 7), 6);
+        assert_eq!(char_column("// 日本語 x", 13), 7);
+    }
+
+    #[test]
+    fn test_with_line_numbers() {
+        assert_eq!(
+            with_line_numbers(["fn main() {", "", "}"], 7),
+            " 8 | fn main() {\n 9 |\n10 | }"
+        );
+        assert_eq!(with_line_numbers(["a"], 0), "1 | a");
+    }
+}
@@ -9,11 +9,10 @@
     views::{clip, empty, label, list, stack, svg, Decorators},
 };
 use lapce_core::buffer::{
-    diff::{expand_diff_lines, rope_diff, DiffExpand, DiffLines},
+    diff::{expand_diff_lines, rope_diff, DiffExpand, DiffLines, DiffOptions},
     rope_text::RopeText,
 };
-use lapce_rpc::{buffer::BufferId, proxy::ProxyResponse};
-use lapce_xi_rope::Rope;
+use lapce_rpc::buffer::BufferId;
 use serde::{Deserialize, Serialize};
 
 use crate::{
@@ -63,7 +62,7 @@ pub fn to_data(
                     common.proxy.clone(),
                     common.config,
                 )),
-                DocContent::History(history) => {
+                DocContent::History(_) => {
                     let doc = Document::new_hisotry(
                         cx,
                         content.clone(),
@@ -72,30 +71,15 @@ pub fn to_data(
                         common.config,
                     );
                     let doc = doc.scope.create_rw_signal(doc);
-
-                    let send = create_ext_action(cx, move |result| {
-                        if let Ok(ProxyResponse::BufferHeadResponse {
-                            content,
-                            ..
-                        }) = result
-                        {
-                            doc.update(|doc| {
-                                doc.init_content(Rope::from(content));
-                            });
-                        }
-                    });
-                    common.proxy.get_buffer_head(
-                        history.path.clone(),
-                        move |result| {
-                            send(result);
-                        },
-                    );
+                    Document::parse_in_background(doc);
+                    Document::load_history(doc);
                     doc
                 }
                 DocContent::Scratch { name, .. } => {
                     let doc_content = DocContent::Scratch {
                         id: BufferId::next(),
                         name: name.to_string(),
+                        read_only: false,
                     };
                     let doc = Document::new_content(
                         cx,
@@ -141,6 +125,8 @@ pub struct DiffEditorData {
     pub left: RwSignal<EditorData>,
     pub right: RwSignal<EditorData>,
     pub focus_right: RwSignal<bool>,
+    /// How the lines of the two sides are compared
+    pub options: RwSignal<DiffOptions>,
 }
 
 impl DiffEditorData {
@@ -179,6 +165,7 @@ pub fn new(
             left,
             right,
             focus_right: cx.create_rw_signal(true),
+            options: cx.create_rw_signal(DiffOptions::default()),
         };
 
         data.listen_diff_changes();
@@ -212,6 +199,7 @@ pub fn copy(
             id: diff_editor_id,
             editor_tab_id: cx.create_rw_signal(editor_tab_id),
             focus_right: cx.create_rw_signal(true),
+            options: cx.create_rw_signal(self.options.get_untracked()),
             left: cx.create_rw_signal(self.left.get_untracked().copy(
                 cx,
                 None,
@@ -230,6 +218,14 @@ pub fn copy(
         diff_editor
     }
 
+    /// Switch whether lines that only differ in whitespace count as changed,
+    /// which has the diff computed again.
+    pub fn toggle_ignore_whitespace(&self) {
+        self.options.update(|options| {
+            options.ignore_whitespace = !options.ignore_whitespace;
+        });
+    }
+
     fn listen_diff_changes(&self) {
         let cx = self.scope;
 
@@ -245,7 +241,9 @@ fn listen_diff_changes(&self) {
             right_doc.with(|doc| (doc.content.clone(), doc.rev()))
         });
 
+        let options = self.options;
         cx.create_effect(move |_| {
+            let options = options.get();
             let (_, left_rev) = left_doc_rev.get();
             let (left_editor_view, left_doc) =
                 left.with_untracked(|editor| (editor.view.kind, editor.view.doc));
@@ -296,6 +294,7 @@ fn listen_diff_changes(&self) {
                     right_rev,
                     right_atomic_rev.clone(),
                     Some(3),
+                    options,
                 );
                 send(changes);
             });
@@ -1,20 +1,26 @@
+use std::collections::HashMap;
+
 use floem::{
     context::PaintCx,
-    cosmic_text::{Attrs, AttrsList, FamilyOwned, TextLayout},
+    cosmic_text::{Attrs, AttrsList, TextLayout},
     id::Id,
-    peniko::kurbo::{Point, Rect, Size},
+    peniko::kurbo::{Circle, Point, Rect, Size},
     reactive::RwSignal,
     view::{ChangeFlags, View},
     Renderer, ViewContext,
 };
 use lapce_core::{buffer::rope_text::RopeText, mode::Mode};
+use lsp_types::DiagnosticSeverity;
 
 use crate::{
     config::{color::LapceColor, LapceConfig},
-    doc::Document,
+    doc::{diagnostic_color, Document},
 };
 
-use super::{view::changes_colors, EditorData};
+use super::{
+    view::{changes_colors, ScreenLines},
+    EditorData,
+};
 
 pub struct EditorGutterView {
     id: Id,
@@ -74,6 +80,50 @@ fn paint_head_changes(
         }
     }
 
+    /// Mark the lines diagnostics start on, in the color of the worst one.
+    fn paint_diagnostics(
+        &self,
+        cx: &mut PaintCx,
+        doc: RwSignal<Document>,
+        screen_lines: &ScreenLines,
+        viewport: Rect,
+        is_normal: bool,
+        config: &LapceConfig,
+    ) {
+        if !is_normal {
+            return;
+        }
+
+        let diagnostics = doc.with_untracked(|doc| doc.diagnostics.diagnostics);
+        let mut severities: HashMap<usize, DiagnosticSeverity> = HashMap::new();
+        diagnostics.with_untracked(|diagnostics| {
+            for diagnostic in diagnostics {
+                let line = diagnostic.diagnostic.range.start.line as usize;
+                if !screen_lines.info.contains_key(&line) {
+                    continue;
+                }
+                let severity = diagnostic
+                    .diagnostic
+                    .severity
+                    .unwrap_or(DiagnosticSeverity::WARNING);
+                severities
+                    .entry(line)
+                    .and_modify(|worst| *worst = (*worst).min(severity))
+                    .or_insert(severity);
+            }
+        });
+
+        let line_height = config.editor.line_height() as f64;
+        for (line, severity) in severities {
+            let y = screen_lines.info[&line].y as f64 - viewport.y0;
+            cx.fill(
+                &Circle::new(Point::new(-5.0, y + line_height / 2.0), 3.0),
+                config.get_color(diagnostic_color(Some(severity))),
+                0.0,
+            );
+        }
+    }
+
     fn paint_sticky_headers(
         &self,
         cx: &mut PaintCx,
@@ -190,8 +240,7 @@ fn paint(&mut self, cx: &mut floem::context::PaintCx) {
             .doc
             .with_untracked(|doc| doc.buffer().line_of_offset(offset));
 
-        let family: Vec<FamilyOwned> =
-            FamilyOwned::parse_list(&config.editor.font_family).collect();
+        let family = config.editor.font_family();
         let attrs = Attrs::new()
             .family(&family)
             .color(*config.get_color(LapceColor::EDITOR_DIM))
@@ -200,6 +249,9 @@ fn paint(&mut self, cx: &mut floem::context::PaintCx) {
         let current_line_attrs_list = AttrsList::new(
             attrs.color(*config.get_color(LapceColor::EDITOR_FOREGROUND)),
         );
+        let show_line_numbers = view
+            .options
+            .with_untracked(|options| options.line_numbers(&config));
         let show_relative = config.core.modal
             && config.editor.modal_mode_relative_line_numbers
             && mode != Mode::Insert
@@ -207,7 +259,7 @@ fn paint(&mut self, cx: &mut floem::context::PaintCx) {
 
         for line in &screen_lines.lines {
             let line = *line;
-            if line > last_line {
+            if line > last_line || !show_line_numbers {
                 break;
             }
 
@@ -243,6 +295,14 @@ fn paint(&mut self, cx: &mut floem::context::PaintCx) {
         }
 
         self.paint_head_changes(cx, view.doc, viewport, kind_is_normal, &config);
+        self.paint_diagnostics(
+            cx,
+            view.doc,
+            &screen_lines,
+            viewport,
+            kind_is_normal,
+            &config,
+        );
         self.paint_sticky_headers(cx, kind_is_normal, &config);
     }
 }
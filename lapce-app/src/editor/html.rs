@@ -0,0 +1,195 @@
+use std::{fmt::Write, ops::Range};
+
+use floem::peniko::Color;
+
+use crate::config::color_theme::SyntaxFontStyle;
+
+/// A line to render, with the highlighted spans of its text by their byte
+/// offsets within it
+pub struct HtmlLine<'a> {
+    pub number: usize,
+    pub text: &'a str,
+    pub spans: Vec<(Range<usize>, Color, Option<SyntaxFontStyle>)>,
+}
+
+/// The colors and font the lines are rendered with
+pub struct HtmlTheme<'a> {
+    pub foreground: Color,
+    pub background: Color,
+    /// The color of the line numbers, if they're shown
+    pub line_numbers: Option<Color>,
+    pub font_family: &'a str,
+}
+
+fn css_color(color: Color) -> String {
+    if color.a == u8::MAX {
+        format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+    } else {
+        format!(
+            "#{:02x}{:02x}{:02x}{:02x}",
+            color.r, color.g, color.b, color.a
+        )
+    }
+}
+
+fn escape_html(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+}
+
+/// A standalone HTML page showing `lines` in `theme`, with the styles inline
+/// so it can be pasted anywhere. The lines without spans are plain text.
+pub fn render_html(title: &str, lines: &[HtmlLine], theme: &HtmlTheme) -> String {
+    let mut html = String::from("<!DOCTYPE html>\n<html>\n<head>\n");
+    html.push_str("<meta charset=\"utf-8\">\n<title>");
+    escape_html(&mut html, title);
+    html.push_str("</title>\n</head>\n<body>\n");
+
+    let _ = write!(
+        html,
+        "<pre style=\"background-color: {}; color: {}; font-family: ",
+        css_color(theme.background),
+        css_color(theme.foreground),
+    );
+    escape_html(&mut html, theme.font_family);
+    html.push_str(", monospace; padding: 1em; overflow-x: auto;\">");
+
+    let number_width = lines
+        .iter()
+        .map(|line| (line.number + 1).to_string().len())
+        .max()
+        .unwrap_or(1);
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            html.push('\n');
+        }
+        if let Some(color) = theme.line_numbers {
+            let _ = write!(
+                html,
+                "<span style=\"color: {}; user-select: none;\">{:>width$}  </span>",
+                css_color(color),
+                line.number + 1,
+                width = number_width,
+            );
+        }
+
+        let mut offset = 0;
+        for (range, color, font_style) in &line.spans {
+            let end = range.end.min(line.text.len());
+            let start = range.start.max(offset).min(end);
+            if start == end
+                || !line.text.is_char_boundary(start)
+                || !line.text.is_char_boundary(end)
+            {
+                continue;
+            }
+            escape_html(&mut html, &line.text[offset..start]);
+            let _ = write!(html, "<span style=\"color: {}", css_color(*color));
+            if let Some(font_style) = font_style {
+                if font_style.bold {
+                    html.push_str("; font-weight: bold");
+                }
+                if font_style.italic {
+                    html.push_str("; font-style: italic");
+                }
+            }
+            html.push_str("\">");
+            escape_html(&mut html, &line.text[start..end]);
+            html.push_str("</span>");
+            offset = end;
+        }
+        escape_html(&mut html, &line.text[offset..]);
+    }
+
+    html.push_str("</pre>\n</body>\n</html>\n");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use floem::peniko::Color;
+
+    use super::{render_html, HtmlLine, HtmlTheme};
+    use crate::config::color_theme::SyntaxFontStyle;
+
+    fn theme(line_numbers: Option<Color>) -> HtmlTheme<'static> {
+        HtmlTheme {
+            foreground: Color::rgb8(0xdd, 0xdd, 0xdd),
+            background: Color::rgb8(0x11, 0x22, 0x33),
+            line_numbers,
+            font_family: "Cascadia Code",
+        }
+    }
+
+    fn body(html: &str) -> &str {
+        let pre = html.find("<pre").unwrap();
+        let start = pre + html[pre..].find("\">").unwrap() + 2;
+        let end = html.rfind("</pre>").unwrap();
+        &html[start..end]
+    }
+
+    #[test]
+    fn test_render_html() {
+        let red = Color::rgb8(0xff, 0, 0);
+        let lines = [
+            HtmlLine {
+                number: 0,
+                text: "if a < b && c > \"d\" {",
+                spans: vec![
+                    (
+                        0..2,
+                        red,
+                        Some(SyntaxFontStyle {
+                            italic: false,
+                            bold: true,
+                        }),
+                    ),
+                    (5..6, Color::rgba8(0, 0, 0xff, 0x80), None),
+                    // Spans running past the end of the line are cut there
+                    (16..40, red, None),
+                ],
+            },
+            HtmlLine {
+                number: 1,
+                text: "}",
+                spans: Vec::new(),
+            },
+        ];
+        let html = render_html("a <b>", &lines, &theme(None));
+        assert!(html.contains("<title>a &lt;b&gt;</title>"));
+        assert!(html.contains("background-color: #112233; color: #dddddd"));
+        assert_eq!(
+            body(&html),
+            concat!(
+                "<span style=\"color: #ff0000; font-weight: bold\">if</span> a ",
+                "<span style=\"color: #0000ff80\">&lt;</span> b &amp;&amp; c &gt; ",
+                "<span style=\"color: #ff0000\">&quot;d&quot; {</span>\n}",
+            )
+        );
+    }
+
+    #[test]
+    fn test_render_html_line_numbers() {
+        let lines = (8..10)
+            .map(|number| HtmlLine {
+                number,
+                text: "x",
+                spans: Vec::new(),
+            })
+            .collect::<Vec<_>>();
+        let html = render_html("", &lines, &theme(Some(Color::rgb8(0, 0, 0))));
+        let number = "<span style=\"color: #000000; user-select: none;\">";
+        assert_eq!(
+            body(&html),
+            format!("{number} 9  </span>x\n{number}10  </span>x")
+        );
+    }
+}
@@ -30,7 +30,8 @@ pub fn to_offset(&self, buffer: &Buffer) -> usize {
             EditorPosition::Position(position) => {
                 buffer.offset_of_position(position)
             }
-            EditorPosition::Offset(offset) => *offset,
+            // The offset might be from before the file changed
+            EditorPosition::Offset(offset) => (*offset).min(buffer.len()),
         }
     }
 }
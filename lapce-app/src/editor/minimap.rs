@@ -0,0 +1,437 @@
+use std::{ops::Range, sync::Arc};
+
+use floem::{
+    context::PaintCx,
+    event::{Event, EventListener},
+    id::Id,
+    peniko::kurbo::{Rect, Size, Vec2},
+    reactive::{create_effect, create_rw_signal, RwSignal},
+    view::{ChangeFlags, View},
+    views::Decorators,
+    Renderer, ViewContext,
+};
+use lapce_core::buffer::{rope_text::RopeText, InvalLines};
+use lapce_rpc::style::LineStyle;
+
+use super::EditorData;
+use crate::config::{color::LapceColor, LapceConfig};
+
+/// The number of columns of a line that the minimap represents, anything after
+/// that is cut off.
+const MAX_COLUMNS: usize = 120;
+/// The height of a line in the minimap when the whole document fits.
+const LINE_HEIGHT: f64 = 2.0;
+/// The minimum height of the viewport rectangle, so it stays draggable on long
+/// documents.
+const MIN_VIEWPORT_HEIGHT: f64 = 4.0;
+
+/// A run of non-whitespace columns on a line which share the same color.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MinimapBlock {
+    pub start: usize,
+    pub end: usize,
+    pub fg_color: Option<String>,
+}
+
+/// A summary of a single line, which is all the minimap needs for painting,
+/// so that it never has to lay out the actual glyphs.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MinimapLine {
+    pub blocks: Vec<MinimapBlock>,
+}
+
+impl MinimapLine {
+    /// Summarize the line `text` with the syntax `styles` of that line, whose
+    /// offsets are relative to the start of the line.
+    pub fn new(text: &str, styles: &[LineStyle], tab_width: usize) -> Self {
+        let tab_width = tab_width.max(1);
+        let mut blocks: Vec<MinimapBlock> = Vec::new();
+        let mut styles = styles.iter().peekable();
+        let mut col = 0;
+        for (i, c) in text.char_indices() {
+            if col >= MAX_COLUMNS || c == '\n' || c == '\r' {
+                break;
+            }
+            if c == '\t' {
+                col += tab_width - col % tab_width;
+                continue;
+            }
+            if c.is_whitespace() {
+                col += 1;
+                continue;
+            }
+
+            while styles.peek().map(|s| s.end <= i).unwrap_or(false) {
+                styles.next();
+            }
+            let fg_color = styles
+                .peek()
+                .filter(|s| s.start <= i)
+                .and_then(|s| s.style.fg_color.clone());
+
+            match blocks.last_mut() {
+                Some(block) if block.end == col && block.fg_color == fg_color => {
+                    block.end += 1;
+                }
+                _ => blocks.push(MinimapBlock {
+                    start: col,
+                    end: col + 1,
+                    fg_color,
+                }),
+            }
+            col += 1;
+        }
+        Self { blocks }
+    }
+}
+
+/// The cached line summaries of a document, kept in line with the buffer by
+/// the invalidated lines of each edit.
+#[derive(Default)]
+pub struct MinimapCache {
+    lines: Vec<Option<Arc<MinimapLine>>>,
+}
+
+impl MinimapCache {
+    pub fn get(&self, line: usize) -> Option<Arc<MinimapLine>> {
+        self.lines.get(line).cloned().flatten()
+    }
+
+    pub fn insert(&mut self, line: usize, summary: Arc<MinimapLine>) {
+        if line >= self.lines.len() {
+            self.lines.resize(line + 1, None);
+        }
+        self.lines[line] = Some(summary);
+    }
+
+    /// Drop the summaries of the lines that were changed by an edit, and shift
+    /// the ones after it.
+    pub fn apply_inval_lines(&mut self, inval_lines: &InvalLines) {
+        let start = inval_lines.start_line;
+        if start >= self.lines.len() {
+            return;
+        }
+        let end = (start + inval_lines.inval_count).min(self.lines.len());
+        self.lines.splice(
+            start..end,
+            std::iter::repeat(None).take(inval_lines.new_count),
+        );
+    }
+
+    /// Drop the summaries of `lines`, whose highlighting changed.
+    pub fn invalidate_lines(&mut self, lines: &[Range<usize>]) {
+        for range in lines {
+            for summary in self.lines.iter_mut().take(range.end).skip(range.start) {
+                *summary = None;
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+}
+
+/// Maps between document lines and the y position in the minimap.
+#[derive(Clone, Copy)]
+struct MinimapLayout {
+    line_height: f64,
+    total_lines: usize,
+}
+
+impl MinimapLayout {
+    fn new(total_lines: usize, height: f64) -> Self {
+        let total_lines = total_lines.max(1);
+        let line_height = LINE_HEIGHT.min(height / total_lines as f64);
+        Self {
+            line_height,
+            total_lines,
+        }
+    }
+
+    fn line_of_y(&self, y: f64) -> usize {
+        if self.line_height <= 0.0 {
+            return 0;
+        }
+        ((y / self.line_height).max(0.0) as usize).min(self.total_lines - 1)
+    }
+
+    /// The lines to paint together with their y position. When the lines are
+    /// thinner than a pixel only one line per pixel row is sampled, so the
+    /// work is bounded by the height of the minimap rather than the document.
+    fn sampled_lines(&self, height: f64) -> Vec<(usize, f64)> {
+        if self.line_height >= 1.0 {
+            (0..self.total_lines)
+                .map(|line| (line, line as f64 * self.line_height))
+                .collect()
+        } else {
+            let mut lines = Vec::new();
+            let mut last_line = None;
+            for row in 0..height.ceil() as usize {
+                let line = self.line_of_y(row as f64);
+                if last_line != Some(line) {
+                    lines.push((line, row as f64));
+                    last_line = Some(line);
+                }
+            }
+            lines
+        }
+    }
+}
+
+pub struct MinimapView {
+    id: Id,
+    editor: RwSignal<EditorData>,
+    size: RwSignal<Size>,
+}
+
+pub fn minimap_view(editor: RwSignal<EditorData>) -> impl View {
+    let cx = ViewContext::get_current();
+    let id = cx.new_id();
+    let size = create_rw_signal(Size::ZERO);
+    let dragging = create_rw_signal(false);
+
+    let (viewport, config) =
+        editor.with_untracked(|e| (e.viewport, e.common.config));
+
+    create_effect(move |_| {
+        let doc = editor.with(|e| e.view.doc);
+        doc.with(|doc| (doc.rev(), doc.cache_rev()));
+        viewport.track();
+        config.track();
+        id.request_paint();
+    });
+
+    MinimapView { id, editor, size }
+        .on_event(EventListener::PointerDown, move |event| {
+            if let Event::PointerDown(pointer_event) = event {
+                id.request_active();
+                dragging.set(true);
+                scroll_to_minimap_y(editor, size, pointer_event.pos.y);
+            }
+            true
+        })
+        .on_event(EventListener::PointerMove, move |event| {
+            if let Event::PointerMove(pointer_event) = event {
+                if dragging.get_untracked() {
+                    scroll_to_minimap_y(editor, size, pointer_event.pos.y);
+                }
+            }
+            true
+        })
+        .on_event(EventListener::PointerUp, move |_| {
+            dragging.set(false);
+            true
+        })
+}
+
+/// Scroll the editor so that the line under `y` in the minimap is in the middle
+/// of the viewport.
+fn scroll_to_minimap_y(editor: RwSignal<EditorData>, size: RwSignal<Size>, y: f64) {
+    let (doc, viewport, scroll_to, config) = editor
+        .with_untracked(|e| (e.view.doc, e.viewport, e.scroll_to, e.common.config));
+    let total_lines = doc.with_untracked(|doc| doc.buffer().num_lines());
+    let layout = MinimapLayout::new(total_lines, size.get_untracked().height);
+    let line = layout.line_of_y(y);
+
+    let viewport = viewport.get_untracked();
+    let line_height = config.get_untracked().editor.line_height() as f64;
+    let y = (line as f64 * line_height - viewport.height() / 2.0).max(0.0);
+    scroll_to.set(Some(Vec2::new(viewport.x0, y)));
+}
+
+impl MinimapView {
+    fn paint_lines(
+        &self,
+        cx: &mut PaintCx,
+        layout: &MinimapLayout,
+        size: Size,
+        config: &LapceConfig,
+    ) {
+        let doc = self.editor.with_untracked(|e| e.view.doc);
+        let column_width = size.width / MAX_COLUMNS as f64;
+        let block_height = layout.line_height.max(1.0);
+        let default_color = config
+            .get_color(LapceColor::EDITOR_FOREGROUND)
+            .with_alpha_factor(0.5);
+
+        for (line, y) in layout.sampled_lines(size.height) {
+            let summary = doc.with_untracked(|doc| doc.minimap_line(line));
+            for block in &summary.blocks {
+                let color = block
+                    .fg_color
+                    .as_ref()
+                    .and_then(|fg| config.get_style_color(fg))
+                    .map(|c| c.with_alpha_factor(0.8))
+                    .unwrap_or(default_color);
+                let rect = Rect::new(
+                    block.start as f64 * column_width,
+                    y,
+                    block.end as f64 * column_width,
+                    y + block_height,
+                );
+                cx.fill(&rect, color, 0.0);
+            }
+        }
+    }
+
+    fn paint_viewport(
+        &self,
+        cx: &mut PaintCx,
+        layout: &MinimapLayout,
+        size: Size,
+        config: &LapceConfig,
+    ) {
+        let viewport = self.editor.with_untracked(|e| e.viewport.get_untracked());
+        let line_height = config.editor.line_height() as f64;
+        let y0 = viewport.y0 / line_height * layout.line_height;
+        let height = (viewport.height() / line_height * layout.line_height)
+            .max(MIN_VIEWPORT_HEIGHT);
+        cx.fill(
+            &Rect::new(0.0, y0, size.width, y0 + height),
+            config
+                .get_color(LapceColor::EDITOR_FOREGROUND)
+                .with_alpha_factor(0.1),
+            0.0,
+        );
+    }
+}
+
+impl View for MinimapView {
+    fn id(&self) -> Id {
+        self.id
+    }
+
+    fn child(&self, _id: Id) -> Option<&dyn View> {
+        None
+    }
+
+    fn child_mut(&mut self, _id: Id) -> Option<&mut dyn View> {
+        None
+    }
+
+    fn children(&self) -> Vec<&dyn View> {
+        Vec::new()
+    }
+
+    fn children_mut(&mut self) -> Vec<&mut dyn View> {
+        Vec::new()
+    }
+
+    fn update(
+        &mut self,
+        _cx: &mut floem::context::UpdateCx,
+        _state: Box<dyn std::any::Any>,
+    ) -> ChangeFlags {
+        ChangeFlags::default()
+    }
+
+    fn layout(
+        &mut self,
+        cx: &mut floem::context::LayoutCx,
+    ) -> floem::taffy::prelude::Node {
+        cx.layout_node(self.id, false, |_| Vec::new())
+    }
+
+    fn compute_layout(&mut self, cx: &mut floem::context::LayoutCx) -> Option<Rect> {
+        if let Some(layout) = cx.get_layout(self.id) {
+            let size =
+                Size::new(layout.size.width as f64, layout.size.height as f64);
+            if self.size.get_untracked() != size {
+                self.size.set(size);
+            }
+        }
+        None
+    }
+
+    fn event(
+        &mut self,
+        _cx: &mut floem::context::EventCx,
+        _id_path: Option<&[Id]>,
+        _event: Event,
+    ) -> bool {
+        false
+    }
+
+    fn paint(&mut self, cx: &mut PaintCx) {
+        let (doc, config) = self
+            .editor
+            .with_untracked(|e| (e.view.doc, e.common.config));
+        let config = config.get_untracked();
+        let size = self.size.get_untracked();
+        let total_lines = doc.with_untracked(|doc| doc.buffer().num_lines());
+        let layout = MinimapLayout::new(total_lines, size.height);
+
+        cx.save();
+        cx.clip(&size.to_rect());
+        self.paint_lines(cx, &layout, size, &config);
+        self.paint_viewport(cx, &layout, size, &config);
+        cx.restore();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lapce_rpc::style::{LineStyle, Style};
+
+    use super::*;
+
+    fn style(start: usize, end: usize, fg: &str) -> LineStyle {
+        LineStyle {
+            start,
+            end,
+            style: Style {
+                fg_color: Some(fg.to_string()),
+            },
+        }
+    }
+
+    #[test]
+    fn test_line_summary() {
+        let line = MinimapLine::new("\tlet a = 1;\n", &[style(1, 4, "keyword")], 4);
+        assert_eq!(
+            line.blocks,
+            vec![
+                MinimapBlock {
+                    start: 4,
+                    end: 7,
+                    fg_color: Some("keyword".to_string()),
+                },
+                MinimapBlock {
+                    start: 8,
+                    end: 9,
+                    fg_color: None,
+                },
+                MinimapBlock {
+                    start: 10,
+                    end: 11,
+                    fg_color: None,
+                },
+                MinimapBlock {
+                    start: 12,
+                    end: 14,
+                    fg_color: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_inval_lines() {
+        let mut cache = MinimapCache::default();
+        for line in 0..4 {
+            cache.insert(line, Arc::new(MinimapLine::default()));
+        }
+        cache.apply_inval_lines(&InvalLines {
+            start_line: 1,
+            inval_count: 1,
+            new_count: 3,
+        });
+        assert!(cache.get(0).is_some());
+        assert!(cache.get(1).is_none());
+        assert!(cache.get(3).is_none());
+        assert!(cache.get(4).is_some());
+        assert!(cache.get(5).is_some());
+        assert!(cache.get(6).is_none());
+    }
+}
@@ -1,405 +1,79 @@
-//! Movement logic for the editor.
+//! Movement logic for the editor, which is [`lapce_core::motion`] with the
+//! lines laid out the way the editor shows them.
 
 use std::collections::HashSet;
 
+use floem::peniko::kurbo::Point;
 use lapce_core::{
-    buffer::rope_text::RopeText,
+    buffer::rope_text::{RopeText, RopeTextVal},
     command::MultiSelectionCommand,
-    cursor::{ColPosition, Cursor, CursorMode},
-    editor::Editor,
+    cursor::{Cursor, CursorMode},
     mode::{Mode, MotionMode},
-    movement::{LinePosition, Movement},
+    motion::{self, MotionEdit, TextLayout},
+    movement::Movement,
     register::Register,
     selection::{SelRegion, Selection},
-    soft_tab::{snap_to_soft_tab, SnapDirection},
 };
 
-use crate::doc::Document;
+use crate::{doc::Document, find::Find};
 
 use super::view_data::EditorViewData;
 
-/// Move a selection region by a given movement.  
-/// Much of the time, this will just be a matter of moving the cursor, but
-/// some movements may depend on the current selection.
-fn move_region(
-    view: &EditorViewData,
-    region: &SelRegion,
-    count: usize,
-    modify: bool,
-    movement: &Movement,
-    mode: Mode,
-) -> SelRegion {
-    let (count, region) = if count >= 1 && !modify && !region.is_caret() {
-        // If we're not a caret, and we are moving left/up or right/down, we want to move
-        // the cursor to the left or right side of the selection.
-        // Ex: `|abc|` -> left/up arrow key -> `|abc`
-        // Ex: `|abc|` -> right/down arrow key -> `abc|`
-        // and it doesn't matter which direction the selection is going, so we use min/max
-        match movement {
-            Movement::Left | Movement::Up => {
-                let leftmost = region.min();
-                (count - 1, SelRegion::new(leftmost, leftmost, region.horiz))
-            }
-            Movement::Right | Movement::Down => {
-                let rightmost = region.max();
-                (
-                    count - 1,
-                    SelRegion::new(rightmost, rightmost, region.horiz),
-                )
-            }
-            _ => (count, *region),
-        }
-    } else {
-        (count, *region)
-    };
-
-    let (end, horiz) = move_offset(
-        view,
-        region.end,
-        region.horiz.as_ref(),
-        count,
-        movement,
-        mode,
-    );
-    let start = match modify {
-        true => region.start,
-        false => end,
-    };
-    SelRegion::new(start, end, horiz)
-}
-
-pub fn move_selection(
-    view: &EditorViewData,
-    selection: &Selection,
-    count: usize,
-    modify: bool,
-    movement: &Movement,
-    mode: Mode,
-) -> Selection {
-    let mut new_selection = Selection::new();
-    for region in selection.regions() {
-        new_selection
-            .add_region(move_region(view, region, count, modify, movement, mode));
+impl TextLayout for EditorViewData {
+    fn rope_text(&self) -> RopeTextVal {
+        EditorViewData::rope_text(self)
     }
-    new_selection
-}
-
-pub fn move_offset(
-    view: &EditorViewData,
-    offset: usize,
-    horiz: Option<&ColPosition>,
-    count: usize,
-    movement: &Movement,
-    mode: Mode,
-) -> (usize, Option<ColPosition>) {
-    let config = view.config.get_untracked();
-
-    match movement {
-        Movement::Left => {
-            let new_offset = move_left(
-                view.rope_text(),
-                offset,
-                mode,
-                count,
-                config.editor.atomic_soft_tab_width(),
-            );
-
-            (new_offset, None)
-        }
-        Movement::Right => {
-            let new_offset = move_right(
-                view.rope_text(),
-                offset,
-                mode,
-                count,
-                config.editor.atomic_soft_tab_width(),
-            );
-
-            (new_offset, None)
-        }
-        Movement::Up => {
-            let font_size = config.editor.font_size();
-            let (new_offset, horiz) =
-                move_up(view, font_size, offset, horiz.cloned(), mode, count);
-
-            (new_offset, Some(horiz))
-        }
-        Movement::Down => {
-            let font_size = config.editor.font_size();
-            let (new_offset, horiz) =
-                move_down(view, font_size, offset, horiz.cloned(), mode, count);
-
-            (new_offset, Some(horiz))
-        }
-        Movement::DocumentStart => (0, Some(ColPosition::Start)),
-        Movement::DocumentEnd => {
-            let (new_offset, horiz) = document_end(view.rope_text(), mode);
-
-            (new_offset, Some(horiz))
-        }
-        Movement::FirstNonBlank => {
-            let (new_offset, horiz) = first_non_blank(view.rope_text(), offset);
-
-            (new_offset, Some(horiz))
-        }
-        Movement::StartOfLine => {
-            let (new_offset, horiz) = start_of_line(view.rope_text(), offset);
-
-            (new_offset, Some(horiz))
-        }
-        Movement::EndOfLine => {
-            let (new_offset, horiz) = end_of_line(view.rope_text(), offset, mode);
 
-            (new_offset, Some(horiz))
-        }
-        Movement::Line(position) => {
-            let font_size = config.editor.font_size();
-            let (new_offset, horiz) =
-                to_line(view, font_size, offset, horiz.cloned(), mode, position);
-
-            (new_offset, Some(horiz))
-        }
-        Movement::Offset(offset) => {
-            let new_offset = view.text().prev_grapheme_offset(*offset + 1).unwrap();
-            (new_offset, None)
-        }
-        Movement::WordEndForward => {
-            let new_offset = view.rope_text().move_n_wordends_forward(
-                offset,
-                count,
-                mode == Mode::Insert,
-            );
-            (new_offset, None)
-        }
-        Movement::WordForward => {
-            let new_offset = view.rope_text().move_n_words_forward(offset, count);
-            (new_offset, None)
-        }
-        Movement::WordBackward => {
-            let new_offset =
-                view.rope_text().move_n_words_backward(offset, count, mode);
-            (new_offset, None)
-        }
-        Movement::NextUnmatched(char) => {
-            let new_offset = view.find_unmatched(offset, false, *char);
-
-            (new_offset, None)
-        }
-        Movement::PreviousUnmatched(char) => {
-            let new_offset = view.find_unmatched(offset, true, *char);
-
-            (new_offset, None)
-        }
-        Movement::MatchPairs => {
-            let new_offset = view.find_matching_pair(offset);
-
-            (new_offset, None)
-        }
-        Movement::ParagraphForward => {
-            let new_offset =
-                view.rope_text().move_n_paragraphs_forward(offset, count);
-
-            (new_offset, None)
-        }
-        Movement::ParagraphBackward => {
-            let new_offset =
-                view.rope_text().move_n_paragraphs_backward(offset, count);
-
-            (new_offset, None)
-        }
+    fn soft_tab_width(&self) -> Option<usize> {
+        self.config.get_untracked().editor.atomic_soft_tab_width()
     }
-}
 
-/// Move the offset to the left by `count` amount.  
-/// If `soft_tab_width` is `Some` (and greater than 1) then the offset will snap to the soft tab.  
-fn move_left(
-    rope_text: impl RopeText,
-    offset: usize,
-    mode: Mode,
-    count: usize,
-    soft_tab_width: Option<usize>,
-) -> usize {
-    let mut new_offset = rope_text.move_left(offset, mode, count);
-
-    if let Some(soft_tab_width) = soft_tab_width {
-        if soft_tab_width > 1 {
-            new_offset = snap_to_soft_tab(
-                rope_text.text(),
-                new_offset,
-                SnapDirection::Left,
-                soft_tab_width,
-            );
-        }
+    fn visual_line(&self, line: usize) -> usize {
+        EditorViewData::visual_line(self, line)
     }
 
-    new_offset
-}
-
-/// Move the offset to the right by `count` amount.
-/// If `soft_tab_width` is `Some` (and greater than 1) then the offset will snap to the soft tab.
-fn move_right(
-    rope_text: impl RopeText,
-    offset: usize,
-    mode: Mode,
-    count: usize,
-    soft_tab_width: Option<usize>,
-) -> usize {
-    let mut new_offset = rope_text.move_right(offset, mode, count);
-
-    if let Some(soft_tab_width) = soft_tab_width {
-        if soft_tab_width > 1 {
-            new_offset = snap_to_soft_tab(
-                rope_text.text(),
-                new_offset,
-                SnapDirection::Right,
-                soft_tab_width,
-            );
-        }
+    fn actual_line(&self, visual_line: usize, bottom_affinity: bool) -> usize {
+        EditorViewData::actual_line(self, visual_line, bottom_affinity)
     }
 
-    new_offset
-}
-
-// These could be abstracted away from `EditorViewData` by having some trait that has a 'get Rope' and 'get point information about text' functions. Would be useful for testing.
-/// Move the offset up by `count` amount.
-fn move_up(
-    view: &EditorViewData,
-    font_size: usize,
-    offset: usize,
-    horiz: Option<ColPosition>,
-    mode: Mode,
-    count: usize,
-) -> (usize, ColPosition) {
-    let rope_text = view.rope_text();
-
-    let line = rope_text.line_of_offset(offset);
-
-    if line == 0 {
-        let line = rope_text.line_of_offset(offset);
-        let new_offset = rope_text.offset_of_line(line);
-        let horiz = horiz.unwrap_or_else(|| {
-            ColPosition::Col(view.line_point_of_offset(offset, font_size).x)
-        });
-        return (new_offset, horiz);
+    fn offset_x(&self, offset: usize) -> f64 {
+        let font_size = self.config.get_untracked().editor.font_size();
+        self.line_point_of_offset(offset, font_size).x
     }
 
-    let visual_line = view.visual_line(line).saturating_sub(count);
-    let line = view.actual_line(visual_line, false);
-
-    let horiz = horiz.unwrap_or_else(|| {
-        ColPosition::Col(view.line_point_of_offset(offset, font_size).x)
-    });
-    let col = view.line_horiz_col(line, font_size, &horiz, mode != Mode::Normal);
-    let new_offset = rope_text.offset_of_line_col(line, col);
-
-    (new_offset, horiz)
-}
-
-/// Move the offset down by `count` amount.
-fn move_down(
-    view: &EditorViewData,
-    font_size: usize,
-    offset: usize,
-    horiz: Option<ColPosition>,
-    mode: Mode,
-    count: usize,
-) -> (usize, ColPosition) {
-    let rope_text = view.rope_text();
-
-    let last_line = rope_text.last_line();
-    let line = rope_text.line_of_offset(offset);
-    if line == last_line {
-        let new_offset = rope_text.offset_line_end(offset, mode != Mode::Normal);
-        let horiz = horiz.unwrap_or_else(|| {
-            ColPosition::Col(view.line_point_of_offset(offset, font_size).x)
-        });
-        return (new_offset, horiz);
+    fn col_at_x(&self, line: usize, x: f64) -> usize {
+        let font_size = self.config.get_untracked().editor.font_size();
+        let text_layout = self.get_text_layout(line, font_size);
+        text_layout.text.hit_point(Point::new(x, 0.0)).index
     }
 
-    let visual_line = view.visual_line(line);
-    let line = view.actual_line(visual_line + count, true);
-    let line = line.min(last_line);
-
-    let horiz = horiz.unwrap_or_else(|| {
-        ColPosition::Col(view.line_point_of_offset(offset, font_size).x)
-    });
-    let col = view.line_horiz_col(line, font_size, &horiz, mode != Mode::Normal);
-    let new_offset = rope_text.offset_of_line_col(line, col);
-
-    (new_offset, horiz)
-}
-
-fn document_end(rope_text: impl RopeText, mode: Mode) -> (usize, ColPosition) {
-    let last_offset =
-        rope_text.offset_line_end(rope_text.len(), mode != Mode::Normal);
-
-    (last_offset, ColPosition::End)
-}
-
-fn first_non_blank(rope_text: impl RopeText, offset: usize) -> (usize, ColPosition) {
-    let line = rope_text.line_of_offset(offset);
-    let non_blank_offset = rope_text.first_non_blank_character_on_line(line);
-    let start_line_offset = rope_text.offset_of_line(line);
-    if offset > non_blank_offset {
-        // Jump to the first non-whitespace character if we're strictly after it
-        (non_blank_offset, ColPosition::FirstNonBlank)
-    } else {
-        // If we're at the start of the line, also jump to the first not blank
-        if start_line_offset == offset {
-            (non_blank_offset, ColPosition::FirstNonBlank)
-        } else {
-            // Otherwise, jump to the start of the line
-            (start_line_offset, ColPosition::Start)
-        }
+    fn find_unmatched(&self, offset: usize, previous: bool, ch: char) -> usize {
+        EditorViewData::find_unmatched(self, offset, previous, ch)
     }
-}
-
-fn start_of_line(rope_text: impl RopeText, offset: usize) -> (usize, ColPosition) {
-    let line = rope_text.line_of_offset(offset);
-    let new_offset = rope_text.offset_of_line(line);
-
-    (new_offset, ColPosition::Start)
-}
-
-fn end_of_line(
-    rope_text: impl RopeText,
-    offset: usize,
-    mode: Mode,
-) -> (usize, ColPosition) {
-    let new_offset = rope_text.offset_line_end(offset, mode != Mode::Normal);
 
-    (new_offset, ColPosition::End)
+    fn find_matching_pair(&self, offset: usize) -> usize {
+        EditorViewData::find_matching_pair(self, offset)
+    }
 }
 
-fn to_line(
-    view: &EditorViewData,
-    font_size: usize,
-    offset: usize,
-    horiz: Option<ColPosition>,
-    mode: Mode,
-    position: &LinePosition,
-) -> (usize, ColPosition) {
-    let rope_text = view.rope_text();
-
-    let line = match position {
-        LinePosition::Line(line) => (line - 1).min(rope_text.last_line()),
-        LinePosition::First => 0,
-        LinePosition::Last => rope_text.last_line(),
-    };
-    let horiz = horiz.unwrap_or_else(|| {
-        ColPosition::Col(view.line_point_of_offset(offset, font_size).x)
-    });
-    let col = view.line_horiz_col(line, font_size, &horiz, mode != Mode::Normal);
-    let new_offset = rope_text.offset_of_line_col(line, col);
-
-    (new_offset, horiz)
+/// Apply the operator `edit` returned by a movement to the document.
+fn apply_motion_edit(
+    doc: &mut Document,
+    cursor: &mut Cursor,
+    edit: MotionEdit,
+    register: &mut Register,
+) {
+    doc.execute_motion_mode(
+        cursor,
+        edit.motion_mode,
+        edit.start,
+        edit.end,
+        edit.is_vertical,
+        register,
+    );
 }
 
-// TODO: passing a view with the document is kinda ehhh, because this would be used when you call
-// .update on the `RwSignal<Document>`, which presumably delinks it from the RwSignal the view has.
-// A solution might be to change the caller to only pass in the editor view and have this do the update, but only when it is needed.
-
-/// Move the current cursor.  
+/// Move the current cursor.
 /// This will signal-update the document for some motion modes.
 pub fn move_cursor(
     view: &EditorViewData,
@@ -409,93 +83,82 @@ pub fn move_cursor(
     modify: bool,
     register: &mut Register,
 ) {
-    match cursor.mode {
-        CursorMode::Normal(offset) => {
-            let (new_offset, horiz) = move_offset(
-                view,
-                offset,
-                cursor.horiz.as_ref(),
-                count,
-                movement,
-                Mode::Normal,
-            );
-            if let Some(motion_mode) = cursor.motion_mode.clone() {
-                let (moved_new_offset, _) = move_offset(
-                    view,
-                    new_offset,
-                    None,
-                    1,
-                    &Movement::Right,
-                    Mode::Insert,
-                );
-                let (start, end) = match movement {
-                    Movement::EndOfLine | Movement::WordEndForward => {
-                        (offset, moved_new_offset)
-                    }
-                    Movement::MatchPairs => {
-                        if new_offset > offset {
-                            (offset, moved_new_offset)
-                        } else {
-                            (moved_new_offset, new_offset)
-                        }
-                    }
-                    _ => (offset, new_offset),
-                };
-                view.doc.update(|doc| {
-                    let deltas = Editor::execute_motion_mode(
-                        cursor,
-                        doc.buffer_mut(),
-                        motion_mode,
-                        start,
-                        end,
-                        movement.is_vertical(),
-                        register,
-                    );
-                    doc.apply_deltas(&deltas);
-                });
-                cursor.motion_mode = None;
-            } else {
-                cursor.mode = CursorMode::Normal(new_offset);
-                cursor.horiz = horiz;
-            }
-        }
-        CursorMode::Visual { start, end, mode } => {
-            let (new_offset, horiz) = move_offset(
-                view,
-                end,
-                cursor.horiz.as_ref(),
-                count,
-                movement,
-                Mode::Visual,
-            );
-            cursor.mode = CursorMode::Visual {
-                start,
-                end: new_offset,
-                mode,
-            };
-            cursor.horiz = horiz;
+    if let Some(edit) = motion::move_cursor(view, cursor, movement, count, modify) {
+        view.doc
+            .update(|doc| apply_motion_edit(doc, cursor, edit, register));
+        cursor.clamp(&view.rope_text());
+    }
+}
+
+/// The most cursors selecting all the occurrences makes, so that doing it for
+/// a short word in a huge file doesn't bring the editor to a halt
+pub const MAX_OCCURRENCE_CURSORS: usize = 10_000;
+
+/// Set the buffer search to find the occurrences of `start..end`, matching
+/// their case if either the search or `multicursor_case_sensitive` says so.
+/// Returns whether the occurrences have to be whole words besides, which they
+/// do when `start..end` is a whole word itself and `multicursor_whole_words`
+/// is set, while the whole words toggle of the search applies anyway.
+fn find_occurrences(
+    view: &EditorViewData,
+    find: &Find,
+    start: usize,
+    end: usize,
+) -> bool {
+    let rope_text = view.rope_text();
+    let config = view.config.get_untracked();
+    find.set_case_sensitive(
+        config.editor.multicursor_case_sensitive || find.case_sensitive(false),
+    );
+    find.set_find(&rope_text.slice_to_cow(start..end));
+    config.editor.multicursor_whole_words
+        && start < end
+        && Find::is_matching_whole_words(rope_text.text(), start, end)
+}
+
+/// The next occurrence of the text of `region` after it, wrapping around,
+/// that isn't selected already.
+fn next_occurrence(
+    view: &EditorViewData,
+    selection: &Selection,
+    region: SelRegion,
+) -> Option<(usize, usize)> {
+    let rope_text = view.rope_text();
+    let find = view.find();
+    let whole_words = find_occurrences(view, &find, region.min(), region.max());
+
+    let mut offset = region.max();
+    let mut seen = HashSet::new();
+    while let Some((start, end)) = find.next(rope_text.text(), offset, false, true) {
+        let whole = !whole_words
+            || Find::is_matching_whole_words(rope_text.text(), start, end);
+        if whole
+            && !selection
+                .regions()
+                .iter()
+                .any(|r| r.min() == start && r.max() == end)
+        {
+            return Some((start, end));
         }
-        CursorMode::Insert(ref selection) => {
-            let selection = move_selection(
-                view,
-                selection,
-                count,
-                modify,
-                movement,
-                Mode::Insert,
-            );
-            cursor.set_insert(selection);
+        if seen.contains(&end) {
+            break;
         }
+        offset = end;
+        seen.insert(offset);
     }
+    None
 }
 
+/// Returns whether selecting all the occurrences stopped at
+/// [`MAX_OCCURRENCE_CURSORS`] of them.
 pub fn do_multi_selection(
     view: &EditorViewData,
     cursor: &mut Cursor,
     cmd: &MultiSelectionCommand,
-) {
+) -> bool {
     use MultiSelectionCommand::*;
     let rope_text = view.rope_text();
+    let mut truncated = false;
 
     match cmd {
         SelectUndo => {
@@ -509,7 +172,7 @@ pub fn do_multi_selection(
         InsertCursorAbove => {
             if let CursorMode::Insert(mut selection) = cursor.mode.clone() {
                 let offset = selection.first().map(|s| s.end).unwrap_or(0);
-                let (new_offset, _) = move_offset(
+                let (new_offset, _) = motion::move_offset(
                     view,
                     offset,
                     cursor.horiz.as_ref(),
@@ -527,7 +190,7 @@ pub fn do_multi_selection(
         InsertCursorBelow => {
             if let CursorMode::Insert(mut selection) = cursor.mode.clone() {
                 let offset = selection.last().map(|s| s.end).unwrap_or(0);
-                let (new_offset, _) = move_offset(
+                let (new_offset, _) = motion::move_offset(
                     view,
                     offset,
                     cursor.horiz.as_ref(),
@@ -578,29 +241,32 @@ pub fn do_multi_selection(
         SelectAllCurrent => {
             if let CursorMode::Insert(mut selection) = cursor.mode.clone() {
                 if !selection.is_empty() {
-                    let find = view.find();
-                    let config = view.config.get_untracked();
-
                     let first = selection.first().unwrap();
                     let (start, end) = if first.is_caret() {
                         rope_text.select_word(first.start)
                     } else {
                         (first.min(), first.max())
                     };
-                    let search_str = rope_text.slice_to_cow(start..end);
-                    let case_sensitive = find.case_sensitive(false);
-                    let multicursor_case_sensitive =
-                        config.editor.multicursor_case_sensitive;
-                    let case_sensitive =
-                        multicursor_case_sensitive || case_sensitive;
-                    // let search_whole_word = config.editor.multicursor_whole_words;
-                    find.set_case_sensitive(case_sensitive);
-                    find.set_find(&search_str);
+                    let find = view.find();
+                    let whole_words = find_occurrences(view, &find, start, end);
                     let mut offset = 0;
                     while let Some((start, end)) =
                         find.next(rope_text.text(), offset, false, false)
                     {
                         offset = end;
+                        if whole_words
+                            && !Find::is_matching_whole_words(
+                                rope_text.text(),
+                                start,
+                                end,
+                            )
+                        {
+                            continue;
+                        }
+                        if selection.len() >= MAX_OCCURRENCE_CURSORS {
+                            truncated = true;
+                            break;
+                        }
                         selection.add_region(SelRegion::new(start, end, None));
                     }
                 }
@@ -620,38 +286,11 @@ pub fn do_multi_selection(
                         }
                     }
                     if !had_caret {
-                        let find = view.find();
-                        let config = view.config.get_untracked();
-
-                        let r = selection.last_inserted().unwrap();
-                        let search_str = rope_text.slice_to_cow(r.min()..r.max());
-                        let case_sensitive = find.case_sensitive(false);
-                        let case_sensitive =
-                            config.editor.multicursor_case_sensitive
-                                || case_sensitive;
-                        // let search_whole_word =
-                        // config.editor.multicursor_whole_words;
-                        find.set_case_sensitive(case_sensitive);
-                        find.set_find(&search_str);
-                        let mut offset = r.max();
-                        let mut seen = HashSet::new();
-                        while let Some((start, end)) =
-                            find.next(rope_text.text(), offset, false, true)
+                        let r = *selection.last_inserted().unwrap();
+                        if let Some((start, end)) =
+                            next_occurrence(view, &selection, r)
                         {
-                            if !selection
-                                .regions()
-                                .iter()
-                                .any(|r| r.min() == start && r.max() == end)
-                            {
-                                selection
-                                    .add_region(SelRegion::new(start, end, None));
-                                break;
-                            }
-                            if seen.contains(&end) {
-                                break;
-                            }
-                            offset = end;
-                            seen.insert(offset);
+                            selection.add_region(SelRegion::new(start, end, None));
                         }
                     }
                 }
@@ -661,38 +300,18 @@ pub fn do_multi_selection(
         SelectSkipCurrent => {
             if let CursorMode::Insert(mut selection) = cursor.mode.clone() {
                 if !selection.is_empty() {
-                    let r = selection.last_inserted().unwrap();
+                    let r = *selection.last_inserted().unwrap();
                     if r.is_caret() {
                         let (start, end) = rope_text.select_word(r.start);
                         selection.replace_last_inserted_region(SelRegion::new(
                             start, end, None,
                         ));
-                    } else {
-                        let find = view.find();
-
-                        let search_str = rope_text.slice_to_cow(r.min()..r.max());
-                        find.set_find(&search_str);
-                        let mut offset = r.max();
-                        let mut seen = HashSet::new();
-                        while let Some((start, end)) =
-                            find.next(rope_text.text(), offset, false, true)
-                        {
-                            if !selection
-                                .regions()
-                                .iter()
-                                .any(|r| r.min() == start && r.max() == end)
-                            {
-                                selection.replace_last_inserted_region(
-                                    SelRegion::new(start, end, None),
-                                );
-                                break;
-                            }
-                            if seen.contains(&end) {
-                                break;
-                            }
-                            offset = end;
-                            seen.insert(offset);
-                        }
+                    } else if let Some((start, end)) =
+                        next_occurrence(view, &selection, r)
+                    {
+                        selection.replace_last_inserted_region(SelRegion::new(
+                            start, end, None,
+                        ));
                     }
                 }
                 cursor.set_insert(selection);
@@ -703,6 +322,7 @@ pub fn do_multi_selection(
             cursor.set_insert(new_selection);
         }
     }
+    truncated
 }
 
 pub fn do_motion_mode(
@@ -711,26 +331,7 @@ pub fn do_motion_mode(
     motion_mode: MotionMode,
     register: &mut Register,
 ) {
-    if let Some(cached_motion_mode) = cursor.motion_mode.take() {
-        // If it's the same MotionMode discriminant, continue, count is cached in the old motion_mode.
-        if core::mem::discriminant(&cached_motion_mode)
-            == core::mem::discriminant(&motion_mode)
-        {
-            let offset = cursor.offset();
-            let deltas = Editor::execute_motion_mode(
-                cursor,
-                doc.buffer_mut(),
-                cached_motion_mode,
-                offset,
-                offset,
-                true,
-                register,
-            );
-            doc.apply_deltas(&deltas);
-        }
-    } else {
-        cursor.motion_mode = Some(motion_mode);
+    if let Some(edit) = motion::do_motion_mode(cursor, motion_mode) {
+        apply_motion_edit(doc, cursor, edit, register);
     }
 }
-
-// TODO: Write tests for the various functions.
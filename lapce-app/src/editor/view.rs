@@ -1,16 +1,22 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    sync::{atomic, Arc},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use floem::{
-    action::{set_ime_allowed, set_ime_cursor_area},
+    action::{exec_after, set_ime_allowed, set_ime_cursor_area, TimerToken},
     context::PaintCx,
-    cosmic_text::{Attrs, AttrsList, FamilyOwned, TextLayout},
+    cosmic_text::{Attrs, AttrsList, TextLayout},
     event::{Event, EventListener},
+    ext_event::create_ext_action,
     id::Id,
     keyboard::ModifiersState,
     peniko::{
-        kurbo::{BezPath, Line, Point, Rect, Size},
+        kurbo::{BezPath, Line, Point, Rect, Size, Vec2},
         Color,
     },
+    pointer::PointerWheelEvent,
     reactive::{
         create_effect, create_memo, create_rw_signal, Memo, ReadSignal, RwSignal,
     },
@@ -24,22 +30,31 @@
     buffer::{diff::DiffLines, rope_text::RopeText},
     cursor::{ColPosition, CursorMode},
     mode::{Mode, VisualMode},
+    register::Clipboard,
     selection::Selection,
 };
+use lapce_rpc::proxy::ProxyResponse;
 use lapce_xi_rope::find::CaseMatching;
+use lsp_types::{DocumentSymbol, DocumentSymbolResponse};
 
 use super::{
+    blame::inline_blame_text,
     gutter::editor_gutter_view,
-    view_data::{EditorViewData, LineExtraStyle},
+    minimap::minimap_view,
+    view_data::{EditorViewData, LineExtraStyle, RenderWhitespace},
     EditorData,
 };
 use crate::{
     app::clickable_icon,
-    command::InternalCommand,
+    command::{CommandKind, InternalCommand, LapceCommand, LapceWorkbenchCommand},
     config::{color::LapceColor, icon::LapceIcons, LapceConfig},
-    doc::{DocContent, Document},
+    doc::{DocContent, Document, PrimarySelection},
+    find::{Find, FindProgress, FIND_MARGIN_LINES, MAX_FIND_COUNT},
+    jump::Jump,
     keypress::KeyPressFocus,
     main_split::MainSplitData,
+    palette::kind::PaletteKind,
+    perf::{self, Phase},
     text_input::text_input,
     window_tab::Focus,
     workspace::LapceWorkspace,
@@ -49,6 +64,10 @@ pub enum DiffSectionKind {
     NoCode,
     Added,
     Removed,
+    /// A line that only differs in whitespace, which the diff ignored
+    Whitespace,
+    /// A line that only differs in its line ending
+    LineEnding,
 }
 
 pub struct DiffSection {
@@ -104,11 +123,24 @@ pub fn editor_view(
     let hide_cursor = editor.with_untracked(|editor| editor.common.hide_cursor);
     create_effect(move |_| {
         hide_cursor.track();
-        let occurrences = editor.with(|editor| editor.view.find_result.occurrences);
-        occurrences.track();
+        let find_rev = editor.with(|editor| editor.common.find.rev);
+        find_rev.track();
+        let options = editor.with(|editor| editor.view.options);
+        options.track();
         id.request_paint();
     });
 
+    // The text layouts are rebuilt with the new colors and fonts once the
+    // config has changed, e.g. when switching color theme.
+    let config = editor.with_untracked(|editor| editor.common.config);
+    create_effect(move |last_config_id| {
+        let config_id = config.with(|config| config.id);
+        if last_config_id.is_some() && last_config_id != Some(config_id) {
+            id.request_layout();
+        }
+        config_id
+    });
+
     create_effect(move |last_rev| {
         let doc = editor.with(|editor| editor.view.doc);
         let rev = doc.with(|doc| doc.rev());
@@ -119,6 +151,62 @@ pub fn editor_view(
         rev
     });
 
+    // Background parses highlight the lines in view first
+    create_effect(move |_| {
+        let (doc, editor_viewport, config) = editor
+            .with(|editor| (editor.view.doc, editor.viewport, editor.common.config));
+        let line_height = config.with(|config| config.editor.line_height()) as f64;
+        let rect = editor_viewport.get();
+        let min_line = (rect.y0 / line_height).floor() as usize;
+        let max_line = (rect.y1 / line_height).ceil() as usize;
+        doc.with_untracked(|doc| doc.set_visible_lines(min_line..max_line + 1));
+    });
+
+    // The text selected in the active editor becomes the primary selection,
+    // for a middle click to paste. It's only taken again once the selection
+    // changes, and an empty one leaves the primary selection as it was.
+    create_effect(move |last_selection: Option<Option<CursorMode>>| {
+        let (cursor, doc, config) = editor
+            .with(|editor| (editor.cursor, editor.view.doc, editor.common.config));
+        if !config.with(|config| config.editor.primary_selection) {
+            return None;
+        }
+        let mode = cursor.with(|cursor| cursor.mode.clone());
+        let is_caret = match &mode {
+            CursorMode::Normal(_) => true,
+            CursorMode::Visual { .. } => false,
+            CursorMode::Insert(selection) => selection.is_caret(),
+        };
+        if is_caret {
+            return None;
+        }
+        if last_selection.flatten().as_ref() != Some(&mode)
+            && is_active.get_untracked()
+        {
+            let text = doc.with_untracked(|doc| {
+                cursor.with_untracked(|cursor| cursor.yank(doc.buffer()).content)
+            });
+            if !text.is_empty() {
+                PrimarySelection.put_string(text);
+            }
+        }
+        Some(mode)
+    });
+
+    // The blame is only asked for while it's shown
+    create_effect(move |_| {
+        let (doc, config) =
+            editor.with(|editor| (editor.view.doc, editor.common.config));
+        if !config.with(|config| config.editor.inline_blame) || !is_active.get() {
+            return;
+        }
+        let blame = doc.with(|doc| doc.blame);
+        if blame.with(|blame| blame.is_none()) {
+            doc.with_untracked(|doc| doc.retrieve_blame());
+        }
+        id.request_paint();
+    });
+
     create_effect(move |last_rev| {
         let (doc, sticky_header_height_signal, config) = editor.with(|editor| {
             (
@@ -157,6 +245,25 @@ pub fn editor_view(
         rev
     });
 
+    // The labels of a jump are for what was in view, so it's left once the
+    // editor scrolls or loses focus
+    let (jump, editor_viewport) =
+        editor.with_untracked(|editor| (editor.jump, editor.viewport));
+    create_effect(move |last| {
+        let current = (editor_viewport.get(), is_active.get());
+        if last.is_some()
+            && last != Some(current)
+            && jump.with_untracked(|jump| jump.is_some())
+        {
+            jump.set(None);
+        }
+        current
+    });
+    create_effect(move |_| {
+        jump.track();
+        id.request_paint();
+    });
+
     let (editor_window_origin, cursor, find_focus, ime_allowed) = editor
         .with_untracked(|editor| {
             (
@@ -166,6 +273,22 @@ pub fn editor_view(
                 editor.common.ime_allowed,
             )
         });
+    // The code actions at the cursor are asked for once it settles, and again
+    // when an edit or new diagnostics cleared them
+    create_effect(move |_| {
+        if !is_active.get() {
+            return;
+        }
+        let doc = editor.with(|editor| editor.view.doc);
+        let offset = cursor.with(|c| c.offset());
+        let requested = doc.with(|doc| {
+            !doc.content.is_file() || doc.code_actions.contains_key(&offset)
+        });
+        if !requested {
+            editor.with_untracked(|editor| editor.schedule_code_actions());
+        }
+    });
+
     create_effect(move |_| {
         let active = is_active.get();
         if active && !find_focus.get() {
@@ -294,6 +417,44 @@ fn paint_diff_sections(
                         0.0,
                     );
                 }
+                DiffSectionKind::Whitespace => {
+                    cx.fill(
+                        &Rect::ZERO
+                            .with_size(Size::new(
+                                viewport.width(),
+                                config.editor.line_height() as f64,
+                            ))
+                            .with_origin(Point::new(
+                                viewport.x0,
+                                (section.start_line * config.editor.line_height())
+                                    as f64,
+                            )),
+                        config
+                            .get_color(LapceColor::SOURCE_CONTROL_MODIFIED)
+                            .with_alpha_factor(0.08),
+                        0.0,
+                    );
+                }
+                DiffSectionKind::LineEnding => {
+                    // Only a thin bar at the start of the line, as the text of
+                    // the line is the same on both sides
+                    cx.fill(
+                        &Rect::ZERO
+                            .with_size(Size::new(
+                                3.0,
+                                config.editor.line_height() as f64,
+                            ))
+                            .with_origin(Point::new(
+                                viewport.x0,
+                                (section.start_line * config.editor.line_height())
+                                    as f64,
+                            )),
+                        config
+                            .get_color(LapceColor::SOURCE_CONTROL_MODIFIED)
+                            .with_alpha_factor(0.5),
+                        0.0,
+                    );
+                }
             }
         }
     }
@@ -540,10 +701,12 @@ fn paint_text(
         let config = config.get_untracked();
         let line_height = config.editor.line_height() as f64;
         let font_size = config.editor.font_size();
+        let render_whitespace = view
+            .options
+            .with_untracked(|options| options.render_whitespace(&config));
 
         let indent_unit = view.indent_unit();
-        let family: Vec<FamilyOwned> =
-            FamilyOwned::parse_list(&config.editor.font_family).collect();
+        let family = config.editor.font_family();
         let attrs = Attrs::new()
             .family(&family)
             .font_size(config.editor.font_size() as f32);
@@ -575,9 +738,8 @@ fn paint_text(
                 viewport,
             );
 
-            if let Some(whitespaces) = &text_layout.whitespaces {
-                let family: Vec<FamilyOwned> =
-                    FamilyOwned::parse_list(&config.editor.font_family).collect();
+            if render_whitespace != RenderWhitespace::None {
+                let family = config.editor.font_family();
                 let attrs = Attrs::new()
                     .color(*config.get_color(LapceColor::EDITOR_VISIBLE_WHITESPACE))
                     .family(&family)
@@ -588,7 +750,10 @@ fn paint_text(
                 let mut tab_text = TextLayout::new();
                 tab_text.set_text("→", attrs_list);
 
-                for (c, (x0, _x1)) in whitespaces.iter() {
+                for (c, (x0, _x1), render) in text_layout.whitespaces.iter() {
+                    if *render > render_whitespace {
+                        continue;
+                    }
                     match *c {
                         '\t' => {
                             cx.draw_text(
@@ -635,6 +800,132 @@ fn paint_text(
         }
     }
 
+    /// Paint the rulers, at the columns of the advance width of the font's
+    /// characters, so that they line up with monospace text.
+    fn paint_rulers(&self, cx: &mut PaintCx, viewport: Rect) {
+        let (doc, config) = self
+            .editor
+            .with_untracked(|editor| (editor.view.doc, editor.common.config));
+        let config = config.get_untracked();
+        let rulers = doc.with_untracked(|doc| doc.rulers(&config));
+        if rulers.is_empty() {
+            return;
+        }
+
+        let family = config.editor.font_family();
+        let attrs = Attrs::new()
+            .family(&family)
+            .font_size(config.editor.font_size() as f32);
+        let mut text = TextLayout::new();
+        text.set_text("a", AttrsList::new(attrs));
+        let char_width = text.hit_position(1).point.x;
+
+        let color = config.get_color(LapceColor::EDITOR_RULER);
+        for column in rulers {
+            let x = (column as f64 * char_width).round() + 0.5;
+            if x < viewport.x0 || x > viewport.x1 {
+                continue;
+            }
+            cx.stroke(
+                &Line::new(Point::new(x, viewport.y0), Point::new(x, viewport.y1)),
+                color,
+                1.0,
+            );
+        }
+    }
+
+    /// Paint the blame of the cursor's line after its end.
+    fn paint_inline_blame(&self, cx: &mut PaintCx, screen_lines: &ScreenLines) {
+        let (view, cursor, config) = self.editor.with_untracked(|editor| {
+            (editor.view.clone(), editor.cursor, editor.common.config)
+        });
+        let config = config.get_untracked();
+        if !config.editor.inline_blame || !self.is_active.get_untracked() {
+            return;
+        }
+
+        let offset = cursor.with_untracked(|cursor| cursor.offset());
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        let blame = view.doc.with_untracked(|doc| {
+            if !matches!(doc.content, DocContent::File(_)) {
+                return None;
+            }
+            let line = doc.buffer().line_of_offset(offset);
+            let text = doc.blame.with_untracked(|blame| {
+                inline_blame_text(blame.as_ref(), line, now)
+            })?;
+            Some((line, text))
+        });
+        let Some((line, text)) = blame else {
+            return;
+        };
+        let Some(info) = screen_lines.info.get(&line) else {
+            return;
+        };
+
+        let font_size = config.editor.font_size();
+        let line_height = config.editor.line_height() as f64;
+        let text_layout = view.get_text_layout(line, font_size);
+        let family = config.editor.font_family();
+        let attrs = Attrs::new()
+            .color(*config.get_color(LapceColor::EDITOR_DIM))
+            .family(&family)
+            .font_size(font_size as f32);
+        let mut blame_text = TextLayout::new();
+        blame_text.set_text(&text, AttrsList::new(attrs));
+        let height = blame_text.size().height;
+        let x = text_layout.text.size().width + font_size as f64 * 2.0;
+        cx.draw_text(
+            &blame_text,
+            Point::new(x, info.y as f64 + (line_height - height) / 2.0),
+        );
+    }
+
+    /// Paint the labels of the jump over the text, without what's been typed
+    /// of them, and only those that are still to be typed.
+    fn paint_jump_labels(&self, cx: &mut PaintCx, screen_lines: &ScreenLines) {
+        let (view, jump, config) = self.editor.with_untracked(|editor| {
+            (editor.view.clone(), editor.jump, editor.common.config)
+        });
+        let Some(Jump::Labels { targets, typed }) = jump.get_untracked() else {
+            return;
+        };
+        let config = config.get_untracked();
+        let font_size = config.editor.font_size();
+        let line_height = config.editor.line_height() as f64;
+        let family = config.editor.font_family();
+        let attrs = Attrs::new()
+            .color(*config.get_color(LapceColor::JUMP_LABEL_FOREGROUND))
+            .family(&family)
+            .font_size(font_size as f32);
+        let background = config.get_color(LapceColor::JUMP_LABEL_BACKGROUND);
+        for target in &targets {
+            let Some(label) = target.label.strip_prefix(typed.as_str()) else {
+                continue;
+            };
+            let (line, col) = view.offset_to_line_col(target.offset);
+            let Some(info) = screen_lines.info.get(&line) else {
+                continue;
+            };
+            let col = view.line_phantom_text(line).col_after(col, false);
+            let x = view.line_point_of_line_col(line, col, font_size).x;
+            let mut text_layout = TextLayout::new();
+            text_layout.set_text(label, AttrsList::new(attrs.clone()));
+            let size = text_layout.size();
+            let rect = Size::new(size.width, line_height)
+                .to_rect()
+                .with_origin(Point::new(x, info.y as f64));
+            cx.fill(&rect, background, 0.0);
+            cx.draw_text(
+                &text_layout,
+                Point::new(x, info.y as f64 + (line_height - size.height) / 2.0),
+            );
+        }
+    }
+
     fn paint_find(&self, cx: &mut PaintCx, screen_lines: &ScreenLines) {
         let visual = self.editor.with_untracked(|e| e.common.find.visual);
         if !visual.get_untracked() {
@@ -650,19 +941,35 @@ fn paint_find(&self, cx: &mut PaintCx, screen_lines: &ScreenLines) {
         let (view, config) = self
             .editor
             .with_untracked(|editor| (editor.view.clone(), editor.common.config));
-        let occurrences = view.find_result().occurrences;
+        let find = view.find();
+        let Some(search) = find.search_string.get_untracked() else {
+            return;
+        };
 
         let config = config.get_untracked();
         let line_height = config.editor.line_height() as f64;
 
         view.update_find();
-        let start = view.offset_of_line(min_line);
-        let end = view.offset_of_line(max_line + 1);
+        // Only the lines on screen are searched, so that a query with a lot of
+        // matches doesn't have them all highlighted, and the highlights don't
+        // wait for all of them to be found
+        let start = view.offset_of_line(min_line.saturating_sub(FIND_MARGIN_LINES));
+        let end = view.offset_of_line(
+            (max_line + 1 + FIND_MARGIN_LINES).min(view.last_line() + 1),
+        );
+        let occurrences = view.doc.with_untracked(|doc| {
+            Find::visible_occurrences(
+                doc.buffer().text(),
+                &search,
+                start,
+                end,
+                find.case_matching.get_untracked(),
+                find.whole_words.get_untracked(),
+            )
+        });
 
         let mut rects = Vec::new();
-        for region in occurrences.with_untracked(|selection| {
-            selection.regions_in_range(start, end).to_vec()
-        }) {
+        for region in occurrences.regions_in_range(start, end) {
             let start = region.min();
             let end = region.max();
             let (start_line, start_col) = view.offset_to_line_col(start);
@@ -960,6 +1267,7 @@ fn event(
     }
 
     fn paint(&mut self, cx: &mut PaintCx) {
+        let _timer = perf::time(Phase::Paint);
         let viewport = self.viewport.get_untracked();
         let (config, screen_lines) = self
             .editor
@@ -972,9 +1280,25 @@ fn paint(&mut self, cx: &mut PaintCx) {
         self.paint_cursor(cx, is_local, &screen_lines);
         self.paint_diff_sections(cx, viewport, &screen_lines, &config);
         self.paint_find(cx, &screen_lines);
+        if !is_local {
+            self.paint_rulers(cx, viewport);
+        }
         self.paint_text(cx, viewport, &screen_lines);
+        self.paint_inline_blame(cx, &screen_lines);
+        self.paint_jump_labels(cx, &screen_lines);
         self.paint_sticky_headers(cx, viewport);
         self.paint_scroll_bar(cx, viewport, is_local, config);
+
+        if let (Some(first), Some(last)) = (
+            screen_lines.lines.iter().min(),
+            screen_lines.lines.iter().max(),
+        ) {
+            let lines = *first..*last + 1;
+            let text_layouts =
+                self.editor.with_untracked(|e| e.view.text_layouts.clone());
+            text_layouts.borrow_mut().evict_far_from(lines.clone());
+            doc.with_untracked(|doc| doc.evict_text_layouts(lines));
+        }
     }
 }
 
@@ -1337,6 +1661,16 @@ pub fn editor_container_view(
                         editor_gutter(editor, is_active),
                         container(|| editor_content(editor, is_active))
                             .style(move |s| s.size_pct(100.0, 100.0)),
+                        minimap_view(editor).style(move |s| {
+                            let config = config.get();
+                            s.height_pct(100.0)
+                                .width_px(config.editor.minimap_width as f32)
+                                .apply_if(
+                                    !config.editor.show_minimap
+                                        || !editor_view.get().is_normal(),
+                                    |s| s.hide(),
+                                )
+                        }),
                         empty().style(move |s| {
                             let config = config.get();
                             s.absolute()
@@ -1407,8 +1741,16 @@ fn editor_gutter(
     let padding_left = 10.0;
     let padding_right = 30.0;
 
-    let (cursor, viewport, scroll_delta, config) = editor
-        .with_untracked(|e| (e.cursor, e.viewport, e.scroll_delta, e.common.config));
+    let (cursor, viewport, scroll_delta, error_flash, config) = editor
+        .with_untracked(|e| {
+            (
+                e.cursor,
+                e.viewport,
+                e.scroll_delta,
+                e.error_flash,
+                e.common.config,
+            )
+        });
 
     let code_action_line = create_memo(move |_| {
         if is_active(true) {
@@ -1440,8 +1782,16 @@ fn editor_gutter(
             stack(|| {
                 (
                     empty().style(move |s| s.width_px(padding_left)),
+                    // Sizes the gutter for the widest line number, if they're
+                    // shown
                     label(move || {
-                        let doc = editor.with(|e| e.view.doc);
+                        let (doc, options) =
+                            editor.with(|e| (e.view.doc, e.view.options));
+                        if !options
+                            .with(|options| options.line_numbers(&config.get()))
+                        {
+                            return String::new();
+                        }
                         doc.with(|doc| (doc.buffer().last_line() + 1).to_string())
                     }),
                     empty().style(move |s| s.width_px(padding_right)),
@@ -1457,7 +1807,10 @@ fn editor_gutter(
                             })
                             .on_event(EventListener::PointerWheel, move |event| {
                                 if let Event::PointerWheel(pointer_event) = event {
-                                    scroll_delta.set(pointer_event.delta);
+                                    scroll_delta.set(wheel_scroll_delta(
+                                        pointer_event,
+                                        &config.get_untracked(),
+                                    ));
                                 }
                                 true
                             })
@@ -1519,11 +1872,14 @@ fn editor_gutter(
                 .style(|s| s.size_pct(100.0, 100.0))
             })
             .style(move |s| {
+                let color = if error_flash.get() == TimerToken::INVALID {
+                    LapceColor::EDITOR_BACKGROUND
+                } else {
+                    LapceColor::EDITOR_ERROR_FLASH
+                };
                 s.absolute()
                     .size_pct(100.0, 100.0)
-                    .background(
-                        *config.get().get_color(LapceColor::EDITOR_BACKGROUND),
-                    )
+                    .background(*config.get().get_color(color))
                     .padding_left_px(padding_left)
                     .padding_right_px(padding_right)
             }),
@@ -1547,6 +1903,9 @@ fn editor_breadcrumbs(
             }
         })
     });
+    let internal_command =
+        editor.with_untracked(|editor| editor.common.internal_command);
+    let symbols = breadcrumb_symbols(editor, config);
     container(move || {
         scroll(move || {
             stack(|| {
@@ -1556,52 +1915,77 @@ fn editor_breadcrumbs(
                         list(
                             move || {
                                 let full_path = doc_path.get().unwrap_or_default();
-                                let mut path = full_path;
-                                if let Some(workspace_path) =
-                                    workspace.clone().path.as_ref()
-                                {
-                                    path = path
-                                        .strip_prefix(workspace_path)
-                                        .unwrap_or(&path)
-                                        .to_path_buf();
-                                }
-                                path.ancestors()
+                                let path = workspace.display_path(&full_path);
+                                let sections = path
+                                    .ancestors()
                                     .collect::<Vec<_>>()
                                     .iter()
                                     .rev()
                                     .filter_map(|path| {
                                         Some(path.file_name()?.to_str()?.to_string())
                                     })
+                                    .collect::<Vec<_>>();
+                                // Clicking a section lists the files next to it
+                                let mut parent = String::new();
+                                sections
+                                    .into_iter()
+                                    .map(|section| {
+                                        let input = parent.clone();
+                                        parent.push_str(&section);
+                                        parent.push(std::path::MAIN_SEPARATOR);
+                                        (section, input)
+                                    })
                                     .collect::<Vec<_>>()
                                     .into_iter()
                                     .enumerate()
                             },
-                            |(i, section)| (*i, section.to_string()),
-                            move |(i, section)| {
+                            |(i, (section, _))| (*i, section.to_string()),
+                            move |(i, (section, input))| {
                                 stack(move || {
                                     (
-                                        svg(move || {
-                                            config.get().ui_svg(
-                                                LapceIcons::BREADCRUMB_SEPARATOR,
-                                            )
-                                        })
-                                        .style(move |s| {
-                                            let config = config.get();
-                                            let size = config.ui.icon_size() as f32;
-                                            s.apply_if(i == 0, |s| s.hide())
-                                                .size_px(size, size)
-                                                .color(*config.get_color(
-                                                    LapceColor::LAPCE_ICON_ACTIVE,
-                                                ))
-                                        }),
+                                        breadcrumb_separator(i == 0, config),
                                         label(move || section.clone()),
                                     )
                                 })
+                                .on_click(move |_| {
+                                    internal_command.send(
+                                        InternalCommand::PaletteWithInput {
+                                            kind: PaletteKind::File,
+                                            input: input.clone(),
+                                        },
+                                    );
+                                    true
+                                })
                                 .style(|s| s.items_center())
+                                .hover_style(|s| s.cursor(CursorStyle::Pointer))
                             },
                         )
-                        .style(|s| s.padding_horiz_px(10.0))
+                        .style(|s| s.padding_left_px(10.0))
                     },
+                    list(
+                        move || symbols.get().into_iter().enumerate(),
+                        |(i, name)| (*i, name.clone()),
+                        move |(_, name)| {
+                            stack(move || {
+                                (
+                                    breadcrumb_separator(false, config),
+                                    label(move || name.clone()),
+                                )
+                            })
+                            .on_click(move |_| {
+                                internal_command.send(
+                                    InternalCommand::PaletteWithInput {
+                                        kind: PaletteKind::DocumentSymbol,
+                                        input: String::new(),
+                                    },
+                                );
+                                true
+                            })
+                            .style(|s| s.items_center())
+                            .hover_style(|s| s.cursor(CursorStyle::Pointer))
+                        },
+                    )
+                    .style(|s| s.padding_right_px(10.0)),
                     label(move || {
                         let doc = editor.with(|editor| editor.view.doc);
                         doc.with_untracked(|doc| {
@@ -1638,15 +2022,137 @@ fn editor_breadcrumbs(
         })
     })
     .style(move |s| {
-        let config = config.get_untracked();
+        let config = config.get();
         let line_height = config.editor.line_height();
         s.items_center()
             .width_pct(100.0)
             .height_px(line_height as f32)
-            .apply_if(doc_path.get().is_none(), |s| s.hide())
+            .apply_if(
+                doc_path.get().is_none() || !config.editor.show_bread_crumbs,
+                |s| s.hide(),
+            )
     })
 }
 
+fn breadcrumb_separator(
+    hide: bool,
+    config: ReadSignal<Arc<LapceConfig>>,
+) -> impl View {
+    svg(move || config.get().ui_svg(LapceIcons::BREADCRUMB_SEPARATOR)).style(
+        move |s| {
+            let config = config.get();
+            let size = config.ui.icon_size() as f32;
+            s.apply_if(hide, |s| s.hide())
+                .size_px(size, size)
+                .color(*config.get_color(LapceColor::LAPCE_ICON_ACTIVE))
+        },
+    )
+}
+
+/// The names of the symbols around the cursor, outermost first. The
+/// document's symbols are asked for again once it stops changing.
+fn breadcrumb_symbols(
+    editor: RwSignal<EditorData>,
+    config: ReadSignal<Arc<LapceConfig>>,
+) -> Memo<Vec<String>> {
+    let (doc, cursor, proxy, scope) = editor.with_untracked(|editor| {
+        (
+            editor.view.doc,
+            editor.cursor,
+            editor.common.proxy.clone(),
+            editor.scope,
+        )
+    });
+    let document_symbols: RwSignal<Vec<(String, lsp_types::Range)>> =
+        create_rw_signal(Vec::new());
+
+    create_effect(move |_| {
+        if !config.with(|config| config.editor.show_bread_crumbs) {
+            return;
+        }
+        let (path, rev, atomic_rev) = doc.with(|doc| {
+            let path = match &doc.content {
+                DocContent::File(path) => Some(path.clone()),
+                _ => None,
+            };
+            (path, doc.rev(), doc.buffer().atomic_rev())
+        });
+        let Some(path) = path else {
+            return;
+        };
+
+        let send = {
+            let atomic_rev = atomic_rev.clone();
+            create_ext_action(scope, move |result| {
+                if atomic_rev.load(atomic::Ordering::Acquire) != rev {
+                    return;
+                }
+                let symbols =
+                    if let Ok(ProxyResponse::GetDocumentSymbols { resp }) = result {
+                        flatten_document_symbols(resp)
+                    } else {
+                        Vec::new()
+                    };
+                document_symbols.set(symbols);
+            })
+        };
+        let proxy = proxy.clone();
+        exec_after(Duration::from_millis(500), move |_| {
+            if atomic_rev.load(atomic::Ordering::Acquire) != rev {
+                return;
+            }
+            proxy.get_document_symbols(path, move |result| {
+                send(result);
+            });
+        });
+    });
+
+    create_memo(move |_| {
+        let offset = cursor.with(|cursor| cursor.offset());
+        let position =
+            doc.with_untracked(|doc| doc.buffer().offset_to_position(offset));
+        document_symbols.with(|symbols| {
+            symbols
+                .iter()
+                .filter(|(_, range)| {
+                    range.start <= position && position <= range.end
+                })
+                .map(|(name, _)| name.clone())
+                .collect()
+        })
+    })
+}
+
+/// All the symbols of a document with their ranges, ordered by where they
+/// start so that enclosing symbols come before the ones inside them.
+fn flatten_document_symbols(
+    resp: DocumentSymbolResponse,
+) -> Vec<(String, lsp_types::Range)> {
+    fn flatten(
+        symbols: Vec<DocumentSymbol>,
+        flat: &mut Vec<(String, lsp_types::Range)>,
+    ) {
+        for symbol in symbols {
+            flat.push((symbol.name, symbol.range));
+            flatten(symbol.children.unwrap_or_default(), flat);
+        }
+    }
+
+    let mut flat = match resp {
+        DocumentSymbolResponse::Flat(symbols) => symbols
+            .into_iter()
+            .map(|symbol| (symbol.name, symbol.location.range))
+            .collect(),
+        DocumentSymbolResponse::Nested(symbols) => {
+            let mut flat = Vec::new();
+            flatten(symbols, &mut flat);
+            flat
+        }
+    };
+    flat.sort_by_key(|(_, range)| range.start);
+    flat
+}
+
 fn editor_content(
     editor: RwSignal<EditorData>,
     is_active: impl Fn(bool) -> bool + 'static + Copy,
@@ -1689,7 +2195,9 @@ fn editor_content(
                 if let Event::PointerDown(pointer_event) = event {
                     id.request_active();
                     let editor = editor.get_untracked();
-                    editor.pointer_down(pointer_event);
+                    // A mouse binding has already run, so it shouldn't run
+                    // again from the window
+                    return editor.pointer_down(pointer_event);
                 }
                 false
             })
@@ -1707,6 +2215,23 @@ fn editor_content(
                 }
                 true
             })
+            .on_event(EventListener::PointerWheel, move |event| {
+                if let Event::PointerWheel(pointer_event) = event {
+                    let editor = editor.get_untracked();
+                    if let Some(zoom) = wheel_zoom(pointer_event) {
+                        editor.common.lapce_command.send(LapceCommand {
+                            kind: CommandKind::Workbench(zoom),
+                            data: None,
+                        });
+                    } else {
+                        editor.scroll_delta.set(wheel_scroll_delta(
+                            pointer_event,
+                            &config.get_untracked(),
+                        ));
+                    }
+                }
+                true
+            })
     })
     .on_move(move |point| {
         window_origin.set(point);
@@ -1764,6 +2289,64 @@ fn editor_content(
     .style(|s| s.absolute().size_pct(100.0, 100.0))
 }
 
+/// The pixels floem reports for one step of a mouse wheel that scrolls by lines.
+const WHEEL_LINE_STEP: f64 = 60.0;
+
+/// The scroll delta for a wheel event. Floem gives line deltas as multiples of
+/// [`WHEEL_LINE_STEP`], which are turned into `wheel-scroll-lines` lines each.
+/// Anything else is a pixel delta from a trackpad, including the momentum ones
+/// after the fingers are lifted, and is used as it is apart from the speed
+/// modifier. Both axes are kept, so horizontal trackpad movement scrolls
+/// horizontally, and a vertical only delta with shift held does too. The delta
+/// only moves the viewport, never the cursor, so it doesn't bring the view back
+/// to the cursor.
+fn wheel_scroll_delta(
+    pointer_event: &PointerWheelEvent,
+    config: &LapceConfig,
+) -> Vec2 {
+    let delta = pointer_event.delta;
+    let delta = if pointer_event.modifiers.shift_key() && delta.x == 0.0 {
+        Vec2::new(delta.y, 0.0)
+    } else {
+        delta
+    };
+    let delta = if is_line_delta(delta) {
+        let lines = config.editor.wheel_scroll_lines.max(1) as f64;
+        delta / WHEEL_LINE_STEP * lines * config.editor.line_height() as f64
+    } else {
+        delta
+    };
+    let modifier = config.editor.scroll_speed_modifier;
+    if modifier > 0.0 {
+        delta * modifier
+    } else {
+        delta
+    }
+}
+
+/// If the wheel delta is whole steps of a mouse wheel rather than pixels.
+fn is_line_delta(delta: Vec2) -> bool {
+    let is_steps = |d: f64| d % WHEEL_LINE_STEP == 0.0;
+    delta != Vec2::ZERO && is_steps(delta.x) && is_steps(delta.y)
+}
+
+/// The zoom for a wheel event with Ctrl held, or Cmd on macOS, which zooms
+/// in when scrolling up rather than scrolling.
+fn wheel_zoom(pointer_event: &PointerWheelEvent) -> Option<LapceWorkbenchCommand> {
+    #[cfg(target_os = "macos")]
+    let zooming = pointer_event.modifiers.super_key();
+    #[cfg(not(target_os = "macos"))]
+    let zooming = pointer_event.modifiers.control_key();
+    if !zooming || pointer_event.delta.y == 0.0 {
+        return None;
+    }
+    Some(if pointer_event.delta.y < 0.0 {
+        LapceWorkbenchCommand::ZoomIn
+    } else {
+        LapceWorkbenchCommand::ZoomOut
+    })
+}
+
 fn search_editor_view(
     find_editor: EditorData,
     find_focus: RwSignal<bool>,
@@ -1851,6 +2434,7 @@ fn replace_editor_view(
 ) -> impl View {
     let config = replace_editor.common.config;
     let visual = replace_editor.common.find.visual;
+    let preserve_case = replace_editor.common.find.preserve_case;
 
     stack(|| {
         (
@@ -1867,11 +2451,18 @@ fn replace_editor_view(
                 false
             })
             .style(|s| s.width_pct(100.0)),
-            empty().style(move |s| {
-                let config = config.get();
-                let size = config.ui.icon_size() as f32 + 10.0;
-                s.size_px(0.0, size).padding_vert_px(4.0)
-            }),
+            clickable_icon(
+                || LapceIcons::SEARCH_PRESERVE_CASE,
+                move || {
+                    preserve_case.update(|preserve_case| {
+                        *preserve_case = !*preserve_case;
+                    });
+                },
+                move || preserve_case.get(),
+                || false,
+                config,
+            )
+            .style(|s| s.padding_horiz_px(6.0).padding_vert_px(4.0)),
         )
     })
     .style(move |s| {
@@ -1902,19 +2493,29 @@ fn find_view(
     let find_pos = create_memo(move |_| {
         let visual = find_visual.get();
         if !visual {
-            return (0, 0);
+            return (None, 0, true);
         }
         let (curosr, view) =
             editor.with(|editor| (editor.cursor, editor.view.clone()));
         let offset = curosr.with(|cursor| cursor.offset());
+        let counted = view
+            .find_result
+            .progress
+            .with(|p| *p == FindProgress::Ready);
         let occurrences = view.find_result.occurrences;
         occurrences.with(|occurrences| {
-            for (i, region) in occurrences.regions().iter().enumerate() {
-                if offset <= region.max() {
-                    return (i + 1, occurrences.regions().len());
-                }
-            }
-            (occurrences.regions().len(), occurrences.regions().len())
+            let regions = occurrences.regions();
+            let i = regions.partition_point(|region| region.max() < offset);
+            // Past the matches counted so far, where the match is isn't known
+            // until the rest are counted
+            let current = if i < regions.len() {
+                Some(i + 1)
+            } else if counted {
+                Some(regions.len())
+            } else {
+                None
+            };
+            (current, regions.len(), counted)
         })
     });
 
@@ -1946,11 +2547,18 @@ fn find_view(
                             replace_focus,
                         ),
                         label(move || {
-                            let (current, all) = find_pos.get();
+                            let (current, all, counted) = find_pos.get();
                             if all == 0 {
-                                "No Results".to_string()
+                                return "No Results".to_string();
+                            }
+                            let all = if counted || all < MAX_FIND_COUNT {
+                                all.to_string()
                             } else {
-                                format!("{current} of {all}")
+                                format!("{MAX_FIND_COUNT}+")
+                            };
+                            match current {
+                                Some(current) => format!("{current} of {all}"),
+                                None => format!("{all} Results"),
                             }
                         })
                         .style(|s| s.margin_left_px(6.0).min_width_px(70.0)),
@@ -1,4 +1,4 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc, sync::Arc};
+use std::{cell::RefCell, collections::HashMap, ops::Range, rc::Rc, sync::Arc};
 
 use floem::{
     cosmic_text::TextLayout,
@@ -10,18 +10,23 @@
     buffer::{
         diff::DiffLines,
         rope_text::{RopeText, RopeTextVal},
+        InvalLines,
     },
     char_buffer::CharBuffer,
-    cursor::ColPosition,
+    color_literal::ColorLiteral,
     mode::Mode,
     soft_tab::{snap_to_soft_tab_line_col, SnapDirection},
     word::WordCursor,
 };
 use lapce_xi_rope::Rope;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     config::LapceConfig,
-    doc::{phantom_text::PhantomTextLine, Document},
+    doc::{
+        phantom_text::{PhantomTextKind, PhantomTextLine},
+        Document,
+    },
     find::{Find, FindResult},
 };
 
@@ -42,10 +47,64 @@ pub struct TextLayoutLine {
     /// (x0, x1 or line display end, style)
     pub extra_style: Vec<LineExtraStyle>,
     pub text: TextLayout,
-    pub whitespaces: Option<Vec<(char, (f64, f64))>>,
+    /// The whitespace characters of the line with their position, and the
+    /// least that whitespace has to be rendered for each to be drawn
+    pub whitespaces: Vec<(char, (f64, f64), RenderWhitespace)>,
     pub indent: f64,
+    /// Whether the brackets were left uncolored because the syntax tree was
+    /// behind the text, so the layout has to be made again once it catches up
+    pub brackets_stale: bool,
+}
+
+/// A change to a document that leaves some of the per-line caches built from
+/// it stale.
+#[derive(Debug, Clone)]
+pub enum LineInval {
+    /// An edit replaced some lines, shifting the ones after it
+    Edit(InvalLines),
+    /// The highlighting of these lines changed
+    Lines(Vec<Range<usize>>),
+}
+
+impl LineInval {
+    /// Drop the entries of `lines` that went stale, and move the ones after an
+    /// edit to their new line.
+    pub fn apply<T>(&self, lines: &mut HashMap<usize, T>) {
+        match self {
+            LineInval::Edit(inval_lines) => {
+                let start = inval_lines.start_line;
+                let end = start + inval_lines.inval_count;
+                let shifted = lines
+                    .keys()
+                    .copied()
+                    .filter(|line| *line >= start)
+                    .collect::<Vec<_>>();
+                let mut moved = Vec::new();
+                for line in shifted {
+                    let entry = lines.remove(&line).unwrap();
+                    if line >= end {
+                        moved.push((
+                            line + inval_lines.new_count - inval_lines.inval_count,
+                            entry,
+                        ));
+                    }
+                }
+                lines.extend(moved);
+            }
+            LineInval::Lines(ranges) => {
+                lines.retain(|line, _| {
+                    !ranges.iter().any(|range| range.contains(line))
+                });
+            }
+        }
+    }
 }
 
+/// How many line layouts are kept for each font size before the ones farthest
+/// from the lines in view are dropped, so that scrolling through a huge file
+/// doesn't keep the layouts of all of it.
+const MAX_CACHED_LAYOUTS: usize = 5000;
+
 /// Keeps track of the text layouts so that we can efficiently reuse them.
 #[derive(Clone, Default)]
 pub struct TextLayoutCache {
@@ -76,6 +135,37 @@ pub fn clear(&mut self, cache_rev: u64) {
         self.max_width = 0.0;
     }
 
+    /// Drop the layouts made stale by `inval`, which brought the document to
+    /// `cache_rev`, keeping the rest.
+    pub fn invalidate(&mut self, inval: &LineInval, cache_rev: u64) {
+        for layouts in self.layouts.values_mut() {
+            inval.apply(layouts);
+        }
+        self.cache_rev = cache_rev;
+    }
+
+    /// Drop the layouts of the lines farthest from `lines` once there are more
+    /// than [`MAX_CACHED_LAYOUTS`] of a font size, down to three quarters of
+    /// that so that it doesn't happen again on the next paint.
+    pub fn evict_far_from(&mut self, lines: Range<usize>) {
+        for layouts in self.layouts.values_mut() {
+            if layouts.len() <= MAX_CACHED_LAYOUTS {
+                continue;
+            }
+            let mut cached = layouts.keys().copied().collect::<Vec<_>>();
+            cached.sort_unstable_by_key(|line| {
+                if *line < lines.start {
+                    lines.start - line
+                } else {
+                    line.saturating_sub(lines.end)
+                }
+            });
+            for line in &cached[MAX_CACHED_LAYOUTS * 3 / 4..] {
+                layouts.remove(line);
+            }
+        }
+    }
+
     pub fn check_attributes(&mut self, config_id: u64) {
         if self.config_id != config_id {
             self.clear(self.cache_rev + 1);
@@ -103,6 +193,84 @@ pub fn is_normal(&self) -> bool {
     }
 }
 
+/// Which whitespace characters are drawn. Each choice draws the ones of the
+/// choices before it as well.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum RenderWhitespace {
+    None,
+    /// The whitespace at the end of lines
+    Trailing,
+    /// The whitespace at the start of lines and the runs of more than one
+    /// whitespace character between words
+    Boundary,
+    All,
+}
+
+impl RenderWhitespace {
+    /// The choice of the `render-whitespace` setting
+    pub fn from_setting(setting: &str) -> Self {
+        match setting {
+            "all" => RenderWhitespace::All,
+            "boundary" => RenderWhitespace::Boundary,
+            "trailing" => RenderWhitespace::Trailing,
+            _ => RenderWhitespace::None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RenderWhitespace::None => "none",
+            RenderWhitespace::Trailing => "trailing",
+            RenderWhitespace::Boundary => "boundary",
+            RenderWhitespace::All => "all",
+        }
+    }
+}
+
+/// How a view displays its document where it differs from the settings, so
+/// that views of the same document can each show it their own way. What isn't
+/// set follows the settings.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ViewOptions {
+    #[serde(default)]
+    pub render_whitespace: Option<RenderWhitespace>,
+    #[serde(default)]
+    pub line_numbers: Option<bool>,
+}
+
+impl ViewOptions {
+    pub fn render_whitespace(&self, config: &LapceConfig) -> RenderWhitespace {
+        self.render_whitespace.unwrap_or_else(|| {
+            RenderWhitespace::from_setting(&config.editor.render_whitespace)
+        })
+    }
+
+    pub fn line_numbers(&self, config: &LapceConfig) -> bool {
+        self.line_numbers.unwrap_or(config.editor.show_line_numbers)
+    }
+
+    /// Hide the whitespace if it's shown, or else show it as the settings
+    /// say to, or all of it if they don't show any.
+    pub fn toggle_whitespace(&mut self, config: &LapceConfig) {
+        let setting =
+            RenderWhitespace::from_setting(&config.editor.render_whitespace);
+        self.render_whitespace = Some(match self.render_whitespace(config) {
+            RenderWhitespace::None if setting == RenderWhitespace::None => {
+                RenderWhitespace::All
+            }
+            RenderWhitespace::None => setting,
+            _ => RenderWhitespace::None,
+        });
+    }
+
+    pub fn toggle_line_numbers(&mut self, config: &LapceConfig) {
+        self.line_numbers = Some(!self.line_numbers(config));
+    }
+}
+
 // TODO(minor): Should this go in another file? It doesn't really need to be with the drawing code for editor views
 /// Data specific to the rendering of a view.  
 /// This has various helper methods that may dispatch to the held [`Document`] signal, but are
@@ -117,6 +285,7 @@ pub struct EditorViewData {
     pub kind: RwSignal<EditorViewKind>,
     /// The text layouts for the document. This may be shared with other views.
     pub text_layouts: Rc<RefCell<TextLayoutCache>>,
+    pub options: RwSignal<ViewOptions>,
 
     pub config: ReadSignal<Arc<LapceConfig>>,
 }
@@ -146,6 +315,7 @@ impl EditorViewData {
     pub fn new(
         doc: RwSignal<Document>,
         kind: RwSignal<EditorViewKind>,
+        options: RwSignal<ViewOptions>,
         config: ReadSignal<Arc<LapceConfig>>,
     ) -> EditorViewData {
         let find_result = doc.with_untracked(|doc| doc.find_result.clone());
@@ -154,6 +324,7 @@ pub fn new(
             find_result,
             kind,
             text_layouts: Rc::new(RefCell::new(TextLayoutCache::new())),
+            options,
             config,
         }
     }
@@ -227,6 +398,7 @@ pub fn duplicate(&self, cx: Scope) -> Self {
             find_result: self.find_result.clone(),
             text_layouts: Rc::new(RefCell::new(TextLayoutCache::new())),
             kind: cx.create_rw_signal(self.kind.get_untracked()),
+            options: cx.create_rw_signal(self.options.get_untracked()),
             config: self.config,
         }
     }
@@ -245,8 +417,16 @@ pub fn get_text_layout(
         {
             let mut text_layouts = self.text_layouts.borrow_mut();
             self.doc.with_untracked(|doc| {
-                if doc.cache_rev() != text_layouts.cache_rev {
-                    text_layouts.clear(doc.cache_rev());
+                if doc.cache_rev() == text_layouts.cache_rev {
+                    return;
+                }
+                match doc.line_invals_since(text_layouts.cache_rev) {
+                    Some(invals) => {
+                        for (cache_rev, inval) in invals {
+                            text_layouts.invalidate(inval, *cache_rev);
+                        }
+                    }
+                    None => text_layouts.clear(doc.cache_rev()),
                 }
             })
         }
@@ -263,6 +443,7 @@ pub fn get_text_layout(
         }
 
         // Get whether there's an entry for this specific font size and line
+        // that is still good to use
         let cache_exists = self
             .text_layouts
             .borrow()
@@ -270,7 +451,10 @@ pub fn get_text_layout(
             .get(&font_size)
             .unwrap()
             .get(&line)
-            .is_some();
+            .map_or(false, |layout| {
+                !layout.brackets_stale
+                    || self.doc.with_untracked(|doc| doc.syntax_behind())
+            });
         // If there isn't an entry then we actually have to create it
         if !cache_exists {
             let text_layout = self
@@ -629,6 +813,41 @@ pub fn offset_of_point(&self, mode: Mode, point: Point) -> (usize, bool) {
         (self.offset_of_line_col(line, col), is_inside)
     }
 
+    /// The color literal whose swatch is at `point`, with its offsets in the
+    /// document.
+    pub fn color_swatch_at(&self, point: Point) -> Option<ColorLiteral> {
+        let config = self.config.get_untracked();
+        let visual_line =
+            (point.y / config.editor.line_height() as f64).floor() as usize;
+        let line = self.actual_line(visual_line, true);
+        if line > self.last_line() {
+            return None;
+        }
+        let text_layout = self.get_text_layout(line, config.editor.font_size());
+        let phantom_text = self.line_phantom_text(line);
+        let col = phantom_text.offset_size_iter().find_map(
+            |(offset, size, col, phantom)| {
+                if phantom.kind != PhantomTextKind::ColorSwatch {
+                    return None;
+                }
+                let start = col + offset;
+                let x0 = text_layout.text.hit_position(start).point.x;
+                let x1 = text_layout.text.hit_position(start + size).point.x;
+                (x0..x1).contains(&point.x).then_some(col)
+            },
+        )?;
+        let line_start = self.offset_of_line(line);
+        self.doc
+            .with_untracked(|doc| doc.line_color_literals(line))
+            .into_iter()
+            .find(|literal| literal.start == col)
+            .map(|literal| ColorLiteral {
+                start: line_start + literal.start,
+                end: line_start + literal.end,
+                ..literal
+            })
+    }
+
     /// Get the (line, col) of a particular point within the editor.
     /// The boolean indicates whether the point is within the text bounds.  
     /// Points outside of vertical bounds will return the last line.
@@ -669,29 +888,6 @@ pub fn line_col_of_point(
         ((line, col), hit_point.is_inside)
     }
 
-    pub fn line_horiz_col(
-        &self,
-        line: usize,
-        font_size: usize,
-        horiz: &ColPosition,
-        caret: bool,
-    ) -> usize {
-        match *horiz {
-            ColPosition::Col(x) => {
-                let text_layout = self.get_text_layout(line, font_size);
-                let hit_point = text_layout.text.hit_point(Point::new(x, 0.0));
-                let n = hit_point.index;
-
-                n.min(self.line_end_col(line, caret))
-            }
-            ColPosition::End => self.line_end_col(line, caret),
-            ColPosition::Start => 0,
-            ColPosition::FirstNonBlank => {
-                self.first_non_blank_character_on_line(line)
-            }
-        }
-    }
-
     /// Advance to the right in the manner of the given mode.  
     /// This is not the same as the [`Movement::Right`] command.
     pub fn move_right(&self, offset: usize, mode: Mode, count: usize) -> usize {
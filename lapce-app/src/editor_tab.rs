@@ -66,6 +66,28 @@ pub struct EditorTabInfo {
 }
 
 impl EditorTabInfo {
+    /// Drop the editors of files which don't exist any more, keeping the same
+    /// child active if it's still there.
+    pub fn retain_existing_files(&mut self) {
+        let mut index = 0;
+        let mut active = self.active;
+        self.children.retain(|child| {
+            let keep = match child {
+                EditorTabChildInfo::Editor(EditorInfo {
+                    content: DocContent::File(path),
+                    ..
+                }) => path.exists(),
+                _ => true,
+            };
+            if !keep && index < self.active {
+                active -= 1;
+            }
+            index += 1;
+            keep
+        });
+        self.active = active.min(self.children.len().saturating_sub(1));
+    }
+
     pub fn to_data(
         &self,
         data: MainSplitData,
@@ -193,39 +215,51 @@ pub fn view_info(
                 let editor_data =
                     editors.with(|editors| editors.get(&editor_id).cloned());
                 let path = if let Some(editor_data) = editor_data {
-                    let ((content, is_pristine), confirmed) =
-                        editor_data.with(|editor_data| {
+                    let ((content, is_pristine, orphaned), confirmed) = editor_data
+                        .with(|editor_data| {
                             (
                                 editor_data.view.doc.with(|doc| {
-                                    (doc.content.clone(), doc.buffer().is_pristine())
+                                    (
+                                        doc.content.clone(),
+                                        doc.buffer().is_pristine(),
+                                        doc.orphaned,
+                                    )
                                 }),
                                 editor_data.confirmed,
                             )
                         });
                     match content {
                         DocContent::File(path) => {
-                            Some((path, confirmed, is_pristine))
+                            Some((path, confirmed, is_pristine, orphaned))
                         }
                         DocContent::Local => None,
                         DocContent::History(_) => None,
-                        DocContent::Scratch { name, .. } => {
-                            Some((PathBuf::from(name), confirmed, is_pristine))
-                        }
+                        DocContent::Scratch { name, .. } => Some((
+                            PathBuf::from(name),
+                            confirmed,
+                            is_pristine,
+                            false,
+                        )),
                     }
                 } else {
                     None
                 };
                 let (icon, color, path, confirmed, is_pristine) = match path {
-                    Some((path, confirmed, is_pritine)) => {
+                    Some((path, confirmed, is_pritine, orphaned)) => {
                         let (svg, color) = config.file_svg(&path);
+                        let name = path
+                            .file_name()
+                            .unwrap_or_default()
+                            .to_str()
+                            .unwrap_or_default();
                         (
                             svg,
                             color.cloned(),
-                            path.file_name()
-                                .unwrap_or_default()
-                                .to_str()
-                                .unwrap_or_default()
-                                .to_string(),
+                            if orphaned {
+                                format!("{name} (deleted)")
+                            } else {
+                                name.to_string()
+                            },
                             confirmed,
                             is_pritine,
                         )
@@ -0,0 +1,62 @@
+//! What the editor does when a command can't do what it was asked, like a
+//! motion at the end of the document, a search that matches nothing or an
+//! operator that's given no motion.
+//!
+//! A counted motion goes as far as it can: `10j` five lines from the end of
+//! the document moves five lines and is no error. It's only an error when the
+//! cursor can't move at all.
+
+use std::time::Duration;
+
+/// How long the gutter of an editor is flashed for
+pub const FLASH_DURATION: Duration = Duration::from_millis(150);
+
+/// How an error is made known, the `error-feedback` setting
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorFeedback {
+    /// The gutter of the editor is flashed in the theme's color
+    Flash,
+    /// The system bell is rung
+    Beep,
+    None,
+}
+
+impl ErrorFeedback {
+    /// The choice of the `error-feedback` setting
+    pub fn from_setting(setting: &str) -> Self {
+        match setting {
+            "flash" => ErrorFeedback::Flash,
+            "beep" => ErrorFeedback::Beep,
+            _ => ErrorFeedback::None,
+        }
+    }
+}
+
+/// Ring the system bell. Linux has no bell of its own to ring, so there the
+/// bell character goes to the terminal Lapce was started from, if any.
+pub fn beep() {
+    #[cfg(target_os = "windows")]
+    {
+        #[link(name = "user32")]
+        extern "system" {
+            fn MessageBeep(kind: u32) -> i32;
+        }
+        // MB_OK, the default sound of the system
+        unsafe { MessageBeep(0) };
+    }
+    #[cfg(target_os = "macos")]
+    {
+        #[link(name = "AppKit", kind = "framework")]
+        extern "C" {
+            fn NSBeep();
+        }
+        unsafe { NSBeep() };
+    }
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        use std::io::Write;
+        let mut stderr = std::io::stderr();
+        let _ = stderr.write_all(b"\x07");
+        let _ = stderr.flush();
+    }
+}
@@ -1,27 +1,51 @@
 use std::{
     collections::HashMap,
     path::{Path, PathBuf},
+    rc::Rc,
+    time::Duration,
 };
 
 use floem::{
+    action::{exec_after, save_as, show_context_menu, TimerToken},
     ext_event::create_ext_action,
+    file::{FileDialogOptions, FileInfo},
+    menu::{Menu, MenuItem},
     reactive::{RwSignal, Scope},
 };
-use lapce_rpc::proxy::ProxyResponse;
+use lapce_rpc::{proxy::ProxyResponse, RpcError};
+use lsp_types::{MessageType, ShowMessageParams, WorkspaceEdit};
 
 use super::node::FileNode;
-use crate::{command::InternalCommand, window_tab::CommonData};
+use crate::{alert::AlertButton, command::InternalCommand, window_tab::CommonData};
+
+/// How long the active file has to stay the same before the explorer follows
+/// it, so switching through files quickly doesn't keep moving the tree
+pub const FOLLOW_DELAY: Duration = Duration::from_millis(150);
+
+/// Where the files moved to the trash can be restored from
+#[cfg(target_os = "windows")]
+const RESTORE_HINT: Option<&str> = Some("It can be restored from the Recycle Bin.");
+#[cfg(target_os = "macos")]
+const RESTORE_HINT: Option<&str> = Some("It can be put back from the Trash.");
+#[cfg(all(unix, not(target_os = "macos")))]
+const RESTORE_HINT: Option<&str> = Some("It can be restored from the trash.");
+#[cfg(not(any(target_os = "windows", unix)))]
+const RESTORE_HINT: Option<&str> = None;
 
 #[derive(Clone)]
 pub struct FileExplorerData {
     pub id: RwSignal<usize>,
     pub root: RwSignal<FileNode>,
+    /// The line of the tree to scroll into view
+    pub scroll_to_line: RwSignal<Option<usize>>,
+    follow_timer: RwSignal<TimerToken>,
     pub common: CommonData,
 }
 
 impl FileExplorerData {
     pub fn new(cx: Scope, common: CommonData) -> Self {
         let path = common.workspace.path.clone().unwrap_or_default();
+        let config = common.config;
         let new_root = cx.create_rw_signal(FileNode {
             path: path.clone(),
             is_dir: true,
@@ -30,11 +54,16 @@ pub fn new(cx: Scope, common: CommonData) -> Self {
             children: HashMap::new(),
             children_open_count: 0,
             line_height: common.ui_line_height,
+            natural_sort: cx.create_memo(move |_| {
+                config.with(|config| config.files.natural_sort)
+            }),
             internal_command: common.internal_command,
         });
         let data = Self {
             id: cx.create_rw_signal(0),
             root: new_root,
+            scroll_to_line: cx.create_rw_signal(None),
+            follow_timer: cx.create_rw_signal(TimerToken::INVALID),
             common,
         };
         data.toggle_expand(&path);
@@ -75,7 +104,70 @@ pub fn toggle_expand(&self, path: &Path) {
         }
     }
 
+    /// Expand the directories down to `path`, reading the ones that haven't
+    /// been, and scroll it into view.
+    pub fn reveal_path(&self, path: &Path) {
+        let root_path = self.root.with_untracked(|root| root.path.clone());
+        let Ok(relative) = path.strip_prefix(&root_path) else {
+            return;
+        };
+        let dirs = relative
+            .ancestors()
+            .skip(1)
+            .map(|dir| root_path.join(dir))
+            .collect::<Vec<_>>();
+        for dir in dirs.into_iter().rev() {
+            let read = self.root.try_update(|root| {
+                let node = root.get_node_mut(&dir)?;
+                if !node.is_dir {
+                    return None;
+                }
+                node.expanded = true;
+                let read = node.read;
+                root.update_node_count_recursive(&dir);
+                Some(read)
+            });
+            match read.flatten() {
+                Some(true) => {}
+                Some(false) => {
+                    // Carry on from there once its children are known
+                    let data = self.clone();
+                    let path = path.to_path_buf();
+                    self.read_dir_then(&dir, move || data.reveal_path(&path));
+                    return;
+                }
+                None => return,
+            }
+        }
+        self.id.update(|id| {
+            *id += 1;
+        });
+        let line = self.root.with_untracked(|root| root.line_of(path));
+        if line.is_some() {
+            self.scroll_to_line.set(line);
+        }
+    }
+
+    /// Reveal `path` once it's been the active file for [`FOLLOW_DELAY`],
+    /// unless another one has become active since.
+    pub fn follow(&self, path: PathBuf) {
+        let data = self.clone();
+        let timer = self.follow_timer;
+        let token = exec_after(FOLLOW_DELAY, move |token| {
+            if timer.try_get_untracked() == Some(token) {
+                data.reveal_path(&path);
+            }
+        });
+        timer.set(token);
+    }
+
     pub fn read_dir(&self, path: &Path) {
+        self.read_dir_then(path, || {});
+    }
+
+    /// Read the children of the directory `path`, and run `done` once they're
+    /// in the tree.
+    fn read_dir_then(&self, path: &Path, done: impl FnOnce() + 'static) {
         let root = self.root;
         let id = self.id;
         let data = self.clone();
@@ -117,6 +209,7 @@ pub fn read_dir(&self, path: &Path) {
                                             children_open_count: 0,
                                             internal_command: node.internal_command,
                                             line_height: node.line_height,
+                                            natural_sort: node.natural_sort,
                                         },
                                     );
                                 }
@@ -124,6 +217,7 @@ pub fn read_dir(&self, path: &Path) {
                         }
                         root.update_node_count_recursive(&path);
                     });
+                    done();
                 }
             })
         };
@@ -165,6 +259,351 @@ pub fn double_click(&self, path: &Path) -> bool {
         }
     }
 
+    pub fn secondary_click(&self, path: &Path) {
+        let is_dir = self
+            .root
+            .with_untracked(|root| root.get_node(path).map(|n| n.is_dir))
+            .unwrap_or(false);
+        let is_root = self.root.with_untracked(|root| root.path == path);
+        // The names are asked for with the native dialog, which can only see
+        // this machine's files
+        let is_local = self.common.workspace.kind.is_local();
+        let dir = if is_dir {
+            path.to_path_buf()
+        } else {
+            path.parent().unwrap_or(path).to_path_buf()
+        };
+
+        let mut menu = Menu::new("");
+        let data = self.clone();
+        let new_file_dir = dir.clone();
+        menu = menu.entry(
+            MenuItem::new("New File...")
+                .enabled(is_local)
+                .action(move || data.new_file(&new_file_dir)),
+        );
+        let data = self.clone();
+        menu = menu.entry(
+            MenuItem::new("New Folder...")
+                .enabled(is_local)
+                .action(move || data.new_folder(&dir)),
+        );
+        menu = menu.separator();
+        let data = self.clone();
+        let rename_path = path.to_path_buf();
+        menu = menu.entry(
+            MenuItem::new("Rename...")
+                .enabled(is_local && !is_root)
+                .action(move || data.rename(&rename_path)),
+        );
+        let data = self.clone();
+        let delete_path = path.to_path_buf();
+        menu = menu.entry(
+            MenuItem::new("Delete")
+                .enabled(!is_root)
+                .action(move || data.delete(&delete_path)),
+        );
+        show_context_menu(menu, None);
+    }
+
+    fn new_file(&self, dir: &Path) {
+        let data = self.clone();
+        ask_for_path(dir, "", move |path| {
+            let internal_command = data.common.internal_command;
+            let config = data.common.config;
+            let opened = path.clone();
+            let done = data.on_done("Couldn't create the file", move || {
+                if config.get_untracked().ui.file_explorer_open_created {
+                    internal_command
+                        .send(InternalCommand::OpenFile { path: opened });
+                }
+            });
+            data.common.proxy.create_file(path, done);
+        });
+    }
+
+    fn new_folder(&self, dir: &Path) {
+        let data = self.clone();
+        ask_for_path(dir, "", move |path| {
+            let done = data.on_done("Couldn't create the folder", || {});
+            data.common.proxy.create_directory(path, done);
+        });
+    }
+
+    /// Ask for the path to rename `path` to, which can be in another folder,
+    /// and rename it.
+    pub fn rename(&self, path: &Path) {
+        let Some(dir) = path.parent() else {
+            return;
+        };
+        let name = file_name(path);
+        let data = self.clone();
+        let from = path.to_path_buf();
+        ask_for_path(dir, &name, move |to| {
+            if to == from {
+                return;
+            }
+            data.rename_to(from.clone(), to);
+        });
+    }
+
+    /// Rename `from` to `to` along with the edits the language servers make
+    /// for it, like the fixes of the imports of a moved module.
+    fn rename_to(&self, from: PathBuf, to: PathBuf) {
+        let data = self.clone();
+        let (asked_from, asked_to) = (from.clone(), to.clone());
+        let send = create_ext_action(
+            self.common.scope,
+            move |result: Result<ProxyResponse, RpcError>| {
+                let edits = match result {
+                    Ok(ProxyResponse::WillRenameFilesResponse { edits }) => edits,
+                    _ => Vec::new(),
+                };
+                data.rename_with_edits(from, to, edits, false);
+            },
+        );
+        self.common
+            .proxy
+            .will_rename_files(asked_from, asked_to, move |result| send(result));
+    }
+
+    /// Rename `from` to `to`, asking whether to replace `to` when it exists
+    /// unless `overwrite`, and apply the `edits` once it's done.
+    fn rename_with_edits(
+        &self,
+        from: PathBuf,
+        to: PathBuf,
+        edits: Vec<WorkspaceEdit>,
+        overwrite: bool,
+    ) {
+        let data = self.clone();
+        let internal_command = self.common.internal_command;
+        let (renamed_from, renamed_to) = (from.clone(), to.clone());
+        let send = create_ext_action(
+            self.common.scope,
+            move |result: Result<ProxyResponse, RpcError>| match result {
+                Ok(_) => {
+                    data.root.update(|root| root.move_node(&from, &to));
+                    data.id.update(|id| *id += 1);
+                    internal_command.send(InternalCommand::PathRenamed {
+                        from,
+                        to,
+                        edits,
+                    });
+                }
+                Err(err) if err.code == RpcError::ALREADY_EXISTS => {
+                    data.confirm_overwrite(from, to, edits);
+                }
+                Err(err) => {
+                    internal_command.send(InternalCommand::ShowAlert {
+                        title: "Couldn't rename".to_string(),
+                        msg: err.message,
+                        buttons: Vec::new(),
+                    });
+                }
+            },
+        );
+        self.common.proxy.rename_path(
+            renamed_from,
+            renamed_to,
+            overwrite,
+            move |result| send(result),
+        );
+    }
+
+    /// Ask whether to replace `to` with `from`, since there's one already.
+    fn confirm_overwrite(
+        &self,
+        from: PathBuf,
+        to: PathBuf,
+        edits: Vec<WorkspaceEdit>,
+    ) {
+        let data = self.clone();
+        let internal_command = self.common.internal_command;
+        internal_command.send(InternalCommand::ShowAlert {
+            title: format!("{} already exists", file_name(&to)),
+            msg: format!("Do you want to replace it with {}?", file_name(&from)),
+            buttons: vec![AlertButton {
+                text: "Replace".to_string(),
+                action: Rc::new(move || {
+                    internal_command.send(InternalCommand::HideAlert);
+                    data.rename_with_edits(
+                        from.clone(),
+                        to.clone(),
+                        edits.clone(),
+                        true,
+                    );
+                }),
+            }],
+        });
+    }
+
+    /// Delete `path` once the user confirms it, telling how many files go
+    /// with it when it's a folder.
+    fn delete(&self, path: &Path) {
+        let is_dir = self
+            .root
+            .with_untracked(|root| root.get_node(path).map(|n| n.is_dir))
+            .unwrap_or(false);
+        if !is_dir {
+            self.confirm_delete(path.to_path_buf(), None);
+            return;
+        }
+        let data = self.clone();
+        let path = path.to_path_buf();
+        let counted = path.clone();
+        let send = create_ext_action(
+            self.common.scope,
+            move |result: Result<ProxyResponse, RpcError>| {
+                let files = match result {
+                    Ok(ProxyResponse::CountFilesResponse { count }) => Some(count),
+                    _ => None,
+                };
+                data.confirm_delete(path, files);
+            },
+        );
+        self.common
+            .proxy
+            .count_files(counted, move |result| send(result));
+    }
+
+    /// Ask whether to move `path`, with the `files` in it, to the trash, or to
+    /// delete it for good when the trash isn't used.
+    fn confirm_delete(&self, path: PathBuf, files: Option<usize>) {
+        if !self.common.config.get_untracked().files.delete_to_trash {
+            self.confirm_permanent_delete(path, files, None);
+            return;
+        }
+        let data = self.clone();
+        let internal_command = self.common.internal_command;
+        internal_command.send(InternalCommand::ShowAlert {
+            title: format!("Do you want to delete {}?", file_name(&path)),
+            msg: format!("It will be moved to the trash{}.", with_files(files)),
+            buttons: vec![AlertButton {
+                text: "Move to Trash".to_string(),
+                action: Rc::new(move || {
+                    internal_command.send(InternalCommand::HideAlert);
+                    data.trash(path.clone(), files);
+                }),
+            }],
+        });
+    }
+
+    /// Ask whether to delete `path` for good, saying why it isn't moved to the
+    /// trash when that failed.
+    fn confirm_permanent_delete(
+        &self,
+        path: PathBuf,
+        files: Option<usize>,
+        trash_error: Option<String>,
+    ) {
+        let name = file_name(&path);
+        let title = match trash_error.as_ref() {
+            Some(_) => format!("{name} couldn't be moved to the trash"),
+            None => format!("Do you want to delete {name} for good?"),
+        };
+        let msg = format!(
+            "{}It will be deleted for good{}, and can't be restored.",
+            trash_error
+                .map(|err| format!("{err}. "))
+                .unwrap_or_default(),
+            with_files(files),
+        );
+        let data = self.clone();
+        let internal_command = self.common.internal_command;
+        internal_command.send(InternalCommand::ShowAlert {
+            title,
+            msg,
+            buttons: vec![AlertButton {
+                text: "Delete Permanently".to_string(),
+                action: Rc::new(move || {
+                    internal_command.send(InternalCommand::HideAlert);
+                    let deleted = data.clone();
+                    let path = path.clone();
+                    let done = data.on_done("Couldn't delete", move || {
+                        deleted.deleted(&path, files, false);
+                    });
+                    data.common.proxy.delete_path(path.clone(), done);
+                }),
+            }],
+        });
+    }
+
+    /// Move `path` to the trash, offering to delete it for good if it can't
+    /// be, like on some network drives.
+    fn trash(&self, path: PathBuf, files: Option<usize>) {
+        let data = self.clone();
+        let trashed = path.clone();
+        let send = create_ext_action(
+            self.common.scope,
+            move |result: Result<ProxyResponse, RpcError>| match result {
+                Ok(_) => {
+                    data.reload();
+                    data.deleted(&path, files, true);
+                }
+                Err(err) => {
+                    data.confirm_permanent_delete(path, files, Some(err.message));
+                }
+            },
+        );
+        self.common
+            .proxy
+            .trash_path(trashed, move |result| send(result));
+    }
+
+    /// Tell that `path` was deleted, in one message with the files in it, and
+    /// where it can be restored from when it went to the trash.
+    fn deleted(&self, path: &Path, files: Option<usize>, trashed: bool) {
+        let name = file_name(path);
+        let message = if trashed {
+            let hint = RESTORE_HINT
+                .filter(|_| self.common.workspace.kind.is_local())
+                .map(|hint| format!(" {hint}"))
+                .unwrap_or_default();
+            format!("Moved {name}{} to the trash.{hint}", with_files(files))
+        } else {
+            format!("Deleted {name}{} for good.", with_files(files))
+        };
+        self.common
+            .internal_command
+            .send(InternalCommand::ShowMessage {
+                title: "Delete".to_string(),
+                message: ShowMessageParams {
+                    typ: MessageType::INFO,
+                    message,
+                },
+            });
+    }
+
+    /// A proxy callback which refreshes the tree and runs `action` when the
+    /// request worked, or tells the user why it didn't.
+    fn on_done(
+        &self,
+        failed: &str,
+        action: impl FnOnce() + 'static,
+    ) -> impl FnOnce(Result<ProxyResponse, RpcError>) + Send {
+        let data = self.clone();
+        let internal_command = self.common.internal_command;
+        let failed = failed.to_string();
+        let send = create_ext_action(
+            self.common.scope,
+            move |result: Result<ProxyResponse, RpcError>| match result {
+                Ok(_) => {
+                    data.reload();
+                    action();
+                }
+                Err(err) => {
+                    internal_command.send(InternalCommand::ShowAlert {
+                        title: failed,
+                        msg: err.message,
+                        buttons: Vec::new(),
+                    });
+                }
+            },
+        );
+        move |result| send(result)
+    }
+
     pub fn middle_click(&self, path: &Path) -> bool {
         let is_dir = self
             .root
@@ -182,3 +621,30 @@ pub fn middle_click(&self, path: &Path) -> bool {
         }
     }
 }
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// The files that go along with a deleted folder, if there are any.
+fn with_files(files: Option<usize>) -> String {
+    match files {
+        None | Some(0) => String::new(),
+        Some(1) => ", with the file in it".to_string(),
+        Some(files) => format!(", with the {files} files in it"),
+    }
+}
+
+/// Ask for a path in `dir` with the save dialog, suggesting `name`.
+fn ask_for_path(dir: &Path, name: &str, f: impl Fn(PathBuf) + 'static) {
+    let options = FileDialogOptions::new()
+        .force_starting_directory(dir)
+        .default_name(name);
+    save_as(options, move |file: Option<FileInfo>| {
+        if let Some(file) = file {
+            f(file.path);
+        }
+    });
+}
@@ -5,6 +5,7 @@
 
 use floem::{reactive::Memo, views::VirtualListVector};
 use itertools::Itertools;
+use lapce_rpc::file::compare_file_names;
 
 use crate::{command::InternalCommand, listener::Listener};
 
@@ -17,6 +18,10 @@ pub struct FileNode {
     pub children: HashMap<PathBuf, FileNode>,
     pub children_open_count: usize,
     pub line_height: Memo<f64>,
+    /// Whether the children are sorted naturally, see [`natural_cmp`]
+    ///
+    /// [`natural_cmp`]: lapce_rpc::file::natural_cmp
+    pub natural_sort: Memo<bool>,
     pub internal_command: Listener<InternalCommand>,
 }
 
@@ -53,6 +58,76 @@ pub fn get_node_mut(&mut self, path: &Path) -> Option<&mut FileNode> {
         Some(node)
     }
 
+    /// Move the node at `from`, with the ones under it, to `to`, when the
+    /// directory it goes in has been read. The directories it leaves and
+    /// goes to are kept as they are, rather than read again.
+    pub fn move_node(&mut self, from: &Path, to: &Path) {
+        let node = from
+            .parent()
+            .and_then(|parent| self.get_node_mut(parent))
+            .and_then(|parent| parent.children.remove(from));
+        if let (Some(mut node), Some(parent)) = (
+            node,
+            to.parent().and_then(|parent| self.get_node_mut(parent)),
+        ) {
+            if parent.read {
+                node.set_path(to);
+                parent.children.insert(to.to_path_buf(), node);
+            }
+        }
+        for path in [from, to] {
+            if let Some(parent) = path.parent() {
+                self.update_node_count_recursive(parent);
+            }
+        }
+    }
+
+    fn set_path(&mut self, path: &Path) {
+        let children = std::mem::take(&mut self.children);
+        self.children = children
+            .into_values()
+            .map(|mut child| {
+                let child_path =
+                    path.join(child.path.file_name().unwrap_or_default());
+                child.set_path(&child_path);
+                (child_path, child)
+            })
+            .collect();
+        self.path = path.to_path_buf();
+    }
+
+    /// The children as they're listed, the directories first.
+    fn sorted_children(
+        &self,
+        natural: bool,
+    ) -> impl Iterator<Item = (&PathBuf, &FileNode)> {
+        self.children.iter().sorted_by(move |(_, a), (_, b)| {
+            b.is_dir
+                .cmp(&a.is_dir)
+                .then_with(|| compare_file_names(&a.path, &b.path, natural))
+        })
+    }
+
+    /// The line `path` is listed on under this node, which isn't listed
+    /// itself, or `None` if it's hidden in a collapsed directory.
+    pub fn line_of(&self, path: &Path) -> Option<usize> {
+        let mut line = 0;
+        let natural = self.natural_sort.get_untracked();
+        for (_, child) in self.sorted_children(natural) {
+            if child.path == path {
+                return Some(line);
+            }
+            if child.is_dir && path.starts_with(&child.path) {
+                if !child.expanded {
+                    return None;
+                }
+                return child.line_of(path).map(|l| line + 1 + l);
+            }
+            line += child.children_open_count + 1;
+        }
+        None
+    }
+
     pub fn update_node_count_recursive(&mut self, path: &Path) {
         for current_path in path.ancestors() {
             self.update_node_count(current_path);
@@ -90,17 +165,13 @@ fn total_len(&self) -> usize {
     }
 
     fn slice(&mut self, _range: std::ops::Range<usize>) -> Self::ItemIterator {
-        let children = if !self.is_dir {
-            HashMap::new()
-        } else if self.expanded {
-            self.children.clone()
+        let children = if self.is_dir && self.expanded {
+            self.sorted_children(self.natural_sort.get())
+                .map(|(path, node)| (path.clone(), node.clone()))
+                .collect()
         } else {
-            HashMap::new()
+            Vec::new()
         };
-        Box::new(
-            children
-                .into_iter()
-                .sorted_by_key(|(_, n)| (!n.is_dir, n.path.to_path_buf())),
-        )
+        Box::new(children.into_iter())
     }
 }
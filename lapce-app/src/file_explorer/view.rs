@@ -3,8 +3,8 @@
 use floem::{
     cosmic_text::Style as FontStyle,
     event::{Event, EventListener},
-    peniko::Color,
-    reactive::{create_rw_signal, ReadSignal, RwSignal},
+    peniko::{kurbo::Rect, Color},
+    reactive::{create_memo, create_rw_signal, Memo, ReadSignal, RwSignal},
     style::{CursorStyle, Style},
     view::View,
     views::{
@@ -19,6 +19,7 @@
     app::clickable_icon,
     command::InternalCommand,
     config::{color::LapceColor, icon::LapceIcons, LapceConfig},
+    doc::DocContent,
     editor_tab::{EditorTabChild, EditorTabData},
     panel::{position::PanelPosition, view::panel_header},
     window_tab::WindowTabData,
@@ -31,7 +32,18 @@ pub fn file_explorer_panel(
     let config = window_tab_data.common.config;
     let data = window_tab_data.file_explorer.clone();
     let root_file_node = window_tab_data.file_explorer.root;
+    let scroll_to_line = window_tab_data.file_explorer.scroll_to_line;
+    let line_height = window_tab_data.common.ui_line_height;
     let proxy = window_tab_data.common.proxy.clone();
+    let main_split = window_tab_data.main_split.clone();
+    let active_path = create_memo(move |_| {
+        let editor = main_split.active_editor.get()?;
+        let doc = editor.with(|editor| editor.view.doc);
+        doc.with(|doc| match &doc.content {
+            DocContent::File(path) => Some(path.clone()),
+            _ => None,
+        })
+    });
     stack(|| {
         (
             stack(move || {
@@ -52,9 +64,20 @@ pub fn file_explorer_panel(
                                 data,
                                 proxy.clone(),
                                 0,
+                                active_path,
                                 config,
                             )
                         })
+                        .on_ensure_visible(move || {
+                            let line = scroll_to_line.get().unwrap_or(0);
+                            let line_height = line_height.get_untracked();
+                            Rect::new(
+                                0.0,
+                                line as f64 * line_height,
+                                0.0,
+                                (line + 1) as f64 * line_height,
+                            )
+                        })
                         .style(|s| s.absolute().size_pct(100.0, 100.0))
                     })
                     .style(|s| s.size_pct(100.0, 100.0).line_height(1.6)),
@@ -74,6 +97,7 @@ fn file_node_view(
     data: FileExplorerData,
     proxy: ProxyRpcHandler,
     level: usize,
+    active_path: Memo<Option<PathBuf>>,
     config: ReadSignal<Arc<LapceConfig>>,
 ) -> impl View {
     let id = data.id;
@@ -97,6 +121,7 @@ fn file_node_view(
                         let double_click_data = data.clone();
                         let aux_click_data = data.clone();
                         let aux_click_path = path.clone();
+                        let active_style_path = path.clone();
                         let click_path = file_node.path.clone();
                         let double_click_path = path.clone();
                         stack(move || {
@@ -184,6 +209,8 @@ fn file_node_view(
                             if let Event::PointerDown(pointer_event) = event {
                                 if pointer_event.button.is_auxiliary() {
                                     aux_click_data.middle_click(&aux_click_path);
+                                } else if pointer_event.button.is_secondary() {
+                                    aux_click_data.secondary_click(&aux_click_path);
                                 }
                             }
                             true
@@ -193,6 +220,17 @@ fn file_node_view(
                                 .padding_right_px(10.0)
                                 .padding_left_px((level * 10) as f32)
                                 .min_width_pct(100.0)
+                                .apply_if(
+                                    active_path.with(|active_path| {
+                                        active_path.as_ref()
+                                            == Some(&active_style_path)
+                                    }),
+                                    |s| {
+                                        s.background(*config.get().get_color(
+                                            LapceColor::PANEL_CURRENT_BACKGROUND,
+                                        ))
+                                    },
+                                )
                         })
                         .hover_style(move |s| {
                             s.background(
@@ -210,6 +248,7 @@ fn file_node_view(
                             data.clone(),
                             proxy.clone(),
                             level + 1,
+                            active_path,
                             config,
                         ))
                     }),
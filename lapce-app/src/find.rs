@@ -1,6 +1,9 @@
-use std::cmp::{max, min};
+use std::{
+    cmp::{max, min},
+    sync::Arc,
+};
 
-use floem::reactive::{RwSignal, Scope};
+use floem::reactive::{use_context, RwSignal, Scope};
 use lapce_core::{
     selection::{SelRegion, Selection},
     word::WordCursor,
@@ -12,8 +15,24 @@
 use regex::{Regex, RegexBuilder};
 use serde::{Deserialize, Serialize};
 
+use self::history::SearchHistory;
+use crate::db::LapceDb;
+
+pub mod history;
+
 const REGEX_SIZE_LIMIT: usize = 1000000;
 
+/// How many matches are counted before the find bar shows the count as this
+/// many and more, until the rest of them are counted
+pub const MAX_FIND_COUNT: usize = 10000;
+
+/// The most matches that are highlighted in a frame
+pub const MAX_FIND_HIGHLIGHTS: usize = 5000;
+
+/// How many lines above and below the ones on screen are searched for the
+/// matches to highlight, for the ones that run onto the screen
+pub const FIND_MARGIN_LINES: usize = 5;
+
 /// Indicates what changed in the find state.
 #[derive(PartialEq, Debug, Clone)]
 pub enum FindProgress {
@@ -70,10 +89,14 @@ pub struct Find {
     pub whole_words: RwSignal<bool>,
     /// The search query should be considered as regular expression.
     pub is_regex: RwSignal<bool>,
+    /// The replacement takes the case of each match it replaces.
+    pub preserve_case: RwSignal<bool>,
     /// replace editor is shown
     pub replace_active: RwSignal<bool>,
     /// replace editor is focused
     pub replace_focus: RwSignal<bool>,
+    /// The queries searched for, shared by all the buffers
+    pub history: RwSignal<SearchHistory>,
 }
 
 impl Find {
@@ -85,8 +108,13 @@ pub fn new(cx: Scope) -> Self {
             case_matching: cx.create_rw_signal(CaseMatching::CaseInsensitive),
             whole_words: cx.create_rw_signal(false),
             is_regex: cx.create_rw_signal(false),
+            preserve_case: cx.create_rw_signal(false),
             replace_active: cx.create_rw_signal(false),
             replace_focus: cx.create_rw_signal(false),
+            history: cx.create_rw_signal({
+                let db: Arc<LapceDb> = use_context().unwrap();
+                db.get_search_history().unwrap_or_default()
+            }),
         };
 
         {
@@ -147,6 +175,29 @@ pub fn set_case_sensitive(&self, case_sensitive: bool) {
         self.case_matching.set(case_matching);
     }
 
+    /// The current search query, if there's one.
+    pub fn query(&self) -> Option<String> {
+        self.search_string
+            .with_untracked(|s| s.as_ref().map(|s| s.content.clone()))
+    }
+
+    /// Remember the current search query in the history, to be recalled in
+    /// the search input and searched for again after a restart.
+    pub fn record_search(&self) {
+        let Some(query) = self.query() else {
+            return;
+        };
+        if self
+            .history
+            .with_untracked(|h| h.last() == Some(query.as_str()))
+        {
+            return;
+        }
+        self.history.update(|history| history.push(&query));
+        let db: Arc<LapceDb> = use_context().unwrap();
+        db.save_search_history(self.history.get_untracked());
+    }
+
     pub fn set_find(&self, search_string: &str) {
         if search_string.is_empty() {
             self.search_string.set(None);
@@ -252,7 +303,9 @@ pub fn next(
                 } else {
                     let mut raw_lines = text.lines_raw(0..offset);
                     let mut find_cursor = Cursor::new(text, 0);
-                    let mut regions = Vec::new();
+                    // Only the last match before the offset is kept, rather
+                    // than all of them
+                    let mut last = None;
                     while let Some(start) = find(
                         &mut find_cursor,
                         &mut raw_lines,
@@ -268,16 +321,16 @@ pub fn next(
                             continue;
                         }
                         if start < offset {
-                            regions.push((start, end));
+                            last = Some((start, end));
                         }
                     }
-                    if !regions.is_empty() {
-                        return Some(regions[regions.len() - 1]);
+                    if last.is_some() {
+                        return last;
                     }
                     if wrap {
                         let mut raw_lines = text.lines_raw(offset..text.len());
                         let mut find_cursor = Cursor::new(text, offset);
-                        let mut regions = Vec::new();
+                        let mut last = None;
                         while let Some(start) = find(
                             &mut find_cursor,
                             &mut raw_lines,
@@ -298,11 +351,11 @@ pub fn next(
                                 text.lines_raw(find_cursor.pos()..text.len());
 
                             if start > offset {
-                                regions.push((start, end));
+                                last = Some((start, end));
                             }
                         }
-                        if !regions.is_empty() {
-                            return Some(regions[regions.len() - 1]);
+                        if last.is_some() {
+                            return last;
                         }
                     }
                 }
@@ -312,7 +365,7 @@ pub fn next(
     }
 
     /// Checks if the start and end of a match is matching whole words.
-    fn is_matching_whole_words(text: &Rope, start: usize, end: usize) -> bool {
+    pub fn is_matching_whole_words(text: &Rope, start: usize, end: usize) -> bool {
         let mut word_end_cursor = WordCursor::new(text, end - 1);
         let mut word_start_cursor = WordCursor::new(text, start + 1);
 
@@ -347,6 +400,7 @@ pub fn find(
         case_matching: CaseMatching,
         whole_words: bool,
         include_slop: bool,
+        limit: Option<usize>,
         occurrences: &mut Selection,
     ) {
         let search_string = &search.content;
@@ -415,11 +469,44 @@ pub fn find(
                 }
             }
 
+            if limit.map_or(false, |limit| occurrences.len() >= limit) {
+                break;
+            }
+
             // update line iterator so that line starts at current cursor position
             raw_lines = text.lines_raw(find_cursor.pos()..to);
         }
     }
 
+    /// The matches of `search` to highlight between `start` and `end`, the
+    /// part of the text on screen, which are [`MAX_FIND_HIGHLIGHTS`] at most
+    /// however many matches the whole text has.
+    pub fn visible_occurrences(
+        text: &Rope,
+        search: &FindSearchString,
+        start: usize,
+        end: usize,
+        case_matching: CaseMatching,
+        whole_words: bool,
+    ) -> Selection {
+        let mut occurrences = Selection::new();
+        if search.content.is_empty() {
+            return occurrences;
+        }
+        Self::find(
+            text,
+            search,
+            start,
+            end,
+            case_matching,
+            whole_words,
+            true,
+            Some(MAX_FIND_HIGHLIGHTS),
+            &mut occurrences,
+        );
+        occurrences
+    }
+
     /// Execute the search on the provided text in the range provided by `start` and `end`.
     pub fn update_find(
         &self,
@@ -537,3 +624,53 @@ pub fn reset(&self) {
         self.progress.set(FindProgress::Started);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use lapce_xi_rope::{find::CaseMatching, Rope};
+
+    use super::{Find, FindSearchString, MAX_FIND_HIGHLIGHTS};
+
+    #[test]
+    fn test_visible_occurrences_bounded() {
+        let search = FindSearchString {
+            content: "e".to_string(),
+            regex: None,
+        };
+        let visible = |text: &Rope, start: usize, end: usize| {
+            let now = Instant::now();
+            let occurrences = Find::visible_occurrences(
+                text,
+                &search,
+                start,
+                end,
+                CaseMatching::CaseInsensitive,
+                false,
+            );
+            let elapsed = now.elapsed();
+            assert!(elapsed < Duration::from_millis(500), "took {elapsed:?}");
+            occurrences
+        };
+
+        // Megabytes of text where nearly every character matches
+        let line = format!("xx{}xx\n", "e".repeat(96));
+        let text = Rope::from(line.repeat(50_000));
+        let start = text.offset_of_line(25_000);
+        let end = text.offset_of_line(25_005);
+        let occurrences = visible(&text, start, end);
+        assert_eq!(occurrences.len(), 5 * 96);
+        assert!(occurrences
+            .regions()
+            .iter()
+            .all(|region| region.min() >= start && region.max() <= end));
+
+        let end = text.offset_of_line(25_100);
+        assert_eq!(visible(&text, start, end).len(), MAX_FIND_HIGHLIGHTS);
+
+        // A minified file, all on one line
+        let text = Rope::from("e".repeat(5_000_000));
+        assert_eq!(visible(&text, 0, text.len()).len(), MAX_FIND_HIGHLIGHTS);
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+/// How many of the latest search queries are remembered across sessions
+pub const MAX_SEARCH_HISTORY: usize = 12;
+
+/// The queries searched for in the buffers, the latest first, and where
+/// recalling them in the search input is at
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SearchHistory {
+    entries: VecDeque<String>,
+    /// The entry the search input was last set to, if it's being recalled
+    #[serde(skip)]
+    position: Option<usize>,
+    /// What was in the search input before recalling started, to go back to
+    #[serde(skip)]
+    draft: String,
+}
+
+impl SearchHistory {
+    /// Remember `query` as the latest, moving it to the front if it was
+    /// searched for before, and forgetting the oldest past
+    /// [`MAX_SEARCH_HISTORY`].
+    pub fn push(&mut self, query: &str) {
+        self.position = None;
+        if query.is_empty() {
+            return;
+        }
+        self.entries.retain(|entry| entry != query);
+        self.entries.push_front(query.to_string());
+        self.entries.truncate(MAX_SEARCH_HISTORY);
+    }
+
+    pub fn last(&self) -> Option<&str> {
+        self.entries.front().map(|s| s.as_str())
+    }
+
+    /// The queries, the latest first.
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|s| s.as_str())
+    }
+
+    /// Whether recalling goes on from the entry the input was set to, rather
+    /// than starting over because it was edited since.
+    fn is_recalling(&self, current: &str) -> bool {
+        self.position
+            .and_then(|position| self.entries.get(position))
+            .map(|entry| entry == current)
+            .unwrap_or(false)
+    }
+
+    /// The query searched for before the one in the search input, `current`,
+    /// or `None` when there's none older.
+    pub fn older(&mut self, current: &str) -> Option<String> {
+        if !self.is_recalling(current) {
+            self.position = None;
+            self.draft = current.to_string();
+        }
+        let position = self.position.map(|p| p + 1).unwrap_or(0);
+        let entry = self.entries.get(position)?.clone();
+        self.position = Some(position);
+        Some(entry)
+    }
+
+    /// The query searched for after the one in the search input, `current`,
+    /// going back to what was typed before recalling past the latest.
+    pub fn newer(&mut self, current: &str) -> Option<String> {
+        if !self.is_recalling(current) {
+            self.position = None;
+            return None;
+        }
+        match self.position? {
+            0 => {
+                self.position = None;
+                Some(std::mem::take(&mut self.draft))
+            }
+            position => {
+                self.position = Some(position - 1);
+                self.entries.get(position - 1).cloned()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SearchHistory, MAX_SEARCH_HISTORY};
+
+    #[test]
+    fn test_search_history() {
+        let mut history = SearchHistory::default();
+        history.push("foo");
+        history.push("bar");
+        history.push("");
+        // Searched for again, it moves to the front
+        history.push("foo");
+        assert_eq!(history.iter().collect::<Vec<_>>(), vec!["foo", "bar"]);
+
+        for i in 0..MAX_SEARCH_HISTORY + 3 {
+            history.push(&i.to_string());
+        }
+        assert_eq!(history.iter().count(), MAX_SEARCH_HISTORY);
+        assert_eq!(history.last(), Some("14"));
+    }
+
+    #[test]
+    fn test_search_history_recall() {
+        let mut history = SearchHistory::default();
+        history.push("foo");
+        history.push("bar");
+
+        assert_eq!(history.newer("ba").as_deref(), None);
+        assert_eq!(history.older("ba").as_deref(), Some("bar"));
+        assert_eq!(history.older("bar").as_deref(), Some("foo"));
+        assert_eq!(history.older("foo").as_deref(), None);
+        assert_eq!(history.newer("foo").as_deref(), Some("bar"));
+        // Past the latest, back to what was typed
+        assert_eq!(history.newer("bar").as_deref(), Some("ba"));
+        assert_eq!(history.newer("ba").as_deref(), None);
+
+        // Edited while recalling, it starts over from the latest
+        assert_eq!(history.older("").as_deref(), Some("bar"));
+        assert_eq!(history.older("bar!").as_deref(), Some("bar"));
+        assert_eq!(history.newer("bar").as_deref(), Some("bar!"));
+    }
+}
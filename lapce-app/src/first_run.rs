@@ -0,0 +1,187 @@
+//! Setting up the config directory the first time Lapce is run on a machine,
+//! and the welcome shown then.
+//!
+//! Lapce knows it was run before from a marker file in the config directory,
+//! which is written once the directory is set up. When the directory can't be
+//! created or written to, Lapce runs on the default settings and says so once,
+//! rather than failing each time it saves something.
+
+use std::{io::Write, path::Path};
+
+use lapce_core::directory::Directory;
+
+use crate::config::{DEFAULT_DARK_THEME, DEFAULT_SETTINGS, DEFAULT_USER_KEYMAPS};
+
+/// The file in the config directory whose being there means it's set up
+const MARKER: &str = "first-run-done";
+
+/// The commands the welcome lists, with what they do
+const WELCOME_COMMANDS: &[(&str, &str)] = &[
+    ("palette.command", "Run any command by its name"),
+    ("palette", "Go to a file"),
+    ("new_file", "Open a new untitled file"),
+    ("open_folder", "Open a folder"),
+    ("palette.workspace", "Open a recent workspace"),
+    ("toggle_terminal_focus", "Show the terminal"),
+    ("open_settings", "Change the settings"),
+    ("open_keyboard_shortcuts", "Change the key bindings"),
+];
+
+/// The state of the config directory once it's been set up
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigDirectory {
+    /// It can be written to, and it was only just set up if `first_run`
+    Ready { first_run: bool },
+    /// It couldn't be set up, for the reason given, so nothing is saved
+    Unavailable(String),
+}
+
+/// Set up the config directory if it wasn't already, with the user's settings,
+/// keymaps and a theme to start from, all commented out so that they don't
+/// change anything until they're edited.
+pub fn setup_config_directory() -> ConfigDirectory {
+    let Some(dir) = Directory::config_directory() else {
+        return ConfigDirectory::Unavailable(
+            "There's no home folder to keep the settings in.".to_string(),
+        );
+    };
+    if dir.join(MARKER).exists() {
+        return ConfigDirectory::Ready { first_run: false };
+    }
+    match write_defaults(&dir) {
+        Ok(()) => ConfigDirectory::Ready { first_run: true },
+        Err(err) => ConfigDirectory::Unavailable(format!(
+            "The settings folder {} can't be written to: {err}",
+            dir.display()
+        )),
+    }
+}
+
+fn write_defaults(dir: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    write_new(
+        &dir.join("settings.toml"),
+        &commented(
+            "Your settings, which are loaded on top of the default ones below \
+             and reloaded when this file is saved. Uncomment a setting to \
+             change it.",
+            DEFAULT_SETTINGS,
+        ),
+    )?;
+    write_new(&dir.join("keymaps.toml"), DEFAULT_USER_KEYMAPS)?;
+    if let Some(themes) = Directory::themes_directory() {
+        write_new(
+            &themes.join("my-theme.toml"),
+            &commented(
+                "A color theme to make your own, which is the default dark \
+                 one. Uncomment it and give it a name of its own to choose \
+                 it in the settings.",
+                DEFAULT_DARK_THEME,
+            ),
+        )?;
+    }
+    std::fs::write(dir.join(MARKER), "")
+}
+
+/// Write `content` to a new file at `path`, unless there's a file there.
+fn write_new(path: &Path, content: &str) -> std::io::Result<()> {
+    match std::fs::OpenOptions::new()
+        .create_new(true)
+        .write(true)
+        .open(path)
+    {
+        Ok(mut file) => file.write_all(content.as_bytes()),
+        Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+/// The toml `content` with all of its lines commented out, under `header`.
+/// The schema line is left out, as it points to the schema in Lapce's source.
+pub fn commented(header: &str, content: &str) -> String {
+    let mut text = String::new();
+    for line in wrap_words(header, 78) {
+        text.push_str("# ");
+        text.push_str(&line);
+        text.push('\n');
+    }
+    let lines = content
+        .lines()
+        .filter(|line| !line.starts_with("#:schema"))
+        .skip_while(|line| line.is_empty());
+    for line in lines {
+        text.push('\n');
+        if !line.is_empty() && !line.starts_with('#') {
+            text.push_str("# ");
+        }
+        text.push_str(line);
+    }
+    text.push('\n');
+    text
+}
+
+/// `text` put on lines of at most `width` characters, broken between words.
+fn wrap_words(text: &str, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        match lines.last_mut() {
+            Some(line) if line.len() + 1 + word.len() <= width => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_string()),
+        }
+    }
+    lines
+}
+
+/// The text of the welcome, with the keys `key_of` gives for its commands.
+pub fn welcome_text(key_of: impl Fn(&str) -> Option<String>) -> String {
+    let mut text = String::from(
+        "# Welcome to Lapce\n\n\
+         Everything Lapce does is a command, which can be run from the \
+         command palette by its name. These are the ones to start with:\n\n",
+    );
+    for (command, desc) in WELCOME_COMMANDS {
+        let key = key_of(command)
+            .map(|key| format!("`{key}`"))
+            .unwrap_or_else(|| "the command palette".to_string());
+        text.push_str(&format!("- {desc}: {key}\n"));
+    }
+    text.push_str(
+        "\nThe settings, key bindings and a color theme of your own are in \
+         the settings folder, commented out until you change them. This is \
+         only shown the first time Lapce is run, and can be closed without \
+         saving.\n",
+    );
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{commented, welcome_text};
+
+    #[test]
+    fn test_commented() {
+        let text = commented(
+            "Your settings.",
+            "#:schema ../schema.json\n\n[core]\n# The leader\nleader = \"space\"\n",
+        );
+        assert_eq!(
+            text,
+            "# Your settings.\n\n# [core]\n# The leader\n# leader = \"space\"\n"
+        );
+        assert!(toml::from_str::<toml::value::Table>(&text)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_welcome_text() {
+        let text = welcome_text(|command| {
+            (command == "palette.command").then(|| "Ctrl+Shift+P".to_string())
+        });
+        assert!(text.contains("- Run any command by its name: `Ctrl+Shift+P`\n"));
+        assert!(text.contains("- Go to a file: the command palette\n"));
+    }
+}
@@ -1,4 +1,4 @@
-use std::{ops::Range, path::PathBuf};
+use std::{collections::HashSet, ops::Range, path::PathBuf};
 
 use floem::{
     ext_event::create_ext_action,
@@ -7,9 +7,19 @@
     views::VirtualListVector,
 };
 use indexmap::IndexMap;
-use lapce_core::{mode::Mode, selection::Selection};
-use lapce_rpc::proxy::{ProxyResponse, SearchMatch};
+use lapce_core::{
+    buffer::rope_text::{RopeText, RopeTextRef},
+    mode::Mode,
+    replace::Replacer,
+    selection::Selection,
+};
+use lapce_rpc::{
+    file::compare_file_names,
+    proxy::{ProxyResponse, SearchMatch},
+    RpcError,
+};
 use lapce_xi_rope::Rope;
+use lsp_types::TextEdit;
 
 use crate::{
     command::{CommandExecuted, CommandKind},
@@ -24,6 +34,10 @@
 pub struct SearchMatchData {
     pub expanded: RwSignal<bool>,
     pub matches: RwSignal<im::Vector<SearchMatch>>,
+    /// Whether replacing touches this file at all
+    pub included: RwSignal<bool>,
+    /// The line and start column of the matches left out of replacing
+    pub excluded: RwSignal<im::HashSet<(usize, usize)>>,
     pub line_height: Memo<f64>,
 }
 
@@ -39,9 +53,31 @@ pub fn height(&self) -> f64 {
     }
 }
 
+/// The inputs of the search panel
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchInput {
+    Pattern,
+    Replacement,
+    /// The globs of the files to search
+    Include,
+}
+
 #[derive(Clone)]
 pub struct GlobalSearchData {
     pub editor: EditorData,
+    pub replace_editor: EditorData,
+    pub include_editor: EditorData,
+    /// The input the keys go to
+    pub focused_input: RwSignal<SearchInput>,
+    /// Whether the last search left out files matching `search.exclude`
+    pub excluded: RwSignal<bool>,
+    /// Whether to search the files matching `search.exclude` too
+    pub search_excluded: RwSignal<bool>,
+    /// What replacing would do with the current inputs, `None` when there's
+    /// nothing to search for or the pattern is invalid
+    pub replacer: RwSignal<Option<Replacer>>,
+    /// Bumped to search again with the same inputs
+    pub refresh: RwSignal<u64>,
     pub search_result: RwSignal<IndexMap<PathBuf, SearchMatchData>>,
     pub main_split: MainSplitData,
     pub common: CommonData,
@@ -68,7 +104,7 @@ fn run_command(
             CommandKind::Edit(_)
             | CommandKind::Move(_)
             | CommandKind::MultiSelection(_) => {
-                self.editor.run_command(command, count, mods);
+                self.input().run_command(command, count, mods);
             }
             CommandKind::MotionMode(_) => {}
         }
@@ -76,7 +112,7 @@ fn run_command(
     }
 
     fn receive_char(&self, c: &str) {
-        self.editor.receive_char(c);
+        self.input().receive_char(c);
     }
 }
 
@@ -112,10 +148,21 @@ fn slice(&mut self, _range: Range<usize>) -> Self::ItemIterator {
 impl GlobalSearchData {
     pub fn new(cx: Scope, main_split: MainSplitData, common: CommonData) -> Self {
         let editor = EditorData::new_local(cx, EditorId::next(), common.clone());
+        let replace_editor =
+            EditorData::new_local(cx, EditorId::next(), common.clone());
+        let include_editor =
+            EditorData::new_local(cx, EditorId::next(), common.clone());
         let search_result = cx.create_rw_signal(IndexMap::new());
 
         let global_search = Self {
             editor,
+            replace_editor,
+            include_editor,
+            focused_input: cx.create_rw_signal(SearchInput::Pattern),
+            excluded: cx.create_rw_signal(false),
+            search_excluded: cx.create_rw_signal(false),
+            replacer: cx.create_rw_signal(None),
+            refresh: cx.create_rw_signal(0),
             search_result,
             main_split,
             common,
@@ -124,6 +171,7 @@ pub fn new(cx: Scope, main_split: MainSplitData, common: CommonData) -> Self {
         {
             let global_search = global_search.clone();
             cx.create_effect(move |_| {
+                global_search.refresh.track();
                 let pattern = global_search
                     .editor
                     .view
@@ -131,18 +179,35 @@ pub fn new(cx: Scope, main_split: MainSplitData, common: CommonData) -> Self {
                     .with(|doc| doc.buffer().to_string());
                 if pattern.is_empty() {
                     global_search.search_result.update(|r| r.clear());
+                    global_search.excluded.set(false);
                     return;
                 }
                 let case_sensitive = global_search.common.find.case_sensitive(true);
                 let whole_word = global_search.common.find.whole_words.get();
                 let is_regex = global_search.common.find.is_regex.get();
+                let include = global_search
+                    .include_editor
+                    .view
+                    .doc
+                    .with(|doc| split_globs(&doc.buffer().to_string()));
+                let exclude = if global_search.search_excluded.get() {
+                    Vec::new()
+                } else {
+                    global_search
+                        .common
+                        .config
+                        .with(|config| config.search.exclude.clone())
+                };
                 let send = {
                     let global_search = global_search.clone();
                     create_ext_action(cx, move |result| {
-                        if let Ok(ProxyResponse::GlobalSearchResponse { matches }) =
-                            result
+                        if let Ok(ProxyResponse::GlobalSearchResponse {
+                            matches,
+                            excluded,
+                        }) = result
                         {
                             global_search.update_matches(matches);
+                            global_search.excluded.set(excluded);
                         }
                     })
                 };
@@ -151,6 +216,8 @@ pub fn new(cx: Scope, main_split: MainSplitData, common: CommonData) -> Self {
                     case_sensitive,
                     whole_word,
                     is_regex,
+                    include,
+                    exclude,
                     move |result| {
                         send(result);
                     },
@@ -167,11 +234,58 @@ pub fn new(cx: Scope, main_split: MainSplitData, common: CommonData) -> Self {
             });
         }
 
+        {
+            let global_search = global_search.clone();
+            cx.create_effect(move |_| {
+                let pattern = global_search
+                    .editor
+                    .view
+                    .doc
+                    .with(|doc| doc.buffer().to_string());
+                let replacement = global_search
+                    .replace_editor
+                    .view
+                    .doc
+                    .with(|doc| doc.buffer().to_string());
+                let case_sensitive = global_search.common.find.case_sensitive(true);
+                let whole_word = global_search.common.find.whole_words.get();
+                let is_regex = global_search.common.find.is_regex.get();
+                let preserve_case = global_search.common.find.preserve_case.get();
+                let replacer = if pattern.is_empty() {
+                    None
+                } else {
+                    Replacer::new(
+                        &pattern,
+                        case_sensitive,
+                        whole_word,
+                        is_regex,
+                        &replacement,
+                        preserve_case,
+                    )
+                    .ok()
+                };
+                global_search.replacer.set(replacer);
+            });
+        }
+
         global_search
     }
 
-    fn update_matches(&self, matches: IndexMap<PathBuf, Vec<SearchMatch>>) {
+    fn input(&self) -> &EditorData {
+        match self.focused_input.get_untracked() {
+            SearchInput::Pattern => &self.editor,
+            SearchInput::Replacement => &self.replace_editor,
+            SearchInput::Include => &self.include_editor,
+        }
+    }
+
+    fn update_matches(&self, mut matches: IndexMap<PathBuf, Vec<SearchMatch>>) {
         let current = self.search_result.get_untracked();
+        let natural = self
+            .common
+            .config
+            .with_untracked(|config| config.files.natural_sort);
+        matches.sort_by(|a, _, b, _| compare_file_names(a, b, natural));
 
         self.search_result.set(
             matches
@@ -185,11 +299,18 @@ fn update_matches(&self, matches: IndexMap<PathBuf, Vec<SearchMatch>>) {
                                     .common
                                     .scope
                                     .create_rw_signal(im::Vector::new()),
+                                included: self.common.scope.create_rw_signal(true),
+                                excluded: self
+                                    .common
+                                    .scope
+                                    .create_rw_signal(im::HashSet::new()),
                                 line_height: self.common.ui_line_height,
                             }
                         });
 
                     match_data.matches.set(matches.into());
+                    // The lines may have moved, so it's unclear what was meant
+                    match_data.excluded.set(im::HashSet::new());
 
                     (path, match_data)
                 })
@@ -207,4 +328,153 @@ pub fn set_pattern(&self, pattern: String) {
             .cursor
             .update(|cursor| cursor.set_insert(Selection::region(0, pattern_len)));
     }
+
+    /// Replace the matches that are still included. The edits go through the
+    /// edit preview like those of a rename, so open documents are changed in
+    /// an edit that can be undone and the other files on disk.
+    pub fn replace_all(&self) {
+        let Some(replacer) = self.replacer.get_untracked() else {
+            return;
+        };
+        let files = self.search_result.with_untracked(|result| {
+            result
+                .iter()
+                .filter(|(_, data)| data.included.get_untracked())
+                .map(|(path, data)| {
+                    let excluded = data.excluded.get_untracked();
+                    let lines = data.matches.with_untracked(|matches| {
+                        matches
+                            .iter()
+                            .filter(|m| !excluded.contains(&(m.line, m.start)))
+                            .map(|m| m.line)
+                            .collect::<HashSet<usize>>()
+                    });
+                    (path.clone(), lines)
+                })
+                .filter(|(_, lines)| !lines.is_empty())
+                .collect::<Vec<_>>()
+        });
+        if files.is_empty() {
+            return;
+        }
+
+        let docs = self.main_split.docs.get_untracked();
+        let mut edits = Vec::new();
+        let mut on_disk = Vec::new();
+        for (path, lines) in files {
+            let doc = docs
+                .get(&path)
+                .filter(|doc| doc.with_untracked(|doc| doc.loaded()));
+            match doc {
+                Some(doc) => {
+                    let (rev, text) = doc.with_untracked(|doc| {
+                        (doc.rev(), doc.buffer().text().clone())
+                    });
+                    let file_edits = replace_edits(&replacer, &text, &lines);
+                    if !file_edits.is_empty() {
+                        edits.push((path, Some(rev as i32), file_edits));
+                    }
+                }
+                None => on_disk.push((path, lines)),
+            }
+        }
+
+        let main_split = self.main_split.clone();
+        let refresh = self.refresh;
+        let apply = move |edits: Vec<(PathBuf, Option<i32>, Vec<TextEdit>)>,
+                          failures: Vec<String>| {
+            main_split.preview_or_apply_file_edits(edits, failures, "Replace", true);
+            refresh.update(|refresh| *refresh += 1);
+        };
+        if on_disk.is_empty() {
+            apply(edits, Vec::new());
+            return;
+        }
+
+        let paths = on_disk.iter().map(|(path, _)| path.clone()).collect();
+        let send = create_ext_action(
+            self.common.scope,
+            move |result: Result<ProxyResponse, RpcError>| {
+                let mut edits = edits;
+                let mut failures = Vec::new();
+                let contents = match result {
+                    Ok(ProxyResponse::ReadFilesResponse { files }) => files,
+                    Ok(_) => Vec::new(),
+                    Err(err) => {
+                        failures.push(err.message);
+                        Vec::new()
+                    }
+                };
+                for (path, content) in contents {
+                    let lines = on_disk
+                        .iter()
+                        .find(|(p, _)| p == &path)
+                        .map(|(_, lines)| lines);
+                    let Some(lines) = lines else {
+                        continue;
+                    };
+                    match content {
+                        Ok(content) => {
+                            let text = Rope::from(content);
+                            let file_edits = replace_edits(&replacer, &text, lines);
+                            if !file_edits.is_empty() {
+                                edits.push((path, None, file_edits));
+                            }
+                        }
+                        Err(err) => {
+                            failures.push(format!("{}: {err}", path.display()))
+                        }
+                    }
+                }
+                apply(edits, failures);
+            },
+        );
+        self.common.proxy.read_files(paths, move |result| {
+            send(result);
+        });
+    }
+}
+
+/// The edits replacing the matches on the given lines of `text`, which count
+/// from 1.
+fn replace_edits(
+    replacer: &Replacer,
+    text: &Rope,
+    lines: &HashSet<usize>,
+) -> Vec<TextEdit> {
+    let rope_text = RopeTextRef::new(text);
+    replacer
+        .edits(&text.to_string(), lines)
+        .into_iter()
+        .map(|(range, new_text)| TextEdit {
+            range: lsp_types::Range {
+                start: rope_text.offset_to_position(range.start),
+                end: rope_text.offset_to_position(range.end),
+            },
+            new_text,
+        })
+        .collect()
+}
+
+/// The globs in the input of the files to search, which are separated by
+/// commas like `src/**, *.rs`.
+fn split_globs(input: &str) -> Vec<String> {
+    input
+        .split(',')
+        .map(|glob| glob.trim())
+        .filter(|glob| !glob.is_empty())
+        .map(|glob| glob.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_globs;
+
+    #[test]
+    fn test_split_globs() {
+        assert!(split_globs("").is_empty());
+        assert!(split_globs(" , ").is_empty());
+        assert_eq!(split_globs("src/**, *.rs,,"), vec!["src/**", "*.rs"]);
+    }
 }
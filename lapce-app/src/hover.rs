@@ -8,6 +8,9 @@
 #[derive(Clone)]
 pub struct HoverData {
     pub active: RwSignal<bool>,
+    /// Bumped for every request, and when the mouse moves on, so that only
+    /// the response to the latest request is shown
+    pub request_id: RwSignal<usize>,
     pub offset: RwSignal<usize>,
     pub editor_id: RwSignal<EditorId>,
     pub content: RwSignal<Vec<MarkdownContent>>,
@@ -18,10 +21,17 @@ impl HoverData {
     pub fn new(cx: Scope) -> Self {
         Self {
             active: cx.create_rw_signal(false),
+            request_id: cx.create_rw_signal(0),
             offset: cx.create_rw_signal(0),
             content: cx.create_rw_signal(Vec::new()),
             editor_id: cx.create_rw_signal(EditorId::next()),
             layout_rect: cx.create_rw_signal(Rect::ZERO),
         }
     }
+
+    /// Start a new request, dropping the response to the last one.
+    pub fn next_request(&self) -> usize {
+        self.request_id.update(|id| *id += 1);
+        self.request_id.get_untracked()
+    }
 }
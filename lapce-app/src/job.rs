@@ -0,0 +1,48 @@
+//! The work that blocks on the file system or on other programs, like reading
+//! the settings or writing the session, which is kept off the UI thread so
+//! that typing stays as fast while it's done.
+//!
+//! Such work is a job, run on a thread of its own by [`spawn_job`], whose
+//! result comes back to the UI thread like any other update. The places that
+//! block call [`assert_off_ui_thread`], which in dev builds logs where they
+//! were called from when that's on the UI thread.
+
+use std::cell::Cell;
+
+use floem::{ext_event::create_ext_action, reactive::Scope};
+
+thread_local! {
+    static IS_UI_THREAD: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Take the current thread to be the UI thread from now on. Lapce calls this
+/// once its windows are set up, as what's done before can't hold up typing.
+pub fn mark_ui_thread() {
+    IS_UI_THREAD.with(|is_ui_thread| is_ui_thread.set(true));
+}
+
+pub fn is_ui_thread() -> bool {
+    IS_UI_THREAD.with(|is_ui_thread| is_ui_thread.get())
+}
+
+/// Flag that `what`, which blocks, is being done on the UI thread, with a
+/// backtrace of where it was done from. It's only checked in dev builds.
+pub fn assert_off_ui_thread(what: &str) {
+    if cfg!(debug_assertions) && is_ui_thread() {
+        let backtrace = std::backtrace::Backtrace::force_capture();
+        tracing::error!("{what} on the UI thread\n{backtrace}");
+    }
+}
+
+/// Run `job` on a thread of its own, and `done` with its result on the UI
+/// thread, unless `cx` is disposed of by then.
+pub fn spawn_job<T: Send + 'static>(
+    cx: Scope,
+    job: impl FnOnce() -> T + Send + 'static,
+    done: impl FnOnce(T) + 'static,
+) {
+    let send = create_ext_action(cx, done);
+    std::thread::spawn(move || {
+        send(job());
+    });
+}
@@ -0,0 +1,230 @@
+//! Jumping to what's in view of the editor by typing its label: a jump labels
+//! the word starts, the line starts or the occurrences of a typed character
+//! that are in view, and typing a label moves the cursor to it.
+//!
+//! The targets closest to the cursor get the shortest labels. No label is the
+//! start of another, so a jump is done as soon as a whole label is typed.
+//! While the labels are shown, the keys type them rather than run their
+//! commands, until the jump is done, cancelled by any other key, or left by
+//! the editor scrolling or losing focus.
+
+/// The characters labels are made of, the easiest to type first
+pub const LABEL_CHARS: &[char] = &[
+    'a', 's', 'd', 'f', 'j', 'k', 'l', 'g', 'h', 'q', 'w', 'e', 'r', 't', 'y', 'u',
+    'i', 'o', 'p', 'z', 'x', 'c', 'v', 'b', 'n', 'm',
+];
+
+/// What a jump goes to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JumpKind {
+    /// The start of each word
+    Word,
+    /// Each occurrence of the character
+    Char(char),
+    /// The first character of each line that isn't whitespace
+    LineStart,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JumpTarget {
+    pub offset: usize,
+    pub label: String,
+}
+
+/// The state of a jump in an editor
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Jump {
+    /// Waiting for the character whose occurrences are jumped to
+    Char,
+    /// The labels are shown, and `typed` is what's been typed of one
+    Labels {
+        targets: Vec<JumpTarget>,
+        typed: String,
+    },
+}
+
+/// What typing `typed` does to a jump
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JumpMatch {
+    /// It's the label of the target at the offset
+    Target(usize),
+    /// It's the start of a label, which is waited for
+    Prefix,
+    /// It's no label
+    None,
+}
+
+/// The columns a jump of `kind` goes to in the line with the text `line`.
+pub fn line_targets(kind: JumpKind, line: &str) -> Vec<usize> {
+    match kind {
+        JumpKind::Word => {
+            let mut cols = Vec::new();
+            let mut in_word = false;
+            for (col, c) in line.char_indices() {
+                let is_word = c.is_alphanumeric() || c == '_';
+                if is_word && !in_word {
+                    cols.push(col);
+                }
+                in_word = is_word;
+            }
+            cols
+        }
+        JumpKind::Char(target) => line
+            .char_indices()
+            .filter(|(_, c)| *c == target)
+            .map(|(col, _)| col)
+            .collect(),
+        JumpKind::LineStart => {
+            let col = line
+                .char_indices()
+                .find(|(_, c)| !c.is_whitespace())
+                .map(|(col, _)| col)
+                .unwrap_or(0);
+            vec![col]
+        }
+    }
+}
+
+/// The targets at `offsets`, labelled in the order of their distance to the
+/// cursor at `cursor`, which is no target itself. There are only as many
+/// targets as there are labels, the farthest ones are left out.
+pub fn label_targets(offsets: &[usize], cursor: usize) -> Vec<JumpTarget> {
+    let mut offsets = offsets
+        .iter()
+        .copied()
+        .filter(|offset| *offset != cursor)
+        .collect::<Vec<_>>();
+    offsets.sort_by_key(|offset| (offset.abs_diff(cursor), *offset));
+    let labels = labels(offsets.len());
+    offsets
+        .into_iter()
+        .zip(labels)
+        .map(|(offset, label)| JumpTarget { offset, label })
+        .collect()
+}
+
+/// `count` labels, or as many as there are if that's fewer, shortest first.
+/// As many characters as can be are labels of their own, and the others start
+/// two-character labels, so that no label is the start of another.
+pub fn labels(count: usize) -> Vec<String> {
+    let n = LABEL_CHARS.len();
+    let count = count.min(n * n);
+    // The number of single-character labels, which leave enough characters
+    // to start the others with
+    let singles = if count <= n {
+        count
+    } else {
+        (n * n - count) / (n - 1)
+    };
+    let mut labels = LABEL_CHARS[..singles]
+        .iter()
+        .map(|c| c.to_string())
+        .collect::<Vec<_>>();
+    'prefixes: for first in &LABEL_CHARS[singles..] {
+        for second in LABEL_CHARS {
+            if labels.len() >= count {
+                break 'prefixes;
+            }
+            labels.push(format!("{first}{second}"));
+        }
+    }
+    labels
+}
+
+/// What typing `typed` does to a jump to `targets`.
+pub fn match_label(targets: &[JumpTarget], typed: &str) -> JumpMatch {
+    if let Some(target) = targets.iter().find(|target| target.label == typed) {
+        JumpMatch::Target(target.offset)
+    } else if targets.iter().any(|target| target.label.starts_with(typed)) {
+        JumpMatch::Prefix
+    } else {
+        JumpMatch::None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        label_targets, labels, line_targets, match_label, JumpKind, JumpMatch,
+        JumpTarget, LABEL_CHARS,
+    };
+
+    #[test]
+    fn test_line_targets() {
+        assert_eq!(
+            line_targets(JumpKind::Word, "  let foo_bar = a.b(1);"),
+            vec![2, 6, 16, 18, 20]
+        );
+        assert_eq!(
+            line_targets(JumpKind::Char('o'), "foo bar o"),
+            vec![1, 2, 8]
+        );
+        assert_eq!(line_targets(JumpKind::LineStart, "    }"), vec![4]);
+        assert_eq!(line_targets(JumpKind::LineStart, "   "), vec![0]);
+        assert_eq!(line_targets(JumpKind::Word, ""), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_labels() {
+        assert_eq!(labels(3), vec!["a", "s", "d"]);
+        assert_eq!(labels(26).len(), 26);
+
+        let n = LABEL_CHARS.len();
+        for count in [27, 100, 400, n * n, n * n + 10] {
+            let labels = labels(count);
+            assert_eq!(labels.len(), count.min(n * n));
+            for (i, label) in labels.iter().enumerate() {
+                for (j, other) in labels.iter().enumerate() {
+                    assert!(i == j || !other.starts_with(label.as_str()));
+                }
+            }
+        }
+        // The shortest labels come first
+        let labels = labels(100);
+        assert!(labels.windows(2).all(|w| w[0].len() <= w[1].len()));
+    }
+
+    #[test]
+    fn test_label_targets() {
+        let targets = label_targets(&[0, 4, 10, 14, 20], 10);
+        assert_eq!(
+            targets,
+            vec![
+                JumpTarget {
+                    offset: 14,
+                    label: "a".to_string()
+                },
+                JumpTarget {
+                    offset: 4,
+                    label: "s".to_string()
+                },
+                JumpTarget {
+                    offset: 0,
+                    label: "d".to_string()
+                },
+                JumpTarget {
+                    offset: 20,
+                    label: "f".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_match_label() {
+        let targets = vec![
+            JumpTarget {
+                offset: 3,
+                label: "a".to_string(),
+            },
+            JumpTarget {
+                offset: 7,
+                label: "ma".to_string(),
+            },
+        ];
+        assert_eq!(match_label(&targets, "a"), JumpMatch::Target(3));
+        assert_eq!(match_label(&targets, "m"), JumpMatch::Prefix);
+        assert_eq!(match_label(&targets, "ma"), JumpMatch::Target(7));
+        assert_eq!(match_label(&targets, "x"), JumpMatch::None);
+    }
+}
@@ -252,6 +252,7 @@ pub fn keymap_view(common: CommonData) -> impl View {
                         key: Vec::new(),
                         modes: Modes::empty(),
                         when: None,
+                        args: None,
                     }
                 };
                 picker.keymap.set(Some(keymap));
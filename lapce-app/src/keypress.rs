@@ -4,28 +4,30 @@
 mod loader;
 mod press;
 
-use std::{path::PathBuf, rc::Rc, str::FromStr};
+use std::{path::PathBuf, rc::Rc, str::FromStr, time::Duration};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use floem::{
+    action::{exec_after, TimerToken},
     keyboard::{Key, KeyEvent, ModifiersState},
-    pointer::PointerInputEvent,
+    pointer::{PointerButton, PointerInputEvent},
     reactive::{RwSignal, Scope},
 };
 use indexmap::IndexMap;
 use itertools::Itertools;
-use lapce_core::mode::{Mode, Modes};
-use tracing::{debug, error};
+use lapce_core::mode::{Mode, Modes, MotionMode};
+use tracing::{debug, error, warn};
 
-use self::{key::KeyInput, keymap::KeyMap, loader::KeyMapLoader};
+use self::{
+    key::KeyInput,
+    keymap::KeyMap,
+    loader::{json_to_toml, KeyMapLoader},
+};
 use crate::{
-    command::{
-        lapce_internal_commands, CommandExecuted, CommandKind, LapceCommand,
-        LapceWorkbenchCommand,
-    },
+    command::{lapce_internal_commands, CommandExecuted, CommandKind, LapceCommand},
     config::LapceConfig,
     keypress::{
-        condition::{CheckCondition, Condition},
+        condition::{parse_mode_comparison, CheckCondition, Condition},
         keymap::KeymapMatch,
     },
     listener::Listener,
@@ -81,11 +83,48 @@ fn from(ev: &'a PointerInputEvent) -> Self {
     }
 }
 
+/// What's been typed so far of a command that's still to be completed
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PendingState {
+    /// The count typed before the command
+    pub count: Option<usize>,
+    /// The operator waiting for its motion, like the `d` of `dw`
+    pub operator: Option<MotionMode>,
+    /// The keys pressed so far of an unfinished key sequence, with the leader
+    /// shown as `<leader>`
+    pub keys: String,
+}
+
+impl PendingState {
+    pub fn is_empty(&self) -> bool {
+        self.count.is_none() && self.operator.is_none() && self.keys.is_empty()
+    }
+}
+
+impl std::fmt::Display for PendingState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let count = self.count.map(|count| count.to_string());
+        let operator = self.operator.as_ref().map(|operator| operator.to_string());
+        let keys = Some(self.keys.clone()).filter(|keys| !keys.is_empty());
+        write!(
+            f,
+            "{}",
+            [count, operator, keys].into_iter().flatten().join(" ")
+        )
+    }
+}
+
 #[derive(Clone)]
 pub struct KeyPressData {
     count: RwSignal<Option<usize>>,
     pending_keypress: RwSignal<Vec<KeyPress>>,
-    workbench_cmd: Listener<LapceWorkbenchCommand>,
+    /// The timer which runs the binding of the pending keys if no further key
+    /// is pressed within `sequence_timeout`.
+    pending_timer: RwSignal<TimerToken>,
+    sequence_timeout: u64,
+    /// The keys of `leader` in keymaps, shown as `<leader>` while pending.
+    leader: Vec<KeyPress>,
+    lapce_command: Listener<LapceCommand>,
     pub commands: Rc<IndexMap<String, LapceCommand>>,
     pub keymaps: Rc<IndexMap<Vec<KeyPress>, Vec<KeyMap>>>,
     pub command_keymaps: Rc<IndexMap<String, Vec<KeyMap>>>,
@@ -97,30 +136,52 @@ impl KeyPressData {
     pub fn new(
         cx: Scope,
         config: &LapceConfig,
-        workbench_cmd: Listener<LapceWorkbenchCommand>,
+        lapce_command: Listener<LapceCommand>,
     ) -> Self {
+        let commands = lapce_internal_commands();
         let (keymaps, command_keymaps) =
-            Self::get_keymaps(config).unwrap_or((IndexMap::new(), IndexMap::new()));
+            Self::get_keymaps(config, &commands, &mut Vec::new()).unwrap_or_else(
+                |err| {
+                    error!("{err}");
+                    Self::default_keymaps(config, &commands).finalize()
+                },
+            );
         let mut keypress = Self {
             count: cx.create_rw_signal(None),
             pending_keypress: cx.create_rw_signal(Vec::new()),
+            pending_timer: cx.create_rw_signal(TimerToken::INVALID),
+            sequence_timeout: config.core.key_sequence_timeout,
+            leader: Self::leader_keys(config),
             keymaps: Rc::new(keymaps),
             command_keymaps: Rc::new(command_keymaps),
-            commands: Rc::new(lapce_internal_commands()),
+            commands: Rc::new(commands),
             commands_with_keymap: Rc::new(Vec::new()),
             commands_without_keymap: Rc::new(Vec::new()),
-            workbench_cmd,
+            lapce_command,
         };
         keypress.load_commands();
         keypress
     }
 
-    pub fn update_keymaps(&mut self, config: &LapceConfig) {
-        if let Ok((new_keymaps, new_command_keymaps)) = Self::get_keymaps(config) {
-            self.keymaps = Rc::new(new_keymaps);
-            self.command_keymaps = Rc::new(new_command_keymaps);
-            self.load_commands();
+    /// Reload the keymaps, returning the problems with the user's keymaps file.
+    /// If the file can't be parsed at all, the current keymaps are kept.
+    pub fn update_keymaps(&mut self, config: &LapceConfig) -> Vec<String> {
+        self.sequence_timeout = config.core.key_sequence_timeout;
+        self.leader = Self::leader_keys(config);
+        // A pending key sequence might not exist any more
+        self.clear_pending();
+        self.count.set(None);
+
+        let mut errors = Vec::new();
+        match Self::get_keymaps(config, &self.commands, &mut errors) {
+            Ok((new_keymaps, new_command_keymaps)) => {
+                self.keymaps = Rc::new(new_keymaps);
+                self.command_keymaps = Rc::new(new_command_keymaps);
+                self.load_commands();
+            }
+            Err(err) => errors.push(err.to_string()),
         }
+        errors
     }
 
     fn load_commands(&mut self) {
@@ -152,6 +213,13 @@ fn handle_count<T: KeyPressFocus>(
         if focus.expect_char() {
             return false;
         }
+        // A digit in the middle of a key sequence is part of the sequence
+        if self
+            .pending_keypress
+            .with_untracked(|pending_keypress| !pending_keypress.is_empty())
+        {
+            return false;
+        }
         let mode = focus.get_mode();
         if mode == Mode::Insert || mode == Mode::Terminal {
             return false;
@@ -174,29 +242,33 @@ fn handle_count<T: KeyPressFocus>(
         false
     }
 
+    /// Run the command of `keymap` with its arguments.
     fn run_command<T: KeyPressFocus>(
         &self,
-        command: &str,
+        keymap: &KeyMap,
         count: Option<usize>,
         mods: ModifiersState,
         focus: &T,
     ) -> CommandExecuted {
-        if let Some(cmd) = self.commands.get(command) {
-            match &cmd.kind {
-                CommandKind::Workbench(cmd) => {
-                    self.workbench_cmd.send(cmd.clone());
-                    CommandExecuted::Yes
-                }
-                CommandKind::Move(_)
-                | CommandKind::Edit(_)
-                | CommandKind::Focus(_)
-                | CommandKind::MotionMode(_)
-                | CommandKind::MultiSelection(_) => {
-                    focus.run_command(cmd, count, mods)
-                }
+        let Some(cmd) = self.commands.get(&keymap.command) else {
+            warn!("Unknown command {:?}", keymap.command);
+            return CommandExecuted::No;
+        };
+        let mut cmd = cmd.clone();
+        cmd.data = keymap
+            .args
+            .as_deref()
+            .and_then(|args| serde_json::from_str(args).ok());
+        match &cmd.kind {
+            CommandKind::Workbench(_) => {
+                self.lapce_command.send(cmd);
+                CommandExecuted::Yes
             }
-        } else {
-            CommandExecuted::No
+            CommandKind::Move(_)
+            | CommandKind::Edit(_)
+            | CommandKind::Focus(_)
+            | CommandKind::MotionMode(_)
+            | CommandKind::MultiSelection(_) => focus.run_command(&cmd, count, mods),
         }
     }
 
@@ -215,25 +287,145 @@ pub fn keypress<'a>(event: impl Into<EventRef<'a>>) -> Option<KeyPress> {
                 // We are removing Shift modifier since the character is already upper case.
                 mods: Self::get_key_modifiers(ev),
             },
-            EventRef::Pointer(ev) => KeyPress {
-                key: KeyInput::Pointer(ev.button),
-                mods: ev.modifiers,
-            },
+            EventRef::Pointer(ev) => {
+                let count = if ev.button == PointerButton::Primary {
+                    ev.count.clamp(1, 3)
+                } else {
+                    1
+                };
+                KeyPress {
+                    key: KeyInput::Pointer(ev.button, count),
+                    mods: ev.modifiers,
+                }
+            }
         };
         Some(keypress)
     }
 
-    pub fn key_down<'a, T: KeyPressFocus>(
+    /// The count and the keys pressed so far of an unfinished key sequence,
+    /// along with `operator`, the one waiting in the editor, for showing to
+    /// the user.
+    pub fn pending_state(&self, operator: Option<MotionMode>) -> PendingState {
+        let keys = self.pending_keypress.with(|pending_keypress| {
+            let leader = self.leader.len();
+            let has_leader = leader > 0
+                && pending_keypress.len() >= leader
+                && pending_keypress[..leader]
+                    .iter()
+                    .map(KeyPress::to_lowercase)
+                    .eq(self.leader.iter().cloned());
+            if has_leader {
+                std::iter::once("<leader>".to_string())
+                    .chain(pending_keypress[leader..].iter().map(|k| k.to_string()))
+                    .join(" ")
+            } else {
+                pending_keypress.iter().map(|k| k.to_string()).join(" ")
+            }
+        });
+        PendingState {
+            count: self.count.get(),
+            operator,
+            keys,
+        }
+    }
+
+    fn leader_keys(config: &LapceConfig) -> Vec<KeyPress> {
+        KeyPress::parse(&config.core.leader)
+            .iter()
+            .map(KeyPress::to_lowercase)
+            .collect()
+    }
+
+    fn clear_pending(&self) {
+        self.pending_timer.set(TimerToken::INVALID);
+        self.pending_keypress.update(|pending_keypress| {
+            pending_keypress.clear();
+        });
+    }
+
+    /// Wait for the next key of the pending sequence, and if none comes in
+    /// time, run the binding of the longest part of it that has one.
+    fn start_pending_timer<T: KeyPressFocus + Clone + 'static>(
+        &self,
+        focus: &T,
+        mods: ModifiersState,
+    ) {
+        if self.sequence_timeout == 0 {
+            self.pending_timer.set(TimerToken::INVALID);
+            return;
+        }
+
+        let keypress = self.clone();
+        let focus = focus.clone();
+        let token =
+            exec_after(Duration::from_millis(self.sequence_timeout), move |token| {
+                if keypress.pending_timer.get_untracked() != token {
+                    return;
+                }
+                let pending = keypress
+                    .pending_keypress
+                    .with_untracked(|pending_keypress| pending_keypress.clone());
+                keypress.clear_pending();
+                let count = keypress.count.try_update(|count| count.take()).unwrap();
+                for i in (1..pending.len() + 1).rev() {
+                    let keymaps = keypress.exact_keymaps(&pending[..i], &focus);
+                    if keymaps.is_empty() {
+                        continue;
+                    }
+                    for keymap in keymaps {
+                        if keypress.run_command(&keymap, count, mods, &focus)
+                            == CommandExecuted::Yes
+                        {
+                            break;
+                        }
+                    }
+                    return;
+                }
+            });
+        self.pending_timer.set(token);
+    }
+
+    /// Whether the key press of `event` is bound to a command on its own, for
+    /// mouse clicks to check before their default behavior.
+    pub fn is_bound<'a, T: KeyPressFocus>(
         &self,
         event: impl Into<EventRef<'a>>,
         focus: &T,
     ) -> bool {
+        let Some(keypress) = Self::keypress(event) else {
+            return false;
+        };
+        self.pending_keypress
+            .with_untracked(|pending_keypress| pending_keypress.is_empty())
+            && self.match_keymap(&[keypress], focus) != KeymapMatch::None
+    }
+
+    pub fn key_down<'a, T: KeyPressFocus + Clone + 'static>(
+        &self,
+        event: impl Into<EventRef<'a>>,
+        focus: &T,
+    ) -> bool {
+        let event = event.into();
         let keypress = match Self::keypress(event) {
             Some(keypress) => keypress,
             None => return false,
         };
         let mods = keypress.mods;
 
+        // Any key press stops the wait for the next key of a sequence
+        self.pending_timer.set(TimerToken::INVALID);
+
+        if keypress.key == KeyInput::Keyboard(Key::Escape)
+            && keypress.mods.is_empty()
+            && self
+                .pending_keypress
+                .with_untracked(|pending_keypress| !pending_keypress.is_empty())
+        {
+            self.clear_pending();
+            self.count.set(None);
+            return true;
+        }
+
         let mode = focus.get_mode();
         if self.handle_count(focus, &keypress) {
             return false;
@@ -247,21 +439,17 @@ pub fn key_down<'a, T: KeyPressFocus>(
             self.match_keymap(pending_keypress, focus)
         });
         match keymatch {
-            KeymapMatch::Full(command) => {
-                self.pending_keypress.update(|pending_keypress| {
-                    pending_keypress.clear();
-                });
+            KeymapMatch::Full(keymap) => {
+                self.clear_pending();
                 let count = self.count.try_update(|count| count.take()).unwrap();
-                self.run_command(&command, count, mods, focus);
+                self.run_command(&keymap, count, mods, focus);
                 return true;
             }
-            KeymapMatch::Multiple(commands) => {
-                self.pending_keypress.update(|pending_keypress| {
-                    pending_keypress.clear();
-                });
+            KeymapMatch::Multiple(keymaps) => {
+                self.clear_pending();
                 let count = self.count.try_update(|count| count.take()).unwrap();
-                for command in commands {
-                    if self.run_command(&command, count, mods, focus)
+                for keymap in keymaps {
+                    if self.run_command(&keymap, count, mods, focus)
                         == CommandExecuted::Yes
                     {
                         return true;
@@ -273,19 +461,18 @@ pub fn key_down<'a, T: KeyPressFocus>(
             KeymapMatch::Prefix => {
                 // Here pending_keypress contains only a prefix of some keymap, so let's keep
                 // collecting key presses.
+                self.start_pending_timer(focus, mods);
                 return false;
             }
             KeymapMatch::None => {
-                self.pending_keypress.update(|pending_keypress| {
-                    pending_keypress.clear();
-                });
+                self.clear_pending();
                 if focus.get_mode() == Mode::Insert {
                     let mut keypress = keypress.clone();
                     keypress.mods.set(ModifiersState::SHIFT, false);
-                    if let KeymapMatch::Full(command) =
+                    if let KeymapMatch::Full(keymap) =
                         self.match_keymap(&[keypress], focus)
                     {
-                        if let Some(cmd) = self.commands.get(&command) {
+                        if let Some(cmd) = self.commands.get(&keymap.command) {
                             if let CommandKind::Move(_) = cmd.kind {
                                 focus.run_command(cmd, None, mods);
                                 return true;
@@ -305,6 +492,25 @@ pub fn key_down<'a, T: KeyPressFocus>(
 
         self.count.set(None);
 
+        let unmodified = Self::key_without_modifiers(event);
+        if let Some(c) = Self::text_input(&keypress, unmodified.as_ref()) {
+            focus.receive_char(c);
+            return true;
+        }
+
+        false
+    }
+
+    /// The text typed by a key press which isn't bound to a command. The key is
+    /// the character the layout produced, so the modifiers which only select
+    /// the character don't stop it from being text: Shift, Option on macOS,
+    /// and AltGr, which Windows reports as Ctrl+Alt. `unmodified` is the key
+    /// without any modifiers, which tells AltGr apart from a Ctrl+Alt chord.
+    #[cfg_attr(not(windows), allow(unused_variables))]
+    fn text_input<'a>(
+        keypress: &'a KeyPress,
+        unmodified: Option<&Key>,
+    ) -> Option<&'a str> {
         let mut mods = keypress.mods;
 
         #[cfg(target_os = "macos")]
@@ -316,25 +522,47 @@ pub fn key_down<'a, T: KeyPressFocus>(
         {
             mods.set(ModifiersState::SHIFT, false);
         }
-        if mods.is_empty() {
-            if let KeyInput::Keyboard(Key::Character(c)) = &keypress.key {
-                focus.receive_char(c);
-                return true;
-            } else if let KeyInput::Keyboard(Key::Space) = &keypress.key {
-                focus.receive_char(" ");
-                return true;
-            }
+        #[cfg(windows)]
+        if mods == ModifiersState::CONTROL | ModifiersState::ALT
+            && unmodified
+                .map_or(false, |key| keypress.key != KeyInput::Keyboard(key.clone()))
+        {
+            mods = ModifiersState::empty();
         }
 
-        false
+        if !mods.is_empty() {
+            return None;
+        }
+        match &keypress.key {
+            KeyInput::Keyboard(Key::Character(c)) => Some(c.as_str()),
+            KeyInput::Keyboard(Key::Space) => Some(" "),
+            _ => None,
+        }
+    }
+
+    /// The key the layout gives for the key press without any modifiers, on
+    /// the platforms where it's needed.
+    fn key_without_modifiers(event: EventRef) -> Option<Key> {
+        match event {
+            #[cfg(windows)]
+            EventRef::Keyboard(ev) => {
+                use floem::keyboard::KeyEventExtModifierSupplement;
+                Some(ev.key.key_without_modifiers())
+            }
+            _ => None,
+        }
     }
 
     fn get_key_modifiers(key_event: &KeyEvent) -> ModifiersState {
+        Self::key_modifiers(&key_event.key.logical_key, key_event.modifiers)
+    }
+
+    fn key_modifiers(key: &Key, mods: ModifiersState) -> ModifiersState {
         // We only care about some modifiers
-        let mut mods = key_event.modifiers;
+        let mut mods = mods;
 
         if mods == ModifiersState::SHIFT {
-            if let Key::Character(c) = &key_event.key.logical_key {
+            if let Key::Character(c) = key {
                 if !c.chars().all(|c| c.is_alphabetic()) {
                     // We remove the shift if there's only shift pressed,
                     // and the character isn't a letter
@@ -343,7 +571,7 @@ fn get_key_modifiers(key_event: &KeyEvent) -> ModifiersState {
             }
         }
 
-        match &key_event.key.logical_key {
+        match key {
             Key::Shift => mods.set(ModifiersState::SHIFT, false),
             Key::Alt => mods.set(ModifiersState::ALT, false),
             Key::Meta => mods.set(ModifiersState::SUPER, false),
@@ -354,6 +582,33 @@ fn get_key_modifiers(key_event: &KeyEvent) -> ModifiersState {
         mods
     }
 
+    /// The keymaps of exactly `keypresses`, in the order their commands should
+    /// be tried.
+    fn exact_keymaps<T: KeyPressFocus>(
+        &self,
+        keypresses: &[KeyPress],
+        check: &T,
+    ) -> Vec<KeyMap> {
+        let keypresses: Vec<KeyPress> =
+            keypresses.iter().map(KeyPress::to_lowercase).collect();
+        let matches = self
+            .matching_keymaps(&keypresses, check)
+            .into_iter()
+            .filter(|m| m.key == keypresses)
+            .collect();
+        Self::by_specificity(matches)
+    }
+
+    /// `keymaps` with the more specific keymaps first, and the later loaded
+    /// ones first among those equally specific, so that user keymaps override
+    /// the defaults.
+    fn by_specificity(keymaps: Vec<&KeyMap>) -> Vec<KeyMap> {
+        let mut keymaps = keymaps;
+        keymaps.reverse();
+        keymaps.sort_by_key(|keymap| std::cmp::Reverse(keymap.specificity()));
+        keymaps.into_iter().cloned().collect()
+    }
+
     fn match_keymap<T: KeyPressFocus>(
         &self,
         keypresses: &[KeyPress],
@@ -361,9 +616,29 @@ fn match_keymap<T: KeyPressFocus>(
     ) -> KeymapMatch {
         let keypresses: Vec<KeyPress> =
             keypresses.iter().map(KeyPress::to_lowercase).collect();
-        let matches = self
-            .keymaps
-            .get(&keypresses)
+        let matches = self.matching_keymaps(&keypresses, check);
+
+        if matches.is_empty() {
+            KeymapMatch::None
+        } else if matches.len() == 1 && matches[0].key == keypresses {
+            KeymapMatch::Full(matches[0].clone())
+        } else if matches.len() > 1
+            && matches.iter().filter(|m| m.key != keypresses).count() == 0
+        {
+            KeymapMatch::Multiple(Self::by_specificity(matches))
+        } else {
+            KeymapMatch::Prefix
+        }
+    }
+
+    /// The keymaps starting with `keypresses` whose mode and condition hold.
+    fn matching_keymaps<'a, T: KeyPressFocus>(
+        &'a self,
+        keypresses: &[KeyPress],
+        check: &T,
+    ) -> Vec<&'a KeyMap> {
+        self.keymaps
+            .get(keypresses)
             .map(|keymaps| {
                 keymaps
                     .iter()
@@ -388,21 +663,7 @@ fn match_keymap<T: KeyPressFocus>(
                     })
                     .collect()
             })
-            .unwrap_or_else(Vec::new);
-
-        if matches.is_empty() {
-            KeymapMatch::None
-        } else if matches.len() == 1 && matches[0].key == keypresses {
-            KeymapMatch::Full(matches[0].command.clone())
-        } else if matches.len() > 1
-            && matches.iter().filter(|m| m.key != keypresses).count() == 0
-        {
-            KeymapMatch::Multiple(
-                matches.iter().rev().map(|m| m.command.clone()).collect(),
-            )
-        } else {
-            KeymapMatch::Prefix
-        }
+            .unwrap_or_else(Vec::new)
     }
 
     fn check_condition<T: KeyPressFocus>(condition: &str, check: &T) -> bool {
@@ -411,6 +672,9 @@ fn check_one_condition<T: KeyPressFocus>(
             check: &T,
         ) -> bool {
             let trimmed = condition.trim();
+            if let Some((mode, equal)) = parse_mode_comparison(trimmed) {
+                return (check.get_mode() == mode) == equal;
+            }
             if let Some(stripped) = trimmed.strip_prefix('!') {
                 if let Ok(condition) = Condition::from_str(stripped) {
                     !check.check_condition(condition)
@@ -429,7 +693,7 @@ fn check_one_condition<T: KeyPressFocus>(
                 check_one_condition(condition, check)
             }
             CheckCondition::Or(left, right) => {
-                let left = check_one_condition(left, check);
+                let left = Self::check_condition(left, check);
                 let right = Self::check_condition(right, check);
 
                 left || right
@@ -443,16 +707,40 @@ fn check_one_condition<T: KeyPressFocus>(
         }
     }
 
+    /// The default keymaps with the user's keymaps file on top, adding the
+    /// keymaps of the file that failed to load to `errors`.
     #[allow(clippy::type_complexity)]
     fn get_keymaps(
         config: &LapceConfig,
+        commands: &IndexMap<String, LapceCommand>,
+        errors: &mut Vec<String>,
     ) -> Result<(
         IndexMap<Vec<KeyPress>, Vec<KeyMap>>,
         IndexMap<String, Vec<KeyMap>>,
     )> {
+        let mut loader = Self::default_keymaps(config, commands);
+        // Errors in the defaults have been logged, but aren't the user's to fix
+        loader.take_errors();
+
+        if let Some((path, content)) = &config.user_keymaps {
+            loader
+                .load_from_str(content, config.core.modal)
+                .map_err(|err| anyhow!("Failed to load from {path:?}: {err}"))?;
+        }
+        errors.extend(loader.take_errors());
+
+        Ok(loader.finalize())
+    }
+
+    fn default_keymaps(
+        config: &LapceConfig,
+        commands: &IndexMap<String, LapceCommand>,
+    ) -> KeyMapLoader {
         let is_modal = config.core.modal;
 
         let mut loader = KeyMapLoader::new();
+        loader.set_leader(&config.core.leader);
+        loader.set_commands(commands.keys());
 
         if let Err(err) = loader.load_from_str(DEFAULT_KEYMAPS_COMMON, is_modal) {
             error!("Failed to load common defaults: {err}");
@@ -468,15 +756,7 @@ fn get_keymaps(
             error!("Failed to load OS defaults: {err}");
         }
 
-        if let Some(path) = Self::file() {
-            if let Ok(content) = std::fs::read_to_string(&path) {
-                if let Err(err) = loader.load_from_str(&content, is_modal) {
-                    error!("Failed to load from {path:?}: {err}");
-                }
-            }
-        }
-
-        Ok(loader.finalize())
+        loader
     }
 
     pub fn file() -> Option<PathBuf> {
@@ -540,6 +820,14 @@ pub fn update_file(keymap: &KeyMap, keys: &[KeyPress]) -> Option<()> {
                     toml_edit::value(toml_edit::Value::from(when.to_string())),
                 );
             }
+            if let Some(args) = keymap
+                .args
+                .as_deref()
+                .and_then(|args| serde_json::from_str(args).ok())
+                .and_then(|args| json_to_toml(&args))
+            {
+                table.insert("args", toml_edit::value(args));
+            }
 
             if !keys.is_empty() {
                 table.insert(
@@ -584,3 +872,131 @@ fn get_modes(toml_keymap: &toml_edit::Table) -> Modes {
         .map(Modes::parse)
         .unwrap_or_else(Modes::empty)
 }
+
+#[cfg(test)]
+mod tests {
+    use floem::keyboard::{Key, ModifiersState};
+
+    use lapce_core::mode::MotionMode;
+
+    use super::{key::KeyInput, KeyPress, KeyPressData, PendingState};
+
+    /// A key press as the layout reports it, with the character it produced.
+    fn layout_keypress(c: &str, mods: ModifiersState) -> KeyPress {
+        let key = Key::Character(c.into());
+        KeyPress {
+            mods: KeyPressData::key_modifiers(&key, mods),
+            key: KeyInput::Keyboard(key),
+        }
+    }
+
+    #[test]
+    fn test_shifted_symbols_match_unshifted_bindings() {
+        // `/` is Shift+7 on QWERTZ and `:` is unshifted on AZERTY, both should
+        // match a binding of the plain character.
+        assert_eq!(
+            layout_keypress("/", ModifiersState::SHIFT),
+            KeyPress::parse("/")[0]
+        );
+        assert_eq!(
+            layout_keypress(":", ModifiersState::empty()),
+            KeyPress::parse(":")[0]
+        );
+        assert_eq!(
+            layout_keypress(":", ModifiersState::SHIFT),
+            KeyPress::parse(":")[0]
+        );
+        // Letters keep Shift, so `shift+i` stays distinct from `i`
+        assert_eq!(
+            layout_keypress("I", ModifiersState::SHIFT),
+            KeyPress::parse("shift+I")[0]
+        );
+    }
+
+    #[test]
+    fn test_plus_key() {
+        assert_eq!(
+            layout_keypress("+", ModifiersState::CONTROL | ModifiersState::SHIFT),
+            KeyPress::parse("ctrl+shift++")[0]
+        );
+        assert_eq!(
+            layout_keypress("+", ModifiersState::empty()),
+            KeyPress::parse("+")[0]
+        );
+        assert_eq!(KeyPress::parse("ctrl++ ctrl+-").len(), 2);
+    }
+
+    #[test]
+    fn test_layout_characters_are_text() {
+        // é on AZERTY, ß on QWERTZ, and a dead key composition
+        for c in ["é", "ß", "ê"] {
+            let keypress = layout_keypress(c, ModifiersState::empty());
+            assert_eq!(KeyPressData::text_input(&keypress, None), Some(c));
+        }
+
+        let keypress = layout_keypress("?", ModifiersState::SHIFT);
+        assert_eq!(KeyPressData::text_input(&keypress, None), Some("?"));
+
+        let keypress = layout_keypress("c", ModifiersState::CONTROL);
+        assert_eq!(KeyPressData::text_input(&keypress, None), None);
+
+        let dead = KeyPress {
+            key: KeyInput::Keyboard(Key::Dead(Some('^'))),
+            mods: ModifiersState::empty(),
+        };
+        assert_eq!(KeyPressData::text_input(&dead, None), None);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_altgr_is_text() {
+        // AltGr+Q is `@` on QWERTZ, reported as Ctrl+Alt
+        let keypress =
+            layout_keypress("@", ModifiersState::CONTROL | ModifiersState::ALT);
+        let q = Key::Character("q".into());
+        assert_eq!(KeyPressData::text_input(&keypress, Some(&q)), Some("@"));
+
+        // Ctrl+Alt+Q on a layout without an AltGr character for it is a chord
+        let keypress =
+            layout_keypress("q", ModifiersState::CONTROL | ModifiersState::ALT);
+        assert_eq!(KeyPressData::text_input(&keypress, Some(&q)), None);
+    }
+
+    #[test]
+    fn test_pending_state() {
+        assert!(PendingState::default().is_empty());
+        assert_eq!(PendingState::default().to_string(), "");
+
+        let pending = PendingState {
+            count: Some(3),
+            operator: None,
+            keys: String::new(),
+        };
+        assert_eq!(pending.to_string(), "3");
+
+        let pending = PendingState {
+            count: None,
+            operator: Some(MotionMode::Delete { count: 2 }),
+            keys: "i".to_string(),
+        };
+        assert!(!pending.is_empty());
+        assert_eq!(pending.to_string(), "2d i");
+
+        let pending = PendingState {
+            count: None,
+            operator: Some(MotionMode::Yank { count: 1 }),
+            keys: "<leader> f".to_string(),
+        };
+        assert_eq!(pending.to_string(), "y <leader> f");
+
+        let pending = PendingState {
+            count: None,
+            operator: Some(MotionMode::Reflow {
+                count: 3,
+                width: 80,
+            }),
+            keys: "i".to_string(),
+        };
+        assert_eq!(pending.to_string(), "3gq i");
+    }
+}
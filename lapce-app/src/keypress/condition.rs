@@ -1,3 +1,6 @@
+use std::str::FromStr;
+
+use lapce_core::mode::Mode;
 use strum_macros::EnumString;
 
 #[derive(Debug, PartialEq, Eq)]
@@ -8,33 +11,55 @@ pub(super) enum CheckCondition<'a> {
 }
 
 impl<'a> CheckCondition<'a> {
+    /// Split off the first operand of `condition`. `&&` binds tighter than
+    /// `||`, so the left side of an `Or` never contains another `||`, and the
+    /// left side of an `And` is a single condition.
     pub(super) fn parse_first(condition: &'a str) -> Self {
-        let or = condition.match_indices("||").next();
-        let and = condition.match_indices("&&").next();
-
-        match (or, and) {
-            (None, None) => CheckCondition::Single(condition),
-            (Some((pos, _)), None) => {
-                CheckCondition::Or(&condition[..pos], &condition[pos + 2..])
-            }
-            (None, Some((pos, _))) => {
-                CheckCondition::And(&condition[..pos], &condition[pos + 2..])
-            }
-            (Some((or_pos, _)), Some((and_pos, _))) => {
-                if or_pos < and_pos {
-                    CheckCondition::Or(
-                        &condition[..or_pos],
-                        &condition[or_pos + 2..],
-                    )
-                } else {
-                    CheckCondition::And(
-                        &condition[..and_pos],
-                        &condition[and_pos + 2..],
-                    )
-                }
-            }
+        if let Some((pos, _)) = condition.match_indices("||").next() {
+            return CheckCondition::Or(&condition[..pos], &condition[pos + 2..]);
+        }
+        if let Some((pos, _)) = condition.match_indices("&&").next() {
+            return CheckCondition::And(&condition[..pos], &condition[pos + 2..]);
         }
+        CheckCondition::Single(condition)
+    }
+}
+
+/// Parse a `mode == insert` or `mode != normal` comparison, returning the
+/// mode and whether it should be equal.
+pub(super) fn parse_mode_comparison(condition: &str) -> Option<(Mode, bool)> {
+    let (lhs, rhs, equal) = if let Some((lhs, rhs)) = condition.split_once("==") {
+        (lhs, rhs, true)
+    } else if let Some((lhs, rhs)) = condition.split_once("!=") {
+        (lhs, rhs, false)
+    } else {
+        return None;
+    };
+    if lhs.trim() != "mode" {
+        return None;
     }
+    let mode = match rhs.trim() {
+        "normal" => Mode::Normal,
+        "insert" => Mode::Insert,
+        "visual" => Mode::Visual,
+        "terminal" => Mode::Terminal,
+        _ => return None,
+    };
+    Some((mode, equal))
+}
+
+/// The identifiers in `condition` which aren't known conditions, which are
+/// always false.
+pub(super) fn unknown_identifiers(condition: &str) -> Vec<&str> {
+    condition
+        .split("||")
+        .flat_map(|c| c.split("&&"))
+        .map(|c| c.trim())
+        .filter(|c| {
+            parse_mode_comparison(c).is_none()
+                && Condition::from_str(c.strip_prefix('!').unwrap_or(c)).is_err()
+        })
+        .collect()
 }
 
 #[derive(EnumString, PartialEq, Eq)]
@@ -53,6 +78,8 @@ pub enum Condition {
     ModalFocus,
     #[strum(serialize = "in_snippet")]
     InSnippet,
+    #[strum(serialize = "motion_mode")]
+    MotionMode,
     #[strum(serialize = "terminal_focus")]
     TerminalFocus,
     #[strum(serialize = "source_control_focus")]
@@ -61,6 +88,8 @@ pub enum Condition {
     PanelFocus,
     #[strum(serialize = "rename_focus")]
     RenameFocus,
+    #[strum(serialize = "color_picker_focus")]
+    ColorPickerFocus,
     #[strum(serialize = "search_active")]
     SearchActive,
     #[strum(serialize = "search_focus")]
@@ -74,11 +103,12 @@ mod test {
     use floem::keyboard::ModifiersState;
     use lapce_core::mode::Mode;
 
-    use super::Condition;
+    use super::{unknown_identifiers, Condition};
     use crate::keypress::{condition::CheckCondition, KeyPressData, KeyPressFocus};
 
     struct MockFocus {
         accepted_conditions: &'static [Condition],
+        mode: Mode,
     }
 
     impl KeyPressFocus for MockFocus {
@@ -87,7 +117,7 @@ fn check_condition(&self, condition: Condition) -> bool {
         }
 
         fn get_mode(&self) -> Mode {
-            unimplemented!()
+            self.mode
         }
 
         fn run_command(
@@ -115,19 +145,24 @@ fn test_parse() {
             CheckCondition::parse_first("foo&&bar")
         );
         assert_eq!(
-            CheckCondition::And("foo", "bar||baz"),
+            CheckCondition::Or("foo&&bar", "baz"),
             CheckCondition::parse_first("foo&&bar||baz")
         );
         assert_eq!(
-            CheckCondition::And("foo ", " bar || baz"),
+            CheckCondition::Or("foo && bar ", " baz"),
             CheckCondition::parse_first("foo && bar || baz")
         );
+        assert_eq!(
+            CheckCondition::And("foo", "bar"),
+            CheckCondition::parse_first("foo&&bar")
+        );
     }
 
     #[test]
     fn test_check_condition() {
         let focus = MockFocus {
             accepted_conditions: &[Condition::EditorFocus, Condition::ListFocus],
+            mode: Mode::Insert,
         };
 
         let test_cases = [
@@ -144,6 +179,19 @@ fn test_check_condition() {
             ("editor_focus && list_focus || baz", true),
             ("editor_focus && list_focus && baz", false),
             ("editor_focus && list_focus && !baz", true),
+            // && binds tighter than ||
+            ("baz && editor_focus || list_focus", true),
+            ("list_focus || baz && editor_focus", true),
+            ("baz || editor_focus && !list_focus", false),
+            // Unknown identifiers are false
+            ("baz", false),
+            ("!baz", true),
+            ("mode == insert", true),
+            ("mode != insert", false),
+            ("mode == normal", false),
+            ("mode == normal || editor_focus", true),
+            ("mode == insert && !editor_focus", false),
+            ("mode == bogus", false),
         ];
 
         for (condition, should_accept) in test_cases.into_iter() {
@@ -154,4 +202,14 @@ fn test_check_condition() {
             );
         }
     }
+
+    #[test]
+    fn test_unknown_identifiers() {
+        assert!(unknown_identifiers("editor_focus && !list_focus").is_empty());
+        assert!(unknown_identifiers("mode == insert || panel_focus").is_empty());
+        assert_eq!(
+            unknown_identifiers("editor_focus && !baz || mode == bogus"),
+            vec!["!baz", "mode == bogus"]
+        );
+    }
 }
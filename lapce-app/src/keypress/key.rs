@@ -9,7 +9,9 @@
 #[derive(Clone, Debug, Eq)]
 pub(crate) enum KeyInput {
     Keyboard(floem::keyboard::Key),
-    Pointer(floem::pointer::PointerButton),
+    /// A mouse button with its click count, which is always 1 for the buttons
+    /// other than the primary one.
+    Pointer(floem::pointer::PointerButton, u8),
 }
 
 impl KeyInput {
@@ -323,15 +325,30 @@ fn is_key_string(s: &str) -> bool {
         })
     }
 
-    fn mouse_from_str(s: &str) -> Option<floem::pointer::PointerButton> {
+    /// Parse a mouse button like `mouseleft` or `mouse:left`, followed by an
+    /// optional click count of the primary button, like `mouse:left:2`.
+    fn mouse_from_str(s: &str) -> Option<(floem::pointer::PointerButton, u8)> {
         use floem::pointer::PointerButton as B;
 
-        Some(match s {
-            "mousemiddle" => B::Auxiliary,
-            "mouseforward" => B::X2,
-            "mousebackward" => B::X1,
+        let (name, count) = s
+            .rsplit_once(':')
+            .and_then(|(name, count)| Some((name, count.parse::<u8>().ok()?)))
+            .unwrap_or((s, 1));
+
+        let button = match name {
+            "mouseleft" | "mouse:left" => B::Primary,
+            "mouseright" | "mouse:right" => B::Secondary,
+            "mousemiddle" | "mouse:middle" => B::Auxiliary,
+            "mouseforward" | "mouse:forward" => B::X2,
+            "mousebackward" | "mouse:back" | "mouse:backward" => B::X1,
             _ => return None,
-        })
+        };
+
+        if count == 0 || count > 3 || (count > 1 && button != B::Primary) {
+            return None;
+        }
+
+        Some((button, count))
     }
 }
 
@@ -662,10 +679,20 @@ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
                     _ => f.write_str("Unidentified"),
                 }
             }
-            Self::Pointer(B::Auxiliary) => f.write_str("MouseMiddle"),
-            Self::Pointer(B::X2) => f.write_str("MouseForward"),
-            Self::Pointer(B::X1) => f.write_str("MouseBackward"),
-            Self::Pointer(_) => f.write_str("MouseUnimplemented"),
+            Self::Pointer(button, count) => {
+                f.write_str(match button {
+                    B::Primary => "MouseLeft",
+                    B::Secondary => "MouseRight",
+                    B::Auxiliary => "MouseMiddle",
+                    B::X2 => "MouseForward",
+                    B::X1 => "MouseBackward",
+                    _ => "MouseUnimplemented",
+                })?;
+                if *count > 1 {
+                    write!(f, ":{count}")?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -678,7 +705,10 @@ fn from_str(s: &str) -> Result<Self, Self::Err> {
 
         KeyInput::keyboard_from_str(&s)
             .map(KeyInput::Keyboard)
-            .or_else(|| KeyInput::mouse_from_str(&s).map(KeyInput::Pointer))
+            .or_else(|| {
+                KeyInput::mouse_from_str(&s)
+                    .map(|(button, count)| KeyInput::Pointer(button, count))
+            })
             .ok_or(())
     }
 }
@@ -688,7 +718,10 @@ fn hash<H: Hasher>(&self, state: &mut H) {
         match self {
             Self::Keyboard(key) => key.hash(state),
             // TODO: Implement `Hash` for `druid::MouseButton`
-            Self::Pointer(btn) => (*btn as u8).hash(state),
+            Self::Pointer(btn, count) => {
+                (*btn as u8).hash(state);
+                count.hash(state);
+            }
         }
     }
 }
@@ -697,7 +730,9 @@ impl PartialEq for KeyInput {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (KeyInput::Keyboard(a), KeyInput::Keyboard(b)) => a.eq(b),
-            (KeyInput::Pointer(a), KeyInput::Pointer(b)) => a.eq(b),
+            (KeyInput::Pointer(a, a_count), KeyInput::Pointer(b, b_count)) => {
+                a.eq(b) && a_count == b_count
+            }
             _ => false,
         }
     }
@@ -1,11 +1,12 @@
+use indexmap::IndexMap;
 use lapce_core::mode::Modes;
 
 use super::KeyPress;
 
 #[derive(PartialEq, Debug)]
 pub(super) enum KeymapMatch {
-    Full(String),
-    Multiple(Vec<String>),
+    Full(KeyMap),
+    Multiple(Vec<KeyMap>),
     Prefix,
     None,
 }
@@ -16,4 +17,41 @@ pub struct KeyMap {
     pub modes: Modes,
     pub when: Option<String>,
     pub command: String,
+    /// The argument the command runs with, as JSON, which is kept as text so
+    /// keymaps can still be compared and hashed.
+    pub args: Option<String>,
+}
+
+impl KeyMap {
+    /// How specific the keymap is about where it applies, which is the number
+    /// of conditions it has, counting its modes as one.
+    pub fn specificity(&self) -> usize {
+        let conditions = self
+            .when
+            .as_ref()
+            .map(|when| when.split("&&").count())
+            .unwrap_or(0);
+        conditions + usize::from(!self.modes.is_empty())
+    }
+}
+
+/// The groups of keymaps that have the same keys, modes and condition but
+/// different commands, so only one of them can ever run.
+pub fn keymap_conflicts(
+    command_keymaps: &IndexMap<String, Vec<KeyMap>>,
+) -> Vec<Vec<KeyMap>> {
+    let mut groups: IndexMap<(&[KeyPress], Modes, Option<&str>), Vec<KeyMap>> =
+        IndexMap::new();
+    for keymap in command_keymaps.values().flatten() {
+        let group = groups
+            .entry((&keymap.key, keymap.modes, keymap.when.as_deref()))
+            .or_default();
+        if !group.iter().any(|k| k.command == keymap.command) {
+            group.push(keymap.clone());
+        }
+    }
+    groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .collect()
 }
@@ -1,13 +1,22 @@
+use std::collections::HashSet;
+
 use anyhow::{anyhow, Result};
 use indexmap::IndexMap;
+use itertools::Itertools;
 use lapce_core::mode::Modes;
-use tracing::{debug, error};
+use tracing::{debug, error, warn};
 
-use super::{keymap::KeyMap, press::KeyPress};
+use super::{condition::unknown_identifiers, keymap::KeyMap, press::KeyPress};
 
 pub struct KeyMapLoader {
     keymaps: IndexMap<Vec<KeyPress>, Vec<KeyMap>>,
     command_keymaps: IndexMap<String, Vec<KeyMap>>,
+    /// The keys that `leader` in a keymap stands for.
+    leader: Option<String>,
+    /// The commands keymaps can be bound to, if known.
+    commands: Option<HashSet<String>>,
+    /// The keymaps that couldn't be loaded.
+    errors: Vec<String>,
 }
 
 impl KeyMapLoader {
@@ -15,38 +24,85 @@ pub fn new() -> Self {
         Self {
             keymaps: Default::default(),
             command_keymaps: Default::default(),
+            leader: None,
+            commands: None,
+            errors: Vec::new(),
         }
     }
 
+    /// Take the errors of the keymaps which couldn't be loaded so far.
+    pub fn take_errors(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.errors)
+    }
+
+    pub fn set_leader(&mut self, leader: &str) -> &mut Self {
+        self.leader = Some(leader.to_string());
+        self
+    }
+
+    /// Only allow binding keys to `commands`, reporting the keymaps of any
+    /// other command as errors.
+    pub fn set_commands<'a>(
+        &mut self,
+        commands: impl IntoIterator<Item = &'a String>,
+    ) -> &mut Self {
+        self.commands = Some(commands.into_iter().cloned().collect());
+        self
+    }
+
     pub fn load_from_str<'a>(
         &'a mut self,
         s: &str,
         modal: bool,
     ) -> Result<&'a mut Self> {
         let toml_keymaps: toml_edit::Document = s.parse()?;
+        // An empty file, or one with only comments, has no keymaps
+        let Some(toml_keymaps) = toml_keymaps.get("keymaps") else {
+            return Ok(self);
+        };
         let toml_keymaps = toml_keymaps
-            .get("keymaps")
-            .and_then(|v| v.as_array_of_tables())
-            .ok_or_else(|| anyhow!("no keymaps"))?;
+            .as_array_of_tables()
+            .ok_or_else(|| anyhow!("keymaps should be an array of tables"))?;
 
         for toml_keymap in toml_keymaps {
-            let keymap = match Self::get_keymap(toml_keymap, modal) {
-                Ok(Some(keymap)) => keymap,
-                Ok(None) => {
-                    // Keymap ignored
-                    continue;
-                }
-                Err(err) => {
-                    error!("Could not parse keymap: {err}");
-                    continue;
-                }
-            };
+            let keymap =
+                match Self::get_keymap(toml_keymap, modal, self.leader.as_deref()) {
+                    Ok(Some(keymap)) => keymap,
+                    Ok(None) => {
+                        // Keymap ignored
+                        continue;
+                    }
+                    Err(err) => {
+                        error!("Could not parse keymap: {err}");
+                        self.errors.push(format!("Could not parse keymap: {err}"));
+                        continue;
+                    }
+                };
+
+            if keymap.command.eq_ignore_ascii_case("none") {
+                self.unbind_key(&keymap);
+                continue;
+            }
 
             let (command, bind) = match keymap.command.strip_prefix('-') {
                 Some(cmd) => (cmd.to_string(), false),
                 None => (keymap.command.clone(), true),
             };
 
+            if bind
+                && self
+                    .commands
+                    .as_ref()
+                    .map(|commands| !commands.contains(&command))
+                    .unwrap_or(false)
+            {
+                let key = keymap.key.iter().join(" ");
+                error!("Unknown command {command:?} for key {key:?}");
+                self.errors
+                    .push(format!("Unknown command {command:?} for key {key:?}"));
+                continue;
+            }
+
             let current_keymaps = self.command_keymaps.entry(command).or_default();
             if bind {
                 current_keymaps.push(keymap.clone());
@@ -76,6 +132,51 @@ pub fn load_from_str<'a>(
         Ok(self)
     }
 
+    /// Remove every binding of the key sequence of `unbind`, whatever its
+    /// command, limited to the same `mode` and `when` if those are given.
+    fn unbind_key(&mut self, unbind: &KeyMap) {
+        let is_keymap = |k: &KeyMap| -> bool {
+            k.key == unbind.key
+                && (unbind.modes.is_empty() || k.modes == unbind.modes)
+                && (unbind.when.is_none() || k.when == unbind.when)
+        };
+        for keymaps in self.command_keymaps.values_mut() {
+            keymaps.retain(|k| !is_keymap(k));
+        }
+        for i in 1..unbind.key.len() + 1 {
+            if let Some(keymaps) = self.keymaps.get_mut(&unbind.key[..i]) {
+                keymaps.retain(|k| !is_keymap(k));
+            }
+        }
+    }
+
+    /// Report the keymaps of the leader key on its own in normal or visual mode
+    /// if there are leader key sequences, since those only run once the wait
+    /// for the rest of a sequence times out.
+    fn check_leader(&self) {
+        let Some(leader) = self.leader.as_deref() else {
+            return;
+        };
+        let leader_keys = KeyPress::parse(leader);
+        let Some(keymaps) = self.keymaps.get(&leader_keys) else {
+            return;
+        };
+        if !keymaps.iter().any(|k| k.key.len() > leader_keys.len()) {
+            return;
+        }
+        for keymap in keymaps {
+            if keymap.key == leader_keys
+                && (keymap.modes.is_empty()
+                    || keymap.modes.intersects(Modes::NORMAL | Modes::VISUAL))
+            {
+                error!(
+                    "The leader key {leader:?} is also bound to {:?}, which only runs after the key sequence timeout",
+                    keymap.command
+                );
+            }
+        }
+    }
+
     #[allow(clippy::type_complexity)]
     pub fn finalize(
         self,
@@ -83,9 +184,11 @@ pub fn finalize(
         IndexMap<Vec<KeyPress>, Vec<KeyMap>>,
         IndexMap<String, Vec<KeyMap>>,
     ) {
+        self.check_leader();
         let Self {
             keymaps: map,
             command_keymaps: command_map,
+            ..
         } = self;
 
         (map, command_map)
@@ -94,13 +197,37 @@ pub fn finalize(
     fn get_keymap(
         toml_keymap: &toml_edit::Table,
         modal: bool,
+        leader: Option<&str>,
     ) -> Result<Option<KeyMap>> {
         let key = toml_keymap
             .get("key")
             .and_then(|v| v.as_str())
             .ok_or_else(|| anyhow!("no key in keymap"))?;
 
-        let modes = get_modes(toml_keymap);
+        let mut modes = get_modes(toml_keymap);
+
+        let has_leader = key.split(' ').any(|k| k.eq_ignore_ascii_case("leader"));
+        let key = if has_leader {
+            let leader =
+                leader.ok_or_else(|| anyhow!("no leader key for {key:?}"))?;
+            // The leader key types as usual in insert mode
+            if modes.is_empty() {
+                modes = Modes::NORMAL | Modes::VISUAL;
+            }
+            key.split(' ')
+                .map(|k| {
+                    if k.eq_ignore_ascii_case("leader") {
+                        leader
+                    } else {
+                        k
+                    }
+                })
+                .join(" ")
+        } else {
+            key.to_string()
+        };
+        let key = key.as_str();
+
         // If not using modal editing, remove keymaps that only make sense in modal.
         if !modal
             && !modes.is_empty()
@@ -111,18 +238,43 @@ fn get_keymap(
             return Ok(None);
         }
 
+        let keys = KeyPress::parse(key);
+        if keys.is_empty() || keys.len() != key.split(' ').count() {
+            return Err(anyhow!("invalid key {key:?}"));
+        }
+
+        let command = toml_keymap
+            .get("command")
+            .and_then(|c| c.as_str())
+            .map(|w| w.trim().to_string())
+            .ok_or_else(|| anyhow!("no command for key {key:?}"))?;
+
+        let when = toml_keymap
+            .get("when")
+            .and_then(|w| w.as_str())
+            .map(|w| w.to_string());
+        if let Some(when) = when.as_deref() {
+            for ident in unknown_identifiers(when) {
+                warn!("Unknown condition {ident:?} in keymap for {key:?}");
+            }
+        }
+
+        let args = match toml_keymap.get("args") {
+            Some(toml_edit::Item::Value(value)) => Some(toml_to_json(value)),
+            Some(toml_edit::Item::Table(table)) => Some(toml_to_json(
+                &toml_edit::Value::InlineTable(table.clone().into_inline_table()),
+            )),
+            Some(_) => return Err(anyhow!("invalid args for key {key:?}")),
+            None => None,
+        }
+        .map(|args| args.to_string());
+
         Ok(Some(KeyMap {
-            key: KeyPress::parse(key),
+            key: keys,
             modes,
-            when: toml_keymap
-                .get("when")
-                .and_then(|w| w.as_str())
-                .map(|w| w.to_string()),
-            command: toml_keymap
-                .get("command")
-                .and_then(|c| c.as_str())
-                .map(|w| w.trim().to_string())
-                .unwrap_or_else(|| "".to_string()),
+            when,
+            command,
+            args,
         }))
     }
 }
@@ -130,6 +282,7 @@ fn get_keymap(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::keypress::keymap::keymap_conflicts;
 
     #[test]
     fn test_keymap() {
@@ -227,6 +380,218 @@ fn test_keymap() {
         let keypress = KeyPress::parse("Ctrl+MouseMiddle");
         assert_eq!(keymaps.get(&keypress).unwrap().len(), 1);
     }
+
+    #[test]
+    fn test_mouse_keymap() {
+        let keymaps = r#"
+[[keymaps]]
+key = "ctrl+mouse:left"
+command = "goto_definition"
+
+[[keymaps]]
+key = "alt+MouseLeft:2"
+command = "select_all"
+
+[[keymaps]]
+key = "mouse:middle"
+command = "clipboard_paste"
+
+[[keymaps]]
+key = "mouse:right:2"
+command = "left"
+        "#;
+        let mut loader = KeyMapLoader::new();
+        loader.load_from_str(keymaps, true).unwrap();
+
+        let (keymaps, _) = loader.finalize();
+
+        let keypress = KeyPress::parse("Ctrl+MouseLeft");
+        assert_eq!(keymaps.get(&keypress).unwrap().len(), 1);
+        assert_eq!(keypress[0].to_string(), "Ctrl+MouseLeft");
+
+        let keypress = KeyPress::parse("alt+mouse:left:2");
+        assert_eq!(keymaps.get(&keypress).unwrap().len(), 1);
+        assert_eq!(keypress[0].to_string(), "Alt+MouseLeft:2");
+        assert!(keymaps.get(&KeyPress::parse("alt+mouse:left")).is_none());
+
+        let keypress = KeyPress::parse("MouseMiddle");
+        assert_eq!(keymaps.get(&keypress).unwrap().len(), 1);
+
+        // Only the primary button has a click count
+        assert!(KeyPress::parse("mouse:right:2").is_empty());
+        assert_eq!(keymaps.len(), 3);
+    }
+
+    #[test]
+    fn test_keymap_conflicts() {
+        let keymaps = r#"
+[[keymaps]]
+key = "ctrl+p"
+command = "palette"
+
+[[keymaps]]
+key = "ctrl+p"
+command = "palette.command"
+
+[[keymaps]]
+key = "ctrl+p"
+command = "palette.line"
+when = "editor_focus"
+
+[[keymaps]]
+key = "ctrl+w"
+command = "left"
+mode = "n"
+
+[[keymaps]]
+key = "ctrl+w"
+command = "left"
+mode = "n"
+        "#;
+        let mut loader = KeyMapLoader::new();
+        loader.load_from_str(keymaps, true).unwrap();
+
+        let (_, command_keymaps) = loader.finalize();
+        let conflicts = keymap_conflicts(&command_keymaps);
+        assert_eq!(conflicts.len(), 1);
+        let commands: Vec<&str> =
+            conflicts[0].iter().map(|k| k.command.as_str()).collect();
+        assert_eq!(commands, vec!["palette", "palette.command"]);
+    }
+
+    #[test]
+    fn test_leader_keymap() {
+        let keymaps = r#"
+[[keymaps]]
+key = "leader f f"
+command = "palette"
+
+[[keymaps]]
+key = "Leader b"
+command = "palette.command"
+mode = "n"
+        "#;
+        let mut loader = KeyMapLoader::new();
+        loader.set_leader("space");
+        loader.load_from_str(keymaps, true).unwrap();
+
+        let (keymaps, command_keymaps) = loader.finalize();
+
+        let keypress = KeyPress::parse("space f f");
+        let keymap = &keymaps.get(&keypress).unwrap()[0];
+        assert_eq!(keymap.command, "palette");
+        assert_eq!(keymap.modes, Modes::NORMAL | Modes::VISUAL);
+
+        let keypress = KeyPress::parse("space");
+        assert_eq!(keymaps.get(&keypress).unwrap().len(), 2);
+        assert_eq!(
+            command_keymaps.get("palette.command").unwrap()[0].modes,
+            Modes::NORMAL
+        );
+
+        // Leader keymaps only make sense with modal editing
+        let mut loader = KeyMapLoader::new();
+        loader.set_leader("space");
+        loader
+            .load_from_str(
+                r#"
+[[keymaps]]
+key = "leader f f"
+command = "palette"
+                "#,
+                false,
+            )
+            .unwrap();
+        let (keymaps, _) = loader.finalize();
+        assert!(keymaps.is_empty());
+    }
+
+    #[test]
+    fn test_unbind_keymap() {
+        let defaults = r#"
+[[keymaps]]
+key = "ctrl+p"
+command = "palette"
+
+[[keymaps]]
+key = "ctrl+w l"
+command = "right"
+when = "n"
+
+[[keymaps]]
+key = "ctrl+w h"
+command = "left"
+when = "n"
+        "#;
+        let user = r#"
+[[keymaps]]
+key = "ctrl+p"
+command = "none"
+
+[[keymaps]]
+key = "ctrl+w l"
+command = "none"
+when = "n"
+
+[[keymaps]]
+key = "ctrl+bogus"
+command = "left"
+        "#;
+        let mut loader = KeyMapLoader::new();
+        loader.load_from_str(defaults, true).unwrap();
+        loader.load_from_str(user, true).unwrap();
+
+        let (keymaps, command_keymaps) = loader.finalize();
+
+        let keypress = KeyPress::parse("ctrl+p");
+        assert!(keymaps.get(&keypress).unwrap().is_empty());
+        assert!(command_keymaps.get("palette").unwrap().is_empty());
+
+        let keypress = KeyPress::parse("ctrl+w");
+        assert_eq!(keymaps.get(&keypress).unwrap().len(), 1);
+        assert_eq!(command_keymaps.get("left").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_keymap_args() {
+        let keymaps = r#"
+[[keymaps]]
+key = "ctrl+`"
+command = "toggle_panel_focus"
+args = "Terminal"
+
+[[keymaps]]
+key = "ctrl+t"
+command = "toggle_panel_focus"
+args = { kind = "Terminal", count = 2 }
+
+[[keymaps]]
+key = "ctrl+g"
+command = "not_a_command"
+        "#;
+        let commands = ["toggle_panel_focus".to_string(), "palette".to_string()];
+        let mut loader = KeyMapLoader::new();
+        loader.set_commands(&commands);
+        loader.load_from_str(keymaps, true).unwrap();
+
+        let errors = loader.take_errors();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("not_a_command"));
+
+        let (keymaps, _) = loader.finalize();
+        assert!(!keymaps.contains_key(&KeyPress::parse("ctrl+g")));
+
+        let keymap = &keymaps.get(&KeyPress::parse("ctrl+`")).unwrap()[0];
+        let args: serde_json::Value =
+            serde_json::from_str(keymap.args.as_deref().unwrap()).unwrap();
+        assert_eq!(args, serde_json::json!("Terminal"));
+
+        let keymap = &keymaps.get(&KeyPress::parse("ctrl+t")).unwrap()[0];
+        let args: serde_json::Value =
+            serde_json::from_str(keymap.args.as_deref().unwrap()).unwrap();
+        assert_eq!(args, serde_json::json!({ "kind": "Terminal", "count": 2 }));
+        assert_eq!(toml_to_json(&json_to_toml(&args).unwrap()), args);
+    }
 }
 
 fn get_modes(toml_keymap: &toml_edit::Table) -> Modes {
@@ -236,3 +601,43 @@ fn get_modes(toml_keymap: &toml_edit::Table) -> Modes {
         .map(Modes::parse)
         .unwrap_or_else(Modes::empty)
 }
+
+fn toml_to_json(value: &toml_edit::Value) -> serde_json::Value {
+    match value {
+        toml_edit::Value::String(s) => s.value().clone().into(),
+        toml_edit::Value::Integer(i) => (*i.value()).into(),
+        toml_edit::Value::Float(f) => (*f.value()).into(),
+        toml_edit::Value::Boolean(b) => (*b.value()).into(),
+        toml_edit::Value::Datetime(d) => d.value().to_string().into(),
+        toml_edit::Value::Array(array) => {
+            array.iter().map(toml_to_json).collect::<Vec<_>>().into()
+        }
+        toml_edit::Value::InlineTable(table) => table
+            .iter()
+            .map(|(k, v)| (k.to_string(), toml_to_json(v)))
+            .collect::<serde_json::Map<_, _>>()
+            .into(),
+    }
+}
+
+/// The TOML value of the JSON `args` of a keymap, for writing it back to the
+/// keymaps file.
+pub(super) fn json_to_toml(value: &serde_json::Value) -> Option<toml_edit::Value> {
+    Some(match value {
+        serde_json::Value::Null => return None,
+        serde_json::Value::Bool(b) => (*b).into(),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => i.into(),
+            None => n.as_f64()?.into(),
+        },
+        serde_json::Value::String(s) => s.as_str().into(),
+        serde_json::Value::Array(array) => {
+            toml_edit::Value::Array(array.iter().filter_map(json_to_toml).collect())
+        }
+        serde_json::Value::Object(map) => toml_edit::Value::InlineTable(
+            map.iter()
+                .filter_map(|(k, v)| Some((k.as_str(), json_to_toml(v)?)))
+                .collect(),
+        ),
+    })
+}
@@ -62,9 +62,16 @@ pub fn label(&self) -> String {
     pub fn parse(key: &str) -> Vec<Self> {
         key.split(' ')
             .filter_map(|k| {
-                let (modifiers, key) = match k.rsplit_once('+') {
-                    Some(pair) => pair,
-                    None => ("", k),
+                // `+` is a key too, as in `ctrl++`
+                let (modifiers, key) = if k == "+" {
+                    ("", k)
+                } else if let Some(modifiers) = k.strip_suffix("++") {
+                    (modifiers, "+")
+                } else {
+                    match k.rsplit_once('+') {
+                        Some(pair) => pair,
+                        None => ("", k),
+                    }
                 };
 
                 let key = match key.parse().ok() {
@@ -1,40 +1,60 @@
 pub mod about;
 pub mod alert;
+pub mod alternate;
 pub mod app;
 pub mod code_action;
+pub mod color_picker;
 pub mod command;
 pub mod completion;
 pub mod config;
+pub mod crash;
 pub mod db;
+pub mod debounce;
 pub mod debug;
 pub mod doc;
+pub mod edit_preview;
 pub mod editor;
 pub mod editor_tab;
+pub mod feedback;
 pub mod file_explorer;
 pub mod find;
+pub mod first_run;
 pub mod focus_text;
 pub mod global_search;
 pub mod history;
 pub mod hover;
 pub mod id;
+pub mod job;
+pub mod jump;
 pub mod keymap;
 pub mod keypress;
 pub mod listener;
+pub mod logging;
 pub mod main_split;
 pub mod markdown;
+pub mod outline;
 pub mod palette;
 pub mod panel;
+pub mod perf;
 pub mod plugin;
+pub mod popup;
+pub mod problem;
 pub mod proxy;
 pub mod rename;
+pub mod resize;
 pub mod settings;
+pub mod signature;
 pub mod snippet;
 pub mod source_control;
+pub mod spell;
 pub mod status;
+pub mod stdin;
+pub mod task;
 pub mod terminal;
 pub mod text_area;
 pub mod text_input;
 pub mod title;
+pub mod trust;
 pub mod update;
 pub mod wave;
 pub mod web_link;
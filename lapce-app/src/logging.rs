@@ -0,0 +1,262 @@
+//! The log Lapce writes to a file of the day in the logs folder, and to stderr
+//! when it's run from a terminal, at the level of the `core.log-level` setting
+//! or of `--log-level`.
+//!
+//! The log can be followed in Lapce as it's written, in a read-only untitled
+//! file that the lines added to the log are appended to. Only whole lines are
+//! read, so that a line being written shows once it's done.
+
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use crossbeam_channel::Sender;
+use is_terminal::IsTerminal;
+use lapce_core::directory::Directory;
+use parking_lot::Mutex;
+use tracing::metadata::LevelFilter;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::{filter::Targets, reload::Handle};
+
+/// The levels the log can be set to, from the fewest messages to the most
+pub const LOG_LEVELS: &[&str] = &["off", "error", "warn", "info", "debug", "trace"];
+
+/// How often the log that's followed is checked for new lines
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The log level named `level`, for `--log-level` and the setting.
+pub fn parse_log_level(level: &str) -> Result<LevelFilter, String> {
+    match level.to_lowercase().as_str() {
+        "off" => Ok(LevelFilter::OFF),
+        "error" => Ok(LevelFilter::ERROR),
+        "warn" => Ok(LevelFilter::WARN),
+        "info" => Ok(LevelFilter::INFO),
+        "debug" => Ok(LevelFilter::DEBUG),
+        "trace" => Ok(LevelFilter::TRACE),
+        _ => Err(format!(
+            "unknown log level `{level}`, expected one of {}",
+            LOG_LEVELS.join(", ")
+        )),
+    }
+}
+
+/// What's logged at `level`: the messages of Lapce itself, not the ones of
+/// the libraries it uses.
+pub fn log_targets(level: LevelFilter) -> Targets {
+    Targets::new()
+        .with_target("lapce_app", level)
+        .with_target("lapce_proxy", level)
+}
+
+/// Start logging at `level`. The level of the file can be changed with the
+/// handle, and the guard has to be kept for as long as the file is written.
+pub fn init_logging(level: LevelFilter) -> (Handle<Targets>, WorkerGuard) {
+    use tracing_subscriber::{filter, fmt, prelude::*, reload};
+
+    let file_appender = tracing_appender::rolling::Builder::new()
+        .max_log_files(10)
+        .rotation(tracing_appender::rolling::Rotation::DAILY)
+        .filename_prefix("lapce")
+        .filename_suffix("log")
+        .build(Directory::logs_directory().expect("Failed to obtain log directory"))
+        .expect("Couldn't create rolling appender");
+    let (log_file, guard) = tracing_appender::non_blocking(file_appender);
+    let (log_file_filter, reload_handle) =
+        reload::Subscriber::new(log_targets(level));
+    let file_layer = tracing_subscriber::fmt::subscriber()
+        .with_ansi(false)
+        .with_writer(log_file)
+        .with_filter(log_file_filter);
+
+    // `LAPCE_LOG` chooses what's logged to stderr, which is nothing unless
+    // Lapce is run from a terminal
+    let console_filter_targets = match std::env::var("LAPCE_LOG") {
+        Ok(targets) => targets.parse::<filter::Targets>().unwrap_or_default(),
+        Err(_) if std::io::stderr().is_terminal() => log_targets(level),
+        Err(_) => filter::Targets::default(),
+    };
+
+    tracing_subscriber::registry()
+        .with(file_layer)
+        .with(
+            fmt::Subscriber::default()
+                .with_writer(std::io::stderr)
+                .with_filter(console_filter_targets),
+        )
+        .init();
+
+    (reload_handle, guard)
+}
+
+/// The file the log is written to today
+pub fn current_log_file() -> Option<PathBuf> {
+    let dir = Directory::logs_directory()?;
+    Some(dir.join(format!(
+        "lapce.{}.log",
+        chrono::prelude::Local::now().format("%Y-%m-%d")
+    )))
+}
+
+/// The lines read from the log that the document following it hasn't taken
+/// yet. What's read while the document is catching up is taken at once.
+#[derive(Clone, Default)]
+pub struct LogLines(Arc<Mutex<PendingLines>>);
+
+#[derive(Default)]
+struct PendingLines {
+    text: String,
+    /// Whether the log was started over, so that what the document has of it
+    /// is replaced rather than added to
+    restart: bool,
+}
+
+impl LogLines {
+    /// The lines, and whether they replace the ones taken before.
+    pub fn take(&self) -> (String, bool) {
+        let pending = std::mem::take(&mut *self.0.lock());
+        (pending.text, pending.restart)
+    }
+
+    fn push(&self, text: &str, restart: bool) {
+        let mut pending = self.0.lock();
+        if restart {
+            pending.text.clear();
+            pending.restart = true;
+        }
+        pending.text.push_str(text);
+    }
+}
+
+/// The whole lines read from the log from an offset
+#[derive(Debug, PartialEq, Eq)]
+struct LogTail {
+    text: String,
+    /// The offset to read the next lines from
+    offset: u64,
+    /// Whether the log was shorter than the offset, so that it was read from
+    /// the start
+    restarted: bool,
+}
+
+/// The whole lines of the log at `path` from `offset`, or from its start if
+/// it's been shrunk since.
+fn read_log(path: &Path, offset: u64) -> std::io::Result<LogTail> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let restarted = len < offset;
+    let start = if restarted { 0 } else { offset };
+    file.seek(SeekFrom::Start(start))?;
+    let mut bytes = Vec::new();
+    file.take(len - start).read_to_end(&mut bytes)?;
+    let complete = bytes.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1);
+    bytes.truncate(complete);
+    Ok(LogTail {
+        text: String::from_utf8_lossy(&bytes).into_owned(),
+        offset: start + complete as u64,
+        restarted,
+    })
+}
+
+/// Read the lines written to the log of the day into `lines` until `closed`
+/// is set, sending on `tx` each time there are new ones. A new day's log is
+/// read from its start, as is a log that was shrunk.
+pub fn follow_log(lines: LogLines, tx: Sender<()>, closed: Arc<AtomicBool>) {
+    let mut path = None;
+    let mut offset = 0;
+    let mut restart = false;
+    while !closed.load(Ordering::Relaxed) {
+        let current = current_log_file();
+        if current != path {
+            restart = path.is_some();
+            path = current;
+            offset = 0;
+        }
+        let tail = path.as_ref().and_then(|path| read_log(path, offset).ok());
+        if let Some(tail) = tail {
+            offset = tail.offset;
+            let restart = std::mem::take(&mut restart) || tail.restarted;
+            if restart || !tail.text.is_empty() {
+                lines.push(&tail.text, restart);
+                if tx.send(()).is_err() {
+                    return;
+                }
+            }
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use tracing::metadata::LevelFilter;
+
+    use super::{parse_log_level, read_log, LogLines, LogTail};
+
+    #[test]
+    fn test_parse_log_level() {
+        assert_eq!(parse_log_level("warn"), Ok(LevelFilter::WARN));
+        assert_eq!(parse_log_level("TRACE"), Ok(LevelFilter::TRACE));
+        assert!(parse_log_level("loud").is_err());
+    }
+
+    #[test]
+    fn test_read_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lapce.log");
+        std::fs::write(&path, "one\ntwo\nthr").unwrap();
+        let tail = read_log(&path, 0).unwrap();
+        assert_eq!(
+            tail,
+            LogTail {
+                text: "one\ntwo\n".to_string(),
+                offset: 8,
+                restarted: false,
+            }
+        );
+
+        // The line that was being written is read once it's done
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(b"ee\nfour").unwrap();
+        let tail = read_log(&path, tail.offset).unwrap();
+        assert_eq!(tail.text, "three\n");
+        assert_eq!(tail.offset, 14);
+        assert_eq!(read_log(&path, tail.offset).unwrap().text, "");
+
+        // A log shorter than what was read of it is read again from the start
+        std::fs::write(&path, "new\n").unwrap();
+        let tail = read_log(&path, tail.offset).unwrap();
+        assert_eq!(
+            tail,
+            LogTail {
+                text: "new\n".to_string(),
+                offset: 4,
+                restarted: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_log_lines() {
+        let lines = LogLines::default();
+        lines.push("a\n", false);
+        lines.push("b\n", false);
+        assert_eq!(lines.take(), ("a\nb\n".to_string(), false));
+        lines.push("c\n", false);
+        lines.push("d\n", true);
+        lines.push("e\n", false);
+        assert_eq!(lines.take(), ("d\ne\n".to_string(), true));
+        assert_eq!(lines.take(), (String::new(), false));
+    }
+}
@@ -1,35 +1,52 @@
 use std::{
-    collections::HashMap,
+    cell::Cell,
     path::{Path, PathBuf},
     rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
     time::Duration,
 };
 
 use floem::{
     action::{exec_after, save_as},
-    ext_event::create_ext_action,
+    ext_event::{create_ext_action, create_signal_from_channel},
     file::{FileDialogOptions, FileInfo},
     keyboard::ModifiersState,
     peniko::kurbo::{Point, Rect, Vec2},
-    reactive::{Memo, RwSignal, Scope},
+    reactive::{use_context, Memo, RwSignal, Scope},
 };
 use itertools::Itertools;
 use lapce_core::{
     buffer::rope_text::RopeText, command::FocusCommand, cursor::Cursor,
-    selection::Selection, syntax::Syntax,
+    editor::EditType, language::LapceLanguage, path::renamed_path,
+    selection::Selection,
+};
+use lapce_rpc::{
+    buffer::BufferId, file::FileEvent, plugin::PluginId, proxy::ProxyResponse,
+    RpcError,
 };
-use lapce_rpc::{buffer::BufferId, plugin::PluginId, proxy::ProxyResponse};
 use lapce_xi_rope::Rope;
 use lsp_types::{
-    CodeAction, CodeActionOrCommand, DiagnosticSeverity, DocumentChangeOperation,
-    DocumentChanges, OneOf, Position, TextEdit, Url, WorkspaceEdit,
+    CodeAction, CodeActionOrCommand, Command, DiagnosticSeverity,
+    DocumentChangeOperation, DocumentChanges, MessageType, OneOf, Position,
+    ShowMessageParams, TextEdit, Url, WorkspaceEdit,
 };
 use serde::{Deserialize, Serialize};
+use tracing::info;
 
 use crate::{
     alert::AlertButton,
+    alternate::{alternate_candidates, default_alternates},
     command::InternalCommand,
-    doc::{DiagnosticData, DocContent, DocHistory, Document, EditorDiagnostic},
+    crash,
+    db::LapceDb,
+    debounce::SETTLE_DELAY,
+    doc::{
+        ansi, DiagnosticData, DocContent, DocHistory, DocWork, Document,
+        EditorDiagnostic,
+    },
     editor::{
         diff::DiffEditorData,
         location::{EditorLocation, EditorPosition},
@@ -39,7 +56,13 @@
         EditorTabChild, EditorTabChildSource, EditorTabData, EditorTabInfo,
     },
     id::{DiffEditorId, EditorId, EditorTabId, KeymapId, SettingsId, SplitId},
+    job::{assert_off_ui_thread, spawn_job},
     keypress::{EventRef, KeyPressData},
+    listener::Listener,
+    logging::{follow_log, LogLines},
+    palette::kind::PaletteKind,
+    resize::move_split_border,
+    spell::{self, SpellAction},
     window_tab::{CommonData, Focus, WindowTabData},
 };
 
@@ -70,7 +93,7 @@ pub fn direction(&self) -> SplitDirection {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum SplitContent {
     EditorTab(EditorTabId),
     Split(SplitId),
@@ -119,6 +142,9 @@ pub struct SplitData {
     pub split_id: SplitId,
     pub children: Vec<SplitContent>,
     pub direction: SplitDirection,
+    /// The shares of the size of the split of the children that were
+    /// resized, the others having a share of 1
+    pub weights: im::HashMap<SplitContent, f64>,
     pub window_origin: Point,
     pub layout_rect: Rect,
 }
@@ -127,9 +153,37 @@ pub struct SplitData {
 pub struct SplitInfo {
     pub children: Vec<SplitContentInfo>,
     pub direction: SplitDirection,
+    /// The shares of the size of the split of the children, in their order
+    #[serde(default)]
+    pub weights: Vec<f64>,
 }
 
 impl SplitInfo {
+    /// Drop the editors of files which don't exist any more, along with the
+    /// editor tabs and splits that are left empty by that.
+    pub fn retain_existing_files(&mut self) {
+        let mut weights = self.weights.iter();
+        let mut retained_weights = Vec::new();
+        self.children.retain_mut(|child| {
+            let weight = weights.next();
+            let retain = match child {
+                SplitContentInfo::EditorTab(tab_info) => {
+                    tab_info.retain_existing_files();
+                    !tab_info.children.is_empty()
+                }
+                SplitContentInfo::Split(split_info) => {
+                    split_info.retain_existing_files();
+                    !split_info.children.is_empty()
+                }
+            };
+            if let (true, Some(weight)) = (retain, weight) {
+                retained_weights.push(*weight);
+            }
+            retain
+        });
+        self.weights = retained_weights;
+    }
+
     pub fn to_data(
         &self,
         data: MainSplitData,
@@ -138,16 +192,23 @@ pub fn to_data(
     ) -> RwSignal<SplitData> {
         let split_data = {
             let cx = data.scope.create_child();
+            let children: Vec<SplitContent> = self
+                .children
+                .iter()
+                .map(|child| child.to_data(data.clone(), split_id))
+                .collect();
+            let weights = children
+                .iter()
+                .copied()
+                .zip(self.weights.iter().copied())
+                .collect();
             let split_data = SplitData {
                 scope: cx,
                 split_id,
                 direction: self.direction,
                 parent_split,
-                children: self
-                    .children
-                    .iter()
-                    .map(|child| child.to_data(data.clone(), split_id))
-                    .collect(),
+                children,
+                weights,
                 window_origin: Point::ZERO,
                 layout_rect: Rect::ZERO,
             };
@@ -197,10 +258,20 @@ pub fn split_info(&self, data: &WindowTabData) -> SplitInfo {
                 .iter()
                 .map(|child| child.content_info(data))
                 .collect(),
+            weights: self
+                .children
+                .iter()
+                .map(|child| self.weight(child))
+                .collect(),
         };
         info
     }
 
+    /// The share of the size of the split of `content`
+    pub fn weight(&self, content: &SplitContent) -> f64 {
+        self.weights.get(content).copied().unwrap_or(1.0)
+    }
+
     pub fn editor_tab_index(&self, editor_tab_id: EditorTabId) -> Option<usize> {
         self.children
             .iter()
@@ -212,6 +283,28 @@ pub fn content_index(&self, content: &SplitContent) -> Option<usize> {
     }
 }
 
+/// How many of the closed editors can be reopened
+const MAX_CLOSED_EDITORS: usize = 30;
+
+/// An editor that was closed, for reopening it as it was
+#[derive(Clone)]
+pub struct ClosedEditor {
+    pub content: ClosedEditorContent,
+    /// The editor tab it was in, which it's reopened in if it's still open
+    pub editor_tab_id: EditorTabId,
+}
+
+#[derive(Clone)]
+pub enum ClosedEditorContent {
+    File {
+        path: PathBuf,
+        offset: usize,
+        scroll_offset: Vec2,
+    },
+    /// A new file closed with its changes unsaved, with its text
+    Scratch { text: Rope },
+}
+
 #[derive(Clone)]
 pub struct MainSplitData {
     pub scope: Scope,
@@ -229,6 +322,14 @@ pub struct MainSplitData {
     pub replace_editor: EditorData,
     pub locations: RwSignal<im::Vector<EditorLocation>>,
     pub current_location: RwSignal<usize>,
+    /// The editors closed, the latest first
+    pub closed_editors: RwSignal<im::Vector<ClosedEditor>>,
+    /// The two editors whose vertical scrolling is linked, so that
+    /// scrolling one scrolls the other by as many lines
+    pub scroll_link: RwSignal<Option<(EditorId, EditorId)>>,
+    /// The file switched to last with Alternate File, and the one it was
+    /// switched from, which switching from it again goes back to
+    pub last_alternate: RwSignal<Option<(PathBuf, PathBuf)>>,
     pub common: CommonData,
 }
 
@@ -247,6 +348,9 @@ pub fn new(cx: Scope, common: CommonData) -> Self {
         let scratch_docs = cx.create_rw_signal(im::HashMap::new());
         let locations = cx.create_rw_signal(im::Vector::new());
         let current_location = cx.create_rw_signal(0);
+        let closed_editors = cx.create_rw_signal(im::Vector::new());
+        let scroll_link = cx.create_rw_signal(None);
+        let last_alternate = cx.create_rw_signal(None);
         let diagnostics = cx.create_rw_signal(im::HashMap::new());
         let find_editor =
             EditorData::new_local(cx, EditorId::next(), common.clone());
@@ -292,7 +396,7 @@ pub fn new(cx: Scope, common: CommonData) -> Self {
             });
         }
 
-        Self {
+        let main_split = Self {
             scope: cx,
             root_split: SplitId::next(),
             splits,
@@ -308,8 +412,44 @@ pub fn new(cx: Scope, common: CommonData) -> Self {
             diagnostics,
             locations,
             current_location,
+            closed_editors,
+            scroll_link,
+            last_alternate,
             common,
-        }
+        };
+        main_split.listen_scroll_link();
+        main_split
+    }
+
+    /// Keep the scrolling of the linked editors in step while the link lasts.
+    /// The link is broken once either of them is closed or shows another
+    /// document.
+    fn listen_scroll_link(&self) {
+        let cx = self.scope;
+        let editors = self.editors;
+        let scroll_link = self.scroll_link;
+        cx.create_effect(move |last: Option<Option<Scope>>| {
+            if let Some(Some(scope)) = last {
+                scope.dispose();
+            }
+            let (left, right) = scroll_link.get()?;
+            let (left, right) = editors.with_untracked(|editors| {
+                Some((editors.get(&left).copied()?, editors.get(&right).copied()?))
+            })?;
+
+            let scope = cx.create_child();
+            let left_pending = Rc::new(Cell::new(None));
+            let right_pending = Rc::new(Cell::new(None));
+            follow_scroll(
+                scope,
+                left,
+                right,
+                left_pending.clone(),
+                right_pending.clone(),
+            );
+            follow_scroll(scope, right, left, right_pending, left_pending);
+            Some(scope)
+        });
     }
 
     pub fn key_down<'a>(
@@ -331,7 +471,6 @@ pub fn key_down<'a>(
                     .with_untracked(|editors| editors.get(&editor_id).copied())?;
                 let editor = editor.get_untracked();
                 keypress.key_down(event, &editor);
-                editor.get_code_actions();
             }
             EditorTabChild::DiffEditor(diff_editor_id) => {
                 let diff_editor =
@@ -344,7 +483,6 @@ pub fn key_down<'a>(
                     diff_editor.left.get_untracked()
                 };
                 keypress.key_down(event, &editor);
-                editor.get_code_actions();
             }
             EditorTabChild::Settings(_) => {
                 return None;
@@ -427,15 +565,59 @@ pub fn jump_to_location(
         self.go_to_location(location, edits);
     }
 
+    /// The path `path` is open under, which is `path` with its `.` and `..`
+    /// resolved unless it's open as it is. Other names for an open file, like
+    /// through a symlink, take the file system to find, see `go_to_location`.
+    pub fn doc_path(&self, path: PathBuf) -> PathBuf {
+        if self.docs.with_untracked(|docs| docs.contains_key(&path)) {
+            return path;
+        }
+        lapce_core::path::normalize(&path)
+    }
+
+    /// The path of the open document of the file with `file_key`, if any.
+    fn doc_path_of_key(&self, file_key: &Path) -> Option<PathBuf> {
+        self.docs.with_untracked(|docs| {
+            docs.iter()
+                .find(|(_, doc)| {
+                    doc.with_untracked(|doc| {
+                        doc.file_key.as_deref() == Some(file_key)
+                    })
+                })
+                .map(|(open, _)| open.clone())
+        })
+    }
+
+    /// Work out what tells the file at `path` apart in the background, and
+    /// keep it in `doc` if it's still at `path` by then.
+    fn set_file_key(&self, doc: RwSignal<Document>, path: PathBuf) {
+        let local = self.common.workspace.kind.is_local();
+        spawn_job(
+            self.scope,
+            {
+                let path = path.clone();
+                move || file_key(local, &path)
+            },
+            move |file_key| {
+                doc.try_update(|doc| {
+                    if doc.content.path() == Some(&path) {
+                        doc.file_key = Some(file_key);
+                    }
+                });
+            },
+        );
+    }
+
     pub fn get_doc(&self, path: PathBuf) -> (RwSignal<Document>, bool) {
         let cx = self.scope;
+        let path = self.doc_path(path);
         let doc = self.docs.with_untracked(|docs| docs.get(&path).cloned());
         if let Some(doc) = doc {
             (doc, false)
         } else {
             let diagnostic_data = self.get_diagnostic_data(&path);
 
-            let doc = Document::new(
+            let mut doc = Document::new(
                 cx,
                 path.clone(),
                 diagnostic_data,
@@ -443,7 +625,12 @@ pub fn get_doc(&self, path: PathBuf) -> (RwSignal<Document>, bool) {
                 self.common.proxy.clone(),
                 self.common.config,
             );
+            let db: Arc<LapceDb> = use_context().unwrap();
+            doc.language_override =
+                db.get_doc_language(&self.common.workspace, &path);
             let doc = cx.create_rw_signal(doc);
+            self.set_file_key(doc, path.clone());
+            Document::parse_in_background(doc);
             self.docs.update(|docs| {
                 docs.insert(path.clone(), doc);
             });
@@ -497,7 +684,12 @@ pub fn get_doc(&self, path: PathBuf) -> (RwSignal<Document>, bool) {
                     let find_result =
                         doc.with_untracked(|doc| doc.find_result.clone());
                     find_result.reset();
-                    Document::tigger_proxy_update(doc, &proxy);
+                    let proxy = proxy.clone();
+                    doc.with_untracked(|doc| doc.work.clone()).schedule(
+                        DocWork::ProxyUpdate,
+                        SETTLE_DELAY,
+                        move || Document::tigger_proxy_update(doc, &proxy),
+                    );
                     rev
                 });
             }
@@ -505,11 +697,18 @@ pub fn get_doc(&self, path: PathBuf) -> (RwSignal<Document>, bool) {
             {
                 let buffer_id = doc.with_untracked(|doc| doc.buffer_id);
                 let set_doc = doc.write_only();
-                let send = create_ext_action(cx, move |content| {
-                    set_doc.update(move |doc| {
-                        doc.init_content(content);
-                    });
-                });
+                let internal_command = self.common.internal_command;
+                let send = {
+                    let path = path.clone();
+                    create_ext_action(cx, move |(content, escapes)| {
+                        set_doc.update(move |doc| {
+                            doc.init_content(content);
+                        });
+                        if escapes {
+                            suggest_ansi_colors(internal_command, &path);
+                        }
+                    })
+                };
 
                 self.common
                     .proxy
@@ -517,7 +716,8 @@ pub fn get_doc(&self, path: PathBuf) -> (RwSignal<Document>, bool) {
                         if let Ok(ProxyResponse::NewBufferResponse { content }) =
                             result
                         {
-                            send(Rope::from(content))
+                            let escapes = ansi::has_escapes(&content);
+                            send((Rope::from(content), escapes))
                         }
                     });
             }
@@ -534,6 +734,34 @@ pub fn go_to_location(
         if self.common.focus.get_untracked() != Focus::Workbench {
             self.common.focus.set(Focus::Workbench);
         }
+        let mut location = location;
+        location.path = self.doc_path(location.path);
+        if self
+            .docs
+            .with_untracked(|docs| docs.contains_key(&location.path))
+        {
+            self.open_location(location, edits);
+            return;
+        }
+
+        // Go to the file where it's open already under another name
+        let main_split = self.clone();
+        let local = self.common.workspace.kind.is_local();
+        let path = location.path.clone();
+        spawn_job(
+            self.scope,
+            move || file_key(local, &path),
+            move |file_key| {
+                let mut location = location;
+                if let Some(path) = main_split.doc_path_of_key(&file_key) {
+                    location.path = path;
+                }
+                main_split.open_location(location, edits);
+            },
+        );
+    }
+
+    fn open_location(&self, location: EditorLocation, edits: Option<Vec<TextEdit>>) {
         let path = location.path.clone();
         let (doc, new_doc) = self.get_doc(path.clone());
 
@@ -554,29 +782,139 @@ pub fn go_to_location(
     }
 
     pub fn open_file_changes(&self, path: PathBuf) {
+        self.open_history_diff(path, "head");
+    }
+
+    /// Compare a file with how it's saved on disk, which are its unsaved
+    /// changes.
+    pub fn open_saved_changes(&self, path: PathBuf) {
+        self.open_history_diff(path, DocHistory::SAVED);
+    }
+
+    /// Switch to the counterpart of the active file, like the source of a
+    /// header or the tests of a module, by the `alternate-files` patterns of
+    /// its language. Switching again goes back to the file switched from.
+    /// When there's no counterpart, it's created if the settings say so, or
+    /// else looked for by name in the file palette.
+    pub fn open_alternate_file(&self) {
+        let Some(path) = self.active_file() else {
+            return;
+        };
+        if let Some((from, to)) = self.last_alternate.get_untracked() {
+            if to == path {
+                self.last_alternate.set(Some((to, from.clone())));
+                self.open_alternate(from);
+                return;
+            }
+        }
+
+        let config = self.common.config.get_untracked();
+        let language = LapceLanguage::from_path(&path);
+        let pairs = config
+            .language_overrides(language)
+            .and_then(|overrides| overrides.alternate_files.clone())
+            .unwrap_or_else(|| default_alternates(language));
+        let candidates = alternate_candidates(&path, &pairs);
+        let internal_command = self.common.internal_command;
+        let Some(first) = candidates.first().cloned() else {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            internal_command.send(InternalCommand::ShowMessage {
+                title: "Alternate File".to_string(),
+                message: ShowMessageParams {
+                    typ: MessageType::INFO,
+                    message: format!("{name} has no alternate file patterns"),
+                },
+            });
+            return;
+        };
+        let create_if_missing = config.files.alternate_create_if_missing;
+
+        let main_split = self.clone();
+        let send = create_ext_action(
+            self.scope,
+            move |result: Result<ProxyResponse, RpcError>| {
+                let found = match result {
+                    Ok(ProxyResponse::ReadFilesResponse { files }) => files
+                        .into_iter()
+                        .find(|(_, content)| content.is_ok())
+                        .map(|(path, _)| path),
+                    _ => None,
+                };
+                if let Some(found) = found {
+                    main_split.last_alternate.set(Some((path, found.clone())));
+                    main_split.open_alternate(found);
+                } else if create_if_missing {
+                    let name = first
+                        .file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .into_owned();
+                    let last_alternate = main_split.last_alternate;
+                    let action = Rc::new(move || {
+                        internal_command.send(InternalCommand::HideAlert);
+                        last_alternate.set(Some((path.clone(), first.clone())));
+                        internal_command.send(InternalCommand::CreateFile {
+                            path: first.clone(),
+                        });
+                    });
+                    internal_command.send(InternalCommand::ShowAlert {
+                        title: format!("Create {name}?"),
+                        msg: "There's no alternate file yet.".to_string(),
+                        buttons: vec![AlertButton {
+                            text: "Create".to_string(),
+                            action,
+                        }],
+                    });
+                } else {
+                    // The counterpart can be somewhere the patterns don't
+                    // point to
+                    let input = first
+                        .file_stem()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .into_owned();
+                    internal_command.send(InternalCommand::PaletteWithInput {
+                        kind: PaletteKind::File,
+                        input,
+                    });
+                }
+            },
+        );
+        self.common
+            .proxy
+            .read_files(candidates, move |result| send(result));
+    }
+
+    fn open_alternate(&self, path: PathBuf) {
+        self.jump_to_location(
+            EditorLocation {
+                path,
+                position: None,
+                scroll_offset: None,
+                ignore_unconfirmed: false,
+                same_editor_tab: false,
+            },
+            None,
+        );
+    }
+
+    /// Open a diff of a version of a file on the left with the file on the
+    /// right.
+    fn open_history_diff(&self, path: PathBuf, version: &str) {
         let (right, _) = self.get_doc(path.clone());
         let left = Document::new_hisotry(
             self.scope,
             DocContent::History(DocHistory {
-                path: path.clone(),
-                version: "head".to_string(),
+                path,
+                version: version.to_string(),
             }),
             self.common.find.clone(),
             self.common.proxy.clone(),
             self.common.config,
         );
         let left = left.scope.create_rw_signal(left);
-
-        let send = create_ext_action(self.scope, move |result| {
-            if let Ok(ProxyResponse::BufferHeadResponse { content, .. }) = result {
-                left.update(|doc| {
-                    doc.init_content(Rope::from(content));
-                });
-            }
-        });
-        self.common.proxy.get_buffer_head(path, move |result| {
-            send(result);
-        });
+        Document::parse_in_background(left);
+        Document::load_history(left);
 
         self.get_editor_tab_child(
             EditorTabChildSource::DiffEditor { left, right },
@@ -867,22 +1205,7 @@ fn get_editor_tab_child(
                 }
                 EditorTabChildSource::NewFileEditor => {
                     let editor_id = EditorId::next();
-                    let name = self.get_name_for_new_file();
-                    let doc_content = DocContent::Scratch {
-                        id: BufferId::next(),
-                        name: name.clone(),
-                    };
-                    let doc = Document::new_content(
-                        self.scope,
-                        doc_content,
-                        self.common.find.clone(),
-                        self.common.proxy.clone(),
-                        self.common.config,
-                    );
-                    let doc = doc.scope.create_rw_signal(doc);
-                    self.scratch_docs.update(|scratch_docs| {
-                        scratch_docs.insert(name, doc);
-                    });
+                    let doc = self.new_scratch_doc();
                     let editor = EditorData::new(
                         self.scope,
                         Some(editor_tab_id),
@@ -953,6 +1276,10 @@ fn get_editor_tab_child(
                             })
                         });
                         if !same_path {
+                            self.unlink_scroll_of(*editor_id);
+                            editor.with_untracked(|editor| {
+                                editor.save_view_state();
+                            });
                             editor.update(|editor| {
                                 editor.update_doc(*doc);
                             });
@@ -1109,7 +1436,29 @@ fn get_editor_tab_child(
         child
     }
 
+    /// Put the cursors of all the editors in the mode matching whether modal
+    /// editing is enabled, so they aren't left in normal mode without the modal
+    /// keymaps to get out of it.
+    pub fn set_modal(&self, modal: bool) {
+        let editors = self.editors.with_untracked(|editors| {
+            editors
+                .values()
+                .map(|e| e.get_untracked())
+                .collect::<Vec<_>>()
+        });
+        let diff_editors = self.diff_editors.with_untracked(|diff_editors| {
+            diff_editors
+                .values()
+                .flat_map(|d| [d.left.get_untracked(), d.right.get_untracked()])
+                .collect::<Vec<_>>()
+        });
+        for editor in editors.iter().chain(diff_editors.iter()) {
+            editor.cursor.update(|cursor| cursor.set_modal(modal));
+        }
+    }
+
     pub fn remove_editor(&self, editor_id: &EditorId) {
+        self.unlink_scroll_of(*editor_id);
         let removed_editor = self
             .editors
             .try_update(|editors| editors.remove(editor_id))
@@ -1118,6 +1467,12 @@ pub fn remove_editor(&self, editor_id: &EditorId) {
             let editor = editor.get_untracked();
             editor.save_doc_position();
 
+            self.docs.with_untracked(|docs| {
+                for doc in docs.values() {
+                    doc.with_untracked(|doc| doc.take_view_state(*editor_id));
+                }
+            });
+
             let (content, _) = editor.view.doc.with_untracked(|doc| {
                 (doc.content.clone(), doc.buffer().is_pristine())
             });
@@ -1141,13 +1496,136 @@ pub fn remove_editor(&self, editor_id: &EditorId) {
                 });
                 if !doc_exists {
                     self.scratch_docs.update(|scratch_docs| {
-                        scratch_docs.remove(&name);
+                        if let Some(doc) = scratch_docs.remove(&name) {
+                            crash::forget_unsaved(
+                                doc.with_untracked(|doc| doc.buffer_id),
+                            );
+                        }
                     });
                 }
+            } else if let DocContent::File(path) = content {
+                self.close_doc_if_unused(&path);
             }
         }
     }
 
+    /// Drop the document of `path` once no editor shows it, so the language
+    /// servers get a `didClose` for it, and a `didOpen` when it's opened
+    /// again. Documents with unsaved changes are kept.
+    fn close_doc_if_unused(&self, path: &Path) {
+        let shows_path = |editor: &RwSignal<EditorData>| {
+            editor.with_untracked(|editor| {
+                editor.view.doc.with_untracked(|doc| {
+                    doc.content.path().map(PathBuf::as_path) == Some(path)
+                })
+            })
+        };
+        let in_use = self
+            .editors
+            .with_untracked(|editors| editors.values().any(&shows_path))
+            || self.diff_editors.with_untracked(|diff_editors| {
+                diff_editors.values().any(|diff_editor| {
+                    shows_path(&diff_editor.left) || shows_path(&diff_editor.right)
+                })
+            });
+        if in_use {
+            return;
+        }
+
+        let Some(doc) = self.docs.with_untracked(|docs| docs.get(path).copied())
+        else {
+            return;
+        };
+        if !doc.with_untracked(|doc| doc.buffer().is_pristine()) {
+            return;
+        }
+        self.docs.update(|docs| {
+            docs.remove(path);
+        });
+        doc.with_untracked(|doc| {
+            // The versions start over when the document is opened again
+            doc.diagnostics.version.set(None);
+            crash::forget_unsaved(doc.buffer_id);
+        });
+        self.common.proxy.close_buffer(path.to_path_buf());
+    }
+
+    /// Write to the log which editor tab holds which editors, and which
+    /// editors are registered without being in any tab, to help track down
+    /// commands that reach the wrong editor.
+    pub fn log_editors(&self) {
+        let editors = self.editors.get_untracked();
+        let describe_editor = |editor_id: &EditorId| -> String {
+            let Some(editor) = editors.get(editor_id) else {
+                return "missing".to_string();
+            };
+            let doc = editor.with_untracked(|editor| editor.view.doc);
+            doc.with_untracked(|doc| match &doc.content {
+                DocContent::File(path) => path.display().to_string(),
+                DocContent::Local => "local".to_string(),
+                DocContent::History(history) => {
+                    format!("{} at {}", history.path.display(), history.version)
+                }
+                DocContent::Scratch { name, .. } => name.clone(),
+            })
+        };
+
+        let mut lines = Vec::new();
+        let active_editor_tab = self.active_editor_tab.get_untracked();
+        let mut in_tabs = std::collections::HashSet::new();
+        let editor_tabs = self.editor_tabs.get_untracked();
+        for (editor_tab_id, editor_tab) in editor_tabs
+            .iter()
+            .sorted_by_key(|(editor_tab_id, _)| editor_tab_id.to_raw())
+        {
+            let active = if Some(*editor_tab_id) == active_editor_tab {
+                " (active)"
+            } else {
+                ""
+            };
+            lines.push(format!("editor tab {}{active}", editor_tab_id.to_raw()));
+            editor_tab.with_untracked(|editor_tab| {
+                for (i, (_, _, child)) in editor_tab.children.iter().enumerate() {
+                    let marker = if i == editor_tab.active { "*" } else { " " };
+                    let child = match child {
+                        EditorTabChild::Editor(editor_id) => {
+                            in_tabs.insert(*editor_id);
+                            format!(
+                                "editor {}: {}",
+                                editor_id.to_raw(),
+                                describe_editor(editor_id)
+                            )
+                        }
+                        EditorTabChild::DiffEditor(id) => {
+                            format!("diff editor {}", id.to_raw())
+                        }
+                        EditorTabChild::Settings(id) => {
+                            format!("settings {}", id.to_raw())
+                        }
+                        EditorTabChild::Keymap(id) => {
+                            format!("keymap {}", id.to_raw())
+                        }
+                    };
+                    lines.push(format!("  {marker} {child}"));
+                }
+            });
+        }
+
+        for editor_id in editors
+            .keys()
+            .filter(|editor_id| !in_tabs.contains(*editor_id))
+            .sorted_by_key(|editor_id| editor_id.to_raw())
+        {
+            lines.push(format!(
+                "editor {} outside of a tab: {}",
+                editor_id.to_raw(),
+                describe_editor(editor_id)
+            ));
+        }
+
+        info!("open editors:\n{}", lines.join("\n"));
+    }
+
     pub fn jump_location_backward(&self, local: bool) {
         let (locations, current_location) = if local {
             let active_editor_tab_id = self.active_editor_tab.get_untracked();
@@ -1233,11 +1711,13 @@ pub fn jump_location_forward(&self, local: bool) {
         self.go_to_location(location, None);
     }
 
+    /// Split the editor tab `editor_tab_id` in `direction`, giving the new
+    /// editor tab, which is a copy of it.
     pub fn split(
         &self,
         direction: SplitDirection,
         editor_tab_id: EditorTabId,
-    ) -> Option<()> {
+    ) -> Option<EditorTabId> {
         let editor_tabs = self.editor_tabs.get_untracked();
         let editor_tab = editor_tabs.get(&editor_tab_id).copied()?;
 
@@ -1254,7 +1734,7 @@ pub fn split(
                 .map(|index| (index, split.children.len()))
         })?;
 
-        if split_direction == direction {
+        let new_editor_tab_id = if split_direction == direction {
             let new_editor_tab = editor_tab.with_untracked(|editor_tab| {
                 self.split_editor_tab(self.scope, split_id, editor_tab)
             })?;
@@ -1265,6 +1745,7 @@ pub fn split(
                     .children
                     .insert(index + 1, SplitContent::EditorTab(new_editor_tab_id));
             });
+            new_editor_tab_id
         } else if children_len == 1 {
             let new_editor_tab = editor_tab.with_untracked(|editor_tab| {
                 self.split_editor_tab(self.scope, split_id, editor_tab)
@@ -1277,6 +1758,7 @@ pub fn split(
                     .children
                     .push(SplitContent::EditorTab(new_editor_tab_id));
             });
+            new_editor_tab_id
         } else {
             let new_split_id = SplitId::next();
 
@@ -1300,6 +1782,7 @@ pub fn split(
                         SplitContent::EditorTab(new_editor_tab_id),
                     ],
                     direction,
+                    weights: im::HashMap::new(),
                     window_origin: Point::ZERO,
                     layout_rect: Rect::ZERO,
                 };
@@ -1310,10 +1793,19 @@ pub fn split(
             });
             split.update(|split| {
                 split.children[index] = SplitContent::Split(new_split_id);
+                if let Some(weight) = split
+                    .weights
+                    .remove(&SplitContent::EditorTab(editor_tab_id))
+                {
+                    split
+                        .weights
+                        .insert(SplitContent::Split(new_split_id), weight);
+                }
             });
-        }
+            new_editor_tab_id
+        };
 
-        Some(())
+        Some(new_editor_tab_id)
     }
 
     fn split_editor_tab(
@@ -1468,6 +1960,91 @@ pub fn split_move(
         Some(())
     }
 
+    /// Make the active editor tab `delta` pixels wider, or taller when not
+    /// `width`, by moving the border of it, or of the split it's in, that's
+    /// across the first split of the direction the size is along.
+    pub fn resize_active_editor_tab(&self, width: bool, delta: f64) -> Option<()> {
+        let editor_tab_id = self.active_editor_tab.get_untracked()?;
+        let direction = if width {
+            SplitDirection::Vertical
+        } else {
+            SplitDirection::Horizontal
+        };
+        let mut content = SplitContent::EditorTab(editor_tab_id);
+        let mut split_id = self
+            .editor_tabs
+            .with_untracked(|editor_tabs| editor_tabs.get(&editor_tab_id).copied())?
+            .with_untracked(|editor_tab| editor_tab.split);
+        loop {
+            let split = self
+                .splits
+                .with_untracked(|splits| splits.get(&split_id).copied())?;
+            let (split_direction, index, len, parent_split) =
+                split.with_untracked(|split| {
+                    (
+                        split.direction,
+                        split.content_index(&content),
+                        split.children.len(),
+                        split.parent_split,
+                    )
+                });
+            if split_direction != direction || len < 2 {
+                content = SplitContent::Split(split_id);
+                split_id = parent_split?;
+                continue;
+            }
+
+            // It grows into what's after it, or before it when it's the last
+            let index = index?;
+            let (first, delta) = if index + 1 < len {
+                (index, delta)
+            } else {
+                (index - 1, -delta)
+            };
+            let (first, second) = split.with_untracked(|split| {
+                (split.children[first], split.children[first + 1])
+            });
+            let size = |content: &SplitContent| {
+                let rect = self.content_rect(content);
+                if width {
+                    rect.width()
+                } else {
+                    rect.height()
+                }
+            };
+            let sizes = (size(&first), size(&second));
+            split.update(|split| {
+                let weights = move_split_border(
+                    (split.weight(&first), split.weight(&second)),
+                    sizes,
+                    delta,
+                );
+                split.weights.insert(first, weights.0);
+                split.weights.insert(second, weights.1);
+            });
+            return Some(());
+        }
+    }
+
+    /// The rect of the editor tab or the split `content` was last laid out in
+    fn content_rect(&self, content: &SplitContent) -> Rect {
+        match content {
+            SplitContent::EditorTab(editor_tab_id) => self
+                .editor_tabs
+                .with_untracked(|editor_tabs| {
+                    editor_tabs.get(editor_tab_id).copied()
+                })
+                .map(|editor_tab| {
+                    editor_tab.with_untracked(|editor_tab| editor_tab.layout_rect)
+                }),
+            SplitContent::Split(split_id) => self
+                .splits
+                .with_untracked(|splits| splits.get(split_id).copied())
+                .map(|split| split.with_untracked(|split| split.layout_rect)),
+        }
+        .unwrap_or(Rect::ZERO)
+    }
+
     pub fn split_exchange(&self, editor_tab_id: EditorTabId) -> Option<()> {
         let editor_tabs = self.editor_tabs.get_untracked();
         let editor_tab = editor_tabs.get(&editor_tab_id).copied()?;
@@ -1825,6 +2402,7 @@ pub fn editor_tab_child_close(
 
         match child {
             EditorTabChild::Editor(editor_id) => {
+                self.remember_closed_editor(editor_tab_id, &editor_id);
                 self.remove_editor(&editor_id);
             }
             EditorTabChild::DiffEditor(diff_editor_id) => {
@@ -1835,6 +2413,11 @@ pub fn editor_tab_child_close(
                 if let Some(diff_editor) = removed_diff_editor {
                     let editor = diff_editor.right.get_untracked();
                     editor.save_doc_position();
+                    let content =
+                        editor.view.doc.with_untracked(|doc| doc.content.clone());
+                    if let DocContent::File(path) = content {
+                        self.close_doc_if_unused(&path);
+                    }
                 }
             }
             EditorTabChild::Settings(_) => {}
@@ -1848,6 +2431,125 @@ pub fn editor_tab_child_close(
         Some(())
     }
 
+    /// Remember the editor being closed, with where its cursor and scroll
+    /// were, so it can be reopened.
+    fn remember_closed_editor(
+        &self,
+        editor_tab_id: EditorTabId,
+        editor_id: &EditorId,
+    ) -> Option<()> {
+        let editor = self
+            .editors
+            .with_untracked(|editors| editors.get(editor_id).copied())?;
+        let editor = editor.get_untracked();
+        let content = editor.view.doc.with_untracked(|doc| match &doc.content {
+            DocContent::File(path) => Some(ClosedEditorContent::File {
+                path: path.clone(),
+                offset: editor.cursor.with_untracked(|c| c.offset()),
+                scroll_offset: editor
+                    .viewport
+                    .with_untracked(|v| v.origin().to_vec2()),
+            }),
+            DocContent::Scratch { .. } if !doc.buffer().is_pristine() => {
+                Some(ClosedEditorContent::Scratch {
+                    text: doc.buffer().text().clone(),
+                })
+            }
+            _ => None,
+        })?;
+        self.closed_editors.update(|closed_editors| {
+            closed_editors.push_front(ClosedEditor {
+                content,
+                editor_tab_id,
+            });
+            closed_editors.truncate(MAX_CLOSED_EDITORS);
+        });
+        Some(())
+    }
+
+    /// Reopen the editor closed last, where its cursor and scroll were, in
+    /// the editor tab it was in if that's still open. Each time goes further
+    /// back, skipping the files that have been deleted since.
+    pub fn reopen_closed_editor(&self) {
+        let is_local = self.common.workspace.kind.is_local();
+        let mut deleted = Vec::new();
+        let closed = loop {
+            let Some(closed) = self
+                .closed_editors
+                .try_update(|closed_editors| closed_editors.pop_front())
+                .unwrap()
+            else {
+                break None;
+            };
+            match &closed.content {
+                ClosedEditorContent::File { path, .. }
+                    if is_local && !path.exists() =>
+                {
+                    deleted.push(path.display().to_string());
+                }
+                _ => break Some(closed),
+            }
+        };
+
+        if !deleted.is_empty() {
+            self.common
+                .internal_command
+                .send(InternalCommand::ShowMessage {
+                    title: "Reopen Closed Editor".to_string(),
+                    message: ShowMessageParams {
+                        typ: MessageType::INFO,
+                        message: format!(
+                            "Skipped the closed files that no longer exist: {}",
+                            deleted.join(", ")
+                        ),
+                    },
+                });
+        }
+
+        let Some(closed) = closed else {
+            return;
+        };
+        if self.editor_tabs.with_untracked(|editor_tabs| {
+            editor_tabs.contains_key(&closed.editor_tab_id)
+        }) {
+            self.active_editor_tab.set(Some(closed.editor_tab_id));
+        }
+        match closed.content {
+            ClosedEditorContent::File {
+                path,
+                offset,
+                scroll_offset,
+            } => {
+                self.jump_to_location(
+                    EditorLocation {
+                        path,
+                        position: Some(EditorPosition::Offset(offset)),
+                        scroll_offset: Some(scroll_offset),
+                        ignore_unconfirmed: true,
+                        same_editor_tab: true,
+                    },
+                    None,
+                );
+            }
+            ClosedEditorContent::Scratch { text } => {
+                let child = self.get_editor_tab_child(
+                    EditorTabChildSource::NewFileEditor,
+                    true,
+                    false,
+                );
+                if let EditorTabChild::Editor(editor_id) = child {
+                    let editor = self
+                        .editors
+                        .with_untracked(|editors| editors.get(&editor_id).copied());
+                    if let Some(editor) = editor {
+                        let doc = editor.with_untracked(|editor| editor.view.doc);
+                        doc.update(|doc| doc.reload(text, false));
+                    }
+                }
+            }
+        }
+    }
+
     pub fn editor_tab_update_layout(
         &self,
         editor_tab_id: &EditorTabId,
@@ -1869,75 +2571,332 @@ pub fn editor_tab_update_layout(
 
     pub fn run_code_action(&self, plugin_id: PluginId, action: CodeActionOrCommand) {
         match action {
-            CodeActionOrCommand::Command(_) => {}
+            CodeActionOrCommand::Command(command) => {
+                self.execute_command(plugin_id, command);
+            }
             CodeActionOrCommand::CodeAction(action) => {
-                if let Some(edit) = action.edit.as_ref() {
-                    self.apply_workspace_edit(edit);
-                } else {
+                if action.edit.is_none() && action.command.is_none() {
                     self.resolve_code_action(plugin_id, action);
+                } else {
+                    self.apply_code_action(plugin_id, action);
                 }
             }
         }
     }
 
-    /// Resolve a code action and apply its held workspace edit
+    /// Apply the edit of a code action and then run its command, which may
+    /// rely on the edit having been made
+    fn apply_code_action(&self, plugin_id: PluginId, action: CodeAction) {
+        if let Some(edit) = action.edit.as_ref() {
+            self.apply_workspace_edit(edit, "Code Action", false);
+        }
+        if let Some(command) = action.command {
+            self.execute_command(plugin_id, command);
+        }
+    }
+
+    /// Resolve a code action and apply it
     fn resolve_code_action(&self, plugin_id: PluginId, action: CodeAction) {
         let main_split = self.clone();
-        let send = create_ext_action(self.scope, move |edit| {
-            main_split.apply_workspace_edit(&edit);
+        let send = create_ext_action(self.scope, move |action| {
+            main_split.apply_code_action(plugin_id, action);
         });
         self.common
             .proxy
             .code_action_resolve(action, plugin_id, move |result| {
                 if let Ok(ProxyResponse::CodeActionResolveResponse { item }) = result
                 {
-                    if let Some(edit) = item.edit {
-                        send(edit);
-                    }
+                    send(*item);
+                }
+            });
+    }
+
+    /// Run a command of the language server. The edits it makes are sent back
+    /// as workspace edits to apply.
+    pub fn execute_command(&self, plugin_id: PluginId, command: Command) {
+        if let Some(action) = SpellAction::from_command(&command) {
+            self.run_spell_action(action);
+            return;
+        }
+
+        let internal_command = self.common.internal_command;
+        let title = command.title.clone();
+        let send = create_ext_action(
+            self.scope,
+            move |result: Result<ProxyResponse, RpcError>| {
+                if let Err(err) = result {
+                    internal_command.send(InternalCommand::ShowMessage {
+                        title,
+                        message: ShowMessageParams {
+                            typ: MessageType::ERROR,
+                            message: err.message,
+                        },
+                    });
                 }
+            },
+        );
+        self.common
+            .proxy
+            .execute_command(plugin_id, command, move |result| {
+                send(result);
             });
     }
 
-    /// Perform a workspace edit, which are from the LSP (such as code actions, or symbol renaming)
-    pub fn apply_workspace_edit(&self, edit: &WorkspaceEdit) {
+    /// Change a misspelled word of the active editor's document, if it's still
+    /// there, or add it to the dictionary.
+    fn run_spell_action(&self, action: SpellAction) {
+        match action {
+            SpellAction::Replace { range, word, to } => {
+                let Some(editor) = self.active_editor.get_untracked() else {
+                    return;
+                };
+                let (doc, cursor) =
+                    editor.with_untracked(|editor| (editor.view.doc, editor.cursor));
+                let delta = doc
+                    .try_update(|doc| {
+                        let current = (range.end <= doc.buffer().len())
+                            .then(|| doc.buffer().slice_to_cow(range.clone()));
+                        if current.as_deref() != Some(word.as_str()) {
+                            return None;
+                        }
+                        let selection = Selection::region(range.start, range.end);
+                        let (delta, _, _) = doc.do_raw_edit(
+                            &[(selection, to.as_str())],
+                            EditType::Other,
+                        );
+                        Some(delta)
+                    })
+                    .flatten();
+                if let Some(delta) = delta {
+                    cursor.update(|cursor| cursor.apply_delta(&delta));
+                }
+            }
+            SpellAction::AddWord { word } => {
+                let main_split = self.clone();
+                spawn_job(
+                    self.scope,
+                    {
+                        let word = word.clone();
+                        move || spell::add_to_dictionary(&word)
+                    },
+                    move |result| {
+                        if let Err(err) = result {
+                            let message = format!(
+                                "Couldn't add \"{word}\" to the dictionary: {err}"
+                            );
+                            main_split.common.internal_command.send(
+                                InternalCommand::ShowMessage {
+                                    title: "Spell Check".to_string(),
+                                    message: ShowMessageParams {
+                                        typ: MessageType::ERROR,
+                                        message,
+                                    },
+                                },
+                            );
+                        }
+                        for doc in main_split.all_docs() {
+                            doc.update(|doc| doc.forget_misspelling(&word));
+                        }
+                    },
+                );
+            }
+        }
+    }
+
+    /// Perform a workspace edit, which are from the LSP (such as code actions,
+    /// or symbol renaming). An edit of more than one file is shown in the
+    /// edit preview first if `editor.preview-workspace-edits` is set.
+    pub fn apply_workspace_edit(
+        &self,
+        edit: &WorkspaceEdit,
+        title: &'static str,
+        summary: bool,
+    ) {
         if let Some(DocumentChanges::Operations(_op)) =
             edit.document_changes.as_ref()
         {
             // TODO
         }
 
-        if let Some(edits) = workspace_edits(edit) {
-            for (url, edits) in edits {
-                if let Ok(path) = url.to_file_path() {
-                    let active_path = self
-                        .active_editor
-                        .get_untracked()
-                        .map(|editor| {
-                            editor.with_untracked(|editor| editor.view.doc)
-                        })
-                        .map(|doc| doc.with_untracked(|doc| doc.content.clone()))
-                        .and_then(|content| content.path().cloned());
-                    let position = if active_path.as_ref() == Some(&path) {
-                        None
-                    } else {
-                        edits
-                            .get(0)
-                            .map(|edit| EditorPosition::Position(edit.range.start))
-                    };
-                    let location = EditorLocation {
-                        path,
-                        position,
-                        scroll_offset: None,
-                        ignore_unconfirmed: true,
-                        same_editor_tab: false,
-                    };
-                    self.jump_to_location(location, Some(edits));
+        let Some(edits) = workspace_edits(edit) else {
+            return;
+        };
+
+        let mut failures = Vec::new();
+        let files = edits
+            .into_iter()
+            .filter_map(|(url, version, edits)| match url.to_file_path() {
+                Ok(path) => Some((path, version, edits)),
+                Err(_) => {
+                    failures.push(format!("{url}: not a local file"));
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        self.preview_or_apply_file_edits(files, failures, title, summary);
+    }
+
+    /// Apply the text edits of each file, or show them in the edit preview
+    /// first when there's more than one file and `editor.preview-workspace-edits`
+    /// is set.
+    pub fn preview_or_apply_file_edits(
+        &self,
+        files: Vec<(PathBuf, Option<i32>, Vec<TextEdit>)>,
+        failures: Vec<String>,
+        title: &'static str,
+        summary: bool,
+    ) {
+        let preview = self
+            .common
+            .config
+            .with_untracked(|config| config.editor.preview_workspace_edits);
+        if preview && files.len() > 1 {
+            self.common.internal_command.send(
+                InternalCommand::PreviewWorkspaceEdit {
+                    title,
+                    files,
+                    failures,
+                },
+            );
+            return;
+        }
+
+        self.apply_file_edits(files, failures, title, summary);
+    }
+
+    /// Apply the text edits of each file, with the version of the document
+    /// they were made for if known. Each open document is changed in one edit
+    /// that can be undone, and the other files are changed on disk. A file
+    /// that changed since the version the edits were made for is left alone.
+    /// The `failures` so far and the new ones are reported under `title`,
+    /// along with the files touched if `summary`.
+    pub fn apply_file_edits(
+        &self,
+        files: Vec<(PathBuf, Option<i32>, Vec<TextEdit>)>,
+        mut failures: Vec<String>,
+        title: &'static str,
+        summary: bool,
+    ) {
+        let docs = self.docs.get_untracked();
+        let mut changed = 0;
+        let mut on_disk = Vec::new();
+        for (path, version, edits) in files {
+            let doc = docs
+                .get(&path)
+                .filter(|doc| doc.with_untracked(|doc| doc.loaded()));
+            let Some(doc) = doc else {
+                on_disk.push((path, version, edits));
+                continue;
+            };
+            let outdated = version
+                .map(|version| doc.with_untracked(|doc| doc.rev() as i32 != version))
+                .unwrap_or(false);
+            if outdated {
+                failures.push(format!(
+                    "{}: it changed since the edits were made",
+                    path.display()
+                ));
+                continue;
+            }
+
+            let delta = doc.try_update(|doc| {
+                let selections = edits
+                    .iter()
+                    .map(|edit| {
+                        let selection = Selection::region(
+                            doc.buffer().offset_of_position(&edit.range.start),
+                            doc.buffer().offset_of_position(&edit.range.end),
+                        );
+                        (selection, edit.new_text.as_str())
+                    })
+                    .collect::<Vec<_>>();
+                doc.do_raw_edit(&selections, EditType::Other).0
+            });
+            if let Some(delta) = delta {
+                for editor in self.editors.get_untracked().values() {
+                    editor.with_untracked(|editor| {
+                        let on_doc = editor
+                            .view
+                            .doc
+                            .with_untracked(|doc| doc.content.path() == Some(&path));
+                        if on_doc {
+                            editor
+                                .cursor
+                                .update(|cursor| cursor.apply_delta(&delta));
+                        }
+                    });
                 }
+                changed += 1;
+            }
+        }
+
+        let internal_command = self.common.internal_command;
+        let report = move |changed: usize, failures: Vec<String>| {
+            if !summary && failures.is_empty() {
+                return;
+            }
+            let mut message = format!(
+                "Changed {changed} {}",
+                if changed == 1 { "file" } else { "files" }
+            );
+            for failure in &failures {
+                message.push('\n');
+                message.push_str(failure);
             }
+            internal_command.send(InternalCommand::ShowMessage {
+                title: title.to_string(),
+                message: ShowMessageParams {
+                    typ: if failures.is_empty() {
+                        MessageType::INFO
+                    } else {
+                        MessageType::WARNING
+                    },
+                    message,
+                },
+            });
+        };
+        if on_disk.is_empty() {
+            report(changed, failures);
+            return;
         }
+
+        let send =
+            create_ext_action(
+                self.scope,
+                move |result: Result<ProxyResponse, RpcError>| match result {
+                    Ok(ProxyResponse::ApplyTextEditsResponse {
+                        files,
+                        failures: disk_failures,
+                    }) => {
+                        let mut failures = failures.clone();
+                        failures.extend(disk_failures.into_iter().map(
+                            |(path, err)| format!("{}: {err}", path.display()),
+                        ));
+                        report(changed + files, failures)
+                    }
+                    Err(err) => {
+                        let mut failures = failures.clone();
+                        failures.push(err.message);
+                        report(changed, failures)
+                    }
+                    Ok(_) => {}
+                },
+            );
+        self.common.proxy.apply_text_edits(on_disk, move |result| {
+            send(result);
+        });
     }
 
     pub fn next_error(&self) {
+        self.jump_to_error(true);
+    }
+
+    pub fn previous_error(&self) {
+        self.jump_to_error(false);
+    }
+
+    fn jump_to_error(&self, forward: bool) {
         let file_diagnostics =
             self.diagnostics_items(DiagnosticSeverity::ERROR, false);
         if file_diagnostics.is_empty() {
@@ -1958,8 +2917,11 @@ pub fn next_error(&self) {
                 });
                 path.map(|path| (path, position))
             });
-        let (path, position) =
-            next_in_file_errors_offset(active_path, &file_diagnostics);
+        let (path, position) = if forward {
+            next_in_file_errors_offset(active_path, &file_diagnostics)
+        } else {
+            previous_in_file_errors_offset(active_path, &file_diagnostics)
+        };
         let location = EditorLocation {
             path,
             position: Some(EditorPosition::Position(position)),
@@ -2008,6 +2970,7 @@ pub fn get_diagnostic_data(&self, path: &Path) -> DiagnosticData {
         } else {
             let diagnostic_data = DiagnosticData {
                 expanded: self.scope.create_rw_signal(true),
+                version: self.scope.create_rw_signal(None),
                 diagnostics: self.scope.create_rw_signal(im::Vector::new()),
             };
             self.diagnostics.update(|d| {
@@ -2029,6 +2992,152 @@ pub fn open_file_changed(&self, path: &Path, content: &str) {
         });
     }
 
+    /// Send the whole text of the document of `path` to the proxy, which
+    /// missed an edit of it.
+    pub fn resync_buffer(&self, path: &Path) {
+        let doc = self.docs.with_untracked(|docs| docs.get(path).copied());
+        if let Some(doc) = doc {
+            doc.with_untracked(|doc| doc.resync_proxy());
+        }
+    }
+
+    /// Have the documents follow their files being renamed or deleted, or
+    /// created again after they were deleted.
+    pub fn handle_file_event(&self, event: FileEvent) {
+        match event {
+            FileEvent::Renamed { from, to } => {
+                let renamed = self.docs.with_untracked(|docs| {
+                    docs.iter()
+                        .filter_map(|(path, doc)| {
+                            Some((
+                                path.clone(),
+                                renamed_path(path, &from, &to)?,
+                                *doc,
+                            ))
+                        })
+                        .collect::<Vec<_>>()
+                });
+                if renamed.is_empty() {
+                    return;
+                }
+                self.docs.update(|docs| {
+                    for (old, new, doc) in &renamed {
+                        docs.remove(old);
+                        docs.insert(new.clone(), *doc);
+                    }
+                });
+                for (old, new, doc) in renamed {
+                    // The language servers publish the diagnostics of the
+                    // file again under its new path
+                    let diagnostics = self.get_diagnostic_data(&new);
+                    self.diagnostics.update(|d| {
+                        d.remove(&old);
+                    });
+                    self.set_file_key(doc, new.clone());
+                    doc.update(|doc| {
+                        doc.content = DocContent::File(new);
+                        doc.file_key = None;
+                        doc.orphaned = false;
+                        doc.diagnostics = diagnostics;
+                        doc.detect_language();
+                        doc.trigger_syntax_change(None);
+                    });
+                }
+            }
+            FileEvent::Deleted { path } => {
+                let docs = self.docs.with_untracked(|docs| {
+                    docs.iter()
+                        .filter(|(open, _)| open.starts_with(&path))
+                        .map(|(_, doc)| *doc)
+                        .collect::<Vec<_>>()
+                });
+                for doc in docs {
+                    doc.update(|doc| {
+                        doc.orphaned = true;
+                        doc.buffer_mut().set_dirty();
+                    });
+                }
+            }
+            FileEvent::Created { path } => {
+                if let Some(doc) =
+                    self.docs.with_untracked(|docs| docs.get(&path).copied())
+                {
+                    if doc.with_untracked(|doc| doc.orphaned) {
+                        doc.update(|doc| doc.orphaned = false);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Have what's kept of the files under `from` follow them to `to`, which
+    /// they were renamed to from Lapce, and apply the `edits` the language
+    /// servers made for the rename, which are of the files as they were.
+    pub fn path_renamed(&self, from: &Path, to: &Path, edits: Vec<WorkspaceEdit>) {
+        let rename = |path: &mut PathBuf| {
+            if let Some(new) = renamed_path(path, from, to) {
+                *path = new;
+            }
+        };
+        self.locations.update(|locations| {
+            for location in locations.iter_mut() {
+                rename(&mut location.path);
+            }
+        });
+        self.last_alternate.update(|last| {
+            if let Some((a, b)) = last {
+                rename(a);
+                rename(b);
+            }
+        });
+        let db: Arc<LapceDb> = use_context().unwrap();
+        db.rename_docs(&self.common.workspace, from, to);
+
+        let mut failures = Vec::new();
+        let mut files = Vec::new();
+        for edit in &edits {
+            for (url, version, edits) in workspace_edits(edit).unwrap_or_default() {
+                match url.to_file_path() {
+                    Ok(mut path) => {
+                        rename(&mut path);
+                        files.push((path, version, edits));
+                    }
+                    Err(_) => failures.push(format!("{url}: not a local file")),
+                }
+            }
+        }
+        if !files.is_empty() || !failures.is_empty() {
+            self.preview_or_apply_file_edits(files, failures, "Rename File", true);
+        }
+    }
+
+    /// The diff editor of the active editor tab, if it's showing one.
+    pub fn active_diff_editor(&self) -> Option<DiffEditorData> {
+        let active_editor_tab = self.active_editor_tab.get_untracked()?;
+        let editor_tab = self.editor_tabs.with_untracked(|editor_tabs| {
+            editor_tabs.get(&active_editor_tab).copied()
+        })?;
+        let (_, _, child) = editor_tab.with_untracked(|editor_tab| {
+            editor_tab.children.get(editor_tab.active).cloned()
+        })?;
+        let EditorTabChild::DiffEditor(diff_editor_id) = child else {
+            return None;
+        };
+        self.diff_editors.with_untracked(|diff_editors| {
+            diff_editors.get(&diff_editor_id).cloned()
+        })
+    }
+
+    /// The path of the file in the active editor, if it's showing one.
+    pub fn active_file(&self) -> Option<PathBuf> {
+        let editor = self.active_editor.get_untracked()?;
+        let doc = editor.with_untracked(|editor| editor.view.doc);
+        doc.with_untracked(|doc| match &doc.content {
+            DocContent::File(path) => Some(path.clone()),
+            _ => None,
+        })
+    }
+
     pub fn set_find_pattern(&self, pattern: Option<String>) {
         if let Some(pattern) = pattern {
             self.find_editor
@@ -2054,10 +3163,392 @@ pub fn open_keymap(&self) {
         self.get_editor_tab_child(EditorTabChildSource::Keymap, false, false);
     }
 
+    /// A new untitled file, named after the ones that are open.
+    fn new_scratch_doc(&self) -> RwSignal<Document> {
+        let name = self.get_name_for_new_file();
+        let doc_content = DocContent::Scratch {
+            id: BufferId::next(),
+            name: name.clone(),
+            read_only: false,
+        };
+        let doc = Document::new_content(
+            self.scope,
+            doc_content,
+            self.common.find.clone(),
+            self.common.proxy.clone(),
+            self.common.config,
+        );
+        let doc = doc.scope.create_rw_signal(doc);
+        self.scratch_docs.update(|scratch_docs| {
+            scratch_docs.insert(name, doc);
+        });
+        doc
+    }
+
+    /// Open `text` in a new untitled file, highlighted as `language`, in a
+    /// split to the right of the editor tab `editor_tab_id`.
+    pub fn open_scratch_beside(
+        &self,
+        editor_tab_id: EditorTabId,
+        text: Rope,
+        language: LapceLanguage,
+    ) -> Option<()> {
+        let doc = self.new_scratch_doc();
+        doc.update(|doc| {
+            doc.set_language(language);
+            doc.reload(text, false);
+        });
+        self.open_doc_beside(editor_tab_id, doc)
+    }
+
+    /// Open the file at `path` in a split to the right of the editor tab
+    /// `editor_tab_id`.
+    pub fn open_file_beside(
+        &self,
+        editor_tab_id: EditorTabId,
+        path: PathBuf,
+    ) -> Option<()> {
+        let (doc, _) = self.get_doc(path);
+        self.open_doc_beside(editor_tab_id, doc)
+    }
+
+    /// Split the editor tab `editor_tab_id` to the right, and show `doc` in
+    /// the editor of the new editor tab rather than the copy of the one it
+    /// was split from.
+    fn open_doc_beside(
+        &self,
+        editor_tab_id: EditorTabId,
+        doc: RwSignal<Document>,
+    ) -> Option<()> {
+        let new_editor_tab_id =
+            self.split(SplitDirection::Vertical, editor_tab_id)?;
+        let editor_tab = self.editor_tabs.with_untracked(|editor_tabs| {
+            editor_tabs.get(&new_editor_tab_id).copied()
+        })?;
+        let child = editor_tab.with_untracked(|editor_tab| {
+            editor_tab
+                .children
+                .first()
+                .map(|(_, _, child)| child.clone())
+        })?;
+        let EditorTabChild::Editor(editor_id) = child else {
+            return None;
+        };
+        let editor = self
+            .editors
+            .with_untracked(|editors| editors.get(&editor_id).copied())?;
+        editor.update(|editor| {
+            editor.update_doc(doc);
+        });
+        let modal = self.common.config.with_untracked(|c| c.core.modal);
+        editor.with_untracked(|editor| {
+            editor.cursor.set(Cursor::origin(modal));
+        });
+        self.active_editor_tab.set(Some(new_editor_tab_id));
+        self.common.focus.set(Focus::Workbench);
+        Some(())
+    }
+
     pub fn new_file(&self) {
         self.get_editor_tab_child(EditorTabChildSource::NewFileEditor, false, false);
     }
 
+    /// Open `text` in a new untitled file, as markdown, which can be closed
+    /// without being saved.
+    pub fn open_welcome(&self, text: &str) -> Option<()> {
+        let doc = self.new_untitled_doc()?;
+        doc.update(|doc| {
+            doc.set_language(LapceLanguage::Markdown);
+            doc.reload(Rope::from(text), true);
+        });
+        Some(())
+    }
+
+    /// Open a new untitled file for what's read from stdin or from the log,
+    /// read-only until it's toggled to be edited.
+    pub fn new_read_only_file(&self) -> Option<RwSignal<Document>> {
+        let doc = self.new_untitled_doc()?;
+        doc.update(|doc| {
+            if let DocContent::Scratch { read_only, .. } = &mut doc.content {
+                *read_only = true;
+            }
+        });
+        Some(doc)
+    }
+
+    /// Follow the log in a new read-only untitled file, which the lines
+    /// written to the log are added to until it's closed. The editors with
+    /// their cursor on the last line keep it there, so that they scroll along.
+    pub fn open_log(&self) {
+        let Some(doc) = self.new_read_only_file() else {
+            return;
+        };
+        let buffer_id = doc.with_untracked(|doc| doc.buffer_id);
+        let lines = LogLines::default();
+        let closed = Arc::new(AtomicBool::new(false));
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let notification = create_signal_from_channel(rx);
+        let editors = self.editors;
+        {
+            let lines = lines.clone();
+            self.scope.create_effect(move |_| {
+                if notification.get().is_none() {
+                    return;
+                }
+                let (text, restart) = lines.take();
+                let last_line = doc.with_untracked(|doc| doc.buffer().last_line());
+                let following = editors
+                    .get_untracked()
+                    .into_iter()
+                    .map(|(_, editor)| editor.get_untracked())
+                    .filter(|editor| {
+                        editor.view.doc.with_untracked(|doc| doc.buffer_id)
+                            == buffer_id
+                            && doc.with_untracked(|doc| {
+                                doc.buffer().line_of_offset(
+                                    editor.cursor.with_untracked(|c| c.offset()),
+                                )
+                            }) == last_line
+                    })
+                    .collect::<Vec<_>>();
+                doc.update(|doc| {
+                    if restart {
+                        doc.reload(Rope::from(""), true);
+                    }
+                    doc.append(&text);
+                });
+                let end = doc.with_untracked(|doc| doc.buffer().len());
+                for editor in following {
+                    editor
+                        .cursor
+                        .update(|cursor| cursor.set_offset(end, false, false));
+                }
+            });
+        }
+        {
+            let closed = closed.clone();
+            let scratch_docs = self.scratch_docs;
+            self.scope.create_effect(move |_| {
+                let open = scratch_docs.with(|scratch_docs| {
+                    scratch_docs.values().any(|doc| {
+                        doc.with_untracked(|doc| doc.buffer_id) == buffer_id
+                    })
+                });
+                if !open {
+                    closed.store(true, Ordering::Relaxed);
+                }
+            });
+        }
+        std::thread::spawn(move || {
+            follow_log(lines, tx, closed);
+        });
+    }
+
+    /// Open what was backed up of a document as Lapce panicked, as unsaved
+    /// changes of its file, or in a new untitled file if it had none.
+    pub fn restore_backup(&self, path: Option<PathBuf>, text: Rope) {
+        let Some(path) = path else {
+            if let Some(doc) = self.new_untitled_doc() {
+                doc.update(|doc| doc.reload(text, false));
+            }
+            return;
+        };
+        self.go_to_location(
+            EditorLocation {
+                path: path.clone(),
+                position: None,
+                scroll_offset: None,
+                ignore_unconfirmed: false,
+                same_editor_tab: false,
+            },
+            None,
+        );
+        let (doc, _) = self.get_doc(path);
+        // The backup takes the place of the text once the file is loaded,
+        // unless the file was saved with it before the panic
+        self.scope.create_effect(move |restored: Option<bool>| {
+            if restored == Some(true) {
+                return true;
+            }
+            if !doc.with(|doc| doc.loaded()) {
+                return false;
+            }
+            doc.update(|doc| {
+                if doc.buffer().text().to_string() != text.to_string() {
+                    doc.reload(text.clone(), false);
+                }
+            });
+            true
+        });
+    }
+
+    /// Open a new untitled file in the active editor tab.
+    fn new_untitled_doc(&self) -> Option<RwSignal<Document>> {
+        let child = self.get_editor_tab_child(
+            EditorTabChildSource::NewFileEditor,
+            false,
+            false,
+        );
+        let EditorTabChild::Editor(editor_id) = child else {
+            return None;
+        };
+        let editor = self
+            .editors
+            .with_untracked(|editors| editors.get(&editor_id).copied())?;
+        Some(editor.with_untracked(|editor| editor.view.doc))
+    }
+
+    /// Link the scrolling of the active editor and of the editor in the
+    /// editor tab next to it.
+    pub fn link_scroll(&self) -> Option<()> {
+        let editor = self.active_editor.get_untracked()?;
+        let (editor_id, editor_tab_id) =
+            editor.with_untracked(|editor| (editor.editor_id, editor.editor_tab_id));
+        let editor_tab_id = editor_tab_id?;
+        let editor_tabs = self.editor_tabs.get_untracked();
+        let split_id = editor_tabs
+            .get(&editor_tab_id)?
+            .with_untracked(|editor_tab| editor_tab.split);
+        let children = self
+            .splits
+            .get_untracked()
+            .get(&split_id)?
+            .with_untracked(|split| split.children.clone());
+        let index = children
+            .iter()
+            .position(|c| c == &SplitContent::EditorTab(editor_tab_id))?;
+        let other = children
+            .get(index + 1)
+            .or_else(|| index.checked_sub(1).and_then(|i| children.get(i)))?;
+        let other_tab = editor_tabs.get(&self.split_content_editor_tab(other)?)?;
+        let (_, _, child) = other_tab.with_untracked(|editor_tab| {
+            editor_tab.children.get(editor_tab.active).cloned()
+        })?;
+        let EditorTabChild::Editor(other_id) = child else {
+            return None;
+        };
+        self.scroll_link.set(Some((editor_id, other_id)));
+        Some(())
+    }
+
+    pub fn unlink_scroll(&self) {
+        self.scroll_link.set(None);
+    }
+
+    /// Break the scroll link if the editor is one of the linked ones.
+    fn unlink_scroll_of(&self, editor_id: EditorId) {
+        let linked = self.scroll_link.with_untracked(|link| {
+            link.map_or(false, |(a, b)| a == editor_id || b == editor_id)
+        });
+        if linked {
+            self.scroll_link.set(None);
+        }
+    }
+
+    /// The first editor tab of the split content.
+    fn split_content_editor_tab(
+        &self,
+        content: &SplitContent,
+    ) -> Option<EditorTabId> {
+        match content {
+            SplitContent::EditorTab(editor_tab_id) => Some(*editor_tab_id),
+            SplitContent::Split(split_id) => {
+                let splits = self.splits.get_untracked();
+                let split = splits.get(split_id)?;
+                let content =
+                    split.with_untracked(|split| split.children.first().copied())?;
+                self.split_content_editor_tab(&content)
+            }
+        }
+    }
+
+    pub fn toggle_ansi_colors(&self) {
+        let Some(editor) = self.active_editor.get_untracked() else {
+            return;
+        };
+        let doc = editor.with_untracked(|editor| editor.view.doc);
+        doc.update(|doc| doc.toggle_ansi());
+    }
+
+    /// Turn the spell checking of the active editor's document on or off.
+    pub fn toggle_spell_check(&self) {
+        let Some(editor) = self.active_editor.get_untracked() else {
+            return;
+        };
+        let doc = editor.with_untracked(|editor| editor.view.doc);
+        doc.update(|doc| doc.toggle_spell_check());
+    }
+
+    /// Show or hide the whitespace in the active editor, leaving the other
+    /// editors of its document as they are.
+    pub fn toggle_whitespace(&self) {
+        let Some(editor) = self.active_editor.get_untracked() else {
+            return;
+        };
+        let options = editor.with_untracked(|editor| editor.view.options);
+        let config = self.common.config.get_untracked();
+        options.update(|options| options.toggle_whitespace(&config));
+    }
+
+    /// Show or hide the line numbers of the active editor.
+    pub fn toggle_line_numbers(&self) {
+        let Some(editor) = self.active_editor.get_untracked() else {
+            return;
+        };
+        let options = editor.with_untracked(|editor| editor.view.options);
+        let config = self.common.config.get_untracked();
+        options.update(|options| options.toggle_line_numbers(&config));
+    }
+
+    /// Start or stop checking the spelling of the open documents, after the
+    /// settings changed.
+    pub fn update_spell_check(&self) {
+        for doc in self.all_docs() {
+            if doc.with_untracked(|doc| doc.spell_check_changed()) {
+                doc.update(|doc| doc.update_spell_check());
+            }
+        }
+    }
+
+    /// Detect the languages of the open files again, after the file
+    /// associations changed, for the ones whose language changed to be
+    /// highlighted and handled by the language servers of the new one.
+    pub fn detect_languages(&self) {
+        for doc in self.docs.get_untracked().values() {
+            doc.update(|doc| {
+                if doc.detect_language() {
+                    doc.trigger_syntax_change(None);
+                }
+            });
+        }
+    }
+
+    /// The documents of the open files and of the untitled ones.
+    fn all_docs(&self) -> Vec<RwSignal<Document>> {
+        let mut docs = self
+            .docs
+            .get_untracked()
+            .values()
+            .copied()
+            .collect::<Vec<_>>();
+        docs.extend(self.scratch_docs.get_untracked().values().copied());
+        docs
+    }
+
+    /// Let the active editor's untitled file be edited if it's read-only, or
+    /// make it read-only if it isn't.
+    pub fn toggle_read_only(&self) {
+        let Some(editor) = self.active_editor.get_untracked() else {
+            return;
+        };
+        let doc = editor.with_untracked(|editor| editor.view.doc);
+        doc.update(|doc| {
+            if let DocContent::Scratch { read_only, .. } = &mut doc.content {
+                *read_only = !*read_only;
+            }
+        });
+    }
+
     pub fn save_as(
         &self,
         doc: RwSignal<Document>,
@@ -2069,7 +3560,7 @@ pub fn save_as(
                 doc.buffer_id,
                 doc.content.clone(),
                 doc.rev(),
-                doc.buffer().to_string(),
+                doc.raw_text(),
             )
         });
         match doc_content {
@@ -2079,10 +3570,9 @@ pub fn save_as(
                     create_ext_action(self.scope, move |result| {
                         if let Ok(_r) = result {
                             doc.update(|doc| {
-                                let syntax = Syntax::init(&path);
                                 doc.content = DocContent::File(path.clone());
                                 doc.buffer_mut().set_pristine();
-                                doc.set_syntax(syntax);
+                                doc.detect_language();
                                 doc.trigger_syntax_change(None);
                             });
                             action();
@@ -2445,6 +3935,7 @@ pub fn move_editor_tab_child_to_new_split(
                 parent_split: Some(to_split_id),
                 children: new_split_children,
                 direction: split.direction(),
+                weights: im::HashMap::new(),
                 window_origin: Point::ZERO,
                 layout_rect: Rect::ZERO,
             };
@@ -2470,9 +3961,49 @@ pub fn move_editor_tab_child_to_new_split(
     }
 }
 
-fn workspace_edits(edit: &WorkspaceEdit) -> Option<HashMap<Url, Vec<TextEdit>>> {
+/// What tells the file at `path` apart, whatever name it's opened under.
+fn file_key(local: bool, path: &Path) -> PathBuf {
+    // Remote files can't be looked at from here, and the case of their names
+    // matters
+    if local {
+        assert_off_ui_thread("Resolving a path");
+        lapce_core::path::file_key(path, lapce_core::path::CASE_INSENSITIVE)
+    } else {
+        lapce_core::path::normalize(path)
+    }
+}
+
+/// Point out that the file at `path` has ANSI escape sequences, which can be
+/// shown as colors instead.
+fn suggest_ansi_colors(internal_command: Listener<InternalCommand>, path: &Path) {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    internal_command.send(InternalCommand::ShowMessage {
+        title: "ANSI Colors".to_string(),
+        message: ShowMessageParams {
+            typ: MessageType::INFO,
+            message: format!(
+                "{name} has ANSI escape sequences, which \"Toggle ANSI Colors\" \
+                 shows as colors"
+            ),
+        },
+    });
+}
+
+/// The edits to each file, with the version of the document they were made for
+/// if the server gave it.
+fn workspace_edits(
+    edit: &WorkspaceEdit,
+) -> Option<Vec<(Url, Option<i32>, Vec<TextEdit>)>> {
     if let Some(changes) = edit.changes.as_ref() {
-        return Some(changes.clone());
+        return Some(
+            changes
+                .iter()
+                .map(|(url, edits)| (url.clone(), None, edits.clone()))
+                .collect(),
+        );
     }
 
     let changes = edit.document_changes.as_ref()?;
@@ -2482,6 +4013,7 @@ fn workspace_edits(edit: &WorkspaceEdit) -> Option<HashMap<Url, Vec<TextEdit>>>
             .map(|e| {
                 (
                     e.text_document.uri.clone(),
+                    e.text_document.version,
                     e.edits
                         .iter()
                         .map(|e| match e {
@@ -2491,13 +4023,14 @@ fn workspace_edits(edit: &WorkspaceEdit) -> Option<HashMap<Url, Vec<TextEdit>>>
                         .collect(),
                 )
             })
-            .collect::<HashMap<Url, Vec<TextEdit>>>(),
+            .collect(),
         DocumentChanges::Operations(ops) => ops
             .iter()
             .filter_map(|o| match o {
                 DocumentChangeOperation::Op(_op) => None,
                 DocumentChangeOperation::Edit(e) => Some((
                     e.text_document.uri.clone(),
+                    e.text_document.version,
                     e.edits
                         .iter()
                         .map(|e| match e {
@@ -2507,7 +4040,7 @@ fn workspace_edits(edit: &WorkspaceEdit) -> Option<HashMap<Url, Vec<TextEdit>>>
                         .collect(),
                 )),
             })
-            .collect::<HashMap<Url, Vec<TextEdit>>>(),
+            .collect(),
     };
     Some(edits)
 }
@@ -2546,3 +4079,77 @@ fn next_in_file_errors_offset(
         file_diagnostics[0].2[0].diagnostic.range.start,
     )
 }
+
+fn previous_in_file_errors_offset(
+    active_path: Option<(PathBuf, Position)>,
+    file_diagnostics: &[(PathBuf, RwSignal<bool>, Vec<EditorDiagnostic>)],
+) -> (PathBuf, Position) {
+    if let Some((active_path, position)) = active_path {
+        for (current_path, _, diagnostics) in file_diagnostics.iter().rev() {
+            if &active_path == current_path {
+                for diagnostic in diagnostics.iter().rev() {
+                    if diagnostic.diagnostic.range.start.line < position.line
+                        || (diagnostic.diagnostic.range.start.line == position.line
+                            && diagnostic.diagnostic.range.start.character
+                                < position.character)
+                    {
+                        return (
+                            (*current_path).clone(),
+                            diagnostic.diagnostic.range.start,
+                        );
+                    }
+                }
+            }
+            if current_path < &active_path {
+                return (
+                    (*current_path).clone(),
+                    diagnostics[diagnostics.len() - 1].diagnostic.range.start,
+                );
+            }
+        }
+    }
+
+    let (path, _, diagnostics) = &file_diagnostics[file_diagnostics.len() - 1];
+    (
+        path.clone(),
+        diagnostics[diagnostics.len() - 1].diagnostic.range.start,
+    )
+}
+
+/// Scroll `follower` by as much as `leader` is scrolled vertically. A view
+/// that's scrolled to follow the other has its target in its `pending`, so
+/// that the move isn't sent back.
+fn follow_scroll(
+    cx: Scope,
+    leader: RwSignal<EditorData>,
+    follower: RwSignal<EditorData>,
+    leader_pending: Rc<Cell<Option<f64>>>,
+    follower_pending: Rc<Cell<Option<f64>>>,
+) {
+    let viewport = leader.with_untracked(|editor| editor.viewport);
+    let (follower_viewport, follower_scroll_to) =
+        follower.with_untracked(|editor| (editor.viewport, editor.scroll_to));
+    cx.create_effect(move |last: Option<f64>| {
+        let y = viewport.with(|viewport| viewport.y0);
+        let Some(last) = last else {
+            return y;
+        };
+        if let Some(target) = leader_pending.take() {
+            // It's following the other view if it moved towards the target,
+            // even if it couldn't reach it at the end of the document
+            if (target - last) * (y - last) > 0.0 {
+                return y;
+            }
+        }
+        let delta = y - last;
+        if delta != 0.0 {
+            let origin = follower_viewport.get_untracked().origin();
+            let target = (origin.y + delta).max(0.0);
+            if target != origin.y {
+                follower_pending.set(Some(target));
+                follower_scroll_to.set(Some(Vec2::new(origin.x, target)));
+            }
+        }
+        y
+    });
+}
@@ -21,11 +21,22 @@ pub fn parse_markdown(
     line_height: f64,
     config: &LapceConfig,
 ) -> Vec<MarkdownContent> {
+    parse_markdown_in(text, line_height, None, config)
+}
+
+/// Parse markdown about code in `language`, which its code blocks that don't
+/// name a language are highlighted as.
+pub fn parse_markdown_in(
+    text: &str,
+    line_height: f64,
+    language: Option<LapceLanguage>,
+    config: &LapceConfig,
+) -> Vec<MarkdownContent> {
+    let default_language = language;
     let mut res = Vec::new();
 
     let mut current_text = String::new();
-    let code_font_family: Vec<FamilyOwned> =
-        FamilyOwned::parse_list(&config.editor.font_family).collect();
+    let code_font_family = config.editor.font_family();
 
     let default_attrs = Attrs::new()
         .color(*config.get_color(LapceColor::EDITOR_FOREGROUND))
@@ -87,12 +98,14 @@ pub fn parse_markdown(
 
                     match &tag {
                         Tag::CodeBlock(kind) => {
-                            let language =
-                                if let CodeBlockKind::Fenced(language) = kind {
+                            let language = match kind {
+                                CodeBlockKind::Fenced(language)
+                                    if !language.is_empty() =>
+                                {
                                     md_language_to_lapce_language(language)
-                                } else {
-                                    None
-                                };
+                                }
+                                _ => default_language,
+                            };
 
                             highlight_as_code(
                                 &mut attr_list,
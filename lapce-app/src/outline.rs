@@ -0,0 +1,532 @@
+use std::{collections::HashSet, path::PathBuf, sync::atomic};
+
+use floem::{
+    ext_event::create_ext_action,
+    keyboard::ModifiersState,
+    reactive::{Memo, RwSignal, Scope},
+};
+use lapce_core::{buffer::rope_text::RopeText, mode::Mode, symbol::scan_symbols};
+use lapce_rpc::proxy::ProxyResponse;
+use lsp_types::{DocumentSymbol, DocumentSymbolResponse, Position, SymbolKind};
+
+use crate::{
+    command::{CommandExecuted, CommandKind, InternalCommand, LapceCommand},
+    debounce::SETTLE_DELAY,
+    doc::{DocContent, DocWork, Document},
+    editor::{
+        location::{EditorLocation, EditorPosition},
+        EditorData,
+    },
+    id::EditorId,
+    keypress::{condition::Condition, KeyPressFocus},
+    window_tab::CommonData,
+};
+
+/// A symbol of the outline. The symbols are kept in the order of a
+/// depth-first walk of the tree, so that the ones nested in a symbol follow
+/// it.
+#[derive(Clone)]
+pub struct OutlineItem {
+    pub name: String,
+    pub detail: Option<String>,
+    pub kind: SymbolKind,
+    pub range: lsp_types::Range,
+    pub selection_range: lsp_types::Range,
+    pub depth: usize,
+    /// The index of the symbol this one is nested in
+    pub parent: Option<usize>,
+    /// The index after the last symbol nested in this one
+    pub end: usize,
+    /// The names of the symbols down to this one, which stay the same when
+    /// the symbols are refreshed, unlike the index
+    pub key: String,
+}
+
+impl OutlineItem {
+    pub fn has_children(&self, index: usize) -> bool {
+        self.end > index + 1
+    }
+
+    fn contains(&self, position: Position) -> bool {
+        self.range.start <= position && position <= self.range.end
+    }
+}
+
+/// The symbols of the document in the active editor, as a tree that can be
+/// narrowed down and collapsed.
+#[derive(Clone)]
+pub struct OutlineData {
+    /// The text the outline is narrowed down to
+    pub filter_editor: EditorData,
+    /// The lowercased content of `filter_editor`
+    pub filter: Memo<String>,
+    /// The file the symbols are of
+    pub path: RwSignal<Option<PathBuf>>,
+    pub items: RwSignal<im::Vector<OutlineItem>>,
+    /// Bumped whenever `items` is replaced, so that the rows of the old
+    /// symbols aren't reused for the new ones
+    pub generation: RwSignal<usize>,
+    /// The keys of the symbols whose nested symbols are hidden
+    pub collapsed: RwSignal<HashSet<String>>,
+    /// The indexes of the symbols shown, after filtering and collapsing
+    pub rows: Memo<im::Vector<usize>>,
+    /// The innermost symbol the cursor of the active editor is in
+    pub active: Memo<Option<usize>>,
+    pub common: CommonData,
+}
+
+impl KeyPressFocus for OutlineData {
+    fn get_mode(&self) -> Mode {
+        Mode::Insert
+    }
+
+    fn check_condition(&self, condition: Condition) -> bool {
+        matches!(condition, Condition::PanelFocus)
+    }
+
+    fn run_command(
+        &self,
+        command: &LapceCommand,
+        count: Option<usize>,
+        mods: ModifiersState,
+    ) -> CommandExecuted {
+        match &command.kind {
+            CommandKind::Workbench(_) => {}
+            CommandKind::Focus(_) => {}
+            CommandKind::Edit(_)
+            | CommandKind::Move(_)
+            | CommandKind::MultiSelection(_) => {
+                self.filter_editor.run_command(command, count, mods);
+            }
+            CommandKind::MotionMode(_) => {}
+        }
+        CommandExecuted::Yes
+    }
+
+    fn receive_char(&self, c: &str) {
+        self.filter_editor.receive_char(c);
+    }
+}
+
+impl OutlineData {
+    pub fn new(
+        cx: Scope,
+        active_editor: Memo<Option<RwSignal<EditorData>>>,
+        common: CommonData,
+    ) -> Self {
+        let filter_editor =
+            EditorData::new_local(cx, EditorId::next(), common.clone());
+        let doc = filter_editor.view.doc;
+        let filter = cx.create_memo(move |_| {
+            doc.with(|doc| doc.buffer().to_string().trim().to_lowercase())
+        });
+        let path = cx.create_rw_signal(None);
+        let items = cx.create_rw_signal(im::Vector::new());
+        let collapsed = cx.create_rw_signal(HashSet::new());
+        let rows = cx.create_memo(move |_| {
+            filter.with(|filter| {
+                items.with(|items| {
+                    collapsed
+                        .with(|collapsed| visible_rows(items, collapsed, filter))
+                })
+            })
+        });
+        let active = cx.create_memo(move |_| {
+            let editor = active_editor.get()?;
+            let (doc, cursor) =
+                editor.with(|editor| (editor.view.doc, editor.cursor));
+            let offset = cursor.with(|cursor| cursor.offset());
+            let position = doc.with_untracked(|doc| {
+                let is_outlined = match &doc.content {
+                    DocContent::File(doc_path) => {
+                        path.with(|path| path.as_ref() == Some(doc_path))
+                    }
+                    _ => false,
+                };
+                is_outlined.then(|| doc.buffer().offset_to_position(offset))
+            })?;
+            items.with(|items| innermost_symbol(items, position))
+        });
+
+        let outline = Self {
+            filter_editor,
+            filter,
+            path,
+            items,
+            generation: cx.create_rw_signal(0),
+            collapsed,
+            rows,
+            active,
+            common,
+        };
+
+        {
+            let outline = outline.clone();
+            cx.create_effect(move |_| {
+                let doc = active_editor
+                    .get()
+                    .map(|editor| editor.with(|editor| editor.view.doc));
+                outline.refresh(doc);
+            });
+        }
+
+        // Reveal the symbol the cursor is in when it's in a collapsed one
+        cx.create_effect(move |_| {
+            let Some(active) = active.get() else {
+                return;
+            };
+            let ancestors = items.with_untracked(|items| {
+                let mut ancestors = Vec::new();
+                let mut parent = items.get(active).and_then(|item| item.parent);
+                while let Some(index) = parent {
+                    let Some(item) = items.get(index) else {
+                        break;
+                    };
+                    ancestors.push(item.key.clone());
+                    parent = item.parent;
+                }
+                ancestors
+            });
+            if collapsed.with_untracked(|collapsed| {
+                ancestors.iter().any(|key| collapsed.contains(key))
+            }) {
+                collapsed.update(|collapsed| {
+                    for key in &ancestors {
+                        collapsed.remove(key);
+                    }
+                });
+            }
+        });
+
+        outline
+    }
+
+    /// Request the symbols of the document again, right away when it's
+    /// another one than the symbols are of, or else once the typing settles.
+    fn refresh(&self, doc: Option<RwSignal<Document>>) {
+        let file = doc.and_then(|doc| {
+            doc.with(|doc| match &doc.content {
+                DocContent::File(path) => {
+                    Some((path.clone(), doc.rev(), doc.buffer().atomic_rev()))
+                }
+                _ => None,
+            })
+            .map(|(path, rev, atomic_rev)| (doc, path, rev, atomic_rev))
+        });
+        let Some((doc, path, rev, atomic_rev)) = file else {
+            if self.path.with_untracked(|path| path.is_some()) {
+                self.path.set(None);
+                self.set_items(im::Vector::new());
+            }
+            return;
+        };
+
+        let is_new_path = self.path.with_untracked(|p| p.as_ref() != Some(&path));
+        if is_new_path {
+            self.path.set(Some(path.clone()));
+            self.collapsed.update(|collapsed| collapsed.clear());
+            self.set_items(im::Vector::new());
+        }
+
+        let outline = self.clone();
+        let send = {
+            let atomic_rev = atomic_rev.clone();
+            create_ext_action(self.common.scope, move |result| {
+                if atomic_rev.load(atomic::Ordering::Acquire) != rev {
+                    return;
+                }
+                let items =
+                    if let Ok(ProxyResponse::GetDocumentSymbols { resp }) = result {
+                        outline_items(resp)
+                    } else {
+                        // No language server gives the symbols of the document
+                        let symbols = doc
+                            .with_untracked(|doc| scan_symbols(doc.buffer().text()));
+                        outline_items(DocumentSymbolResponse::Nested(symbols))
+                    };
+                outline.set_items(items);
+            })
+        };
+        let proxy = self.common.proxy.clone();
+        if is_new_path {
+            proxy.get_document_symbols(path, move |result| {
+                send(result);
+            });
+            return;
+        }
+
+        // The outline may have moved on to another document by then
+        let outline_path = self.path;
+        doc.with_untracked(|doc| doc.work.clone()).schedule(
+            DocWork::Outline,
+            SETTLE_DELAY,
+            move || {
+                let is_current = outline_path.try_with_untracked(
+                    |p| matches!(p, Some(Some(p)) if p == &path),
+                );
+                if !is_current || atomic_rev.load(atomic::Ordering::Acquire) != rev {
+                    return;
+                }
+                proxy.get_document_symbols(path, move |result| {
+                    send(result);
+                });
+            },
+        );
+    }
+
+    fn set_items(&self, items: im::Vector<OutlineItem>) {
+        self.items.set(items);
+        self.generation.update(|generation| *generation += 1);
+    }
+
+    pub fn toggle_collapsed(&self, index: usize) {
+        let Some(key) = self
+            .items
+            .with_untracked(|items| items.get(index).map(|item| item.key.clone()))
+        else {
+            return;
+        };
+        self.collapsed.update(|collapsed| {
+            if !collapsed.remove(&key) {
+                collapsed.insert(key);
+            }
+        });
+    }
+
+    pub fn jump_to(&self, index: usize) {
+        let (Some(path), Some(item)) = (
+            self.path.get_untracked(),
+            self.items.with_untracked(|items| items.get(index).cloned()),
+        ) else {
+            return;
+        };
+        self.common
+            .internal_command
+            .send(InternalCommand::JumpToLocation {
+                location: EditorLocation {
+                    path,
+                    position: Some(EditorPosition::Position(
+                        item.selection_range.start,
+                    )),
+                    scroll_offset: None,
+                    ignore_unconfirmed: false,
+                    same_editor_tab: false,
+                },
+            });
+    }
+}
+
+/// The symbols of the response as outline items. Flat lists are nested by
+/// which symbols' ranges hold the others.
+pub fn outline_items(resp: DocumentSymbolResponse) -> im::Vector<OutlineItem> {
+    fn push_nested(
+        symbols: Vec<DocumentSymbol>,
+        depth: usize,
+        parent: Option<usize>,
+        items: &mut im::Vector<OutlineItem>,
+    ) {
+        for symbol in symbols {
+            let index = items.len();
+            let key = outline_key(items, parent, &symbol.name);
+            items.push_back(OutlineItem {
+                name: symbol.name,
+                detail: symbol.detail,
+                kind: symbol.kind,
+                range: symbol.range,
+                selection_range: symbol.selection_range,
+                depth,
+                parent,
+                end: index + 1,
+                key,
+            });
+            push_nested(
+                symbol.children.unwrap_or_default(),
+                depth + 1,
+                Some(index),
+                items,
+            );
+            let end = items.len();
+            items[index].end = end;
+        }
+    }
+
+    let mut items = im::Vector::new();
+    match resp {
+        DocumentSymbolResponse::Nested(symbols) => {
+            push_nested(symbols, 0, None, &mut items);
+        }
+        DocumentSymbolResponse::Flat(mut symbols) => {
+            symbols.sort_by_key(|symbol| symbol.location.range.start);
+            // The symbols holding the one being added
+            let mut stack: Vec<usize> = Vec::new();
+            for symbol in symbols {
+                let range = symbol.location.range;
+                while let Some(&parent) = stack.last() {
+                    let parent_range = items[parent].range;
+                    if parent_range.start <= range.start
+                        && range.end <= parent_range.end
+                    {
+                        break;
+                    }
+                    stack.pop();
+                    items[parent].end = items.len();
+                }
+                let index = items.len();
+                let parent = stack.last().copied();
+                let key = outline_key(&items, parent, &symbol.name);
+                items.push_back(OutlineItem {
+                    name: symbol.name,
+                    detail: symbol.container_name,
+                    kind: symbol.kind,
+                    range,
+                    selection_range: range,
+                    depth: stack.len(),
+                    parent,
+                    end: index + 1,
+                    key,
+                });
+                stack.push(index);
+            }
+            let len = items.len();
+            for index in stack {
+                items[index].end = len;
+            }
+        }
+    }
+    items
+}
+
+fn outline_key(
+    items: &im::Vector<OutlineItem>,
+    parent: Option<usize>,
+    name: &str,
+) -> String {
+    match parent.and_then(|parent| items.get(parent)) {
+        Some(parent) => format!("{}\n{name}", parent.key),
+        None => name.to_string(),
+    }
+}
+
+/// The innermost symbol that `position` is in.
+fn innermost_symbol(
+    items: &im::Vector<OutlineItem>,
+    position: Position,
+) -> Option<usize> {
+    let mut found = None;
+    let mut index = 0;
+    while let Some(item) = items.get(index) {
+        if item.contains(position) {
+            found = Some(index);
+            index += 1;
+        } else {
+            index = item.end;
+        }
+    }
+    found
+}
+
+/// The indexes of the symbols shown. With a filter, those are the symbols
+/// whose name has it and the ones holding them, whether collapsed or not.
+fn visible_rows(
+    items: &im::Vector<OutlineItem>,
+    collapsed: &HashSet<String>,
+    filter: &str,
+) -> im::Vector<usize> {
+    let mut rows = im::Vector::new();
+    if filter.is_empty() {
+        let mut index = 0;
+        while let Some(item) = items.get(index) {
+            rows.push_back(index);
+            index = if collapsed.contains(&item.key) {
+                item.end
+            } else {
+                index + 1
+            };
+        }
+        return rows;
+    }
+
+    let mut shown = vec![false; items.len()];
+    for (index, item) in items.iter().enumerate() {
+        if !item.name.to_lowercase().contains(filter) {
+            continue;
+        }
+        let mut next = Some(index);
+        while let Some(index) = next {
+            if shown[index] {
+                break;
+            }
+            shown[index] = true;
+            next = items[index].parent;
+        }
+    }
+    rows.extend(
+        shown
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, shown)| shown.then_some(index)),
+    );
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use lsp_types::{
+        DocumentSymbolResponse, Location, Position, Range, SymbolInformation,
+        SymbolKind, Url,
+    };
+
+    use super::{innermost_symbol, outline_items, visible_rows};
+
+    #[allow(deprecated)]
+    fn symbol(name: &str, start: u32, end: u32) -> SymbolInformation {
+        SymbolInformation {
+            name: name.to_string(),
+            kind: SymbolKind::FUNCTION,
+            tags: None,
+            deprecated: None,
+            location: Location {
+                uri: Url::parse("file:///a.rs").unwrap(),
+                range: Range::new(Position::new(start, 0), Position::new(end, 1)),
+            },
+            container_name: None,
+        }
+    }
+
+    #[test]
+    fn test_outline_items() {
+        let items = outline_items(DocumentSymbolResponse::Flat(vec![
+            symbol("c", 6, 7),
+            symbol("a", 0, 5),
+            symbol("b", 1, 2),
+            symbol("d", 3, 4),
+        ]));
+        let names: Vec<_> = items.iter().map(|item| item.name.as_str()).collect();
+        assert_eq!(names, vec!["a", "b", "d", "c"]);
+        let depths: Vec<_> = items.iter().map(|item| item.depth).collect();
+        assert_eq!(depths, vec![0, 1, 1, 0]);
+        let ends: Vec<_> = items.iter().map(|item| item.end).collect();
+        assert_eq!(ends, vec![3, 2, 3, 4]);
+        assert_eq!(items[2].parent, Some(0));
+        assert_eq!(items[2].key, "a\nd");
+
+        assert_eq!(innermost_symbol(&items, Position::new(3, 0)), Some(2));
+        assert_eq!(innermost_symbol(&items, Position::new(5, 0)), Some(0));
+        assert_eq!(innermost_symbol(&items, Position::new(6, 0)), Some(3));
+        assert_eq!(innermost_symbol(&items, Position::new(8, 0)), None);
+
+        let mut collapsed = HashSet::new();
+        let rows = |collapsed: &HashSet<String>, filter: &str| {
+            visible_rows(&items, collapsed, filter)
+                .into_iter()
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(rows(&collapsed, ""), vec![0, 1, 2, 3]);
+        assert_eq!(rows(&collapsed, "d"), vec![0, 2]);
+        collapsed.insert("a".to_string());
+        assert_eq!(rows(&collapsed, ""), vec![0, 3]);
+        assert_eq!(rows(&collapsed, "b"), vec![0, 1]);
+    }
+}
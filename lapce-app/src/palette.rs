@@ -1,34 +1,41 @@
 use std::{
     cell::RefCell,
+    cmp::Reverse,
     collections::{HashMap, HashSet},
-    path::PathBuf,
+    path::{Component, Path, PathBuf},
     rc::Rc,
     sync::{
-        atomic::{AtomicU64, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
         Arc,
     },
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 use anyhow::Result;
 use crossbeam_channel::{Receiver, Sender, TryRecvError};
 use floem::{
+    action::exec_after,
     ext_event::{create_ext_action, create_signal_from_channel},
     keyboard::ModifiersState,
     reactive::{use_context, ReadSignal, RwSignal, Scope},
 };
 use itertools::Itertools;
 use lapce_core::{
-    buffer::rope_text::RopeText, command::FocusCommand, language::LapceLanguage,
-    mode::Mode, movement::Movement, selection::Selection, syntax::Syntax,
+    buffer::rope_text::{RopeText, RopeTextRef},
+    command::FocusCommand,
+    language::LapceLanguage,
+    mode::Mode,
+    movement::Movement,
+    selection::Selection,
 };
-use lapce_rpc::proxy::ProxyResponse;
+use lapce_rpc::{file::natural_cmp, proxy::ProxyResponse};
 use lapce_xi_rope::Rope;
-use lsp_types::DocumentSymbolResponse;
+use lsp_types::{DocumentSymbolResponse, MessageType, ShowMessageParams};
 use nucleo::Utf32Str;
 use strum::{EnumMessage, IntoEnumIterator};
 
 use self::{
+    command_history::{CommandHistory, CommandHistoryEntry},
     item::{PaletteItem, PaletteItemContent},
     kind::PaletteKind,
 };
@@ -43,17 +50,31 @@
         EditorData,
     },
     id::EditorId,
-    keypress::{condition::Condition, KeyPressData, KeyPressFocus},
+    job::spawn_job,
+    keypress::{
+        condition::Condition,
+        keymap::{keymap_conflicts, KeyMap},
+        KeyPressData, KeyPressFocus,
+    },
     main_split::MainSplitData,
     proxy::path_from_url,
     source_control::SourceControlData,
+    task::task_configs,
     window_tab::{CommonData, Focus},
     workspace::{LapceWorkspace, LapceWorkspaceType, SshHost},
 };
 
+pub mod command_history;
 pub mod item;
 pub mod kind;
 
+/// How long the input has to settle before the workspace symbols are queried
+const WORKSPACE_SYMBOL_DELAY: Duration = Duration::from_millis(200);
+
+/// The item of the language palette that has the language of the file
+/// detected again, rather than the one chosen for it
+const DETECT_LANGUAGE: &str = "Detect From the File";
+
 #[derive(Clone, PartialEq, Eq)]
 pub enum PaletteStatus {
     Inactive,
@@ -65,13 +86,97 @@ pub enum PaletteStatus {
 pub struct PaletteInput {
     pub input: String,
     pub kind: PaletteKind,
+    /// The position typed after the path in the file palette, as in
+    /// `src/main.rs:10:5`, which the file is opened at.
+    pub position: Option<EditorPosition>,
 }
 
 impl PaletteInput {
     /// Update the current input in the palette, and the kind of palette it is
     pub fn update_input(&mut self, input: String, kind: PaletteKind) {
         self.kind = kind.get_palette_kind(&input);
-        self.input = self.kind.get_input(&input).to_string();
+        let input = self.kind.get_input(&input);
+        let (input, position) = if self.kind == PaletteKind::File {
+            split_line_col(input)
+        } else {
+            (input, None)
+        };
+        self.input = input.to_string();
+        self.position = position;
+    }
+}
+
+/// Split a trailing 1-based `:line` or `:line:column` off `input`. A trailing
+/// `:` is split off too, so the files don't all stop matching while the
+/// position is being typed.
+fn split_line_col(input: &str) -> (&str, Option<EditorPosition>) {
+    let Some((rest, last)) = input.rsplit_once(':') else {
+        return (input, None);
+    };
+    if last.is_empty() {
+        return split_line_col(rest);
+    }
+    let Ok(last) = last.parse::<usize>() else {
+        return (input, None);
+    };
+
+    if let Some((path, line)) = rest.rsplit_once(':') {
+        if let Ok(line) = line.parse::<usize>() {
+            let position = lsp_types::Position {
+                line: line.saturating_sub(1) as u32,
+                character: last.saturating_sub(1) as u32,
+            };
+            return (path, Some(EditorPosition::Position(position)));
+        }
+    }
+    (rest, Some(EditorPosition::Line(last.saturating_sub(1))))
+}
+
+/// Where the file typed in the file palette is created in `workspace`, or
+/// why it can't be.
+fn new_file_path(workspace: Option<&Path>, input: &str) -> Result<PathBuf, String> {
+    let Some(workspace) = workspace else {
+        return Err("Open a folder to create files in".to_string());
+    };
+    let input = input.trim();
+    if input.is_empty() {
+        return Err("Type the path of the file to create".to_string());
+    }
+    if input.ends_with(['/', '\\']) {
+        return Err(format!("\"{input}\" isn't the path of a file"));
+    }
+    if let Some(c) = input.chars().find(|c| {
+        c.is_control() || matches!(c, '<' | '>' | ':' | '"' | '|' | '?' | '*')
+    }) {
+        return Err(format!("A file name can't have {c:?} in it"));
+    }
+    let path = Path::new(input);
+    for component in path.components() {
+        match component {
+            Component::Normal(_) | Component::CurDir => {}
+            Component::ParentDir => {
+                return Err(format!(
+                    "\"{input}\" goes out of the workspace with \"..\""
+                ));
+            }
+            Component::RootDir | Component::Prefix(_) => {
+                return Err(format!("\"{input}\" isn't relative to the workspace"));
+            }
+        }
+    }
+    Ok(workspace.join(path))
+}
+
+/// The item of the file at `path` in `workspace`, which is shown without the
+/// workspace's path to avoid clutter.
+fn file_item(workspace: &LapceWorkspace, full_path: PathBuf) -> PaletteItem {
+    let path = workspace.display_path(&full_path);
+    let filter_text = path.to_str().unwrap_or("").to_string();
+    PaletteItem {
+        content: PaletteItemContent::File { path, full_path },
+        filter_text,
+        score: 0,
+        indices: Vec::new(),
     }
 }
 
@@ -92,8 +197,11 @@ pub struct PaletteData {
     pub keypress: ReadSignal<KeyPressData>,
     /// Listened on for which entry in the palette has been clicked
     pub clicked_index: RwSignal<Option<usize>>,
-    pub executed_commands: Rc<RefCell<HashMap<String, Instant>>>,
+    /// The commands run from the palette, the latest first
+    pub command_history: Rc<RefCell<CommandHistory>>,
     pub executed_run_configs: Rc<RefCell<HashMap<(RunDebugMode, String), Instant>>>,
+    /// The workspace symbols picked, by their name and file
+    pub executed_workspace_symbols: Rc<RefCell<HashMap<(String, PathBuf), Instant>>>,
     pub main_split: MainSplitData,
     pub references: RwSignal<Vec<EditorLocation>>,
     pub source_control: SourceControlData,
@@ -116,6 +224,7 @@ pub fn new(
         let input = cx.create_rw_signal(PaletteInput {
             input: "".to_string(),
             kind: PaletteKind::File,
+            position: None,
         });
         let kind = cx.create_rw_signal(PaletteKind::File);
         let input_editor =
@@ -126,6 +235,17 @@ pub fn new(
         let has_preview = cx.create_rw_signal(false);
         let run_id = cx.create_rw_signal(0);
         let run_id_counter = Arc::new(AtomicU64::new(0));
+        // Whether the items with the same score are sorted naturally, which is
+        // read by the thread filtering them
+        let natural_sort = Arc::new(AtomicBool::new(true));
+        {
+            let natural_sort = natural_sort.clone();
+            let config = common.config;
+            cx.create_effect(move |_| {
+                let natural = config.with(|config| config.files.natural_sort);
+                natural_sort.store(natural, Ordering::Relaxed);
+            });
+        }
 
         let (run_tx, run_rx) = crossbeam_channel::unbounded();
         {
@@ -159,8 +279,9 @@ pub fn new(
         let (resp_tx, resp_rx) = crossbeam_channel::unbounded();
         {
             let run_id = run_id_counter.clone();
+            let natural_sort = natural_sort.clone();
             std::thread::spawn(move || {
-                Self::update_process(run_id, run_rx, resp_tx);
+                Self::update_process(run_id, natural_sort, run_rx, resp_tx);
             });
         }
         let (filtered_items, set_filtered_items) =
@@ -169,11 +290,40 @@ pub fn new(
             let resp = create_signal_from_channel(resp_rx);
             let run_id = run_id.read_only();
             let input = input.read_only();
+            let items = items.read_only();
+            let workspace = workspace.clone();
             cx.create_effect(move |_| {
-                if let Some((filter_run_id, filter_input, new_items)) = resp.get() {
+                if let Some((filter_run_id, filter_input, mut new_items)) =
+                    resp.get()
+                {
                     if run_id.get_untracked() == filter_run_id
                         && input.get_untracked().input == filter_input
                     {
+                        // A path that matches no file is offered to be created,
+                        // once the files are in. What has spaces is more
+                        // likely a query than a path.
+                        if new_items.is_empty()
+                            && input.with_untracked(|i| i.kind == PaletteKind::File)
+                            && items.with_untracked(|items| !items.is_empty())
+                            && !filter_input.trim().contains(char::is_whitespace)
+                        {
+                            if let Ok(full_path) = new_file_path(
+                                workspace.path.as_deref(),
+                                &filter_input,
+                            ) {
+                                new_items.push_back(PaletteItem {
+                                    content: PaletteItemContent::CreateFile {
+                                        full_path,
+                                    },
+                                    filter_text: format!(
+                                        "Create file '{}'",
+                                        filter_input.trim()
+                                    ),
+                                    score: 0,
+                                    indices: Vec::new(),
+                                });
+                            }
+                        }
                         set_filtered_items.set(new_items);
                         index.set(0);
                     }
@@ -183,6 +333,7 @@ pub fn new(
 
         let clicked_index = cx.create_rw_signal(Option::<usize>::None);
 
+        let db: Arc<LapceDb> = use_context().unwrap();
         let palette = Self {
             run_id_counter,
             main_split,
@@ -199,8 +350,11 @@ pub fn new(
             kind,
             keypress,
             clicked_index,
-            executed_commands: Rc::new(RefCell::new(HashMap::new())),
+            command_history: Rc::new(RefCell::new(
+                db.get_command_history().unwrap_or_default(),
+            )),
             executed_run_configs: Rc::new(RefCell::new(HashMap::new())),
+            executed_workspace_symbols: Rc::new(RefCell::new(HashMap::new())),
             references,
             source_control,
             common,
@@ -296,18 +450,24 @@ pub fn new(
 
     /// Start and focus the palette for the given kind.
     pub fn run(&self, kind: PaletteKind) {
+        self.run_with_input(kind, "");
+    }
+
+    /// Start and focus the palette for the given kind, with `input` typed in
+    /// after the kind's symbol prefix.
+    pub fn run_with_input(&self, kind: PaletteKind, input: &str) {
         self.common.focus.set(Focus::Palette);
         self.status.set(PaletteStatus::Started);
-        let symbol = kind.symbol();
+        let input = format!("{}{input}", kind.symbol());
         self.kind.set(kind);
-        // Refresh the palette input with only the symbol prefix, losing old content.
+        // Refresh the palette input, losing old content.
         self.input_editor
             .view
             .doc
-            .update(|doc| doc.reload(Rope::from(symbol), true));
+            .update(|doc| doc.reload(Rope::from(input.as_str()), true));
         self.input_editor
             .cursor
-            .update(|cursor| cursor.set_insert(Selection::caret(symbol.len())));
+            .update(|cursor| cursor.set_insert(Selection::caret(input.len())));
     }
 
     /// Execute the internal behavior of the palette for the given kind. This ignores updating and
@@ -329,6 +489,9 @@ fn run_inner(&self, kind: PaletteKind) {
             PaletteKind::Command => {
                 self.get_commands();
             }
+            PaletteKind::Keymap => {
+                self.get_keymaps();
+            }
             PaletteKind::Workspace => {
                 self.get_workspaces();
             }
@@ -347,6 +510,9 @@ fn run_inner(&self, kind: PaletteKind) {
             PaletteKind::RunAndDebug => {
                 self.get_run_configs();
             }
+            PaletteKind::Task => {
+                self.get_tasks();
+            }
             PaletteKind::ColorTheme => {
                 self.get_color_themes();
                 self.preselect_matching(
@@ -363,8 +529,9 @@ fn run_inner(&self, kind: PaletteKind) {
                 self.get_languages();
                 if let Some(editor) = self.main_split.active_editor.get_untracked() {
                     let doc = editor.with_untracked(|editor| (editor.view.doc));
-                    let language =
-                        doc.with_untracked(|doc| doc.syntax().language.to_string());
+                    let language = doc.with_untracked(|doc| {
+                        doc.syntax().language.name().to_string()
+                    });
                     self.preselect_matching(language.as_str());
                 }
             }
@@ -416,35 +583,54 @@ fn get_files(&self) {
             create_ext_action(self.common.scope, move |items: Vec<PathBuf>| {
                 let items = items
                     .into_iter()
-                    .map(|path| {
-                        let full_path = path.clone();
-                        // Strip the workspace prefix off the path, to avoid clutter
-                        let path =
-                            if let Some(workspace_path) = workspace.path.as_ref() {
-                                path.strip_prefix(workspace_path)
-                                    .unwrap_or(&full_path)
-                                    .to_path_buf()
-                            } else {
-                                path
-                            };
-                        let filter_text = path.to_str().unwrap_or("").to_string();
-                        PaletteItem {
-                            content: PaletteItemContent::File { path, full_path },
-                            filter_text,
-                            score: 0,
-                            indices: Vec::new(),
-                        }
-                    })
+                    .map(|path| file_item(&workspace, path))
                     .collect::<im::Vector<_>>();
                 set_items.set(items);
             });
-        self.common.proxy.get_files(move |result| {
+        // Without a workspace, list the folder of the file being edited
+        let folder = if self.workspace.path.is_none() {
+            self.main_split
+                .active_editor
+                .get_untracked()
+                .and_then(|editor| {
+                    editor
+                        .with_untracked(|editor| editor.view.doc)
+                        .with_untracked(|doc| doc.content.path().cloned())
+                        .and_then(|path| path.parent().map(|p| p.to_path_buf()))
+                })
+        } else {
+            None
+        };
+        let exclude = self.common.config.get_untracked().files.exclude.clone();
+        self.common.proxy.get_files(folder, exclude, move |result| {
             if let Ok(ProxyResponse::GetFilesResponse { items }) = result {
                 send(items);
             }
         });
     }
 
+    /// Have the files listed follow the files added to and removed from the
+    /// workspace while the palette is open.
+    pub fn update_files(&self, added: Vec<PathBuf>, removed: Vec<PathBuf>) {
+        if self.status.get_untracked() == PaletteStatus::Inactive
+            || self.kind.get_untracked() != PaletteKind::File
+        {
+            return;
+        }
+        let removed = removed.into_iter().collect::<HashSet<_>>();
+        self.items.update(|items| {
+            items.retain(|item| match &item.content {
+                PaletteItemContent::File { full_path, .. } => {
+                    !removed.contains(full_path)
+                }
+                _ => true,
+            });
+            for path in added {
+                items.push_back(file_item(&self.workspace, path));
+            }
+        });
+    }
+
     /// Initialize the palette with the lines in the current document.
     fn get_lines(&self) {
         let editor = self.main_split.active_editor.get_untracked();
@@ -489,22 +675,29 @@ fn get_commands(&self) {
         const EXCLUDED_ITEMS: &[&str] = &["palette.command"];
 
         let items = self.keypress.with_untracked(|keypress| {
-            // Get all the commands we've executed, and sort them by how recently they were
-            // executed. Ignore commands without descriptions.
-            let mut items: im::Vector<PaletteItem> = self
-                .executed_commands
-                .borrow()
+            // The commands run before, the latest first, with the data they ran
+            // with. Ignore commands without descriptions.
+            let mut history: Vec<CommandHistoryEntry> = Vec::new();
+            for entry in self.command_history.borrow().iter() {
+                if !history.contains(entry) {
+                    history.push(entry.clone());
+                }
+            }
+            let mut items: im::Vector<PaletteItem> = history
                 .iter()
-                .sorted_by_key(|(_, i)| *i)
-                .rev()
-                .filter_map(|(key, _)| {
-                    keypress.commands.get(key).and_then(|c| {
-                        c.kind.desc().as_ref().map(|m| PaletteItem {
-                            content: PaletteItemContent::Command { cmd: c.clone() },
-                            filter_text: m.to_string(),
-                            score: 0,
-                            indices: vec![],
-                        })
+                .filter_map(|entry| {
+                    let cmd = keypress.commands.get(&entry.command)?;
+                    let desc = cmd.kind.desc()?;
+                    Some(PaletteItem {
+                        content: PaletteItemContent::Command {
+                            cmd: LapceCommand {
+                                kind: cmd.kind.clone(),
+                                data: entry.data.clone(),
+                            },
+                        },
+                        filter_text: desc.to_string(),
+                        score: 0,
+                        indices: vec![],
                     })
                 })
                 .collect();
@@ -515,7 +708,7 @@ fn get_commands(&self) {
                     return None;
                 }
 
-                if self.executed_commands.borrow().contains_key(c.kind.str()) {
+                if history.iter().any(|entry| entry.command == c.kind.str()) {
                     return None;
                 }
 
@@ -533,6 +726,68 @@ fn get_commands(&self) {
         self.items.set(items);
     }
 
+    /// Initialize the palette with every active keymap, from the same keymaps the
+    /// key presses are matched against, with the conflicting ones first.
+    fn get_keymaps(&self) {
+        let items = self.keypress.with_untracked(|keypress| {
+            let command_name = |command: &str| -> String {
+                keypress
+                    .commands
+                    .get(command)
+                    .and_then(|cmd| cmd.kind.desc())
+                    .map(|desc| desc.to_string())
+                    .unwrap_or_else(|| command.to_string())
+            };
+            let keymap_text = |keymap: &KeyMap| -> String {
+                let keys = keymap.key.iter().map(|k| k.label()).join(" ");
+                let mut text = format!("{keys}  {}", command_name(&keymap.command));
+                if !keymap.modes.is_empty() {
+                    text.push_str(&format!("  mode: {}", keymap.modes));
+                }
+                if let Some(when) = &keymap.when {
+                    text.push_str(&format!("  when: {when}"));
+                }
+                text
+            };
+
+            let mut items: im::Vector<PaletteItem> =
+                keymap_conflicts(&keypress.command_keymaps)
+                    .into_iter()
+                    .map(|keymaps| {
+                        let commands = keymaps
+                            .iter()
+                            .map(|k| command_name(&k.command))
+                            .join(", ");
+                        let text = format!(
+                            "Conflict: {} runs only one of: {commands}",
+                            keymaps[0].key.iter().map(|k| k.label()).join(" ")
+                        );
+                        PaletteItem {
+                            content: PaletteItemContent::KeymapConflict { keymaps },
+                            filter_text: text,
+                            score: 0,
+                            indices: vec![],
+                        }
+                    })
+                    .collect();
+
+            items.extend(keypress.command_keymaps.values().flatten().map(
+                |keymap| PaletteItem {
+                    content: PaletteItemContent::Keymap {
+                        keymap: keymap.clone(),
+                    },
+                    filter_text: keymap_text(keymap),
+                    score: 0,
+                    indices: vec![],
+                },
+            ));
+
+            items
+        });
+
+        self.items.set(items);
+    }
+
     /// Initialize the palette with all the available workspaces, local and remote.
     fn get_workspaces(&self) {
         let db: Arc<LapceDb> = use_context().unwrap();
@@ -566,30 +821,82 @@ fn get_workspaces(&self) {
 
     /// Initialize the list of references in the file, from the current editor location.
     fn get_references(&self) {
-        let items = self
-            .references
-            .get_untracked()
-            .into_iter()
-            .map(|l| {
-                let full_path = l.path.clone();
-                let mut path = l.path.clone();
-                if let Some(workspace_path) = self.workspace.path.as_ref() {
-                    path = path
-                        .strip_prefix(workspace_path)
-                        .unwrap_or(&full_path)
-                        .to_path_buf();
+        let references = self.references.get_untracked();
+
+        // The text of each file that's open, and the files to read
+        let mut texts: HashMap<PathBuf, Option<Rope>> = HashMap::new();
+        let mut unopened = Vec::new();
+        for location in &references {
+            if texts.contains_key(&location.path) {
+                continue;
+            }
+            let doc = self
+                .main_split
+                .docs
+                .with_untracked(|docs| docs.get(&location.path).cloned());
+            let text = doc.and_then(|doc| {
+                doc.with_untracked(|doc| {
+                    doc.loaded().then(|| doc.buffer().text().clone())
+                })
+            });
+            if text.is_none() && self.workspace.kind.is_local() {
+                unopened.push(location.path.clone());
+            }
+            texts.insert(location.path.clone(), text);
+        }
+
+        let workspace = self.workspace.clone();
+        let set_items = self.items.write_only();
+        let run_id = self.run_id.get_untracked();
+        let current_run_id = self.run_id.read_only();
+        spawn_job(
+            self.common.scope,
+            move || {
+                for path in unopened {
+                    let text = std::fs::read_to_string(&path).ok().map(Rope::from);
+                    texts.insert(path, text);
                 }
-                let filter_text = path.to_str().unwrap_or("").to_string();
-                PaletteItem {
-                    content: PaletteItemContent::Reference { path, location: l },
-                    filter_text,
-                    score: 0,
-                    indices: vec![],
+                texts
+            },
+            move |texts| {
+                if current_run_id.get_untracked() != run_id {
+                    return;
                 }
-            })
-            .collect();
-
-        self.items.set(items);
+                let items = references
+                    .into_iter()
+                    .map(|l| {
+                        let path = workspace.display_path(&l.path);
+                        let filter_text = path.to_str().unwrap_or("").to_string();
+                        let preview = match (&l.position, texts.get(&l.path)) {
+                            (
+                                Some(EditorPosition::Position(position)),
+                                Some(Some(text)),
+                            ) => {
+                                let text = RopeTextRef::new(text);
+                                let line = position.line as usize;
+                                if line <= text.last_line() {
+                                    text.line_content(line).trim().to_string()
+                                } else {
+                                    String::new()
+                                }
+                            }
+                            _ => String::new(),
+                        };
+                        PaletteItem {
+                            content: PaletteItemContent::Reference {
+                                path,
+                                location: l,
+                                preview,
+                            },
+                            filter_text,
+                            score: 0,
+                            indices: vec![],
+                        }
+                    })
+                    .collect();
+                set_items.set(items);
+            },
+        );
     }
 
     fn get_document_symbols(&self) {
@@ -660,14 +967,29 @@ fn get_document_symbols(&self) {
         });
     }
 
+    /// Query the symbols of the workspace once the input settles, the symbols
+    /// picked recently first. Every change of the input runs the palette
+    /// again, which drops the requests for the earlier input.
     fn get_workspace_symbols(&self) {
         let input = self.input.get_untracked().input;
+        let run_id = self.run_id.get_untracked();
+        let current_run_id = self.run_id.read_only();
+        self.common.proxy.cancel_workspace_symbols();
 
         let set_items = self.items.write_only();
+        let executed = self.executed_workspace_symbols.clone();
         let send = create_ext_action(self.common.scope, move |result| {
+            if current_run_id.get_untracked() != run_id {
+                return;
+            }
             if let Ok(ProxyResponse::GetWorkspaceSymbols { symbols }) = result {
+                let executed = executed.borrow();
                 let items: im::Vector<PaletteItem> = symbols
                     .iter()
+                    .sorted_by_key(|s| {
+                        let key = (s.name.clone(), path_from_url(&s.location.uri));
+                        Reverse(executed.get(&key).copied())
+                    })
                     .map(|s| {
                         // TODO: Should we be using filter text?
                         let mut filter_text = s.name.clone();
@@ -701,11 +1023,15 @@ fn get_workspace_symbols(&self) {
             }
         });
 
-        self.common
-            .proxy
-            .get_workspace_symbols(input, move |result| {
+        let proxy = self.common.proxy.clone();
+        exec_after(WORKSPACE_SYMBOL_DELAY, move |_| {
+            if current_run_id.get_untracked() != run_id {
+                return;
+            }
+            proxy.get_workspace_symbols(input, move |result| {
                 send(result);
             });
+        });
     }
 
     fn get_ssh_hosts(&self) {
@@ -731,6 +1057,10 @@ fn get_ssh_hosts(&self) {
     }
 
     fn get_run_configs(&self) {
+        if !self.common.workspace_trust.is_trusted() {
+            self.restricted("run configs");
+            return;
+        }
         let configs = run_configs(self.common.workspace.path.as_deref());
         if configs.is_none() {
             if let Some(path) = self.workspace.path.as_ref() {
@@ -789,6 +1119,66 @@ fn get_run_configs(&self) {
             .set(items.into_iter().map(|(_, item)| item).collect());
     }
 
+    fn get_tasks(&self) {
+        if !self.common.workspace_trust.is_trusted() {
+            self.restricted("tasks");
+            return;
+        }
+        let workspace = self.common.workspace.path.clone();
+        let internal_command = self.common.internal_command;
+        let set_items = self.items.write_only();
+        let run_id = self.run_id.get_untracked();
+        let current_run_id = self.run_id.read_only();
+        spawn_job(
+            self.common.scope,
+            {
+                let workspace = workspace.clone();
+                move || task_configs(workspace.as_deref())
+            },
+            move |configs| {
+                if current_run_id.get_untracked() != run_id {
+                    return;
+                }
+                if configs.is_none() {
+                    if let Some(path) = workspace.as_ref() {
+                        let path = path.join(".lapce").join("tasks.toml");
+                        internal_command.send(InternalCommand::OpenFile { path });
+                    }
+                }
+                let items = configs
+                    .map(|configs| configs.tasks)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|task| PaletteItem {
+                        filter_text: format!("{} {}", task.name, task.command),
+                        content: PaletteItemContent::Task { task },
+                        score: 0,
+                        indices: Vec::new(),
+                    })
+                    .collect();
+                set_items.set(items);
+            },
+        );
+    }
+
+    /// Tell that the `what` of the workspace aren't used while it's restricted,
+    /// with nothing to pick.
+    fn restricted(&self, what: &str) {
+        self.items.set(im::Vector::new());
+        self.common
+            .internal_command
+            .send(InternalCommand::ShowMessage {
+                title: "Restricted Mode".to_string(),
+                message: ShowMessageParams {
+                    typ: MessageType::INFO,
+                    message: format!(
+                        "The {what} of the workspace aren't used until it's \
+                         trusted, from Manage Workspace Trust"
+                    ),
+                },
+            });
+    }
+
     fn get_color_themes(&self) {
         let config = self.common.config.get_untracked();
         let items = config
@@ -820,7 +1210,8 @@ fn get_icon_themes(&self) {
     }
 
     fn get_languages(&self) {
-        let langs = LapceLanguage::languages();
+        let mut langs = LapceLanguage::languages();
+        langs.insert(0, DETECT_LANGUAGE);
         let items = langs
             .iter()
             .map(|lang| PaletteItem {
@@ -875,6 +1266,34 @@ fn preselect_matching(&self, matching: &str) {
         self.index.set(idx);
     }
 
+    /// Remember `cmd` in the command history, if running it again does the
+    /// same.
+    fn record_command(&self, cmd: &LapceCommand) {
+        if !cmd.kind.is_repeatable() {
+            return;
+        }
+        let mut history = self.command_history.borrow_mut();
+        history.push(CommandHistoryEntry {
+            command: cmd.kind.str().to_string(),
+            data: cmd.data.clone(),
+        });
+        let db: Arc<LapceDb> = use_context().unwrap();
+        db.save_command_history(history.clone());
+    }
+
+    /// The latest command run from the palette, with the data it ran with.
+    pub fn last_command(&self) -> Option<LapceCommand> {
+        let history = self.command_history.borrow();
+        let entry = history.last()?;
+        let cmd = self.keypress.with_untracked(|keypress| {
+            keypress.commands.get(&entry.command).cloned()
+        })?;
+        Some(LapceCommand {
+            kind: cmd.kind,
+            data: entry.data.clone(),
+        })
+    }
+
     fn select(&self) {
         let index = self.index.get_untracked();
         let items = self.filtered_items.get_untracked();
@@ -890,9 +1309,31 @@ fn select(&self) {
                     self.common.lapce_command.send(cmd);
                 }
                 PaletteItemContent::File { full_path, .. } => {
+                    let position = self.input.with_untracked(|input| input.position);
+                    if let Some(position) = position {
+                        self.common.internal_command.send(
+                            InternalCommand::JumpToLocation {
+                                location: EditorLocation {
+                                    path: full_path.to_owned(),
+                                    position: Some(position),
+                                    scroll_offset: None,
+                                    ignore_unconfirmed: false,
+                                    same_editor_tab: false,
+                                },
+                            },
+                        );
+                    } else {
+                        self.common.internal_command.send(
+                            InternalCommand::OpenFile {
+                                path: full_path.to_owned(),
+                            },
+                        );
+                    }
+                }
+                PaletteItemContent::CreateFile { full_path } => {
                     self.common
                         .internal_command
-                        .send(InternalCommand::OpenFile {
+                        .send(InternalCommand::CreateFile {
                             path: full_path.to_owned(),
                         });
                 }
@@ -924,8 +1365,19 @@ fn select(&self) {
                     );
                 }
                 PaletteItemContent::Command { cmd } => {
+                    self.record_command(cmd);
                     self.common.lapce_command.send(cmd.clone());
                 }
+                PaletteItemContent::Keymap { keymap } => {
+                    let cmd = self.keypress.with_untracked(|keypress| {
+                        keypress.commands.get(&keymap.command).cloned()
+                    });
+                    if let Some(cmd) = cmd {
+                        self.record_command(&cmd);
+                        self.common.lapce_command.send(cmd);
+                    }
+                }
+                PaletteItemContent::KeymapConflict { .. } => {}
                 PaletteItemContent::Workspace { workspace } => {
                     self.common
                         .window_command
@@ -980,7 +1432,11 @@ fn select(&self) {
                         },
                     );
                 }
-                PaletteItemContent::WorkspaceSymbol { location, .. } => {
+                PaletteItemContent::WorkspaceSymbol { name, location, .. } => {
+                    self.executed_workspace_symbols.borrow_mut().insert(
+                        (name.clone(), location.path.clone()),
+                        Instant::now(),
+                    );
                     self.common.internal_command.send(
                         InternalCommand::JumpToLocation {
                             location: location.clone(),
@@ -995,6 +1451,11 @@ fn select(&self) {
                         },
                     );
                 }
+                PaletteItemContent::Task { task } => {
+                    self.common
+                        .internal_command
+                        .send(InternalCommand::RunTask { task: task.clone() });
+                }
                 PaletteItemContent::ColorTheme { name } => self
                     .common
                     .internal_command
@@ -1019,18 +1480,28 @@ fn select(&self) {
                             return;
                         }
                     };
-                    doc.update(|doc| {
-                        if name.is_empty() || name.to_lowercase().eq("plain text") {
-                            doc.set_syntax(Syntax::plaintext())
-                        } else {
-                            let lang = match LapceLanguage::from_name(name) {
-                                Some(v) => v,
-                                None => return,
-                            };
-                            doc.set_language(lang);
+                    let language = if name == DETECT_LANGUAGE {
+                        None
+                    } else if name.is_empty() || name.to_lowercase().eq("plain text")
+                    {
+                        Some(LapceLanguage::Plaintext)
+                    } else {
+                        match LapceLanguage::from_name(name) {
+                            Some(v) => Some(v),
+                            None => return,
                         }
-                        doc.trigger_syntax_change(None);
-                    });
+                    };
+                    let path = doc
+                        .try_update(|doc| {
+                            doc.set_language_override(language);
+                            doc.content.path().cloned()
+                        })
+                        .flatten();
+                    // The choice sticks to the file for when it's opened again
+                    if let Some(path) = path {
+                        let db: Arc<LapceDb> = use_context().unwrap();
+                        db.save_doc_language(&self.workspace, path, language);
+                    }
                 }
                 PaletteItemContent::SCMReference { name } => {
                     self.common
@@ -1070,6 +1541,7 @@ fn preview(&self, _cx: Scope) {
             match &item.content {
                 PaletteItemContent::PaletteHelp { .. } => {}
                 PaletteItemContent::File { .. } => {}
+                PaletteItemContent::CreateFile { .. } => {}
                 PaletteItemContent::Line { line, .. } => {
                     self.has_preview.set(true);
                     let editor = self.main_split.active_editor.get_untracked();
@@ -1102,8 +1574,11 @@ fn preview(&self, _cx: Scope) {
                     );
                 }
                 PaletteItemContent::Command { .. } => {}
+                PaletteItemContent::Keymap { .. } => {}
+                PaletteItemContent::KeymapConflict { .. } => {}
                 PaletteItemContent::Workspace { .. } => {}
                 PaletteItemContent::RunAndDebug { .. } => {}
+                PaletteItemContent::Task { .. } => {}
                 PaletteItemContent::SshHost { .. } => {}
                 PaletteItemContent::Language { .. } => {}
                 PaletteItemContent::Reference { location, .. } => {
@@ -1193,6 +1668,36 @@ fn cancel(&self) {
         self.close();
     }
 
+    /// Create the file typed in the file palette, whether it matches some
+    /// files or not.
+    fn create_file(&self) {
+        let (kind, input) = self
+            .input
+            .with_untracked(|input| (input.kind, input.input.clone()));
+        if kind != PaletteKind::File {
+            return;
+        }
+        match new_file_path(self.workspace.path.as_deref(), &input) {
+            Ok(path) => {
+                self.close();
+                self.common
+                    .internal_command
+                    .send(InternalCommand::CreateFile { path });
+            }
+            Err(message) => {
+                self.common
+                    .internal_command
+                    .send(InternalCommand::ShowMessage {
+                        title: "Create File".to_string(),
+                        message: ShowMessageParams {
+                            typ: MessageType::ERROR,
+                            message,
+                        },
+                    });
+            }
+        }
+    }
+
     /// Close the palette, reverting focus back to the workbench.
     fn close(&self) {
         self.status.set(PaletteStatus::Inactive);
@@ -1254,6 +1759,9 @@ fn run_focus_command(&self, cmd: &FocusCommand) -> CommandExecuted {
             FocusCommand::ListSelect => {
                 self.select();
             }
+            FocusCommand::PaletteCreateFile => {
+                self.create_file();
+            }
             _ => return CommandExecuted::No,
         }
         CommandExecuted::Yes
@@ -1265,6 +1773,7 @@ fn filter_items(
         input: &str,
         items: im::Vector<PaletteItem>,
         matcher: &mut nucleo::Matcher,
+        natural_sort: bool,
     ) -> Option<im::Vector<PaletteItem>> {
         if input.is_empty() {
             return Some(items);
@@ -1300,6 +1809,9 @@ fn filter_items(
         filtered_items.sort_by(|a, b| {
             let order = b.score.cmp(&a.score);
             match order {
+                std::cmp::Ordering::Equal if natural_sort => {
+                    natural_cmp(&a.filter_text, &b.filter_text)
+                }
                 std::cmp::Ordering::Equal => a.filter_text.cmp(&b.filter_text),
                 _ => order,
             }
@@ -1313,6 +1825,7 @@ fn filter_items(
 
     fn update_process(
         run_id: Arc<AtomicU64>,
+        natural_sort: Arc<AtomicBool>,
         receiver: Receiver<(u64, String, im::Vector<PaletteItem>)>,
         resp_tx: Sender<(u64, String, im::Vector<PaletteItem>)>,
     ) {
@@ -1344,6 +1857,7 @@ fn receive_batch(
                     &input,
                     items,
                     &mut matcher,
+                    natural_sort.load(Ordering::Relaxed),
                 ) {
                     let _ = resp_tx.send((current_run_id, input, filtered_items));
                 }
@@ -1394,3 +1908,52 @@ fn receive_char(&self, c: &str) {
         self.input_editor.receive_char(c);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_line_col() {
+        assert_eq!(split_line_col("src/main.rs"), ("src/main.rs", None));
+        assert_eq!(
+            split_line_col("src/main.rs:10"),
+            ("src/main.rs", Some(EditorPosition::Line(9)))
+        );
+        assert_eq!(
+            split_line_col("src/main.rs:10:5"),
+            (
+                "src/main.rs",
+                Some(EditorPosition::Position(lsp_types::Position {
+                    line: 9,
+                    character: 4,
+                }))
+            )
+        );
+        assert_eq!(
+            split_line_col("src/main.rs:10:"),
+            ("src/main.rs", Some(EditorPosition::Line(9)))
+        );
+        assert_eq!(split_line_col("main:"), ("main", None));
+        assert_eq!(split_line_col("a:b"), ("a:b", None));
+    }
+    #[test]
+    fn test_new_file_path() {
+        let workspace = Some(Path::new("/work"));
+        assert_eq!(
+            new_file_path(workspace, "src/foo/bar.rs"),
+            Ok(PathBuf::from("/work/src/foo/bar.rs"))
+        );
+        assert_eq!(
+            new_file_path(workspace, "./notes.md"),
+            Ok(PathBuf::from("/work/./notes.md"))
+        );
+        assert!(new_file_path(workspace, "").is_err());
+        assert!(new_file_path(workspace, "src/").is_err());
+        assert!(new_file_path(workspace, "../outside.rs").is_err());
+        assert!(new_file_path(workspace, "src/../../outside.rs").is_err());
+        assert!(new_file_path(workspace, "/etc/passwd").is_err());
+        assert!(new_file_path(workspace, "what?.rs").is_err());
+        assert!(new_file_path(None, "src/lib.rs").is_err());
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// How many of the commands run from the palette are remembered, across
+/// sessions
+pub const MAX_COMMAND_HISTORY: usize = 20;
+
+/// A command run from the palette, with the data it ran with, so that running
+/// it again does the same
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CommandHistoryEntry {
+    pub command: String,
+    pub data: Option<Value>,
+}
+
+/// The commands run from the palette, the latest first
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CommandHistory {
+    entries: VecDeque<CommandHistoryEntry>,
+}
+
+impl CommandHistory {
+    /// Remember `entry` as the latest command, unless it's the same as the
+    /// latest already, forgetting the oldest past [`MAX_COMMAND_HISTORY`].
+    pub fn push(&mut self, entry: CommandHistoryEntry) {
+        if self.entries.front() == Some(&entry) {
+            return;
+        }
+        self.entries.push_front(entry);
+        self.entries.truncate(MAX_COMMAND_HISTORY);
+    }
+
+    pub fn last(&self) -> Option<&CommandHistoryEntry> {
+        self.entries.front()
+    }
+
+    /// The commands, the latest first.
+    pub fn iter(&self) -> impl Iterator<Item = &CommandHistoryEntry> {
+        self.entries.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{CommandHistory, CommandHistoryEntry, MAX_COMMAND_HISTORY};
+
+    fn entry(command: &str, data: Option<serde_json::Value>) -> CommandHistoryEntry {
+        CommandHistoryEntry {
+            command: command.to_string(),
+            data,
+        }
+    }
+
+    #[test]
+    fn test_command_history() {
+        let mut history = CommandHistory::default();
+        assert_eq!(history.last(), None);
+
+        history.push(entry("toggle_minimap", None));
+        history.push(entry("toggle_minimap", None));
+        history.push(entry("goto_line", Some(json!(10))));
+        // Only the consecutive ones are merged, and the data tells them apart
+        history.push(entry("goto_line", Some(json!(20))));
+        history.push(entry("toggle_minimap", None));
+
+        assert_eq!(history.last(), Some(&entry("toggle_minimap", None)));
+        assert_eq!(
+            history.iter().cloned().collect::<Vec<_>>(),
+            vec![
+                entry("toggle_minimap", None),
+                entry("goto_line", Some(json!(20))),
+                entry("goto_line", Some(json!(10))),
+                entry("toggle_minimap", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_command_history_limit() {
+        let mut history = CommandHistory::default();
+        for i in 0..MAX_COMMAND_HISTORY + 5 {
+            history.push(entry("goto_line", Some(i.into())));
+        }
+        assert_eq!(history.iter().count(), MAX_COMMAND_HISTORY);
+        assert_eq!(
+            history.last(),
+            Some(&entry("goto_line", Some((MAX_COMMAND_HISTORY + 4).into())))
+        );
+        assert_eq!(
+            history.iter().last(),
+            Some(&entry("goto_line", Some(5.into())))
+        );
+    }
+}
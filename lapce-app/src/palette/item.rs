@@ -7,6 +7,8 @@
     command::{LapceCommand, LapceWorkbenchCommand},
     debug::RunDebugMode,
     editor::location::EditorLocation,
+    keypress::keymap::KeyMap,
+    task::TaskConfig,
     workspace::{LapceWorkspace, SshHost},
 };
 
@@ -27,6 +29,10 @@ pub enum PaletteItemContent {
         path: PathBuf,
         full_path: PathBuf,
     },
+    /// Creating the file typed in, which matches none of the workspace
+    CreateFile {
+        full_path: PathBuf,
+    },
     Line {
         line: usize,
         content: String,
@@ -34,12 +40,21 @@ pub enum PaletteItemContent {
     Command {
         cmd: LapceCommand,
     },
+    Keymap {
+        keymap: KeyMap,
+    },
+    /// Keymaps with the same keys and conditions but different commands.
+    KeymapConflict {
+        keymaps: Vec<KeyMap>,
+    },
     Workspace {
         workspace: LapceWorkspace,
     },
     Reference {
         path: PathBuf,
         location: EditorLocation,
+        /// The line the reference is on, trimmed
+        preview: String,
     },
     DocumentSymbol {
         kind: SymbolKind,
@@ -60,6 +75,9 @@ pub enum PaletteItemContent {
         mode: RunDebugMode,
         config: RunDebugConfig,
     },
+    Task {
+        task: TaskConfig,
+    },
     ColorTheme {
         name: String,
     },
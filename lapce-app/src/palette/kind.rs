@@ -8,12 +8,14 @@ pub enum PaletteKind {
     File,
     Line,
     Command,
+    Keymap,
     Workspace,
     Reference,
     DocumentSymbol,
     WorkspaceSymbol,
     SshHost,
     RunAndDebug,
+    Task,
     ColorTheme,
     IconTheme,
     Language,
@@ -32,9 +34,11 @@ pub fn symbol(&self) -> &'static str {
             PaletteKind::Workspace => ">",
             PaletteKind::Command => ":",
             PaletteKind::File
+            | PaletteKind::Keymap
             | PaletteKind::Reference
             | PaletteKind::SshHost
             | PaletteKind::RunAndDebug
+            | PaletteKind::Task
             | PaletteKind::ColorTheme
             | PaletteKind::IconTheme
             | PaletteKind::Language
@@ -68,12 +72,14 @@ pub fn command(self) -> Option<LapceWorkbenchCommand> {
             }
             PaletteKind::Workspace => Some(LapceWorkbenchCommand::PaletteWorkspace),
             PaletteKind::Command => Some(LapceWorkbenchCommand::PaletteCommand),
+            PaletteKind::Keymap => Some(LapceWorkbenchCommand::PaletteKeymap),
             PaletteKind::File => Some(LapceWorkbenchCommand::Palette),
             PaletteKind::Reference => None, // InternalCommand::PaletteReferences
             PaletteKind::SshHost => Some(LapceWorkbenchCommand::ConnectSshHost),
             PaletteKind::RunAndDebug => {
                 Some(LapceWorkbenchCommand::PaletteRunAndDebug)
             }
+            PaletteKind::Task => Some(LapceWorkbenchCommand::PaletteTask),
             PaletteKind::ColorTheme => Some(LapceWorkbenchCommand::ChangeColorTheme),
             PaletteKind::IconTheme => Some(LapceWorkbenchCommand::ChangeIconTheme),
             PaletteKind::Language => Some(LapceWorkbenchCommand::ChangeFileLanguage),
@@ -97,9 +103,11 @@ pub fn command(self) -> Option<LapceWorkbenchCommand> {
     pub fn get_input<'a>(&self, input: &'a str) -> &'a str {
         match self {
             PaletteKind::File
+            | PaletteKind::Keymap
             | PaletteKind::Reference
             | PaletteKind::SshHost
             | PaletteKind::RunAndDebug
+            | PaletteKind::Task
             | PaletteKind::ColorTheme
             | PaletteKind::IconTheme
             | PaletteKind::Language
@@ -10,6 +10,7 @@
 };
 use crate::{
     db::LapceDb,
+    resize::resize_panel,
     window_tab::{CommonData, Focus},
 };
 
@@ -28,8 +29,15 @@ pub fn default_panel_order() -> PanelOrder {
     );
     order.insert(
         PanelPosition::BottomLeft,
-        im::vector![PanelKind::Terminal, PanelKind::Search, PanelKind::Problem,],
+        im::vector![
+            PanelKind::Terminal,
+            PanelKind::Search,
+            PanelKind::Problem,
+            PanelKind::EditPreview,
+            PanelKind::Task,
+        ],
     );
+    order.insert(PanelPosition::RightTop, im::vector![PanelKind::Outline]);
 
     order
 }
@@ -297,6 +305,28 @@ pub fn toggle_bottom_maximize(&self) {
         });
     }
 
+    /// Make the panel container `kind` is in `delta` pixels bigger, when
+    /// it's resized along `width`, the left and right ones only being
+    /// resized in width and the bottom one in height. `room` is how many
+    /// pixels the editors can give up for it.
+    pub fn resize(&self, kind: PanelKind, width: bool, delta: f64, room: f64) {
+        let Some((_, position)) = self.panel_position(&kind) else {
+            return;
+        };
+        let container = position.container();
+        if width == container.is_bottom() {
+            return;
+        }
+        self.size.update(|size| {
+            let size = match container {
+                PanelContainerPosition::Left => &mut size.left,
+                PanelContainerPosition::Bottom => &mut size.bottom,
+                PanelContainerPosition::Right => &mut size.right,
+            };
+            *size = resize_panel(*size, delta, *size + room);
+        });
+    }
+
     pub fn panel_bottom_maximized(&self, tracked: bool) -> bool {
         let styles = if tracked {
             self.styles.get()
@@ -0,0 +1,304 @@
+use std::rc::Rc;
+
+use floem::{
+    style::{CursorStyle, Style},
+    view::View,
+    views::{
+        container, container_box, empty, label, scroll, stack, svg, virtual_list,
+        Decorators, VirtualListDirection, VirtualListItemSize,
+    },
+};
+
+use super::position::PanelPosition;
+use crate::{
+    app::VectorItems,
+    command::InternalCommand,
+    config::color::LapceColor,
+    edit_preview::{EditPreviewData, PreviewRow},
+    editor::location::{EditorLocation, EditorPosition},
+    settings::checkbox,
+    window_tab::WindowTabData,
+};
+
+pub fn edit_preview_panel(
+    window_tab_data: Rc<WindowTabData>,
+    _position: PanelPosition,
+) -> impl View {
+    let edit_preview = window_tab_data.edit_preview.clone();
+    stack(|| {
+        (
+            edit_preview_header(edit_preview.clone()),
+            edit_preview_rows(edit_preview),
+        )
+    })
+    .style(|s| s.size_pct(100.0, 100.0).flex_col())
+}
+
+/// What the edits are from and how many files they touch, with the buttons
+/// to apply or discard them.
+fn edit_preview_header(edit_preview: EditPreviewData) -> impl View {
+    let config = edit_preview.common.config;
+    let title = edit_preview.title;
+    let files = edit_preview.files;
+    let loading = edit_preview.loading;
+    let apply = edit_preview.clone();
+    let discard = edit_preview;
+    let button = move |s: Style| {
+        let config = config.get();
+        s.margin_left_px(6.0)
+            .padding_horiz_px(10.0)
+            .border(1.0)
+            .border_radius(6.0)
+            .border_color(*config.get_color(LapceColor::LAPCE_BORDER))
+            .apply_if(files.with(|files| files.is_empty()), |s| s.hide())
+    };
+    stack(|| {
+        (
+            label(move || {
+                if loading.get() {
+                    return "Working out the changes...".to_string();
+                }
+                let len = files.with(|files| files.len());
+                if len == 0 {
+                    return "No changes to preview".to_string();
+                }
+                format!(
+                    "{}: {len} {}",
+                    title.get(),
+                    if len == 1 { "file" } else { "files" }
+                )
+            })
+            .style(|s| s.flex_grow(1.0).min_width_px(0.0).text_ellipsis()),
+            label(|| "Apply".to_string())
+                .on_click(move |_| {
+                    apply.apply();
+                    true
+                })
+                .style(button)
+                .hover_style(move |s| {
+                    s.cursor(CursorStyle::Pointer).background(
+                        *config
+                            .get()
+                            .get_color(LapceColor::PANEL_HOVERED_BACKGROUND),
+                    )
+                }),
+            label(|| "Discard".to_string())
+                .on_click(move |_| {
+                    discard.discard();
+                    true
+                })
+                .style(button)
+                .hover_style(move |s| {
+                    s.cursor(CursorStyle::Pointer).background(
+                        *config
+                            .get()
+                            .get_color(LapceColor::PANEL_HOVERED_BACKGROUND),
+                    )
+                }),
+        )
+    })
+    .style(|s| s.width_pct(100.0).padding_px(10.0).items_center())
+}
+
+/// The files, hunks and lines of the preview. Only the rows in view are
+/// made, as the edits can touch a lot of files.
+fn edit_preview_rows(edit_preview: EditPreviewData) -> impl View {
+    let config = edit_preview.common.config;
+    let ui_line_height = edit_preview.common.ui_line_height;
+    let rows = edit_preview.rows;
+    let generation = edit_preview.generation;
+    container(|| {
+        scroll(move || {
+            virtual_list(
+                VirtualListDirection::Vertical,
+                VirtualListItemSize::Fixed(Box::new(move || ui_line_height.get())),
+                move || VectorItems(rows.get()),
+                move |(_, row)| (generation.get_untracked(), *row),
+                move |(_, row)| edit_preview_row(edit_preview.clone(), row),
+            )
+            .style(|s| s.flex_col().min_width_pct(100.0))
+        })
+        .style(|s| s.absolute().size_pct(100.0, 100.0))
+    })
+    .style(move |s| {
+        s.width_pct(100.0)
+            .flex_basis_px(0.0)
+            .flex_grow(1.0)
+            .color(*config.get().get_color(LapceColor::PANEL_FOREGROUND))
+    })
+}
+
+fn edit_preview_row(edit_preview: EditPreviewData, row: PreviewRow) -> impl View {
+    let config = edit_preview.common.config;
+    let ui_line_height = edit_preview.common.ui_line_height;
+    let workspace = edit_preview.common.workspace.clone();
+    let internal_command = edit_preview.common.internal_command;
+    let file_index = match row {
+        PreviewRow::File(file)
+        | PreviewRow::Error(file)
+        | PreviewRow::Hunk(file, _)
+        | PreviewRow::Line { file, .. } => file,
+    };
+    let Some(file) = edit_preview
+        .files
+        .with_untracked(|files| files.get(file_index).cloned())
+    else {
+        return container_box(|| Box::new(empty()));
+    };
+    let excluded = file.excluded;
+    let icon_size = move || config.get().ui.icon_size() as f32;
+
+    let view = match row {
+        PreviewRow::File(_) => {
+            let path = workspace.display_path(&file.path);
+            let style_path = path.clone();
+            let file_name = path
+                .file_name()
+                .and_then(|s| s.to_str())
+                .unwrap_or("")
+                .to_string();
+            let folder = path
+                .parent()
+                .and_then(|s| s.to_str())
+                .unwrap_or("")
+                .to_string();
+            let len = file.hunks.as_ref().map(|hunks| hunks.len()).unwrap_or(0);
+            container_box(move || {
+                Box::new(stack(move || {
+                    (
+                        checkbox(
+                            move || {
+                                len > 0
+                                    && excluded.with(|excluded| excluded.len() < len)
+                            },
+                            config,
+                        )
+                        .on_click(move |_| {
+                            edit_preview.toggle_file(file_index);
+                            true
+                        })
+                        .style(|s| s.margin_left_px(10.0)),
+                        svg(move || config.get().file_svg(&path).0).style(
+                            move |s| {
+                                let config = config.get();
+                                let size = config.ui.icon_size() as f32;
+                                let color = config.file_svg(&style_path).1.copied();
+                                s.margin_horiz_px(6.0)
+                                    .size_px(size, size)
+                                    .min_size_px(size, size)
+                                    .apply_opt(color, Style::color)
+                            },
+                        ),
+                        label(move || file_name.clone())
+                            .style(|s| s.margin_right_px(6.0)),
+                        label(move || folder.clone()).style(move |s| {
+                            s.color(*config.get().get_color(LapceColor::EDITOR_DIM))
+                                .min_width_px(0.0)
+                                .text_ellipsis()
+                        }),
+                    )
+                }))
+            })
+        }
+        PreviewRow::Error(_) => {
+            let error = file.hunks.err().unwrap_or_default();
+            container_box(move || {
+                Box::new(label(move || error.clone()).style(move |s| {
+                    s.margin_left_px(10.0 + icon_size() + 6.0)
+                        .color(*config.get().get_color(LapceColor::EDITOR_DIM))
+                }))
+            })
+        }
+        PreviewRow::Hunk(_, hunk) => {
+            let line = file
+                .hunks
+                .as_ref()
+                .ok()
+                .and_then(|hunks| hunks.get(hunk))
+                .map(|hunk| hunk.line)
+                .unwrap_or(0);
+            let path = file.path.clone();
+            container_box(move || {
+                Box::new(stack(move || {
+                    (
+                        checkbox(
+                            move || {
+                                !excluded.with(|excluded| excluded.contains(&hunk))
+                            },
+                            config,
+                        )
+                        .on_click(move |_| {
+                            edit_preview.toggle_hunk(file_index, hunk);
+                            true
+                        })
+                        .style(move |s| s.margin_left_px(10.0 + icon_size() + 6.0)),
+                        label(move || format!("Line {}", line + 1))
+                            .on_click(move |_| {
+                                internal_command.send(
+                                    InternalCommand::JumpToLocation {
+                                        location: EditorLocation {
+                                            path: path.clone(),
+                                            position: Some(EditorPosition::Line(
+                                                line,
+                                            )),
+                                            scroll_offset: None,
+                                            ignore_unconfirmed: false,
+                                            same_editor_tab: false,
+                                        },
+                                    },
+                                );
+                                true
+                            })
+                            .style(move |s| {
+                                s.margin_left_px(6.0).color(
+                                    *config.get().get_color(LapceColor::EDITOR_DIM),
+                                )
+                            })
+                            .hover_style(|s| s.cursor(CursorStyle::Pointer)),
+                    )
+                }))
+            })
+        }
+        PreviewRow::Line {
+            hunk, added, index, ..
+        } => {
+            let text = file
+                .hunks
+                .as_ref()
+                .ok()
+                .and_then(|hunks| hunks.get(hunk))
+                .and_then(|hunk| {
+                    if added {
+                        hunk.after.get(index)
+                    } else {
+                        hunk.before.get(index)
+                    }
+                })
+                .cloned()
+                .unwrap_or_default();
+            let text = format!("{} {text}", if added { '+' } else { '-' });
+            container_box(move || {
+                Box::new(label(move || text.clone()).style(move |s| {
+                    let config = config.get();
+                    let included =
+                        !excluded.with(|excluded| excluded.contains(&hunk));
+                    let color = if !included {
+                        LapceColor::EDITOR_DIM
+                    } else if added {
+                        LapceColor::SOURCE_CONTROL_ADDED
+                    } else {
+                        LapceColor::SOURCE_CONTROL_REMOVED
+                    };
+                    s.margin_left_px(10.0 + config.ui.icon_size() as f32 + 6.0)
+                        .color(*config.get_color(color))
+                }))
+            })
+        }
+    };
+    view.style(move |s| {
+        s.min_width_pct(100.0)
+            .items_center()
+            .padding_right_px(10.0)
+            .height_px(ui_line_height.get() as f32)
+    })
+}
@@ -2,7 +2,7 @@
 
 use floem::{
     event::EventListener,
-    reactive::ReadSignal,
+    reactive::{ReadSignal, RwSignal},
     style::{CursorStyle, Style},
     view::View,
     views::{
@@ -10,6 +10,8 @@
         VirtualListDirection, VirtualListItemSize,
     },
 };
+use lapce_core::{buffer::rope_text::RopeText, replace::Replacer};
+use lapce_rpc::proxy::SearchMatch;
 use lapce_xi_rope::find::CaseMatching;
 
 use super::{kind::PanelKind, position::PanelPosition};
@@ -17,10 +19,12 @@
     app::clickable_icon,
     command::InternalCommand,
     config::{color::LapceColor, icon::LapceIcons, LapceConfig},
+    doc::Document,
     editor::location::{EditorLocation, EditorPosition},
     focus_text::focus_text,
-    global_search::{GlobalSearchData, SearchMatchData},
+    global_search::{GlobalSearchData, SearchInput, SearchMatchData},
     listener::Listener,
+    settings::checkbox,
     text_input::text_input,
     window_tab::{Focus, WindowTabData},
     workspace::LapceWorkspace,
@@ -32,22 +36,35 @@ pub fn global_search_panel(
 ) -> impl View {
     let global_search = window_tab_data.global_search.clone();
     let editor = global_search.editor.clone();
+    let replace_editor = global_search.replace_editor.clone();
+    let include_editor = global_search.include_editor.clone();
+    let focused_input = global_search.focused_input;
+    let excluded = global_search.excluded;
+    let search_excluded = global_search.search_excluded;
     let config = global_search.common.config;
     let workspace = global_search.common.workspace.clone();
     let internal_command = global_search.common.internal_command;
     let case_matching = global_search.common.find.case_matching;
     let whole_word = global_search.common.find.whole_words;
     let is_regex = global_search.common.find.is_regex;
+    let preserve_case = global_search.common.find.preserve_case;
 
     let focus = global_search.common.focus;
-    let is_focused = move || focus.get() == Focus::Panel(PanelKind::Search);
+    let is_input_focused = move |input: SearchInput| {
+        focus.get() == Focus::Panel(PanelKind::Search)
+            && focused_input.get() == input
+    };
+    let replace_data = global_search.clone();
 
     stack(|| {
         (
             container(|| {
                 stack(|| {
                     (
-                        text_input(editor, is_focused).style(|s| s.width_pct(100.0)),
+                        text_input(editor, move || {
+                            is_input_focused(SearchInput::Pattern)
+                        })
+                        .style(|s| s.width_pct(100.0)),
                         clickable_icon(
                             || LapceIcons::SEARCH_CASE_SENSITIVE,
                             move || {
@@ -94,6 +111,7 @@ pub fn global_search_panel(
                 })
                 .on_event(EventListener::PointerDown, move |_| {
                     focus.set(Focus::Panel(PanelKind::Search));
+                    focused_input.set(SearchInput::Pattern);
                     false
                 })
                 .style(move |s| {
@@ -108,12 +126,136 @@ pub fn global_search_panel(
                 })
             })
             .style(|s| s.width_pct(100.0).padding_px(10.0)),
+            container(|| {
+                stack(|| {
+                    (
+                        text_input(replace_editor, move || {
+                            is_input_focused(SearchInput::Replacement)
+                        })
+                        .style(|s| s.width_pct(100.0)),
+                        clickable_icon(
+                            || LapceIcons::SEARCH_PRESERVE_CASE,
+                            move || {
+                                preserve_case.update(|preserve_case| {
+                                    *preserve_case = !*preserve_case;
+                                });
+                            },
+                            move || preserve_case.get(),
+                            || false,
+                            config,
+                        )
+                        .style(|s| s.padding_vert_px(4.0)),
+                        clickable_icon(
+                            || LapceIcons::SEARCH_REPLACE_ALL,
+                            move || replace_data.replace_all(),
+                            || false,
+                            || false,
+                            config,
+                        )
+                        .style(|s| s.padding_left_px(6.0)),
+                    )
+                })
+                .on_event(EventListener::PointerDown, move |_| {
+                    focus.set(Focus::Panel(PanelKind::Search));
+                    focused_input.set(SearchInput::Replacement);
+                    false
+                })
+                .style(move |s| {
+                    s.width_pct(100.0)
+                        .padding_right_px(6.0)
+                        .items_center()
+                        .border(1.0)
+                        .border_radius(6.0)
+                        .border_color(
+                            *config.get().get_color(LapceColor::LAPCE_BORDER),
+                        )
+                })
+            })
+            .style(|s| {
+                s.width_pct(100.0)
+                    .padding_horiz_px(10.0)
+                    .padding_bottom_px(10.0)
+            }),
+            container(|| {
+                text_input(include_editor, move || {
+                    is_input_focused(SearchInput::Include)
+                })
+                .placeholder(|| "files to include, like src/**, *.rs".to_string())
+                .on_event(EventListener::PointerDown, move |_| {
+                    focus.set(Focus::Panel(PanelKind::Search));
+                    focused_input.set(SearchInput::Include);
+                    false
+                })
+                .style(move |s| {
+                    s.width_pct(100.0)
+                        .border(1.0)
+                        .border_radius(6.0)
+                        .border_color(
+                            *config.get().get_color(LapceColor::LAPCE_BORDER),
+                        )
+                })
+            })
+            .style(|s| {
+                s.width_pct(100.0)
+                    .padding_horiz_px(10.0)
+                    .padding_bottom_px(10.0)
+            }),
+            excluded_files_view(excluded, search_excluded, config),
             search_result(workspace, global_search, internal_command, config),
         )
     })
     .style(|s| s.absolute().size_pct(100.0, 100.0).flex_col())
 }
 
+/// Shows when files matching `search.exclude` were left out of the results,
+/// with a button to search them too.
+fn excluded_files_view(
+    excluded: RwSignal<bool>,
+    search_excluded: RwSignal<bool>,
+    config: ReadSignal<Arc<LapceConfig>>,
+) -> impl View {
+    stack(|| {
+        (
+            label(move || {
+                if search_excluded.get() {
+                    "Searching the excluded files too.".to_string()
+                } else {
+                    "Some files are excluded by search.exclude.".to_string()
+                }
+            })
+            .style(move |s| {
+                s.color(*config.get().get_color(LapceColor::EDITOR_DIM))
+            }),
+            label(move || {
+                if search_excluded.get() {
+                    "Exclude them".to_string()
+                } else {
+                    "Search excluded files too".to_string()
+                }
+            })
+            .on_click(move |_| {
+                search_excluded.update(|search_excluded| {
+                    *search_excluded = !*search_excluded;
+                });
+                true
+            })
+            .style(move |s| {
+                s.margin_left_px(6.0)
+                    .cursor(CursorStyle::Pointer)
+                    .color(*config.get().get_color(LapceColor::EDITOR_LINK))
+            }),
+        )
+    })
+    .style(move |s| {
+        let shown = excluded.get() || search_excluded.get();
+        s.width_pct(100.0)
+            .padding_horiz_px(10.0)
+            .padding_bottom_px(10.0)
+            .items_center()
+            .apply_if(!shown, |s| s.hide())
+    })
+}
+
 fn search_result(
     workspace: Arc<LapceWorkspace>,
     global_search_data: GlobalSearchData,
@@ -121,6 +263,8 @@ fn search_result(
     config: ReadSignal<Arc<LapceConfig>>,
 ) -> impl View {
     let ui_line_height = global_search_data.common.ui_line_height;
+    let replacer = global_search_data.replacer;
+    let replace_doc = global_search_data.replace_editor.view.doc;
     container(|| {
         scroll(move || {
             virtual_list(
@@ -134,14 +278,7 @@ fn search_result(
                 move |(path, _)| path.to_owned(),
                 move |(path, match_data)| {
                     let full_path = path.clone();
-                    let path = if let Some(workspace_path) = workspace.path.as_ref()
-                    {
-                        path.strip_prefix(workspace_path)
-                            .unwrap_or(&full_path)
-                            .to_path_buf()
-                    } else {
-                        path
-                    };
+                    let path = workspace.display_path(&path);
                     let style_path = path.clone();
 
                     let file_name = path
@@ -157,11 +294,21 @@ fn search_result(
                         .to_string();
 
                     let expanded = match_data.expanded;
+                    let included = match_data.included;
+                    let excluded = match_data.excluded;
 
                     stack(|| {
                         (
                             stack(|| {
                                 (
+                                    checkbox(move || included.get(), config)
+                                        .on_click(move |_| {
+                                            included.update(|included| {
+                                                *included = !*included;
+                                            });
+                                            true
+                                        })
+                                        .style(|s| s.margin_left_px(10.0)),
                                     svg(move || {
                                         config.get().ui_svg(if expanded.get() {
                                             LapceIcons::ITEM_OPENED
@@ -173,7 +320,7 @@ fn search_result(
                                         move |s| {
                                             let config = config.get();
                                             let size = config.ui.icon_size() as f32;
-                                            s.margin_left_px(10.0)
+                                            s.margin_left_px(6.0)
                                                 .margin_right_px(6.0)
                                                 .size_px(size, size)
                                                 .min_size_px(size, size)
@@ -252,53 +399,28 @@ fn search_result(
                                 move |m| {
                                     let path = full_path.clone();
                                     let line_number = m.line;
-                                    let start = m.start;
-                                    let end = m.end;
-                                    let line_content = m.line_content.clone();
+                                    let position = (m.line, m.start);
+                                    let preview_content = m.line_content.clone();
 
-                                    focus_text(
-                                        move || {
-                                            let config = config.get();
-                                            let content = if config
-                                                .ui
-                                                .trim_search_results_whitespace
-                                            {
-                                                m.line_content.trim()
-                                            } else {
-                                                &m.line_content
-                                            };
-                                            format!("{}: {content}", m.line,)
-                                        },
-                                        move || {
-                                            let config = config.get();
-                                            let mut offset = if config
-                                                .ui
-                                                .trim_search_results_whitespace
-                                            {
-                                                line_content.trim_start().len()
-                                                    as i32
-                                                    - line_content.len() as i32
-                                            } else {
-                                                0
-                                            };
-                                            offset += line_number.to_string().len()
-                                                as i32
-                                                + 2;
-
-                                            ((start as i32 + offset) as usize
-                                                ..(end as i32 + offset) as usize)
-                                                .collect()
-                                        },
-                                        move || {
-                                            *config
-                                                .get()
-                                                .get_color(LapceColor::EDITOR_FOCUS)
-                                        },
-                                    )
+                                    stack(|| {
+                                        (
+                                            match_checkbox(
+                                                included, excluded, position, config,
+                                            ),
+                                            match_text(m, config),
+                                            replace_preview(
+                                                preview_content,
+                                                replacer,
+                                                replace_doc,
+                                                config,
+                                            ),
+                                        )
+                                    })
                                     .style(move |s| {
                                         let config = config.get();
                                         let icon_size = config.ui.icon_size() as f32;
                                         s.margin_left_px(10.0 + icon_size + 6.0)
+                                            .items_center()
                                     })
                                     .on_click(move |_| {
                                         internal_command.send(
@@ -340,3 +462,89 @@ fn search_result(
     })
     .style(|s| s.size_pct(100.0, 100.0))
 }
+
+/// Whether the match at `position`, its line and start column, is replaced,
+/// which can be toggled.
+fn match_checkbox(
+    included: RwSignal<bool>,
+    excluded: RwSignal<im::HashSet<(usize, usize)>>,
+    position: (usize, usize),
+    config: ReadSignal<Arc<LapceConfig>>,
+) -> impl View {
+    checkbox(
+        move || {
+            included.get() && !excluded.with(|excluded| excluded.contains(&position))
+        },
+        config,
+    )
+    .on_click(move |_| {
+        excluded.update(|excluded| {
+            if excluded.remove(&position).is_none() {
+                excluded.insert(position);
+            }
+        });
+        true
+    })
+    .style(|s| s.margin_right_px(6.0))
+}
+
+/// What the line becomes once replaced, shown when there's a replacement.
+fn replace_preview(
+    line_content: String,
+    replacer: RwSignal<Option<Replacer>>,
+    replace_doc: RwSignal<Document>,
+    config: ReadSignal<Arc<LapceConfig>>,
+) -> impl View {
+    label(move || {
+        replacer.with(|replacer| {
+            replacer
+                .as_ref()
+                .map(|replacer| {
+                    format!("→ {}", replacer.replace_line(&line_content).trim())
+                })
+                .unwrap_or_default()
+        })
+    })
+    .style(move |s| {
+        let has_replacement = replace_doc.with(|doc| !doc.buffer().is_empty());
+        let config = config.get();
+        s.margin_left_px(6.0)
+            .color(*config.get_color(LapceColor::EDITOR_DIM))
+            .apply_if(!has_replacement || replacer.with(|r| r.is_none()), |s| {
+                s.hide()
+            })
+    })
+}
+
+/// The line of a match with the match highlighted.
+fn match_text(m: SearchMatch, config: ReadSignal<Arc<LapceConfig>>) -> impl View {
+    let line_number = m.line;
+    let start = m.start;
+    let end = m.end;
+    let line_content = m.line_content.clone();
+
+    focus_text(
+        move || {
+            let config = config.get();
+            let content = if config.ui.trim_search_results_whitespace {
+                m.line_content.trim()
+            } else {
+                &m.line_content
+            };
+            format!("{}: {content}", m.line,)
+        },
+        move || {
+            let config = config.get();
+            let mut offset = if config.ui.trim_search_results_whitespace {
+                line_content.trim_start().len() as i32 - line_content.len() as i32
+            } else {
+                0
+            };
+            offset += line_number.to_string().len() as i32 + 2;
+
+            ((start as i32 + offset) as usize..(end as i32 + offset) as usize)
+                .collect()
+        },
+        move || *config.get().get_color(LapceColor::EDITOR_FOCUS),
+    )
+}
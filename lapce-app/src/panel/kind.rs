@@ -15,6 +15,9 @@ pub enum PanelKind {
     Search,
     Problem,
     Debug,
+    Outline,
+    EditPreview,
+    Task,
 }
 
 impl PanelKind {
@@ -27,6 +30,9 @@ pub fn svg_name(&self) -> &'static str {
             PanelKind::Search => LapceIcons::SEARCH,
             PanelKind::Problem => LapceIcons::PROBLEM,
             PanelKind::Debug => LapceIcons::DEBUG,
+            PanelKind::Outline => LapceIcons::OUTLINE,
+            PanelKind::EditPreview => LapceIcons::EDIT_PREVIEW,
+            PanelKind::Task => LapceIcons::TASK,
         }
     }
 
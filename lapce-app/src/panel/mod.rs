@@ -1,11 +1,14 @@
 pub mod data;
 pub mod debug_view;
+pub mod edit_preview_view;
 pub mod global_search_view;
 pub mod kind;
+pub mod outline_view;
 pub mod plugin_view;
 pub mod position;
 pub mod problem_view;
 pub mod source_control_view;
 pub mod style;
+pub mod task_view;
 pub mod terminal_view;
 pub mod view;
@@ -0,0 +1,187 @@
+use std::rc::Rc;
+
+use floem::{
+    event::EventListener,
+    peniko::{
+        kurbo::{Point, Size},
+        Color,
+    },
+    style::CursorStyle,
+    view::View,
+    views::{
+        container, container_box, empty, label, scroll, stack, svg, virtual_list,
+        Decorators, VirtualListDirection, VirtualListItemSize,
+    },
+};
+
+use super::{kind::PanelKind, position::PanelPosition};
+use crate::{
+    app::VectorItems,
+    config::{color::LapceColor, icon::LapceIcons},
+    outline::OutlineData,
+    text_input::text_input,
+    window_tab::{Focus, WindowTabData},
+};
+
+pub fn outline_panel(
+    window_tab_data: Rc<WindowTabData>,
+    _position: PanelPosition,
+) -> impl View {
+    let config = window_tab_data.common.config;
+    let filter_editor = window_tab_data.outline.filter_editor.clone();
+    let focus = window_tab_data.common.focus;
+    let is_focused = move || focus.get() == Focus::Panel(PanelKind::Outline);
+    stack(|| {
+        (
+            container(|| {
+                text_input(filter_editor, is_focused)
+                    .on_event(EventListener::PointerDown, move |_| {
+                        focus.set(Focus::Panel(PanelKind::Outline));
+                        false
+                    })
+                    .style(move |s| {
+                        s.width_pct(100.0)
+                            .border(1.0)
+                            .border_radius(6.0)
+                            .border_color(
+                                *config.get().get_color(LapceColor::LAPCE_BORDER),
+                            )
+                    })
+            })
+            .style(|s| s.width_pct(100.0).padding_px(10.0)),
+            outline_tree(window_tab_data.outline.clone()),
+        )
+    })
+    .style(|s| s.size_pct(100.0, 100.0).flex_col())
+}
+
+fn outline_tree(outline: OutlineData) -> impl View {
+    let config = outline.common.config;
+    let ui_line_height = outline.common.ui_line_height;
+    let rows = outline.rows;
+    let active = outline.active;
+    let generation = outline.generation;
+    container(|| {
+        scroll(move || {
+            virtual_list(
+                VirtualListDirection::Vertical,
+                VirtualListItemSize::Fixed(Box::new(move || ui_line_height.get())),
+                move || VectorItems(rows.get()),
+                move |(_, index)| (generation.get_untracked(), *index),
+                move |(_, index)| outline_item_view(outline.clone(), index),
+            )
+            .style(|s| s.flex_col().min_width_pct(100.0))
+        })
+        .on_ensure_visible(move || {
+            let line_height = ui_line_height.get();
+            let row = active
+                .get()
+                .and_then(|active| rows.with(|rows| rows.index_of(&active)))
+                .unwrap_or(0);
+            Size::new(1.0, line_height)
+                .to_rect()
+                .with_origin(Point::new(0.0, row as f64 * line_height))
+        })
+        .style(|s| s.absolute().size_pct(100.0, 100.0))
+    })
+    .style(move |s| {
+        s.width_pct(100.0)
+            .flex_basis_px(0.0)
+            .flex_grow(1.0)
+            .color(*config.get().get_color(LapceColor::PANEL_FOREGROUND))
+    })
+}
+
+fn outline_item_view(outline: OutlineData, index: usize) -> impl View {
+    let config = outline.common.config;
+    let ui_line_height = outline.common.ui_line_height;
+    let collapsed = outline.collapsed;
+    let active = outline.active;
+    let item = outline
+        .items
+        .with_untracked(|items| items.get(index).cloned());
+    let Some(item) = item else {
+        return container_box(|| Box::new(empty()));
+    };
+    let has_children = item.has_children(index);
+    let depth = item.depth;
+    let kind = item.kind;
+    let key = item.key.clone();
+    let name = item.name.clone();
+    let detail = item.detail.clone().unwrap_or_default();
+    let toggle_outline = outline.clone();
+
+    container_box(move || {
+        Box::new(
+            stack(move || {
+                (
+                    svg(move || {
+                        let is_collapsed =
+                            collapsed.with(|collapsed| collapsed.contains(&key));
+                        config.get().ui_svg(if is_collapsed {
+                            LapceIcons::ITEM_CLOSED
+                        } else {
+                            LapceIcons::ITEM_OPENED
+                        })
+                    })
+                    .on_click(move |_| {
+                        if has_children {
+                            toggle_outline.toggle_collapsed(index);
+                        }
+                        true
+                    })
+                    .style(move |s| {
+                        let config = config.get();
+                        let size = config.ui.icon_size() as f32;
+                        let color = if has_children {
+                            *config.get_color(LapceColor::LAPCE_ICON_ACTIVE)
+                        } else {
+                            Color::TRANSPARENT
+                        };
+                        s.size_px(size, size).margin_left_px(10.0).color(color)
+                    }),
+                    svg(move || config.get().symbol_svg(&kind).unwrap_or_default())
+                        .style(move |s| {
+                            let config = config.get();
+                            let size = config.ui.icon_size() as f32;
+                            s.min_width_px(size)
+                                .size_px(size, size)
+                                .margin_horiz_px(6.0)
+                                .color(
+                                    *config.get_color(LapceColor::LAPCE_ICON_ACTIVE),
+                                )
+                        }),
+                    label(move || name.clone()).style(|s| s.margin_right_px(6.0)),
+                    label(move || detail.clone()).style(move |s| {
+                        s.color(*config.get().get_color(LapceColor::EDITOR_DIM))
+                            .min_width_px(0.0)
+                            .text_ellipsis()
+                    }),
+                )
+            })
+            .style(move |s| {
+                s.items_center()
+                    .padding_right_px(10.0)
+                    .padding_left_px((depth * 10) as f32)
+                    .min_width_pct(100.0)
+                    .height_px(ui_line_height.get() as f32)
+            }),
+        )
+    })
+    .on_click(move |_| {
+        outline.jump_to(index);
+        true
+    })
+    .style(move |s| {
+        s.min_width_pct(100.0)
+            .apply_if(active.get() == Some(index), |s| {
+                s.background(
+                    *config.get().get_color(LapceColor::PANEL_CURRENT_BACKGROUND),
+                )
+            })
+    })
+    .hover_style(move |s| {
+        s.background(*config.get().get_color(LapceColor::PANEL_HOVERED_BACKGROUND))
+            .cursor(CursorStyle::Pointer)
+    })
+}
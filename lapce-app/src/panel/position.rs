@@ -24,6 +24,21 @@ pub fn is_first(&self) -> bool {
         )
     }
 
+    /// The panel container the position is in
+    pub fn container(&self) -> PanelContainerPosition {
+        match self {
+            PanelPosition::LeftTop | PanelPosition::LeftBottom => {
+                PanelContainerPosition::Left
+            }
+            PanelPosition::BottomLeft | PanelPosition::BottomRight => {
+                PanelContainerPosition::Bottom
+            }
+            PanelPosition::RightTop | PanelPosition::RightBottom => {
+                PanelContainerPosition::Right
+            }
+        }
+    }
+
     pub fn peer(&self) -> PanelPosition {
         match &self {
             PanelPosition::LeftTop => PanelPosition::LeftBottom,
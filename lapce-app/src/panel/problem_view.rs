@@ -1,6 +1,7 @@
 use std::{path::PathBuf, rc::Rc, sync::Arc};
 
 use floem::{
+    event::EventListener,
     peniko::Color,
     reactive::{create_memo, create_rw_signal, ReadSignal},
     style::{CursorStyle, Style},
@@ -9,15 +10,18 @@
 };
 use lsp_types::{DiagnosticRelatedInformation, DiagnosticSeverity};
 
-use super::{position::PanelPosition, view::panel_header};
+use super::{kind::PanelKind, position::PanelPosition, view::panel_header};
 use crate::{
+    app::clickable_icon,
     command::InternalCommand,
     config::{color::LapceColor, icon::LapceIcons, LapceConfig},
     doc::{DiagnosticData, EditorDiagnostic},
     editor::location::{EditorLocation, EditorPosition},
     listener::Listener,
+    problem::ProblemData,
     proxy::path_from_url,
-    window_tab::WindowTabData,
+    text_input::text_input,
+    window_tab::{Focus, WindowTabData},
     workspace::LapceWorkspace,
 };
 
@@ -27,6 +31,65 @@ pub fn problem_panel(
 ) -> impl View {
     let config = window_tab_data.common.config;
     let is_bottom = position.is_bottom();
+    let filter_editor = window_tab_data.problem.filter_editor.clone();
+    let show_errors = window_tab_data.problem.show_errors;
+    let show_warnings = window_tab_data.problem.show_warnings;
+    let focus = window_tab_data.common.focus;
+    let is_focused = move || focus.get() == Focus::Panel(PanelKind::Problem);
+    stack(|| {
+        (
+            container(|| {
+                stack(|| {
+                    (
+                        text_input(filter_editor, is_focused)
+                            .style(|s| s.width_pct(100.0)),
+                        clickable_icon(
+                            || LapceIcons::ERROR,
+                            move || show_errors.update(|show| *show = !*show),
+                            move || show_errors.get(),
+                            || false,
+                            config,
+                        )
+                        .style(|s| s.padding_vert_px(4.0)),
+                        clickable_icon(
+                            || LapceIcons::WARNING,
+                            move || show_warnings.update(|show| *show = !*show),
+                            move || show_warnings.get(),
+                            || false,
+                            config,
+                        )
+                        .style(|s| s.padding_left_px(6.0)),
+                    )
+                })
+                .on_event(EventListener::PointerDown, move |_| {
+                    focus.set(Focus::Panel(PanelKind::Problem));
+                    false
+                })
+                .style(move |s| {
+                    s.width_pct(100.0)
+                        .padding_right_px(6.0)
+                        .items_center()
+                        .border(1.0)
+                        .border_radius(6.0)
+                        .border_color(
+                            *config.get().get_color(LapceColor::LAPCE_BORDER),
+                        )
+                })
+            })
+            .style(|s| s.width_pct(100.0).padding_px(10.0)),
+            problem_sections(window_tab_data, is_bottom),
+        )
+    })
+    .style(|s| s.size_pct(100.0, 100.0).flex_col())
+}
+
+fn problem_sections(
+    window_tab_data: Rc<WindowTabData>,
+    is_bottom: bool,
+) -> impl View {
+    let config = window_tab_data.common.config;
+    let show_errors = window_tab_data.problem.show_errors;
+    let show_warnings = window_tab_data.problem.show_warnings;
     stack(|| {
         (
             stack(|| {
@@ -46,6 +109,7 @@ pub fn problem_panel(
                     .border_color(*config.get_color(LapceColor::LAPCE_BORDER))
                     .apply_if(is_bottom, |s| s.border_right(1.0))
                     .apply_if(!is_bottom, |s| s.border_bottom(1.0))
+                    .apply_if(!show_errors.get(), |s| s.hide())
             }),
             stack(|| {
                 (
@@ -53,11 +117,18 @@ pub fn problem_panel(
                     problem_section(window_tab_data, DiagnosticSeverity::WARNING),
                 )
             })
-            .style(|s| s.flex_col().flex_basis_px(0.0).flex_grow(1.0)),
+            .style(move |s| {
+                s.flex_col()
+                    .flex_basis_px(0.0)
+                    .flex_grow(1.0)
+                    .apply_if(!show_warnings.get(), |s| s.hide())
+            }),
         )
     })
     .style(move |s| {
-        s.size_pct(100.0, 100.0)
+        s.width_pct(100.0)
+            .flex_basis_px(0.0)
+            .flex_grow(1.0)
             .apply_if(!is_bottom, |s| s.flex_col())
     })
 }
@@ -68,6 +139,7 @@ fn problem_section(
 ) -> impl View {
     let config = window_tab_data.common.config;
     let main_split = window_tab_data.main_split.clone();
+    let problem = window_tab_data.problem.clone();
     let internal_command = window_tab_data.common.internal_command;
     container(|| {
         scroll(move || {
@@ -80,6 +152,7 @@ fn problem_section(
                         workspace.clone(),
                         path,
                         diagnostic_data,
+                        problem.clone(),
                         severity,
                         internal_command,
                         config,
@@ -97,18 +170,25 @@ fn file_view(
     workspace: Arc<LapceWorkspace>,
     path: PathBuf,
     diagnostic_data: DiagnosticData,
+    problem: ProblemData,
     severity: DiagnosticSeverity,
     internal_command: Listener<InternalCommand>,
     config: ReadSignal<Arc<LapceConfig>>,
 ) -> impl View {
     let collpased = create_rw_signal(false);
 
+    let full_path = path.clone();
+    let path = workspace.display_path(&path);
+    let filter_path = path.to_string_lossy().to_string();
+
     let diagnostics = create_memo(move |_| {
         let diagnostics = diagnostic_data.diagnostics.get();
         let diagnostics: im::Vector<EditorDiagnostic> = diagnostics
             .into_iter()
             .filter_map(|d| {
-                if d.diagnostic.severity == Some(severity) {
+                if d.diagnostic.severity == Some(severity)
+                    && problem.matches(severity, &filter_path, &d.diagnostic.message)
+                {
                     Some(d)
                 } else {
                     None
@@ -118,14 +198,6 @@ fn file_view(
         diagnostics
     });
 
-    let full_path = path.clone();
-    let path = if let Some(workspace_path) = workspace.path.as_ref() {
-        path.strip_prefix(workspace_path)
-            .unwrap_or(&full_path)
-            .to_path_buf()
-    } else {
-        path
-    };
     let style_path = path.clone();
 
     let icon = match severity {
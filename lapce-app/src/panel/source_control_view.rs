@@ -87,7 +87,7 @@ pub fn source_control_panel(
                                 if let Event::PointerDown(pointer_event) = event {
                                     id.request_active();
                                     let editor = editor.get_untracked();
-                                    editor.pointer_down(&pointer_event);
+                                    return editor.pointer_down(&pointer_event);
                                 }
                                 false
                             })
@@ -221,14 +221,9 @@ fn file_diffs_view(source_control: SourceControlData) -> impl View {
         let full_path = path.clone();
         let diff_for_menu = diff.clone();
         let path_for_click = full_path.clone();
+        let path_for_menu = full_path.clone();
 
-        let path = if let Some(workspace_path) = workspace.path.as_ref() {
-            path.strip_prefix(workspace_path)
-                .unwrap_or(&full_path)
-                .to_path_buf()
-        } else {
-            path
-        };
+        let path = workspace.display_path(&path);
         let file_name = path
             .file_name()
             .and_then(|s| s.to_str())
@@ -330,6 +325,7 @@ fn file_diffs_view(source_control: SourceControlData) -> impl View {
         })
         .on_event(EventListener::PointerDown, move |event| {
             let diff_for_menu = diff_for_menu.clone();
+            let is_deleted = matches!(diff_for_menu, FileDiff::Deleted(_));
 
             let discard = move || {
                 lapce_command.send(LapceCommand {
@@ -342,7 +338,39 @@ fn file_diffs_view(source_control: SourceControlData) -> impl View {
 
             if let Event::PointerDown(pointer_event) = event {
                 if pointer_event.button.is_secondary() {
+                    let open = {
+                        let path = path_for_menu.clone();
+                        move || {
+                            internal_command.send(InternalCommand::OpenFile {
+                                path: path.clone(),
+                            });
+                        }
+                    };
+                    let toggle = {
+                        let path = path_for_menu.clone();
+                        move || {
+                            file_diffs.update(|diffs| {
+                                if let Some((_, checked)) = diffs.get_mut(&path) {
+                                    *checked = !*checked;
+                                }
+                            });
+                        }
+                    };
                     let menu = Menu::new("")
+                        .entry(
+                            MenuItem::new("Open File")
+                                .enabled(!is_deleted)
+                                .action(open),
+                        )
+                        .separator()
+                        .entry(
+                            MenuItem::new(if checked {
+                                "Unstage Changes"
+                            } else {
+                                "Stage Changes"
+                            })
+                            .action(toggle),
+                        )
                         .entry(MenuItem::new("Discard Changes").action(discard));
                     show_context_menu(menu, None);
                 }
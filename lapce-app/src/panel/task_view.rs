@@ -0,0 +1,242 @@
+use std::rc::Rc;
+
+use floem::{
+    cosmic_text::{Attrs, AttrsList, TextLayout, Weight},
+    peniko::kurbo::{Point, Size},
+    style::CursorStyle,
+    view::View,
+    views::{
+        container, label, list, rich_text, scroll, stack, virtual_list, Decorators,
+        VirtualListDirection, VirtualListItemSize,
+    },
+};
+
+use super::position::PanelPosition;
+use crate::{
+    app::{clickable_icon, VectorItems},
+    config::{color::LapceColor, icon::LapceIcons, LapceConfig},
+    task::{OutputLine, TaskData, TaskRun},
+    window_tab::WindowTabData,
+};
+
+pub fn task_panel(
+    window_tab_data: Rc<WindowTabData>,
+    _position: PanelPosition,
+) -> impl View {
+    let task = window_tab_data.task.clone();
+    stack(|| (task_header(task.clone()), task_output(task)))
+        .style(|s| s.size_pct(100.0, 100.0).flex_col())
+}
+
+/// The run whose output is shown, tracking which one it is.
+fn shown_run(task: &TaskData) -> Option<TaskRun> {
+    let task_id = task.shown.get()?;
+    task.runs
+        .with(|runs| runs.iter().find(|run| run.task_id == task_id).cloned())
+}
+
+/// The runs with how they ended, to pick the one to show, and the buttons to
+/// run the shown one again or kill it.
+fn task_header(task: TaskData) -> impl View {
+    let config = task.common.config;
+    let runs = task.runs;
+    let shown = task.shown;
+    let rerun = task.clone();
+    let kill = task;
+    let running = move || {
+        let Some(task_id) = shown.get() else {
+            return false;
+        };
+        runs.with(|runs| {
+            runs.iter()
+                .find(|run| run.task_id == task_id)
+                .map_or(false, |run| run.exit.with(|exit| exit.is_none()))
+        })
+    };
+    stack(|| {
+        (
+            label(|| "Run a task from the Run Task palette".to_string()).style(
+                move |s| {
+                    s.padding_horiz_px(10.0)
+                        .color(*config.get().get_color(LapceColor::EDITOR_DIM))
+                        .apply_if(runs.with(|runs| !runs.is_empty()), |s| s.hide())
+                },
+            ),
+            list(
+                move || runs.get(),
+                |run| run.task_id,
+                move |run| {
+                    let task_id = run.task_id;
+                    label(move || format!("{}: {}", run.task.name, run.status()))
+                        .on_click(move |_| {
+                            shown.set(Some(task_id));
+                            true
+                        })
+                        .style(move |s| {
+                            let config = config.get();
+                            s.padding_horiz_px(10.0)
+                                .padding_vert_px(4.0)
+                                .border_radius(6.0)
+                                .cursor(CursorStyle::Pointer)
+                                .apply_if(shown.get() == Some(task_id), |s| {
+                                    s.background(*config.get_color(
+                                        LapceColor::PANEL_CURRENT_BACKGROUND,
+                                    ))
+                                })
+                        })
+                        .hover_style(move |s| {
+                            s.background(
+                                *config
+                                    .get()
+                                    .get_color(LapceColor::PANEL_HOVERED_BACKGROUND),
+                            )
+                        })
+                },
+            )
+            .style(|s| s.flex_grow(1.0).min_width_px(0.0).items_center()),
+            clickable_icon(
+                || LapceIcons::DEBUG_RESTART,
+                move || {
+                    if let Some(run) = rerun
+                        .shown
+                        .get_untracked()
+                        .and_then(|task_id| rerun.run_of(task_id))
+                    {
+                        rerun.run(run.task);
+                    }
+                },
+                || false,
+                move || shown.with(|shown| shown.is_none()),
+                config,
+            ),
+            clickable_icon(
+                || LapceIcons::DEBUG_STOP,
+                move || {
+                    if let Some(task_id) = kill.shown.get_untracked() {
+                        kill.kill(task_id);
+                    }
+                },
+                || false,
+                move || !running(),
+                config,
+            ),
+        )
+    })
+    .style(move |s| {
+        s.width_pct(100.0)
+            .items_center()
+            .padding_horiz_px(6.0)
+            .padding_vert_px(4.0)
+            .border_bottom(1.0)
+            .border_color(*config.get().get_color(LapceColor::LAPCE_BORDER))
+    })
+}
+
+/// The output of the shown run, which follows the lines as they're written.
+fn task_output(task: TaskData) -> impl View {
+    let config = task.common.config;
+    let line_height = move || config.get().editor.line_height() as f64;
+    let lines = {
+        let task = task.clone();
+        move || {
+            shown_run(&task)
+                .map(|run| run.lines.get())
+                .unwrap_or_default()
+        }
+    };
+    let ensure_lines = lines.clone();
+    let key_task = task.clone();
+    container(|| {
+        scroll(|| {
+            virtual_list(
+                VirtualListDirection::Vertical,
+                VirtualListItemSize::Fixed(Box::new(line_height)),
+                move || VectorItems(lines()),
+                // Lines are dropped from the front as the output grows, so
+                // they're told apart by how many lines came before them
+                move |(index, _)| {
+                    let run = key_task
+                        .shown
+                        .get_untracked()
+                        .and_then(|task_id| key_task.run_of(task_id));
+                    let dropped =
+                        run.as_ref().map_or(0, |run| run.dropped.get_untracked());
+                    (run.map(|run| run.task_id), dropped + *index)
+                },
+                move |(_, line)| output_line(task.clone(), line),
+            )
+            .style(|s| s.flex_col().min_width_pct(100.0))
+        })
+        .on_ensure_visible(move || {
+            let line_height = line_height();
+            let last = ensure_lines().len().saturating_sub(1);
+            Size::new(1.0, line_height)
+                .to_rect()
+                .with_origin(Point::new(0.0, last as f64 * line_height))
+        })
+        .style(|s| s.absolute().size_pct(100.0, 100.0))
+    })
+    .style(move |s| {
+        s.width_pct(100.0)
+            .flex_basis_px(0.0)
+            .flex_grow(1.0)
+            .color(*config.get().get_color(LapceColor::PANEL_FOREGROUND))
+    })
+}
+
+/// A line of output, which goes to the place in a file it points to when
+/// it's clicked if there's one.
+fn output_line(task: TaskData, line: OutputLine) -> impl View {
+    let config = task.common.config;
+    let has_link = line.link.is_some();
+    let link = line.link.clone();
+    rich_text(move || output_layout(&line, &config.get()))
+        .on_click(move |_| {
+            let Some(link) = link.as_ref() else {
+                return false;
+            };
+            if let Some(run) = task
+                .shown
+                .get_untracked()
+                .and_then(|task_id| task.run_of(task_id))
+            {
+                task.open_link(&run, link);
+            }
+            true
+        })
+        .style(move |s| {
+            s.padding_horiz_px(10.0)
+                .height_px(config.get().editor.line_height() as f32)
+                .apply_if(has_link, |s| s.cursor(CursorStyle::Pointer))
+        })
+}
+
+/// The text of a line of output in the colors of its escape sequences, with
+/// the place it points to looking like a link.
+fn output_layout(line: &OutputLine, config: &LapceConfig) -> TextLayout {
+    let family = config.editor.font_family();
+    let attrs = Attrs::new()
+        .color(*config.get_color(LapceColor::PANEL_FOREGROUND))
+        .family(&family)
+        .font_size(config.editor.font_size() as f32);
+    let mut attrs_list = AttrsList::new(attrs);
+    for (range, style) in &line.styles {
+        let mut attrs = attrs;
+        if let Some(fg) = style.fg {
+            attrs = attrs.color(*config.get_color(fg));
+        }
+        if style.bold {
+            attrs = attrs.weight(Weight::BOLD);
+        }
+        attrs_list.add_span(range.clone(), attrs);
+    }
+    if let Some(link) = line.link.as_ref() {
+        attrs_list.add_span(
+            link.range.clone(),
+            attrs.color(*config.get_color(LapceColor::EDITOR_LINK)),
+        );
+    }
+    let mut text_layout = TextLayout::new();
+    text_layout.set_text(&line.text, attrs_list);
+    text_layout
+}
@@ -9,19 +9,22 @@
 
 use super::{
     debug_view::debug_panel,
+    edit_preview_view::edit_preview_panel,
     global_search_view::global_search_panel,
     kind::PanelKind,
+    outline_view::outline_panel,
     plugin_view::plugin_panel,
     position::{PanelContainerPosition, PanelPosition},
     problem_view::problem_panel,
     source_control_view::source_control_panel,
+    task_view::task_panel,
     terminal_view::terminal_panel,
 };
 use crate::{
     app::clickable_icon,
     config::{color::LapceColor, icon::LapceIcons, LapceConfig},
     file_explorer::view::file_explorer_panel,
-    window_tab::{DragContent, WindowTabData},
+    window_tab::{DragContent, Focus, WindowTabData},
 };
 
 pub fn panel_container_view(
@@ -136,6 +139,7 @@ fn panel_view(
     position: PanelPosition,
 ) -> impl View {
     let panel = window_tab_data.panel.clone();
+    let focus = window_tab_data.common.focus;
     let panels = move || {
         panel
             .panels
@@ -173,8 +177,25 @@ fn panel_view(
                 PanelKind::Debug => container_box(|| {
                     Box::new(debug_panel(window_tab_data.clone(), position))
                 }),
+                PanelKind::Outline => container_box(|| {
+                    Box::new(outline_panel(window_tab_data.clone(), position))
+                }),
+                PanelKind::EditPreview => container_box(|| {
+                    Box::new(edit_preview_panel(window_tab_data.clone(), position))
+                }),
+                PanelKind::Task => container_box(|| {
+                    Box::new(task_panel(window_tab_data.clone(), position))
+                }),
             };
-            view.style(|s| s.size_pct(100.0, 100.0))
+            // Clicking anywhere in the panel gives it the keyboard, rather
+            // than only its inputs
+            view.on_event(EventListener::PointerDown, move |_| {
+                if focus.get_untracked() != Focus::Panel(kind) {
+                    focus.set(Focus::Panel(kind));
+                }
+                false
+            })
+            .style(|s| s.size_pct(100.0, 100.0))
         },
     )
     .style(move |s| {
@@ -225,6 +246,9 @@ fn panel_picker(
                 PanelKind::Search => LapceIcons::SEARCH,
                 PanelKind::Problem => LapceIcons::PROBLEM,
                 PanelKind::Debug => LapceIcons::DEBUG_ALT,
+                PanelKind::Outline => LapceIcons::OUTLINE,
+                PanelKind::EditPreview => LapceIcons::EDIT_PREVIEW,
+                PanelKind::Task => LapceIcons::TASK,
             };
             let is_active = {
                 let window_tab_data = window_tab_data.clone();
@@ -0,0 +1,343 @@
+use std::{
+    fmt::Write,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use floem::{
+    id::Id,
+    view::{ChangeFlags, View},
+    ViewContext,
+};
+
+/// Whether the timings are recorded, which is only while the overlay shows
+/// them, so that the timers cost a relaxed load otherwise
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+static RECORDER: Mutex<Recorder> = Mutex::new(Recorder::new());
+
+/// How many of the latest samples of each phase the percentiles are over
+const SAMPLES: usize = 256;
+
+/// A phase of handling an edit, from the key press to the paint
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    KeyDispatch,
+    Edit,
+    Highlight,
+    Layout,
+    Paint,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 5] = [
+        Phase::KeyDispatch,
+        Phase::Edit,
+        Phase::Highlight,
+        Phase::Layout,
+        Phase::Paint,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::KeyDispatch => "key dispatch",
+            Phase::Edit => "edit",
+            Phase::Highlight => "highlight",
+            Phase::Layout => "layout",
+            Phase::Paint => "paint",
+        }
+    }
+}
+
+/// A ring of the latest samples, kept in place so recording one doesn't
+/// allocate
+#[derive(Clone, Copy)]
+struct Samples<T: Copy> {
+    samples: [T; SAMPLES],
+    /// Where the next sample goes
+    next: usize,
+    len: usize,
+}
+
+impl<T: Copy> Samples<T> {
+    const fn new(empty: T) -> Self {
+        Self {
+            samples: [empty; SAMPLES],
+            next: 0,
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, sample: T) {
+        self.samples[self.next] = sample;
+        self.next = (self.next + 1) % SAMPLES;
+        self.len = (self.len + 1).min(SAMPLES);
+    }
+
+    fn latest(&self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        Some(self.samples[(self.next + SAMPLES - 1) % SAMPLES])
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &T> {
+        self.samples[..self.len].iter()
+    }
+}
+
+impl Samples<Duration> {
+    /// The duration `p` of the samples are no longer than, for `p` from 0 to
+    /// 1.
+    fn percentile(&self, p: f64) -> Option<Duration> {
+        if self.len == 0 {
+            return None;
+        }
+        let mut sorted = self.samples;
+        let sorted = &mut sorted[..self.len];
+        sorted.sort_unstable();
+        let index = ((self.len - 1) as f64 * p.clamp(0.0, 1.0)).round() as usize;
+        Some(sorted[index])
+    }
+}
+
+struct Recorder {
+    phases: [Samples<Duration>; Phase::ALL.len()],
+    /// When the latest frames were painted
+    frames: Samples<Option<Instant>>,
+}
+
+impl Recorder {
+    const fn new() -> Self {
+        Self {
+            phases: [Samples::new(Duration::ZERO); Phase::ALL.len()],
+            frames: Samples::new(None),
+        }
+    }
+
+    /// The frames painted in the last second.
+    fn fps(&self, now: Instant) -> usize {
+        self.frames
+            .iter()
+            .flatten()
+            .filter(|frame| now.saturating_duration_since(**frame).as_secs() < 1)
+            .count()
+    }
+}
+
+/// Records the time from its creation to its drop as a sample of its phase,
+/// if the timings are being recorded.
+#[must_use]
+pub struct PhaseTimer {
+    phase: Phase,
+    start: Option<Instant>,
+}
+
+impl Drop for PhaseTimer {
+    fn drop(&mut self) {
+        if let Some(start) = self.start {
+            let elapsed = start.elapsed();
+            if let Ok(mut recorder) = RECORDER.lock() {
+                recorder.phases[self.phase as usize].push(elapsed);
+            }
+        }
+    }
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Start or stop recording the timings, starting over from no samples.
+pub fn set_enabled(enabled: bool) {
+    if let Ok(mut recorder) = RECORDER.lock() {
+        *recorder = Recorder::new();
+    }
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Time `phase` until the returned timer is dropped.
+pub fn time(phase: Phase) -> PhaseTimer {
+    PhaseTimer {
+        phase,
+        start: is_enabled().then(Instant::now),
+    }
+}
+
+/// Count a frame towards the frames per second. It's done by the
+/// [`FrameCounter`] of the window, so each frame counts once however many
+/// editors it paints.
+fn frame() {
+    if !is_enabled() {
+        return;
+    }
+    if let Ok(mut recorder) = RECORDER.lock() {
+        recorder.frames.push(Some(Instant::now()));
+    }
+}
+
+pub fn frame_counter() -> FrameCounter {
+    let cx = ViewContext::get_current();
+    let id = cx.new_id();
+    FrameCounter { id }
+}
+
+/// A view that paints nothing, and counts each time the window it's in is
+/// painted.
+pub struct FrameCounter {
+    id: Id,
+}
+
+impl View for FrameCounter {
+    fn id(&self) -> Id {
+        self.id
+    }
+
+    fn child(&self, _id: Id) -> Option<&dyn View> {
+        None
+    }
+
+    fn child_mut(&mut self, _id: Id) -> Option<&mut dyn View> {
+        None
+    }
+
+    fn children(&self) -> Vec<&dyn View> {
+        Vec::new()
+    }
+
+    fn children_mut(&mut self) -> Vec<&mut dyn View> {
+        Vec::new()
+    }
+
+    fn update(
+        &mut self,
+        _cx: &mut floem::context::UpdateCx,
+        _state: Box<dyn std::any::Any>,
+    ) -> ChangeFlags {
+        ChangeFlags::empty()
+    }
+
+    fn layout(
+        &mut self,
+        cx: &mut floem::context::LayoutCx,
+    ) -> floem::taffy::prelude::Node {
+        cx.layout_node(self.id, false, |_cx| Vec::new())
+    }
+
+    fn event(
+        &mut self,
+        _cx: &mut floem::context::EventCx,
+        _id_path: Option<&[Id]>,
+        _event: floem::event::Event,
+    ) -> bool {
+        false
+    }
+
+    fn paint(&mut self, _cx: &mut floem::context::PaintCx) {
+        frame();
+    }
+}
+
+/// The timings of a phase, over its latest samples
+pub struct PhaseStats {
+    pub phase: Phase,
+    pub latest: Duration,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+}
+
+/// The frames painted in the last second, and the timings of the phases
+/// that have been timed.
+pub fn stats() -> (usize, Vec<PhaseStats>) {
+    let Ok(recorder) = RECORDER.lock() else {
+        return (0, Vec::new());
+    };
+    let phases = Phase::ALL
+        .iter()
+        .filter_map(|phase| {
+            let samples = &recorder.phases[*phase as usize];
+            Some(PhaseStats {
+                phase: *phase,
+                latest: samples.latest()?,
+                p50: samples.percentile(0.5)?,
+                p95: samples.percentile(0.95)?,
+                p99: samples.percentile(0.99)?,
+            })
+        })
+        .collect();
+    (recorder.fps(Instant::now()), phases)
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// The frames per second and the latest timing of each phase, a line each.
+pub fn overlay_text() -> String {
+    let (fps, phases) = stats();
+    let mut text = format!("{fps} fps");
+    for stats in phases {
+        let _ = write!(
+            text,
+            "\n{}: {:.2}ms",
+            stats.phase.name(),
+            millis(stats.latest)
+        );
+    }
+    text
+}
+
+/// The percentiles of the timings of each phase, for saving to a file.
+pub fn summary() -> String {
+    let (fps, phases) = stats();
+    let mut text = format!("frames in the last second: {fps}\n\n");
+    let _ = writeln!(
+        text,
+        "{:<14}{:>10}{:>10}{:>10}{:>10}",
+        "phase", "latest", "p50", "p95", "p99"
+    );
+    for stats in phases {
+        let _ = writeln!(
+            text,
+            "{:<14}{:>8.2}ms{:>8.2}ms{:>8.2}ms{:>8.2}ms",
+            stats.phase.name(),
+            millis(stats.latest),
+            millis(stats.p50),
+            millis(stats.p95),
+            millis(stats.p99),
+        );
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Samples, SAMPLES};
+
+    #[test]
+    fn test_samples() {
+        let mut samples = Samples::new(Duration::ZERO);
+        assert_eq!(samples.latest(), None);
+        assert_eq!(samples.percentile(0.5), None);
+
+        for ms in 1..=100 {
+            samples.push(Duration::from_millis(ms));
+        }
+        assert_eq!(samples.latest(), Some(Duration::from_millis(100)));
+        assert_eq!(samples.percentile(0.0), Some(Duration::from_millis(1)));
+        assert_eq!(samples.percentile(0.5), Some(Duration::from_millis(51)));
+        assert_eq!(samples.percentile(1.0), Some(Duration::from_millis(100)));
+
+        // The oldest samples make way for the new ones
+        for _ in 0..SAMPLES {
+            samples.push(Duration::from_millis(1000));
+        }
+        assert_eq!(samples.percentile(0.0), Some(Duration::from_millis(1000)));
+    }
+}
@@ -0,0 +1,168 @@
+use floem::peniko::kurbo::{Point, Rect, Size};
+
+/// The side of its anchor a popup goes on while there's room for it there
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PopupSide {
+    Above,
+    Below,
+}
+
+/// Where a popup of `size` goes to be next to `anchor`, the line it's for
+/// from the x it lines up with. It goes on `side` of the line if it fits
+/// there, else on the other side if it fits there, else on the side with more
+/// room. It's kept within `bounds` as far as it fits in them.
+pub fn popup_origin(
+    anchor: Rect,
+    size: Size,
+    bounds: Rect,
+    side: PopupSide,
+) -> Point {
+    let room_above = anchor.y0 - bounds.y0;
+    let room_below = bounds.y1 - anchor.y1;
+    let above = match side {
+        PopupSide::Above => {
+            room_above >= size.height
+                || (room_below < size.height && room_above >= room_below)
+        }
+        PopupSide::Below => {
+            room_below < size.height
+                && (room_above >= size.height || room_above > room_below)
+        }
+    };
+    let y = if above {
+        anchor.y0 - size.height
+    } else {
+        anchor.y1
+    };
+
+    // A popup bigger than the room on either side covers the line rather
+    // than going out of the window
+    let y = y.min(bounds.y1 - size.height).max(bounds.y0);
+    let x = anchor.x0.min(bounds.x1 - size.width - 1.0).max(bounds.x0);
+    Point::new(x, y)
+}
+
+/// Whether the line of `anchor` can be seen in `viewport`, as the popups
+/// anchored to it are hidden until it's scrolled back.
+pub fn anchor_in_view(anchor: Rect, viewport: Rect) -> bool {
+    anchor.y1 > viewport.y0 && anchor.y0 < viewport.y1
+}
+
+#[cfg(test)]
+mod tests {
+    use floem::peniko::kurbo::{Point, Rect, Size};
+
+    use super::{anchor_in_view, popup_origin, PopupSide};
+
+    const BOUNDS: Rect = Rect::new(0.0, 0.0, 800.0, 600.0);
+    const SIZE: Size = Size::new(200.0, 100.0);
+
+    fn line(x: f64, y: f64) -> Rect {
+        Rect::new(x, y, x + 1.0, y + 20.0)
+    }
+
+    #[test]
+    fn test_popup_middle() {
+        let anchor = line(300.0, 300.0);
+        assert_eq!(
+            popup_origin(anchor, SIZE, BOUNDS, PopupSide::Above),
+            Point::new(300.0, 200.0)
+        );
+        assert_eq!(
+            popup_origin(anchor, SIZE, BOUNDS, PopupSide::Below),
+            Point::new(300.0, 320.0)
+        );
+    }
+
+    #[test]
+    fn test_popup_top_edge() {
+        // No room above, so it flips below
+        let anchor = line(300.0, 50.0);
+        assert_eq!(
+            popup_origin(anchor, SIZE, BOUNDS, PopupSide::Above),
+            Point::new(300.0, 70.0)
+        );
+        assert_eq!(
+            popup_origin(anchor, SIZE, BOUNDS, PopupSide::Below),
+            Point::new(300.0, 70.0)
+        );
+    }
+
+    #[test]
+    fn test_popup_bottom_edge() {
+        // No room below, so it flips above
+        let anchor = line(300.0, 560.0);
+        assert_eq!(
+            popup_origin(anchor, SIZE, BOUNDS, PopupSide::Below),
+            Point::new(300.0, 460.0)
+        );
+        assert_eq!(
+            popup_origin(anchor, SIZE, BOUNDS, PopupSide::Above),
+            Point::new(300.0, 460.0)
+        );
+    }
+
+    #[test]
+    fn test_popup_left_and_right_edges() {
+        let anchor = line(-30.0, 300.0);
+        assert_eq!(popup_origin(anchor, SIZE, BOUNDS, PopupSide::Below).x, 0.0);
+
+        // Kept inside, with the pixel for the border
+        let anchor = line(750.0, 300.0);
+        assert_eq!(
+            popup_origin(anchor, SIZE, BOUNDS, PopupSide::Below).x,
+            599.0
+        );
+    }
+
+    #[test]
+    fn test_popup_corners() {
+        let size = SIZE;
+        for (anchor, side, origin) in [
+            (line(0.0, 0.0), PopupSide::Above, Point::new(0.0, 20.0)),
+            (line(790.0, 0.0), PopupSide::Above, Point::new(599.0, 20.0)),
+            (line(0.0, 580.0), PopupSide::Below, Point::new(0.0, 480.0)),
+            (
+                line(790.0, 580.0),
+                PopupSide::Below,
+                Point::new(599.0, 480.0),
+            ),
+        ] {
+            assert_eq!(popup_origin(anchor, size, BOUNDS, side), origin);
+        }
+    }
+
+    #[test]
+    fn test_popup_taller_than_either_side() {
+        let size = Size::new(200.0, 400.0);
+
+        // The side with more room, as far as it fits in the window
+        let anchor = line(300.0, 250.0);
+        assert_eq!(
+            popup_origin(anchor, size, BOUNDS, PopupSide::Above),
+            Point::new(300.0, 200.0)
+        );
+        let anchor = line(300.0, 350.0);
+        assert_eq!(
+            popup_origin(anchor, size, BOUNDS, PopupSide::Below),
+            Point::new(300.0, 0.0)
+        );
+
+        // Taller than the window, it starts at its top
+        let size = Size::new(200.0, 700.0);
+        assert_eq!(
+            popup_origin(anchor, size, BOUNDS, PopupSide::Below),
+            Point::new(300.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_anchor_in_view() {
+        let viewport = Rect::new(0.0, 100.0, 800.0, 500.0);
+        assert!(anchor_in_view(line(0.0, 100.0), viewport));
+        assert!(anchor_in_view(line(0.0, 90.0), viewport));
+        assert!(!anchor_in_view(line(0.0, 80.0), viewport));
+        assert!(anchor_in_view(line(0.0, 490.0), viewport));
+        assert!(!anchor_in_view(line(0.0, 500.0), viewport));
+    }
+}
@@ -0,0 +1,103 @@
+use floem::{
+    keyboard::ModifiersState,
+    reactive::{Memo, RwSignal, Scope},
+};
+use lapce_core::mode::Mode;
+use lsp_types::DiagnosticSeverity;
+
+use crate::{
+    command::{CommandExecuted, CommandKind, LapceCommand},
+    editor::EditorData,
+    id::EditorId,
+    keypress::{condition::Condition, KeyPressFocus},
+    window_tab::CommonData,
+};
+
+#[derive(Clone)]
+pub struct ProblemData {
+    /// The text the problems panel is narrowed down to
+    pub filter_editor: EditorData,
+    /// The lowercased content of `filter_editor`
+    pub filter: Memo<String>,
+    /// Whether the errors are shown, which can be toggled
+    pub show_errors: RwSignal<bool>,
+    /// Whether the warnings are shown, which can be toggled
+    pub show_warnings: RwSignal<bool>,
+    pub common: CommonData,
+}
+
+impl KeyPressFocus for ProblemData {
+    fn get_mode(&self) -> Mode {
+        Mode::Insert
+    }
+
+    fn check_condition(&self, condition: Condition) -> bool {
+        matches!(condition, Condition::PanelFocus)
+    }
+
+    fn run_command(
+        &self,
+        command: &LapceCommand,
+        count: Option<usize>,
+        mods: ModifiersState,
+    ) -> CommandExecuted {
+        match &command.kind {
+            CommandKind::Workbench(_) => {}
+            CommandKind::Focus(_) => {}
+            CommandKind::Edit(_)
+            | CommandKind::Move(_)
+            | CommandKind::MultiSelection(_) => {
+                self.filter_editor.run_command(command, count, mods);
+            }
+            CommandKind::MotionMode(_) => {}
+        }
+        CommandExecuted::Yes
+    }
+
+    fn receive_char(&self, c: &str) {
+        self.filter_editor.receive_char(c);
+    }
+}
+
+impl ProblemData {
+    pub fn new(cx: Scope, common: CommonData) -> Self {
+        let filter_editor =
+            EditorData::new_local(cx, EditorId::next(), common.clone());
+        let doc = filter_editor.view.doc;
+        let filter = cx.create_memo(move |_| {
+            doc.with(|doc| doc.buffer().to_string().trim().to_lowercase())
+        });
+        Self {
+            filter_editor,
+            filter,
+            show_errors: cx.create_rw_signal(true),
+            show_warnings: cx.create_rw_signal(true),
+            common,
+        }
+    }
+
+    /// Whether the problems of `severity` are shown.
+    pub fn shows(&self, severity: DiagnosticSeverity) -> bool {
+        if severity == DiagnosticSeverity::ERROR {
+            self.show_errors.get()
+        } else {
+            self.show_warnings.get()
+        }
+    }
+
+    /// Whether a problem of `severity` with `message` in the file shown as
+    /// `path` is kept by the severity toggles and the filter.
+    pub fn matches(
+        &self,
+        severity: DiagnosticSeverity,
+        path: &str,
+        message: &str,
+    ) -> bool {
+        self.shows(severity)
+            && self.filter.with(|filter| {
+                filter.is_empty()
+                    || path.to_lowercase().contains(filter.as_str())
+                    || message.to_lowercase().contains(filter.as_str())
+            })
+    }
+}
@@ -5,19 +5,20 @@
 use lapce_proxy::dispatch::Dispatcher;
 use lapce_rpc::{
     core::{CoreHandler, CoreNotification, CoreRpcHandler},
-    plugin::VoltID,
+    plugin::{LanguageServerConfig, VoltID},
     proxy::{ProxyRpcHandler, ProxyStatus},
     terminal::TermId,
 };
-use lsp_types::Url;
+use lsp_types::{ProgressParamsValue, ProgressToken, Url, WorkDoneProgress};
 use tracing::error;
 
-use self::{remote::start_remote, ssh::SshRemote};
+use self::{batch::batch_notifications, remote::start_remote, ssh::SshRemote};
 use crate::{
     terminal::event::TermEvent,
     workspace::{LapceWorkspace, LapceWorkspaceType},
 };
 
+pub mod batch;
 mod remote;
 mod ssh;
 #[cfg(windows)]
@@ -32,7 +33,10 @@ pub struct Proxy {
 pub struct ProxyData {
     pub proxy_rpc: ProxyRpcHandler,
     pub core_rpc: CoreRpcHandler,
-    pub notification: ReadSignal<Option<CoreNotification>>,
+    /// The notifications from the proxy, a batch at a time
+    pub notification: ReadSignal<Option<Vec<CoreNotification>>>,
+    /// Tells the next batch of notifications can be sent
+    pub notification_handled: Sender<()>,
 }
 
 impl ProxyData {
@@ -46,6 +50,7 @@ pub fn new_proxy(
     workspace: Arc<LapceWorkspace>,
     disabled_volts: Vec<VoltID>,
     plugin_configurations: HashMap<String, HashMap<String, serde_json::Value>>,
+    language_servers: HashMap<String, LanguageServerConfig>,
     term_tx: Sender<(TermId, TermEvent)>,
 ) -> ProxyData {
     let proxy_rpc = ProxyRpcHandler::new();
@@ -62,6 +67,7 @@ pub fn new_proxy(
                 workspace.path.clone(),
                 disabled_volts,
                 plugin_configurations,
+                language_servers,
                 1,
                 1,
             );
@@ -118,15 +124,56 @@ pub fn new_proxy(
         })
     };
 
-    let notification = create_signal_from_channel(rx);
+    let (batch_tx, batch_rx) = crossbeam_channel::unbounded();
+    let (notification_handled, handled_rx) = crossbeam_channel::unbounded();
+    std::thread::spawn(move || {
+        batch_notifications(rx, batch_tx, handled_rx, superseding_key);
+    });
+    let notification = create_signal_from_channel(batch_rx);
 
     ProxyData {
         proxy_rpc,
         core_rpc,
         notification,
+        notification_handled,
     }
 }
 
+/// What a notification is the latest state of, which a later notification
+/// of the same makes out of date
+#[derive(PartialEq, Eq, Hash)]
+enum SupersedingKey {
+    Diagnostics(Url),
+    FileContent(PathBuf),
+    BufferOutOfSync(PathBuf),
+    Diff,
+    WorkspaceFiles,
+    ProgressReport(ProgressToken),
+}
+
+fn superseding_key(rpc: &CoreNotification) -> Option<SupersedingKey> {
+    Some(match rpc {
+        CoreNotification::PublishDiagnostics { diagnostics } => {
+            SupersedingKey::Diagnostics(diagnostics.uri.clone())
+        }
+        CoreNotification::OpenFileChanged { path, .. } => {
+            SupersedingKey::FileContent(path.clone())
+        }
+        CoreNotification::BufferOutOfSync { path } => {
+            SupersedingKey::BufferOutOfSync(path.clone())
+        }
+        CoreNotification::DiffInfo { .. } => SupersedingKey::Diff,
+        CoreNotification::WorkspaceFileChange => SupersedingKey::WorkspaceFiles,
+        CoreNotification::WorkDoneProgress { progress } => match progress.value {
+            ProgressParamsValue::WorkDone(WorkDoneProgress::Report(_)) => {
+                SupersedingKey::ProgressReport(progress.token.clone())
+            }
+            _ => return None,
+        },
+        _ => return None,
+    })
+}
+
 impl CoreHandler for Proxy {
     fn handle_notification(&mut self, rpc: lapce_rpc::core::CoreNotification) {
         if let CoreNotification::UpdateTerminal { term_id, content } = &rpc {
@@ -0,0 +1,226 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+};
+
+use crossbeam_channel::{Receiver, Sender};
+
+/// The most notifications the UI handles between two turns of its event loop,
+/// so that a flood of them doesn't hold up the input
+pub const MAX_BATCH: usize = 256;
+
+/// Forward what's received on `rx` to `tx` in batches, one at a time: the
+/// next batch is only sent once `handled` tells the UI is done with the last
+/// one, which lets the UI handle its input in between. What comes in while
+/// the UI is busy waits, and a notification that has the same key as a later
+/// one is dropped, as the later one makes it out of date.
+pub fn batch_notifications<T, K: Eq + Hash>(
+    rx: Receiver<T>,
+    tx: Sender<Vec<T>>,
+    handled: Receiver<()>,
+    key: impl Fn(&T) -> Option<K>,
+) {
+    let mut pending = Pending::new(key);
+    let mut open = true;
+    loop {
+        if pending.is_empty() {
+            if !open {
+                return;
+            }
+            match rx.recv() {
+                Ok(notification) => pending.push(notification),
+                Err(_) => return,
+            }
+        }
+        for notification in rx.try_iter() {
+            pending.push(notification);
+        }
+        if tx.send(pending.take(MAX_BATCH)).is_err() {
+            return;
+        }
+
+        while open {
+            crossbeam_channel::select! {
+                recv(handled) -> done => {
+                    if done.is_err() {
+                        return;
+                    }
+                    break;
+                }
+                recv(rx) -> notification => match notification {
+                    Ok(notification) => pending.push(notification),
+                    Err(_) => open = false,
+                },
+            }
+        }
+        if !open && handled.recv().is_err() {
+            return;
+        }
+    }
+}
+
+/// The notifications waiting for the UI, in the order they came in. The
+/// ones a later notification made out of date are left as `None`.
+struct Pending<T, K, F> {
+    notifications: VecDeque<Option<T>>,
+    /// How many of the notifications aren't out of date
+    live: usize,
+    /// How many notifications were taken before the first one waiting
+    taken: u64,
+    /// Where the latest notification of each key is
+    latest: HashMap<K, u64>,
+    key: F,
+}
+
+impl<T, K: Eq + Hash, F: Fn(&T) -> Option<K>> Pending<T, K, F> {
+    fn new(key: F) -> Self {
+        Self {
+            notifications: VecDeque::new(),
+            live: 0,
+            taken: 0,
+            latest: HashMap::new(),
+            key,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.live == 0
+    }
+
+    fn push(&mut self, notification: T) {
+        let index = self.taken + self.notifications.len() as u64;
+        if let Some(key) = (self.key)(&notification) {
+            if let Some(old) = self.latest.insert(key, index) {
+                let old = (old - self.taken) as usize;
+                if self.notifications[old].take().is_some() {
+                    self.live -= 1;
+                }
+            }
+        }
+        self.notifications.push_back(Some(notification));
+        self.live += 1;
+    }
+
+    /// Take up to `max` of the notifications, the first ones first.
+    fn take(&mut self, max: usize) -> Vec<T> {
+        let mut batch = Vec::new();
+        while batch.len() < max {
+            let Some(notification) = self.notifications.pop_front() else {
+                break;
+            };
+            let index = self.taken;
+            self.taken += 1;
+            let Some(notification) = notification else {
+                continue;
+            };
+            if let Some(key) = (self.key)(&notification) {
+                if self.latest.get(&key) == Some(&index) {
+                    self.latest.remove(&key);
+                }
+            }
+            self.live -= 1;
+            batch.push(notification);
+        }
+        batch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::{batch_notifications, Pending, MAX_BATCH};
+
+    #[test]
+    fn test_pending_drops_superseded() {
+        let mut pending = Pending::new(|n: &(u32, u32)| Some(n.0));
+        pending.push((1, 1));
+        pending.push((2, 1));
+        pending.push((1, 2));
+        assert_eq!(pending.take(1), vec![(2, 1)]);
+        pending.push((2, 2));
+        pending.push((1, 3));
+        assert_eq!(pending.take(10), vec![(2, 2), (1, 3)]);
+        assert!(pending.is_empty());
+        pending.push((1, 4));
+        assert_eq!(pending.take(10), vec![(1, 4)]);
+    }
+
+    #[test]
+    fn test_pending_keeps_unkeyed() {
+        let mut pending = Pending::new(|_: &u32| None::<u32>);
+        for i in 0..5 {
+            pending.push(i);
+        }
+        assert_eq!(pending.take(3), vec![0, 1, 2]);
+        assert_eq!(pending.take(3), vec![3, 4]);
+    }
+
+    /// A flood that comes in while the UI is busy reaches it as the latest
+    /// update of each buffer, in the order they came in.
+    #[test]
+    fn test_flood_is_coalesced() {
+        const BUFFERS: u32 = 100;
+        const UPDATES: u32 = 50_000;
+
+        let (notification_tx, notification_rx) = crossbeam_channel::unbounded();
+        let (batch_tx, batch_rx) = crossbeam_channel::unbounded();
+        let (handled_tx, handled_rx) = crossbeam_channel::unbounded();
+        for i in 0..UPDATES {
+            notification_tx.send((i % BUFFERS, i)).unwrap();
+        }
+        drop(notification_tx);
+
+        thread::spawn(move || {
+            batch_notifications(
+                notification_rx,
+                batch_tx,
+                handled_rx,
+                |n: &(u32, u32)| Some(n.0),
+            )
+        });
+
+        let batch = batch_rx.recv().unwrap();
+        assert!(batch.len() <= MAX_BATCH);
+        let expected = (UPDATES - BUFFERS..UPDATES)
+            .map(|i| (i % BUFFERS, i))
+            .collect::<Vec<_>>();
+        assert_eq!(batch, expected);
+        handled_tx.send(()).unwrap();
+        // Nothing is left, so the batching is done
+        assert!(batch_rx.recv().is_err());
+    }
+
+    /// The next batch waits until the UI is done with the last one, and then
+    /// brings what came in meanwhile, the latest of each buffer first come
+    /// first.
+    #[test]
+    fn test_next_batch_waits_for_handled() {
+        let (notification_tx, notification_rx) = crossbeam_channel::unbounded();
+        let (batch_tx, batch_rx) = crossbeam_channel::unbounded();
+        let (handled_tx, handled_rx) = crossbeam_channel::unbounded();
+
+        thread::spawn(move || {
+            batch_notifications(
+                notification_rx,
+                batch_tx,
+                handled_rx,
+                |n: &(u32, u32)| Some(n.0),
+            )
+        });
+
+        notification_tx.send((1, 1)).unwrap();
+        assert_eq!(batch_rx.recv().unwrap(), vec![(1, 1)]);
+
+        notification_tx.send((2, 1)).unwrap();
+        notification_tx.send((1, 2)).unwrap();
+        notification_tx.send((2, 2)).unwrap();
+        assert!(batch_rx.try_recv().is_err());
+        drop(notification_tx);
+
+        handled_tx.send(()).unwrap();
+        assert_eq!(batch_rx.recv().unwrap(), vec![(1, 2), (2, 2)]);
+        handled_tx.send(()).unwrap();
+        assert!(batch_rx.recv().is_err());
+    }
+}
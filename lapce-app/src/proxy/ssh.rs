@@ -1,7 +1,7 @@
 use std::{path::Path, process::Command};
 
 use anyhow::Result;
-use tracing::debug;
+use tracing::{debug, trace};
 
 use super::remote::Remote;
 use crate::{proxy::new_command, workspace::SshHost};
@@ -37,10 +37,9 @@ fn upload_file(&self, local: impl AsRef<Path>, remote: &str) -> Result<()> {
             cmd.arg("-P").arg(port.to_string());
         }
 
-        let output = cmd
-            .arg(local.as_ref())
-            .arg(dbg!(format!("{}:{remote}", self.ssh.user_host())))
-            .output()?;
+        let destination = format!("{}:{remote}", self.ssh.user_host());
+        trace!("copying to {destination}");
+        let output = cmd.arg(local.as_ref()).arg(destination).output()?;
 
         debug!("{}", String::from_utf8_lossy(&output.stderr));
         debug!("{}", String::from_utf8_lossy(&output.stdout));
@@ -7,9 +7,9 @@
     reactive::{RwSignal, Scope},
 };
 use lapce_core::{command::FocusCommand, mode::Mode, selection::Selection};
-use lapce_rpc::proxy::ProxyResponse;
+use lapce_rpc::{proxy::ProxyResponse, RpcError};
 use lapce_xi_rope::Rope;
-use lsp_types::Position;
+use lsp_types::{MessageType, Position, ShowMessageParams};
 
 use crate::{
     command::{CommandExecuted, CommandKind, InternalCommand, LapceCommand},
@@ -136,12 +136,25 @@ fn confirm(&self) {
             let path = self.path.get_untracked();
             let position = self.position.get_untracked();
             let internal_command = self.common.internal_command;
-            let send = create_ext_action(self.common.scope, move |result| {
-                if let Ok(ProxyResponse::Rename { edit }) = result {
-                    internal_command
-                        .send(InternalCommand::ApplyWorkspaceEdit { edit });
-                }
-            });
+            let send = create_ext_action(
+                self.common.scope,
+                move |result: Result<ProxyResponse, RpcError>| match result {
+                    Ok(ProxyResponse::Rename { edit }) => {
+                        internal_command
+                            .send(InternalCommand::ApplyRenameEdit { edit });
+                    }
+                    Err(err) => {
+                        internal_command.send(InternalCommand::ShowMessage {
+                            title: "Rename".to_string(),
+                            message: ShowMessageParams {
+                                typ: MessageType::ERROR,
+                                message: err.message,
+                            },
+                        });
+                    }
+                    Ok(_) => {}
+                },
+            );
             self.common.proxy.rename(
                 path,
                 position,
@@ -0,0 +1,136 @@
+//! Resizing the editor tabs and the panels from the keyboard, in the resize
+//! mode: the arrow keys or `hjkl` make the focused editor tab or panel wider,
+//! narrower, taller or shorter, by larger steps with Shift, until Enter or
+//! Escape is pressed.
+
+use floem::keyboard::Key;
+
+/// How many pixels a key resizes by
+pub const RESIZE_STEP: f64 = 10.0;
+
+/// How many pixels a key resizes by with Shift
+pub const RESIZE_STEP_LARGE: f64 = 50.0;
+
+/// The smallest an editor tab is made in the resize mode
+pub const MIN_EDITOR_TAB_SIZE: f64 = 100.0;
+
+/// The smallest a panel is made in the resize mode
+pub const MIN_PANEL_SIZE: f64 = 100.0;
+
+/// What a key does in the resize mode
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResizeKey {
+    /// Add the pixels to the width
+    Width(f64),
+    /// Add the pixels to the height
+    Height(f64),
+    /// Leave the resize mode
+    Done,
+}
+
+impl ResizeKey {
+    pub fn from_key(key: &Key, shift: bool) -> Option<Self> {
+        let step = if shift {
+            RESIZE_STEP_LARGE
+        } else {
+            RESIZE_STEP
+        };
+        let resize = match key {
+            Key::ArrowLeft => ResizeKey::Width(-step),
+            Key::ArrowRight => ResizeKey::Width(step),
+            Key::ArrowUp => ResizeKey::Height(step),
+            Key::ArrowDown => ResizeKey::Height(-step),
+            Key::Enter | Key::Escape => ResizeKey::Done,
+            Key::Character(c) => match c.to_lowercase().as_str() {
+                "h" => ResizeKey::Width(-step),
+                "l" => ResizeKey::Width(step),
+                "k" => ResizeKey::Height(step),
+                "j" => ResizeKey::Height(-step),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(resize)
+    }
+}
+
+/// The weights of two neighboring children of a split, which are `sizes`
+/// pixels, once `delta` pixels of the second are given to the first. Neither
+/// is made smaller than [`MIN_EDITOR_TAB_SIZE`], and the sum of the weights
+/// stays the same, so that the other children keep their sizes.
+pub fn move_split_border(
+    weights: (f64, f64),
+    sizes: (f64, f64),
+    delta: f64,
+) -> (f64, f64) {
+    let total = sizes.0 + sizes.1;
+    if total <= 0.0 {
+        return weights;
+    }
+    let min = MIN_EDITOR_TAB_SIZE.min(total / 2.0);
+    let first = (sizes.0 + delta).clamp(min, total - min);
+    let weight = weights.0 + weights.1;
+    (weight * first / total, weight * (total - first) / total)
+}
+
+/// The size of a panel of `size` pixels once `delta` pixels are added to it,
+/// which is at most `max`, and at least [`MIN_PANEL_SIZE`].
+pub fn resize_panel(size: f64, delta: f64, max: f64) -> f64 {
+    (size + delta).min(max).max(MIN_PANEL_SIZE)
+}
+
+#[cfg(test)]
+mod tests {
+    use floem::keyboard::Key;
+
+    use super::{move_split_border, resize_panel, ResizeKey};
+
+    #[test]
+    fn test_resize_key() {
+        assert_eq!(
+            ResizeKey::from_key(&Key::ArrowLeft, false),
+            Some(ResizeKey::Width(-10.0))
+        );
+        assert_eq!(
+            ResizeKey::from_key(&Key::Character("L".into()), true),
+            Some(ResizeKey::Width(50.0))
+        );
+        assert_eq!(
+            ResizeKey::from_key(&Key::Character("k".into()), false),
+            Some(ResizeKey::Height(10.0))
+        );
+        assert_eq!(
+            ResizeKey::from_key(&Key::Escape, false),
+            Some(ResizeKey::Done)
+        );
+        assert_eq!(
+            ResizeKey::from_key(&Key::Character("x".into()), false),
+            None
+        );
+    }
+
+    #[test]
+    fn test_move_split_border() {
+        assert_eq!(
+            move_split_border((1.0, 1.0), (400.0, 400.0), 40.0),
+            (1.1, 0.9)
+        );
+        // Neither side goes under the minimum
+        assert_eq!(
+            move_split_border((1.0, 1.0), (400.0, 400.0), 1000.0),
+            (1.75, 0.25)
+        );
+        assert_eq!(
+            move_split_border((1.0, 1.0), (150.0, 150.0), -100.0),
+            (1.0 / 1.5, 2.0 / 1.5)
+        );
+        assert_eq!(move_split_border((1.0, 2.0), (0.0, 0.0), 10.0), (1.0, 2.0));
+    }
+
+    #[test]
+    fn test_resize_panel() {
+        assert_eq!(resize_panel(300.0, 10.0, 1000.0), 310.0);
+        assert_eq!(resize_panel(300.0, 800.0, 1000.0), 1000.0);
+        assert_eq!(resize_panel(120.0, -50.0, 1000.0), 100.0);
+    }
+}
@@ -0,0 +1,213 @@
+use std::ops::Range;
+
+use floem::{
+    cosmic_text::{Attrs, AttrsList, TextLayout, Weight},
+    peniko::kurbo::Rect,
+    reactive::{RwSignal, Scope},
+    timer::TimerToken,
+};
+use lapce_core::{encoding::offset_utf16_to_utf8_str, language::LapceLanguage};
+use lsp_types::{
+    Documentation, MarkupKind, ParameterLabel, SignatureHelp, SignatureInformation,
+};
+
+use crate::{
+    config::{color::LapceColor, LapceConfig},
+    id::EditorId,
+    markdown::{from_plaintext, highlight_as_code, parse_markdown, MarkdownContent},
+};
+
+/// The signature of the call the cursor is in, shown above it while its
+/// arguments are typed.
+#[derive(Clone)]
+pub struct SignatureData {
+    pub active: RwSignal<bool>,
+    /// Bumped for every request, and when the signature is dismissed, so
+    /// that only the response to the latest request is shown
+    pub request_id: RwSignal<usize>,
+    /// The timer of the request waiting for the typing to settle
+    pub timer: RwSignal<TimerToken>,
+    pub editor_id: RwSignal<EditorId>,
+    /// The offset of the `(` of the call
+    pub offset: RwSignal<usize>,
+    pub content: RwSignal<Vec<MarkdownContent>>,
+    pub layout_rect: RwSignal<Rect>,
+}
+
+impl SignatureData {
+    pub fn new(cx: Scope) -> Self {
+        Self {
+            active: cx.create_rw_signal(false),
+            request_id: cx.create_rw_signal(0),
+            timer: cx.create_rw_signal(TimerToken::INVALID),
+            editor_id: cx.create_rw_signal(EditorId::next()),
+            offset: cx.create_rw_signal(0),
+            content: cx.create_rw_signal(Vec::new()),
+            layout_rect: cx.create_rw_signal(Rect::ZERO),
+        }
+    }
+
+    /// Start a new request, dropping the response to the last one.
+    pub fn next_request(&self) -> usize {
+        self.request_id.update(|id| *id += 1);
+        self.request_id.get_untracked()
+    }
+
+    pub fn cancel(&self) {
+        self.next_request();
+        self.timer.set(TimerToken::INVALID);
+        if self.active.get_untracked() {
+            self.active.set(false);
+        }
+    }
+
+    pub fn receive(
+        &self,
+        request_id: usize,
+        resp: &SignatureHelp,
+        language: Option<LapceLanguage>,
+        config: &LapceConfig,
+    ) {
+        if request_id != self.request_id.get_untracked() {
+            return;
+        }
+
+        let content = signature_content(resp, language, config);
+        if content.is_empty() {
+            self.active.set(false);
+        } else {
+            self.content.set(content);
+            self.active.set(true);
+        }
+    }
+}
+
+/// The active signature, with the active parameter in bold, followed by the
+/// documentation of the parameter or else of the signature.
+fn signature_content(
+    resp: &SignatureHelp,
+    language: Option<LapceLanguage>,
+    config: &LapceConfig,
+) -> Vec<MarkdownContent> {
+    let index = resp.active_signature.unwrap_or(0) as usize;
+    let Some(signature) = resp
+        .signatures
+        .get(index)
+        .or_else(|| resp.signatures.first())
+    else {
+        return Vec::new();
+    };
+    let active_parameter = signature.active_parameter.or(resp.active_parameter);
+
+    let code_font_family = config.editor.font_family();
+    let attrs = Attrs::new()
+        .color(*config.get_color(LapceColor::EDITOR_FOREGROUND))
+        .family(&code_font_family)
+        .font_size(config.editor.font_size() as f32);
+    let mut attr_list = AttrsList::new(attrs);
+    if config.editor.signature_label_code_block {
+        highlight_as_code(
+            &mut attr_list,
+            attrs,
+            language,
+            &signature.label,
+            0,
+            config,
+        );
+    }
+    if let Some(range) = active_parameter_range(signature, active_parameter) {
+        attr_list.add_span(range, attrs.weight(Weight::BOLD));
+    }
+    let mut text_layout = TextLayout::new();
+    text_layout.set_text(&signature.label, attr_list);
+    let mut content = vec![MarkdownContent::Text(text_layout)];
+
+    let parameter_documentation = active_parameter
+        .and_then(|i| signature.parameters.as_ref()?.get(i as usize))
+        .and_then(|parameter| parameter.documentation.as_ref());
+    if let Some(documentation) =
+        parameter_documentation.or(signature.documentation.as_ref())
+    {
+        let documentation = match documentation {
+            Documentation::String(text) => from_plaintext(text, 1.5, config),
+            Documentation::MarkupContent(content) => match content.kind {
+                MarkupKind::PlainText => from_plaintext(&content.value, 1.5, config),
+                MarkupKind::Markdown => parse_markdown(&content.value, 1.5, config),
+            },
+        };
+        if !documentation.is_empty() {
+            content.push(MarkdownContent::Separator);
+            content.extend(documentation);
+        }
+    }
+
+    content
+}
+
+/// The byte range of the active parameter in the label of the signature.
+fn active_parameter_range(
+    signature: &SignatureInformation,
+    active_parameter: Option<u32>,
+) -> Option<Range<usize>> {
+    let parameter = signature
+        .parameters
+        .as_ref()?
+        .get(active_parameter? as usize)?;
+    let label = &signature.label;
+    match &parameter.label {
+        ParameterLabel::Simple(name) => {
+            // Past the `(`, so that it's not found in the function name
+            let params_start = label.find('(').map(|i| i + 1).unwrap_or(0);
+            let start = label[params_start..].find(name.as_str())? + params_start;
+            Some(start..start + name.len())
+        }
+        ParameterLabel::LabelOffsets([start, end]) => {
+            let start = offset_utf16_to_utf8_str(label, *start as usize);
+            let end = offset_utf16_to_utf8_str(label, *end as usize);
+            (start < end).then_some(start..end)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{ParameterInformation, ParameterLabel, SignatureInformation};
+
+    use super::active_parameter_range;
+
+    fn signature(parameters: Vec<ParameterLabel>) -> SignatureInformation {
+        SignatureInformation {
+            label: "fn a(a: i32, é: &str)".to_string(),
+            documentation: None,
+            parameters: Some(
+                parameters
+                    .into_iter()
+                    .map(|label| ParameterInformation {
+                        label,
+                        documentation: None,
+                    })
+                    .collect(),
+            ),
+            active_parameter: None,
+        }
+    }
+
+    #[test]
+    fn test_active_parameter_range() {
+        let simple = signature(vec![
+            ParameterLabel::Simple("a: i32".to_string()),
+            ParameterLabel::Simple("é: &str".to_string()),
+        ]);
+        assert_eq!(active_parameter_range(&simple, Some(0)), Some(5..11));
+        assert_eq!(active_parameter_range(&simple, Some(1)), Some(13..21));
+        assert_eq!(active_parameter_range(&simple, Some(2)), None);
+        assert_eq!(active_parameter_range(&simple, None), None);
+
+        let offsets = signature(vec![
+            ParameterLabel::LabelOffsets([5, 11]),
+            ParameterLabel::LabelOffsets([13, 20]),
+        ]);
+        assert_eq!(active_parameter_range(&offsets, Some(0)), Some(5..11));
+        assert_eq!(active_parameter_range(&offsets, Some(1)), Some(13..21));
+    }
+}
@@ -0,0 +1,171 @@
+use std::{
+    fs,
+    io::{self, Write},
+    ops::Range,
+    path::PathBuf,
+};
+
+use lapce_core::{directory::Directory, spell::Dictionary};
+use lsp_types::{CodeAction, CodeActionKind, CodeActionOrCommand, Command};
+use once_cell::sync::Lazy;
+use parking_lot::{RwLock, RwLockReadGuard};
+
+use crate::job::assert_off_ui_thread;
+
+/// The word list the spell checker comes with
+const DEFAULT_DICTIONARY: &str = include_str!("../../defaults/dictionary.txt");
+
+/// How many words a misspelled word can be changed to from the code actions
+const MAX_SUGGESTIONS: usize = 5;
+
+/// The commands of the code actions that fix a misspelled word, which are run
+/// by the editor rather than a language server
+const REPLACE_COMMAND: &str = "lapce.spell.replace";
+const ADD_WORD_COMMAND: &str = "lapce.spell.add_word";
+
+/// The words spelled right: the bundled list and the user's dictionary, read
+/// the first time some text is checked
+static DICTIONARY: Lazy<RwLock<Dictionary>> = Lazy::new(|| {
+    let mut dictionary = Dictionary::from_list(DEFAULT_DICTIONARY);
+    if let Some(words) =
+        user_dictionary_file().and_then(|path| fs::read_to_string(path).ok())
+    {
+        dictionary.extend(&words);
+    }
+    RwLock::new(dictionary)
+});
+
+pub fn dictionary() -> RwLockReadGuard<'static, Dictionary> {
+    DICTIONARY.read()
+}
+
+/// The file of the words added to the dictionary, one per line
+pub fn user_dictionary_file() -> Option<PathBuf> {
+    Some(Directory::config_directory()?.join("dictionary.txt"))
+}
+
+/// Add `word` to the user's dictionary, so that it's no longer misspelled.
+pub fn add_to_dictionary(word: &str) -> io::Result<()> {
+    assert_off_ui_thread("Writing the dictionary");
+    DICTIONARY.write().insert(word);
+    let path = user_dictionary_file().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, "no config directory")
+    })?;
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{word}")
+}
+
+/// A fix of a misspelled word, picked from the code actions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpellAction {
+    /// Change the `word` at `range` to `to`
+    Replace {
+        range: Range<usize>,
+        word: String,
+        to: String,
+    },
+    /// Add `word` to the dictionary
+    AddWord { word: String },
+}
+
+impl SpellAction {
+    /// The fix that a code action's `command` is for, if it's for one.
+    pub fn from_command(command: &Command) -> Option<Self> {
+        let args = command.arguments.as_deref().unwrap_or_default();
+        let string = |i: usize| Some(args.get(i)?.as_str()?.to_string());
+        match command.command.as_str() {
+            REPLACE_COMMAND => {
+                let start = args.first()?.as_u64()? as usize;
+                let end = args.get(1)?.as_u64()? as usize;
+                Some(SpellAction::Replace {
+                    range: start..end,
+                    word: string(2)?,
+                    to: string(3)?,
+                })
+            }
+            ADD_WORD_COMMAND => Some(SpellAction::AddWord { word: string(0)? }),
+            _ => None,
+        }
+    }
+
+    fn code_action(&self) -> CodeActionOrCommand {
+        let (title, command, arguments) = match self {
+            SpellAction::Replace { range, word, to } => (
+                format!("Change to \"{to}\""),
+                REPLACE_COMMAND,
+                vec![
+                    range.start.into(),
+                    range.end.into(),
+                    word.as_str().into(),
+                    to.as_str().into(),
+                ],
+            ),
+            SpellAction::AddWord { word } => (
+                format!("Add \"{word}\" to Dictionary"),
+                ADD_WORD_COMMAND,
+                vec![word.as_str().into()],
+            ),
+        };
+        CodeActionOrCommand::CodeAction(CodeAction {
+            title: title.clone(),
+            kind: Some(CodeActionKind::QUICKFIX),
+            command: Some(Command {
+                title,
+                command: command.to_string(),
+                arguments: Some(arguments),
+            }),
+            ..Default::default()
+        })
+    }
+}
+
+/// The code actions for the misspelled `word` at `range`: changing it to
+/// each of the words it's likely a misspelling of, and adding it to the
+/// dictionary.
+pub fn code_actions(word: &str, range: Range<usize>) -> Vec<CodeActionOrCommand> {
+    let mut actions = dictionary()
+        .suggestions(word, MAX_SUGGESTIONS)
+        .into_iter()
+        .map(|to| SpellAction::Replace {
+            range: range.clone(),
+            word: word.to_string(),
+            to,
+        })
+        .collect::<Vec<_>>();
+    actions.push(SpellAction::AddWord {
+        word: word.to_string(),
+    });
+    actions.iter().map(SpellAction::code_action).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::CodeActionOrCommand;
+
+    use super::SpellAction;
+
+    #[test]
+    fn test_action_from_command() {
+        let actions = [
+            SpellAction::Replace {
+                range: 4..8,
+                word: "wrod".to_string(),
+                to: "word".to_string(),
+            },
+            SpellAction::AddWord {
+                word: "wrod".to_string(),
+            },
+        ];
+        for action in actions {
+            let CodeActionOrCommand::CodeAction(code_action) = action.code_action()
+            else {
+                panic!("not a code action");
+            };
+            let command = code_action.command.unwrap();
+            assert_eq!(SpellAction::from_command(&command), Some(action));
+        }
+    }
+}
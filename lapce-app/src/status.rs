@@ -1,29 +1,41 @@
 use std::{
     rc::Rc,
     sync::{atomic::AtomicU64, Arc},
+    time::Duration,
 };
 
 use floem::{
-    reactive::{create_memo, ReadSignal, RwSignal},
+    action::{exec_after, TimerToken},
+    peniko::kurbo::{Point, Size},
+    reactive::{create_effect, create_memo, create_rw_signal, ReadSignal, RwSignal},
     style::{AlignItems, CursorStyle, Display},
     view::View,
-    views::{label, list, stack, svg, Decorators},
+    views::{
+        container, label, list, scroll, stack, svg, virtual_list, Decorators,
+        VirtualListDirection, VirtualListItemSize,
+    },
 };
 use indexmap::IndexMap;
-use lapce_core::mode::Mode;
+use lapce_core::{indent::IndentStyle, mode::Mode};
+use lapce_rpc::plugin::{LanguageServerStatus, VoltID};
 use lsp_types::{DiagnosticSeverity, ProgressToken};
 
 use crate::{
-    app::clickable_icon,
+    app::{clickable_icon, VectorItems},
     command::LapceWorkbenchCommand,
     config::{color::LapceColor, icon::LapceIcons, LapceConfig},
+    editor::view_data::RenderWhitespace,
     listener::Listener,
     palette::kind::PaletteKind,
     panel::{kind::PanelKind, position::PanelContainerPosition},
     source_control::SourceControlData,
-    window_tab::{WindowTabData, WorkProgress},
+    window_tab::{LanguageServerInfo, WindowTabData, WorkProgress},
 };
 
+const SPINNER_FRAMES: [&str; 10] =
+    ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+const SPINNER_INTERVAL: Duration = Duration::from_millis(100);
+
 pub fn status(
     window_tab_data: Rc<WindowTabData>,
     source_control: SourceControlData,
@@ -66,6 +78,13 @@ pub fn status(
     };
 
     let progresses = window_tab_data.progresses;
+    let language_servers = window_tab_data.language_servers;
+    let expanded_language_server = window_tab_data.expanded_language_server;
+    let pending = {
+        let window_tab_data = window_tab_data.clone();
+        create_memo(move |_| window_tab_data.pending_state())
+    };
+    let workspace_trust = window_tab_data.common.workspace_trust;
     let mode = create_memo(move |_| window_tab_data.mode());
 
     stack(move || {
@@ -73,11 +92,20 @@ pub fn status(
         (
             stack(|| {
                 (
-                    label(move || match mode.get() {
-                        Mode::Normal => "Normal".to_string(),
-                        Mode::Insert => "Insert".to_string(),
-                        Mode::Visual => "Visual".to_string(),
-                        Mode::Terminal => "Terminal".to_string(),
+                    label(move || {
+                        let mode = match mode.get() {
+                            Mode::Normal => "Normal",
+                            Mode::Insert => "Insert",
+                            Mode::Visual => "Visual",
+                            Mode::Terminal => "Terminal",
+                        };
+                        pending.with(|pending| {
+                            if pending.is_empty() {
+                                mode.to_string()
+                            } else {
+                                format!("{mode} ({pending}…)")
+                            }
+                        })
                     })
                     .style(move |s| {
                         let config = config.get();
@@ -116,6 +144,25 @@ pub fn status(
                             .height_pct(100.0)
                             .align_items(Some(AlignItems::Center))
                     }),
+                    label(|| "Restricted Mode".to_string())
+                        .on_click(move |_| {
+                            workbench_command
+                                .send(LapceWorkbenchCommand::ManageWorkspaceTrust);
+                            true
+                        })
+                        .style(move |s| {
+                            s.padding_horiz_px(10.0)
+                                .height_pct(100.0)
+                                .align_items(Some(AlignItems::Center))
+                                .apply_if(workspace_trust.is_trusted(), |s| s.hide())
+                        })
+                        .hover_style(move |s| {
+                            s.cursor(CursorStyle::Pointer).background(
+                                *config
+                                    .get()
+                                    .get_color(LapceColor::PANEL_HOVERED_BACKGROUND),
+                            )
+                        }),
                     stack(move || {
                         (
                             svg(move || config.get().ui_svg(LapceIcons::SCM)).style(
@@ -201,6 +248,11 @@ pub fn status(
                             )
                         })
                     },
+                    language_server_view(
+                        language_servers,
+                        expanded_language_server,
+                        config,
+                    ),
                     progress_view(progresses),
                 )
             })
@@ -297,6 +349,20 @@ pub fn status(
             })
             .style(|s| s.height_pct(100.0).items_center()),
             stack(|| {
+                let pending_keys =
+                    label(move || pending.with(|pending| pending.to_string()))
+                        .style(move |s| {
+                            let is_empty =
+                                pending.with(|pending| pending.is_empty());
+                            s.display(if is_empty {
+                                Display::None
+                            } else {
+                                Display::Flex
+                            })
+                            .height_pct(100.0)
+                            .padding_horiz_px(10.0)
+                            .items_center()
+                        });
                 let palette_clone = palette.clone();
                 let cursor_info = label(move || {
                     if let Some(editor) = editor() {
@@ -318,10 +384,9 @@ pub fn status(
                                     format!("{status} ({selection_range} selected)");
                             }
                         }
-                        let selection_count = cursor.get_selection_count();
-                        if selection_count > 1 {
-                            status =
-                                format!("{status} {selection_count} selections");
+                        let cursor_count = cursor.get_selection_count();
+                        if cursor_count > 1 {
+                            status = format!("{status} {cursor_count} cursors");
                         }
                         return status;
                     }
@@ -353,11 +418,99 @@ pub fn status(
                             .get_color(LapceColor::PANEL_HOVERED_BACKGROUND),
                     )
                 });
+                let indent_info = label(move || {
+                    if let Some(editor) = editor() {
+                        let doc = editor.with(|editor| editor.view.doc);
+                        match doc.with(|doc| doc.buffer().indent_style()) {
+                            IndentStyle::Tabs => "Tabs".to_string(),
+                            IndentStyle::Spaces(n) => format!("Spaces: {n}"),
+                        }
+                    } else {
+                        String::new()
+                    }
+                })
+                .style(move |s| {
+                    s.display(
+                        if editor()
+                            .map(|f| f.get().view.doc.get().content.is_file())
+                            .unwrap_or(false)
+                        {
+                            Display::Flex
+                        } else {
+                            Display::None
+                        },
+                    )
+                    .height_pct(100.0)
+                    .padding_horiz_px(10.0)
+                    .items_center()
+                });
+                let line_ending_info = label(move || {
+                    if let Some(editor) = editor() {
+                        let doc = editor.with(|editor| editor.view.doc);
+                        match doc.with(|doc| doc.buffer().line_ending()) {
+                            "\r\n" => "CRLF".to_string(),
+                            _ => "LF".to_string(),
+                        }
+                    } else {
+                        String::new()
+                    }
+                })
+                .style(move |s| {
+                    s.display(
+                        if editor()
+                            .map(|f| f.get().view.doc.get().content.is_file())
+                            .unwrap_or(false)
+                        {
+                            Display::Flex
+                        } else {
+                            Display::None
+                        },
+                    )
+                    .height_pct(100.0)
+                    .padding_horiz_px(10.0)
+                    .items_center()
+                });
+                // How the active editor shows its document, where it doesn't
+                // look like it does by default
+                let view_info = label(move || {
+                    let Some(editor) = editor() else {
+                        return String::new();
+                    };
+                    let options = editor.with(|editor| editor.view.options).get();
+                    let config = config.get();
+                    let mut info = Vec::new();
+                    let render_whitespace = options.render_whitespace(&config);
+                    if render_whitespace != RenderWhitespace::None {
+                        info.push(format!(
+                            "Whitespace: {}",
+                            render_whitespace.name()
+                        ));
+                    }
+                    if !options.line_numbers(&config) {
+                        info.push("No Line Numbers".to_string());
+                    }
+                    info.join(", ")
+                })
+                .style(move |s| {
+                    s.display(
+                        if editor()
+                            .map(|f| f.get().view.doc.get().content.is_file())
+                            .unwrap_or(false)
+                        {
+                            Display::Flex
+                        } else {
+                            Display::None
+                        },
+                    )
+                    .height_pct(100.0)
+                    .padding_horiz_px(10.0)
+                    .items_center()
+                });
                 let palette_clone = palette.clone();
                 let language_info = label(move || {
                     if let Some(editor) = editor() {
                         let doc = editor.with(|editor| editor.view.doc);
-                        doc.with(|doc| doc.syntax().language.to_string())
+                        doc.with(|doc| doc.syntax().language.name().to_string())
                     } else {
                         String::new()
                     }
@@ -388,7 +541,14 @@ pub fn status(
                             .get_color(LapceColor::PANEL_HOVERED_BACKGROUND),
                     )
                 });
-                (cursor_info, language_info)
+                (
+                    pending_keys,
+                    cursor_info,
+                    indent_info,
+                    line_ending_info,
+                    view_info,
+                    language_info,
+                )
             })
             .style(|s| {
                 s.height_pct(100.0)
@@ -408,26 +568,266 @@ pub fn status(
     })
 }
 
+fn language_server_status(status: &LanguageServerStatus) -> String {
+    match status {
+        LanguageServerStatus::Starting => "starting".to_string(),
+        LanguageServerStatus::Running => "ready".to_string(),
+        LanguageServerStatus::Restarting { attempt } => {
+            format!("restarting ({attempt})")
+        }
+        LanguageServerStatus::Failed { message } => format!("failed: {message}"),
+        LanguageServerStatus::Stopped => "stopped".to_string(),
+    }
+}
+
+fn language_server_view(
+    language_servers: RwSignal<IndexMap<VoltID, LanguageServerInfo>>,
+    expanded: RwSignal<Option<VoltID>>,
+    config: ReadSignal<Arc<LapceConfig>>,
+) -> impl View {
+    list(
+        move || language_servers.get(),
+        |(volt_id, info)| (volt_id.clone(), info.status.clone()),
+        move |(volt_id, info)| {
+            let is_failed =
+                matches!(info.status, LanguageServerStatus::Failed { .. });
+            let status = language_server_status(&info.status);
+            let server = info.server;
+            stack(|| {
+                (label(move || server.clone()), {
+                    label(move || format!(": {status}"))
+                        .style(|s| s.min_width_px(0.0).text_ellipsis())
+                })
+            })
+            .on_click(move |_| {
+                expanded.update(|expanded| {
+                    if expanded.as_ref() == Some(&volt_id) {
+                        *expanded = None;
+                    } else {
+                        *expanded = Some(volt_id.clone());
+                    }
+                });
+                true
+            })
+            .style(move |s| {
+                s.height_pct(100.0)
+                    .padding_horiz_px(10.0)
+                    .items_center()
+                    .apply_if(is_failed, |s| {
+                        s.color(*config.get().get_color(LapceColor::LAPCE_ERROR))
+                    })
+            })
+            .hover_style(move |s| {
+                s.cursor(CursorStyle::Pointer).background(
+                    *config.get().get_color(LapceColor::PANEL_HOVERED_BACKGROUND),
+                )
+            })
+        },
+    )
+}
+
+/// Advance the spinner of the work in progress until it's all done.
+fn spin(
+    progresses: RwSignal<IndexMap<ProgressToken, WorkProgress>>,
+    frame: RwSignal<usize>,
+    timer: RwSignal<TimerToken>,
+) {
+    let token = exec_after(SPINNER_INTERVAL, move |token| {
+        if timer.get_untracked() != token
+            || progresses.with_untracked(|progresses| progresses.is_empty())
+        {
+            return;
+        }
+        frame.update(|frame| *frame = (*frame + 1) % SPINNER_FRAMES.len());
+        spin(progresses, frame, timer);
+    });
+    timer.set(token);
+}
+
 fn progress_view(
     progresses: RwSignal<IndexMap<ProgressToken, WorkProgress>>,
 ) -> impl View {
+    let frame = create_rw_signal(0);
+    let timer = create_rw_signal(TimerToken::INVALID);
+    create_effect(move |was_busy: Option<bool>| {
+        let is_busy = progresses.with(|progresses| !progresses.is_empty());
+        if is_busy && was_busy != Some(true) {
+            spin(progresses, frame, timer);
+        }
+        is_busy
+    });
+
     let id = AtomicU64::new(0);
     list(
         move || progresses.get(),
         move |_| id.fetch_add(1, std::sync::atomic::Ordering::Relaxed),
         move |(_, p)| {
+            let title = match p.percentage {
+                Some(percentage) => format!("{} {percentage}%", p.title),
+                None => p.title.clone(),
+            };
             stack(|| {
-                (label(move || p.title.clone()), {
-                    let message = p.message.unwrap_or_default();
-                    let is_empty = message.is_empty();
-                    label(move || format!(": {message}")).style(move |s| {
-                        s.min_width_px(0.0)
-                            .text_ellipsis()
-                            .apply_if(is_empty, |s| s.hide())
-                    })
-                })
+                (
+                    label(move || SPINNER_FRAMES[frame.get()].to_string())
+                        .style(|s| s.margin_right_px(6.0)),
+                    label(move || title.clone()),
+                    {
+                        let message = p.message.unwrap_or_default();
+                        let is_empty = message.is_empty();
+                        label(move || format!(": {message}")).style(move |s| {
+                            s.min_width_px(0.0)
+                                .text_ellipsis()
+                                .apply_if(is_empty, |s| s.hide())
+                        })
+                    },
+                )
             })
             .style(|s| s.margin_left_px(10.0))
         },
     )
 }
+
+/// The stderr log of the language server picked in the status bar, with
+/// buttons to restart or stop it.
+pub fn language_server_log(window_tab_data: Rc<WindowTabData>) -> impl View {
+    let config = window_tab_data.common.config;
+    let ui_line_height = window_tab_data.common.ui_line_height;
+    let language_servers = window_tab_data.language_servers;
+    let expanded = window_tab_data.expanded_language_server;
+    let proxy = window_tab_data.common.proxy.clone();
+    let info = create_memo(move |_| {
+        let volt_id = expanded.get()?;
+        language_servers.with(|servers| servers.get(&volt_id).cloned())
+    });
+    let log = create_memo(move |_| {
+        info.with(|info| info.as_ref().map(|info| info.log.clone()))
+            .unwrap_or_default()
+    });
+
+    let restart_proxy = proxy.clone();
+    container(|| {
+        stack(|| {
+            (
+                stack(|| {
+                    (
+                        label(move || {
+                            info.with(|info| {
+                                info.as_ref()
+                                    .map(|info| {
+                                        format!(
+                                            "{}: {}",
+                                            info.server,
+                                            language_server_status(&info.status)
+                                        )
+                                    })
+                                    .unwrap_or_default()
+                            })
+                        })
+                        .style(|s| {
+                            s.min_width_px(0.0)
+                                .flex_basis_px(0.0)
+                                .flex_grow(1.0)
+                                .text_ellipsis()
+                        }),
+                        clickable_icon(
+                            || LapceIcons::DEBUG_RESTART,
+                            move || {
+                                if let Some(volt_id) = expanded.get_untracked() {
+                                    restart_proxy.restart_language_server(volt_id);
+                                }
+                            },
+                            || false,
+                            || false,
+                            config,
+                        ),
+                        clickable_icon(
+                            || LapceIcons::DEBUG_STOP,
+                            move || {
+                                if let Some(volt_id) = expanded.get_untracked() {
+                                    proxy.stop_language_server(volt_id);
+                                }
+                            },
+                            move || {
+                                info.with(|info| {
+                                    matches!(
+                                        info.as_ref().map(|info| &info.status),
+                                        Some(LanguageServerStatus::Stopped)
+                                    )
+                                })
+                            },
+                            || false,
+                            config,
+                        ),
+                        clickable_icon(
+                            || LapceIcons::CLOSE,
+                            move || expanded.set(None),
+                            || false,
+                            || false,
+                            config,
+                        ),
+                    )
+                })
+                .style(move |s| {
+                    s.width_pct(100.0)
+                        .items_center()
+                        .padding_horiz_px(10.0)
+                        .padding_vert_px(4.0)
+                        .border_bottom(1.0)
+                        .border_color(
+                            *config.get().get_color(LapceColor::LAPCE_BORDER),
+                        )
+                }),
+                container(|| {
+                    scroll(|| {
+                        virtual_list(
+                            VirtualListDirection::Vertical,
+                            VirtualListItemSize::Fixed(Box::new(move || {
+                                ui_line_height.get()
+                            })),
+                            move || VectorItems(log.get()),
+                            |(index, _)| *index,
+                            move |(_, line)| {
+                                label(move || line.clone()).style(move |s| {
+                                    s.padding_horiz_px(10.0)
+                                        .height_px(ui_line_height.get() as f32)
+                                        .items_center()
+                                })
+                            },
+                        )
+                        .style(|s| s.flex_col().min_width_pct(100.0))
+                    })
+                    .on_ensure_visible(move || {
+                        let line_height = ui_line_height.get();
+                        let last = log.with(|log| log.len().saturating_sub(1));
+                        Size::new(1.0, line_height)
+                            .to_rect()
+                            .with_origin(Point::new(0.0, last as f64 * line_height))
+                    })
+                    .style(|s| s.absolute().size_pct(100.0, 100.0))
+                })
+                .style(|s| s.width_pct(100.0).flex_basis_px(0.0).flex_grow(1.0)),
+            )
+        })
+        .style(move |s| {
+            let config = config.get();
+            s.flex_col()
+                .width_px(600.0)
+                .max_width_pct(80.0)
+                .height_px(300.0)
+                .margin_left_px(10.0)
+                .border(1.0)
+                .border_radius(6.0)
+                .border_color(*config.get_color(LapceColor::LAPCE_BORDER))
+                .background(*config.get_color(LapceColor::PANEL_BACKGROUND))
+                .color(*config.get_color(LapceColor::PANEL_FOREGROUND))
+        })
+    })
+    .style(move |s| {
+        s.absolute()
+            .size_pct(100.0, 100.0)
+            .flex_col()
+            .justify_end()
+            .padding_bottom_px(config.get().ui.status_height() as f32 + 4.0)
+            .apply_if(info.with(|info| info.is_none()), |s| s.hide())
+    })
+}
@@ -0,0 +1,109 @@
+use std::{
+    io::{ErrorKind, Read},
+    sync::Arc,
+};
+
+use crossbeam_channel::Sender;
+use parking_lot::Mutex;
+
+/// The path argument that has Lapce read stdin, like `git diff | lapce -`
+pub const STDIN_ARG: &str = "-";
+
+/// How much of stdin is read at a time
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// The text read from stdin that the buffer showing it hasn't taken yet.
+/// What's read while the buffer is catching up is taken at once.
+#[derive(Clone, Default)]
+pub struct StdinText(Arc<Mutex<String>>);
+
+impl StdinText {
+    pub fn take(&self) -> String {
+        std::mem::take(&mut *self.0.lock())
+    }
+
+    fn push(&self, text: &str) {
+        self.0.lock().push_str(text);
+    }
+}
+
+/// Read stdin into `text` until it's closed, sending on `tx` each time there's
+/// more of it.
+pub fn read_stdin(text: StdinText, tx: Sender<()>) {
+    let mut stdin = std::io::stdin().lock();
+    let mut chunk = vec![0; CHUNK_SIZE];
+    let mut bytes = Vec::new();
+    loop {
+        match stdin.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => bytes.extend_from_slice(&chunk[..n]),
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(_) => break,
+        }
+        let decoded = take_complete(&mut bytes);
+        if decoded.is_empty() {
+            continue;
+        }
+        text.push(&decoded);
+        if tx.send(()).is_err() {
+            return;
+        }
+    }
+    if !bytes.is_empty() {
+        text.push(&String::from_utf8_lossy(&bytes));
+        let _ = tx.send(());
+    }
+}
+
+/// Take the text of `bytes` but for a character cut off at their end, which
+/// is left for the next chunk to complete. What isn't UTF-8 is replaced.
+fn take_complete(bytes: &mut Vec<u8>) -> String {
+    let rest = bytes.split_off(complete_len(bytes));
+    let text = String::from_utf8_lossy(bytes).into_owned();
+    *bytes = rest;
+    text
+}
+
+/// How many of `bytes` there are before a character that's cut off at their
+/// end, if there's one.
+fn complete_len(bytes: &[u8]) -> usize {
+    // A character is at most 4 bytes, so one that's cut off starts in the last 3
+    for i in (bytes.len().saturating_sub(3)..bytes.len()).rev() {
+        let width = match bytes[i] {
+            0x80..=0xBF => continue,
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => 1,
+        };
+        return if i + width > bytes.len() {
+            i
+        } else {
+            bytes.len()
+        };
+    }
+    bytes.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::take_complete;
+
+    #[test]
+    fn test_take_complete() {
+        let text = "añ€😀";
+        let mut bytes = Vec::new();
+        let mut decoded = String::new();
+        // Cut off at every byte
+        for byte in text.bytes() {
+            bytes.push(byte);
+            decoded.push_str(&take_complete(&mut bytes));
+        }
+        assert_eq!(decoded, text);
+        assert!(bytes.is_empty());
+
+        let mut bytes = b"a\xFFb\xE2\x82".to_vec();
+        assert_eq!(take_complete(&mut bytes), "a\u{FFFD}b");
+        assert_eq!(bytes, b"\xE2\x82");
+    }
+}
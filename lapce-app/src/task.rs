@@ -0,0 +1,340 @@
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use floem::reactive::{RwSignal, Scope};
+use lapce_rpc::task::{TaskExit, TaskId};
+use lsp_types::{MessageType, Position, ShowMessageParams};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    command::InternalCommand,
+    doc::ansi::{AnsiStyle, AnsiText},
+    editor::location::{EditorLocation, EditorPosition},
+    job::assert_off_ui_thread,
+    window_tab::CommonData,
+};
+
+pub const DEFAULT_TASKS_TOML: &str = include_str!("../../defaults/tasks.toml");
+
+/// How many lines of output of a run are kept, the oldest being dropped
+const MAX_OUTPUT_LINES: usize = 10000;
+
+/// A path followed by a line and maybe a column, like `src/main.rs:10:5`
+static LINK_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"((?:[A-Za-z]:[\\/])?[^\s:"'(),<>\[\]]+):(\d+)(?::(\d+))?"#)
+        .unwrap()
+});
+
+/// A shell command of the workspace, from `.lapce/tasks.toml`
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TaskConfig {
+    pub name: String,
+    pub command: String,
+    /// Where the command is run, the workspace if it isn't set
+    #[serde(default)]
+    pub cwd: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct TaskConfigs {
+    #[serde(default)]
+    pub tasks: Vec<TaskConfig>,
+}
+
+pub fn task_configs(workspace: Option<&Path>) -> Option<TaskConfigs> {
+    let workspace = workspace?;
+    assert_off_ui_thread("Reading the tasks");
+    let tasks_toml = workspace.join(".lapce").join("tasks.toml");
+    if !tasks_toml.exists() {
+        if !workspace.join(".lapce").exists() {
+            let _ = std::fs::create_dir_all(workspace.join(".lapce"));
+        }
+        let _ = std::fs::write(&tasks_toml, DEFAULT_TASKS_TOML);
+        return None;
+    }
+    let content = std::fs::read_to_string(tasks_toml).ok()?;
+    let configs: TaskConfigs = toml::from_str(&content).ok()?;
+    Some(configs)
+}
+
+/// A place in a file that a line of output points to
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutputLink {
+    /// Where it is in the line
+    pub range: Range<usize>,
+    pub path: String,
+    /// The line and the column, from 1 as they're written
+    pub line: u32,
+    pub column: Option<u32>,
+}
+
+/// The first `path:line:column` of `text`, with the column left out or not,
+/// like the ones of errors and of test failures.
+pub fn find_link(text: &str) -> Option<OutputLink> {
+    LINK_REGEX.captures_iter(text).find_map(|captures| {
+        let path = captures.get(1)?.as_str();
+        // What isn't a path, like a time or the port of a URL
+        if !path.contains(['.', '/', '\\']) || path.contains("//") {
+            return None;
+        }
+        let line = captures.get(2)?.as_str().parse::<u32>().ok()?;
+        let column = captures
+            .get(3)
+            .and_then(|column| column.as_str().parse::<u32>().ok());
+        Some(OutputLink {
+            range: captures.get(0)?.range(),
+            path: path.to_string(),
+            line,
+            column,
+        })
+    })
+}
+
+/// A line of a task's output, shown in the colors of its ANSI escape
+/// sequences
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutputLine {
+    pub text: String,
+    pub styles: Vec<(Range<usize>, AnsiStyle)>,
+    pub link: Option<OutputLink>,
+}
+
+impl OutputLine {
+    pub fn parse(line: &str) -> Self {
+        let (text, ansi) = AnsiText::parse(line);
+        let styles = ansi.styles(0..text.len());
+        let link = find_link(&text);
+        Self { text, styles, link }
+    }
+}
+
+/// A run of a task, with its output
+#[derive(Clone)]
+pub struct TaskRun {
+    pub task_id: TaskId,
+    pub task: TaskConfig,
+    /// Where the command runs, which the paths of the output are relative to
+    pub cwd: Option<PathBuf>,
+    pub lines: RwSignal<im::Vector<OutputLine>>,
+    /// How many lines were dropped from the front of the output
+    pub dropped: RwSignal<usize>,
+    /// How it ended, if it did
+    pub exit: RwSignal<Option<TaskExit>>,
+}
+
+impl TaskRun {
+    /// What became of the run, like "failed with exit code 1"
+    pub fn status(&self) -> String {
+        self.exit.with(|exit| match exit {
+            None => "running".to_string(),
+            Some(TaskExit::Code(0)) => "finished".to_string(),
+            Some(TaskExit::Code(code)) => {
+                format!("failed with exit code {code}")
+            }
+            Some(TaskExit::Signal) => "stopped".to_string(),
+            Some(TaskExit::Error(err)) => format!("couldn't run: {err}"),
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct TaskData {
+    /// The runs of the tasks, the latest last, with a run of each task at most
+    pub runs: RwSignal<im::Vector<TaskRun>>,
+    /// The run whose output is shown
+    pub shown: RwSignal<Option<TaskId>>,
+    pub common: CommonData,
+}
+
+impl TaskData {
+    pub fn new(cx: Scope, common: CommonData) -> Self {
+        Self {
+            runs: cx.create_rw_signal(im::Vector::new()),
+            shown: cx.create_rw_signal(None),
+            common,
+        }
+    }
+
+    pub fn run_of(&self, task_id: TaskId) -> Option<TaskRun> {
+        self.runs.with_untracked(|runs| {
+            runs.iter().find(|run| run.task_id == task_id).cloned()
+        })
+    }
+
+    /// Run `task` and show its output, in place of the last run of the task,
+    /// which is killed if it's still running.
+    pub fn run(&self, task: TaskConfig) {
+        let previous = self.runs.with_untracked(|runs| {
+            runs.iter().position(|run| run.task.name == task.name)
+        });
+        if let Some(index) = previous {
+            let mut runs = self.runs.get_untracked();
+            let run = runs.remove(index);
+            if run.exit.with_untracked(|exit| exit.is_none()) {
+                self.common.proxy.kill_task(run.task_id);
+            }
+            self.runs.set(runs);
+        }
+
+        let cx = self.common.scope;
+        let task_id = TaskId::next();
+        let cwd = self.cwd(&task);
+        self.common
+            .proxy
+            .run_task(task_id, task.command.clone(), cwd.clone());
+        let run = TaskRun {
+            task_id,
+            task,
+            cwd,
+            lines: cx.create_rw_signal(im::Vector::new()),
+            dropped: cx.create_rw_signal(0),
+            exit: cx.create_rw_signal(None),
+        };
+        self.runs.update(|runs| runs.push_back(run));
+        self.shown.set(Some(task_id));
+    }
+
+    /// Run the task that was run last again.
+    pub fn rerun_last(&self) {
+        let last = self
+            .runs
+            .with_untracked(|runs| runs.last().map(|run| run.task.clone()));
+        if let Some(task) = last {
+            self.run(task);
+        }
+    }
+
+    pub fn kill(&self, task_id: TaskId) {
+        self.common.proxy.kill_task(task_id);
+    }
+
+    pub fn kill_all(&self) {
+        for run in self.runs.get_untracked() {
+            if run.exit.with_untracked(|exit| exit.is_none()) {
+                self.common.proxy.kill_task(run.task_id);
+            }
+        }
+    }
+
+    pub fn output(&self, task_id: TaskId, line: &str) {
+        let Some(run) = self.run_of(task_id) else {
+            return;
+        };
+        let line = OutputLine::parse(line);
+        let full = run
+            .lines
+            .with_untracked(|lines| lines.len() >= MAX_OUTPUT_LINES);
+        if full {
+            run.dropped.update(|dropped| *dropped += 1);
+        }
+        run.lines.update(|lines| {
+            if full {
+                lines.pop_front();
+            }
+            lines.push_back(line);
+        });
+    }
+
+    /// Keep how the run ended, and tell it.
+    pub fn exited(&self, task_id: TaskId, exit: TaskExit) {
+        let Some(run) = self.run_of(task_id) else {
+            return;
+        };
+        let typ = match &exit {
+            TaskExit::Code(0) | TaskExit::Signal => MessageType::INFO,
+            TaskExit::Code(_) | TaskExit::Error(_) => MessageType::ERROR,
+        };
+        run.exit.set(Some(exit));
+        self.common
+            .internal_command
+            .send(InternalCommand::ShowMessage {
+                title: "Task".to_string(),
+                message: ShowMessageParams {
+                    typ,
+                    message: format!("{} {}", run.task.name, run.status()),
+                },
+            });
+    }
+
+    /// Go to the place in a file that a line of the output of `run` points to.
+    pub fn open_link(&self, run: &TaskRun, link: &OutputLink) {
+        let mut path = PathBuf::from(&link.path);
+        if path.is_relative() {
+            if let Some(cwd) =
+                run.cwd.as_ref().or(self.common.workspace.path.as_ref())
+            {
+                path = cwd.join(path);
+            }
+        }
+        let position = Position::new(
+            link.line.saturating_sub(1),
+            link.column.unwrap_or(1).saturating_sub(1),
+        );
+        self.common
+            .internal_command
+            .send(InternalCommand::JumpToLocation {
+                location: EditorLocation {
+                    path,
+                    position: Some(EditorPosition::Position(position)),
+                    scroll_offset: None,
+                    ignore_unconfirmed: false,
+                    same_editor_tab: false,
+                },
+            });
+    }
+
+    /// Where `task` is run, with `${workspace}` in its `cwd` being the
+    /// workspace, which a relative `cwd` is in too.
+    fn cwd(&self, task: &TaskConfig) -> Option<PathBuf> {
+        let workspace = self.common.workspace.path.as_ref();
+        let Some(cwd) = task.cwd.as_ref() else {
+            return workspace.cloned();
+        };
+        let cwd = match workspace.and_then(|workspace| workspace.to_str()) {
+            Some(workspace) => cwd.replace("${workspace}", workspace),
+            None => cwd.clone(),
+        };
+        let cwd = PathBuf::from(cwd);
+        match workspace {
+            Some(workspace) if cwd.is_relative() => Some(workspace.join(cwd)),
+            _ => Some(cwd),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_link, OutputLine};
+
+    #[test]
+    fn test_find_link() {
+        let link = find_link("  --> src/main.rs:10:5").unwrap();
+        assert_eq!(link.path, "src/main.rs");
+        assert_eq!((link.line, link.column), (10, Some(5)));
+        assert_eq!(link.range, 6..22);
+
+        let link = find_link("main.c:3: warning: unused").unwrap();
+        assert_eq!(
+            (link.path.as_str(), link.line, link.column),
+            ("main.c", 3, None)
+        );
+
+        let link = find_link(r"C:\code\lib.rs:7:1: error").unwrap();
+        assert_eq!(link.path, r"C:\code\lib.rs");
+
+        assert!(find_link("took 12:30, see http://localhost:8080").is_none());
+    }
+
+    #[test]
+    fn test_output_line() {
+        let line = OutputLine::parse("\x1b[1;31merror\x1b[0m: at lib.rs:2");
+        assert_eq!(line.text, "error: at lib.rs:2");
+        assert_eq!(line.styles.len(), 1);
+        assert_eq!(line.styles[0].0, 0..5);
+        assert_eq!(line.link.unwrap().range, 10..18);
+    }
+}
@@ -180,13 +180,7 @@ fn run_command(
                         }
                     }
                     if let Some(s) = clipboard.get_string() {
-                        if check_bracketed_paste {
-                            self.receive_char("\x1b[200~");
-                            self.receive_char(&s.replace('\x1b', ""));
-                            self.receive_char("\x1b[201~");
-                        } else {
-                            self.receive_char(&s);
-                        }
+                        self.receive_char(&paste_text(&s, check_bracketed_paste));
                     }
                 }
                 _ => return CommandExecuted::No,
@@ -657,3 +651,30 @@ pub fn new_process(&self, run_debug: Option<RunDebugProcess>) {
             .terminal_resize(self.term_id, width, height);
     }
 }
+
+/// What is written to the terminal for pasting `text`. With bracketed paste
+/// the program is told where the text starts and ends, and can take it as a
+/// whole rather than running its lines. Without it the line breaks are sent
+/// as the Enter key is.
+fn paste_text(text: &str, bracketed: bool) -> String {
+    if bracketed {
+        // The text can't end the paste early
+        format!("\x1b[200~{}\x1b[201~", text.replace('\x1b', ""))
+    } else {
+        text.replace("\r\n", "\r").replace('\n', "\r")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::paste_text;
+
+    #[test]
+    fn test_paste_text() {
+        assert_eq!(
+            paste_text("ls\x1b[201~\necho", true),
+            "\x1b[200~ls[201~\necho\x1b[201~"
+        );
+        assert_eq!(paste_text("ls\r\necho\n", false), "ls\recho\r");
+    }
+}
@@ -0,0 +1,143 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    debug::DEFAULT_RUN_TOML,
+    task::DEFAULT_TASKS_TOML,
+    workspace::{LapceWorkspace, LapceWorkspaceType},
+};
+
+/// The settings that run programs, as `section.key` or a whole section, with
+/// `*` standing for any key, which a workspace only sets for itself when it's
+/// trusted
+pub const TRUSTED_SETTINGS: &[&str] = &[
+    "language-servers",
+    "terminal.shell",
+    "languages.*.formatter",
+];
+
+/// The sections of the settings that are Lapce's own. The other ones are the
+/// settings of plugins, which can point them to the programs they run, so they
+/// need a trusted workspace too.
+const LAPCE_SECTIONS: &[&str] = &[
+    "core",
+    "ui",
+    "editor",
+    "terminal",
+    "color-theme",
+    "icon-theme",
+    "theme",
+    "languages",
+    "language-servers",
+    "files",
+    "search",
+];
+
+/// Whether the configuration of a workspace that runs programs, which are its
+/// tasks, its run configs and its settings in [`TRUSTED_SETTINGS`], is used
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WorkspaceTrust {
+    #[default]
+    Trusted,
+    Restricted,
+}
+
+impl WorkspaceTrust {
+    pub fn is_trusted(self) -> bool {
+        self == WorkspaceTrust::Trusted
+    }
+}
+
+/// The path the trust of `workspace` is kept by, the same however the
+/// workspace is opened. Only local workspaces have configuration of their own.
+pub fn trust_path(workspace: &LapceWorkspace) -> Option<PathBuf> {
+    if workspace.kind != LapceWorkspaceType::Local {
+        return None;
+    }
+    let path = workspace.path.as_ref()?;
+    Some(path.canonicalize().unwrap_or_else(|_| path.clone()))
+}
+
+/// Whether `workspace` has configuration of its own that runs programs. The
+/// task and run files Lapce writes when there are none don't count until
+/// they're changed.
+pub fn has_local_config(workspace: &LapceWorkspace) -> bool {
+    let Some(path) = trust_path(workspace) else {
+        return false;
+    };
+    let dir = path.join(".lapce");
+    let changed = |name: &str, default: &str| {
+        std::fs::read_to_string(dir.join(name))
+            .map_or(false, |content| content != default)
+    };
+    dir.join("settings.toml").exists()
+        || changed("tasks.toml", DEFAULT_TASKS_TOML)
+        || changed("run.toml", DEFAULT_RUN_TOML)
+}
+
+/// Take the settings that need a trusted workspace out of `settings`.
+pub fn restrict_settings(settings: &mut toml::value::Table) {
+    settings.retain(|section, _| LAPCE_SECTIONS.contains(&section.as_str()));
+    for setting in TRUSTED_SETTINGS {
+        remove_setting(settings, &setting.split('.').collect::<Vec<_>>());
+    }
+}
+
+/// Remove the setting at `keys` from `table`.
+fn remove_setting(table: &mut toml::value::Table, keys: &[&str]) {
+    match keys {
+        [] => {}
+        [key] => {
+            table.remove(*key);
+        }
+        ["*", rest @ ..] => {
+            for value in table.values_mut() {
+                if let toml::Value::Table(table) = value {
+                    remove_setting(table, rest);
+                }
+            }
+        }
+        [key, rest @ ..] => {
+            if let Some(toml::Value::Table(table)) = table.get_mut(*key) {
+                remove_setting(table, rest);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::restrict_settings;
+
+    #[test]
+    fn test_restrict_settings() {
+        let mut settings: toml::value::Table = toml::from_str(
+            r#"
+            [editor]
+            tab-width = 2
+            [terminal]
+            shell = "/tmp/shell"
+            font-size = 12
+            [languages.python]
+            indent-width = 2
+            formatter = "/tmp/formatter"
+            [language-servers.rust]
+            command = "/tmp/server"
+            [some-plugin]
+            serverPath = "/tmp/server"
+            "#,
+        )
+        .unwrap();
+        restrict_settings(&mut settings);
+
+        let keys = settings.keys().map(String::as_str).collect::<Vec<_>>();
+        assert_eq!(keys, ["editor", "languages", "terminal"]);
+        let terminal = settings["terminal"].as_table().unwrap();
+        assert!(terminal.get("shell").is_none());
+        assert_eq!(terminal["font-size"].as_integer(), Some(12));
+        let python = settings["languages"]["python"].as_table().unwrap();
+        assert!(python.get("formatter").is_none());
+        assert_eq!(python["indent-width"].as_integer(), Some(2));
+    }
+}
@@ -8,9 +8,17 @@
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    app::AppCommand, command::WindowCommand, config::LapceConfig, db::LapceDb,
-    keypress::EventRef, listener::Listener, update::ReleaseInfo,
-    window_tab::WindowTabData, workspace::LapceWorkspace,
+    app::AppCommand,
+    command::WindowCommand,
+    config::LapceConfig,
+    db::LapceDb,
+    job::spawn_job,
+    keypress::EventRef,
+    listener::Listener,
+    trust::WorkspaceTrust,
+    update::ReleaseInfo,
+    window_tab::{WindowTabData, WorkspaceSetup},
+    workspace::LapceWorkspace,
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,6 +35,35 @@ pub struct WindowInfo {
     pub tabs: TabsInfo,
 }
 
+/// What a window is opened with that's read from the disk, for itself and
+/// for each of its workspaces, the default workspace if it has none.
+pub struct WindowSetup {
+    config: LapceConfig,
+    workspaces: Vec<(LapceWorkspace, WorkspaceSetup)>,
+}
+
+impl WindowSetup {
+    pub fn load(db: &LapceDb, info: &WindowInfo) -> Self {
+        let config = LapceConfig::load(
+            &LapceWorkspace::default(),
+            &[],
+            WorkspaceTrust::Trusted,
+        );
+        let mut workspaces = info.tabs.workspaces.clone();
+        if workspaces.is_empty() {
+            workspaces.push(LapceWorkspace::default());
+        }
+        let workspaces = workspaces
+            .into_iter()
+            .map(|workspace| {
+                let setup = WorkspaceSetup::load(db, &workspace);
+                (workspace, setup)
+            })
+            .collect();
+        Self { config, workspaces }
+    }
+}
+
 /// `WindowData` is the application model for a top-level window.
 ///
 /// A top-level window can be independently moved around and
@@ -62,13 +99,13 @@ impl WindowData {
     pub fn new(
         window_id: WindowId,
         info: WindowInfo,
+        setup: WindowSetup,
         window_scale: RwSignal<f64>,
         latest_release: ReadSignal<Arc<Option<ReleaseInfo>>>,
         app_command: Listener<AppCommand>,
     ) -> Self {
         let cx = Scope::new();
-        let config = LapceConfig::load(&LapceWorkspace::default(), &[]);
-        let config = cx.create_rw_signal(Arc::new(config));
+        let config = cx.create_rw_signal(Arc::new(setup.config));
         let root_view_id = cx.create_rw_signal(floem::id::Id::next());
 
         let window_tabs = cx.create_rw_signal(im::Vector::new());
@@ -80,25 +117,11 @@ pub fn new(
 
         let ime_allowed = cx.create_rw_signal(false);
 
-        for w in info.tabs.workspaces {
-            let window_tab = Rc::new(WindowTabData::new(
-                cx,
-                Arc::new(w),
-                window_command,
-                window_scale,
-                latest_release,
-                num_window_tabs,
-                ime_allowed,
-            ));
-            window_tabs.update(|window_tabs| {
-                window_tabs.push_back((cx.create_rw_signal(0), window_tab));
-            });
-        }
-
-        if window_tabs.with_untracked(|window_tabs| window_tabs.is_empty()) {
+        for (workspace, setup) in setup.workspaces {
             let window_tab = Rc::new(WindowTabData::new(
                 cx,
-                Arc::new(LapceWorkspace::default()),
+                Arc::new(workspace),
+                setup,
                 window_command,
                 window_scale,
                 latest_release,
@@ -142,15 +165,96 @@ pub fn new(
         window_data
     }
 
+    /// Read the settings again, in a job for the window and in one for each
+    /// of its workspaces, and use them once they're read.
     pub fn reload_config(&self) {
-        let config = LapceConfig::load(&LapceWorkspace::default(), &[]);
-        self.config.set(Arc::new(config));
+        let window_config = self.config;
+        spawn_job(
+            self.scope,
+            || {
+                LapceConfig::load(
+                    &LapceWorkspace::default(),
+                    &[],
+                    WorkspaceTrust::Trusted,
+                )
+            },
+            move |mut config| {
+                config.keep_color_theme(&window_config.get_untracked());
+                window_config.set(Arc::new(config));
+            },
+        );
         let window_tabs = self.window_tabs.get_untracked();
         for (_, window_tab) in window_tabs {
             window_tab.reload_config();
         }
     }
 
+    /// Replace the workspace of the window tab at `active` with `workspace`.
+    fn set_workspace(
+        &self,
+        workspace: LapceWorkspace,
+        setup: WorkspaceSetup,
+        active: usize,
+    ) {
+        let window_tab = Rc::new(WindowTabData::new(
+            self.scope,
+            Arc::new(workspace),
+            setup,
+            self.window_command,
+            self.window_scale,
+            self.latest_release,
+            self.num_window_tabs,
+            self.ime_allowed,
+        ));
+        self.window_tabs.update(|window_tabs| {
+            if window_tabs.is_empty() {
+                window_tabs.push_back((self.scope.create_rw_signal(0), window_tab));
+            } else {
+                let active = window_tabs.len().saturating_sub(1).min(active);
+                let (_, old_window_tab) = window_tabs
+                    .set(active, (self.scope.create_rw_signal(0), window_tab));
+                old_window_tab.proxy.shutdown();
+            }
+        });
+        self.app_command.send(AppCommand::SaveApp);
+    }
+
+    /// Open `workspace` in a new window tab, after the active one or at the
+    /// `end`.
+    fn new_workspace_tab(
+        &self,
+        workspace: LapceWorkspace,
+        setup: WorkspaceSetup,
+        end: bool,
+    ) {
+        let window_tab = Rc::new(WindowTabData::new(
+            self.scope,
+            Arc::new(workspace),
+            setup,
+            self.window_command,
+            self.window_scale,
+            self.latest_release,
+            self.num_window_tabs,
+            self.ime_allowed,
+        ));
+        let active = self.active.get_untracked();
+        let active = self
+            .window_tabs
+            .try_update(|tabs| {
+                if end || tabs.is_empty() {
+                    tabs.push_back((self.scope.create_rw_signal(0), window_tab));
+                    tabs.len() - 1
+                } else {
+                    let index = tabs.len().min(active + 1);
+                    tabs.insert(index, (self.scope.create_rw_signal(0), window_tab));
+                    index
+                }
+            })
+            .unwrap();
+        self.active.set(active);
+        self.app_command.send(AppCommand::SaveApp);
+    }
+
     pub fn run_window_command(&self, cmd: WindowCommand) {
         match cmd {
             WindowCommand::SetWorkspace { workspace } => {
@@ -161,67 +265,40 @@ pub fn run_window_command(&self, cmd: WindowCommand) {
                 self.window_tabs.with_untracked(|window_tabs| {
                     if !window_tabs.is_empty() {
                         let active = window_tabs.len().saturating_sub(1).min(active);
-                        let _ = db.insert_window_tab(window_tabs[active].1.clone());
+                        let _ = db.save_window_tab(window_tabs[active].1.clone());
                     }
                 });
 
-                let window_tab = Rc::new(WindowTabData::new(
+                let window_data = self.clone();
+                spawn_job(
                     self.scope,
-                    Arc::new(workspace),
-                    self.window_command,
-                    self.window_scale,
-                    self.latest_release,
-                    self.num_window_tabs,
-                    self.ime_allowed,
-                ));
-                self.window_tabs.update(|window_tabs| {
-                    if window_tabs.is_empty() {
-                        window_tabs
-                            .push_back((self.scope.create_rw_signal(0), window_tab));
-                    } else {
-                        let active = window_tabs.len().saturating_sub(1).min(active);
-                        let (_, old_window_tab) = window_tabs.set(
-                            active,
-                            (self.scope.create_rw_signal(0), window_tab),
-                        );
-                        old_window_tab.proxy.shutdown();
-                    }
-                })
+                    move || {
+                        // The workspace might be the one just saved, when it's
+                        // opened again
+                        db.wait_saved();
+                        let setup = WorkspaceSetup::load(&db, &workspace);
+                        (workspace, setup)
+                    },
+                    move |(workspace, setup)| {
+                        window_data.set_workspace(workspace, setup, active);
+                    },
+                );
             }
             WindowCommand::NewWorkspaceTab { workspace, end } => {
                 let db: Arc<LapceDb> = use_context().unwrap();
                 let _ = db.update_recent_workspace(&workspace);
 
-                let window_tab = Rc::new(WindowTabData::new(
+                let window_data = self.clone();
+                spawn_job(
                     self.scope,
-                    Arc::new(workspace),
-                    self.window_command,
-                    self.window_scale,
-                    self.latest_release,
-                    self.num_window_tabs,
-                    self.ime_allowed,
-                ));
-                let active = self.active.get_untracked();
-                let active = self
-                    .window_tabs
-                    .try_update(|tabs| {
-                        if end || tabs.is_empty() {
-                            tabs.push_back((
-                                self.scope.create_rw_signal(0),
-                                window_tab,
-                            ));
-                            tabs.len() - 1
-                        } else {
-                            let index = tabs.len().min(active + 1);
-                            tabs.insert(
-                                index,
-                                (self.scope.create_rw_signal(0), window_tab),
-                            );
-                            index
-                        }
-                    })
-                    .unwrap();
-                self.active.set(active);
+                    move || {
+                        let setup = WorkspaceSetup::load(&db, &workspace);
+                        (workspace, setup)
+                    },
+                    move |(workspace, setup)| {
+                        window_data.new_workspace_tab(workspace, setup, end);
+                    },
+                );
             }
             WindowCommand::CloseWorkspaceTab { index } => {
                 let active = self.active.get_untracked();
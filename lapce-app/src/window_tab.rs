@@ -1,13 +1,21 @@
-use std::{collections::HashSet, env, path::Path, sync::Arc, time::Instant};
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    env,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use crossbeam_channel::Sender;
 use floem::{
-    action::{open_file, TimerToken},
+    action::{exec_after, open_file, TimerToken},
     cosmic_text::{Attrs, AttrsList, FamilyOwned, LineHeightValue, TextLayout},
     ext_event::{create_ext_action, create_signal_from_channel},
     file::FileDialogOptions,
     keyboard::ModifiersState,
-    peniko::kurbo::{Point, Rect, Vec2},
+    peniko::kurbo::{Point, Rect, Size, Vec2},
     reactive::{use_context, Memo, ReadSignal, RwSignal, Scope, WriteSignal},
 };
 use indexmap::IndexMap;
@@ -17,11 +25,13 @@
     core::CoreNotification,
     dap_types::RunDebugConfig,
     file::PathObject,
-    proxy::{ProxyRpcHandler, ProxyStatus},
+    plugin::{LanguageServerStatus, VoltID},
+    proxy::{ProxyResponse, ProxyRpcHandler, ProxyStatus},
     source_control::FileDiff,
     terminal::TermId,
+    RpcError,
 };
-use lsp_types::{ProgressParams, ProgressToken, ShowMessageParams};
+use lsp_types::{MessageType, ProgressParams, ProgressToken, ShowMessageParams};
 use serde_json::Value;
 use tracing::{debug, error};
 
@@ -29,42 +39,57 @@
     about::AboutData,
     alert::{AlertBoxData, AlertButton},
     code_action::{CodeActionData, CodeActionStatus},
+    color_picker::ColorPickerData,
     command::{
         CommandExecuted, CommandKind, InternalCommand, LapceCommand,
         LapceWorkbenchCommand, WindowCommand,
     },
-    completion::{CompletionData, CompletionStatus},
-    config::LapceConfig,
+    completion::{resolve_active_item, CompletionData, CompletionStatus},
+    config::{ui::UIConfig, watcher::ConfigWatcher, LapceConfig},
+    crash::CrashReport,
     db::LapceDb,
     debug::{DapData, RunDebugMode, RunDebugProcess},
     doc::{DocContent, EditorDiagnostic},
+    edit_preview::EditPreviewData,
     editor::{
         location::{EditorLocation, EditorPosition},
-        reset_blink_cursor,
+        reset_blink_cursor, EditorData,
     },
     editor_tab::EditorTabChild,
     file_explorer::data::FileExplorerData,
     find::Find,
+    first_run::{welcome_text, ConfigDirectory},
     global_search::GlobalSearchData,
     hover::HoverData,
-    id::WindowTabId,
-    keypress::{condition::Condition, EventRef, KeyPressData, KeyPressFocus},
+    id::{EditorId, WindowTabId},
+    job::spawn_job,
+    keypress::{
+        condition::Condition, EventRef, KeyPressData, KeyPressFocus, PendingState,
+    },
     listener::Listener,
     main_split::{MainSplitData, SplitData, SplitDirection},
+    outline::OutlineData,
     palette::{kind::PaletteKind, PaletteData, PaletteStatus},
     panel::{
         data::{default_panel_order, PanelData},
         kind::PanelKind,
         position::PanelContainerPosition,
     },
+    perf::{self, Phase},
     plugin::PluginData,
+    popup::{self, PopupSide},
+    problem::ProblemData,
     proxy::{new_proxy, path_from_url, ProxyData},
     rename::RenameData,
+    resize::{ResizeKey, MIN_EDITOR_TAB_SIZE},
+    signature::SignatureData,
     source_control::SourceControlData,
+    task::TaskData,
     terminal::{
         event::{terminal_update_process, TermEvent, TermNotification},
         panel::TerminalPanelData,
     },
+    trust::{has_local_config, trust_path, WorkspaceTrust},
     update::ReleaseInfo,
     workspace::{LapceWorkspace, LapceWorkspaceType, WorkspaceInfo},
 };
@@ -75,6 +100,7 @@ pub enum Focus {
     Palette,
     CodeAction,
     Rename,
+    ColorPicker,
     AboutPopup,
     Panel(PanelKind),
 }
@@ -99,6 +125,31 @@ pub struct WorkProgress {
     pub percentage: Option<u32>,
 }
 
+/// How many lines of the stderr of a language server are kept.
+const LANGUAGE_SERVER_LOG_LINES: usize = 1000;
+
+#[derive(Clone, PartialEq)]
+pub struct LanguageServerInfo {
+    /// The command of a configured server, or the name of the volt
+    pub server: String,
+    pub status: LanguageServerStatus,
+    /// The last lines the server wrote to its stderr
+    pub log: im::Vector<String>,
+}
+
+#[derive(Clone)]
+pub struct WindowMessage {
+    pub title: String,
+    pub message: ShowMessageParams,
+    /// How many times the message was shown while it was still open.
+    pub count: usize,
+    /// The text the message has a button to copy, like the backtrace of a
+    /// crash
+    pub details: Option<String>,
+    /// The timer which closes the message, if it isn't an error or warning.
+    timer: TimerToken,
+}
+
 #[derive(Clone)]
 pub struct CommonData {
     pub workspace: Arc<LapceWorkspace>,
@@ -107,6 +158,7 @@ pub struct CommonData {
     pub keypress: RwSignal<KeyPressData>,
     pub completion: RwSignal<CompletionData>,
     pub hover: HoverData,
+    pub signature: SignatureData,
     pub register: RwSignal<Register>,
     pub find: Find,
     pub window_origin: RwSignal<Point>,
@@ -126,6 +178,40 @@ pub struct CommonData {
     pub mouse_hover_timer: RwSignal<TimerToken>,
     pub hide_cursor: RwSignal<bool>,
     pub ime_allowed: RwSignal<bool>,
+    /// Bumped for every definition or references request, so that the
+    /// response to one the user has moved on from is dropped
+    pub navigation_request: RwSignal<usize>,
+    /// Whether the configuration of the workspace that runs programs is used,
+    /// which is fixed until the workspace is opened again
+    pub workspace_trust: WorkspaceTrust,
+}
+
+impl CommonData {
+    /// Keep whether the workspace is trusted, and open it again when that
+    /// changes, for its settings and its language servers to follow, once
+    /// it's written.
+    pub fn set_workspace_trust(&self, db: Arc<LapceDb>, trust: WorkspaceTrust) {
+        self.internal_command.send(InternalCommand::HideAlert);
+        let workspace = self.workspace.clone();
+        let changed = trust != self.workspace_trust;
+        let window_command = self.window_command;
+        spawn_job(
+            self.scope,
+            move || {
+                if let Err(err) = db.insert_workspace_trust(&workspace, trust) {
+                    error!("{:?}", err);
+                }
+                workspace
+            },
+            move |workspace| {
+                if changed {
+                    window_command.send(WindowCommand::SetWorkspace {
+                        workspace: (*workspace).clone(),
+                    });
+                }
+            },
+        );
+    }
 }
 
 #[derive(Clone)]
@@ -139,9 +225,14 @@ pub struct WindowTabData {
     pub panel: PanelData,
     pub terminal: TerminalPanelData,
     pub plugin: PluginData,
+    pub problem: ProblemData,
+    pub outline: OutlineData,
+    pub edit_preview: EditPreviewData,
+    pub task: TaskData,
     pub code_action: RwSignal<CodeActionData>,
     pub source_control: SourceControlData,
     pub rename: RenameData,
+    pub color_picker: ColorPickerData,
     pub global_search: GlobalSearchData,
     pub about_data: AboutData,
     pub alert_data: AlertBoxData,
@@ -153,7 +244,19 @@ pub struct WindowTabData {
     pub latest_release: ReadSignal<Arc<Option<ReleaseInfo>>>,
     pub num_window_tabs: Memo<usize>,
     pub progresses: RwSignal<IndexMap<ProgressToken, WorkProgress>>,
-    pub messages: RwSignal<Vec<(String, ShowMessageParams)>>,
+    /// The language servers by the volt they come from
+    pub language_servers: RwSignal<IndexMap<VoltID, LanguageServerInfo>>,
+    /// The language server whose log is shown above the status bar
+    pub expanded_language_server: RwSignal<Option<VoltID>>,
+    /// Whether the frames per second and the timings of the editor are shown
+    /// in the corner
+    pub timing_overlay: RwSignal<bool>,
+    /// Whether the keys resize the focused editor tab or panel
+    pub resize_mode: RwSignal<bool>,
+    pub messages: RwSignal<Vec<WindowMessage>>,
+    /// Watches the directory of the workspace settings file, for reloading
+    /// the config when they're changed
+    settings_watcher: Rc<RefCell<Option<notify::RecommendedWatcher>>>,
     pub common: CommonData,
 }
 
@@ -183,10 +286,76 @@ fn run_command(
     fn receive_char(&self, _c: &str) {}
 }
 
+/// What a workspace is opened with that's read from the disk: its settings,
+/// its session and whether it's trusted. A workspace opened while Lapce runs
+/// has it read by a job, off the UI thread.
+pub struct WorkspaceSetup {
+    disabled_volts: Vec<VoltID>,
+    workspace_disabled_volts: Vec<VoltID>,
+    /// Whether the workspace is trusted, if that was decided
+    trust_decided: Option<WorkspaceTrust>,
+    workspace_trust: WorkspaceTrust,
+    workspace_info: Option<WorkspaceInfo>,
+    config: LapceConfig,
+}
+
+impl WorkspaceSetup {
+    pub fn load(db: &LapceDb, workspace: &LapceWorkspace) -> Self {
+        let disabled_volts = db.get_disabled_volts().unwrap_or_default();
+        let workspace_disabled_volts = db
+            .get_workspace_disabled_volts(workspace)
+            .unwrap_or_default();
+        let mut all_disabled_volts = disabled_volts.clone();
+        all_disabled_volts.extend(workspace_disabled_volts.clone());
+
+        // A workspace whose trust wasn't decided is restricted until it is, if
+        // it has configuration that runs programs
+        let trust_decided = db.get_workspace_trust(workspace).ok();
+        let workspace_trust = trust_decided.unwrap_or_else(|| {
+            if has_local_config(workspace) {
+                WorkspaceTrust::Restricted
+            } else {
+                WorkspaceTrust::Trusted
+            }
+        });
+
+        let mut workspace_info = if workspace.path.is_some() {
+            db.get_workspace_info(workspace).ok()
+        } else {
+            let mut info = db.get_workspace_info(workspace).ok();
+            if let Some(info) = info.as_mut() {
+                info.split.children.clear();
+            }
+            info
+        };
+        // Files might have been removed since, which only can be checked here
+        // for a local workspace
+        if workspace.kind.is_local() {
+            if let Some(info) = workspace_info.as_mut() {
+                info.split.retain_existing_files();
+            }
+        }
+
+        let config =
+            LapceConfig::load(workspace, &all_disabled_volts, workspace_trust);
+
+        Self {
+            disabled_volts,
+            workspace_disabled_volts,
+            trust_decided,
+            workspace_trust,
+            workspace_info,
+            config,
+        }
+    }
+}
+
 impl WindowTabData {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         cx: Scope,
         workspace: Arc<LapceWorkspace>,
+        setup: WorkspaceSetup,
         window_command: Listener<WindowCommand>,
         window_scale: RwSignal<f64>,
         latest_release: ReadSignal<Arc<Option<ReleaseInfo>>>,
@@ -196,29 +365,21 @@ pub fn new(
         let cx = cx.create_child();
         let db: Arc<LapceDb> = use_context().unwrap();
 
-        let disabled_volts = db.get_disabled_volts().unwrap_or_default();
-        let workspace_disabled_volts = db
-            .get_workspace_disabled_volts(&workspace)
-            .unwrap_or_default();
+        let WorkspaceSetup {
+            disabled_volts,
+            workspace_disabled_volts,
+            trust_decided,
+            workspace_trust,
+            workspace_info,
+            config,
+        } = setup;
         let mut all_disabled_volts = disabled_volts.clone();
         all_disabled_volts.extend(workspace_disabled_volts.clone());
-
-        let workspace_info = if workspace.path.is_some() {
-            db.get_workspace_info(&workspace).ok()
-        } else {
-            let mut info = db.get_workspace_info(&workspace).ok();
-            if let Some(info) = info.as_mut() {
-                info.split.children.clear();
-            }
-            info
-        };
-
-        let config = LapceConfig::load(&workspace, &all_disabled_volts);
         let lapce_command = Listener::new_empty(cx);
         let workbench_command = Listener::new_empty(cx);
         let internal_command = Listener::new_empty(cx);
         let keypress =
-            cx.create_rw_signal(KeyPressData::new(cx, &config, workbench_command));
+            cx.create_rw_signal(KeyPressData::new(cx, &config, lapce_command));
         let proxy_status = cx.create_rw_signal(None);
 
         let (term_tx, term_rx) = crossbeam_channel::unbounded();
@@ -235,6 +396,7 @@ pub fn new(
             workspace.clone(),
             all_disabled_volts,
             config.plugins.clone(),
+            config.language_servers.clone(),
             term_tx.clone(),
         );
         let (config, set_config) = cx.create_signal(Arc::new(config));
@@ -269,6 +431,7 @@ pub fn new(
             focus,
             completion,
             hover,
+            signature: SignatureData::new(cx),
             register,
             find,
             window_command,
@@ -288,6 +451,8 @@ pub fn new(
             hide_cursor: cx.create_rw_signal(false),
             window_origin: cx.create_rw_signal(Point::ZERO),
             ime_allowed,
+            navigation_request: cx.create_rw_signal(0),
+            workspace_trust,
         };
 
         let main_split = MainSplitData::new(cx, common.clone());
@@ -309,6 +474,7 @@ pub fn new(
                     split_id: root_split,
                     children: Vec::new(),
                     direction: SplitDirection::Horizontal,
+                    weights: im::HashMap::new(),
                     window_origin: Point::ZERO,
                     layout_rect: Rect::ZERO,
                 };
@@ -352,6 +518,7 @@ pub fn new(
             TerminalPanelData::new(workspace.clone(), None, common.clone());
 
         let rename = RenameData::new(cx, common.clone());
+        let color_picker = ColorPickerData::new(cx, common.clone());
         let global_search =
             GlobalSearchData::new(cx, main_split.clone(), common.clone());
 
@@ -381,9 +548,21 @@ pub fn new(
             });
         }
 
+        resolve_active_item(cx, common.completion, common.proxy.clone());
+
+        let problem = ProblemData::new(cx, common.clone());
+        let outline = OutlineData::new(cx, main_split.active_editor, common.clone());
+        let edit_preview =
+            EditPreviewData::new(cx, main_split.clone(), common.clone());
+        let task = TaskData::new(cx, common.clone());
+
         let about_data = AboutData::new(cx, common.focus);
         let alert_data = AlertBoxData::new(cx, common.clone());
 
+        let (settings_tx, settings_rx) = crossbeam_channel::bounded(1);
+        let settings_watcher =
+            notify::recommended_watcher(ConfigWatcher::new(settings_tx)).ok();
+
         let window_tab_data = Self {
             scope: cx,
             window_tab_id: WindowTabId::next(),
@@ -396,7 +575,12 @@ pub fn new(
             code_action,
             source_control,
             plugin,
+            problem,
+            outline,
+            edit_preview,
+            task,
             rename,
+            color_picker,
             global_search,
             about_data,
             alert_data,
@@ -408,7 +592,12 @@ pub fn new(
             num_window_tabs,
             latest_release,
             progresses: cx.create_rw_signal(IndexMap::new()),
+            language_servers: cx.create_rw_signal(IndexMap::new()),
+            expanded_language_server: cx.create_rw_signal(None),
+            timing_overlay: cx.create_rw_signal(false),
+            resize_mode: cx.create_rw_signal(false),
             messages: cx.create_rw_signal(Vec::new()),
+            settings_watcher: Rc::new(RefCell::new(settings_watcher)),
             common,
         };
 
@@ -419,6 +608,7 @@ pub fn new(
             let config = window_tab_data.common.config;
             let active_editor = window_tab_data.main_split.active_editor;
             let rename_active = window_tab_data.rename.active;
+            let color_picker = window_tab_data.color_picker.clone();
             cx.create_effect(move |_| {
                 let focus = focus.get();
                 active_editor.track();
@@ -426,6 +616,10 @@ pub fn new(
                 if focus != Focus::Rename && rename_active.get_untracked() {
                     rename_active.set(false);
                 }
+                if focus != Focus::ColorPicker && color_picker.active.get_untracked()
+                {
+                    color_picker.cancel();
+                }
             });
         }
 
@@ -454,33 +648,167 @@ pub fn new(
         {
             let window_tab_data = window_tab_data.clone();
             let notification = window_tab_data.proxy.notification;
+            let handled = window_tab_data.proxy.notification_handled.clone();
             cx.create_effect(move |_| {
-                notification.with(|rpc| {
-                    if let Some(rpc) = rpc.as_ref() {
-                        window_tab_data.handle_core_notification(rpc);
+                notification.with(|batch| {
+                    if let Some(batch) = batch.as_ref() {
+                        for rpc in batch {
+                            window_tab_data.handle_core_notification(rpc);
+                        }
+                        let _ = handled.send(());
                     }
                 });
             });
         }
 
+        {
+            let window_tab_data = window_tab_data.clone();
+            let notification = create_signal_from_channel(settings_rx);
+            cx.create_effect(move |_| {
+                if notification.get().is_some() {
+                    window_tab_data.reload_config();
+                }
+            });
+        }
+        window_tab_data.watch_workspace_settings();
+
+        {
+            let file_explorer = window_tab_data.file_explorer.clone();
+            let main_split = window_tab_data.main_split.clone();
+            let config = window_tab_data.common.config;
+            cx.create_effect(move |_| {
+                let follow = config.with(|config| config.ui.file_explorer_follow);
+                main_split.active_editor.track();
+                if !follow {
+                    return;
+                }
+                if let Some(path) = main_split.active_file() {
+                    file_explorer.follow(path);
+                }
+            });
+        }
+
+        if trust_decided.is_none() && !workspace_trust.is_trusted() {
+            window_tab_data.show_trust_prompt();
+        }
+
         window_tab_data
     }
 
+    /// Ask whether to trust the configuration of the workspace that runs
+    /// programs, or to keep it restricted.
+    pub fn show_trust_prompt(&self) {
+        let db: Arc<LapceDb> = use_context().unwrap();
+        let name = self.workspace.display().unwrap_or_default();
+        let current = match self.common.workspace_trust {
+            WorkspaceTrust::Trusted => "It's trusted.",
+            WorkspaceTrust::Restricted => "It's open in restricted mode.",
+        };
+        let button = |text: &str, trust: WorkspaceTrust| {
+            let common = self.common.clone();
+            let db = db.clone();
+            AlertButton {
+                text: text.to_string(),
+                action: Rc::new(move || {
+                    common.set_workspace_trust(db.clone(), trust)
+                }),
+            }
+        };
+        self.show_alert(
+            format!("Do you trust the authors of {name}?"),
+            format!(
+                "The workspace has tasks, run configs or settings that can \
+                 run programs, like its language servers and its terminal \
+                 shell. In restricted mode they're left out, and its files \
+                 open as usual. {current}"
+            ),
+            vec![
+                button("Trust", WorkspaceTrust::Trusted),
+                button("Open Restricted", WorkspaceTrust::Restricted),
+            ],
+        );
+    }
+
+    /// Watch the directory of the workspace settings file, if the workspace
+    /// has one and it's there, so the config is reloaded when it's changed.
+    fn watch_workspace_settings(&self) {
+        let Some(path) = LapceConfig::workspace_settings_file(&self.workspace)
+        else {
+            return;
+        };
+        let Some(dir) = path.parent().filter(|dir| dir.exists()) else {
+            return;
+        };
+        if let Some(watcher) = self.settings_watcher.borrow_mut().as_mut() {
+            let _ = watcher.watch(dir, notify::RecursiveMode::NonRecursive);
+        }
+    }
+
+    /// Read the settings again, in a job, and use them once they're read.
     pub fn reload_config(&self) {
         let db: Arc<LapceDb> = use_context().unwrap();
+        let workspace = self.workspace.clone();
+        let workspace_trust = self.common.workspace_trust;
+        let window_tab = self.clone();
+        spawn_job(
+            self.scope,
+            move || {
+                let disabled_volts = db.get_disabled_volts().unwrap_or_default();
+                let workspace_disabled_volts = db
+                    .get_workspace_disabled_volts(&workspace)
+                    .unwrap_or_default();
+                let mut all_disabled_volts = disabled_volts;
+                all_disabled_volts.extend(workspace_disabled_volts);
+                LapceConfig::load(&workspace, &all_disabled_volts, workspace_trust)
+            },
+            move |config| window_tab.set_reloaded_config(config),
+        );
+    }
 
-        let disabled_volts = db.get_disabled_volts().unwrap_or_default();
-        let workspace_disabled_volts = db
-            .get_workspace_disabled_volts(&self.workspace)
+    fn set_reloaded_config(&self, mut config: LapceConfig) {
+        config.keep_color_theme(&self.common.config.get_untracked());
+        for err in &config.color_theme_errors {
+            self.show_message(
+                "Color Theme",
+                &ShowMessageParams {
+                    typ: MessageType::ERROR,
+                    message: err.clone(),
+                },
+            );
+        }
+        let keymap_errors = self
+            .common
+            .keypress
+            .try_update(|keypress| keypress.update_keymaps(&config))
             .unwrap_or_default();
-        let mut all_disabled_volts = disabled_volts;
-        all_disabled_volts.extend(workspace_disabled_volts);
-
-        let config = LapceConfig::load(&self.workspace, &all_disabled_volts);
-        self.common.keypress.update(|keypress| {
-            keypress.update_keymaps(&config);
-        });
+        for err in keymap_errors {
+            self.show_message(
+                "Keymaps",
+                &ShowMessageParams {
+                    typ: MessageType::ERROR,
+                    message: err,
+                },
+            );
+        }
+        let old_config = self.common.config.get_untracked();
+        if config.core.modal != old_config.core.modal {
+            self.main_split.set_modal(config.core.modal);
+        }
+        let associations_changed =
+            config.editor.file_associations != old_config.editor.file_associations;
         self.set_config.set(Arc::new(config));
+        self.main_split.update_spell_check();
+        if associations_changed {
+            self.main_split.detect_languages();
+        }
+    }
+
+    /// Scale the UI around the editors, which composes with the zoom of the
+    /// whole window and the scale of the display. It's saved in the settings,
+    /// and their file being watched makes every window follow.
+    fn set_ui_scale(&self, scale: f64) {
+        let scale = UIConfig::clamp_scale(scale);
+        LapceConfig::update_file("ui", "scale", toml_edit::Value::from(scale));
     }
 
     pub fn run_lapce_command(&self, cmd: LapceCommand) {
@@ -488,7 +816,10 @@ pub fn run_lapce_command(&self, cmd: LapceCommand) {
             CommandKind::Workbench(command) => {
                 self.run_workbench_command(command, cmd.data);
             }
-            CommandKind::Focus(_) | CommandKind::Edit(_) | CommandKind::Move(_) => {
+            CommandKind::Focus(_)
+            | CommandKind::Edit(_)
+            | CommandKind::Move(_)
+            | CommandKind::MultiSelection(_) => {
                 if self.palette.status.get_untracked() != PaletteStatus::Inactive {
                     self.palette
                         .run_command(&cmd, None, ModifiersState::empty());
@@ -502,7 +833,6 @@ pub fn run_lapce_command(&self, cmd: LapceCommand) {
                 }
             }
             CommandKind::MotionMode(_) => {}
-            CommandKind::MultiSelection(_) => {}
         }
     }
 
@@ -570,6 +900,75 @@ pub fn run_workbench_command(
             NewFile => {
                 self.main_split.new_file();
             }
+            ToggleReadOnly => {
+                self.main_split.toggle_read_only();
+            }
+            ToggleAnsiColors => {
+                self.main_split.toggle_ansi_colors();
+            }
+            ToggleSpellCheck => {
+                self.main_split.toggle_spell_check();
+            }
+            ToggleWhitespace => {
+                self.main_split.toggle_whitespace();
+            }
+            ToggleLineNumbers => {
+                self.main_split.toggle_line_numbers();
+            }
+            LinkScroll => {
+                self.main_split.link_scroll();
+            }
+            UnlinkScroll => {
+                self.main_split.unlink_scroll();
+            }
+            ReopenClosedEditor => {
+                self.main_split.reopen_closed_editor();
+            }
+            OpenChanges => {
+                let path = self.main_split.active_editor.get_untracked().and_then(
+                    |editor| {
+                        let doc = editor.with_untracked(|editor| editor.view.doc);
+                        doc.with_untracked(|doc| {
+                            if let DocContent::File(path) = &doc.content {
+                                Some(path.clone())
+                            } else {
+                                None
+                            }
+                        })
+                    },
+                );
+                if let Some(path) = path {
+                    self.main_split.open_file_changes(path);
+                }
+            }
+            CompareWithSaved => {
+                if let Some(path) = self.main_split.active_file() {
+                    self.main_split.open_saved_changes(path);
+                }
+            }
+            RevertFile => {
+                if let Some(editor) = self.main_split.active_editor.get_untracked() {
+                    editor.get_untracked().revert_file();
+                }
+            }
+            ToggleDiffIgnoreWhitespace => {
+                if let Some(diff_editor) = self.main_split.active_diff_editor() {
+                    diff_editor.toggle_ignore_whitespace();
+                }
+            }
+            AlternateFile => {
+                self.main_split.open_alternate_file();
+            }
+            ResizeMode => {
+                self.resize_mode.set(true);
+            }
+            RenameFile => {
+                if let Some(path) = self.main_split.active_file() {
+                    if self.common.workspace.kind.is_local() {
+                        self.file_explorer.rename(&path);
+                    }
+                }
+            }
             RevealActiveFileInFileExplorer => {
                 if let Some(editor_data) = self.main_split.active_editor.get() {
                     editor_data.with_untracked(|editor_data| {
@@ -587,6 +986,22 @@ pub fn run_workbench_command(
                     });
                 }
             }
+            RevealActiveFileInPanel => {
+                if let Some(path) = self.main_split.active_file() {
+                    self.show_panel(PanelKind::FileExplorer);
+                    self.file_explorer.reveal_path(&path);
+                }
+            }
+            ToggleFileExplorerFollow => {
+                let follow =
+                    self.common.config.get_untracked().ui.file_explorer_follow;
+                // The config file is watched
+                LapceConfig::update_file(
+                    "ui",
+                    "file-explorer-follow",
+                    toml_edit::Value::from(!follow),
+                );
+            }
 
             SaveAll => {
                 self.main_split.editors.with_untracked(|editors| {
@@ -621,6 +1036,33 @@ pub fn run_workbench_command(
                 self.main_split.open_settings();
             }
             OpenSettingsFile => {
+                if LapceConfig::workspace_settings_file(&self.workspace).is_none() {
+                    self.run_workbench_command(OpenUserSettingsFile, None);
+                    return;
+                }
+                let button = |text: &str, cmd: LapceWorkbenchCommand| {
+                    let internal_command = self.common.internal_command;
+                    let workbench_command = self.common.workbench_command;
+                    AlertButton {
+                        text: text.to_string(),
+                        action: Rc::new(move || {
+                            internal_command.send(InternalCommand::HideAlert);
+                            workbench_command.send(cmd.clone());
+                        }),
+                    }
+                };
+                self.show_alert(
+                    "Which settings do you want to edit?".to_string(),
+                    "The workspace settings take precedence over the user \
+                     settings in this workspace."
+                        .to_string(),
+                    vec![
+                        button("User Settings", OpenUserSettingsFile),
+                        button("Workspace Settings", OpenWorkspaceSettingsFile),
+                    ],
+                );
+            }
+            OpenUserSettingsFile => {
                 if let Some(path) = LapceConfig::settings_file() {
                     self.main_split.jump_to_location(
                         EditorLocation {
@@ -634,6 +1076,56 @@ pub fn run_workbench_command(
                     );
                 }
             }
+            OpenWorkspaceSettingsFile => {
+                let Some(path) =
+                    LapceConfig::workspace_settings_file(&self.workspace)
+                else {
+                    self.show_message(
+                        "Workspace Settings",
+                        &ShowMessageParams {
+                            typ: MessageType::INFO,
+                            message: "Only a local folder has workspace settings"
+                                .to_string(),
+                        },
+                    );
+                    return;
+                };
+                let window_tab = self.clone();
+                spawn_job(
+                    self.scope,
+                    {
+                        let path = path.clone();
+                        move || {
+                            if path.exists() {
+                                return false;
+                            }
+                            if let Some(dir) = path.parent() {
+                                let _ = std::fs::create_dir_all(dir);
+                            }
+                            let _ = std::fs::OpenOptions::new()
+                                .create_new(true)
+                                .write(true)
+                                .open(&path);
+                            true
+                        }
+                    },
+                    move |created| {
+                        if created {
+                            window_tab.watch_workspace_settings();
+                        }
+                        window_tab.main_split.jump_to_location(
+                            EditorLocation {
+                                path,
+                                position: None,
+                                scroll_offset: None,
+                                ignore_unconfirmed: false,
+                                same_editor_tab: false,
+                            },
+                            None,
+                        );
+                    },
+                );
+            }
             OpenSettingsDirectory => {
                 if let Some(dir) = Directory::config_directory() {
                     open_uri(&dir);
@@ -657,21 +1149,45 @@ pub fn run_workbench_command(
                 }
             }
             OpenLogFile => {
-                if let Some(dir) = Directory::logs_directory() {
-                    self.open_paths(&[PathObject::from_path(
-                        dir.join(format!(
-                            "lapce.{}.log",
-                            chrono::prelude::Local::now().format("%Y-%m-%d")
-                        )),
-                        false,
-                    )])
-                }
+                self.main_split.open_log();
             }
             OpenLogsDirectory => {
                 if let Some(dir) = Directory::logs_directory() {
                     open_uri(&dir);
                 }
             }
+            LogOpenEditors => {
+                self.main_split.log_editors();
+            }
+            ToggleTimingOverlay => {
+                let shown = !self.timing_overlay.get_untracked();
+                perf::set_enabled(shown);
+                self.timing_overlay.set(shown);
+            }
+            SaveTimings => {
+                if let Some(dir) = Directory::logs_directory() {
+                    let path = dir.join(format!(
+                        "timings.{}.txt",
+                        chrono::prelude::Local::now().format("%Y-%m-%d_%H-%M-%S")
+                    ));
+                    let summary = perf::summary();
+                    let window_tab = self.clone();
+                    spawn_job(
+                        self.scope,
+                        {
+                            let path = path.clone();
+                            move || std::fs::write(path, summary)
+                        },
+                        move |result| match result {
+                            Ok(()) => window_tab
+                                .open_paths(&[PathObject::from_path(path, false)]),
+                            Err(err) => {
+                                error!("Failed to save the timings: {err}");
+                            }
+                        },
+                    );
+                }
+            }
             OpenProxyDirectory => {
                 if let Some(dir) = Directory::proxy_directory() {
                     open_uri(&dir);
@@ -691,8 +1207,23 @@ pub fn run_workbench_command(
             InstallTheme => {}
             ExportCurrentThemeSettings => {}
             ToggleInlayHints => {}
+            ToggleInlineBlame => {
+                let inline_blame =
+                    self.common.config.get_untracked().editor.inline_blame;
+                // The config file is watched
+                LapceConfig::update_file(
+                    "editor",
+                    "inline-blame",
+                    toml_edit::Value::from(!inline_blame),
+                );
+            }
 
             // ==== Window ====
+            ManageWorkspaceTrust => {
+                if trust_path(&self.workspace).is_some() {
+                    self.show_trust_prompt();
+                }
+            }
             ReloadWindow => {
                 self.common
                     .window_command
@@ -866,6 +1397,14 @@ pub fn run_workbench_command(
             PaletteCommand => {
                 self.palette.run(PaletteKind::Command);
             }
+            PaletteKeymap => {
+                self.palette.run(PaletteKind::Keymap);
+            }
+            RepeatLastCommand => {
+                if let Some(cmd) = self.palette.last_command() {
+                    self.common.lapce_command.send(cmd);
+                }
+            }
             PaletteWorkspace => {
                 self.palette.run(PaletteKind::Workspace);
             }
@@ -901,6 +1440,20 @@ pub fn run_workbench_command(
                     self.terminal.stop_run_debug(term_id);
                 }
             }
+            PaletteTask => {
+                self.palette.run(PaletteKind::Task);
+            }
+            RerunLastTask => {
+                if self.task.runs.with_untracked(|runs| runs.is_empty()) {
+                    self.palette.run(PaletteKind::Task);
+                } else {
+                    self.task.rerun_last();
+                    self.show_panel(PanelKind::Task);
+                }
+            }
+            KillTasks => {
+                self.task.kill_all();
+            }
 
             // ==== UI ====
             ZoomIn => {
@@ -922,6 +1475,17 @@ pub fn run_workbench_command(
             ZoomReset => {
                 self.window_scale.set(1.0);
             }
+            UiZoomIn => {
+                let scale = self.common.config.with_untracked(|c| c.ui.scale());
+                self.set_ui_scale(scale + 0.1);
+            }
+            UiZoomOut => {
+                let scale = self.common.config.with_untracked(|c| c.ui.scale());
+                self.set_ui_scale(scale - 0.1);
+            }
+            UiZoomReset => {
+                self.set_ui_scale(1.0);
+            }
 
             ToggleMaximizedPanel => {
                 if let Some(data) = data {
@@ -987,6 +1551,9 @@ pub fn run_workbench_command(
             ToggleSearchFocus => {
                 self.toggle_panel_focus(PanelKind::Search);
             }
+            ToggleOutlineFocus => {
+                self.toggle_panel_focus(PanelKind::Outline);
+            }
             ToggleTerminalVisual => {
                 self.toggle_panel_visual(PanelKind::Terminal);
             }
@@ -1008,9 +1575,27 @@ pub fn run_workbench_command(
             ToggleSearchVisual => {
                 self.toggle_panel_visual(PanelKind::Search);
             }
+            ToggleOutlineVisual => {
+                self.toggle_panel_visual(PanelKind::Outline);
+            }
+            ToggleEditPreviewVisual => {
+                self.toggle_panel_visual(PanelKind::EditPreview);
+            }
+            ToggleTasksVisual => {
+                self.toggle_panel_visual(PanelKind::Task);
+            }
             FocusEditor => {
                 self.common.focus.set(Focus::Workbench);
             }
+            FocusLeftPanel => {
+                self.focus_container(&PanelContainerPosition::Left);
+            }
+            FocusBottomPanel => {
+                self.focus_container(&PanelContainerPosition::Bottom);
+            }
+            FocusRightPanel => {
+                self.focus_container(&PanelContainerPosition::Right);
+            }
             FocusTerminal => {
                 self.common.focus.set(Focus::Panel(PanelKind::Terminal));
             }
@@ -1123,7 +1708,9 @@ pub fn run_workbench_command(
             NextError => {
                 self.main_split.next_error();
             }
-            PreviousError => {}
+            PreviousError => {
+                self.main_split.previous_error();
+            }
             Quit => {}
         }
     }
@@ -1156,6 +1743,9 @@ pub fn run_internal_command(&self, cmd: InternalCommand) {
                     None,
                 );
             }
+            InternalCommand::CreateFile { path } => {
+                self.create_file(path);
+            }
             InternalCommand::OpenFileInNewTab { path } => {
                 self.main_split.jump_to_location(
                     EditorLocation {
@@ -1181,6 +1771,9 @@ pub fn run_internal_command(&self, cmd: InternalCommand) {
                 self.palette.references.set(references);
                 self.palette.run(PaletteKind::Reference);
             }
+            InternalCommand::PaletteWithInput { kind, input } => {
+                self.palette.run_with_input(kind, &input);
+            }
             InternalCommand::Split {
                 direction,
                 editor_tab_id,
@@ -1218,8 +1811,11 @@ pub fn run_internal_command(&self, cmd: InternalCommand) {
             InternalCommand::RunCodeAction { plugin_id, action } => {
                 self.main_split.run_code_action(plugin_id, action);
             }
-            InternalCommand::ApplyWorkspaceEdit { edit } => {
-                self.main_split.apply_workspace_edit(&edit);
+            InternalCommand::ApplyRenameEdit { edit } => {
+                self.main_split.apply_workspace_edit(&edit, "Rename", true);
+            }
+            InternalCommand::PathRenamed { from, to, edits } => {
+                self.main_split.path_renamed(&from, &to, edits);
             }
             InternalCommand::SaveJumpLocation {
                 path,
@@ -1244,6 +1840,10 @@ pub fn run_internal_command(&self, cmd: InternalCommand) {
             InternalCommand::RunAndDebug { mode, config } => {
                 self.run_and_debug(cx, &mode, &config);
             }
+            InternalCommand::RunTask { task } => {
+                self.task.run(task);
+                self.show_panel(PanelKind::Task);
+            }
             InternalCommand::StartRename {
                 path,
                 placeholder,
@@ -1252,6 +1852,15 @@ pub fn run_internal_command(&self, cmd: InternalCommand) {
             } => {
                 self.rename.start(path, placeholder, start, position);
             }
+            InternalCommand::ShowColorPicker { editor_id, literal } => {
+                let editor = self
+                    .main_split
+                    .editors
+                    .with_untracked(|editors| editors.get(&editor_id).copied());
+                if let Some(editor) = editor {
+                    self.color_picker.start(editor.get_untracked(), literal);
+                }
+            }
             InternalCommand::Search { pattern } => {
                 self.main_split.set_find_pattern(pattern);
             }
@@ -1292,7 +1901,7 @@ pub fn run_internal_command(&self, cmd: InternalCommand) {
                     );
                 } else {
                     let mut new_config = self.common.config.get().as_ref().clone();
-                    new_config.set_color_theme(&self.workspace, &name);
+                    new_config.set_color_theme(&name);
                     self.set_config.set(Arc::new(new_config));
                 }
             }
@@ -1306,7 +1915,7 @@ pub fn run_internal_command(&self, cmd: InternalCommand) {
                     );
                 } else {
                     let mut new_config = self.common.config.get().as_ref().clone();
-                    new_config.set_icon_theme(&self.workspace, &name);
+                    new_config.set_icon_theme(&name);
                     self.set_config.set(Arc::new(new_config));
                 }
             }
@@ -1339,9 +1948,34 @@ pub fn run_internal_command(&self, cmd: InternalCommand) {
             InternalCommand::HideAlert => {
                 self.alert_data.active.set(false);
             }
+            InternalCommand::ShowMessage { title, message } => {
+                self.show_message(&title, &message);
+            }
             InternalCommand::SaveScratchDoc { doc } => {
                 self.main_split.save_scratch_doc(doc);
             }
+            InternalCommand::OpenScratchBeside {
+                editor_tab_id,
+                text,
+                language,
+            } => {
+                self.main_split
+                    .open_scratch_beside(editor_tab_id, text, language);
+            }
+            InternalCommand::OpenFileBeside {
+                editor_tab_id,
+                path,
+            } => {
+                self.main_split.open_file_beside(editor_tab_id, path);
+            }
+            InternalCommand::PreviewWorkspaceEdit {
+                title,
+                files,
+                failures,
+            } => {
+                self.edit_preview.preview(title, files, failures);
+                self.show_panel(PanelKind::EditPreview);
+            }
             InternalCommand::UpdateProxyStatus { status } => {
                 self.common.proxy_status.set(Some(status));
             }
@@ -1381,6 +2015,26 @@ fn handle_core_notification(&self, rpc: &CoreNotification) {
                     doc.with_untracked(|doc| doc.retrieve_head());
                 }
             }
+            CoreNotification::SignatureHelpResponse {
+                request_id, resp, ..
+            } => {
+                let signature = &self.common.signature;
+                let editor_id = signature.editor_id.get_untracked();
+                let language = self
+                    .main_split
+                    .editors
+                    .with_untracked(|editors| editors.get(&editor_id).copied())
+                    .map(|editor| {
+                        let doc = editor.with_untracked(|editor| editor.view.doc);
+                        doc.with_untracked(|doc| doc.syntax().language)
+                    });
+                signature.receive(
+                    *request_id,
+                    resp,
+                    language,
+                    &self.common.config.get_untracked(),
+                );
+            }
             CoreNotification::CompletionResponse {
                 request_id,
                 input,
@@ -1410,6 +2064,19 @@ fn handle_core_notification(&self, rpc: &CoreNotification) {
             }
             CoreNotification::PublishDiagnostics { diagnostics } => {
                 let path = path_from_url(&diagnostics.uri);
+                let diagnostic_data = self.main_split.get_diagnostic_data(&path);
+                // A server that is behind may publish for a version it has
+                // since published newer diagnostics for
+                let latest_version = diagnostic_data.version.get_untracked();
+                if let (Some(version), Some(latest_version)) =
+                    (diagnostics.version, latest_version)
+                {
+                    if version < latest_version {
+                        return;
+                    }
+                }
+                diagnostic_data.version.set(diagnostics.version);
+
                 let diagnostics: im::Vector<EditorDiagnostic> = diagnostics
                     .diagnostics
                     .iter()
@@ -1420,10 +2087,7 @@ fn handle_core_notification(&self, rpc: &CoreNotification) {
                     .sorted_by_key(|d| d.diagnostic.range.start)
                     .collect();
 
-                self.main_split
-                    .get_diagnostic_data(&path)
-                    .diagnostics
-                    .set(diagnostics);
+                diagnostic_data.diagnostics.set(diagnostics);
 
                 // inform the document about the diagnostics
                 if let Some(doc) = self
@@ -1476,6 +2140,12 @@ fn handle_core_notification(&self, rpc: &CoreNotification) {
             CoreNotification::OpenFileChanged { path, content } => {
                 self.main_split.open_file_changed(path, content);
             }
+            CoreNotification::BufferOutOfSync { path } => {
+                self.main_split.resync_buffer(path);
+            }
+            CoreNotification::FileEvent { event } => {
+                self.main_split.handle_file_event(event.clone());
+            }
             CoreNotification::VoltInstalled { volt, icon } => {
                 self.plugin.volt_installed(volt, icon);
             }
@@ -1485,6 +2155,46 @@ fn handle_core_notification(&self, rpc: &CoreNotification) {
             CoreNotification::WorkDoneProgress { progress } => {
                 self.update_progress(progress);
             }
+            CoreNotification::LanguageServerStatus {
+                volt_id,
+                server,
+                status,
+            } => {
+                self.language_servers.update(|servers| {
+                    if let Some(info) = servers.get_mut(volt_id) {
+                        info.status = status.clone();
+                    } else if *status != LanguageServerStatus::Stopped {
+                        servers.insert(
+                            volt_id.clone(),
+                            LanguageServerInfo {
+                                server: server.clone(),
+                                status: status.clone(),
+                                log: im::Vector::new(),
+                            },
+                        );
+                    }
+                });
+            }
+            CoreNotification::LanguageServerLog { volt_id, line } => {
+                self.language_servers.update(|servers| {
+                    if let Some(info) = servers.get_mut(volt_id) {
+                        if info.log.len() >= LANGUAGE_SERVER_LOG_LINES {
+                            info.log.pop_front();
+                        }
+                        info.log.push_back(line.clone());
+                    }
+                });
+            }
+            CoreNotification::TaskOutput { task_id, line } => {
+                self.task.output(*task_id, line);
+            }
+            CoreNotification::TaskExited { task_id, exit } => {
+                self.task.exited(*task_id, exit.clone());
+            }
+            CoreNotification::ApplyWorkspaceEdit { edit } => {
+                self.main_split
+                    .apply_workspace_edit(edit, "Apply Edit", false);
+            }
             CoreNotification::ShowMessage { title, message } => {
                 self.show_message(title, message);
             }
@@ -1513,16 +2223,25 @@ fn handle_core_notification(&self, rpc: &CoreNotification) {
             CoreNotification::WorkspaceFileChange => {
                 self.file_explorer.reload();
             }
+            CoreNotification::FileIndexChanged { added, removed } => {
+                self.palette.update_files(added.clone(), removed.clone());
+            }
             _ => {}
         }
     }
 
     pub fn key_down<'a>(&self, event: impl Into<EventRef<'a>> + Copy) {
+        let _timer = perf::time(Phase::KeyDispatch);
+        let keypress = self.common.keypress.get_untracked();
         if self.alert_data.active.get_untracked() {
+            keypress.key_down(event, &self.alert_data);
+            return;
+        }
+        if self.resize_mode.get_untracked() {
+            self.resize_key_down(event.into());
             return;
         }
         let focus = self.common.focus.get_untracked();
-        let keypress = self.common.keypress.get_untracked();
         let executed = match focus {
             Focus::Workbench => self.main_split.key_down(event, &keypress).is_some(),
             Focus::Palette => {
@@ -1538,6 +2257,10 @@ pub fn key_down<'a>(&self, event: impl Into<EventRef<'a>> + Copy) {
                 keypress.key_down(event, &self.rename);
                 true
             }
+            Focus::ColorPicker => {
+                keypress.key_down(event, &self.color_picker);
+                true
+            }
             Focus::AboutPopup => {
                 keypress.key_down(event, &self.about_data);
                 true
@@ -1558,6 +2281,14 @@ pub fn key_down<'a>(&self, event: impl Into<EventRef<'a>> + Copy) {
                 keypress.key_down(event, &self.source_control);
                 true
             }
+            Focus::Panel(PanelKind::Problem) => {
+                keypress.key_down(event, &self.problem);
+                true
+            }
+            Focus::Panel(PanelKind::Outline) => {
+                keypress.key_down(event, &self.outline);
+                true
+            }
             _ => false,
         };
 
@@ -1585,180 +2316,150 @@ pub fn hover_origin(&self) -> Option<Point> {
             return None;
         }
 
-        let editor_id = self.common.hover.editor_id.get_untracked();
+        self.popup_origin(
+            self.common.hover.editor_id.get_untracked(),
+            self.common.hover.offset.get_untracked(),
+            self.common.hover.layout_rect.get().size(),
+        )
+    }
+
+    pub fn signature_origin(&self) -> Option<Point> {
+        if !self.common.signature.active.get_untracked() {
+            return None;
+        }
+
+        self.popup_origin(
+            self.common.signature.editor_id.get_untracked(),
+            self.common.signature.offset.get_untracked(),
+            self.common.signature.layout_rect.get().size(),
+        )
+    }
+
+    /// Where a popup of `size` goes to be above `offset` in the editor, or
+    /// below it if there's no room above.
+    fn popup_origin(
+        &self,
+        editor_id: EditorId,
+        offset: usize,
+        size: Size,
+    ) -> Option<Point> {
         let editor = self
             .main_split
             .editors
             .with(|editors| editors.get(&editor_id).copied())?;
+        let anchor = self.popup_anchor(editor, offset)?;
+        Some(popup::popup_origin(
+            anchor,
+            size,
+            self.popup_bounds(),
+            PopupSide::Above,
+        ))
+    }
 
+    /// The line of `offset` in `editor`, from the x of the offset, in the
+    /// coordinates of the window tab. It's `None` while the line is scrolled
+    /// out of the editor. It follows the scrolling of the editor and the edits
+    /// to its document.
+    fn popup_anchor(
+        &self,
+        editor: RwSignal<EditorData>,
+        offset: usize,
+    ) -> Option<Rect> {
         let (window_origin, viewport, view) =
             editor.with_untracked(|e| (e.window_origin, e.viewport, e.view.clone()));
 
-        let (point_above, point_below) =
-            view.points_of_offset(self.common.hover.offset.get_untracked());
+        let offset = view.doc.with(|doc| offset.min(doc.buffer().len()));
+        let (point_above, point_below) = view.points_of_offset(offset);
+        let viewport = viewport.get();
+        let anchor = Rect::from_points(point_above, point_below);
+        if !popup::anchor_in_view(anchor, viewport) {
+            return None;
+        }
 
         let window_origin =
             window_origin.get() - self.common.window_origin.get().to_vec2();
-        let viewport = viewport.get();
-        let hover_size = self.common.hover.layout_rect.get().size();
-        let tab_size = self.layout_rect.get().size();
-
-        let mut origin = window_origin
-            + Vec2::new(
-                point_below.x - viewport.x0,
-                (point_above.y - viewport.y0) - hover_size.height,
-            );
-        if origin.y < 0.0 {
-            origin.y = window_origin.y + point_below.y - viewport.y0;
-        }
-        if origin.x + hover_size.width + 1.0 > tab_size.width {
-            origin.x = tab_size.width - hover_size.width - 1.0;
-        }
-        if origin.x <= 0.0 {
-            origin.x = 0.0;
-        }
+        Some(anchor - viewport.origin().to_vec2() + window_origin.to_vec2())
+    }
 
-        Some(origin)
+    /// The area of the window tab the popups are kept in.
+    fn popup_bounds(&self) -> Rect {
+        self.layout_rect.get().size().to_rect()
     }
 
-    pub fn completion_origin(&self) -> Point {
+    pub fn completion_origin(&self) -> Option<Point> {
         let completion = self.common.completion.get();
         if completion.status == CompletionStatus::Inactive {
-            return Point::ZERO;
+            return None;
         }
         let config = self.common.config.get();
-        let editor =
-            if let Some(editor) = self.main_split.active_editor.get_untracked() {
-                editor
-            } else {
-                return Point::ZERO;
-            };
-
-        let (window_origin, viewport, view) =
-            editor.with_untracked(|e| (e.window_origin, e.viewport, e.view.clone()));
-
-        let (point_above, point_below) = view.points_of_offset(completion.offset);
-
-        let window_origin =
-            window_origin.get() - self.common.window_origin.get().to_vec2();
-        let viewport = viewport.get();
-        let completion_size = completion.layout_rect.size();
-        let tab_size = self.layout_rect.get().size();
-
-        let mut origin = window_origin
-            + Vec2::new(
-                point_below.x
-                    - viewport.x0
-                    - config.editor.line_height() as f64
-                    - 5.0,
-                point_below.y - viewport.y0,
-            );
-        if origin.y + completion_size.height > tab_size.height {
-            origin.y = window_origin.y + (point_above.y - viewport.y0)
-                - completion_size.height;
-        }
-        if origin.x + completion_size.width + 1.0 > tab_size.width {
-            origin.x = tab_size.width - completion_size.width - 1.0;
-        }
-        if origin.x <= 0.0 {
-            origin.x = 0.0;
-        }
-
-        origin
+        let editor = self.main_split.active_editor.get_untracked()?;
+        let anchor = self.popup_anchor(editor, completion.offset)?;
+        // The labels line up with the text, past the icons before them
+        let anchor =
+            anchor - Vec2::new(config.editor.line_height() as f64 + 5.0, 0.0);
+
+        Some(popup::popup_origin(
+            anchor,
+            completion.layout_rect.size(),
+            self.popup_bounds(),
+            PopupSide::Below,
+        ))
     }
 
-    pub fn code_action_origin(&self) -> Point {
+    pub fn code_action_origin(&self) -> Option<Point> {
         let code_action = self.code_action.get();
-        let config = self.common.config.get();
         if code_action.status.get_untracked() == CodeActionStatus::Inactive {
-            return Point::ZERO;
-        }
-
-        let tab_size = self.layout_rect.get().size();
-        let code_action_size = code_action.layout_rect.size();
-
-        let editor =
-            if let Some(editor) = self.main_split.active_editor.get_untracked() {
-                editor
-            } else {
-                return Point::ZERO;
-            };
-
-        let (window_origin, viewport, view) =
-            editor.with_untracked(|e| (e.window_origin, e.viewport, e.view.clone()));
-
-        let (_point_above, point_below) = view.points_of_offset(code_action.offset);
-
-        let window_origin =
-            window_origin.get() - self.common.window_origin.get().to_vec2();
-        let viewport = viewport.get();
-
-        let mut origin = window_origin
-            + Vec2::new(
-                if code_action.mouse_click {
-                    0.0
-                } else {
-                    point_below.x - viewport.x0
-                },
-                point_below.y - viewport.y0,
-            );
-
-        if origin.y + code_action_size.height > tab_size.height {
-            origin.y = origin.y
-                - config.editor.line_height() as f64
-                - code_action_size.height;
-        }
-        if origin.x + code_action_size.width + 1.0 > tab_size.width {
-            origin.x = tab_size.width - code_action_size.width - 1.0;
+            return None;
         }
-        if origin.x <= 0.0 {
-            origin.x = 0.0;
+        let editor = self.main_split.active_editor.get_untracked()?;
+        let mut anchor = self.popup_anchor(editor, code_action.offset)?;
+        if code_action.mouse_click {
+            // From the lightbulb, at the start of the editor
+            let editor_x = editor.with_untracked(|e| e.window_origin).get().x
+                - self.common.window_origin.get().x;
+            anchor = anchor.with_origin(Point::new(editor_x, anchor.y0));
         }
 
-        origin
+        Some(popup::popup_origin(
+            anchor,
+            code_action.layout_rect.size(),
+            self.popup_bounds(),
+            PopupSide::Below,
+        ))
     }
 
-    pub fn rename_origin(&self) -> Point {
-        let config = self.common.config.get();
+    pub fn rename_origin(&self) -> Option<Point> {
         if !self.rename.active.get() {
-            return Point::ZERO;
+            return None;
         }
+        let editor = self.main_split.active_editor.get_untracked()?;
+        let anchor = self.popup_anchor(editor, self.rename.start.get_untracked())?;
+
+        Some(popup::popup_origin(
+            anchor,
+            self.rename.layout_rect.get().size(),
+            self.popup_bounds(),
+            PopupSide::Below,
+        ))
+    }
 
-        let tab_size = self.layout_rect.get().size();
-        let rename_size = self.rename.layout_rect.get().size();
-
-        let editor =
-            if let Some(editor) = self.main_split.active_editor.get_untracked() {
-                editor
-            } else {
-                return Point::ZERO;
-            };
-
-        let (window_origin, viewport, view) =
-            editor.with_untracked(|e| (e.window_origin, e.viewport, e.view.clone()));
-
-        let (_point_above, point_below) =
-            view.points_of_offset(self.rename.start.get_untracked());
-
-        let window_origin =
-            window_origin.get() - self.common.window_origin.get().to_vec2();
-        let viewport = viewport.get();
-
-        let mut origin = window_origin
-            + Vec2::new(point_below.x - viewport.x0, point_below.y - viewport.y0);
-
-        if origin.y + rename_size.height > tab_size.height {
-            origin.y =
-                origin.y - config.editor.line_height() as f64 - rename_size.height;
-        }
-        if origin.x + rename_size.width + 1.0 > tab_size.width {
-            origin.x = tab_size.width - rename_size.width - 1.0;
-        }
-        if origin.x <= 0.0 {
-            origin.x = 0.0;
+    pub fn color_picker_origin(&self) -> Option<Point> {
+        if !self.color_picker.active.get() {
+            return None;
         }
-
-        origin
+        let (editor, literal) = self.color_picker.target.get_untracked()?;
+        let editor = self
+            .main_split
+            .editors
+            .with_untracked(|editors| editors.get(&editor.editor_id).copied())?;
+        let anchor = self.popup_anchor(editor, literal.start)?;
+
+        Some(popup::popup_origin(
+            anchor,
+            self.color_picker.layout_rect.get().size(),
+            self.popup_bounds(),
+            PopupSide::Below,
+        ))
     }
 
     /// Get the mode for the current editor or terminal
@@ -1778,6 +2479,23 @@ pub fn mode(&self) -> Mode {
         }
     }
 
+    /// What's been typed so far of a command that's still to be completed,
+    /// with the operator of the active editor that's waiting for its motion.
+    pub fn pending_state(&self) -> PendingState {
+        let operator = if self.common.focus.get() == Focus::Workbench {
+            self.main_split.active_editor.get().and_then(|editor| {
+                editor
+                    .with_untracked(|editor| editor.cursor)
+                    .with(|cursor| cursor.motion_mode.clone())
+            })
+        } else {
+            None
+        };
+        self.common
+            .keypress
+            .with(|keypress| keypress.pending_state(operator))
+    }
+
     pub fn toggle_panel_visual(&self, kind: PanelKind) {
         if self.panel.is_panel_visible(&kind) {
             self.hide_panel(kind);
@@ -1792,14 +2510,17 @@ fn toggle_panel_focus(&self, kind: PanelKind) {
             PanelKind::FileExplorer
             | PanelKind::Plugin
             | PanelKind::Problem
-            | PanelKind::Debug => {
+            | PanelKind::Debug
+            | PanelKind::EditPreview
+            | PanelKind::Task => {
                 // Some panels don't accept focus (yet). Fall back to visibility check
                 // in those cases.
                 self.panel.is_panel_visible(&kind)
             }
-            PanelKind::Terminal | PanelKind::SourceControl | PanelKind::Search => {
-                self.is_panel_focused(kind)
-            }
+            PanelKind::Terminal
+            | PanelKind::SourceControl
+            | PanelKind::Search
+            | PanelKind::Outline => self.is_panel_focused(kind),
         };
         if should_hide {
             self.hide_panel(kind);
@@ -1845,6 +2566,20 @@ fn toggle_container_visual(&self, position: &PanelContainerPosition) {
         }
     }
 
+    /// Show and focus the active panel on one of the sides.
+    fn focus_container(&self, position: &PanelContainerPosition) {
+        let kind = self
+            .panel
+            .active_panel_at_position(&position.first(), false)
+            .or_else(|| {
+                self.panel
+                    .active_panel_at_position(&position.second(), false)
+            });
+        if let Some((kind, _)) = kind {
+            self.show_panel(kind);
+        }
+    }
+
     fn is_panel_focused(&self, kind: PanelKind) -> bool {
         // Moving between e.g. Search and Problems doesn't affect focus, so we need to also check
         // visibility.
@@ -1941,6 +2676,137 @@ fn run_in_terminal(
         }
     }
 
+    /// Create the file, with the folders it's in, then show it in the file
+    /// explorer and open it.
+    fn create_file(&self, path: PathBuf) {
+        let file_explorer = self.file_explorer.clone();
+        let internal_command = self.common.internal_command;
+        let opened = path.clone();
+        let send = create_ext_action(
+            self.common.scope,
+            move |result: Result<ProxyResponse, RpcError>| match result {
+                Ok(_) => {
+                    file_explorer.reload();
+                    internal_command
+                        .send(InternalCommand::OpenFile { path: opened });
+                }
+                Err(err) => {
+                    internal_command.send(InternalCommand::ShowAlert {
+                        title: "Couldn't create the file".to_string(),
+                        msg: err.message,
+                        buttons: Vec::new(),
+                    });
+                }
+            },
+        );
+        self.common
+            .proxy
+            .create_file(path, move |result| send(result));
+    }
+
+    /// Resize the focused panel, or else the active editor tab, by the key
+    /// of `event` in the resize mode, or leave it on Enter or Escape. The
+    /// other keys do nothing in it.
+    fn resize_key_down(&self, event: EventRef) {
+        let EventRef::Keyboard(event) = event else {
+            return;
+        };
+        let Some(resize) =
+            ResizeKey::from_key(&event.key.logical_key, event.modifiers.shift_key())
+        else {
+            return;
+        };
+        let (width, delta) = match resize {
+            ResizeKey::Width(delta) => (true, delta),
+            ResizeKey::Height(delta) => (false, delta),
+            ResizeKey::Done => {
+                self.resize_mode.set(false);
+                return;
+            }
+        };
+        if let Focus::Panel(kind) = self.common.focus.get_untracked() {
+            let root_split = self.main_split.root_split;
+            let rect = self
+                .main_split
+                .splits
+                .with_untracked(|splits| splits.get(&root_split).copied())
+                .map(|split| split.with_untracked(|split| split.layout_rect))
+                .unwrap_or(Rect::ZERO);
+            let room = if width { rect.width() } else { rect.height() };
+            let room = (room - MIN_EDITOR_TAB_SIZE).max(0.0);
+            self.panel.resize(kind, width, delta, room);
+        } else {
+            self.main_split.resize_active_editor_tab(width, delta);
+        }
+    }
+
+    /// Show how the config directory was set up as Lapce started: the
+    /// welcome on the first run, or why nothing is saved when it couldn't be.
+    pub fn show_config_directory(&self, config_directory: &ConfigDirectory) {
+        match config_directory {
+            ConfigDirectory::Ready { first_run: true } => {
+                let text = self.common.keypress.with_untracked(|keypress| {
+                    welcome_text(|command| {
+                        let keymap =
+                            keypress.command_keymaps.get(command)?.first()?;
+                        Some(keymap.key.iter().map(|key| key.label()).join(" "))
+                    })
+                });
+                self.main_split.open_welcome(&text);
+            }
+            ConfigDirectory::Ready { first_run: false } => {}
+            ConfigDirectory::Unavailable(reason) => {
+                self.show_message(
+                    "Settings Aren't Saved",
+                    &ShowMessageParams {
+                        typ: MessageType::WARNING,
+                        message: format!(
+                            "{reason} Lapce runs on the default settings, and \
+                             nothing it remembers, like the recent workspaces \
+                             and the open files, is saved."
+                        ),
+                    },
+                );
+            }
+        }
+    }
+
+    /// Restore what was backed up as Lapce panicked the last time it ran, and
+    /// point at the crash log.
+    pub fn show_crash_report(&self, report: &CrashReport) {
+        for (path, text) in &report.backups {
+            self.main_split.restore_backup(path.clone(), text.clone());
+        }
+        let restored = match report.backups.len() {
+            0 => String::new(),
+            1 => " The file with unsaved changes was restored.".to_string(),
+            n => format!(" The {n} files with unsaved changes were restored."),
+        };
+        let changed = report
+            .changed
+            .iter()
+            .map(|path| {
+                format!(
+                    " The unsaved changes of {} weren't restored, as it changed \
+                     on disk since.",
+                    path.display()
+                )
+            })
+            .collect::<String>();
+        self.show_message_with_details(
+            "Lapce Crashed",
+            &ShowMessageParams {
+                typ: MessageType::ERROR,
+                message: format!(
+                    "Lapce crashed the last time it ran.{restored}{changed} \
+                     What happened is written to {}.",
+                    report.log.display()
+                ),
+            },
+            Some(report.details.clone()),
+        );
+    }
+
     pub fn open_paths(&self, paths: &[PathObject]) {
         let (folders, files): (Vec<&PathObject>, Vec<&PathObject>) =
             paths.iter().partition(|p| p.is_dir);
@@ -1982,10 +2848,57 @@ pub fn open_paths(&self, paths: &[PathObject]) {
         }
     }
 
+    /// The window title, `file ● - workspace - Lapce` with the dot only when
+    /// the focused file has unsaved changes. It tracks the signals it reads, so
+    /// it is recomputed whenever the focused editor, its document or the
+    /// workspace changes.
+    pub fn title(&self) -> String {
+        /// File names longer than this lose their start
+        const MAX_FILE_NAME: usize = 60;
+
+        let mut parts = Vec::new();
+        let file = self.main_split.active_editor.get().and_then(|editor| {
+            let doc = editor.with(|editor| editor.view.doc);
+            doc.with(|doc| {
+                let name = match &doc.content {
+                    DocContent::File(path) => path
+                        .file_name()
+                        .unwrap_or(path.as_os_str())
+                        .to_string_lossy()
+                        .to_string(),
+                    DocContent::Scratch { name, .. } => name.clone(),
+                    DocContent::Local | DocContent::History(_) => return None,
+                };
+                Some((name, doc.buffer().is_pristine()))
+            })
+        });
+        if let Some((name, is_pristine)) = file {
+            let count = name.chars().count();
+            let name = if count > MAX_FILE_NAME {
+                let rest: String =
+                    name.chars().skip(count - MAX_FILE_NAME + 1).collect();
+                format!("…{rest}")
+            } else {
+                name
+            };
+            if is_pristine {
+                parts.push(name);
+            } else {
+                parts.push(format!("{name} ●"));
+            }
+        }
+        if let Some(workspace) = self.workspace.display() {
+            parts.push(workspace);
+        }
+        parts.push("Lapce".to_string());
+        parts.join(" - ")
+    }
+
     pub fn show_alert(&self, title: String, msg: String, buttons: Vec<AlertButton>) {
         self.alert_data.title.set(title);
         self.alert_data.msg.set(msg);
         self.alert_data.buttons.set(buttons);
+        self.alert_data.selected.set(0);
         self.alert_data.active.set(true);
     }
 
@@ -2022,8 +2935,46 @@ fn update_progress(&self, progress: &ProgressParams) {
     }
 
     fn show_message(&self, title: &str, message: &ShowMessageParams) {
+        self.show_message_with_details(title, message, None);
+    }
+
+    fn show_message_with_details(
+        &self,
+        title: &str,
+        message: &ShowMessageParams,
+        details: Option<String>,
+    ) {
+        // Errors and warnings stay until they're closed
+        let timer = if message.typ == MessageType::ERROR
+            || message.typ == MessageType::WARNING
+        {
+            TimerToken::INVALID
+        } else {
+            let messages = self.messages;
+            exec_after(Duration::from_secs(5), move |token| {
+                messages.update(|messages| {
+                    messages.retain(|message| message.timer != token);
+                });
+            })
+        };
+
         self.messages.update(|messages| {
-            messages.push((title.to_string(), message.clone()));
+            if let Some(shown) = messages
+                .iter_mut()
+                .find(|shown| shown.title == title && shown.message == *message)
+            {
+                // Showing the message again keeps it open for longer
+                shown.count += 1;
+                shown.timer = timer;
+            } else {
+                messages.push(WindowMessage {
+                    title: title.to_string(),
+                    message: message.clone(),
+                    count: 1,
+                    details,
+                    timer,
+                });
+            }
         });
     }
 }
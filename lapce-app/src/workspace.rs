@@ -1,5 +1,9 @@
-use std::{fmt::Display, path::PathBuf};
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
 
+use lapce_core::directory::Directory;
 use serde::{Deserialize, Serialize};
 
 use crate::{main_split::SplitInfo, panel::data::PanelInfo};
@@ -113,6 +117,26 @@ pub fn display(&self) -> Option<String> {
         };
         Some(format!("{path}{remote}"))
     }
+
+    /// The path to show for `path`, which is relative to the workspace folder
+    /// if it's in there, or else to the home folder as `~/...` when local.
+    pub fn display_path(&self, path: &Path) -> PathBuf {
+        if let Some(relative) = self
+            .path
+            .as_ref()
+            .and_then(|workspace_path| path.strip_prefix(workspace_path).ok())
+        {
+            return relative.to_path_buf();
+        }
+        if self.kind.is_local() {
+            if let Some(home) = Directory::home_dir() {
+                if let Ok(relative) = path.strip_prefix(home) {
+                    return Path::new("~").join(relative);
+                }
+            }
+        }
+        path.to_path_buf()
+    }
 }
 
 impl Default for LapceWorkspace {
@@ -0,0 +1,201 @@
+//! The edits made along with what's typed in prose and markup: the markers of
+//! Markdown lists and quotes carried on to new lines, and the closing tags of
+//! HTML and XML elements.
+
+/// The elements of HTML that have no closing tag
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta",
+    "param", "source", "track", "wbr",
+];
+
+/// What a new line after a list item or a quote starts with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListContinuation {
+    /// The item has text, so the new line is a new item, starting with this
+    /// indent and marker
+    Continue(String),
+    /// The item is empty, so the list ends, with the marker taken away
+    End,
+}
+
+/// How a line goes on to a new line when it's split with `before` and `after`
+/// on either side of the cursor, if it's a list item or a quote. The markers
+/// are `- `, `* `, `+ `, numbers like `1. ` or `1) ` which count up, and `> `
+/// for quotes, which can be nested or have a list in them. The boxes of task
+/// lists start unchecked.
+pub fn list_continuation(before: &str, after: &str) -> Option<ListContinuation> {
+    let indent_len = before.len() - before.trim_start_matches([' ', '\t']).len();
+    let (indent, mut rest) = before.split_at(indent_len);
+    let mut prefix = indent.to_string();
+    let mut found = false;
+
+    while let Some(after) = rest.strip_prefix('>') {
+        let after_space = after.strip_prefix(' ').unwrap_or(after);
+        prefix.push_str("> ");
+        rest = after_space;
+        found = true;
+    }
+
+    if let Some((marker, after)) = list_marker(rest) {
+        prefix.push_str(&marker);
+        rest = after;
+        found = true;
+        for task in ["[ ] ", "[x] ", "[X] "] {
+            if let Some(after) = rest.strip_prefix(task) {
+                prefix.push_str("[ ] ");
+                rest = after;
+                break;
+            }
+        }
+    }
+
+    if !found {
+        return None;
+    }
+    if rest.trim().is_empty() && after.trim().is_empty() {
+        Some(ListContinuation::End)
+    } else {
+        Some(ListContinuation::Continue(prefix))
+    }
+}
+
+/// The marker of the next item of a list that `text` starts with an item
+/// of, with the spacing after it, and the text after the marker.
+fn list_marker(text: &str) -> Option<(String, &str)> {
+    let mut chars = text.char_indices();
+    let (_, first) = chars.next()?;
+    let (marker, after) = if matches!(first, '-' | '*' | '+') {
+        (first.to_string(), &text[1..])
+    } else {
+        let digits =
+            text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        if digits == 0 || digits > 9 {
+            return None;
+        }
+        let number: u64 = text[..digits].parse().ok()?;
+        let delimiter = text[digits..].chars().next()?;
+        if !matches!(delimiter, '.' | ')') {
+            return None;
+        }
+        (format!("{}{delimiter}", number + 1), &text[digits + 1..])
+    };
+    let spacing = after.len() - after.trim_start_matches([' ', '\t']).len();
+    // A marker needs space after it, unless it's all there is on the line
+    if spacing == 0 && !after.is_empty() {
+        return None;
+    }
+    let spacing = if spacing == 0 { " " } else { &after[..spacing] };
+    Some((
+        format!("{marker}{spacing}"),
+        &after[spacing.len().min(after.len())..],
+    ))
+}
+
+/// The closing tag of the element whose opening tag `text` ends with, right
+/// after its `>` was typed, like `</div>` for `<div class="a">`. Closing
+/// tags, self-closing ones, comments, declarations, and the elements of HTML
+/// without a closing tag have none, and neither does a `<` that follows a
+/// word, like the one of `Vec<T>`.
+pub fn closing_tag(text: &str) -> Option<String> {
+    let before = text.strip_suffix('>')?;
+    let start = before.rfind('<')?;
+    if before[..start]
+        .chars()
+        .next_back()
+        .map_or(false, |c| c.is_alphanumeric() || c == '_')
+    {
+        return None;
+    }
+    let tag = &before[start + 1..];
+    // A `>` in a quoted attribute doesn't end the tag
+    let mut quote = None;
+    for c in tag.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            None if matches!(c, '"' | '\'') => quote = Some(c),
+            _ => {}
+        }
+    }
+    if quote.is_some() {
+        return None;
+    }
+    if tag.ends_with('/') {
+        return None;
+    }
+    let name_len = tag
+        .find(|c: char| !(c.is_alphanumeric() || matches!(c, '-' | '_' | ':' | '.')))
+        .unwrap_or(tag.len());
+    let name = &tag[..name_len];
+    if !name.chars().next().map_or(false, char::is_alphabetic) {
+        return None;
+    }
+    if VOID_ELEMENTS.contains(&name.to_ascii_lowercase().as_str()) {
+        return None;
+    }
+    // The name is followed by the attributes, after some space
+    if !tag[name_len..]
+        .chars()
+        .next()
+        .map_or(true, char::is_whitespace)
+    {
+        return None;
+    }
+    Some(format!("</{name}>"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_continuation() {
+        let cont = |line: &str| match list_continuation(line, "") {
+            Some(ListContinuation::Continue(prefix)) => Some(prefix),
+            _ => None,
+        };
+        assert_eq!(cont("- item").as_deref(), Some("- "));
+        assert_eq!(cont("  * item").as_deref(), Some("  * "));
+        assert_eq!(cont("9. item").as_deref(), Some("10. "));
+        assert_eq!(cont("1)  item").as_deref(), Some("2)  "));
+        assert_eq!(cont("> quote").as_deref(), Some("> "));
+        assert_eq!(cont(">> - item").as_deref(), Some("> > - "));
+        assert_eq!(cont("- [x] done").as_deref(), Some("- [ ] "));
+
+        assert_eq!(list_continuation("- ", ""), Some(ListContinuation::End));
+        assert_eq!(list_continuation("  3. ", ""), Some(ListContinuation::End));
+        assert_eq!(list_continuation("> ", ""), Some(ListContinuation::End));
+        assert_eq!(list_continuation("- [ ] ", ""), Some(ListContinuation::End));
+        assert_eq!(
+            list_continuation("- ", "item"),
+            Some(ListContinuation::Continue("- ".to_string()))
+        );
+
+        assert_eq!(list_continuation("text", ""), None);
+        assert_eq!(list_continuation("**bold**", ""), None);
+        assert_eq!(list_continuation("---", ""), None);
+        assert_eq!(list_continuation("2024.01", ""), None);
+        assert_eq!(list_continuation("", ""), None);
+    }
+
+    #[test]
+    fn test_closing_tag() {
+        assert_eq!(closing_tag("<div>").as_deref(), Some("</div>"));
+        assert_eq!(
+            closing_tag(r#"  <a href="/x" title='a>b'>"#).as_deref(),
+            Some("</a>")
+        );
+        assert_eq!(closing_tag("<svg:rect>").as_deref(), Some("</svg:rect>"));
+        assert_eq!(closing_tag("<Foo.Bar>").as_deref(), Some("</Foo.Bar>"));
+        assert_eq!(closing_tag(r#"<p title="don't">"#).as_deref(), Some("</p>"));
+
+        assert_eq!(closing_tag("</div>"), None);
+        assert_eq!(closing_tag("<br/>"), None);
+        assert_eq!(closing_tag("<img src=\"a\">"), None);
+        assert_eq!(closing_tag("<!DOCTYPE html>"), None);
+        assert_eq!(closing_tag("<?xml version=\"1.0\"?>"), None);
+        assert_eq!(closing_tag("Vec<T>"), None);
+        assert_eq!(closing_tag("<a title=\"x>"), None);
+        assert_eq!(closing_tag("a > b"), None);
+        assert_eq!(closing_tag("<>"), None);
+    }
+}
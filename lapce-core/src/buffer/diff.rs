@@ -21,6 +21,27 @@ pub struct DiffBothInfo {
     pub left: Range<usize>,
     pub right: Range<usize>,
     pub skip: Option<Range<usize>>,
+    /// The lines of the run, from its start, that compared the same on both
+    /// sides but aren't quite, with how they differ
+    pub differences: Vec<(usize, LineDifference)>,
+}
+
+/// How a line of a [`DiffLines::Both`] run differs from its counterpart
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineDifference {
+    /// Only in whitespace, which was ignored by [`DiffOptions`]
+    Whitespace,
+    /// Only in its line ending, like a `\r\n` against a `\n`, or the last
+    /// line having one on only one side
+    LineEnding,
+}
+
+/// How the lines of the two texts of a diff are compared
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DiffOptions {
+    /// Lines that differ only in the amount of whitespace in them, like
+    /// their indentation or trailing spaces, count as the same
+    pub ignore_whitespace: bool,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -82,9 +103,10 @@ pub fn rope_diff(
     rev: u64,
     atomic_rev: Arc<AtomicU64>,
     context_lines: Option<usize>,
+    options: DiffOptions,
 ) -> Option<Vec<DiffLines>> {
-    let left_lines = left_rope.lines(..).collect::<Vec<Cow<str>>>();
-    let right_lines = right_rope.lines(..).collect::<Vec<Cow<str>>>();
+    let left_lines = compared_lines(&left_rope, options);
+    let right_lines = compared_lines(&right_rope, options);
 
     let left_count = left_lines.len();
     let right_count = right_lines.len();
@@ -167,6 +189,7 @@ pub fn rope_diff(
             left: 0..leading_equals,
             right: 0..leading_equals,
             skip: None,
+            differences: Vec::new(),
         }))
     }
     left_line += leading_equals;
@@ -194,6 +217,7 @@ pub fn rope_diff(
                         left: left_line..left_line + 1,
                         right: right_line..right_line + 1,
                         skip: None,
+                        differences: Vec::new(),
                     })),
                 }
                 left_line += 1;
@@ -214,8 +238,38 @@ pub fn rope_diff(
             left: left_count - trailing_equals..left_count,
             right: right_count - trailing_equals..right_count,
             skip: None,
+            differences: Vec::new(),
         }));
     }
+
+    let left_raw = left_rope.lines_raw(..).collect::<Vec<Cow<str>>>();
+    let right_raw = right_rope.lines_raw(..).collect::<Vec<Cow<str>>>();
+    for change in changes.iter_mut() {
+        if atomic_rev.load(atomic::Ordering::Acquire) != rev {
+            return None;
+        }
+        if let DiffLines::Both(info) = change {
+            for (i, (left, right)) in left_raw[info.left.clone()]
+                .iter()
+                .zip(&right_raw[info.right.clone()])
+                .enumerate()
+            {
+                if left == right {
+                    continue;
+                }
+                let ending = ['\r', '\n'];
+                let difference = if left.trim_end_matches(ending)
+                    == right.trim_end_matches(ending)
+                {
+                    LineDifference::LineEnding
+                } else {
+                    LineDifference::Whitespace
+                };
+                info.differences.push((i, difference));
+            }
+        }
+    }
+
     if let Some(context_lines) = context_lines {
         if !changes.is_empty() {
             let changes_last = changes.len() - 1;
@@ -244,3 +298,267 @@ pub fn rope_diff(
 
     Some(changes)
 }
+
+/// The lines of `rope` as they're compared by a diff with `options`.
+fn compared_lines(rope: &Rope, options: DiffOptions) -> Vec<Cow<'_, str>> {
+    rope.lines(..)
+        .map(|line| {
+            if options.ignore_whitespace {
+                Cow::Owned(line.split_whitespace().collect::<Vec<_>>().join(" "))
+            } else {
+                line
+            }
+        })
+        .collect()
+}
+
+/// A run of changed lines, with the lines it covers on both sides. One of
+/// the ranges is empty for lines that were only added or only removed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiffHunk {
+    pub left: Range<usize>,
+    pub right: Range<usize>,
+}
+
+/// Group the changes of a diff into hunks, so that removed lines directly
+/// followed by added lines count as one modification.
+pub fn diff_hunks<'a>(
+    diff_lines: impl IntoIterator<Item = &'a DiffLines>,
+) -> Vec<DiffHunk> {
+    let mut hunks = Vec::new();
+    let mut current: Option<DiffHunk> = None;
+    let mut left_line = 0;
+    let mut right_line = 0;
+    for diff_line in diff_lines {
+        match diff_line {
+            DiffLines::Both(info) => {
+                hunks.extend(current.take());
+                left_line = info.left.end;
+                right_line = info.right.end;
+            }
+            DiffLines::Left(range) => {
+                let hunk = current.get_or_insert(DiffHunk {
+                    left: range.start..range.start,
+                    right: right_line..right_line,
+                });
+                hunk.left.end = range.end;
+                left_line = range.end;
+            }
+            DiffLines::Right(range) => {
+                let hunk = current.get_or_insert(DiffHunk {
+                    left: left_line..left_line,
+                    right: range.start..range.start,
+                });
+                hunk.right.end = range.end;
+                right_line = range.end;
+            }
+        }
+    }
+    hunks.extend(current);
+    hunks
+}
+
+/// The line of the right text that `line` of the left text ends up at, going
+/// by the `hunks` of their diff. A changed line goes to the line at the same
+/// place in what it was changed to, or to its last line when that's shorter.
+pub fn map_line(hunks: &[DiffHunk], line: usize) -> usize {
+    let mut right_line = line;
+    for hunk in hunks {
+        if line < hunk.left.start {
+            break;
+        }
+        if line < hunk.left.end {
+            let offset = line - hunk.left.start;
+            return hunk.right.start
+                + offset.min(hunk.right.len().saturating_sub(1));
+        }
+        right_line = line + hunk.right.end - hunk.left.end;
+    }
+    right_line
+}
+
+/// Move the right side of a diff along with an edit of the right text that
+/// replaced `inval_count` lines starting at `line` with `new_count` lines,
+/// so the changes stay on the right lines until the diff is computed again.
+pub fn shift_diff_lines<'a>(
+    diff_lines: impl IntoIterator<Item = &'a mut DiffLines>,
+    line: usize,
+    inval_count: usize,
+    new_count: usize,
+) {
+    let shift = |n: usize| (n + new_count).saturating_sub(inval_count);
+    let mut shifted = false;
+    for diff_line in diff_lines {
+        let range = match diff_line {
+            DiffLines::Left(_) => continue,
+            DiffLines::Both(info) => &mut info.right,
+            DiffLines::Right(range) => range,
+        };
+        if shifted {
+            range.start = shift(range.start);
+            range.end = shift(range.end).max(range.start);
+        } else if line < range.end {
+            range.end = shift(range.end).max(range.start);
+            shifted = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic::AtomicU64, Arc};
+
+    use lapce_xi_rope::Rope;
+
+    use super::{
+        diff_hunks, map_line, rope_diff, shift_diff_lines, DiffBothInfo, DiffHunk,
+        DiffLines, DiffOptions, LineDifference,
+    };
+
+    fn both(
+        left: std::ops::Range<usize>,
+        right: std::ops::Range<usize>,
+    ) -> DiffLines {
+        DiffLines::Both(DiffBothInfo {
+            left,
+            right,
+            skip: None,
+            differences: Vec::new(),
+        })
+    }
+
+    #[test]
+    fn test_diff_hunks() {
+        let changes = vec![
+            both(0..2, 0..2),
+            DiffLines::Left(2..3),
+            DiffLines::Right(2..4),
+            both(3..5, 4..6),
+            DiffLines::Left(5..6),
+            both(6..7, 6..7),
+            DiffLines::Right(7..8),
+        ];
+        assert_eq!(
+            diff_hunks(&changes),
+            vec![
+                DiffHunk {
+                    left: 2..3,
+                    right: 2..4
+                },
+                DiffHunk {
+                    left: 5..6,
+                    right: 6..6
+                },
+                DiffHunk {
+                    left: 7..7,
+                    right: 7..8
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_map_line() {
+        let hunks = [
+            DiffHunk {
+                left: 2..3,
+                right: 2..4,
+            },
+            DiffHunk {
+                left: 5..7,
+                right: 6..6,
+            },
+            DiffHunk {
+                left: 8..8,
+                right: 7..9,
+            },
+        ];
+        let mapped = (0..10)
+            .map(|line| map_line(&hunks, line))
+            .collect::<Vec<_>>();
+        assert_eq!(mapped, [0, 1, 2, 4, 5, 6, 6, 6, 9, 10]);
+    }
+
+    #[test]
+    fn test_shift_diff_lines() {
+        let mut changes = vec![
+            both(0..2, 0..2),
+            DiffLines::Right(2..3),
+            DiffLines::Left(2..3),
+            both(3..5, 3..5),
+        ];
+        // Two new lines typed in on line 1
+        shift_diff_lines(&mut changes, 1, 1, 3);
+        assert_eq!(
+            changes,
+            vec![
+                both(0..2, 0..4),
+                DiffLines::Right(4..5),
+                DiffLines::Left(2..3),
+                both(3..5, 5..7),
+            ]
+        );
+
+        // The added line deleted again
+        shift_diff_lines(&mut changes, 4, 2, 1);
+        assert_eq!(
+            changes,
+            vec![
+                both(0..2, 0..4),
+                DiffLines::Right(4..4),
+                DiffLines::Left(2..3),
+                both(3..5, 4..6),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rope_diff_options() {
+        let diff = |left: &str, right: &str, ignore_whitespace: bool| {
+            rope_diff(
+                Rope::from(left),
+                Rope::from(right),
+                0,
+                Arc::new(AtomicU64::new(0)),
+                None,
+                DiffOptions { ignore_whitespace },
+            )
+            .unwrap()
+        };
+        let left = "a\r\n  b c\nd\n";
+        let right = "a\n    b  c \nd";
+
+        let changes = diff(left, right, false);
+        assert_eq!(
+            diff_hunks(&changes),
+            vec![DiffHunk {
+                left: 1..2,
+                right: 1..2
+            }]
+        );
+        let DiffLines::Both(info) = &changes[0] else {
+            panic!()
+        };
+        assert_eq!(info.differences, vec![(0, LineDifference::LineEnding)]);
+
+        let changes = diff(left, right, true);
+        assert!(diff_hunks(&changes).is_empty());
+        let differences = changes
+            .iter()
+            .flat_map(|change| match change {
+                DiffLines::Both(info) => info.differences.clone(),
+                _ => Vec::new(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            differences,
+            vec![
+                (0, LineDifference::LineEnding),
+                (1, LineDifference::Whitespace),
+                (2, LineDifference::LineEnding),
+            ]
+        );
+
+        assert_eq!(diff_hunks(&diff("a b\n", "ab\n", true)).len(), 1);
+    }
+}
@@ -20,6 +20,7 @@
     cursor::CursorMode,
     editor::EditType,
     indent::{auto_detect_indent_style, IndentStyle},
+    language_config::LanguageConfig,
     mode::Mode,
     selection::Selection,
     syntax::{self, edit::SyntaxEdit, Syntax},
@@ -90,6 +91,7 @@ pub struct Buffer {
     last_edit_type: EditType,
 
     indent_style: IndentStyle,
+    language_config: Arc<LanguageConfig>,
 
     max_len: usize,
     max_len_line: usize,
@@ -131,6 +133,7 @@ pub fn new(text: &str) -> Self {
             this_edit_type: EditType::Other,
             last_edit_type: EditType::Other,
             indent_style: IndentStyle::DEFAULT_INDENT,
+            language_config: Arc::new(LanguageConfig::default()),
 
             max_len: 0,
             max_len_line: 0,
@@ -147,6 +150,13 @@ pub fn set_pristine(&mut self) {
         self.pristine_rev_id = self.rev();
     }
 
+    /// Mark the buffer as having changes that aren't saved, whatever its
+    /// revision, like when its file was deleted
+    pub fn set_dirty(&mut self) {
+        // No revision has this number, so none of them is the saved one
+        self.pristine_rev_id = u64::MAX;
+    }
+
     pub fn is_pristine(&self) -> bool {
         self.is_equivalent_revision(self.pristine_rev_id, self.rev())
     }
@@ -267,15 +277,38 @@ pub fn reload(
         (delta, inval_lines, edits)
     }
 
-    pub fn detect_indent(&mut self, syntax: &Syntax) {
+    /// Detect the indentation used by the text, falling back to the one of the
+    /// buffer's language config.
+    pub fn detect_indent(&mut self) {
         self.indent_style = auto_detect_indent_style(&self.text)
-            .unwrap_or_else(|| IndentStyle::from_str(syntax.language.indent_unit()));
+            .unwrap_or(self.language_config.indent);
+    }
+
+    pub fn language_config(&self) -> &LanguageConfig {
+        &self.language_config
+    }
+
+    pub fn set_language_config(&mut self, config: Arc<LanguageConfig>) {
+        self.language_config = config;
     }
 
     pub fn indent_unit(&self) -> &'static str {
         self.indent_style.as_str()
     }
 
+    pub fn indent_style(&self) -> IndentStyle {
+        self.indent_style
+    }
+
+    /// The line ending of the buffer, going by its first line.
+    pub fn line_ending(&self) -> &'static str {
+        if self.line_content(0).ends_with("\r\n") {
+            "\r\n"
+        } else {
+            "\n"
+        }
+    }
+
     pub fn reset_edit_type(&mut self) {
         self.last_edit_type = EditType::Other;
     }
@@ -589,31 +622,32 @@ fn compute_undo(&self, groups: &BTreeSet<usize>) -> (Revision, Subset) {
             }
         }
 
-        let cursor_before = self
-            .revs
-            .get(first_candidate)
-            .and_then(|rev| rev.cursor_before.clone());
-
-        let cursor_after = self
+        // The cursor before the first edit of the group and after its last.
+        // A command making several edits records them on its last one only.
+        let group = self
             .revs
             .get(first_candidate)
             .and_then(|rev| match &rev.edit {
-                Contents::Edit { undo_group, .. } => Some(undo_group),
+                Contents::Edit { undo_group, .. } => Some(*undo_group),
                 Contents::Undo { .. } => None,
-            })
-            .and_then(|group| {
-                let mut cursor = None;
-                for rev in &self.revs[first_candidate..] {
-                    if let Contents::Edit { ref undo_group, .. } = rev.edit {
-                        if group == undo_group {
-                            cursor = rev.cursor_after.as_ref();
-                        } else {
-                            break;
-                        }
-                    }
-                }
-                cursor.cloned()
             });
+        let group_revs = self.revs[first_candidate..]
+            .iter()
+            .filter(|rev| matches!(rev.edit, Contents::Edit { .. }))
+            .take_while(|rev| {
+                matches!(
+                    rev.edit,
+                    Contents::Edit { undo_group, .. } if Some(undo_group) == group
+                )
+            });
+        let cursor_before = group_revs
+            .clone()
+            .find_map(|rev| rev.cursor_before.as_ref())
+            .cloned();
+        let cursor_after = group_revs
+            .filter_map(|rev| rev.cursor_after.as_ref())
+            .last()
+            .cloned();
 
         let deletes_bitxor = self.deletes_from_union.bitxor(&deletes_from_union);
         let max_undo_so_far = self.revs.last().unwrap().max_undo_so_far;
@@ -677,6 +711,16 @@ fn undo(
         (delta, inval_lines, edits, cursor_before, cursor_after)
     }
 
+    /// Whether there's an edit to undo
+    pub fn can_undo(&self) -> bool {
+        self.cur_undo > 1
+    }
+
+    /// Whether there's an undone edit to redo
+    pub fn can_redo(&self) -> bool {
+        self.cur_undo < self.live_undos.len()
+    }
+
     pub fn do_undo(
         &mut self,
     ) -> Option<(RopeDelta, InvalLines, SyntaxEdit, Option<CursorMode>)> {
@@ -6,7 +6,8 @@
 use crate::{
     encoding::{offset_utf16_to_utf8, offset_utf8_to_utf16},
     mode::Mode,
-    paragraph::ParagraphCursor,
+    paragraph::{next_paragraph_boundary, prev_paragraph_boundary},
+    sentence::{next_sentence_start, prev_sentence_start},
     word::WordCursor,
 };
 
@@ -115,6 +116,28 @@ fn offset_of_line_col(&self, line: usize, col: usize) -> usize {
         offset
     }
 
+    /// Clamp `offset` to where a cursor in `mode` can be: at the start of a
+    /// grapheme within the text and, in normal mode, not past the last
+    /// character of its line, which is only a position for inserting.
+    /// ```rust,ignore
+    /// let text = Rope::from("ab\n");
+    /// let text = RopeText::new(&text);
+    /// assert_eq!(text.clamp_offset(2, Mode::Normal), 1);  // "a|b"
+    /// assert_eq!(text.clamp_offset(2, Mode::Insert), 2);  // "ab|"
+    /// assert_eq!(text.clamp_offset(10, Mode::Insert), 3); // "ab\n|"
+    /// ```
+    fn clamp_offset(&self, offset: usize, mode: Mode) -> usize {
+        let len = self.len();
+        let offset = if offset >= len {
+            len
+        } else {
+            let next = self.next_grapheme_offset(offset, 1, len);
+            self.prev_grapheme_offset(next, 1, 0)
+        };
+        self.offset_line_end(offset, mode != Mode::Normal)
+            .min(offset)
+    }
+
     fn line_end_col(&self, line: usize, caret: bool) -> usize {
         let line_start = self.offset_of_line(line);
         let offset = self.line_end_offset(line, caret);
@@ -303,35 +326,70 @@ fn move_right(&self, offset: usize, mode: Mode, count: usize) -> usize {
         self.next_grapheme_offset(offset, count, max_offset)
     }
 
-    fn find_nth_paragraph<F>(
+    /// Move to the `count`th blank line after the paragraph at `offset`, or to
+    /// the end of the text if there are fewer paragraphs after it.
+    fn move_n_paragraphs_forward(
         &self,
         offset: usize,
-        mut count: usize,
-        mut find_next: F,
-    ) -> usize
-    where
-        F: FnMut(&mut ParagraphCursor) -> Option<usize>,
-    {
-        let mut cursor = ParagraphCursor::new(self.text(), offset);
-        let mut new_offset = offset;
-        while count != 0 {
-            // FIXME: wait for if-let-chain
-            if let Some(offset) = find_next(&mut cursor) {
-                new_offset = offset;
-            } else {
-                break;
+        count: usize,
+        mode: Mode,
+    ) -> usize {
+        let mut line = self.line_of_offset(offset);
+        for _ in 0..count {
+            match next_paragraph_boundary(self.text(), line) {
+                Some(boundary) => line = boundary,
+                None => {
+                    return self.offset_line_end(self.len(), mode != Mode::Normal)
+                }
             }
-            count -= 1;
         }
-        new_offset
+        self.offset_of_line(line)
     }
 
-    fn move_n_paragraphs_forward(&self, offset: usize, count: usize) -> usize {
-        self.find_nth_paragraph(offset, count, |cursor| cursor.next_boundary())
+    /// Move to the `count`th blank line before the paragraph at `offset`, or
+    /// to the start of the text if there are fewer paragraphs before it.
+    fn move_n_paragraphs_backward(&self, offset: usize, count: usize) -> usize {
+        let mut line = self.line_of_offset(offset);
+        for _ in 0..count {
+            match prev_paragraph_boundary(self.text(), line) {
+                Some(boundary) => line = boundary,
+                None => return 0,
+            }
+        }
+        self.offset_of_line(line)
     }
 
-    fn move_n_paragraphs_backward(&self, offset: usize, count: usize) -> usize {
-        self.find_nth_paragraph(offset, count, |cursor| cursor.prev_boundary())
+    /// Move to the start of the `count`th sentence after `offset`, or to the
+    /// end of the text if there are fewer sentences after it.
+    fn move_n_sentences_forward(
+        &self,
+        offset: usize,
+        count: usize,
+        mode: Mode,
+    ) -> usize {
+        let mut offset = offset;
+        for _ in 0..count {
+            match next_sentence_start(self.text(), offset) {
+                Some(start) => offset = start,
+                None => {
+                    return self.offset_line_end(self.len(), mode != Mode::Normal)
+                }
+            }
+        }
+        offset
+    }
+
+    /// Move to the start of the `count`th sentence before `offset`, or to the
+    /// start of the text if there are fewer sentences before it.
+    fn move_n_sentences_backward(&self, offset: usize, count: usize) -> usize {
+        let mut offset = offset;
+        for _ in 0..count {
+            match prev_sentence_start(self.text(), offset) {
+                Some(start) => offset = start,
+                None => return 0,
+            }
+        }
+        offset
     }
 
     /// Find the nth (`count`) word starting at `offset` in either direction
@@ -507,7 +565,7 @@ mod tests {
     use lapce_xi_rope::Rope;
 
     use super::RopeText;
-    use crate::buffer::rope_text::RopeTextVal;
+    use crate::{buffer::rope_text::RopeTextVal, mode::Mode};
 
     #[test]
     fn test_line_content() {
@@ -609,6 +667,40 @@ fn test_line_end_offset() {
         assert_eq!(text.line_end_offset(2, false), 11); // "world|"
     }
 
+    #[test]
+    fn test_clamp_offset() {
+        let text = Rope::from("");
+        let text = RopeTextVal::new(text);
+
+        assert_eq!(text.clamp_offset(0, Mode::Normal), 0);
+        assert_eq!(text.clamp_offset(0, Mode::Insert), 0);
+        assert_eq!(text.clamp_offset(5, Mode::Normal), 0);
+        assert_eq!(text.clamp_offset(5, Mode::Insert), 0);
+
+        let text = Rope::from("a");
+        let text = RopeTextVal::new(text);
+
+        assert_eq!(text.clamp_offset(0, Mode::Normal), 0);
+        assert_eq!(text.clamp_offset(1, Mode::Normal), 0);
+        assert_eq!(text.clamp_offset(1, Mode::Visual), 1);
+        assert_eq!(text.clamp_offset(1, Mode::Insert), 1);
+        assert_eq!(text.clamp_offset(5, Mode::Insert), 1);
+
+        let text = Rope::from("abc\r\n\ndef");
+        let text = RopeTextVal::new(text);
+
+        assert_eq!(text.clamp_offset(1, Mode::Normal), 1);
+        assert_eq!(text.clamp_offset(3, Mode::Normal), 2);
+        assert_eq!(text.clamp_offset(3, Mode::Insert), 3);
+        // Not between the \r and \n
+        assert_eq!(text.clamp_offset(4, Mode::Insert), 3);
+        // The empty line
+        assert_eq!(text.clamp_offset(5, Mode::Normal), 5);
+        assert_eq!(text.clamp_offset(9, Mode::Normal), 8);
+        assert_eq!(text.clamp_offset(9, Mode::Insert), 9);
+        assert_eq!(text.clamp_offset(20, Mode::Normal), 8);
+    }
+
     #[test]
     fn test_prev_grapheme_offset() {
         let text = Rope::from("");
@@ -657,4 +749,40 @@ fn test_first_non_blank_character_on_line() {
         assert_eq!(text.first_non_blank_character_on_line(4), 10);
         assert_eq!(text.first_non_blank_character_on_line(5), 10);
     }
+
+    #[test]
+    fn test_motions_at_the_extremes() {
+        for text in ["", "a", "abc", "abc\n", "a\r\n"] {
+            let text = RopeTextVal::new(Rope::from(text));
+            let len = text.len();
+            for offset in 0..=len {
+                let offset = text.clamp_offset(offset, Mode::Insert);
+                for mode in [Mode::Normal, Mode::Visual, Mode::Insert] {
+                    for count in [1, 3] {
+                        let line = text.line_of_offset(offset);
+                        let moved = [
+                            text.move_left(offset, mode, count),
+                            text.move_right(offset, mode, count),
+                            text.move_n_words_forward(offset, count),
+                            text.move_n_words_backward(offset, count, mode),
+                            text.move_n_wordends_forward(
+                                offset,
+                                count,
+                                mode == Mode::Insert,
+                            ),
+                            text.move_n_paragraphs_forward(offset, count, mode),
+                            text.move_n_paragraphs_backward(offset, count),
+                            text.move_n_sentences_forward(offset, count, mode),
+                            text.move_n_sentences_backward(offset, count),
+                            text.offset_line_end(offset, mode != Mode::Normal),
+                            text.first_non_blank_character_on_line(line),
+                        ];
+                        for moved in moved {
+                            assert!(moved <= len, "{:?}: {moved}", text.text);
+                        }
+                    }
+                }
+            }
+        }
+    }
 }
@@ -16,6 +16,27 @@ fn is_pristine() {
         buffer.do_undo();
         assert!(buffer.is_pristine());
     }
+
+    #[test]
+    fn set_dirty() {
+        let mut buffer = Buffer::new("");
+        buffer.init_content(Rope::from("abc"));
+        buffer.set_dirty();
+        assert!(!buffer.is_pristine());
+        // Undoing doesn't get back to what's saved, as it isn't anywhere
+        buffer.edit(&[(Selection::caret(0), "d")], EditType::InsertChars);
+        buffer.do_undo();
+        assert!(!buffer.is_pristine());
+        buffer.set_pristine();
+        assert!(buffer.is_pristine());
+    }
+
+    #[test]
+    fn line_ending() {
+        assert_eq!(Buffer::new("a\nb\r\n").line_ending(), "\n");
+        assert_eq!(Buffer::new("a\r\nb\n").line_ending(), "\r\n");
+        assert_eq!(Buffer::new("").line_ending(), "\n");
+    }
 }
 
 mod motion {
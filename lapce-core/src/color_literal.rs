@@ -0,0 +1,242 @@
+use std::ops::Range;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/// A hex color, or an `rgb()`/`rgba()` one with its numbers separated by
+/// commas
+static COLOR_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(concat!(
+        r"#[0-9A-Fa-f]{3,8}\b",
+        r"|\brgba?\(\s*\d{1,3}\s*,\s*\d{1,3}\s*,\s*\d{1,3}\s*",
+        r"(?:,\s*(?:\d*\.)?\d+\s*)?\)",
+    ))
+    .unwrap()
+});
+
+/// How a color literal is written, which it's written again in when it's
+/// changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorFormat {
+    /// `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`
+    Hex { digits: usize, uppercase: bool },
+    /// `rgb(r, g, b)` or `rgba(r, g, b, a)`, with the alpha from 0 to 1
+    Rgb { alpha: bool, spaced: bool },
+}
+
+/// A color written in some text, like `#ff8800`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorLiteral {
+    /// Where it is in the text
+    pub start: usize,
+    pub end: usize,
+    /// The red, green, blue and alpha of the color
+    pub rgba: [u8; 4],
+    pub format: ColorFormat,
+}
+
+impl ColorLiteral {
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    /// `rgb` written in the format of this literal, with its alpha kept.
+    /// Short hex literals are written long when the color needs it.
+    pub fn with_rgb(&self, rgb: [u8; 3]) -> String {
+        let [r, g, b] = rgb;
+        let a = self.rgba[3];
+        match self.format {
+            ColorFormat::Hex { digits, uppercase } => {
+                let short = matches!(digits, 3 | 4)
+                    && [r, g, b, a].iter().all(|c| c % 17 == 0);
+                let mut text = match (short, digits) {
+                    (true, 3) => format!("#{:x}{:x}{:x}", r / 17, g / 17, b / 17),
+                    (true, _) => {
+                        format!("#{:x}{:x}{:x}{:x}", r / 17, g / 17, b / 17, a / 17)
+                    }
+                    (false, 3 | 6) => format!("#{r:02x}{g:02x}{b:02x}"),
+                    (false, _) => format!("#{r:02x}{g:02x}{b:02x}{a:02x}"),
+                };
+                if uppercase {
+                    text.make_ascii_uppercase();
+                }
+                text
+            }
+            ColorFormat::Rgb { alpha, spaced } => {
+                let separator = if spaced { ", " } else { "," };
+                let mut numbers = vec![r.to_string(), g.to_string(), b.to_string()];
+                if alpha {
+                    let alpha = format!("{:.2}", a as f64 / 255.0);
+                    let alpha = alpha.trim_end_matches('0').trim_end_matches('.');
+                    numbers.push(alpha.to_string());
+                }
+                let name = if alpha { "rgba" } else { "rgb" };
+                format!("{name}({})", numbers.join(separator))
+            }
+        }
+    }
+}
+
+/// The color literals of `text`. A `#` that follows a letter, a digit or an
+/// underscore is part of an identifier rather than a color, as is a hex
+/// literal of a length colors aren't written in.
+pub fn color_literals(text: &str) -> Vec<ColorLiteral> {
+    COLOR_REGEX
+        .find_iter(text)
+        .filter(|found| {
+            !text[..found.start()]
+                .chars()
+                .next_back()
+                .map(|c| c.is_alphanumeric() || c == '_' || c == '#')
+                .unwrap_or(false)
+        })
+        .filter_map(|found| {
+            let literal = found.as_str();
+            let (rgba, format) = match literal.strip_prefix('#') {
+                Some(hex) => parse_hex(hex)?,
+                None => parse_rgb(literal)?,
+            };
+            Some(ColorLiteral {
+                start: found.start(),
+                end: found.end(),
+                rgba,
+                format,
+            })
+        })
+        .collect()
+}
+
+fn parse_hex(hex: &str) -> Option<([u8; 4], ColorFormat)> {
+    let digits = hex.len();
+    let channel = |i: usize, len: usize| {
+        let value = u8::from_str_radix(&hex[i * len..(i + 1) * len], 16).ok()?;
+        Some(if len == 1 { value * 17 } else { value })
+    };
+    let rgba = match digits {
+        3 | 4 => {
+            let alpha = if digits == 4 { channel(3, 1)? } else { 255 };
+            [channel(0, 1)?, channel(1, 1)?, channel(2, 1)?, alpha]
+        }
+        6 | 8 => {
+            let alpha = if digits == 8 { channel(3, 2)? } else { 255 };
+            [channel(0, 2)?, channel(1, 2)?, channel(2, 2)?, alpha]
+        }
+        _ => return None,
+    };
+    let uppercase = hex.chars().any(|c| c.is_ascii_uppercase())
+        && !hex.chars().any(|c| c.is_ascii_lowercase());
+    Some((rgba, ColorFormat::Hex { digits, uppercase }))
+}
+
+fn parse_rgb(literal: &str) -> Option<([u8; 4], ColorFormat)> {
+    let alpha = literal.starts_with("rgba");
+    let inner = literal.split_once('(')?.1.strip_suffix(')')?;
+    let spaced = inner.contains(", ");
+    let numbers = inner.split(',').map(str::trim).collect::<Vec<_>>();
+    let channel = |s: &str| s.parse::<u8>().ok();
+    let a = match numbers.get(3) {
+        Some(a) => (a.parse::<f64>().ok()?.clamp(0.0, 1.0) * 255.0).round() as u8,
+        None => 255,
+    };
+    // `rgb()` with an alpha, or `rgba()` without one, isn't rewritten in a
+    // form it wasn't in
+    if alpha != (numbers.len() == 4) {
+        return None;
+    }
+    let rgba = [
+        channel(numbers[0])?,
+        channel(numbers[1])?,
+        channel(numbers[2])?,
+        a,
+    ];
+    Some((rgba, ColorFormat::Rgb { alpha, spaced }))
+}
+
+/// The hue from 0 to 360, and the saturation and value from 0 to 1, of a
+/// color.
+pub fn rgb_to_hsv([r, g, b]: [u8; 3]) -> (f64, f64, f64) {
+    let [r, g, b] = [r, g, b].map(|c| c as f64 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+    (hue, saturation, max)
+}
+
+/// The color of a hue from 0 to 360, and a saturation and a value from 0 to
+/// 1.
+pub fn hsv_to_rgb(hue: f64, saturation: f64, value: f64) -> [u8; 3] {
+    let hue = hue.rem_euclid(360.0) / 60.0;
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = value - chroma;
+    [r, g, b].map(|c| ((c + m) * 255.0).round() as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_literals() {
+        let text = "color: #ff8800; background: #FFF; fill: rgba(0, 128, 255, 0.5)";
+        let found = color_literals(text);
+        assert_eq!(found.len(), 3);
+        assert_eq!(&text[found[0].range()], "#ff8800");
+        assert_eq!(found[0].rgba, [255, 136, 0, 255]);
+        assert_eq!(found[1].rgba, [255, 255, 255, 255]);
+        assert_eq!(
+            found[1].format,
+            ColorFormat::Hex {
+                digits: 3,
+                uppercase: true
+            }
+        );
+        assert_eq!(found[2].rgba, [0, 128, 255, 128]);
+
+        // Identifiers and hex numbers of other lengths aren't colors
+        assert!(color_literals("a#fff #ffffff0 #12345 #abcdefgh x_#fff").is_empty());
+        assert!(color_literals("myrgb(1, 2, 3) rgb(1, 2, 300)").is_empty());
+    }
+
+    #[test]
+    fn test_with_rgb() {
+        let literal = |text: &str| color_literals(text)[0];
+        assert_eq!(literal("#fff").with_rgb([0, 0x11, 0xff]), "#01f");
+        assert_eq!(literal("#fff").with_rgb([1, 2, 3]), "#010203");
+        assert_eq!(literal("#FF880080").with_rgb([0, 0xaa, 0]), "#00AA0080");
+        assert_eq!(literal("#ff8800").with_rgb([0, 0, 0]), "#000000");
+        assert_eq!(literal("rgb(1,2,3)").with_rgb([4, 5, 6]), "rgb(4,5,6)");
+        assert_eq!(
+            literal("rgba(1, 2, 3, 0.5)").with_rgb([4, 5, 6]),
+            "rgba(4, 5, 6, 0.5)"
+        );
+    }
+
+    #[test]
+    fn test_hsv() {
+        assert_eq!(rgb_to_hsv([255, 0, 0]), (0.0, 1.0, 1.0));
+        assert_eq!(rgb_to_hsv([0, 0, 255]), (240.0, 1.0, 1.0));
+        for rgb in [[255, 136, 0], [12, 200, 99], [0, 0, 0], [128, 128, 128]] {
+            let (h, s, v) = rgb_to_hsv(rgb);
+            assert_eq!(hsv_to_rgb(h, s, v), rgb);
+        }
+    }
+}
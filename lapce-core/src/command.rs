@@ -179,6 +179,16 @@ pub enum MoveCommand {
     #[strum(message = "Paragraph backward")]
     #[strum(serialize = "paragraph_backward")]
     ParagraphBackward,
+    #[strum(message = "Sentence forward")]
+    #[strum(serialize = "sentence_forward")]
+    SentenceForward,
+    #[strum(message = "Sentence backward")]
+    #[strum(serialize = "sentence_backward")]
+    SentenceBackward,
+    #[strum(serialize = "inner_paragraph")]
+    InnerParagraph,
+    #[strum(serialize = "around_paragraph")]
+    AroundParagraph,
 }
 
 impl MoveCommand {
@@ -212,6 +222,10 @@ pub fn to_movement(&self, count: Option<usize>) -> Movement {
             PreviousUnmatchedLeftCurlyBracket => Movement::PreviousUnmatched('{'),
             ParagraphForward => Movement::ParagraphForward,
             ParagraphBackward => Movement::ParagraphBackward,
+            SentenceForward => Movement::SentenceForward,
+            SentenceBackward => Movement::SentenceBackward,
+            InnerParagraph => Movement::InnerParagraph,
+            AroundParagraph => Movement::AroundParagraph,
         }
     }
 }
@@ -270,6 +284,8 @@ pub enum FocusCommand {
     ListPreviousPage,
     #[strum(serialize = "list.expand")]
     ListExpand,
+    #[strum(serialize = "palette.create_file")]
+    PaletteCreateFile,
     #[strum(serialize = "jump_to_next_snippet_placeholder")]
     JumpToNextSnippetPlaceholder,
     #[strum(serialize = "jump_to_prev_snippet_placeholder")]
@@ -304,6 +320,9 @@ pub enum FocusCommand {
     #[strum(message = "Go to Type Definition")]
     #[strum(serialize = "goto_type_definition")]
     GotoTypeDefinition,
+    #[strum(message = "Find References")]
+    #[strum(serialize = "find_references")]
+    FindReferences,
     #[strum(message = "Show Hover")]
     #[strum(serialize = "show_hover")]
     ShowHover,
@@ -313,6 +332,42 @@ pub enum FocusCommand {
     #[strum(message = "Go to Previous Difference")]
     #[strum(serialize = "previous_diff")]
     PreviousDiff,
+    #[strum(message = "Revert Change")]
+    #[strum(serialize = "revert_hunk")]
+    RevertHunk,
+    #[strum(message = "Copy Blame Commit Hash")]
+    #[strum(serialize = "copy_blame_commit_hash")]
+    CopyBlameCommitHash,
+    #[strum(message = "Show Blame Commit Message")]
+    #[strum(serialize = "show_blame_commit")]
+    ShowBlameCommit,
+    #[strum(message = "Export as HTML")]
+    #[strum(serialize = "export_html")]
+    ExportHtml,
+    #[strum(message = "Copy as HTML")]
+    #[strum(serialize = "copy_as_html")]
+    CopyAsHtml,
+    #[strum(message = "Copy File Path")]
+    #[strum(serialize = "copy_file_path")]
+    CopyFilePath,
+    #[strum(message = "Copy Relative File Path")]
+    #[strum(serialize = "copy_relative_file_path")]
+    CopyRelativeFilePath,
+    #[strum(message = "Copy Location (path:line:column)")]
+    #[strum(serialize = "copy_location")]
+    CopyLocation,
+    #[strum(message = "Copy with Line Numbers")]
+    #[strum(serialize = "copy_with_line_numbers")]
+    CopyWithLineNumbers,
+    #[strum(message = "Open Selection in New Scratch Buffer")]
+    #[strum(serialize = "selection_to_scratch")]
+    SelectionToScratch,
+    #[strum(message = "Move Selection to New Scratch Buffer")]
+    #[strum(serialize = "move_selection_to_scratch")]
+    MoveSelectionToScratch,
+    #[strum(message = "Extract Selection to New File...")]
+    #[strum(serialize = "extract_selection_to_file")]
+    ExtractSelectionToFile,
     #[strum(message = "Toggle Code Lens")]
     #[strum(serialize = "toggle_code_lens")]
     ToggleCodeLens,
@@ -322,6 +377,12 @@ pub enum FocusCommand {
     #[strum(serialize = "format_document")]
     #[strum(message = "Format Document")]
     FormatDocument,
+    #[strum(serialize = "organize_imports")]
+    #[strum(message = "Organize Imports")]
+    OrganizeImports,
+    #[strum(serialize = "fix_all")]
+    #[strum(message = "Fix All")]
+    FixAll,
     #[strum(serialize = "search")]
     Search,
     #[strum(serialize = "focus_replace_editor")]
@@ -338,14 +399,28 @@ pub enum FocusCommand {
     GoToMark,
     #[strum(serialize = "repeat_last_inline_find")]
     RepeatLastInlineFind,
+    #[strum(message = "Jump to Word")]
+    #[strum(serialize = "jump_to_word")]
+    JumpToWord,
+    #[strum(message = "Jump to Character")]
+    #[strum(serialize = "jump_to_char")]
+    JumpToChar,
+    #[strum(message = "Jump to Line")]
+    #[strum(serialize = "jump_to_line")]
+    JumpToLine,
+    #[strum(message = "Repeat Last Shift")]
+    #[strum(serialize = "repeat_last_shift")]
+    RepeatLastShift,
     #[strum(message = "Save")]
     #[strum(serialize = "save")]
     Save,
     #[strum(message = "Save Without Formatting")]
     #[strum(serialize = "save_without_format")]
     SaveWithoutFormatting,
+    #[strum(message = "Save and Close Window")]
     #[strum(serialize = "save_and_exit")]
     SaveAndExit,
+    #[strum(message = "Close Window Without Saving")]
     #[strum(serialize = "force_exit")]
     ForceExit,
     #[strum(serialize = "rename_symbol")]
@@ -353,6 +428,8 @@ pub enum FocusCommand {
     Rename,
     #[strum(serialize = "confirm_rename")]
     ConfirmRename,
+    #[strum(serialize = "confirm_color_picker")]
+    ConfirmColorPicker,
     #[strum(serialize = "select_next_syntax_item")]
     SelectNextSyntaxItem,
     #[strum(serialize = "select_previous_syntax_item")]
@@ -381,6 +458,8 @@ pub enum MotionModeCommand {
     MotionModeOutdent,
     #[strum(serialize = "motion_mode_yank")]
     MotionModeYank,
+    #[strum(serialize = "motion_mode_reflow")]
+    MotionModeReflow,
 }
 
 #[derive(
@@ -405,12 +484,16 @@ pub enum MultiSelectionCommand {
     InsertCursorEndOfLine,
     #[strum(serialize = "select_current_line")]
     SelectCurrentLine,
+    #[strum(message = "Select All Occurrences")]
     #[strum(serialize = "select_all_current")]
     SelectAllCurrent,
+    #[strum(message = "Add Next Occurrence")]
     #[strum(serialize = "select_next_current")]
     SelectNextCurrent,
+    #[strum(message = "Skip to Next Occurrence")]
     #[strum(serialize = "select_skip_current")]
     SelectSkipCurrent,
+    #[strum(message = "Select All")]
     #[strum(serialize = "select_all")]
     SelectAll,
 }
@@ -81,6 +81,23 @@ pub fn origin(modal: bool) -> Self {
         )
     }
 
+    /// Switch to the mode the cursor starts in when modal editing is enabled or
+    /// disabled, keeping its offset. Without modal editing there is only insert
+    /// mode.
+    pub fn set_modal(&mut self, modal: bool) {
+        let offset = self.offset();
+        match (&self.mode, modal) {
+            (CursorMode::Insert(_), true) => {
+                self.set_mode(CursorMode::Normal(offset));
+            }
+            (CursorMode::Normal(_) | CursorMode::Visual { .. }, false) => {
+                self.motion_mode = None;
+                self.set_insert(Selection::caret(offset));
+            }
+            _ => {}
+        }
+    }
+
     pub fn offset(&self) -> usize {
         self.mode.offset()
     }
@@ -120,11 +137,42 @@ pub fn set_insert(&mut self, selection: Selection) {
         self.set_mode(CursorMode::Insert(selection));
     }
 
+    /// Clamp the offsets of the cursor to where it can be in its mode, see
+    /// [`RopeText::clamp_offset`].
+    pub fn clamp(&mut self, text: &impl RopeText) {
+        let mode = self.get_mode();
+        match &mut self.mode {
+            CursorMode::Normal(offset) => {
+                *offset = text.clamp_offset(*offset, mode);
+            }
+            CursorMode::Visual { start, end, .. } => {
+                *start = text.clamp_offset(*start, mode);
+                *end = text.clamp_offset(*end, mode);
+            }
+            CursorMode::Insert(selection) => {
+                // The regions within the text are left as they are, as the
+                // selections made in insert mode can end anywhere
+                let len = text.len();
+                if selection.regions().iter().any(|region| region.max() > len) {
+                    let mut clamped = Selection::new();
+                    for region in selection.regions() {
+                        clamped.add_region(SelRegion::new(
+                            text.clamp_offset(region.start, mode),
+                            text.clamp_offset(region.end, mode),
+                            region.horiz,
+                        ));
+                    }
+                    *selection = clamped;
+                }
+            }
+        }
+    }
+
     pub fn update_selection(&mut self, buffer: &Buffer, selection: Selection) {
         match self.mode {
             CursorMode::Normal(_) | CursorMode::Visual { .. } => {
-                let offset = selection.min_offset();
-                let offset = buffer.offset_line_end(offset, false).min(offset);
+                let offset =
+                    buffer.clamp_offset(selection.min_offset(), Mode::Normal);
                 self.mode = CursorMode::Normal(offset);
             }
             CursorMode::Insert(_) => {
@@ -530,9 +578,9 @@ pub fn get_first_selection_after(
         .map(|selection| {
             let cursor_mode = match cursor.mode {
                 CursorMode::Normal(_) | CursorMode::Visual { .. } => {
-                    let offset = selection.min_offset();
-                    let offset = buffer.offset_line_end(offset, false).min(offset);
-                    CursorMode::Normal(offset)
+                    CursorMode::Normal(
+                        buffer.clamp_offset(selection.min_offset(), Mode::Normal),
+                    )
                 }
                 CursorMode::Insert(_) => CursorMode::Insert(selection),
             };
@@ -540,3 +588,30 @@ pub fn get_first_selection_after(
             Cursor::new(cursor_mode, None, None)
         })
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Cursor, CursorMode};
+    use crate::{mode::VisualMode, selection::Selection};
+
+    #[test]
+    fn test_set_modal() {
+        let mut cursor = Cursor::new(
+            CursorMode::Visual {
+                start: 2,
+                end: 5,
+                mode: VisualMode::Normal,
+            },
+            None,
+            None,
+        );
+        cursor.set_modal(false);
+        assert_eq!(cursor.mode, CursorMode::Insert(Selection::caret(5)));
+
+        cursor.set_modal(false);
+        assert_eq!(cursor.mode, CursorMode::Insert(Selection::caret(5)));
+
+        cursor.set_modal(true);
+        assert_eq!(cursor.mode, CursorMode::Normal(5));
+    }
+}
@@ -59,6 +59,20 @@ pub fn logs_directory() -> Option<PathBuf> {
         }
     }
 
+    /// Get the path to the directory the unsaved files are backed up to when
+    /// Lapce panics
+    pub fn backups_directory() -> Option<PathBuf> {
+        if let Some(dir) = Self::data_local_directory() {
+            let dir = dir.join("backups");
+            if !dir.exists() {
+                let _ = std::fs::create_dir(&dir);
+            }
+            Some(dir)
+        } else {
+            None
+        }
+    }
+
     /// Get the path to cache directory
     pub fn cache_directory() -> Option<PathBuf> {
         if let Some(dir) = Self::data_local_directory() {
@@ -72,6 +86,20 @@ pub fn cache_directory() -> Option<PathBuf> {
         }
     }
 
+    /// Get the path to the directory the file index of each workspace is
+    /// cached in
+    pub fn file_index_directory() -> Option<PathBuf> {
+        if let Some(dir) = Self::cache_directory() {
+            let dir = dir.join("file-index");
+            if !dir.exists() {
+                let _ = std::fs::create_dir(&dir);
+            }
+            Some(dir)
+        } else {
+            None
+        }
+    }
+
     /// Directory to store proxy executables used on local
     /// host as well, as ones uploaded to remote host when
     /// connecting
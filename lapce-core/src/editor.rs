@@ -1,25 +1,29 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 
 use itertools::Itertools;
 use lapce_xi_rope::RopeDelta;
 
 use crate::{
+    auto_edit::{closing_tag, list_continuation, ListContinuation},
     buffer::{rope_text::RopeText, Buffer, InvalLines},
     command::EditCommand,
     cursor::{get_first_selection_after, Cursor, CursorMode},
     mode::{Mode, MotionMode, VisualMode},
+    reflow::reflow,
     register::{Clipboard, Register, RegisterData, RegisterKind},
     selection::{InsertDrift, SelRegion, Selection},
     syntax::{
         edit::SyntaxEdit,
-        util::{
-            has_unmatched_pair, matching_char, matching_pair_direction,
-            str_is_pair_left, str_matching_pair,
-        },
+        util::{has_unmatched_pair, matching_char},
         Syntax,
     },
     word::{get_char_property, CharClassification},
 };
+
+/// How many lines before the `>` of a tag are looked at for its `<`, as the
+/// attributes of a tag can be on lines of their own
+const TAG_LINES: usize = 10;
+
 fn format_start_end(
     buffer: &Buffer,
     start: usize,
@@ -85,18 +89,30 @@ pub fn insert(
         syntax: &Syntax,
         auto_closing_matching_pairs: bool,
     ) -> Vec<(RopeDelta, InvalLines, SyntaxEdit)> {
+        let old_cursor = cursor.mode.clone();
         let mut deltas = Vec::new();
         if let CursorMode::Insert(selection) = &cursor.mode {
             if s.chars().count() != 1 {
+                // Text of more than a line is pasted rather than typed, so it's
+                // undone on its own instead of along with the typing before it
+                let edit_type = if s.contains('\n') {
+                    EditType::Paste
+                } else {
+                    EditType::InsertChars
+                };
                 let (delta, inval_lines, edits) =
-                    buffer.edit(&[(selection, s)], EditType::InsertChars);
+                    buffer.edit(&[(selection, s)], edit_type);
                 let selection =
                     selection.apply_delta(&delta, true, InsertDrift::Default);
                 deltas.push((delta, inval_lines, edits));
                 cursor.mode = CursorMode::Insert(selection);
             } else {
                 let c = s.chars().next().unwrap();
-                let matching_pair_type = matching_pair_direction(c);
+                let config = buffer.language_config();
+                let closing_pair = config.closing_pair(c);
+                let is_closing_pair = config.is_closing_pair(c);
+                let is_outdent_char = config.outdent_chars.contains(&c);
+                let auto_close_tags = config.auto_close_tags && c == '>';
 
                 // The main edit operations
                 let mut edits = vec![];
@@ -115,30 +131,21 @@ pub fn insert(
                         None
                     };
 
-                    // when text is selected, and [,{,(,'," is inserted
-                    // wrap the text with that char and its corresponding closing pair
-                    if region.start != region.end
-                        && (matching_pair_type == Some(true)
-                            || c == '"'
-                            || c == '\'')
-                    {
-                        edits.push((
-                            Selection::region(region.min(), region.min()),
-                            c.to_string(),
-                        ));
-                        edits_after.push((
-                            idx,
-                            match c {
-                                '"' => '"',
-                                '\'' => '\'',
-                                _ => matching_char(c).unwrap(),
-                            },
-                        ));
-                        continue;
+                    // when text is selected, and the opening char of a pair is
+                    // inserted, wrap the text with that char and its closing pair
+                    if region.start != region.end {
+                        if let Some(closing_pair) = closing_pair {
+                            edits.push((
+                                Selection::region(region.min(), region.min()),
+                                c.to_string(),
+                            ));
+                            edits_after.push((idx, closing_pair.to_string()));
+                            continue;
+                        }
                     }
 
                     if auto_closing_matching_pairs {
-                        if (c == '"' || c == '\'') && cursor_char == Some(c) {
+                        if is_closing_pair && cursor_char == Some(c) {
                             // Skip the closing character
                             let new_offset =
                                 buffer.next_grapheme_offset(offset, 1, buffer.len());
@@ -147,29 +154,18 @@ pub fn insert(
                             continue;
                         }
 
-                        if matching_pair_type == Some(false) {
-                            if cursor_char == Some(c) {
-                                // Skip the closing character
-                                let new_offset = buffer.next_grapheme_offset(
-                                    offset,
-                                    1,
-                                    buffer.len(),
-                                );
-
-                                *region = SelRegion::caret(new_offset);
-                                continue;
-                            }
-
+                        if is_outdent_char {
                             let line = buffer.line_of_offset(offset);
                             let line_start = buffer.offset_of_line(line);
                             if buffer.slice_to_cow(line_start..offset).trim() == "" {
-                                let opening_character = matching_char(c).unwrap();
-                                if let Some(previous_offset) = buffer
-                                    .previous_unmatched(
-                                        syntax,
-                                        opening_character,
-                                        offset,
-                                    )
+                                if let Some(previous_offset) = matching_char(c)
+                                    .and_then(|opening_character| {
+                                        buffer.previous_unmatched(
+                                            syntax,
+                                            opening_character,
+                                            offset,
+                                        )
+                                    })
                                 {
                                     // Auto-indent closing character to the same level as the opening.
                                     let previous_line =
@@ -189,8 +185,7 @@ pub fn insert(
                             }
                         }
 
-                        if matching_pair_type == Some(true) || c == '"' || c == '\''
-                        {
+                        if let Some(closing_pair) = closing_pair {
                             // Create a late edit to insert the closing pair, if allowed.
                             let is_whitespace_or_punct = cursor_char
                                 .map(|c| {
@@ -201,9 +196,10 @@ pub fn insert(
                                 })
                                 .unwrap_or(true);
 
-                            let should_insert_pair = match c {
-                                '"' | '\'' => {
-                                    is_whitespace_or_punct
+                            // A pair that opens and closes with the same char,
+                            // like quotes, also needs to start a word
+                            let should_insert_pair = if c == closing_pair {
+                                is_whitespace_or_punct
                                         && prev_cursor_char
                                             .map(|c| {
                                                 let prop = get_char_property(c);
@@ -213,21 +209,39 @@ pub fn insert(
                                                         == CharClassification::Punctuation
                                             })
                                             .unwrap_or(true)
-                                }
-                                _ => is_whitespace_or_punct,
+                            } else {
+                                // Brackets in strings and comments are left
+                                // unclosed, as they rarely are closed there
+                                is_whitespace_or_punct
+                                    && !syntax.is_in_literal(offset)
                             };
 
                             if should_insert_pair {
-                                let insert_after = match c {
-                                    '"' => '"',
-                                    '\'' => '\'',
-                                    _ => matching_char(c).unwrap(),
-                                };
-                                edits_after.push((idx, insert_after));
+                                edits_after.push((idx, closing_pair.to_string()));
                             }
                         };
                     }
 
+                    if auto_close_tags
+                        && region.is_caret()
+                        && !syntax.is_in_literal(offset)
+                    {
+                        let line = buffer.line_of_offset(offset);
+                        let start =
+                            buffer.offset_of_line(line.saturating_sub(TAG_LINES));
+                        let before = buffer.slice_to_cow(start..offset);
+                        if let Some(tag) = closing_tag(&format!("{before}>")) {
+                            let line_end = buffer.line_end_offset(line, true);
+                            // The tag isn't closed twice
+                            if !buffer
+                                .slice_to_cow(offset..line_end)
+                                .starts_with(&tag)
+                            {
+                                edits_after.push((idx, tag));
+                            }
+                        }
+                    }
+
                     let current_selection =
                         Selection::region(region.start, region.end);
 
@@ -306,6 +320,11 @@ pub fn insert(
                 cursor.mode = CursorMode::Insert(selection);
             }
         }
+        if !deltas.is_empty() {
+            // Keep track of the change in the cursor mode for undo/redo
+            buffer.set_cursor_before(old_cursor);
+            buffer.set_cursor_after(cursor.mode.clone());
+        }
         deltas
     }
 
@@ -345,6 +364,8 @@ fn insert_new_line(
         let mut edits = Vec::with_capacity(selection.regions().len());
         let mut extra_edits = Vec::new();
         let mut shift = 0i32;
+        let indent_chars = buffer.language_config().indent_chars.clone();
+        let continue_lists = buffer.language_config().continue_lists;
         for region in selection.regions() {
             let offset = region.max();
             let line = buffer.line_of_offset(offset);
@@ -353,24 +374,52 @@ fn insert_new_line(
             let line_indent = buffer.indent_on_line(line);
             let first_half = buffer.slice_to_cow(line_start..offset);
             let second_half = buffer.slice_to_cow(offset..line_end);
+
+            // A list item goes on with a new item, whose marker takes the
+            // place of the indent, or ends the list when it's empty
+            let continuation = (continue_lists && region.is_caret())
+                .then(|| list_continuation(&first_half, &second_half))
+                .flatten();
+            match continuation {
+                Some(ListContinuation::End) => {
+                    shift -= (offset - line_start) as i32;
+                    edits.push((
+                        Selection::region(line_start, offset),
+                        String::new(),
+                    ));
+                    continue;
+                }
+                Some(ListContinuation::Continue(prefix)) => {
+                    let new_line_content = format!("\n{prefix}");
+                    shift += new_line_content.len() as i32;
+                    edits.push((Selection::caret(offset), new_line_content));
+                    continue;
+                }
+                None => {}
+            }
+
             let second_half = second_half.trim();
+            let last_char = first_half.chars().rev().find(|&c| c != ' ');
+            let ends_with_indent_char =
+                matches!(last_char, Some(c) if indent_chars.contains(&c));
 
             let new_line_content = {
                 let indent_storage;
-                let indent = if has_unmatched_pair(&first_half) {
-                    indent_storage =
-                        format!("{}{}", line_indent, buffer.indent_unit());
-                    &indent_storage
-                } else if second_half.is_empty() {
-                    indent_storage = buffer.indent_on_line(line + 1);
-                    if indent_storage.len() > line_indent.len() {
+                let indent =
+                    if ends_with_indent_char || has_unmatched_pair(&first_half) {
+                        indent_storage =
+                            format!("{}{}", line_indent, buffer.indent_unit());
                         &indent_storage
+                    } else if second_half.is_empty() {
+                        indent_storage = buffer.indent_on_line(line + 1);
+                        if indent_storage.len() > line_indent.len() {
+                            &indent_storage
+                        } else {
+                            &line_indent
+                        }
                     } else {
                         &line_indent
-                    }
-                } else {
-                    &line_indent
-                };
+                    };
                 format!("\n{indent}")
             };
 
@@ -381,17 +430,16 @@ fn insert_new_line(
 
             edits.push((selection, new_line_content));
 
-            if let Some(c) = first_half.chars().rev().find(|&c| c != ' ') {
-                if let Some(true) = matching_pair_direction(c) {
-                    if let Some(c) = matching_char(c) {
-                        if second_half.starts_with(c) {
-                            let selection = Selection::caret(
-                                (region.max() as i32 + shift) as usize,
-                            );
-                            let content = format!("\n{line_indent}");
-                            extra_edits.push((selection, content));
-                        }
-                    }
+            // Put the closing bracket of an indenting opening one on its own
+            // line
+            if let (true, Some(c)) =
+                (ends_with_indent_char, last_char.and_then(matching_char))
+            {
+                if second_half.starts_with(c) {
+                    let selection =
+                        Selection::caret((region.max() as i32 + shift) as usize);
+                    let content = format!("\n{line_indent}");
+                    extra_edits.push((selection, content));
                 }
             }
         }
@@ -471,18 +519,32 @@ pub fn execute_motion_mode(
                     },
                 );
             }
-            MotionMode::Indent => {
-                let selection = Selection::region(start, end);
-                let (delta, inval_lines, edits) = Self::do_indent(buffer, selection);
-                deltas.push((delta, inval_lines, edits));
+            MotionMode::Indent { count } => {
+                deltas.push(Self::shift_motion_lines(
+                    cursor, buffer, start, end, count, false,
+                ));
             }
-            MotionMode::Outdent => {
-                let selection = Selection::region(start, end);
-                let (delta, inval_lines, edits) =
-                    Self::do_outdent(buffer, selection);
-                deltas.push((delta, inval_lines, edits));
+            MotionMode::Outdent { count } => {
+                deltas.push(Self::shift_motion_lines(
+                    cursor, buffer, start, end, count, true,
+                ));
+            }
+            MotionMode::Reflow { count, width } => {
+                let start_line = buffer.line_of_offset(start.min(end));
+                let end_line = buffer
+                    .line_of_offset(start.max(end))
+                    .max(start_line + count.max(1) - 1);
+                if let Some(delta) =
+                    Self::do_reflow(buffer, start_line, end_line, width)
+                {
+                    deltas.push(delta);
+                }
+                let offset = buffer.first_non_blank_character_on_line(start_line);
+                cursor.mode = CursorMode::Normal(offset);
+                cursor.horiz = None;
             }
         }
+        cursor.clamp(buffer);
         deltas
     }
 
@@ -529,11 +591,24 @@ pub fn do_paste(
                 }
             }
             VisualMode::Linewise | VisualMode::Blockwise => {
+                // Whether the lines go after the last line, which has no line
+                // ending to put them after
+                let mut after_last_line = false;
                 let (selection, content) = match &cursor.mode {
                     CursorMode::Normal(offset) => {
                         let line = buffer.line_of_offset(*offset);
                         let offset = buffer.offset_of_line(line + 1);
-                        (Selection::caret(offset), data.content.clone())
+                        if buffer.line_end_offset(line, true) == offset {
+                            after_last_line = true;
+                            let content = &data.content;
+                            let content = content
+                                .strip_suffix("\r\n")
+                                .or_else(|| content.strip_suffix('\n'))
+                                .unwrap_or(content);
+                            (Selection::caret(offset), format!("\n{content}"))
+                        } else {
+                            (Selection::caret(offset), data.content.clone())
+                        }
                     }
                     CursorMode::Insert(selection) => {
                         let mut selection = selection.clone();
@@ -567,7 +642,7 @@ pub fn do_paste(
                 match cursor.mode {
                     CursorMode::Normal(_) | CursorMode::Visual { .. } => {
                         let offset = selection.min_offset();
-                        let offset = if cursor.is_visual() {
+                        let offset = if cursor.is_visual() || after_last_line {
                             offset + 1
                         } else {
                             offset
@@ -585,73 +660,233 @@ pub fn do_paste(
         deltas
     }
 
-    fn do_indent(
+    /// Rewrap the lines from `start_line` to `end_line` to `width` columns,
+    /// in a single edit so that it's undone at once.
+    pub fn do_reflow(
         buffer: &mut Buffer,
-        selection: Selection,
-    ) -> (RopeDelta, InvalLines, SyntaxEdit) {
-        let indent = buffer.indent_unit();
-        let mut edits = Vec::new();
+        start_line: usize,
+        end_line: usize,
+        width: usize,
+    ) -> Option<(RopeDelta, InvalLines, SyntaxEdit)> {
+        let start = buffer.offset_of_line(start_line);
+        let end = buffer.offset_of_line(end_line + 1);
+        let text = buffer.slice_to_cow(start..end);
+        let reflowed = reflow(&text, width);
+        if reflowed == text {
+            return None;
+        }
+        let selection = Selection::region(start, end);
+        Some(buffer.edit(&[(&selection, reflowed.as_str())], EditType::Other))
+    }
 
-        let mut lines = HashSet::new();
+    /// The lines touched by the selection, leaving out the last line of a
+    /// region that ends at its start.
+    fn selection_lines(buffer: &Buffer, selection: &Selection) -> BTreeSet<usize> {
+        let mut lines = BTreeSet::new();
         for region in selection.regions() {
             let start_line = buffer.line_of_offset(region.min());
             let mut end_line = buffer.line_of_offset(region.max());
-            if end_line > start_line {
-                let end_line_start = buffer.offset_of_line(end_line);
-                if end_line_start == region.max() {
-                    end_line -= 1;
-                }
-            }
-            for line in start_line..=end_line {
-                if lines.insert(line) {
-                    let line_content = buffer.line_content(line);
-                    if line_content == "\n" || line_content == "\r\n" {
-                        continue;
-                    }
-                    let nonblank = buffer.first_non_blank_character_on_line(line);
-                    let edit = crate::indent::create_edit(buffer, nonblank, indent);
-                    edits.push(edit);
-                }
+            if end_line > start_line
+                && buffer.offset_of_line(end_line) == region.max()
+            {
+                end_line -= 1;
             }
+            lines.extend(start_line..=end_line);
         }
+        lines
+    }
 
-        buffer.edit(&edits, EditType::Indent)
+    /// Shift the lines from `start` to `end` by an indent, or the `count`
+    /// lines from the first of them if there are more, like `3>>` does. The
+    /// cursor is left at the first non-blank character of the first line.
+    fn shift_motion_lines(
+        cursor: &mut Cursor,
+        buffer: &mut Buffer,
+        start: usize,
+        end: usize,
+        count: usize,
+        outdent: bool,
+    ) -> (RopeDelta, InvalLines, SyntaxEdit) {
+        let start_line = buffer.line_of_offset(start.min(end));
+        let end_line = buffer
+            .line_of_offset(start.max(end))
+            .max(start_line + count.max(1) - 1);
+        let shifted = Self::shift_lines(buffer, start_line..=end_line, 1, outdent);
+        let offset = buffer.first_non_blank_character_on_line(start_line);
+        cursor.mode = CursorMode::Normal(offset);
+        cursor.horiz = None;
+        shifted
     }
 
-    fn do_outdent(
+    /// Shift `lines` right by `units` indents, or left when `outdent`. The
+    /// indents are added to or taken from the start of each line, so that the
+    /// alignment within the shifted text is kept, and a line is never
+    /// outdented past its leading whitespace. Blank lines are left as they
+    /// are, unless the language config shifts them too.
+    pub fn shift_lines(
         buffer: &mut Buffer,
-        selection: Selection,
+        lines: impl IntoIterator<Item = usize>,
+        units: usize,
+        outdent: bool,
     ) -> (RopeDelta, InvalLines, SyntaxEdit) {
         let indent = buffer.indent_unit();
+        let shift_blank_lines = buffer.language_config().shift_blank_lines;
         let mut edits = Vec::new();
+        for line in lines {
+            if line > buffer.last_line() {
+                break;
+            }
+            if !shift_blank_lines && buffer.line_content(line).trim().is_empty() {
+                continue;
+            }
+            if outdent {
+                if let Some((selection, content)) =
+                    crate::indent::create_shift_out(buffer, line, indent, units)
+                {
+                    edits.push((selection, content.to_string()));
+                }
+            } else {
+                edits.push(crate::indent::create_shift_in(
+                    buffer, line, indent, units,
+                ));
+            }
+        }
+        let edits = edits
+            .iter()
+            .map(|(selection, content)| (selection, content.as_str()))
+            .collect::<Vec<_>>();
+        let edit_type = if outdent {
+            EditType::Outdent
+        } else {
+            EditType::Indent
+        };
+        buffer.edit(&edits, edit_type)
+    }
 
-        let mut lines = HashSet::new();
+    /// The non-blank lines touched by the selection, with their content.
+    fn selected_lines(
+        buffer: &Buffer,
+        selection: &Selection,
+    ) -> BTreeMap<usize, String> {
+        let mut lines = BTreeMap::new();
         for region in selection.regions() {
             let start_line = buffer.line_of_offset(region.min());
-            let mut end_line = buffer.line_of_offset(region.max());
-            if end_line > start_line {
-                let end_line_start = buffer.offset_of_line(end_line);
-                if end_line_start == region.max() {
-                    end_line -= 1;
+            let end_line = buffer.line_of_offset(region.max());
+            let end_line_offset = buffer.offset_of_line(end_line);
+            let end = if end_line > start_line && region.max() == end_line_offset {
+                end_line_offset
+            } else {
+                buffer.offset_of_line(end_line + 1)
+            };
+            let start = buffer.offset_of_line(start_line);
+            for (i, content) in buffer.text().lines(start..end).enumerate() {
+                if !content.trim().is_empty() {
+                    lines.insert(start_line + i, content.to_string());
                 }
             }
-            for line in start_line..=end_line {
-                if lines.insert(line) {
-                    let line_content = buffer.line_content(line);
-                    if line_content == "\n" || line_content == "\r\n" {
-                        continue;
-                    }
-                    let nonblank = buffer.first_non_blank_character_on_line(line);
-                    if let Some(edit) =
-                        crate::indent::create_outdent(buffer, nonblank, indent)
-                    {
-                        edits.push(edit);
-                    }
-                }
+        }
+        lines
+    }
+
+    fn toggle_line_comment(
+        buffer: &mut Buffer,
+        selection: &Selection,
+        token: &str,
+    ) -> (RopeDelta, InvalLines, SyntaxEdit) {
+        let lines = Self::selected_lines(buffer, selection);
+        let had_comment = lines
+            .values()
+            .all(|content| content.trim_start().starts_with(token));
+
+        let mut selection = Selection::new();
+        if had_comment {
+            for (line, content) in lines.iter() {
+                let trimmed_content = content.trim_start();
+                let indent = content.len() - trimmed_content.len();
+                let had_space_after_comment =
+                    trimmed_content[token.len()..].starts_with(' ');
+                let start = buffer.offset_of_line(*line) + indent;
+                selection.add_region(SelRegion::new(
+                    start,
+                    start + token.len() + usize::from(had_space_after_comment),
+                    None,
+                ));
             }
+            buffer.edit(&[(&selection, "")], EditType::ToggleComment)
+        } else {
+            let smallest_indent = lines
+                .values()
+                .map(|content| content.len() - content.trim_start().len())
+                .min()
+                .unwrap_or(0);
+            for line in lines.keys() {
+                let start = buffer.offset_of_line(*line) + smallest_indent;
+                selection.add_region(SelRegion::caret(start));
+            }
+            buffer.edit(
+                &[(&selection, &format!("{token} "))],
+                EditType::ToggleComment,
+            )
         }
+    }
 
-        buffer.edit(&edits, EditType::Outdent)
+    /// Toggle comments for languages without line comments by wrapping each
+    /// line in a block comment of its own.
+    fn toggle_block_comment(
+        buffer: &mut Buffer,
+        selection: &Selection,
+        start_token: &str,
+        end_token: &str,
+    ) -> (RopeDelta, InvalLines, SyntaxEdit) {
+        let lines = Self::selected_lines(buffer, selection);
+        let had_comment = lines.values().all(|content| {
+            let trimmed_content = content.trim();
+            trimmed_content.len() >= start_token.len() + end_token.len()
+                && trimmed_content.starts_with(start_token)
+                && trimmed_content.ends_with(end_token)
+        });
+
+        let mut starts = Selection::new();
+        let mut ends = Selection::new();
+        if had_comment {
+            for (line, content) in lines.iter() {
+                let line_start = buffer.offset_of_line(*line);
+                let trimmed_content = content.trim();
+                let indent = content.len() - content.trim_start().len();
+                let inner = &trimmed_content
+                    [start_token.len()..trimmed_content.len() - end_token.len()];
+                let start_len =
+                    start_token.len() + usize::from(inner.starts_with(' '));
+                let end_len = end_token.len()
+                    + usize::from(inner.len() > 1 && inner.ends_with(' '));
+
+                let start = line_start + indent;
+                let end = start + trimmed_content.len();
+                starts.add_region(SelRegion::new(start, start + start_len, None));
+                ends.add_region(SelRegion::new(end - end_len, end, None));
+            }
+            buffer.edit(&[(&starts, ""), (&ends, "")], EditType::ToggleComment)
+        } else {
+            let smallest_indent = lines
+                .values()
+                .map(|content| content.len() - content.trim_start().len())
+                .min()
+                .unwrap_or(0);
+            for (line, content) in lines.iter() {
+                let line_start = buffer.offset_of_line(*line);
+                starts.add_region(SelRegion::caret(line_start + smallest_indent));
+                ends.add_region(SelRegion::caret(
+                    line_start + content.trim_end().len(),
+                ));
+            }
+            buffer.edit(
+                &[
+                    (&starts, &format!("{start_token} ")),
+                    (&ends, &format!(" {end_token}")),
+                ],
+                EditType::ToggleComment,
+            )
+        }
     }
 
     fn duplicate_line(
@@ -700,12 +935,80 @@ fn duplicate_line(
         vec![(delta, inval_lines, edits)]
     }
 
+    /// Put the cursors back where they were before the edits that were undone,
+    /// or after the ones that were redone, as `restored` recorded them, in a
+    /// mode the editor can be in. Without a record, the cursor goes to the
+    /// first change.
+    fn restore_cursor(
+        cursor: &mut Cursor,
+        buffer: &Buffer,
+        delta: &RopeDelta,
+        restored: Option<CursorMode>,
+        modal: bool,
+    ) {
+        let Some(mode) = restored else {
+            if let Some(new_cursor) =
+                get_first_selection_after(cursor, buffer, delta)
+            {
+                *cursor = new_cursor;
+            } else {
+                cursor.apply_delta(delta);
+            }
+            return;
+        };
+        cursor.mode = match mode {
+            CursorMode::Insert(_) if modal => CursorMode::Normal(mode.offset()),
+            CursorMode::Normal(_) | CursorMode::Visual { .. } if !modal => {
+                CursorMode::Insert(Selection::caret(mode.offset()))
+            }
+            mode => mode,
+        };
+        // In case the text isn't the one the cursors were recorded with
+        cursor.clamp(buffer);
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub fn do_edit<T: Clipboard>(
         cursor: &mut Cursor,
         buffer: &mut Buffer,
         cmd: &EditCommand,
-        syntax: &Syntax,
+        clipboard: &mut T,
+        modal: bool,
+        register: &mut Register,
+        smart_tab: bool,
+    ) -> Vec<(RopeDelta, InvalLines, SyntaxEdit)> {
+        let old_cursor = cursor.mode.clone();
+        // What's selected is kept in the delete register when the command
+        // changes it
+        let yank_data = if let CursorMode::Visual { .. } = &cursor.mode {
+            Some(cursor.yank(buffer))
+        } else {
+            None
+        };
+
+        let deltas = Self::edit_command(
+            cursor, buffer, cmd, clipboard, modal, register, smart_tab,
+        );
+        // Whatever the command left it at, the cursor stays where it can be
+        // in its mode
+        cursor.clamp(buffer);
+
+        if !deltas.is_empty() {
+            if let Some(data) = yank_data {
+                register.add_delete(data);
+            }
+            // Keep track of the change in the cursor mode for undo/redo
+            buffer.set_cursor_before(old_cursor);
+            buffer.set_cursor_after(cursor.mode.clone());
+        }
+        deltas
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn edit_command<T: Clipboard>(
+        cursor: &mut Cursor,
+        buffer: &mut Buffer,
+        cmd: &EditCommand,
         clipboard: &mut T,
         modal: bool,
         register: &mut Register,
@@ -802,7 +1105,23 @@ pub fn do_edit<T: Clipboard>(
             InsertTab => {
                 let mut deltas = Vec::new();
                 if let CursorMode::Insert(selection) = &cursor.mode {
-                    if smart_tab {
+                    let multi_line = selection.regions().iter().any(|region| {
+                        buffer.line_of_offset(region.min())
+                            != buffer.line_of_offset(region.max())
+                    });
+                    if multi_line {
+                        // The selected lines are indented rather than replaced
+                        let lines = Self::selection_lines(buffer, selection);
+                        let (delta, inval_lines, edits) =
+                            Self::shift_lines(buffer, lines, 1, false);
+                        let selection = selection.apply_delta(
+                            &delta,
+                            true,
+                            InsertDrift::Default,
+                        );
+                        deltas.push((delta, inval_lines, edits));
+                        cursor.mode = CursorMode::Insert(selection);
+                    } else if smart_tab {
                         let indent = buffer.indent_unit();
                         let mut edits = Vec::new();
 
@@ -853,7 +1172,9 @@ pub fn do_edit<T: Clipboard>(
             }
             IndentLine => {
                 let selection = cursor.edit_selection(buffer);
-                let (delta, inval_lines, edits) = Self::do_indent(buffer, selection);
+                let lines = Self::selection_lines(buffer, &selection);
+                let (delta, inval_lines, edits) =
+                    Self::shift_lines(buffer, lines, 1, false);
                 cursor.apply_delta(&delta);
                 vec![(delta, inval_lines, edits)]
             }
@@ -873,77 +1194,23 @@ pub fn do_edit<T: Clipboard>(
             }
             OutdentLine => {
                 let selection = cursor.edit_selection(buffer);
+                let lines = Self::selection_lines(buffer, &selection);
                 let (delta, inval_lines, edits) =
-                    Self::do_outdent(buffer, selection);
+                    Self::shift_lines(buffer, lines, 1, true);
                 cursor.apply_delta(&delta);
                 vec![(delta, inval_lines, edits)]
             }
             ToggleLineComment => {
-                let mut lines = HashSet::new();
                 let selection = cursor.edit_selection(buffer);
-                let comment_token = syntax.language.comment_token();
-                let mut had_comment = true;
-                let mut smallest_indent = usize::MAX;
-                for region in selection.regions() {
-                    let mut line = buffer.line_of_offset(region.min());
-                    let end_line = buffer.line_of_offset(region.max());
-                    let end_line_offset = buffer.offset_of_line(end_line);
-                    let end = if end_line > line && region.max() == end_line_offset {
-                        end_line_offset
+                let config = buffer.language_config();
+                let (delta, inval_lines, edits) =
+                    if let Some(token) = config.line_comment.clone() {
+                        Self::toggle_line_comment(buffer, &selection, &token)
+                    } else if let Some((start, end)) = config.block_comment.clone() {
+                        Self::toggle_block_comment(buffer, &selection, &start, &end)
                     } else {
-                        buffer.offset_of_line(end_line + 1)
+                        return vec![];
                     };
-                    let start = buffer.offset_of_line(line);
-                    for content in buffer.text().lines(start..end) {
-                        let trimmed_content = content.trim_start();
-                        if trimmed_content.is_empty() {
-                            line += 1;
-                            continue;
-                        }
-                        let indent = content.len() - trimmed_content.len();
-                        if indent < smallest_indent {
-                            smallest_indent = indent;
-                        }
-                        if !trimmed_content.starts_with(comment_token) {
-                            had_comment = false;
-                            lines.insert((line, indent, 0));
-                        } else {
-                            let had_space_after_comment =
-                                trimmed_content.chars().nth(comment_token.len())
-                                    == Some(' ');
-                            lines.insert((
-                                line,
-                                indent,
-                                comment_token.len()
-                                    + usize::from(had_space_after_comment),
-                            ));
-                        }
-                        line += 1;
-                    }
-                }
-
-                let (delta, inval_lines, edits) = if had_comment {
-                    let mut selection = Selection::new();
-                    for (line, indent, len) in lines.iter() {
-                        let start = buffer.offset_of_line(*line) + indent;
-                        selection.add_region(SelRegion::new(
-                            start,
-                            start + len,
-                            None,
-                        ))
-                    }
-                    buffer.edit(&[(&selection, "")], EditType::ToggleComment)
-                } else {
-                    let mut selection = Selection::new();
-                    for (line, _, _) in lines.iter() {
-                        let start = buffer.offset_of_line(*line) + smallest_indent;
-                        selection.add_region(SelRegion::new(start, start, None))
-                    }
-                    buffer.edit(
-                        &[(&selection, &format!("{comment_token} "))],
-                        EditType::ToggleComment,
-                    )
-                };
                 cursor.apply_delta(&delta);
                 vec![(delta, inval_lines, edits)]
             }
@@ -951,23 +1218,7 @@ pub fn do_edit<T: Clipboard>(
                 if let Some((delta, inval_lines, edits, cursor_mode)) =
                     buffer.do_undo()
                 {
-                    if let Some(cursor_mode) = cursor_mode {
-                        cursor.mode = if modal {
-                            CursorMode::Normal(cursor_mode.offset())
-                        } else if cursor.is_insert() {
-                            cursor_mode
-                        } else {
-                            CursorMode::Insert(Selection::caret(
-                                cursor_mode.offset(),
-                            ))
-                        };
-                    } else if let Some(new_cursor) =
-                        get_first_selection_after(cursor, buffer, &delta)
-                    {
-                        *cursor = new_cursor
-                    } else {
-                        cursor.apply_delta(&delta);
-                    }
+                    Self::restore_cursor(cursor, buffer, &delta, cursor_mode, modal);
                     vec![(delta, inval_lines, edits)]
                 } else {
                     vec![]
@@ -977,23 +1228,7 @@ pub fn do_edit<T: Clipboard>(
                 if let Some((delta, inval_lines, edits, cursor_mode)) =
                     buffer.do_redo()
                 {
-                    if let Some(cursor_mode) = cursor_mode {
-                        cursor.mode = if modal {
-                            CursorMode::Normal(cursor_mode.offset())
-                        } else if cursor.is_insert() {
-                            cursor_mode
-                        } else {
-                            CursorMode::Insert(Selection::caret(
-                                cursor_mode.offset(),
-                            ))
-                        };
-                    } else if let Some(new_cursor) =
-                        get_first_selection_after(cursor, buffer, &delta)
-                    {
-                        *cursor = new_cursor
-                    } else {
-                        cursor.apply_delta(&delta);
-                    }
+                    Self::restore_cursor(cursor, buffer, &delta, cursor_mode, modal);
                     vec![(delta, inval_lines, edits)]
                 } else {
                     vec![]
@@ -1009,9 +1244,8 @@ pub fn do_edit<T: Clipboard>(
                         end,
                         mode: _,
                     } => {
-                        let offset = *start.min(end);
                         let offset =
-                            buffer.offset_line_end(offset, false).min(offset);
+                            buffer.clamp_offset(*start.min(end), Mode::Normal);
                         cursor.mode = CursorMode::Normal(offset);
                     }
                     CursorMode::Normal(_) | CursorMode::Insert(_) => {}
@@ -1064,9 +1298,8 @@ pub fn do_edit<T: Clipboard>(
                         let data = cursor.yank(buffer);
                         register.add_yank(data);
 
-                        let offset = *start.min(end);
                         let offset =
-                            buffer.offset_line_end(offset, false).min(offset);
+                            buffer.clamp_offset(*start.min(end), Mode::Normal);
                         cursor.mode = CursorMode::Normal(offset);
                     }
                     CursorMode::Normal(_) => {}
@@ -1169,34 +1402,27 @@ pub fn do_edit<T: Clipboard>(
                                     selection.min_offset()..selection.max_offset(),
                                 )
                                 .to_string();
-                            if str_is_pair_left(&delete_str)
-                                || delete_str == "\""
-                                || delete_str == "'"
-                            {
-                                let matching_char = match delete_str.as_str() {
-                                    "\"" => Some('"'),
-                                    "'" => Some('\''),
-                                    _ => str_matching_pair(&delete_str),
-                                };
-                                if let Some(c) = matching_char {
-                                    let offset = selection.max_offset();
-                                    let line = buffer.line_of_offset(offset);
-                                    let line_end =
-                                        buffer.line_end_offset(line, true);
-                                    let content = buffer
-                                        .slice_to_cow(offset..line_end)
-                                        .to_string();
-                                    if content.trim().starts_with(&c.to_string()) {
-                                        let index = content
-                                            .match_indices(c)
-                                            .next()
-                                            .unwrap()
-                                            .0;
-                                        selection = Selection::region(
-                                            selection.min_offset(),
-                                            offset + index + 1,
-                                        );
-                                    }
+                            let mut chars = delete_str.chars();
+                            let closing_pair = match (chars.next(), chars.next()) {
+                                (Some(c), None) => {
+                                    buffer.language_config().closing_pair(c)
+                                }
+                                _ => None,
+                            };
+                            if let Some(c) = closing_pair {
+                                let offset = selection.max_offset();
+                                let line = buffer.line_of_offset(offset);
+                                let line_end = buffer.line_end_offset(line, true);
+                                let content = buffer
+                                    .slice_to_cow(offset..line_end)
+                                    .to_string();
+                                if content.trim().starts_with(&c.to_string()) {
+                                    let index =
+                                        content.match_indices(c).next().unwrap().0;
+                                    selection = Selection::region(
+                                        selection.min_offset(),
+                                        offset + index + 1,
+                                    );
                                 }
                             }
                         }
@@ -1402,7 +1628,10 @@ pub fn do_edit<T: Clipboard>(
                     true,
                     true,
                 );
-                let selection = Selection::region(start, end - 1); // -1 because we want to keep the line itself
+                // Keep the line ending, as the line itself stays
+                let end_line = buffer.line_of_offset(end.saturating_sub(1));
+                let end = buffer.line_end_offset(end_line, true).max(start);
+                let selection = Selection::region(start, end);
                 let (delta, inval_lines, edits) =
                     buffer.edit(&[(&selection, "")], EditType::Delete);
                 let selection =
@@ -1465,7 +1694,7 @@ pub fn do_edit<T: Clipboard>(
                         )
                     }
                     CursorMode::Visual { end, .. } => {
-                        buffer.offset_line_end(*end, false).min(*end)
+                        buffer.clamp_offset(*end, Mode::Normal)
                     }
                     CursorMode::Normal(offset) => *offset,
                 };
@@ -1544,14 +1773,36 @@ enum DuplicateDirection {
 
 #[cfg(test)]
 mod test {
+    use std::sync::Arc;
+
+    use strum::IntoEnumIterator;
+
     use crate::{
         buffer::{rope_text::RopeText, Buffer},
+        command::EditCommand,
         cursor::{Cursor, CursorMode},
         editor::{DuplicateDirection, Editor},
+        language::LapceLanguage,
+        language_config::LanguageConfig,
+        mode::{Mode, MotionMode, VisualMode},
+        register::{Clipboard, Register, RegisterData},
         selection::{SelRegion, Selection},
         syntax::Syntax,
     };
 
+    #[derive(Default)]
+    struct TestClipboard(Option<String>);
+
+    impl Clipboard for TestClipboard {
+        fn get_string(&mut self) -> Option<String> {
+            self.0.clone()
+        }
+
+        fn put_string(&mut self, s: impl AsRef<str>) {
+            self.0 = Some(s.as_ref().to_string());
+        }
+    }
+
     #[test]
     fn test_insert_simple() {
         let mut buffer = Buffer::new("abc");
@@ -1631,6 +1882,114 @@ fn test_insert_pair_without_auto_closing() {
         assert_eq!("a{} bc\ne{} fg\n", buffer.slice_to_cow(0..buffer.len()));
     }
 
+    #[cfg(feature = "lang-rust")]
+    #[test]
+    fn test_insert_pair_in_literal() {
+        let mut buffer = Buffer::new("let s = \"a \"; // b \n");
+        let mut syntax = Syntax::from_language(LapceLanguage::Rust);
+        syntax.parse(0, buffer.text().clone(), None);
+        let mut selection = Selection::new();
+        selection.add_region(SelRegion::caret(10));
+        selection.add_region(SelRegion::caret(18));
+        let mut cursor = Cursor::new(CursorMode::Insert(selection), None, None);
+
+        Editor::insert(&mut cursor, &mut buffer, "{", &syntax, true);
+        assert_eq!(
+            "let s = \"a{ \"; // b{ \n",
+            buffer.slice_to_cow(0..buffer.len())
+        );
+
+        // Outside of them they're closed
+        let mut buffer = Buffer::new("let s = \"a \"; // b \n");
+        let mut cursor =
+            Cursor::new(CursorMode::Insert(Selection::caret(8)), None, None);
+        Editor::insert(&mut cursor, &mut buffer, "(", &syntax, true);
+        assert_eq!(
+            "let s = ()\"a \"; // b \n",
+            buffer.slice_to_cow(0..buffer.len())
+        );
+    }
+
+    #[test]
+    fn test_insert_pair_language_config() {
+        let mut buffer = Buffer::new("a \n");
+        buffer
+            .set_language_config(Arc::new(LanguageConfig::new(LapceLanguage::Rust)));
+        let mut cursor =
+            Cursor::new(CursorMode::Insert(Selection::caret(2)), None, None);
+
+        Editor::insert(&mut cursor, &mut buffer, "'", &Syntax::plaintext(), true);
+        assert_eq!("a '\n", buffer.slice_to_cow(0..buffer.len()));
+        Editor::insert(&mut cursor, &mut buffer, " ", &Syntax::plaintext(), true);
+        Editor::insert(&mut cursor, &mut buffer, "\"", &Syntax::plaintext(), true);
+        assert_eq!("a ' \"\"\n", buffer.slice_to_cow(0..buffer.len()));
+    }
+
+    #[test]
+    fn test_continue_lists() {
+        let mut buffer = Buffer::new("  1. a");
+        buffer.set_language_config(Arc::new(LanguageConfig::new(
+            LapceLanguage::Markdown,
+        )));
+        let mut cursor =
+            Cursor::new(CursorMode::Insert(Selection::caret(6)), None, None);
+        edit(&mut cursor, &mut buffer, EditCommand::InsertNewLine, false);
+        assert_eq!("  1. a\n  2. ", buffer.slice_to_cow(0..buffer.len()));
+        assert_eq!(cursor.offset(), buffer.len());
+
+        // An empty item ends the list
+        edit(&mut cursor, &mut buffer, EditCommand::InsertNewLine, false);
+        assert_eq!("  1. a\n", buffer.slice_to_cow(0..buffer.len()));
+        assert_eq!(cursor.offset(), buffer.len());
+
+        // Without the rule, the new line only keeps the indent
+        let mut buffer = Buffer::new("  - a");
+        let mut cursor =
+            Cursor::new(CursorMode::Insert(Selection::caret(5)), None, None);
+        edit(&mut cursor, &mut buffer, EditCommand::InsertNewLine, false);
+        assert_eq!("  - a\n  ", buffer.slice_to_cow(0..buffer.len()));
+    }
+
+    #[test]
+    fn test_auto_close_tags() {
+        let mut buffer = Buffer::new("<div class=\"a\"\n");
+        buffer
+            .set_language_config(Arc::new(LanguageConfig::new(LapceLanguage::Html)));
+        let mut cursor =
+            Cursor::new(CursorMode::Insert(Selection::caret(14)), None, None);
+        Editor::insert(&mut cursor, &mut buffer, ">", &Syntax::plaintext(), true);
+        assert_eq!(
+            "<div class=\"a\"></div>\n",
+            buffer.slice_to_cow(0..buffer.len())
+        );
+        assert_eq!(cursor.offset(), 15);
+
+        Editor::insert(&mut cursor, &mut buffer, "<", &Syntax::plaintext(), true);
+        Editor::insert(&mut cursor, &mut buffer, "b", &Syntax::plaintext(), true);
+        Editor::insert(&mut cursor, &mut buffer, "r", &Syntax::plaintext(), true);
+        Editor::insert(&mut cursor, &mut buffer, ">", &Syntax::plaintext(), true);
+        assert_eq!(
+            "<div class=\"a\"><br></div>\n",
+            buffer.slice_to_cow(0..buffer.len())
+        );
+    }
+
+    #[test]
+    fn test_toggle_block_comment() {
+        let mut buffer = Buffer::new("a {\n  b;\n\n}\n");
+        let selection = Selection::region(0, buffer.len());
+
+        Editor::toggle_block_comment(&mut buffer, &selection, "/*", "*/");
+        assert_eq!(
+            "/* a { */\n/*   b; */\n\n/* } */\n",
+            buffer.slice_to_cow(0..buffer.len())
+        );
+
+        let selection = Selection::region(0, buffer.len());
+        Editor::toggle_block_comment(&mut buffer, &selection, "/*", "*/");
+        assert_eq!("a {\n  b;\n\n}\n", buffer.slice_to_cow(0..buffer.len()));
+    }
+
     #[test]
     fn duplicate_down_simple() {
         let mut buffer = Buffer::new("first line\nsecond line\n");
@@ -1784,5 +2143,381 @@ fn check_multiple_cursor_match_insertion() {
         assert_eq!(cursor.mode, CursorMode::Insert(end_selection));
     }
 
+    #[test]
+    fn test_edit_commands_at_the_extremes() {
+        let pastes = [
+            RegisterData::default(),
+            RegisterData {
+                content: "x".to_string(),
+                mode: VisualMode::Normal,
+            },
+            RegisterData {
+                content: "x\n".to_string(),
+                mode: VisualMode::Linewise,
+            },
+        ];
+        for text in ["", "a", "abc", "abc\n", "a\r\n"] {
+            let len = text.len();
+            let buffer = Buffer::new(text);
+            let last = buffer.clamp_offset(len, Mode::Normal);
+            let modes = [
+                CursorMode::Normal(0),
+                CursorMode::Normal(last),
+                CursorMode::Insert(Selection::caret(0)),
+                CursorMode::Insert(Selection::caret(len)),
+                CursorMode::Insert(Selection::region(0, len)),
+                CursorMode::Visual {
+                    start: 0,
+                    end: buffer.clamp_offset(len, Mode::Visual),
+                    mode: VisualMode::Normal,
+                },
+                CursorMode::Visual {
+                    start: last,
+                    end: 0,
+                    mode: VisualMode::Linewise,
+                },
+                CursorMode::Visual {
+                    start: 0,
+                    end: last,
+                    mode: VisualMode::Blockwise,
+                },
+            ];
+            for cmd in EditCommand::iter() {
+                for mode in &modes {
+                    for paste in &pastes {
+                        let mut buffer = Buffer::new(text);
+                        let mut cursor = Cursor::new(mode.clone(), None, None);
+                        let mut register = Register::default();
+                        register.add_yank(paste.clone());
+                        let mut clipboard =
+                            TestClipboard(Some(paste.content.clone()));
+                        Editor::do_edit(
+                            &mut cursor,
+                            &mut buffer,
+                            &cmd,
+                            &mut clipboard,
+                            !cursor.is_insert(),
+                            &mut register,
+                            true,
+                        );
+
+                        let len = buffer.len();
+                        let offsets = match &cursor.mode {
+                            CursorMode::Normal(offset) => vec![*offset],
+                            CursorMode::Visual { start, end, .. } => {
+                                vec![*start, *end]
+                            }
+                            CursorMode::Insert(selection) => selection
+                                .regions()
+                                .iter()
+                                .flat_map(|region| [region.start, region.end])
+                                .collect(),
+                        };
+                        for offset in offsets {
+                            assert_eq!(
+                                buffer.clamp_offset(offset, cursor.get_mode()),
+                                offset,
+                                "{cmd:?} on {text:?} from {mode:?} left the \
+                                 cursor at {:?} in {:?}",
+                                cursor.mode,
+                                buffer.slice_to_cow(0..len),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_shift_lines() {
+        let mut buffer = Buffer::new("a\n  b  c\n\n   \nd");
+        Editor::shift_lines(&mut buffer, 0..=4, 1, false);
+        assert_eq!(
+            "    a\n      b  c\n\n   \n    d",
+            buffer.slice_to_cow(0..buffer.len())
+        );
+        // Outdenting stops at the text
+        Editor::shift_lines(&mut buffer, 0..=4, 2, true);
+        assert_eq!("a\nb  c\n\n   \nd", buffer.slice_to_cow(0..buffer.len()));
+
+        let mut buffer = Buffer::new("a\n\tb\n\t  c");
+        buffer.detect_indent();
+        Editor::shift_lines(&mut buffer, 1..=2, 1, false);
+        assert_eq!("a\n\t\tb\n\t\t  c", buffer.slice_to_cow(0..buffer.len()));
+        Editor::shift_lines(&mut buffer, 0..=2, 2, true);
+        assert_eq!("a\nb\n  c", buffer.slice_to_cow(0..buffer.len()));
+
+        let mut buffer = Buffer::new("a\n\nb");
+        let mut config = LanguageConfig::default();
+        config.shift_blank_lines = true;
+        buffer.set_language_config(Arc::new(config));
+        Editor::shift_lines(&mut buffer, 0..=2, 1, false);
+        assert_eq!("    a\n    \n    b", buffer.slice_to_cow(0..buffer.len()));
+    }
+
+    #[test]
+    fn test_shift_operator() {
+        let mut buffer = Buffer::new("a\n  b\nc");
+        let mut cursor = Cursor::new(CursorMode::Normal(0), None, None);
+        Editor::execute_motion_mode(
+            &mut cursor,
+            &mut buffer,
+            MotionMode::Indent { count: 2 },
+            0,
+            0,
+            true,
+            &mut Register::default(),
+        );
+        assert_eq!("    a\n      b\nc", buffer.slice_to_cow(0..buffer.len()));
+        assert_eq!(cursor.mode, CursorMode::Normal(4));
+
+        Editor::execute_motion_mode(
+            &mut cursor,
+            &mut buffer,
+            MotionMode::Outdent { count: 1 },
+            4,
+            10,
+            true,
+            &mut Register::default(),
+        );
+        assert_eq!("a\n  b\nc", buffer.slice_to_cow(0..buffer.len()));
+        assert_eq!(cursor.mode, CursorMode::Normal(0));
+    }
+
+    #[test]
+    fn test_tab_on_lines() {
+        let mut buffer = Buffer::new("ab\ncd\nef");
+        let mut cursor =
+            Cursor::new(CursorMode::Insert(Selection::region(1, 4)), None, None);
+        edit(&mut cursor, &mut buffer, EditCommand::InsertTab, false);
+        assert_eq!("    ab\n    cd\nef", buffer.slice_to_cow(0..buffer.len()));
+
+        edit(&mut cursor, &mut buffer, EditCommand::OutdentLine, false);
+        assert_eq!("ab\ncd\nef", buffer.slice_to_cow(0..buffer.len()));
+
+        // A region ending at the start of a line leaves that line out
+        let mut cursor =
+            Cursor::new(CursorMode::Insert(Selection::region(0, 3)), None, None);
+        edit(&mut cursor, &mut buffer, EditCommand::InsertTab, false);
+        assert_eq!("    ab\ncd\nef", buffer.slice_to_cow(0..buffer.len()));
+    }
+
+    #[test]
+    fn test_paste_lines_after_the_last_line() {
+        let mut buffer = Buffer::new("abc");
+        let mut cursor = Cursor::new(CursorMode::Normal(1), None, None);
+        let data = RegisterData {
+            content: "x\n".to_string(),
+            mode: VisualMode::Linewise,
+        };
+        Editor::do_paste(&mut cursor, &mut buffer, &data);
+        assert_eq!("abc\nx", buffer.slice_to_cow(0..buffer.len()));
+        assert_eq!(cursor.mode, CursorMode::Normal(4));
+
+        let mut buffer = Buffer::new("");
+        let mut cursor = Cursor::new(CursorMode::Normal(0), None, None);
+        Editor::do_paste(&mut cursor, &mut buffer, &data);
+        assert_eq!("\nx", buffer.slice_to_cow(0..buffer.len()));
+        assert_eq!(cursor.mode, CursorMode::Normal(1));
+    }
+
+    #[test]
+    fn test_large_paste() {
+        let content = (0..100_000)
+            .map(|i| format!("    let line_{i} = ({i}, \"{{\");\n"))
+            .collect::<String>();
+        let mut buffer = Buffer::new("fn main() {\n}\n");
+        let before = buffer.text().clone();
+        let mut cursor =
+            Cursor::new(CursorMode::Insert(Selection::caret(12)), None, None);
+
+        let start = std::time::Instant::now();
+        let deltas = Editor::do_edit(
+            &mut cursor,
+            &mut buffer,
+            &EditCommand::ClipboardPaste,
+            &mut TestClipboard(Some(content.clone())),
+            false,
+            &mut Register::default(),
+            true,
+        );
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+        // A single edit, with the brackets and quotes left unpaired and the
+        // lines as they were
+        assert_eq!(deltas.len(), 1);
+        assert_eq!(buffer.len(), before.len() + content.len());
+        assert_eq!(
+            buffer.slice_to_cow(12..12 + content.len()),
+            content.as_str()
+        );
+
+        edit(&mut cursor, &mut buffer, EditCommand::Undo, false);
+        assert_eq!(buffer.text().to_string(), before.to_string());
+    }
+
+    #[test]
+    fn test_insert_lines_is_undone_on_its_own() {
+        let mut buffer = Buffer::new("");
+        let mut cursor =
+            Cursor::new(CursorMode::Insert(Selection::caret(0)), None, None);
+        for s in ["a", "b", "c\nd\n", "e"] {
+            Editor::insert(&mut cursor, &mut buffer, s, &Syntax::plaintext(), true);
+        }
+        assert_eq!("abc\nd\ne", buffer.slice_to_cow(0..buffer.len()));
+
+        edit(&mut cursor, &mut buffer, EditCommand::Undo, false);
+        assert_eq!("abc\nd\n", buffer.slice_to_cow(0..buffer.len()));
+        edit(&mut cursor, &mut buffer, EditCommand::Undo, false);
+        assert_eq!("ab", buffer.slice_to_cow(0..buffer.len()));
+    }
+
+    #[test]
+    fn test_delete_line_and_insert_keeps_the_line() {
+        for (text, expected) in [("", ""), ("  abc", "  "), ("abc\r\nd", "\r\nd")] {
+            let mut buffer = Buffer::new(text);
+            let mut cursor = Cursor::new(CursorMode::Normal(0), None, None);
+            Editor::do_edit(
+                &mut cursor,
+                &mut buffer,
+                &EditCommand::DeleteLineAndInsert,
+                &mut TestClipboard::default(),
+                true,
+                &mut Register::default(),
+                true,
+            );
+            assert_eq!(expected, buffer.slice_to_cow(0..buffer.len()));
+        }
+    }
+
+    /// Run `cmd` like the editor does, recording the cursors before and after
+    /// it for undo and redo.
+    fn edit(
+        cursor: &mut Cursor,
+        buffer: &mut Buffer,
+        cmd: EditCommand,
+        modal: bool,
+    ) {
+        Editor::do_edit(
+            cursor,
+            buffer,
+            &cmd,
+            &mut TestClipboard::default(),
+            modal,
+            &mut Register::default(),
+            true,
+        );
+    }
+
+    #[test]
+    fn test_undo_redo_multiple_cursors() {
+        let mut buffer = Buffer::new("abc\nefg\n");
+        let mut selection = Selection::new();
+        selection.add_region(SelRegion::caret(1));
+        selection.add_region(SelRegion::caret(5));
+        let before = CursorMode::Insert(selection);
+        let mut cursor = Cursor::new(before.clone(), None, None);
+
+        Editor::insert(&mut cursor, &mut buffer, "x", &Syntax::plaintext(), true);
+        Editor::insert(&mut cursor, &mut buffer, "y", &Syntax::plaintext(), true);
+        assert_eq!("axybc\nexyfg\n", buffer.slice_to_cow(0..buffer.len()));
+        let after = cursor.mode.clone();
+
+        // The cursors are moved away before undoing
+        cursor.mode = CursorMode::Insert(Selection::caret(0));
+        edit(&mut cursor, &mut buffer, EditCommand::Undo, false);
+        assert_eq!("abc\nefg\n", buffer.slice_to_cow(0..buffer.len()));
+        assert_eq!(cursor.mode, before);
+
+        cursor.mode = CursorMode::Insert(Selection::caret(0));
+        edit(&mut cursor, &mut buffer, EditCommand::Redo, false);
+        assert_eq!("axybc\nexyfg\n", buffer.slice_to_cow(0..buffer.len()));
+        assert_eq!(cursor.mode, after);
+    }
+
+    #[test]
+    fn test_undo_redo_selections() {
+        let mut buffer = Buffer::new("abc\nefg\nhij\n");
+        let mut selection = Selection::new();
+        selection.add_region(SelRegion::new(0, 2, None));
+        selection.add_region(SelRegion::new(10, 8, None));
+        let before = CursorMode::Insert(selection);
+        let mut cursor = Cursor::new(before.clone(), None, None);
+
+        edit(&mut cursor, &mut buffer, EditCommand::DeleteBackward, false);
+        assert_eq!("c\nefg\nj\n", buffer.slice_to_cow(0..buffer.len()));
+        let after = cursor.mode.clone();
+
+        edit(&mut cursor, &mut buffer, EditCommand::Undo, false);
+        assert_eq!("abc\nefg\nhij\n", buffer.slice_to_cow(0..buffer.len()));
+        assert_eq!(cursor.mode, before);
+
+        edit(&mut cursor, &mut buffer, EditCommand::Redo, false);
+        assert_eq!("c\nefg\nj\n", buffer.slice_to_cow(0..buffer.len()));
+        assert_eq!(cursor.mode, after);
+
+        // Undone again after being redone, they're back once more
+        edit(&mut cursor, &mut buffer, EditCommand::Undo, false);
+        assert_eq!(cursor.mode, before);
+    }
+
+    #[test]
+    fn test_undo_redo_visual_block() {
+        let mut buffer = Buffer::new("abcd\nefgh\nijkl\n");
+        let before = CursorMode::Visual {
+            start: 1,
+            end: 12,
+            mode: VisualMode::Blockwise,
+        };
+        let mut cursor = Cursor::new(before.clone(), None, None);
+
+        edit(&mut cursor, &mut buffer, EditCommand::DeleteForward, true);
+        assert_eq!("ad\neh\nil\n", buffer.slice_to_cow(0..buffer.len()));
+        let after = cursor.mode.clone();
+        assert_eq!(after, CursorMode::Normal(1));
+
+        cursor.mode = CursorMode::Normal(0);
+        edit(&mut cursor, &mut buffer, EditCommand::Undo, true);
+        assert_eq!("abcd\nefgh\nijkl\n", buffer.slice_to_cow(0..buffer.len()));
+        assert_eq!(cursor.mode, before);
+
+        cursor.mode = CursorMode::Normal(0);
+        edit(&mut cursor, &mut buffer, EditCommand::Redo, true);
+        assert_eq!("ad\neh\nil\n", buffer.slice_to_cow(0..buffer.len()));
+        assert_eq!(cursor.mode, after);
+    }
+
+    #[test]
+    fn test_undo_redo_in_the_mode_of_the_editor() {
+        let mut buffer = Buffer::new("abc\n");
+        let mut cursor =
+            Cursor::new(CursorMode::Insert(Selection::caret(3)), None, None);
+        Editor::insert(&mut cursor, &mut buffer, "d", &Syntax::plaintext(), true);
+
+        // Undone in normal mode, the cursor stays in normal mode, on the last
+        // character as it can't be past it
+        cursor.mode = CursorMode::Normal(0);
+        edit(&mut cursor, &mut buffer, EditCommand::Undo, true);
+        assert_eq!("abc\n", buffer.slice_to_cow(0..buffer.len()));
+        assert_eq!(cursor.mode, CursorMode::Normal(2));
+
+        // The other way around, the visual selection becomes a caret
+        let mut buffer = Buffer::new("abc\n");
+        let mut cursor = Cursor::new(
+            CursorMode::Visual {
+                start: 0,
+                end: 1,
+                mode: VisualMode::Normal,
+            },
+            None,
+            None,
+        );
+        edit(&mut cursor, &mut buffer, EditCommand::DeleteForward, true);
+        assert_eq!("c\n", buffer.slice_to_cow(0..buffer.len()));
+        cursor.mode = CursorMode::Insert(Selection::caret(0));
+        edit(&mut cursor, &mut buffer, EditCommand::Undo, false);
+        assert_eq!(cursor.mode, CursorMode::Insert(Selection::caret(1)));
+    }
+
     // TODO(dbuga): add tests duplicating selections (multiple line blocks)
 }
@@ -0,0 +1,402 @@
+//! An editor without a view, to script edits with and to test the editing
+//! commands against: it's made from a text, runs the commands by the names
+//! the keymaps give them, and its text, cursor, register and undo history can
+//! be read back.
+//!
+//! The lines aren't wrapped and each character is a column wide, so vertical
+//! movements go by characters rather than by where the text is drawn.
+
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::{
+    buffer::{
+        rope_text::{RopeText, RopeTextVal},
+        Buffer,
+    },
+    command::{EditCommand, MotionModeCommand, MoveCommand},
+    cursor::{Cursor, CursorMode},
+    editor::Editor,
+    mode::Mode,
+    motion::{self, MotionEdit, OperatorAction, TextLayout},
+    movement::Movement,
+    register::{Clipboard, Register, RegisterData},
+    syntax::Syntax,
+    word::WordCursor,
+};
+
+/// The width the lines are rewrapped to, as there's no view to take it from
+const REFLOW_WIDTH: usize = 80;
+
+/// A command name that's no edit, movement or operator
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("Unknown command `{0}`")]
+pub struct UnknownCommand(pub String);
+
+/// The clipboard of the editor, which only it sees
+#[derive(Default)]
+struct HeadlessClipboard(Option<String>);
+
+impl Clipboard for HeadlessClipboard {
+    fn get_string(&mut self) -> Option<String> {
+        self.0.clone()
+    }
+
+    fn put_string(&mut self, s: impl AsRef<str>) {
+        self.0 = Some(s.as_ref().to_string());
+    }
+}
+
+/// The text laid out a line per line and a column per character
+struct PlainLayout<'a> {
+    buffer: &'a Buffer,
+}
+
+impl TextLayout for PlainLayout<'_> {
+    fn rope_text(&self) -> RopeTextVal {
+        RopeTextVal::new(self.buffer.text().clone())
+    }
+
+    fn soft_tab_width(&self) -> Option<usize> {
+        None
+    }
+
+    fn visual_line(&self, line: usize) -> usize {
+        line
+    }
+
+    fn actual_line(&self, visual_line: usize, _bottom_affinity: bool) -> usize {
+        visual_line
+    }
+
+    fn offset_x(&self, offset: usize) -> f64 {
+        let line = self.buffer.line_of_offset(offset);
+        let start = self.buffer.offset_of_line(line);
+        self.buffer.slice_to_cow(start..offset).chars().count() as f64
+    }
+
+    fn col_at_x(&self, line: usize, x: f64) -> usize {
+        let content = self.buffer.line_content(line);
+        content
+            .char_indices()
+            .nth(x.round() as usize)
+            .map(|(col, _)| col)
+            .unwrap_or(content.len())
+    }
+
+    fn find_unmatched(&self, offset: usize, previous: bool, ch: char) -> usize {
+        let mut cursor = WordCursor::new(self.buffer.text(), offset);
+        let new_offset = if previous {
+            cursor.previous_unmatched(ch)
+        } else {
+            cursor.next_unmatched(ch)
+        };
+        new_offset.unwrap_or(offset)
+    }
+
+    fn find_matching_pair(&self, offset: usize) -> usize {
+        WordCursor::new(self.buffer.text(), offset)
+            .match_pairs()
+            .unwrap_or(offset)
+    }
+}
+
+pub struct HeadlessEditor {
+    buffer: Buffer,
+    cursor: Cursor,
+    register: Register,
+    clipboard: HeadlessClipboard,
+    syntax: Syntax,
+    modal: bool,
+}
+
+impl HeadlessEditor {
+    /// An editor of `text` with the cursor at its start, in normal mode when
+    /// `modal`.
+    pub fn new(text: &str, modal: bool) -> Self {
+        Self {
+            buffer: Buffer::new(text),
+            cursor: Cursor::origin(modal),
+            register: Register::default(),
+            clipboard: HeadlessClipboard::default(),
+            syntax: Syntax::plaintext(),
+            modal,
+        }
+    }
+
+    pub fn text(&self) -> String {
+        self.buffer.to_string()
+    }
+
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+
+    pub fn cursor(&self) -> &Cursor {
+        &self.cursor
+    }
+
+    /// Put the cursor at `mode`, clamped to the text.
+    pub fn set_cursor(&mut self, mode: CursorMode) {
+        self.cursor.mode = mode;
+        self.cursor.horiz = None;
+        self.cursor.clamp(&self.buffer);
+    }
+
+    pub fn offset(&self) -> usize {
+        self.cursor.offset()
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.cursor.get_mode()
+    }
+
+    /// What a paste puts in, which is the last text yanked or deleted
+    pub fn register(&self) -> &RegisterData {
+        &self.register.unnamed
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.buffer.can_undo()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.buffer.can_redo()
+    }
+
+    /// Run the edit, movement or operator named `name`, like `delete_line`,
+    /// `word_forward` or `motion_mode_delete`.
+    pub fn run(&mut self, name: &str) -> Result<(), UnknownCommand> {
+        self.run_count(name, None)
+    }
+
+    /// Run the command named `name` with the count typed before it, which
+    /// edits don't take.
+    pub fn run_count(
+        &mut self,
+        name: &str,
+        count: Option<usize>,
+    ) -> Result<(), UnknownCommand> {
+        if let Ok(cmd) = EditCommand::from_str(name) {
+            self.run_edit(&cmd);
+        } else if let Ok(cmd) = MoveCommand::from_str(name) {
+            let movement = cmd.to_movement(count);
+            self.run_move(&movement, count.unwrap_or(1), false);
+        } else if let Ok(cmd) = MotionModeCommand::from_str(name) {
+            self.run_motion_mode(&cmd, count);
+        } else {
+            return Err(UnknownCommand(name.to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn run_edit(&mut self, cmd: &EditCommand) {
+        Editor::do_edit(
+            &mut self.cursor,
+            &mut self.buffer,
+            cmd,
+            &mut self.clipboard,
+            self.modal,
+            &mut self.register,
+            true,
+        );
+    }
+
+    /// Move the cursor, or extend the selection when `modify`, by `count`
+    /// times `movement`, applying the operator waiting for it if there's one.
+    pub fn run_move(&mut self, movement: &Movement, count: usize, modify: bool) {
+        let layout = PlainLayout {
+            buffer: &self.buffer,
+        };
+        let edit =
+            motion::move_cursor(&layout, &mut self.cursor, movement, count, modify);
+        if let Some(edit) = edit {
+            self.apply_motion_edit(edit);
+        }
+    }
+
+    /// Have the operator `cmd` wait for its movement, or apply it to the
+    /// selected lines in visual mode, like the editor does.
+    pub fn run_motion_mode(
+        &mut self,
+        cmd: &MotionModeCommand,
+        count: Option<usize>,
+    ) {
+        let action = motion::run_motion_mode(
+            &self.buffer,
+            &mut self.cursor,
+            cmd,
+            count,
+            REFLOW_WIDTH,
+        );
+        match action {
+            Some(OperatorAction::Edit(edit)) => self.apply_motion_edit(edit),
+            Some(OperatorAction::Shift {
+                lines,
+                count,
+                outdent,
+            }) => {
+                let start_line = *lines.start();
+                Editor::shift_lines(&mut self.buffer, lines, count, outdent);
+                let offset =
+                    self.buffer.first_non_blank_character_on_line(start_line);
+                self.cursor.mode = CursorMode::Normal(offset);
+                self.cursor.horiz = None;
+            }
+            None => {}
+        }
+    }
+
+    /// Type `text` in insert mode, a character at a time, with a new line for
+    /// each line break.
+    pub fn type_text(&mut self, text: &str) {
+        for c in text.chars() {
+            if c == '\n' {
+                self.run_edit(&EditCommand::InsertNewLine);
+            } else {
+                Editor::insert(
+                    &mut self.cursor,
+                    &mut self.buffer,
+                    &c.to_string(),
+                    &self.syntax,
+                    true,
+                );
+            }
+        }
+    }
+
+    fn apply_motion_edit(&mut self, edit: MotionEdit) {
+        Editor::execute_motion_mode(
+            &mut self.cursor,
+            &mut self.buffer,
+            edit.motion_mode,
+            edit.start,
+            edit.end,
+            edit.is_vertical,
+            &mut self.register,
+        );
+        self.cursor.clamp(&self.buffer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HeadlessEditor, UnknownCommand};
+    use crate::mode::{Mode, VisualMode};
+
+    #[test]
+    fn test_motions() {
+        let mut editor = HeadlessEditor::new("one two\nthree\n  four", true);
+        editor.run("word_forward").unwrap();
+        assert_eq!(editor.offset(), 4);
+        // Moving down keeps to the column the cursor was in
+        editor.run("down").unwrap();
+        assert_eq!(editor.offset(), 12);
+        editor.run("down").unwrap();
+        assert_eq!(editor.offset(), 18);
+        editor.run("line_start_non_blank").unwrap();
+        assert_eq!(editor.offset(), 16);
+        editor.run("document_start").unwrap();
+        assert_eq!(editor.offset(), 0);
+        editor
+            .run_count("go_to_line_default_last", Some(2))
+            .unwrap();
+        assert_eq!(editor.offset(), 8);
+        assert_eq!(editor.run("fly"), Err(UnknownCommand("fly".to_string())));
+    }
+
+    #[test]
+    fn test_vertical_motion_to_first_non_blank() {
+        let mut editor = HeadlessEditor::new("  a\n    b", true);
+        editor.run("line_start_non_blank").unwrap();
+        assert_eq!(editor.offset(), 2);
+        editor.run("down").unwrap();
+        assert_eq!(editor.offset(), 8);
+    }
+
+    #[test]
+    fn test_delete_lines() {
+        let mut editor = HeadlessEditor::new("one\ntwo\nthree\nfour\n", true);
+        editor.run("down").unwrap();
+        editor.run("motion_mode_delete").unwrap();
+        assert_eq!(editor.text(), "one\ntwo\nthree\nfour\n");
+        editor.run("motion_mode_delete").unwrap();
+        assert_eq!(editor.text(), "one\nthree\nfour\n");
+        assert_eq!(editor.offset(), 4);
+        assert_eq!(editor.register().content, "two\n");
+        assert_eq!(editor.register().mode, VisualMode::Linewise);
+
+        // The count is the one of the first operator
+        editor.run_count("motion_mode_delete", Some(2)).unwrap();
+        editor.run("motion_mode_delete").unwrap();
+        assert_eq!(editor.text(), "one\n");
+        assert_eq!(editor.register().content, "three\nfour\n");
+    }
+
+    #[test]
+    fn test_yank_and_paste() {
+        let mut editor = HeadlessEditor::new("x\ny", true);
+        editor.run("motion_mode_yank").unwrap();
+        editor.run("motion_mode_yank").unwrap();
+        assert_eq!(editor.text(), "x\ny");
+        assert_eq!(editor.register().content, "x\n");
+        editor.run("paste").unwrap();
+        assert_eq!(editor.text(), "x\nx\ny");
+        assert_eq!(editor.offset(), 2);
+    }
+
+    #[test]
+    fn test_visual_delete_fills_register() {
+        let mut editor = HeadlessEditor::new("abcdef", true);
+        editor.run("toggle_visual_mode").unwrap();
+        editor.run_count("right", Some(2)).unwrap();
+        assert_eq!(editor.mode(), Mode::Visual);
+        editor.run("delete_forward").unwrap();
+        assert_eq!(editor.text(), "def");
+        assert_eq!(editor.mode(), Mode::Normal);
+        assert_eq!(editor.register().content, "abc");
+        assert_eq!(editor.register().mode, VisualMode::Normal);
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut editor = HeadlessEditor::new("one\ntwo\nthree\n", true);
+        assert!(!editor.can_undo());
+        editor.run("down").unwrap();
+        editor.run("motion_mode_delete").unwrap();
+        editor.run("motion_mode_delete").unwrap();
+        editor.run("paste").unwrap();
+        assert_eq!(editor.text(), "one\nthree\ntwo\n");
+
+        // The cursor goes back to where it was before the paste
+        editor.run("undo").unwrap();
+        assert_eq!(editor.text(), "one\nthree\n");
+        assert_eq!(editor.offset(), 4);
+        editor.run("undo").unwrap();
+        assert_eq!(editor.text(), "one\ntwo\nthree\n");
+        assert!(!editor.can_undo());
+        assert!(editor.can_redo());
+
+        editor.run("redo").unwrap();
+        editor.run("redo").unwrap();
+        assert_eq!(editor.text(), "one\nthree\ntwo\n");
+        assert!(!editor.can_redo());
+    }
+
+    #[test]
+    fn test_type_text() {
+        let mut editor = HeadlessEditor::new("", false);
+        assert_eq!(editor.mode(), Mode::Insert);
+        editor.type_text("ab\ncd");
+        assert_eq!(editor.text(), "ab\ncd");
+        assert_eq!(editor.offset(), 5);
+
+        // The typing after the new line is undone on its own
+        editor.run("undo").unwrap();
+        assert_eq!(editor.text(), "ab\n");
+        assert_eq!(editor.offset(), 3);
+        editor.run("redo").unwrap();
+        assert_eq!(editor.text(), "ab\ncd");
+    }
+}
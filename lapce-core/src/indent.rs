@@ -66,25 +66,62 @@ pub fn create_edit<'s>(
     (Selection::caret(offset), indent)
 }
 
-pub fn create_outdent<'s>(
+/// The edit that shifts `line` right by `units` indents, inserted at its
+/// start so that what is aligned after them stays aligned.
+pub fn create_shift_in(
     buffer: &Buffer,
-    offset: usize,
-    indent: &'s str,
-) -> Option<(Selection, &'s str)> {
-    let (_, col) = buffer.offset_to_line_col(offset);
-    if col == 0 {
-        return None;
-    }
+    line: usize,
+    indent: &str,
+    units: usize,
+) -> (Selection, String) {
+    (
+        Selection::caret(buffer.offset_of_line(line)),
+        indent.repeat(units),
+    )
+}
 
-    let start = if indent.starts_with('\t') {
-        offset - 1
+/// The edit that shifts `line` left by `units` indents, taken from its
+/// leading whitespace a tab or an indent's worth of spaces at a time. It
+/// stops at the first non-whitespace character, and is `None` for a line
+/// that has no leading whitespace.
+pub fn create_shift_out(
+    buffer: &Buffer,
+    line: usize,
+    indent: &str,
+    units: usize,
+) -> Option<(Selection, &'static str)> {
+    // Spaces indented with tabs are taken as many at a time as the default
+    // indent has
+    let width = if indent.starts_with('\t') {
+        IndentStyle::DEFAULT_INDENT.as_str().len()
     } else {
-        let r = col % indent.len();
-        let r = if r == 0 { indent.len() } else { r };
-        offset - r
+        indent.len()
     };
-
-    Some((Selection::region(start, offset), ""))
+    let start = buffer.offset_of_line(line);
+    let mut end = start;
+    let mut removed = 0;
+    let mut spaces = 0;
+    for c in buffer.line_content(line).chars() {
+        if removed == units {
+            break;
+        }
+        match c {
+            '\t' => {
+                removed += 1;
+                spaces = 0;
+            }
+            ' ' => {
+                spaces += 1;
+                if spaces == width {
+                    removed += 1;
+                    spaces = 0;
+                }
+            }
+            _ => break,
+        }
+        end += 1;
+    }
+    (end > start).then(|| (Selection::region(start, end), ""))
 }
 
 /// Attempts to detect the indentation style used in a document.
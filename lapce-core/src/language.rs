@@ -1,4 +1,8 @@
-use std::{collections::HashSet, path::Path, str::FromStr};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    str::FromStr,
+};
 
 use once_cell::sync::Lazy;
 use strum_macros::{AsRefStr, Display, EnumMessage, EnumString};
@@ -75,6 +79,10 @@ pub struct SyntaxProperties {
     /// Filenames that belong to this language  
     /// `["Dockerfile"]` for Dockerfile, `[".editorconfig"]` for EditorConfig
     files: &'static [&'static str],
+    /// Interpreters that scripts of this language name in their shebang line,
+    /// without any version suffix.  
+    /// `["python"]` for python, `["bash", "zsh"]` for bash, for example.
+    shebangs: &'static [&'static str],
     /// File name extensions to determine the language.  
     /// `["py"]` for python, `["rs"]` for rust, for example.
     extensions: &'static [&'static str],
@@ -176,6 +184,8 @@ pub enum LapceLanguage {
     Elm,
     #[strum(message = "Erlang")]
     Erlang,
+    #[strum(message = "Git Commit")]
+    GitCommit,
     #[strum(message = "Glimmer")]
     Glimmer,
     #[strum(message = "GLSL")]
@@ -208,6 +218,8 @@ pub enum LapceLanguage {
     Latex,
     #[strum(message = "Lua")]
     Lua,
+    #[strum(message = "Makefile")]
+    Make,
     #[strum(message = "Markdown")]
     Markdown,
     #[strum(serialize = "markdown.inline")]
@@ -272,7 +284,8 @@ pub enum LapceLanguage {
         id: LapceLanguage::Plaintext,
 
         indent: "    ",
-        files: &[],
+        files: &["gemfile.lock"],
+        shebangs: &[],
         extensions: &[],
 
         comment: comment_properties!(),
@@ -283,7 +296,8 @@ pub enum LapceLanguage {
         id: LapceLanguage::Bash,
 
         indent: "  ",
-        files: &[],
+        files: &[".bashrc", ".bash_profile", ".bash_aliases"],
+        shebangs: &["bash", "zsh"],
         extensions: &["bash"],
 
         comment: comment_properties!("#"),
@@ -304,6 +318,7 @@ pub enum LapceLanguage {
 
         indent: "    ",
         files: &[],
+        shebangs: &[],
         extensions: &["c", "h"],
 
         comment: comment_properties!("//"),
@@ -324,6 +339,7 @@ pub enum LapceLanguage {
 
         indent: "  ",
         files: &[],
+        shebangs: &[],
         extensions: &[
             "clj",
             "edn",
@@ -353,6 +369,7 @@ pub enum LapceLanguage {
 
         indent: "  ",
         files: &[],
+        shebangs: &[],
         extensions: &["cmake"],
 
         comment: comment_properties!("#"),
@@ -373,6 +390,7 @@ pub enum LapceLanguage {
 
         indent: "    ",
         files: &[],
+        shebangs: &[],
         extensions: &["cpp", "cxx", "cc", "c++", "hpp", "hxx", "hh", "h++"],
 
         comment: comment_properties!("//"),
@@ -397,6 +415,7 @@ pub enum LapceLanguage {
 
         indent: "  ",
         files: &[],
+        shebangs: &[],
         extensions: &["cs", "csx"],
 
         comment: comment_properties!("#"),
@@ -428,6 +447,7 @@ pub enum LapceLanguage {
 
         indent: "  ",
         files: &[],
+        shebangs: &[],
         extensions: &["css"],
 
         comment: comment_properties!("/*", "*/"),
@@ -448,6 +468,7 @@ pub enum LapceLanguage {
 
         indent: "    ",
         files: &[],
+        shebangs: &[],
         extensions: &["d", "di", "dlang"],
 
         comment: CommentProperties {
@@ -475,6 +496,7 @@ pub enum LapceLanguage {
 
         indent: "  ",
         files: &[],
+        shebangs: &[],
         extensions: &["dart"],
 
         comment: CommentProperties {
@@ -510,6 +532,7 @@ pub enum LapceLanguage {
 
         indent: "  ",
         files: &["dockerfile", "containerfile"],
+        shebangs: &[],
         extensions: &["containerfile", "dockerfile"],
 
         comment: comment_properties!("#"),
@@ -530,6 +553,7 @@ pub enum LapceLanguage {
 
         indent: "  ",
         files: &[],
+        shebangs: &["elixir"],
         extensions: &["ex", "exs", "eex", "heex", "sface"],
 
         comment: comment_properties!("#"),
@@ -550,6 +574,7 @@ pub enum LapceLanguage {
 
         indent: "    ",
         files: &[],
+        shebangs: &[],
         extensions: &["elm"],
 
         comment: comment_properties!("#"),
@@ -570,6 +595,7 @@ pub enum LapceLanguage {
 
         indent: "    ",
         files: &[],
+        shebangs: &[],
         extensions: &["erl", "hrl"],
 
         comment: comment_properties!("%"),
@@ -585,11 +611,24 @@ pub enum LapceLanguage {
         #[cfg(not(feature = "lang-erlang"))]
         tree_sitter: None,
     },
+    SyntaxProperties {
+        id: LapceLanguage::GitCommit,
+
+        indent: "  ",
+        files: &["commit_editmsg", "merge_msg", "tag_editmsg"],
+        shebangs: &[],
+        extensions: &[],
+
+        comment: comment_properties!("#"),
+
+        tree_sitter: None,
+    },
     SyntaxProperties {
         id: LapceLanguage::Glimmer,
 
         indent: "  ",
         files: &[],
+        shebangs: &[],
         extensions: &["hbs"],
 
         comment: comment_properties!("{{!", "!}}"),
@@ -610,6 +649,7 @@ pub enum LapceLanguage {
 
         indent: "  ",
         files: &[],
+        shebangs: &[],
         extensions: &[
             "glsl", "cs", "vs", "gs", "fs", "csh", "vsh", "gsh", "fsh", "cshader",
             "vshader", "gshader", "fshader", "comp", "vert", "geom", "frag", "tesc",
@@ -635,6 +675,7 @@ pub enum LapceLanguage {
 
         indent: "    ",
         files: &[],
+        shebangs: &[],
         extensions: &["go"],
 
         comment: comment_properties!("//"),
@@ -664,6 +705,7 @@ pub enum LapceLanguage {
 
         indent: "        ",
         files: &[],
+        shebangs: &[],
         extensions: &["ha"],
 
         comment: comment_properties!("//"),
@@ -684,6 +726,7 @@ pub enum LapceLanguage {
 
         indent: "  ",
         files: &[],
+        shebangs: &[],
         extensions: &["hs"],
 
         comment: comment_properties!("--"),
@@ -704,6 +747,7 @@ pub enum LapceLanguage {
 
         indent: "  ",
         files: &[],
+        shebangs: &[],
         extensions: &["hx"],
 
         comment: comment_properties!("//"),
@@ -724,6 +768,7 @@ pub enum LapceLanguage {
 
         indent: "  ",
         files: &[],
+        shebangs: &[],
         extensions: &["hcl", "tf"],
 
         comment: comment_properties!("//"),
@@ -744,6 +789,7 @@ pub enum LapceLanguage {
 
         indent: "    ",
         files: &[],
+        shebangs: &[],
         extensions: &["html", "htm"],
 
         comment: comment_properties!("<!--", "-->"),
@@ -764,6 +810,7 @@ pub enum LapceLanguage {
 
         indent: "    ",
         files: &[],
+        shebangs: &[],
         extensions: &["java"],
 
         comment: comment_properties!("//"),
@@ -784,6 +831,7 @@ pub enum LapceLanguage {
 
         indent: "  ",
         files: &[],
+        shebangs: &["node", "nodejs"],
         extensions: &["js", "cjs", "mjs"],
 
         comment: comment_properties!("//"),
@@ -803,7 +851,8 @@ pub enum LapceLanguage {
         id: LapceLanguage::Json,
 
         indent: "    ",
-        files: &[],
+        files: &["pipfile.lock"],
+        shebangs: &[],
         extensions: &["json"],
 
         comment: comment_properties!(),
@@ -824,6 +873,7 @@ pub enum LapceLanguage {
 
         indent: "  ",
         files: &[],
+        shebangs: &[],
         extensions: &["jsx"],
 
         comment: comment_properties!("//"),
@@ -845,6 +895,7 @@ pub enum LapceLanguage {
 
         indent: "    ",
         files: &[],
+        shebangs: &["julia"],
         extensions: &["julia", "jl"],
 
         comment: CommentProperties {
@@ -872,6 +923,7 @@ pub enum LapceLanguage {
 
         indent: "  ",
         files: &[],
+        shebangs: &[],
         extensions: &["kt", "kts"],
 
         comment: CommentProperties {
@@ -899,6 +951,7 @@ pub enum LapceLanguage {
 
         indent: "  ",
         files: &[],
+        shebangs: &[],
         extensions: &["tex"],
 
         comment: comment_properties!("%"),
@@ -919,6 +972,7 @@ pub enum LapceLanguage {
 
         indent: "  ",
         files: &[],
+        shebangs: &["lua", "luajit"],
         extensions: &["lua"],
 
         comment: comment_properties!("--"),
@@ -934,11 +988,24 @@ pub enum LapceLanguage {
         #[cfg(not(feature = "lang-lua"))]
         tree_sitter: None,
     },
+    SyntaxProperties {
+        id: LapceLanguage::Make,
+
+        indent: "\t",
+        files: &["makefile", "gnumakefile"],
+        shebangs: &["make"],
+        extensions: &["mk", "mak"],
+
+        comment: comment_properties!("#"),
+
+        tree_sitter: None,
+    },
     SyntaxProperties {
         id: LapceLanguage::Markdown,
 
         indent: "    ",
         files: &[],
+        shebangs: &[],
         extensions: &["md"],
 
         comment: comment_properties!(),
@@ -960,6 +1027,7 @@ pub enum LapceLanguage {
         indent: "    ",
         // markdown inline is only used as an injection by the Markdown language
         files: &[],
+        shebangs: &[],
         extensions: &[],
 
         comment: comment_properties!(),
@@ -984,6 +1052,7 @@ pub enum LapceLanguage {
 
         indent: "  ",
         files: &[],
+        shebangs: &[],
         extensions: &["nix"],
 
         comment: CommentProperties {
@@ -1011,6 +1080,7 @@ pub enum LapceLanguage {
 
         indent: "  ",
         files: &[],
+        shebangs: &[],
         extensions: &["ml"],
 
         comment: CommentProperties {
@@ -1038,9 +1108,10 @@ pub enum LapceLanguage {
 
         indent: "  ",
         files: &[],
+        shebangs: &[],
         extensions: &["mli"],
 
-        comment: comment_properties!("(*"),
+        comment: comment_properties!("(*", "*)"),
 
         #[cfg(feature = "lang-ocaml")]
         tree_sitter: Some(TreeSitterProperties {
@@ -1058,6 +1129,7 @@ pub enum LapceLanguage {
 
         indent: "  ",
         files: &[],
+        shebangs: &["php"],
         extensions: &["php"],
 
         comment: comment_properties!("//"),
@@ -1099,6 +1171,7 @@ pub enum LapceLanguage {
 
         indent: "    ",
         files: &[],
+        shebangs: &[],
         extensions: &["prisma"],
 
         comment: comment_properties!("//"),
@@ -1119,6 +1192,7 @@ pub enum LapceLanguage {
 
         indent: "  ",
         files: &[],
+        shebangs: &[],
         extensions: &["proto"],
 
         comment: comment_properties!("//"),
@@ -1139,6 +1213,7 @@ pub enum LapceLanguage {
 
         indent: "    ",
         files: &[],
+        shebangs: &["python"],
         extensions: &["py", "pyi", "pyc", "pyd", "pyw"],
 
         comment: comment_properties!("#"),
@@ -1170,6 +1245,7 @@ pub enum LapceLanguage {
 
         indent: "  ",
         files: &[],
+        shebangs: &[],
         extensions: &["ql"],
 
         comment: comment_properties!("//"),
@@ -1190,6 +1266,7 @@ pub enum LapceLanguage {
 
         indent: "  ",
         files: &[],
+        shebangs: &["rscript"],
         extensions: &["r"],
 
         comment: comment_properties!("#"),
@@ -1209,7 +1286,8 @@ pub enum LapceLanguage {
         id: LapceLanguage::Ruby,
 
         indent: "  ",
-        files: &[],
+        files: &["gemfile", "rakefile"],
+        shebangs: &["ruby"],
         extensions: &["rb"],
 
         comment: comment_properties!("#"),
@@ -1230,9 +1308,17 @@ pub enum LapceLanguage {
 
         indent: "    ",
         files: &[],
+        shebangs: &[],
         extensions: &["rs"],
 
-        comment: comment_properties!("//"),
+        comment: CommentProperties {
+            single_line_start: Some("//"),
+            single_line_end: None,
+
+            multi_line_start: Some("/*"),
+            multi_line_prefix: None,
+            multi_line_end: Some("*/"),
+        },
 
         #[cfg(feature = "lang-rust")]
         tree_sitter: Some(TreeSitterProperties {
@@ -1258,6 +1344,7 @@ pub enum LapceLanguage {
 
         indent: "  ",
         files: &[],
+        shebangs: &[],
         extensions: &["scm", "ss"],
 
         comment: comment_properties!(";"),
@@ -1278,6 +1365,7 @@ pub enum LapceLanguage {
 
         indent: "  ",
         files: &[],
+        shebangs: &[],
         extensions: &["scss"],
 
         comment: comment_properties!("//"),
@@ -1298,6 +1386,7 @@ pub enum LapceLanguage {
 
         indent: "  ",
         files: &[],
+        shebangs: &["sh", "dash", "ash"],
         extensions: &["sh"],
 
         comment: comment_properties!("#"),
@@ -1318,6 +1407,7 @@ pub enum LapceLanguage {
 
         indent: "  ",
         files: &[],
+        shebangs: &[],
         extensions: &["sql"],
 
         comment: comment_properties!("--"),
@@ -1338,6 +1428,7 @@ pub enum LapceLanguage {
 
         indent: "  ",
         files: &[],
+        shebangs: &[],
         extensions: &["svelte"],
 
         comment: comment_properties!("//"),
@@ -1358,6 +1449,7 @@ pub enum LapceLanguage {
 
         indent: "  ",
         files: &[],
+        shebangs: &[],
         extensions: &["swift"],
 
         comment: comment_properties!("//"),
@@ -1377,7 +1469,8 @@ pub enum LapceLanguage {
         id: LapceLanguage::Toml,
 
         indent: "  ",
-        files: &[],
+        files: &["cargo.lock", "pipfile", "poetry.lock"],
+        shebangs: &[],
         extensions: &["toml"],
 
         comment: comment_properties!("#"),
@@ -1398,6 +1491,7 @@ pub enum LapceLanguage {
 
         indent: "    ",
         files: &[],
+        shebangs: &[],
         extensions: &["tsx"],
 
         comment: comment_properties!("//"),
@@ -1418,6 +1512,7 @@ pub enum LapceLanguage {
 
         indent: "    ",
         files: &[],
+        shebangs: &["deno", "ts-node"],
         extensions: &["ts", "cts", "mts"],
 
         comment: comment_properties!("//"),
@@ -1438,6 +1533,7 @@ pub enum LapceLanguage {
 
         indent: "  ",
         files: &[],
+        shebangs: &[],
         extensions: &["vue"],
 
         comment: comment_properties!("//"),
@@ -1458,6 +1554,7 @@ pub enum LapceLanguage {
 
         indent: "    ",
         files: &[],
+        shebangs: &[],
         extensions: &["wgsl"],
 
         comment: comment_properties!("//"),
@@ -1478,9 +1575,10 @@ pub enum LapceLanguage {
 
         indent: "    ",
         files: &[],
+        shebangs: &[],
         extensions: &["xml", "csproj"],
 
-        comment: comment_properties!("//"),
+        comment: comment_properties!("<!--", "-->"),
 
         #[cfg(feature = "lang-xml")]
         tree_sitter: Some(TreeSitterProperties {
@@ -1498,6 +1596,7 @@ pub enum LapceLanguage {
 
         indent: "  ",
         files: &[],
+        shebangs: &[],
         extensions: &["yml", "yaml"],
 
         comment: comment_properties!("#"),
@@ -1518,6 +1617,7 @@ pub enum LapceLanguage {
 
         indent: "    ",
         files: &[],
+        shebangs: &[],
         extensions: &["zig"],
 
         comment: comment_properties!("//"),
@@ -1547,28 +1647,159 @@ pub fn from_path(path: &Path) -> LapceLanguage {
     }
 
     fn from_path_raw(path: &Path) -> Option<LapceLanguage> {
-        let filename = path.file_stem()?.to_str()?.to_lowercase();
-        let extension = path.extension()?.to_str()?.to_lowercase();
-        // NOTE: This is a linear search.  It is assumed that this function
+        let filename = path.file_name()?.to_str()?.to_lowercase();
+        let stem = path.file_stem()?.to_str()?.to_lowercase();
+        // NOTE: These are linear searches.  It is assumed that this function
         // isn't called in any tight loop.
-        for properties in LANGUAGES {
-            if properties.files.contains(&filename.as_str())
-                || properties.extensions.contains(&extension.as_str())
+        // The whole file name goes first, so that `Pipfile.lock` isn't taken
+        // for a `Pipfile`
+        for name in [&filename, &stem] {
+            if let Some(properties) = LANGUAGES
+                .iter()
+                .find(|properties| properties.files.contains(&name.as_str()))
             {
                 return Some(properties.id);
             }
         }
 
-        None
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        LANGUAGES
+            .iter()
+            .find(|properties| properties.extensions.contains(&extension.as_str()))
+            .map(|properties| properties.id)
+    }
+
+    /// Detect the language of a script from its shebang line, such as
+    /// `#!/usr/bin/env python3` or `#!/bin/sh -e`.
+    pub fn from_shebang(line: &str) -> Option<LapceLanguage> {
+        let mut args = line.strip_prefix("#!")?.split_whitespace();
+        let mut interpreter = args.next()?.rsplit('/').next()?;
+        if interpreter == "env" {
+            // Skip the options and variables `env` may be given first
+            interpreter =
+                args.find(|arg| !arg.starts_with('-') && !arg.contains('='))?;
+        }
+        let interpreter = interpreter
+            .trim_end_matches(|c: char| c.is_ascii_digit() || c == '.')
+            .to_lowercase();
+
+        LANGUAGES
+            .iter()
+            .find(|properties| properties.shebangs.contains(&interpreter.as_str()))
+            .map(|properties| properties.id)
     }
 
+    /// Guess the language of text that has no path, like what's piped to
+    /// `lapce -`, from its shebang line or how it starts.
+    pub fn from_content(text: &str) -> Option<LapceLanguage> {
+        if let Some(language) = Self::from_shebang(text.lines().next()?) {
+            return Some(language);
+        }
+        let text = text.trim_start();
+        let start = text.chars().take(16).collect::<String>().to_lowercase();
+        if start.starts_with("<?xml") {
+            Some(LapceLanguage::Xml)
+        } else if start.starts_with("<!doctype html") || start.starts_with("<html") {
+            Some(LapceLanguage::Html)
+        } else if text.starts_with('{')
+            && text[1..].trim_start().starts_with(['"', '}'])
+        {
+            Some(LapceLanguage::Json)
+        } else {
+            None
+        }
+    }
+
+    /// Detect the language of the file at `path`, whose text starts with
+    /// `first_line`.  
+    /// `associations` maps file names, extensions and globs to the language
+    /// names the user assigned them, which take precedence over the built-in
+    /// ones. The shebang line is only looked at if the path alone is not
+    /// recognized.
+    pub fn detect(
+        path: &Path,
+        first_line: &str,
+        associations: &HashMap<String, String>,
+    ) -> LapceLanguage {
+        Self::from_associations(path, associations)
+            .or_else(|| Self::from_path_raw(path))
+            .or_else(|| Self::from_shebang(first_line))
+            .unwrap_or(LapceLanguage::Plaintext)
+    }
+
+    /// The language `associations` assign to the file at `path`, if any.  
+    /// A key is a file name, like `Justfile`, an extension, like `h`, or a
+    /// glob, like `*.conf` or `.github/workflows/*.yml`. A glob with a `/` is
+    /// matched with the end of the path, and one without with the file name.
+    /// A file name wins over a glob, and a glob over an extension, and the
+    /// longest of the globs that match wins.
+    pub fn from_associations(
+        path: &Path,
+        associations: &HashMap<String, String>,
+    ) -> Option<LapceLanguage> {
+        if associations.is_empty() {
+            return None;
+        }
+        let filename = path.file_name()?.to_str()?;
+        let extension = path.extension().and_then(|ext| ext.to_str());
+        let name = associations
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(filename))
+            .or_else(|| {
+                associations
+                    .iter()
+                    .filter(|(key, _)| is_glob(key))
+                    .filter(|(key, _)| glob_matches(key, path, filename))
+                    .max_by(|(a, _), (b, _)| a.len().cmp(&b.len()).then(b.cmp(a)))
+            })
+            .or_else(|| {
+                let extension = extension?;
+                associations.iter().find(|(key, _)| {
+                    !is_glob(key)
+                        && key
+                            .trim_start_matches('.')
+                            .eq_ignore_ascii_case(extension)
+                })
+            })
+            .map(|(_, name)| name)?;
+        Self::from_name(name)
+    }
+
+    /// Find a language by its id, like `cpp`, or its display name, like
+    /// `C++`, ignoring case.
     pub fn from_name(name: &str) -> Option<LapceLanguage> {
-        match LapceLanguage::from_str(name.to_lowercase().as_str()) {
-            Ok(v) => Some(v),
-            Err(e) => {
-                eprintln!("failed parsing {name} LapceLanguage: {e}");
-                None
-            }
+        if let Ok(language) = LapceLanguage::from_str(name) {
+            return Some(language);
+        }
+        let language = LANGUAGES.iter().map(|properties| properties.id).find(|id| {
+            matches!(
+                strum::EnumMessage::get_message(id),
+                Some(message) if message.eq_ignore_ascii_case(name)
+            )
+        });
+        if language.is_none() {
+            error!("unknown language {name}");
+        }
+        language
+    }
+
+    /// The name the language is shown to the user by, like `C++`.
+    pub fn name(&self) -> &str {
+        strum::EnumMessage::get_message(self).unwrap_or(self.as_ref())
+    }
+
+    /// The id language servers know the language by, like
+    /// `typescriptreact` for TSX.
+    pub fn lsp_id(&self) -> String {
+        match self {
+            LapceLanguage::Jsx => "javascriptreact".to_string(),
+            LapceLanguage::Tsx => "typescriptreact".to_string(),
+            LapceLanguage::GitCommit => "git-commit".to_string(),
+            LapceLanguage::Bash | LapceLanguage::Sh => "shellscript".to_string(),
+            LapceLanguage::Make => "makefile".to_string(),
+            LapceLanguage::Latex => "tex".to_string(),
+            LapceLanguage::ProtoBuf => "proto".to_string(),
+            _ => self.as_ref().to_lowercase(),
         }
     }
 
@@ -1609,6 +1840,21 @@ pub fn sticky_header_tags(&self) -> &[&'static str] {
         }
     }
 
+    /// The line comment token of the language, and its block comment tokens.
+    /// Comments that have to be closed even on a single line are block
+    /// comments.
+    pub fn comment_tokens(
+        &self,
+    ) -> (Option<&'static str>, Option<(&'static str, &'static str)>) {
+        let comment = self.properties().comment;
+        match (comment.single_line_start, comment.single_line_end) {
+            (Some(start), Some(end)) => (None, Some((start, end))),
+            (start, _) => {
+                (start, comment.multi_line_start.zip(comment.multi_line_end))
+            }
+        }
+    }
+
     pub fn comment_token(&self) -> &str {
         self.properties()
             .comment
@@ -1762,6 +2008,34 @@ fn load_grammar(
     Ok(language)
 }
 
+/// Whether the key of a file association is a glob rather than a file name or
+/// an extension
+fn is_glob(key: &str) -> bool {
+    key.contains(['*', '?', '[', '{', '/'])
+}
+
+fn glob_matches(glob: &str, path: &Path, filename: &str) -> bool {
+    let pattern = if !glob.contains('/') || glob.starts_with(['/', '*']) {
+        glob.to_string()
+    } else {
+        format!("**/{glob}")
+    };
+    let Ok(glob) = globset::GlobBuilder::new(&pattern)
+        .case_insensitive(true)
+        .literal_separator(true)
+        .build()
+    else {
+        error!("invalid glob {pattern} in the file associations");
+        return false;
+    };
+    let matcher = glob.compile_matcher();
+    if pattern.contains('/') {
+        matcher.is_match(path)
+    } else {
+        matcher.is_match(filename)
+    }
+}
+
 /// Walk an AST and determine which lines to include in the code lens.
 ///
 /// Node types listed in `list` will be walked, along with their children. All
@@ -1791,3 +2065,94 @@ fn walk_tree(
         cursor.goto_parent();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_path() {
+        let language = |path: &str| LapceLanguage::from_path(Path::new(path));
+        assert_eq!(language("src/main.rs"), LapceLanguage::Rust);
+        assert_eq!(language("Cargo.lock"), LapceLanguage::Toml);
+        assert_eq!(language("Pipfile"), LapceLanguage::Toml);
+        assert_eq!(language("Pipfile.lock"), LapceLanguage::Json);
+        assert_eq!(language("Gemfile"), LapceLanguage::Ruby);
+        assert_eq!(language("Gemfile.lock"), LapceLanguage::Plaintext);
+        assert_eq!(language("Makefile"), LapceLanguage::Make);
+        assert_eq!(language(".git/COMMIT_EDITMSG"), LapceLanguage::GitCommit);
+        assert_eq!(language("docker/Dockerfile"), LapceLanguage::Dockerfile);
+        assert_eq!(language("README"), LapceLanguage::Plaintext);
+    }
+
+    #[test]
+    fn test_from_shebang() {
+        let language = LapceLanguage::from_shebang;
+        assert_eq!(language("#!/bin/sh -e"), Some(LapceLanguage::Sh));
+        assert_eq!(
+            language("#!/usr/bin/python3.11"),
+            Some(LapceLanguage::Python)
+        );
+        assert_eq!(
+            language("#!/usr/bin/env -S deno run"),
+            Some(LapceLanguage::Typescript)
+        );
+        assert_eq!(language("#!/usr/bin/env perl"), None);
+        assert_eq!(language("# bash"), None);
+    }
+
+    #[test]
+    fn test_from_content() {
+        let language = LapceLanguage::from_content;
+        assert_eq!(
+            language("#!/usr/bin/env python3\nprint()"),
+            Some(LapceLanguage::Python)
+        );
+        assert_eq!(
+            language("<?xml version=\"1.0\"?>\n<a/>"),
+            Some(LapceLanguage::Xml)
+        );
+        assert_eq!(language("\n<!DOCTYPE html>"), Some(LapceLanguage::Html));
+        assert_eq!(language("{\n  \"a\": 1"), Some(LapceLanguage::Json));
+        assert_eq!(language("{}"), Some(LapceLanguage::Json));
+        // Logs and diffs are left as plain text
+        assert_eq!(language("[INFO] started"), None);
+        assert_eq!(language("{ foo }"), None);
+        assert_eq!(language("diff --git a/b b/b"), None);
+        assert_eq!(language(""), None);
+    }
+
+    #[test]
+    fn test_detect() {
+        let associations = HashMap::from([
+            ("h".to_string(), "C++".to_string()),
+            ("Justfile".to_string(), "make".to_string()),
+            ("*.conf".to_string(), "toml".to_string()),
+            ("nginx*.conf".to_string(), "bash".to_string()),
+            (".github/workflows/*.yml".to_string(), "json".to_string()),
+            ("yml".to_string(), "toml".to_string()),
+        ]);
+        let detect = |path: &str, first_line: &str| {
+            LapceLanguage::detect(Path::new(path), first_line, &associations)
+        };
+        assert_eq!(detect("lib.h", ""), LapceLanguage::Cpp);
+        assert_eq!(detect("justfile", ""), LapceLanguage::Make);
+        assert_eq!(detect("main.c", ""), LapceLanguage::C);
+        assert_eq!(detect("build", "#!/bin/bash"), LapceLanguage::Bash);
+        // The path wins over the shebang line
+        assert_eq!(detect("script.rb", "#!/bin/bash"), LapceLanguage::Ruby);
+        assert_eq!(detect("notes", "hello"), LapceLanguage::Plaintext);
+        // The longest glob wins, and globs win over extensions
+        assert_eq!(detect("/etc/app.conf", ""), LapceLanguage::Toml);
+        assert_eq!(detect("/etc/nginx-site.conf", ""), LapceLanguage::Bash);
+        assert_eq!(
+            detect("/p/.github/workflows/ci.yml", ""),
+            LapceLanguage::Json
+        );
+        assert_eq!(detect("/p/.github/ci.yml", ""), LapceLanguage::Toml);
+        assert_eq!(
+            detect("/p/.github/workflows/a/ci.yml", ""),
+            LapceLanguage::Toml
+        );
+    }
+}
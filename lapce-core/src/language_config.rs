@@ -0,0 +1,282 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{indent::IndentStyle, language::LapceLanguage};
+
+/// How editing behaves in the buffers of a language: comment toggling,
+/// auto-indent, auto-closing pairs, and the indentation used when a file
+/// doesn't show its own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LanguageConfig {
+    /// The token that starts a line comment, like `//`
+    pub line_comment: Option<String>,
+    /// The tokens around a block comment, like `/*` and `*/`
+    pub block_comment: Option<(String, String)>,
+    pub indent: IndentStyle,
+    /// The characters that indent the next line when a line ends with them
+    pub indent_chars: Vec<char>,
+    /// The closing brackets that outdent a line to the line of their opening
+    /// one when typed at its start
+    pub outdent_chars: Vec<char>,
+    /// The opening and closing characters that are inserted together
+    pub auto_pairs: Vec<(char, char)>,
+    /// Whether shifting lines with the indent and outdent commands shifts
+    /// blank lines too
+    pub shift_blank_lines: bool,
+    /// Whether the color literals of the text, like `#ff8800`, are shown with
+    /// a swatch of their color
+    pub color_swatches: bool,
+    /// Whether a new line after a list item or a quote carries on its
+    /// marker, see [`list_continuation`](crate::auto_edit::list_continuation)
+    pub continue_lists: bool,
+    /// Whether typing the `>` of an opening tag inserts its closing tag, see
+    /// [`closing_tag`](crate::auto_edit::closing_tag)
+    pub auto_close_tags: bool,
+}
+
+impl Default for LanguageConfig {
+    fn default() -> Self {
+        Self::new(LapceLanguage::Plaintext)
+    }
+}
+
+impl LanguageConfig {
+    /// The built-in config of `language`. Languages without anything specific
+    /// get generic defaults.
+    pub fn new(language: LapceLanguage) -> Self {
+        let (line_comment, block_comment) = language.comment_tokens();
+
+        let mut indent_chars = vec!['{', '[', '('];
+        if language == LapceLanguage::Python {
+            indent_chars.push(':');
+        }
+
+        let mut auto_pairs = vec![('(', ')'), ('[', ']'), ('{', '}'), ('"', '"')];
+        match language {
+            // The quote starts lifetimes, chars, and quoted forms respectively
+            LapceLanguage::Rust
+            | LapceLanguage::Ocaml
+            | LapceLanguage::OcamlInterface
+            | LapceLanguage::Clojure
+            | LapceLanguage::Scheme => {}
+            _ => auto_pairs.push(('\'', '\'')),
+        }
+        if matches!(
+            language,
+            LapceLanguage::Javascript
+                | LapceLanguage::Jsx
+                | LapceLanguage::Typescript
+                | LapceLanguage::Tsx
+                | LapceLanguage::Markdown
+        ) {
+            auto_pairs.push(('`', '`'));
+        }
+
+        Self {
+            line_comment: line_comment.map(str::to_string),
+            block_comment: block_comment
+                .map(|(start, end)| (start.to_string(), end.to_string())),
+            indent: IndentStyle::from_str(language.indent_unit()),
+            indent_chars,
+            outdent_chars: vec!['}', ']', ')'],
+            auto_pairs,
+            shift_blank_lines: false,
+            color_swatches: matches!(
+                language,
+                LapceLanguage::Css
+                    | LapceLanguage::Scss
+                    | LapceLanguage::Html
+                    | LapceLanguage::Svelte
+                    | LapceLanguage::Vue
+                    | LapceLanguage::Toml
+                    | LapceLanguage::Json
+                    | LapceLanguage::Yaml
+            ),
+            continue_lists: matches!(
+                language,
+                LapceLanguage::Markdown
+                    | LapceLanguage::MarkdownInline
+                    | LapceLanguage::GitCommit
+            ),
+            auto_close_tags: matches!(
+                language,
+                LapceLanguage::Html
+                    | LapceLanguage::Xml
+                    | LapceLanguage::Svelte
+                    | LapceLanguage::Vue
+                    | LapceLanguage::Glimmer
+                    | LapceLanguage::Jsx
+                    | LapceLanguage::Tsx
+            ),
+        }
+    }
+
+    /// Apply the user's overrides on top of this config.
+    pub fn apply(&mut self, overrides: &LanguageConfigOverrides) {
+        if let Some(line_comment) = overrides.line_comment.as_ref() {
+            self.line_comment =
+                Some(line_comment.clone()).filter(|token| !token.is_empty());
+        }
+        if let Some(block_comment) = overrides.block_comment.as_ref() {
+            self.block_comment = Some(block_comment.clone())
+                .filter(|(start, end)| !start.is_empty() && !end.is_empty());
+        }
+        match (overrides.indent_with_tabs, overrides.indent_width) {
+            (Some(true), _) => self.indent = IndentStyle::Tabs,
+            (_, Some(width)) => {
+                self.indent = IndentStyle::Spaces(
+                    width.clamp(1, IndentStyle::LONGEST_INDENT.len() as u8),
+                )
+            }
+            (Some(false), None) => {
+                if self.indent == IndentStyle::Tabs {
+                    self.indent = IndentStyle::DEFAULT_INDENT;
+                }
+            }
+            (None, None) => {}
+        }
+        if let Some(indent_chars) = overrides.indent_chars.as_ref() {
+            self.indent_chars = indent_chars.chars().collect();
+        }
+        if let Some(outdent_chars) = overrides.outdent_chars.as_ref() {
+            self.outdent_chars = outdent_chars.chars().collect();
+        }
+        if let Some(auto_pairs) = overrides.auto_pairs.as_ref() {
+            self.auto_pairs = auto_pairs
+                .iter()
+                .filter_map(|pair| {
+                    let mut chars = pair.chars();
+                    let pair = (chars.next()?, chars.next()?);
+                    chars.next().is_none().then_some(pair)
+                })
+                .collect();
+        }
+        if let Some(shift_blank_lines) = overrides.shift_blank_lines {
+            self.shift_blank_lines = shift_blank_lines;
+        }
+        if let Some(color_swatches) = overrides.color_swatches {
+            self.color_swatches = color_swatches;
+        }
+        if let Some(continue_lists) = overrides.continue_lists {
+            self.continue_lists = continue_lists;
+        }
+        if let Some(auto_close_tags) = overrides.auto_close_tags {
+            self.auto_close_tags = auto_close_tags;
+        }
+    }
+
+    /// The character that closes a pair opened by `c`, if it opens one.
+    pub fn closing_pair(&self, c: char) -> Option<char> {
+        self.auto_pairs
+            .iter()
+            .find(|(open, _)| *open == c)
+            .map(|(_, close)| *close)
+    }
+
+    /// Whether `c` closes an auto-inserted pair.
+    pub fn is_closing_pair(&self, c: char) -> bool {
+        self.auto_pairs.iter().any(|(_, close)| *close == c)
+    }
+}
+
+/// The user's overrides of the [`LanguageConfig`] of a language. Everything
+/// left out keeps its built-in default.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct LanguageConfigOverrides {
+    /// The line comment token, or an empty string for none
+    pub line_comment: Option<String>,
+    /// The block comment tokens, like `["/*", "*/"]`
+    pub block_comment: Option<(String, String)>,
+    /// How many spaces an indent is
+    pub indent_width: Option<u8>,
+    /// Whether to indent with tabs rather than spaces
+    pub indent_with_tabs: Option<bool>,
+    /// The characters that indent the next line when a line ends with them
+    pub indent_chars: Option<String>,
+    /// The closing brackets that outdent the line they are typed at the start
+    /// of
+    pub outdent_chars: Option<String>,
+    /// The pairs of characters inserted together, like `["()", "\"\""]`
+    pub auto_pairs: Option<Vec<String>>,
+    /// The columns to draw rulers at in the editor, like `[72]`, over the
+    /// `editor.rulers` setting
+    pub rulers: Option<Vec<usize>>,
+    /// Whether to check the spelling of the comments and strings, or of all
+    /// the text of prose, over the `editor.spell-check` setting
+    pub spell_check: Option<bool>,
+    /// Whether shifting lines shifts blank lines too, over the
+    /// `editor.shift-blank-lines` setting
+    pub shift_blank_lines: Option<bool>,
+    /// Whether color literals are shown with a swatch of their color
+    pub color_swatches: Option<bool>,
+    /// Whether Enter carries on the marker of a list item or a quote
+    pub continue_lists: Option<bool>,
+    /// Whether typing the `>` of an opening tag inserts its closing tag
+    pub auto_close_tags: Option<bool>,
+    /// A command that formats the documents when no language server does,
+    /// given the text on its stdin and printing it formatted, like
+    /// `prettier --stdin-filepath {path}`, with `{path}` the document's path
+    pub formatter: Option<String>,
+    /// The pairs of patterns of the files that Alternate File switches
+    /// between, like `["{name}.h", "{name}.c"]`, with `{name}` standing for
+    /// the same name in both
+    pub alternate_files: Option<Vec<(String, String)>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_config() {
+        let config = LanguageConfig::new(LapceLanguage::Rust);
+        assert_eq!(config.line_comment.as_deref(), Some("//"));
+        assert_eq!(
+            config.block_comment,
+            Some(("/*".to_string(), "*/".to_string()))
+        );
+        assert_eq!(config.indent, IndentStyle::Spaces(4));
+        assert_eq!(config.closing_pair('('), Some(')'));
+        assert_eq!(config.closing_pair('\''), None);
+
+        let config = LanguageConfig::new(LapceLanguage::Css);
+        assert_eq!(config.line_comment, None);
+        assert_eq!(
+            config.block_comment,
+            Some(("/*".to_string(), "*/".to_string()))
+        );
+
+        let config = LanguageConfig::new(LapceLanguage::Make);
+        assert_eq!(config.indent, IndentStyle::Tabs);
+        assert_eq!(config.line_comment.as_deref(), Some("#"));
+
+        let config = LanguageConfig::new(LapceLanguage::Python);
+        assert!(config.indent_chars.contains(&':'));
+        assert!(!config.color_swatches);
+        assert!(LanguageConfig::new(LapceLanguage::Css).color_swatches);
+        assert!(LanguageConfig::new(LapceLanguage::Markdown).continue_lists);
+        assert!(LanguageConfig::new(LapceLanguage::Html).auto_close_tags);
+        assert!(!config.continue_lists && !config.auto_close_tags);
+    }
+
+    #[test]
+    fn test_overrides() {
+        let mut config = LanguageConfig::new(LapceLanguage::Rust);
+        config.apply(&LanguageConfigOverrides {
+            line_comment: Some(String::new()),
+            indent_width: Some(2),
+            auto_pairs: Some(vec!["<>".to_string(), "bad".to_string()]),
+            ..Default::default()
+        });
+        assert_eq!(config.line_comment, None);
+        assert!(config.block_comment.is_some());
+        assert_eq!(config.indent, IndentStyle::Spaces(2));
+        assert_eq!(config.auto_pairs, vec![('<', '>')]);
+
+        config.apply(&LanguageConfigOverrides {
+            indent_with_tabs: Some(true),
+            ..Default::default()
+        });
+        assert_eq!(config.indent, IndentStyle::Tabs);
+    }
+}
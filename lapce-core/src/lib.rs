@@ -1,23 +1,34 @@
 #![allow(clippy::manual_clamp)]
 
+pub mod auto_edit;
 pub mod buffer;
 pub mod char_buffer;
 pub mod chars;
+pub mod color_literal;
 pub mod command;
 pub mod cursor;
 pub mod directory;
 pub mod editor;
 pub mod encoding;
+pub mod headless;
 pub mod indent;
 pub mod language;
+pub mod language_config;
 pub mod lens;
 pub mod meta;
 pub mod mode;
+pub mod motion;
 pub mod movement;
 pub mod paragraph;
+pub mod path;
+pub mod reflow;
 pub mod register;
+pub mod replace;
 pub mod selection;
+pub mod sentence;
 pub mod soft_tab;
+pub mod spell;
 pub mod style;
+pub mod symbol;
 pub mod syntax;
 pub mod word;
@@ -6,10 +6,49 @@
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum MotionMode {
-    Delete { count: usize },
-    Yank { count: usize },
-    Indent,
-    Outdent,
+    Delete {
+        count: usize,
+    },
+    Yank {
+        count: usize,
+    },
+    Indent {
+        count: usize,
+    },
+    Outdent {
+        count: usize,
+    },
+    /// Rewrap the lines to `width` columns
+    Reflow {
+        count: usize,
+        width: usize,
+    },
+}
+
+impl std::fmt::Display for MotionMode {
+    /// The count and the key of the operator in the default keymaps, like
+    /// `2d`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MotionMode::Delete { count }
+            | MotionMode::Yank { count }
+            | MotionMode::Indent { count }
+            | MotionMode::Outdent { count }
+            | MotionMode::Reflow { count, .. }
+                if *count > 1 =>
+            {
+                write!(f, "{count}")?;
+            }
+            _ => {}
+        }
+        f.write_str(match self {
+            MotionMode::Delete { .. } => "d",
+            MotionMode::Yank { .. } => "y",
+            MotionMode::Indent { .. } => ">",
+            MotionMode::Outdent { .. } => "<",
+            MotionMode::Reflow { .. } => "gq",
+        })
+    }
 }
 
 #[derive(
@@ -0,0 +1,675 @@
+//! Moving the cursor by a [`Movement`], whatever lays the text out.
+//!
+//! Most movements only depend on the text, but the vertical ones follow the
+//! lines as they're shown, which a [`TextLayout`] gives: the editor view wraps
+//! lines and places the characters by their fonts, while a headless editor
+//! has a line per line of the text and a column per character.
+//!
+//! An operator waiting for its movement isn't applied here, but returned as a
+//! [`MotionEdit`] for the caller to apply to its buffer.
+
+use std::ops::{Range, RangeInclusive};
+
+use crate::{
+    buffer::rope_text::{RopeText, RopeTextVal},
+    command::MotionModeCommand,
+    cursor::{ColPosition, Cursor, CursorMode},
+    mode::{Mode, MotionMode, VisualMode},
+    movement::{LinePosition, Movement},
+    paragraph::paragraph_object,
+    selection::{SelRegion, Selection},
+    soft_tab::{snap_to_soft_tab, SnapDirection},
+};
+
+/// How the text is laid out in lines and columns, for the movements that
+/// depend on it
+pub trait TextLayout {
+    /// The text the cursor moves through
+    fn rope_text(&self) -> RopeTextVal;
+
+    /// The width of the soft tabs the cursor moves over at once, if any
+    fn soft_tab_width(&self) -> Option<usize>;
+
+    /// The line as it's shown of the line of the text `line`
+    fn visual_line(&self, line: usize) -> usize;
+
+    /// The line of the text of the line as it's shown `visual_line`, which
+    /// is the one after it rather than before when it's no line of the text
+    /// and `bottom_affinity` is set.
+    fn actual_line(&self, visual_line: usize, bottom_affinity: bool) -> usize;
+
+    /// The horizontal position of `offset` in its line.
+    fn offset_x(&self, offset: usize) -> f64;
+
+    /// The column of `line` closest to the horizontal position `x`.
+    fn col_at_x(&self, line: usize, x: f64) -> usize;
+
+    /// The offset of the next `ch` after `offset`, or the previous one before
+    /// it, that isn't matched by its pair.
+    fn find_unmatched(&self, offset: usize, previous: bool, ch: char) -> usize;
+
+    /// The offset of the bracket matching the one at `offset`.
+    fn find_matching_pair(&self, offset: usize) -> usize;
+}
+
+/// An operator to apply between two offsets, once the movement it waited for
+/// is made or the operator is doubled
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MotionEdit {
+    pub motion_mode: MotionMode,
+    pub start: usize,
+    pub end: usize,
+    /// Whether the movement was vertical, so that whole lines are acted on
+    pub is_vertical: bool,
+}
+
+/// What an operator does right away when it's run, rather than wait for its
+/// movement
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OperatorAction {
+    /// Apply the operator between two offsets
+    Edit(MotionEdit),
+    /// Shift `lines` by `count` indents, to the left when `outdent`
+    Shift {
+        lines: RangeInclusive<usize>,
+        count: usize,
+        outdent: bool,
+    },
+}
+
+/// Move a selection region by a given movement.
+/// Much of the time, this will just be a matter of moving the cursor, but
+/// some movements may depend on the current selection.
+fn move_region(
+    view: &impl TextLayout,
+    region: &SelRegion,
+    count: usize,
+    modify: bool,
+    movement: &Movement,
+    mode: Mode,
+) -> SelRegion {
+    let (count, region) = if count >= 1 && !modify && !region.is_caret() {
+        // If we're not a caret, and we are moving left/up or right/down, we want to move
+        // the cursor to the left or right side of the selection.
+        // Ex: `|abc|` -> left/up arrow key -> `|abc`
+        // Ex: `|abc|` -> right/down arrow key -> `abc|`
+        // and it doesn't matter which direction the selection is going, so we use min/max
+        match movement {
+            Movement::Left | Movement::Up => {
+                let leftmost = region.min();
+                (count - 1, SelRegion::new(leftmost, leftmost, region.horiz))
+            }
+            Movement::Right | Movement::Down => {
+                let rightmost = region.max();
+                (
+                    count - 1,
+                    SelRegion::new(rightmost, rightmost, region.horiz),
+                )
+            }
+            _ => (count, *region),
+        }
+    } else {
+        (count, *region)
+    };
+
+    let (end, horiz) = move_offset(
+        view,
+        region.end,
+        region.horiz.as_ref(),
+        count,
+        movement,
+        mode,
+    );
+    let start = match modify {
+        true => region.start,
+        false => end,
+    };
+    SelRegion::new(start, end, horiz)
+}
+
+pub fn move_selection(
+    view: &impl TextLayout,
+    selection: &Selection,
+    count: usize,
+    modify: bool,
+    movement: &Movement,
+    mode: Mode,
+) -> Selection {
+    let mut new_selection = Selection::new();
+    for region in selection.regions() {
+        new_selection
+            .add_region(move_region(view, region, count, modify, movement, mode));
+    }
+    new_selection
+}
+
+pub fn move_offset(
+    view: &impl TextLayout,
+    offset: usize,
+    horiz: Option<&ColPosition>,
+    count: usize,
+    movement: &Movement,
+    mode: Mode,
+) -> (usize, Option<ColPosition>) {
+    match movement {
+        Movement::Left => {
+            let new_offset = move_left(
+                view.rope_text(),
+                offset,
+                mode,
+                count,
+                view.soft_tab_width(),
+            );
+
+            (new_offset, None)
+        }
+        Movement::Right => {
+            let new_offset = move_right(
+                view.rope_text(),
+                offset,
+                mode,
+                count,
+                view.soft_tab_width(),
+            );
+
+            (new_offset, None)
+        }
+        Movement::Up => {
+            let (new_offset, horiz) =
+                move_up(view, offset, horiz.cloned(), mode, count);
+
+            (new_offset, Some(horiz))
+        }
+        Movement::Down => {
+            let (new_offset, horiz) =
+                move_down(view, offset, horiz.cloned(), mode, count);
+
+            (new_offset, Some(horiz))
+        }
+        Movement::DocumentStart => (0, Some(ColPosition::Start)),
+        Movement::DocumentEnd => {
+            let (new_offset, horiz) = document_end(view.rope_text(), mode);
+
+            (new_offset, Some(horiz))
+        }
+        Movement::FirstNonBlank => {
+            let (new_offset, horiz) = first_non_blank(view.rope_text(), offset);
+
+            (new_offset, Some(horiz))
+        }
+        Movement::StartOfLine => {
+            let (new_offset, horiz) = start_of_line(view.rope_text(), offset);
+
+            (new_offset, Some(horiz))
+        }
+        Movement::EndOfLine => {
+            let (new_offset, horiz) = end_of_line(view.rope_text(), offset, mode);
+
+            (new_offset, Some(horiz))
+        }
+        Movement::Line(position) => {
+            let (new_offset, horiz) =
+                to_line(view, offset, horiz.cloned(), mode, position);
+
+            (new_offset, Some(horiz))
+        }
+        Movement::Offset(offset) => {
+            let new_offset = view.rope_text().clamp_offset(*offset, mode);
+            (new_offset, None)
+        }
+        Movement::WordEndForward => {
+            let new_offset = view.rope_text().move_n_wordends_forward(
+                offset,
+                count,
+                mode == Mode::Insert,
+            );
+            (new_offset, None)
+        }
+        Movement::WordForward => {
+            let new_offset = view.rope_text().move_n_words_forward(offset, count);
+            (new_offset, None)
+        }
+        Movement::WordBackward => {
+            let new_offset =
+                view.rope_text().move_n_words_backward(offset, count, mode);
+            (new_offset, None)
+        }
+        Movement::NextUnmatched(char) => {
+            let new_offset = view.find_unmatched(offset, false, *char);
+
+            (new_offset, None)
+        }
+        Movement::PreviousUnmatched(char) => {
+            let new_offset = view.find_unmatched(offset, true, *char);
+
+            (new_offset, None)
+        }
+        Movement::MatchPairs => {
+            let new_offset = view.find_matching_pair(offset);
+
+            (new_offset, None)
+        }
+        Movement::ParagraphForward => {
+            let new_offset = view
+                .rope_text()
+                .move_n_paragraphs_forward(offset, count, mode);
+
+            (new_offset, None)
+        }
+        Movement::ParagraphBackward => {
+            let new_offset =
+                view.rope_text().move_n_paragraphs_backward(offset, count);
+
+            (new_offset, None)
+        }
+        Movement::SentenceForward => {
+            let new_offset = view
+                .rope_text()
+                .move_n_sentences_forward(offset, count, mode);
+
+            (new_offset, None)
+        }
+        Movement::SentenceBackward => {
+            let new_offset =
+                view.rope_text().move_n_sentences_backward(offset, count);
+
+            (new_offset, None)
+        }
+        // Text objects select a range rather than move, see `move_cursor`
+        Movement::InnerParagraph | Movement::AroundParagraph => (offset, None),
+    }
+}
+
+/// The lines of the paragraph text object `movement` at `offset`.
+fn paragraph_object_lines(
+    view: &impl TextLayout,
+    offset: usize,
+    count: usize,
+    movement: &Movement,
+) -> Range<usize> {
+    let rope_text = view.rope_text();
+    let line = rope_text.line_of_offset(offset);
+    paragraph_object(
+        rope_text.text(),
+        line,
+        count,
+        *movement == Movement::AroundParagraph,
+    )
+}
+
+/// Move the offset to the left by `count` amount.
+/// If `soft_tab_width` is `Some` (and greater than 1) then the offset will snap to the soft tab.
+fn move_left(
+    rope_text: impl RopeText,
+    offset: usize,
+    mode: Mode,
+    count: usize,
+    soft_tab_width: Option<usize>,
+) -> usize {
+    let mut new_offset = rope_text.move_left(offset, mode, count);
+
+    if let Some(soft_tab_width) = soft_tab_width {
+        if soft_tab_width > 1 {
+            new_offset = snap_to_soft_tab(
+                rope_text.text(),
+                new_offset,
+                SnapDirection::Left,
+                soft_tab_width,
+            );
+        }
+    }
+
+    new_offset
+}
+
+/// Move the offset to the right by `count` amount.
+/// If `soft_tab_width` is `Some` (and greater than 1) then the offset will snap to the soft tab.
+fn move_right(
+    rope_text: impl RopeText,
+    offset: usize,
+    mode: Mode,
+    count: usize,
+    soft_tab_width: Option<usize>,
+) -> usize {
+    let mut new_offset = rope_text.move_right(offset, mode, count);
+
+    if let Some(soft_tab_width) = soft_tab_width {
+        if soft_tab_width > 1 {
+            new_offset = snap_to_soft_tab(
+                rope_text.text(),
+                new_offset,
+                SnapDirection::Right,
+                soft_tab_width,
+            );
+        }
+    }
+
+    new_offset
+}
+
+/// The column of `line` at the horizontal position `horiz`.
+fn horiz_col(
+    view: &impl TextLayout,
+    line: usize,
+    horiz: &ColPosition,
+    caret: bool,
+) -> usize {
+    let rope_text = view.rope_text();
+    match *horiz {
+        ColPosition::Col(x) => view
+            .col_at_x(line, x)
+            .min(rope_text.line_end_col(line, caret)),
+        ColPosition::End => rope_text.line_end_col(line, caret),
+        ColPosition::Start => 0,
+        ColPosition::FirstNonBlank => {
+            rope_text.first_non_blank_character_on_line(line)
+                - rope_text.offset_of_line(line)
+        }
+    }
+}
+
+/// Move the offset up by `count` amount.
+fn move_up(
+    view: &impl TextLayout,
+    offset: usize,
+    horiz: Option<ColPosition>,
+    mode: Mode,
+    count: usize,
+) -> (usize, ColPosition) {
+    let rope_text = view.rope_text();
+
+    let line = rope_text.line_of_offset(offset);
+
+    if line == 0 {
+        let line = rope_text.line_of_offset(offset);
+        let new_offset = rope_text.offset_of_line(line);
+        let horiz = horiz.unwrap_or_else(|| ColPosition::Col(view.offset_x(offset)));
+        return (new_offset, horiz);
+    }
+
+    let visual_line = view.visual_line(line).saturating_sub(count);
+    let line = view.actual_line(visual_line, false);
+
+    let horiz = horiz.unwrap_or_else(|| ColPosition::Col(view.offset_x(offset)));
+    let col = horiz_col(view, line, &horiz, mode != Mode::Normal);
+    let new_offset = rope_text.offset_of_line_col(line, col);
+
+    (new_offset, horiz)
+}
+
+/// Move the offset down by `count` amount.
+fn move_down(
+    view: &impl TextLayout,
+    offset: usize,
+    horiz: Option<ColPosition>,
+    mode: Mode,
+    count: usize,
+) -> (usize, ColPosition) {
+    let rope_text = view.rope_text();
+
+    let last_line = rope_text.last_line();
+    let line = rope_text.line_of_offset(offset);
+    if line == last_line {
+        let new_offset = rope_text.offset_line_end(offset, mode != Mode::Normal);
+        let horiz = horiz.unwrap_or_else(|| ColPosition::Col(view.offset_x(offset)));
+        return (new_offset, horiz);
+    }
+
+    let visual_line = view.visual_line(line);
+    let line = view.actual_line(visual_line + count, true);
+    let line = line.min(last_line);
+
+    let horiz = horiz.unwrap_or_else(|| ColPosition::Col(view.offset_x(offset)));
+    let col = horiz_col(view, line, &horiz, mode != Mode::Normal);
+    let new_offset = rope_text.offset_of_line_col(line, col);
+
+    (new_offset, horiz)
+}
+
+fn document_end(rope_text: impl RopeText, mode: Mode) -> (usize, ColPosition) {
+    let last_offset =
+        rope_text.offset_line_end(rope_text.len(), mode != Mode::Normal);
+
+    (last_offset, ColPosition::End)
+}
+
+fn first_non_blank(rope_text: impl RopeText, offset: usize) -> (usize, ColPosition) {
+    let line = rope_text.line_of_offset(offset);
+    let non_blank_offset = rope_text.first_non_blank_character_on_line(line);
+    let start_line_offset = rope_text.offset_of_line(line);
+    if offset > non_blank_offset {
+        // Jump to the first non-whitespace character if we're strictly after it
+        (non_blank_offset, ColPosition::FirstNonBlank)
+    } else {
+        // If we're at the start of the line, also jump to the first not blank
+        if start_line_offset == offset {
+            (non_blank_offset, ColPosition::FirstNonBlank)
+        } else {
+            // Otherwise, jump to the start of the line
+            (start_line_offset, ColPosition::Start)
+        }
+    }
+}
+
+fn start_of_line(rope_text: impl RopeText, offset: usize) -> (usize, ColPosition) {
+    let line = rope_text.line_of_offset(offset);
+    let new_offset = rope_text.offset_of_line(line);
+
+    (new_offset, ColPosition::Start)
+}
+
+fn end_of_line(
+    rope_text: impl RopeText,
+    offset: usize,
+    mode: Mode,
+) -> (usize, ColPosition) {
+    let new_offset = rope_text.offset_line_end(offset, mode != Mode::Normal);
+
+    (new_offset, ColPosition::End)
+}
+
+fn to_line(
+    view: &impl TextLayout,
+    offset: usize,
+    horiz: Option<ColPosition>,
+    mode: Mode,
+    position: &LinePosition,
+) -> (usize, ColPosition) {
+    let rope_text = view.rope_text();
+
+    let line = match position {
+        LinePosition::Line(line) => {
+            line.saturating_sub(1).min(rope_text.last_line())
+        }
+        LinePosition::First => 0,
+        LinePosition::Last => rope_text.last_line(),
+    };
+    let horiz = horiz.unwrap_or_else(|| ColPosition::Col(view.offset_x(offset)));
+    let col = horiz_col(view, line, &horiz, mode != Mode::Normal);
+    let new_offset = rope_text.offset_of_line_col(line, col);
+
+    (new_offset, horiz)
+}
+
+/// Move the cursor by `movement`, or return the edit of the operator that
+/// waited for it, which the caller applies.
+pub fn move_cursor(
+    view: &impl TextLayout,
+    cursor: &mut Cursor,
+    movement: &Movement,
+    count: usize,
+    modify: bool,
+) -> Option<MotionEdit> {
+    let mut edit = None;
+    match cursor.mode {
+        CursorMode::Normal(offset) => {
+            let (new_offset, horiz) = move_offset(
+                view,
+                offset,
+                cursor.horiz.as_ref(),
+                count,
+                movement,
+                Mode::Normal,
+            );
+            if let Some(motion_mode) = cursor.motion_mode.take() {
+                let (moved_new_offset, _) = move_offset(
+                    view,
+                    new_offset,
+                    None,
+                    1,
+                    &Movement::Right,
+                    Mode::Insert,
+                );
+                let (start, end) = match movement {
+                    Movement::EndOfLine | Movement::WordEndForward => {
+                        (offset, moved_new_offset)
+                    }
+                    Movement::MatchPairs => {
+                        if new_offset > offset {
+                            (offset, moved_new_offset)
+                        } else {
+                            (moved_new_offset, new_offset)
+                        }
+                    }
+                    Movement::InnerParagraph | Movement::AroundParagraph => {
+                        let lines =
+                            paragraph_object_lines(view, offset, count, movement);
+                        (
+                            view.rope_text().offset_of_line(lines.start),
+                            view.rope_text().offset_of_line(lines.end - 1),
+                        )
+                    }
+                    _ => (offset, new_offset),
+                };
+                edit = Some(MotionEdit {
+                    motion_mode,
+                    start,
+                    end,
+                    is_vertical: movement.is_vertical(),
+                });
+            } else {
+                cursor.mode = CursorMode::Normal(new_offset);
+                cursor.horiz = horiz;
+            }
+        }
+        CursorMode::Visual { .. } if movement.is_text_object() => {
+            let offset = cursor.offset();
+            let lines = paragraph_object_lines(view, offset, count, movement);
+            cursor.mode = CursorMode::Visual {
+                start: view.rope_text().offset_of_line(lines.start),
+                end: view.rope_text().offset_of_line(lines.end - 1),
+                mode: VisualMode::Linewise,
+            };
+            cursor.horiz = None;
+        }
+        CursorMode::Visual { start, end, mode } => {
+            let (new_offset, horiz) = move_offset(
+                view,
+                end,
+                cursor.horiz.as_ref(),
+                count,
+                movement,
+                Mode::Visual,
+            );
+            cursor.mode = CursorMode::Visual {
+                start,
+                end: new_offset,
+                mode,
+            };
+            cursor.horiz = horiz;
+        }
+        CursorMode::Insert(ref selection) => {
+            let selection = move_selection(
+                view,
+                selection,
+                count,
+                modify,
+                movement,
+                Mode::Insert,
+            );
+            cursor.set_insert(selection);
+        }
+    }
+    cursor.clamp(&view.rope_text());
+    edit
+}
+
+/// Run the operator of `cmd`, repeated `count` times. In visual mode the
+/// selected lines of `text` are rewrapped to `reflow_width` or shifted right
+/// away, and else it's left to [`do_motion_mode`].
+pub fn run_motion_mode(
+    text: &impl RopeText,
+    cursor: &mut Cursor,
+    cmd: &MotionModeCommand,
+    count: Option<usize>,
+    reflow_width: usize,
+) -> Option<OperatorAction> {
+    let count = count.unwrap_or(1);
+    let motion_mode = match cmd {
+        MotionModeCommand::MotionModeDelete => MotionMode::Delete { count },
+        MotionModeCommand::MotionModeIndent => MotionMode::Indent { count },
+        MotionModeCommand::MotionModeOutdent => MotionMode::Outdent { count },
+        MotionModeCommand::MotionModeYank => MotionMode::Yank { count },
+        MotionModeCommand::MotionModeReflow => MotionMode::Reflow {
+            count,
+            width: reflow_width,
+        },
+    };
+    let visual = match cursor.mode {
+        CursorMode::Visual { start, end, .. } => Some((start, end)),
+        _ => None,
+    };
+
+    match (visual, motion_mode) {
+        (Some((start, end)), motion_mode @ MotionMode::Reflow { .. }) => {
+            Some(OperatorAction::Edit(MotionEdit {
+                motion_mode,
+                start,
+                end,
+                is_vertical: true,
+            }))
+        }
+        // The count of a visual shift is how many indents to shift by
+        (
+            Some((start, end)),
+            MotionMode::Indent { count } | MotionMode::Outdent { count },
+        ) => {
+            let start_line = text.line_of_offset(start.min(end));
+            let end_line = text.line_of_offset(start.max(end));
+            Some(OperatorAction::Shift {
+                lines: start_line..=end_line,
+                count,
+                outdent: matches!(cmd, MotionModeCommand::MotionModeOutdent),
+            })
+        }
+        (_, motion_mode) => {
+            do_motion_mode(cursor, motion_mode).map(OperatorAction::Edit)
+        }
+    }
+}
+
+/// Have the operator `motion_mode` wait for its movement, or return its edit
+/// of the lines at the cursor when it's typed twice, like `dd`. An operator
+/// typed after another one drops both.
+pub fn do_motion_mode(
+    cursor: &mut Cursor,
+    motion_mode: MotionMode,
+) -> Option<MotionEdit> {
+    if let Some(cached_motion_mode) = cursor.motion_mode.take() {
+        // If it's the same MotionMode discriminant, continue, count is cached in the old motion_mode.
+        if core::mem::discriminant(&cached_motion_mode)
+            == core::mem::discriminant(&motion_mode)
+        {
+            let offset = cursor.offset();
+            return Some(MotionEdit {
+                motion_mode: cached_motion_mode,
+                start: offset,
+                end: offset,
+                is_vertical: true,
+            });
+        }
+    } else {
+        cursor.motion_mode = Some(motion_mode);
+    }
+    None
+}
@@ -26,6 +26,12 @@ pub enum Movement {
     MatchPairs,
     ParagraphForward,
     ParagraphBackward,
+    SentenceForward,
+    SentenceBackward,
+    /// The paragraph text objects, which select lines rather than move, for
+    /// motion modes to act on
+    InnerParagraph,
+    AroundParagraph,
 }
 
 impl PartialEq for Movement {
@@ -45,6 +51,8 @@ pub fn is_vertical(&self) -> bool {
                 | Movement::DocumentEnd
                 | Movement::ParagraphForward
                 | Movement::ParagraphBackward
+                | Movement::InnerParagraph
+                | Movement::AroundParagraph
         )
     }
 
@@ -61,9 +69,15 @@ pub fn is_jump(&self) -> bool {
                 | Movement::DocumentEnd
                 | Movement::ParagraphForward
                 | Movement::ParagraphBackward
+                | Movement::SentenceForward
+                | Movement::SentenceBackward
         )
     }
 
+    pub fn is_text_object(&self) -> bool {
+        matches!(self, Movement::InnerParagraph | Movement::AroundParagraph)
+    }
+
     pub fn update_index(
         &self,
         index: usize,
@@ -1,135 +1,124 @@
-use lapce_xi_rope::{Cursor, Rope, RopeInfo};
+use std::ops::Range;
 
-/// Describe char classifications used to compose word boundaries
-#[derive(Copy, Clone, PartialEq, Eq)]
-pub enum CharClassification {
-    /// Carriage Return (`r`)
-    Cr,
-    /// Line feed (`\n`)
-    Lf,
-    /// Includes letters and all of non-ascii unicode
-    Other,
-}
+use lapce_xi_rope::Rope;
 
-/// A word boundary can be the start of a word, its end or both for punctuation
-#[derive(PartialEq, Eq)]
-enum ParagraphBoundary {
-    /// Denote that this is not a boundary
-    Interior,
-    /// A boundary indicating the end of a new-line sequence
-    Start,
-    /// A boundary indicating the start of a new-line sequence
-    End,
-    /// Both start and end boundaries (when we have only one empty
-    /// line)
-    Both,
+/// Whether the line is blank, only holding whitespace if anything. Blank
+/// lines are what separates paragraphs.
+pub fn is_blank_line(text: &Rope, line: usize) -> bool {
+    let start = text.offset_of_line(line);
+    let end = text.offset_of_line(line + 1);
+    text.slice_to_cow(start..end).trim().is_empty()
 }
 
-impl ParagraphBoundary {
-    fn is_start(&self) -> bool {
-        *self == ParagraphBoundary::Start || *self == ParagraphBoundary::Both
-    }
+fn last_line(text: &Rope) -> usize {
+    text.line_of_offset(text.len())
+}
 
-    fn is_end(&self) -> bool {
-        *self == ParagraphBoundary::End || *self == ParagraphBoundary::Both
+/// The first blank line after the paragraph at `line`, or after the next
+/// paragraph if `line` is blank. `None` if it's the last paragraph.
+pub fn next_paragraph_boundary(text: &Rope, line: usize) -> Option<usize> {
+    let last_line = last_line(text);
+    let mut line = line;
+    while line <= last_line && is_blank_line(text, line) {
+        line += 1;
     }
-
-    #[allow(unused)]
-    fn is_boundary(&self) -> bool {
-        *self != ParagraphBoundary::Interior
+    while line <= last_line && !is_blank_line(text, line) {
+        line += 1;
     }
+    (line <= last_line).then_some(line)
 }
 
-/// A cursor providing utility function to navigate the rope
-/// by parahraphs boundaries.
-/// Boundaries can be the start of a word, its end, punctuation etc.
-pub struct ParagraphCursor<'a> {
-    pub(crate) inner: Cursor<'a, RopeInfo>,
-}
-
-impl<'a> ParagraphCursor<'a> {
-    pub fn new(text: &'a Rope, pos: usize) -> ParagraphCursor<'a> {
-        let inner = Cursor::new(text, pos);
-        ParagraphCursor { inner }
+/// The last blank line before the paragraph at `line`, or before the previous
+/// paragraph if `line` is blank. `None` if it's the first paragraph.
+pub fn prev_paragraph_boundary(text: &Rope, line: usize) -> Option<usize> {
+    let mut line = line;
+    while line > 0 && is_blank_line(text, line) {
+        line -= 1;
     }
-
-    pub fn prev_boundary(&mut self) -> Option<usize> {
-        if let (Some(ch1), Some(ch2), Some(ch3)) = (
-            self.inner.prev_codepoint(),
-            self.inner.prev_codepoint(),
-            self.inner.prev_codepoint(),
-        ) {
-            let mut prop1 = get_char_property(ch1);
-            let mut prop2 = get_char_property(ch2);
-            let mut prop3 = get_char_property(ch3);
-            let mut candidate = self.inner.pos();
-
-            while let Some(prev) = self.inner.prev_codepoint() {
-                let prop_prev = get_char_property(prev);
-                if classify_boundary(prop_prev, prop3, prop2, prop1).is_start() {
-                    break;
-                }
-                (prop3, prop2, prop1) = (prop_prev, prop3, prop2);
-                candidate = self.inner.pos();
-            }
-
-            self.inner.set(candidate + 1);
-            return Some(candidate + 1);
-        }
-
-        None
+    while line > 0 && !is_blank_line(text, line) {
+        line -= 1;
     }
+    is_blank_line(text, line).then_some(line)
+}
 
-    pub fn next_boundary(&mut self) -> Option<usize> {
-        if let (Some(ch1), Some(ch2), Some(ch3)) = (
-            self.inner.next_codepoint(),
-            self.inner.next_codepoint(),
-            self.inner.next_codepoint(),
-        ) {
-            let mut prop1 = get_char_property(ch1);
-            let mut prop2 = get_char_property(ch2);
-            let mut prop3 = get_char_property(ch3);
-            let mut candidate = self.inner.pos();
-
-            while let Some(next) = self.inner.next_codepoint() {
-                let prop_next = get_char_property(next);
-                if classify_boundary(prop1, prop2, prop3, prop_next).is_end() {
-                    break;
-                }
-
-                (prop1, prop2, prop3) = (prop2, prop3, prop_next);
-                candidate = self.inner.pos();
-            }
-            self.inner.set(candidate - 1);
-            return Some(candidate - 1);
-        }
-        None
+/// The lines of the run of blank or of non-blank lines that `line` is in.
+fn block(text: &Rope, line: usize) -> Range<usize> {
+    let last_line = last_line(text);
+    let blank = is_blank_line(text, line);
+    let mut start = line;
+    while start > 0 && is_blank_line(text, start - 1) == blank {
+        start -= 1;
+    }
+    let mut end = line + 1;
+    while end <= last_line && is_blank_line(text, end) == blank {
+        end += 1;
     }
+    start..end
 }
 
-/// Return the [`CharClassification`] of the input character
-pub fn get_char_property(codepoint: char) -> CharClassification {
-    match codepoint {
-        '\r' => CharClassification::Cr,
-        '\n' => CharClassification::Lf,
-        _ => CharClassification::Other,
+/// The lines of the paragraph text object at `line`, spanning `count`
+/// paragraphs. Blank lines count as paragraphs of their own for the inner
+/// object, while the around object also takes the blank lines after each
+/// paragraph, or before it when there are none after the last one.
+pub fn paragraph_object(
+    text: &Rope,
+    line: usize,
+    count: usize,
+    around: bool,
+) -> Range<usize> {
+    let last_line = last_line(text);
+    let line = line.min(last_line);
+    let blocks = if around {
+        count.max(1) * 2
+    } else {
+        count.max(1)
+    };
+
+    let Range { mut start, mut end } = block(text, line);
+    let mut taken = 1;
+    while taken < blocks && end <= last_line {
+        end = block(text, end).end;
+        taken += 1;
     }
+    if around
+        && taken < blocks
+        && start > 0
+        && !is_blank_line(text, line)
+        && is_blank_line(text, start - 1)
+    {
+        start = block(text, start - 1).start;
+    }
+    start..end
 }
 
-fn classify_boundary(
-    before_prev: CharClassification,
-    prev: CharClassification,
-    next: CharClassification,
-    after_next: CharClassification,
-) -> ParagraphBoundary {
-    use self::{CharClassification::*, ParagraphBoundary::*};
+#[cfg(test)]
+mod test {
+    use lapce_xi_rope::Rope;
+
+    use super::*;
+
+    #[test]
+    fn test_paragraph_boundaries() {
+        let text = Rope::from("a\nb\n\n  \nc\n\nd");
+        assert!(is_blank_line(&text, 3));
+        assert_eq!(next_paragraph_boundary(&text, 0), Some(2));
+        assert_eq!(next_paragraph_boundary(&text, 2), Some(5));
+        assert_eq!(next_paragraph_boundary(&text, 5), None);
+        assert_eq!(prev_paragraph_boundary(&text, 6), Some(5));
+        assert_eq!(prev_paragraph_boundary(&text, 5), Some(3));
+        assert_eq!(prev_paragraph_boundary(&text, 4), Some(3));
+        assert_eq!(prev_paragraph_boundary(&text, 1), None);
+    }
 
-    match (before_prev, prev, next, after_next) {
-        (Other, Lf, Lf, Other) => Both,
-        (_, Lf, Lf, Other) => Start,
-        (Lf, Cr, Lf, Other) => Start,
-        (Other, Lf, Lf, _) => End,
-        (Other, Cr, Lf, Cr) => End,
-        _ => Interior,
+    #[test]
+    fn test_paragraph_object() {
+        let text = Rope::from("a\nb\n\n  \nc\n\nd");
+        assert_eq!(paragraph_object(&text, 1, 1, false), 0..2);
+        assert_eq!(paragraph_object(&text, 2, 1, false), 2..4);
+        assert_eq!(paragraph_object(&text, 0, 2, false), 0..4);
+        assert_eq!(paragraph_object(&text, 0, 1, true), 0..4);
+        assert_eq!(paragraph_object(&text, 3, 1, true), 2..5);
+        // The last paragraph takes the blank lines before it
+        assert_eq!(paragraph_object(&text, 6, 1, true), 5..7);
     }
 }
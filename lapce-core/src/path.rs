@@ -0,0 +1,168 @@
+use std::path::{Component, Path, PathBuf};
+
+/// Whether the file systems of this platform usually ignore case, so that
+/// `Main.rs` and `main.rs` are the same file
+pub const CASE_INSENSITIVE: bool = cfg!(any(windows, target_os = "macos"));
+
+/// `path` with its `.` and `..` resolved without looking at the file system,
+/// for paths that may not exist. A `..` past the root is dropped.
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => normalized.push(component),
+            },
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// The real path of `path`, with symlinks and `..` resolved when it exists,
+/// and with only `.` and `..` resolved when it doesn't.
+pub fn canonicalize(path: &Path) -> PathBuf {
+    match path.canonicalize() {
+        Ok(canonical) => strip_verbatim(canonical),
+        Err(_) => normalize(path),
+    }
+}
+
+/// What the paths naming the same file have in common, be it through a
+/// symlink, with `..` in it, or in another case where the case is ignored.
+pub fn file_key(path: &Path, case_insensitive: bool) -> PathBuf {
+    let path = canonicalize(path);
+    if case_insensitive {
+        PathBuf::from(path.to_string_lossy().to_lowercase())
+    } else {
+        path
+    }
+}
+
+/// Where `path` is after `from` was renamed to `to`, if it's `from` or in
+/// it, for the files in a renamed folder too.
+pub fn renamed_path(path: &Path, from: &Path, to: &Path) -> Option<PathBuf> {
+    let rest = path.strip_prefix(from).ok()?;
+    if rest.as_os_str().is_empty() {
+        Some(to.to_path_buf())
+    } else {
+        Some(to.join(rest))
+    }
+}
+
+/// Windows canonicalizes `C:\a` to `\\?\C:\a`, which the rest of the paths
+/// aren't written like.
+#[cfg(windows)]
+fn strip_verbatim(path: PathBuf) -> PathBuf {
+    use std::path::Prefix;
+
+    let mut components = path.components();
+    match components.next() {
+        Some(Component::Prefix(prefix)) => match prefix.kind() {
+            Prefix::VerbatimDisk(disk) => {
+                let mut stripped = PathBuf::from(format!("{}:\\", disk as char));
+                stripped.extend(components.skip(1));
+                stripped
+            }
+            _ => path,
+        },
+        _ => path,
+    }
+}
+
+#[cfg(not(windows))]
+fn strip_verbatim(path: PathBuf) -> PathBuf {
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{file_key, normalize, renamed_path};
+
+    #[test]
+    fn test_normalize() {
+        for (path, normalized) in [
+            ("/a/./b/../c", "/a/c"),
+            ("/a/b/../../..", "/"),
+            ("a/../../b", "../b"),
+            ("../../b", "../../b"),
+            ("./src/main.rs", "src/main.rs"),
+        ] {
+            assert_eq!(normalize(Path::new(path)), PathBuf::from(normalized));
+        }
+    }
+
+    #[test]
+    fn test_renamed_path() {
+        let renamed = |path: &str, from: &str, to: &str| {
+            renamed_path(Path::new(path), Path::new(from), Path::new(to))
+        };
+        assert_eq!(
+            renamed("/ws/a.rs", "/ws/a.rs", "/ws/b.rs"),
+            Some(PathBuf::from("/ws/b.rs"))
+        );
+        assert_eq!(
+            renamed("/ws/src/a.rs", "/ws/src", "/ws/lib"),
+            Some(PathBuf::from("/ws/lib/a.rs"))
+        );
+        assert_eq!(renamed("/ws/src2/a.rs", "/ws/src", "/ws/lib"), None);
+        assert_eq!(renamed("/ws/b.rs", "/ws/a.rs", "/ws/c.rs"), None);
+    }
+
+    #[test]
+    fn test_file_key_relative_path() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(dir.join("src/main.rs"), "").unwrap();
+
+        let key = file_key(&dir.join("src/main.rs"), false);
+        assert_eq!(file_key(&dir.join("./src/main.rs"), false), key);
+        assert_eq!(file_key(&dir.join("src/../src/main.rs"), false), key);
+        assert_ne!(file_key(&dir.join("src/lib.rs"), false), key);
+        // Files that don't exist yet are compared by their normalized paths
+        assert_eq!(
+            file_key(&dir.join("src/../new.rs"), false),
+            dir.join("new.rs")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_file_key_symlinked_dir() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        std::fs::create_dir_all(dir.join("real")).unwrap();
+        std::fs::write(dir.join("real/main.rs"), "").unwrap();
+        std::os::unix::fs::symlink(dir.join("real"), dir.join("link")).unwrap();
+
+        assert_eq!(
+            file_key(&dir.join("link/main.rs"), false),
+            file_key(&dir.join("real/main.rs"), false)
+        );
+        assert_eq!(
+            file_key(&dir.join("link/../real/main.rs"), false),
+            file_key(&dir.join("real/main.rs"), false)
+        );
+    }
+
+    #[test]
+    fn test_file_key_case() {
+        let path = Path::new("/not/on/disk/Main.rs");
+        assert_ne!(
+            file_key(path, false),
+            file_key(&path.with_file_name("main.rs"), false)
+        );
+        assert_eq!(
+            file_key(path, true),
+            file_key(&path.with_file_name("main.rs"), true)
+        );
+    }
+}
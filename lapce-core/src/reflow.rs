@@ -0,0 +1,130 @@
+/// The comment and quote markers that are kept at the start of each line of
+/// a rewrapped paragraph, the longest ones first.
+const MARKERS: &[&str] = &["//!", "///", "//", "--", "#", ">", ";", "*"];
+
+/// The part of `line` that starts each line of its paragraph: its
+/// indentation and a comment or quote marker, with the whitespace after it.
+pub fn line_prefix(line: &str) -> &str {
+    let indent = line.len() - line.trim_start().len();
+    let rest = &line[indent..];
+    let Some(marker) = MARKERS.iter().find(|marker| rest.starts_with(**marker))
+    else {
+        return &line[..indent];
+    };
+    let after = &rest[marker.len()..];
+    let space = after.len() - after.trim_start().len();
+    &line[..indent + marker.len() + space]
+}
+
+/// Rewrap `text`, made of whole lines, so that its lines are no longer than
+/// `width` characters where possible. Each paragraph keeps the prefix of its
+/// lines, blank lines still separate paragraphs, and lines with different
+/// prefixes aren't joined. A word longer than the width gets a line of its
+/// own rather than being split.
+pub fn reflow(text: &str, width: usize) -> String {
+    let line_ending = if text.contains("\r\n") { "\r\n" } else { "\n" };
+    let body = text
+        .strip_suffix(line_ending)
+        .or_else(|| text.strip_suffix('\n'))
+        .unwrap_or(text);
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut paragraph: Option<(&str, Vec<&str>)> = None;
+    for line in body.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let prefix = line_prefix(line);
+        let content = line[prefix.len()..].trim();
+        match &mut paragraph {
+            Some((current, words)) if *current == prefix && !content.is_empty() => {
+                words.extend(content.split_whitespace());
+                continue;
+            }
+            _ => {}
+        }
+        if let Some((prefix, words)) = paragraph.take() {
+            wrap(prefix, &words, width, &mut lines);
+        }
+        if content.is_empty() {
+            lines.push(line.to_string());
+        } else {
+            paragraph = Some((prefix, content.split_whitespace().collect()));
+        }
+    }
+    if let Some((prefix, words)) = paragraph {
+        wrap(prefix, &words, width, &mut lines);
+    }
+
+    let mut reflowed = lines.join(line_ending);
+    if body.len() < text.len() {
+        reflowed.push_str(line_ending);
+    }
+    reflowed
+}
+
+/// Lay `words` out on lines that start with `prefix`, each as full as fits
+/// in `width`.
+fn wrap(prefix: &str, words: &[&str], width: usize, lines: &mut Vec<String>) {
+    let prefix_width = prefix.chars().count();
+    let mut line = String::new();
+    let mut line_width = 0;
+    for word in words {
+        let word_width = word.chars().count();
+        if !line.is_empty() && line_width + 1 + word_width > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if line.is_empty() {
+            line.push_str(prefix);
+            line_width = prefix_width;
+        } else {
+            line.push(' ');
+            line_width += 1;
+        }
+        line.push_str(word);
+        line_width += word_width;
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{line_prefix, reflow};
+
+    #[test]
+    fn test_line_prefix() {
+        assert_eq!(line_prefix("    // some comment"), "    // ");
+        assert_eq!(line_prefix("/// docs"), "/// ");
+        assert_eq!(line_prefix("# heading"), "# ");
+        assert_eq!(line_prefix("> quoted"), "> ");
+        assert_eq!(line_prefix("  plain text"), "  ");
+        assert_eq!(line_prefix("text"), "");
+    }
+
+    #[test]
+    fn test_reflow() {
+        let text = "// one two three\n// four five six seven\n";
+        assert_eq!(
+            reflow(text, 16),
+            "// one two three\n// four five six\n// seven\n"
+        );
+        assert_eq!(reflow(text, 80), "// one two three four five six seven\n");
+        assert_eq!(reflow("a b c", 3), "a b\nc");
+    }
+
+    #[test]
+    fn test_reflow_paragraphs() {
+        let text = "# a b\n# c d\n#\n# e\n\n> f\nno prefix\n";
+        assert_eq!(reflow(text, 80), "# a b c d\n#\n# e\n\n> f\nno prefix\n");
+        assert_eq!(reflow("a\r\nb\r\n", 80), "a b\r\n");
+    }
+
+    #[test]
+    fn test_reflow_long_word() {
+        let text = "    // see https://example.com/a/very/long/link here\n";
+        assert_eq!(
+            reflow(text, 20),
+            "    // see\n    // https://example.com/a/very/long/link\n    // here\n"
+        );
+    }
+}
@@ -0,0 +1,313 @@
+use std::{collections::HashSet, ops::Range};
+
+use regex::{Regex, RegexBuilder};
+
+/// Replaces the matches of a search, line by line like the search itself.
+#[derive(Clone, Debug)]
+pub struct Replacer {
+    regex: Regex,
+    replacement: String,
+    /// Whether `$1`, `${name}` and the like in the replacement refer to the
+    /// groups of the match, which is only the case for regex searches
+    expand: bool,
+    /// Whether the replacement takes the case of each match, see
+    /// [`preserve_case`]
+    preserve_case: bool,
+}
+
+impl Replacer {
+    pub fn new(
+        pattern: &str,
+        case_sensitive: bool,
+        whole_word: bool,
+        is_regex: bool,
+        replacement: &str,
+        preserve_case: bool,
+    ) -> Result<Self, regex::Error> {
+        let pattern = if is_regex {
+            pattern.to_string()
+        } else {
+            regex::escape(pattern)
+        };
+        let pattern = if whole_word {
+            format!(r"\b(?:{pattern})\b")
+        } else {
+            pattern
+        };
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(!case_sensitive)
+            .build()?;
+        Ok(Self {
+            regex,
+            replacement: replacement.to_string(),
+            expand: is_regex,
+            preserve_case,
+        })
+    }
+
+    /// `line` with all of its matches replaced.
+    pub fn replace_line(&self, line: &str) -> String {
+        let mut edits = Vec::new();
+        self.line_edits(line, 0, &mut edits);
+        apply_edits(line, &edits)
+    }
+
+    /// The byte ranges of `text` to replace, with their new text, for the
+    /// matches on the given lines, which count from 1.
+    pub fn edits(
+        &self,
+        text: &str,
+        lines: &HashSet<usize>,
+    ) -> Vec<(Range<usize>, String)> {
+        let mut edits = Vec::new();
+        let mut offset = 0;
+        for (i, line) in text.split_inclusive('\n').enumerate() {
+            if lines.contains(&(i + 1)) {
+                let content = line.trim_end_matches(['\n', '\r']);
+                self.line_edits(content, offset, &mut edits);
+            }
+            offset += line.len();
+        }
+        edits
+    }
+
+    fn line_edits(
+        &self,
+        line: &str,
+        offset: usize,
+        edits: &mut Vec<(Range<usize>, String)>,
+    ) {
+        for captures in self.regex.captures_iter(line) {
+            let Some(m) = captures.get(0) else {
+                continue;
+            };
+            let mut new = String::new();
+            if self.expand {
+                captures.expand(&self.replacement, &mut new);
+            } else {
+                new.push_str(&self.replacement);
+            }
+            if self.preserve_case {
+                new = preserve_case(m.as_str(), &new);
+            }
+            edits.push((offset + m.start()..offset + m.end(), new));
+        }
+    }
+}
+
+/// `replacement` in the case `matched` is written in: all lowercase, all
+/// uppercase, or capitalized, like `color` → `colour` turning `COLOR` into
+/// `COLOUR` and `Color` into `Colour`. A mix of cases is kept as best it can
+/// be, with the words of the replacement following the words of the match,
+/// whether they're split by case (`camelCase`) or by `_` and `-`
+/// (`snake_case`). A replacement that has uppercase letters of its own is left
+/// as it is when the match is lowercase.
+pub fn preserve_case(matched: &str, replacement: &str) -> String {
+    match Case::of(matched) {
+        Case::None => replacement.to_string(),
+        Case::Lower => {
+            if replacement.chars().any(char::is_uppercase) {
+                replacement.to_string()
+            } else {
+                replacement.to_lowercase()
+            }
+        }
+        Case::Upper => replacement.to_uppercase(),
+        Case::Capitalized => capitalize(replacement),
+        Case::Mixed => {
+            let matched = words(matched)
+                .into_iter()
+                .map(|word| Case::of(&matched[word]))
+                .collect::<Vec<_>>();
+            let words = words(replacement);
+            if matched.len() < 2 || words.len() < 2 {
+                return replacement.to_string();
+            }
+            let mut result = String::with_capacity(replacement.len());
+            let mut last = 0;
+            for (i, word) in words.into_iter().enumerate() {
+                // What's between the words, like `_`, is kept
+                result.push_str(&replacement[last..word.start]);
+                let new = &replacement[word.clone()];
+                // Words the match doesn't have take the case of its last one
+                let case = matched.get(i).or(matched.last()).copied();
+                result.push_str(&match case {
+                    Some(Case::Upper) => new.to_uppercase(),
+                    Some(Case::Capitalized) => capitalize(&new.to_lowercase()),
+                    Some(Case::Lower) => new.to_lowercase(),
+                    _ => new.to_string(),
+                });
+                last = word.end;
+            }
+            result.push_str(&replacement[last..]);
+            result
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Case {
+    /// Nothing with a case, like `_1`
+    None,
+    Lower,
+    Upper,
+    /// An uppercase letter followed by lowercase ones, or a single uppercase
+    /// letter
+    Capitalized,
+    Mixed,
+}
+
+impl Case {
+    fn of(text: &str) -> Case {
+        let mut cased = text
+            .chars()
+            .filter(|c| c.is_lowercase() || c.is_uppercase());
+        let Some(first) = cased.next() else {
+            return Case::None;
+        };
+        let rest = cased.collect::<Vec<_>>();
+        let rest_lower = rest.iter().all(|c| c.is_lowercase());
+        if first.is_lowercase() {
+            if rest_lower {
+                Case::Lower
+            } else {
+                Case::Mixed
+            }
+        } else if rest_lower {
+            Case::Capitalized
+        } else if rest.iter().all(|c| c.is_uppercase()) {
+            Case::Upper
+        } else {
+            Case::Mixed
+        }
+    }
+}
+
+/// `text` with its first letter uppercase.
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// The byte ranges of the words of an identifier, which are split by what
+/// isn't a letter or a digit, by a lowercase letter followed by an uppercase
+/// one (`camelCase`), and by an uppercase letter followed by an uppercase one
+/// and then a lowercase one (`HTTPServer`).
+fn words(text: &str) -> Vec<Range<usize>> {
+    let chars = text.char_indices().collect::<Vec<_>>();
+    let mut words = Vec::new();
+    let mut start = None;
+    for (i, &(offset, c)) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            if let Some(start) = start.take() {
+                words.push(start..offset);
+            }
+            continue;
+        }
+        let boundary = match (i.checked_sub(1).map(|i| chars[i].1), chars.get(i + 1))
+        {
+            (Some(prev), _) if prev.is_lowercase() && c.is_uppercase() => true,
+            (Some(prev), Some(&(_, next))) => {
+                prev.is_uppercase() && c.is_uppercase() && next.is_lowercase()
+            }
+            _ => false,
+        };
+        match start {
+            Some(word) if boundary => {
+                words.push(word..offset);
+                start = Some(offset);
+            }
+            Some(_) => {}
+            None => start = Some(offset),
+        }
+    }
+    if let Some(start) = start {
+        words.push(start..text.len());
+    }
+    words
+}
+
+/// `text` with the `edits` from [`Replacer::edits`] applied.
+pub fn apply_edits(text: &str, edits: &[(Range<usize>, String)]) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+    for (range, new) in edits {
+        result.push_str(&text[last..range.start]);
+        result.push_str(new);
+        last = range.end;
+    }
+    result.push_str(&text[last..]);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::{apply_edits, preserve_case, Replacer};
+
+    #[test]
+    fn test_replace_lines() {
+        let text = "foo bar\nFoo foo\r\nfoo\n";
+        let replacer =
+            Replacer::new("foo", false, false, false, "$1x", false).unwrap();
+        let edits = replacer.edits(text, &HashSet::from([2, 3]));
+        assert_eq!(apply_edits(text, &edits), "foo bar\n$1x $1x\r\n$1x\n");
+
+        let replacer =
+            Replacer::new("foo", true, true, false, "baz", false).unwrap();
+        assert_eq!(replacer.replace_line("foo foobar Foo"), "baz foobar Foo");
+    }
+
+    #[test]
+    fn test_replace_capture_groups() {
+        let replacer =
+            Replacer::new(r"(\w+)\.unwrap\(\)", true, false, true, "${1}?", false)
+                .unwrap();
+        assert_eq!(
+            replacer.replace_line("let a = b.unwrap() + c.unwrap();"),
+            "let a = b? + c?;"
+        );
+    }
+
+    #[test]
+    fn test_preserve_case() {
+        let cases = [
+            ("color", "colour", "colour"),
+            ("Color", "colour", "Colour"),
+            ("COLOR", "colour", "COLOUR"),
+            ("color", "myColour", "myColour"),
+            ("Color", "myColour", "MyColour"),
+            ("fooBar", "bazQux", "bazQux"),
+            ("fooBar", "baz_qux", "baz_Qux"),
+            ("FooBar", "baz_qux_quux", "Baz_Qux_Quux"),
+            ("HTTPServer", "httpClient", "HTTPClient"),
+            ("HTTPServer", "server", "server"),
+            ("getHTTPServer", "get_web_server", "get_WEB_Server"),
+            ("snake_case_name", "other_name", "other_name"),
+            ("SNAKE_CASE_NAME", "other_name", "OTHER_NAME"),
+            ("Snake_case_name", "other_name", "Other_name"),
+            ("Snake_Case", "other_name", "Other_Name"),
+            ("X", "why", "Why"),
+            ("_1", "two", "two"),
+            ("Über", "öl", "Öl"),
+        ];
+        for (matched, replacement, expected) in cases {
+            assert_eq!(
+                preserve_case(matched, replacement),
+                expected,
+                "{matched} -> {replacement}"
+            );
+        }
+
+        let replacer =
+            Replacer::new("color", false, false, false, "colour", true).unwrap();
+        assert_eq!(
+            replacer.replace_line("color Color COLOR"),
+            "colour Colour COLOUR"
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use lapce_xi_rope::{Cursor, Rope, RopeInfo};
+
+/// The characters that may follow the punctuation ending a sentence, like the
+/// quote in `"Hi."`
+const SENTENCE_CLOSERS: &[char] = &[')', ']', '"', '\''];
+
+/// Whether a sentence starts at `offset`, which should be on a non-blank
+/// character. That's the case after a `.`, `!` or `?` followed by whitespace,
+/// after a blank line, and at the first non-blank character of the text.
+fn is_sentence_start(text: &Rope, offset: usize) -> bool {
+    let mut cursor = Cursor::<RopeInfo>::new(text, offset);
+    let mut had_space = false;
+    let mut newlines = 0;
+    loop {
+        match cursor.prev_codepoint() {
+            None => return true,
+            Some(c) if c.is_whitespace() => {
+                had_space = true;
+                if c == '\n' {
+                    newlines += 1;
+                    if newlines == 2 {
+                        return true;
+                    }
+                }
+            }
+            Some(mut c) => {
+                if !had_space {
+                    return false;
+                }
+                while SENTENCE_CLOSERS.contains(&c) {
+                    match cursor.prev_codepoint() {
+                        Some(prev) => c = prev,
+                        None => return false,
+                    }
+                }
+                return matches!(c, '.' | '!' | '?');
+            }
+        }
+    }
+}
+
+/// Whether `offset` is on the first character of a word.
+fn is_word_start(text: &Rope, offset: usize) -> bool {
+    let next = Cursor::<RopeInfo>::new(text, offset).next_codepoint();
+    let prev = Cursor::<RopeInfo>::new(text, offset).prev_codepoint();
+    matches!(next, Some(c) if !c.is_whitespace())
+        && !matches!(prev, Some(c) if !c.is_whitespace())
+}
+
+/// The start of the first sentence after `offset`.
+pub fn next_sentence_start(text: &Rope, offset: usize) -> Option<usize> {
+    let mut cursor = Cursor::<RopeInfo>::new(text, offset);
+    while cursor.next_codepoint().is_some() {
+        let offset = cursor.pos();
+        if offset < text.len()
+            && is_word_start(text, offset)
+            && is_sentence_start(text, offset)
+        {
+            return Some(offset);
+        }
+    }
+    None
+}
+
+/// The start of the last sentence before `offset`.
+pub fn prev_sentence_start(text: &Rope, offset: usize) -> Option<usize> {
+    let mut cursor = Cursor::<RopeInfo>::new(text, offset);
+    while cursor.prev_codepoint().is_some() {
+        let offset = cursor.pos();
+        if is_word_start(text, offset) && is_sentence_start(text, offset) {
+            return Some(offset);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use lapce_xi_rope::Rope;
+
+    use super::*;
+
+    #[test]
+    fn test_sentence_starts() {
+        let text = Rope::from("  One. Two? \"Three!\" Four\n\nFive, e.g. six");
+        assert_eq!(next_sentence_start(&text, 0), Some(2));
+        assert_eq!(next_sentence_start(&text, 2), Some(7));
+        assert_eq!(next_sentence_start(&text, 7), Some(12));
+        assert_eq!(next_sentence_start(&text, 12), Some(21));
+        assert_eq!(next_sentence_start(&text, 21), Some(27));
+        // An abbreviation ends a sentence too, like it does in vim
+        assert_eq!(next_sentence_start(&text, 27), Some(38));
+        assert_eq!(next_sentence_start(&text, 38), None);
+
+        assert_eq!(prev_sentence_start(&text, 30), Some(27));
+        assert_eq!(prev_sentence_start(&text, 27), Some(21));
+        assert_eq!(prev_sentence_start(&text, 9), Some(7));
+        assert_eq!(prev_sentence_start(&text, 2), None);
+    }
+}
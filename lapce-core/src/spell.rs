@@ -0,0 +1,292 @@
+use std::{collections::HashSet, ops::Range};
+
+/// Words shorter than this aren't checked, as they are mostly abbreviations
+const MIN_WORD_LEN: usize = 3;
+
+/// How many edits away from a misspelled word its suggestions can be
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+/// The words that are spelled right, kept in lowercase so that any casing of
+/// them passes.
+#[derive(Debug, Clone, Default)]
+pub struct Dictionary {
+    words: HashSet<String>,
+}
+
+impl Dictionary {
+    /// A dictionary of the words of `list`, one per line.
+    pub fn from_list(list: &str) -> Self {
+        let mut dictionary = Self::default();
+        dictionary.extend(list);
+        dictionary
+    }
+
+    /// Add the words of `list`, one per line. Blank lines and lines starting
+    /// with `#` are left out.
+    pub fn extend(&mut self, list: &str) {
+        for line in list.lines() {
+            let word = line.trim();
+            if !word.is_empty() && !word.starts_with('#') {
+                self.insert(word);
+            }
+        }
+    }
+
+    pub fn insert(&mut self, word: &str) {
+        self.words.insert(word.to_lowercase());
+    }
+
+    /// Whether `word` is spelled right, in any casing, or is the possessive of
+    /// a word that is.
+    pub fn contains(&self, word: &str) -> bool {
+        let word = word.to_lowercase();
+        self.words.contains(&word)
+            || word
+                .strip_suffix("'s")
+                .map(|word| self.words.contains(word))
+                .unwrap_or(false)
+    }
+
+    /// Up to `max` words of the dictionary that `word` could be a misspelling
+    /// of, the closest first, and among those the ones that start the same
+    /// way. They are cased like `word` is.
+    pub fn suggestions(&self, word: &str, max: usize) -> Vec<String> {
+        let lower = word.to_lowercase();
+        let chars = lower.chars().collect::<Vec<_>>();
+        let mut found = self
+            .words
+            .iter()
+            .filter(|candidate| {
+                candidate.len().abs_diff(lower.len()) <= MAX_SUGGESTION_DISTANCE
+            })
+            .filter_map(|candidate| {
+                let candidate_chars = candidate.chars().collect::<Vec<_>>();
+                let distance = edit_distance(&chars, &candidate_chars);
+                (distance > 0 && distance <= MAX_SUGGESTION_DISTANCE).then(|| {
+                    let same_start = candidate_chars.first() == chars.first();
+                    ((distance, !same_start, candidate.as_str()), candidate)
+                })
+            })
+            .collect::<Vec<_>>();
+        found.sort_by(|(a, _), (b, _)| a.cmp(b));
+        found
+            .into_iter()
+            .take(max)
+            .map(|(_, candidate)| match_case(word, candidate))
+            .collect()
+    }
+}
+
+/// How many insertions, deletions, substitutions and swaps of neighbouring
+/// characters it takes to turn `a` into `b`.
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut rows = vec![(0..=b.len()).collect::<Vec<_>>()];
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            row[j] = (rows[i - 1][j] + 1)
+                .min(row[j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+        rows.push(row);
+    }
+    rows[a.len()][b.len()]
+}
+
+/// `word` in the casing of `like`: in capitals, capitalized or as it is.
+fn match_case(like: &str, word: &str) -> String {
+    let mut chars = like.chars();
+    let first_upper = chars.next().map(char::is_uppercase).unwrap_or(false);
+    if first_upper && like.len() > 1 && chars.all(|c| !c.is_lowercase()) {
+        word.to_uppercase()
+    } else if first_upper {
+        let mut chars = word.chars();
+        chars
+            .next()
+            .map(|first| first.to_uppercase().chain(chars).collect())
+            .unwrap_or_default()
+    } else {
+        word.to_string()
+    }
+}
+
+/// The ranges of the words of `text` to check. Identifiers are split into the
+/// words they are made of, like `snake_case` and `camelCase` into two each.
+/// What looks like a number, a path, a URL or an email address isn't checked,
+/// nor are acronyms in capitals and words with letters out of ASCII, which a
+/// word list for English doesn't cover.
+pub fn words(text: &str) -> Vec<Range<usize>> {
+    let mut words = Vec::new();
+    for chunk in text.split(char::is_whitespace) {
+        if chunk.contains("://") || chunk.contains('@') {
+            continue;
+        }
+        let chunk_start = chunk.as_ptr() as usize - text.as_ptr() as usize;
+        let is_token_char = |c: char| c.is_alphanumeric() || c == '_' || c == '\'';
+        let mut rest = chunk;
+        while let Some(start) = rest.find(is_token_char) {
+            let end = rest[start..]
+                .find(|c| !is_token_char(c))
+                .map(|len| start + len)
+                .unwrap_or(rest.len());
+            let token = &rest[start..end];
+            let offset = chunk_start + (chunk.len() - rest.len()) + start;
+            if !is_glued(&rest[..start], &rest[end..])
+                && token
+                    .chars()
+                    .all(|c| c.is_ascii_alphabetic() || !c.is_alphanumeric())
+            {
+                for part in split_identifier(token) {
+                    let part_start = part.start;
+                    let word = &token[part];
+                    let trimmed = word.trim_start_matches('\'');
+                    let word_start = part_start + word.len() - trimmed.len();
+                    let trimmed = trimmed.trim_end_matches('\'');
+                    if trimmed.len() >= MIN_WORD_LEN
+                        && trimmed.chars().any(|c| c.is_ascii_lowercase())
+                    {
+                        let start = offset + word_start;
+                        words.push(start..start + trimmed.len());
+                    }
+                }
+            }
+            rest = &rest[end..];
+        }
+    }
+    words
+}
+
+/// Whether a token is part of a path, a file name or an escape sequence,
+/// from the text of its chunk `before` and `after` it.
+fn is_glued(before: &str, after: &str) -> bool {
+    let joins = |c: char| matches!(c, '.' | '/' | '\\' | ':');
+    let mut before = before.chars().rev();
+    let mut after = after.chars();
+    let glued_before = match (before.next(), before.next()) {
+        (Some('\\'), _) => true,
+        (Some(c), Some(prev)) => joins(c) && prev.is_alphanumeric(),
+        _ => false,
+    };
+    let glued_after = match (after.next(), after.next()) {
+        (Some(c), Some(next)) => joins(c) && next.is_alphanumeric(),
+        _ => false,
+    };
+    glued_before || glued_after
+}
+
+/// The ranges of the words of an identifier of ASCII letters, split at
+/// underscores and where the case changes, keeping acronyms whole, like
+/// `parseHTTPResponse` into `parse`, `HTTP` and `Response`.
+fn split_identifier(token: &str) -> Vec<Range<usize>> {
+    let bytes = token.as_bytes();
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for i in 0..bytes.len() {
+        if bytes[i] == b'_' {
+            if start < i {
+                parts.push(start..i);
+            }
+            start = i + 1;
+            continue;
+        }
+        if i == start {
+            continue;
+        }
+        let prev = bytes[i - 1];
+        let next = bytes.get(i + 1).copied();
+        let boundary = bytes[i].is_ascii_uppercase()
+            && (prev.is_ascii_lowercase()
+                || (prev.is_ascii_uppercase()
+                    && next.map(|c| c.is_ascii_lowercase()).unwrap_or(false)));
+        if boundary {
+            parts.push(start..i);
+            start = i;
+        }
+    }
+    if start < bytes.len() {
+        parts.push(start..bytes.len());
+    }
+    parts
+}
+
+/// The ranges of the words of `text` that aren't in the dictionary.
+pub fn misspellings(dictionary: &Dictionary, text: &str) -> Vec<Range<usize>> {
+    words(text)
+        .into_iter()
+        .filter(|range| !dictionary.contains(&text[range.clone()]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word_list(text: &str) -> Vec<&str> {
+        words(text).into_iter().map(|range| &text[range]).collect()
+    }
+
+    #[test]
+    fn test_words() {
+        assert_eq!(
+            word_list("Some plain text, isn't it?"),
+            vec!["Some", "plain", "text", "isn't"]
+        );
+        assert_eq!(
+            word_list("parseHTTPResponse snake_case_name"),
+            vec!["parse", "Response", "snake", "case", "name"]
+        );
+        assert_eq!(
+            word_list("'quoted' words of a \"string\""),
+            vec!["quoted", "words", "string"]
+        );
+    }
+
+    #[test]
+    fn test_words_skipped() {
+        assert!(word_list("https://example.com/page mail@example.com").is_empty());
+        assert!(word_list("src/main.rs utf8 x86_64 \\ttab").is_empty());
+        assert!(word_list("NASA naïve").is_empty());
+        assert_eq!(word_list("end of line."), vec!["end", "line"]);
+    }
+
+    #[test]
+    fn test_misspellings() {
+        let dictionary = Dictionary::from_list("# words\nthe\nword\nspelled\n");
+        let text = "The wrod is spelled wrong in the word's line";
+        let found = misspellings(&dictionary, text)
+            .into_iter()
+            .map(|range| &text[range])
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec!["wrod", "wrong", "line"]);
+    }
+
+    #[test]
+    fn test_suggestions() {
+        let dictionary = Dictionary::from_list("word\nwork\nwords\nsword\nfar\n");
+        assert_eq!(
+            dictionary.suggestions("wrod", 3),
+            vec!["word", "words", "work"]
+        );
+        assert_eq!(dictionary.suggestions("Wrod", 1), vec!["Word"]);
+        assert_eq!(dictionary.suggestions("WROD", 1), vec!["WORD"]);
+        assert!(dictionary.suggestions("completely", 3).is_empty());
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        let distance = |a: &str, b: &str| {
+            edit_distance(
+                &a.chars().collect::<Vec<_>>(),
+                &b.chars().collect::<Vec<_>>(),
+            )
+        };
+        assert_eq!(distance("word", "word"), 0);
+        assert_eq!(distance("wrod", "word"), 1);
+        assert_eq!(distance("wod", "word"), 1);
+        assert_eq!(distance("kitten", "sitting"), 3);
+    }
+}
@@ -0,0 +1,185 @@
+use lapce_xi_rope::Rope;
+use lsp_types::{DocumentSymbol, Position, Range, SymbolKind};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::encoding::offset_utf8_to_utf16_str;
+
+/// A declaration keyword, after the modifiers that may come before it, and
+/// the name it declares
+static DECLARATION: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(concat!(
+        r"^(?:(?:pub(?:\([^)]*\))?|export|default|async|unsafe|static|public",
+        r"|private|protected|abstract|final|extern(?:\s+\x22[^\x22]*\x22)?)\s+)*",
+        r"(fn|func|function|def|class|struct|enum|union|trait|interface",
+        r"|impl(?:<[^>]*>)?|type|mod|module|namespace)\s+",
+        // The receiver of a Go method
+        r"(?:\([^)]*\)\s*)?",
+        r"([A-Za-z_$][A-Za-z0-9_$]*)",
+    ))
+    .unwrap()
+});
+
+fn symbol_kind(keyword: &str, in_type: bool) -> SymbolKind {
+    match keyword {
+        "fn" | "func" | "function" | "def" if in_type => SymbolKind::METHOD,
+        "fn" | "func" | "function" | "def" => SymbolKind::FUNCTION,
+        "class" => SymbolKind::CLASS,
+        "struct" | "union" => SymbolKind::STRUCT,
+        "enum" => SymbolKind::ENUM,
+        "trait" | "interface" => SymbolKind::INTERFACE,
+        "type" => SymbolKind::TYPE_PARAMETER,
+        "mod" | "module" => SymbolKind::MODULE,
+        "namespace" => SymbolKind::NAMESPACE,
+        _ => SymbolKind::OBJECT,
+    }
+}
+
+/// Whether the symbol of `kind` holds methods rather than nested functions.
+fn is_type(kind: SymbolKind) -> bool {
+    matches!(
+        kind,
+        SymbolKind::CLASS
+            | SymbolKind::STRUCT
+            | SymbolKind::INTERFACE
+            | SymbolKind::OBJECT
+    )
+}
+
+/// Whether the line closes the block of the symbol above it at the same
+/// indent, like `}` or `end`, rather than starting something new.
+fn is_block_end(line: &str) -> bool {
+    line.starts_with(['}', ')', ']']) || line == "end" || line.starts_with("end ")
+}
+
+/// The declarations found in `text` by their keywords, for documents no
+/// language server gives the symbols of. A symbol lasts until the next line
+/// indented no deeper than it, and holds the symbols declared inside.
+#[allow(deprecated)]
+pub fn scan_symbols(text: &Rope) -> Vec<DocumentSymbol> {
+    fn close(
+        stack: &mut Vec<(usize, DocumentSymbol)>,
+        roots: &mut Vec<DocumentSymbol>,
+        end: Position,
+    ) {
+        if let Some((_, mut symbol)) = stack.pop() {
+            symbol.range.end = end.max(symbol.selection_range.end);
+            match stack.last_mut() {
+                Some((_, parent)) => {
+                    parent.children.get_or_insert_with(Vec::new).push(symbol)
+                }
+                None => roots.push(symbol),
+            }
+        }
+    }
+
+    let mut roots = Vec::new();
+    let mut stack: Vec<(usize, DocumentSymbol)> = Vec::new();
+    // The end of the last non-blank line
+    let mut last_end = Position::new(0, 0);
+
+    for (line_number, line) in text.lines(0..text.len()).enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() {
+            continue;
+        }
+        let line_number = line_number as u32;
+        let indent = line.len() - trimmed.len();
+        let line_end = Position::new(
+            line_number,
+            offset_utf8_to_utf16_str(&line, line.trim_end().len()) as u32,
+        );
+
+        let block_end = is_block_end(trimmed);
+        while matches!(stack.last(), Some((i, _)) if *i >= indent) {
+            let end = if block_end && stack.last().map(|(i, _)| *i) == Some(indent) {
+                line_end
+            } else {
+                last_end
+            };
+            close(&mut stack, &mut roots, end);
+        }
+        last_end = line_end;
+
+        let Some(captures) = DECLARATION.captures(trimmed) else {
+            continue;
+        };
+        let (Some(keyword), Some(name)) = (captures.get(1), captures.get(2)) else {
+            continue;
+        };
+        let keyword = keyword.as_str();
+        let keyword = keyword.split('<').next().unwrap_or(keyword);
+        let in_type =
+            matches!(stack.last(), Some((_, parent)) if is_type(parent.kind));
+        let name_start = offset_utf8_to_utf16_str(&line, indent + name.start());
+        let name_end = offset_utf8_to_utf16_str(&line, indent + name.end());
+        let selection_range = Range::new(
+            Position::new(line_number, name_start as u32),
+            Position::new(line_number, name_end as u32),
+        );
+        let symbol = DocumentSymbol {
+            name: name.as_str().to_string(),
+            detail: None,
+            kind: symbol_kind(keyword, in_type),
+            tags: None,
+            deprecated: None,
+            range: Range::new(
+                Position::new(
+                    line_number,
+                    offset_utf8_to_utf16_str(&line, indent) as u32,
+                ),
+                line_end,
+            ),
+            selection_range,
+            children: None,
+        };
+        stack.push((indent, symbol));
+    }
+
+    while !stack.is_empty() {
+        close(&mut stack, &mut roots, last_end);
+    }
+    roots
+}
+
+#[cfg(test)]
+mod test {
+    use lapce_xi_rope::Rope;
+
+    use super::*;
+
+    #[test]
+    fn test_scan_symbols() {
+        let text = Rope::from(concat!(
+            "pub struct A {\n    a: i32,\n}\n\nimpl<T> Foo for A {\n",
+            "    pub(crate) fn new() -> Self {\n        todo!()\n    }\n\n",
+            "    async fn b(&self) {}\n}\n\nfn main() {}\n",
+        ));
+        let symbols = scan_symbols(&text);
+        let names: Vec<&str> = symbols.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["A", "Foo", "main"]);
+        assert_eq!(symbols[0].kind, SymbolKind::STRUCT);
+        assert_eq!(symbols[0].range.end, Position::new(2, 1));
+
+        let methods = symbols[1].children.as_ref().unwrap();
+        let names: Vec<&str> = methods.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["new", "b"]);
+        assert_eq!(methods[0].kind, SymbolKind::METHOD);
+        assert_eq!(methods[0].range.start, Position::new(5, 4));
+        assert_eq!(methods[0].range.end, Position::new(7, 5));
+        assert_eq!(methods[0].selection_range.start, Position::new(5, 18));
+        assert_eq!(symbols[1].range.end, Position::new(10, 1));
+        assert_eq!(symbols[2].kind, SymbolKind::FUNCTION);
+
+        let text = Rope::from(concat!(
+            "class A:\n    def a(self):\n        pass\n\n",
+            "    def b(self):\n        pass\ndef c():\n    pass\n",
+        ));
+        let symbols = scan_symbols(&text);
+        assert_eq!(symbols.len(), 2);
+        assert_eq!(symbols[0].range.end, Position::new(5, 12));
+        assert_eq!(symbols[0].children.as_ref().unwrap().len(), 2);
+        assert_eq!(symbols[1].name, "c");
+        assert_eq!(symbols[1].range.end, Position::new(7, 8));
+    }
+}
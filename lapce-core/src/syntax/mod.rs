@@ -10,8 +10,10 @@
     cell::RefCell,
     collections::{HashSet, VecDeque},
     mem,
+    ops::Range,
     path::Path,
     sync::{atomic::AtomicUsize, Arc},
+    time::Instant,
 };
 
 use itertools::Itertools;
@@ -31,7 +33,10 @@
         HighlightConfiguration, HighlightEvent, HighlightIter, HighlightIterLayer,
         IncludedChildren, LocalScope,
     },
-    util::{matching_bracket_general, matching_pair_direction, RopeProvider},
+    util::{
+        matching_bracket_general, matching_char, matching_pair_direction,
+        RopeProvider,
+    },
 };
 use crate::{
     language::LapceLanguage,
@@ -93,6 +98,7 @@ fn parse(
         parser: &mut Parser,
         source: &Rope,
         had_edits: bool,
+        cancellation_flag: Option<&AtomicUsize>,
     ) -> Result<(), Error> {
         parser.set_included_ranges(&self.ranges).unwrap();
 
@@ -100,24 +106,28 @@ fn parse(
             .set_language(self.config.language)
             .map_err(|_| Error::InvalidLanguage)?;
 
-        // unsafe { syntax.parser.set_cancellation_flag(cancellation_flag) };
-        let tree = parser
-            .parse_with(
-                &mut |byte, _| {
-                    if byte <= source.len() {
-                        source
-                            .iter_chunks(byte..)
-                            .next()
-                            .map(|s| s.as_bytes())
-                            .unwrap_or(&[])
-                    } else {
-                        &[]
-                    }
-                },
-                had_edits.then_some(()).and(self.tree.as_ref()),
-            )
-            .ok_or(Error::Cancelled)?;
-        // unsafe { ts_parser.parser.set_cancellation_flag(None) };
+        // Safety: the flag is unset again below, before the borrow of it ends
+        unsafe { parser.set_cancellation_flag(cancellation_flag) };
+        let tree = parser.parse_with(
+            &mut |byte, _| {
+                if byte <= source.len() {
+                    source
+                        .iter_chunks(byte..)
+                        .next()
+                        .map(|s| s.as_bytes())
+                        .unwrap_or(&[])
+                } else {
+                    &[]
+                }
+            },
+            had_edits.then_some(()).and(self.tree.as_ref()),
+        );
+        unsafe { parser.set_cancellation_flag(None) };
+        let Some(tree) = tree else {
+            // Otherwise the next parse would resume the cancelled one
+            parser.reset();
+            return Err(Error::Cancelled);
+        };
         self.tree = Some(tree);
         Ok(())
     }
@@ -156,7 +166,7 @@ pub fn new(
         let mut syntax = SyntaxLayers { root, layers };
 
         if let Some(source) = source {
-            let _ = syntax.update(0, 0, source, None);
+            let _ = syntax.update(0, 0, source, None, None);
         }
 
         syntax
@@ -168,6 +178,7 @@ pub fn update(
         new_rev: u64,
         source: &Rope,
         syntax_edits: Option<&[SyntaxEdit]>,
+        cancellation_flag: Option<&AtomicUsize>,
     ) -> Result<(), Error> {
         let mut queue = VecDeque::new();
         queue.push_back(self.root);
@@ -300,7 +311,15 @@ fn point_sub(a: Point, b: Point) -> Point {
                 }
 
                 // Re-parse the tree.
-                layer.parse(&mut ts_parser.parser, source, had_edits)?;
+                if let Err(err) = layer.parse(
+                    &mut ts_parser.parser,
+                    source,
+                    had_edits,
+                    cancellation_flag,
+                ) {
+                    ts_parser.cursors.push(cursor);
+                    return Err(err);
+                }
                 layer.rev = new_rev;
 
                 // Switch to an immutable borrow.
@@ -524,6 +543,15 @@ pub fn highlight_iter<'a>(
     // TODO: Folding
 }
 
+/// A bracket in the text, like `(` or `}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bracket {
+    pub offset: usize,
+    /// How many bracket pairs the pair of this one is nested in, or `None` if
+    /// it has no pair
+    pub depth: Option<usize>,
+}
+
 #[derive(Clone)]
 pub struct Syntax {
     pub rev: u64,
@@ -535,6 +563,24 @@ pub struct Syntax {
     pub line_height: usize,
     pub lens_height: usize,
     pub styles: Option<Arc<Spans<Style>>>,
+    /// The lines whose highlighting the last parse recomputed, or `None` if it
+    /// highlighted the whole text
+    pub changed_lines: Option<Vec<Range<usize>>>,
+    /// Whether only the `changed_lines` are highlighted so far, with the rest
+    /// of the parse still to come
+    pub partial: bool,
+}
+
+/// What a parse in the background may take, see [`Syntax::parse_within`].
+pub struct ParseBudget<'a> {
+    /// Cancels the parse once set to anything but zero
+    pub cancellation_flag: &'a AtomicUsize,
+    /// When the parse should be done by. A parse running later than that
+    /// highlights the `priority` range first, and hands that to `on_partial`.
+    pub deadline: Instant,
+    /// The byte range to highlight first, usually what's on screen
+    pub priority: Range<usize>,
+    pub on_partial: Box<dyn FnOnce(Syntax) + 'a>,
 }
 
 impl std::fmt::Debug for Syntax {
@@ -547,6 +593,7 @@ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
             .field("line_height", &self.line_height)
             .field("lens_height", &self.lens_height)
             .field("styles", &self.styles)
+            .field("partial", &self.partial)
             .finish()
     }
 }
@@ -573,6 +620,8 @@ pub fn from_language(language: LapceLanguage) -> Syntax {
             lens_height: 0,
             normal_lines: Vec::new(),
             styles: None,
+            changed_lines: None,
+            partial: false,
         }
     }
 
@@ -582,48 +631,70 @@ pub fn parse(
         new_text: Rope,
         edits: Option<&[SyntaxEdit]>,
     ) {
+        let _ = self.parse_within(new_rev, new_text, edits, None);
+    }
+
+    /// Parse like [`Syntax::parse`], within what `budget` allows.
+    ///
+    /// Once the parse is cancelled, it stops with [`Error::Cancelled`] and the
+    /// syntax is left half updated, so it should be dropped.
+    pub fn parse_within(
+        &mut self,
+        new_rev: u64,
+        new_text: Rope,
+        edits: Option<&[SyntaxEdit]>,
+        budget: Option<ParseBudget<'_>>,
+    ) -> Result<(), Error> {
+        let cancellation_flag =
+            budget.as_ref().map(|budget| budget.cancellation_flag);
         let layers = match &mut self.layers {
             Some(layers) => layers,
-            None => return,
+            None => return Ok(()),
         };
         let edits = edits.filter(|edits| new_rev == self.rev + edits.len() as u64);
-        let _ = layers.update(self.rev, new_rev, &new_text, edits);
-        let tree = layers.try_tree();
-
-        let styles = if tree.is_some() {
-            let mut current_hl: Option<Highlight> = None;
-            let mut highlights: SpansBuilder<Style> =
-                SpansBuilder::new(new_text.len());
-
-            // TODO: Should we be ignoring highlight errors via flattening them?
-            for highlight in layers
-                .highlight_iter(&new_text, Some(0..new_text.len()), None)
-                .flatten()
-            {
-                match highlight {
-                    HighlightEvent::Source { start, end } => {
-                        if let Some(hl) = current_hl {
-                            if let Some(hl) = SCOPES.get(hl.0) {
-                                highlights.add_span(
-                                    Interval::new(start, end),
-                                    Style {
-                                        fg_color: Some(hl.to_string()),
-                                    },
-                                );
-                            }
-                        }
-                    }
-                    HighlightEvent::HighlightStart(hl) => {
-                        current_hl = Some(hl);
-                    }
-                    HighlightEvent::HighlightEnd => current_hl = None,
+        let input_edits = edits
+            .unwrap_or_default()
+            .iter()
+            .flat_map(|edit| &edit.0)
+            .collect::<Vec<_>>();
+        // The tree before the edits, moved along with them, which tree-sitter
+        // compares with the reparsed one to tell which text it now sees
+        // differently. Injected layers are not compared, so documents with
+        // any are always highlighted in full.
+        let old_tree = if edits.is_some() && layers.layers.len() == 1 {
+            layers.try_tree().cloned().map(|mut tree| {
+                for edit in &input_edits {
+                    tree.edit(edit);
                 }
-            }
-
-            Some(Arc::new(highlights.build()))
+                tree
+            })
         } else {
             None
         };
+        match layers.update(self.rev, new_rev, &new_text, edits, cancellation_flag) {
+            Err(Error::Cancelled) => return Err(Error::Cancelled),
+            Err(_) | Ok(()) => {}
+        }
+        let tree = layers.try_tree();
+
+        // The old styles have to be shifted to the new text already, which
+        // the document does with each edit. Those of a partial highlight are
+        // stale outside of the part.
+        let old_styles = self
+            .styles
+            .as_ref()
+            .filter(|styles| !self.partial && styles.len() == new_text.len());
+        let changed_lines = match (old_tree, tree, old_styles) {
+            (Some(old_tree), Some(tree), Some(_)) if layers.layers.len() == 1 => {
+                let ranges = edited_ranges(&input_edits).into_iter().chain(
+                    old_tree
+                        .changed_ranges(tree)
+                        .map(|range| range.start_byte..range.end_byte),
+                );
+                Some(lines_of_ranges(&new_text, ranges))
+            }
+            _ => None,
+        };
 
         let normal_lines = if let Some(tree) = tree {
             let mut cursor = tree.walk();
@@ -641,11 +712,85 @@ pub fn parse(
             &normal_lines,
         );
 
+        // The lines to highlight, or `None` for all of them
+        let highlight_lines = match (tree, &changed_lines) {
+            (None, _) => Some(Vec::new()),
+            (Some(_), changed_lines) => changed_lines.clone(),
+        };
+
+        // A parse that ran late shows the lines in view before the rest
+        if let Some(budget) = budget {
+            let priority_lines = new_text
+                .line_of_offset(budget.priority.start.min(new_text.len()))
+                ..new_text.line_of_offset(budget.priority.end.min(new_text.len()))
+                    + 1;
+            let beyond_priority = match &highlight_lines {
+                Some(lines) => lines.iter().any(|lines| {
+                    lines.start < priority_lines.start
+                        || lines.end > priority_lines.end
+                }),
+                None => true,
+            };
+            if Instant::now() > budget.deadline && beyond_priority {
+                let lines = match &highlight_lines {
+                    Some(lines) => lines
+                        .iter()
+                        .filter_map(|lines| {
+                            let start = lines.start.max(priority_lines.start);
+                            let end = lines.end.min(priority_lines.end);
+                            (start < end).then_some(start..end)
+                        })
+                        .collect(),
+                    None => vec![priority_lines],
+                };
+                let styles = highlight_lines_over(
+                    layers,
+                    &new_text,
+                    old_styles.map(|styles| styles.as_ref()),
+                    &lines,
+                    cancellation_flag,
+                )?;
+                (budget.on_partial)(Syntax {
+                    rev: new_rev,
+                    language: self.language,
+                    text: new_text.clone(),
+                    layers: Some(layers.clone()),
+                    lens: lens.clone(),
+                    normal_lines: normal_lines.clone(),
+                    line_height: self.line_height,
+                    lens_height: self.lens_height,
+                    styles: Some(Arc::new(styles)),
+                    changed_lines: Some(lines),
+                    partial: true,
+                });
+            }
+        }
+
+        let styles = match (tree, &highlight_lines) {
+            (None, _) => None,
+            (Some(_), Some(lines)) => Some(Arc::new(highlight_lines_over(
+                layers,
+                &new_text,
+                old_styles.map(|styles| styles.as_ref()),
+                lines,
+                cancellation_flag,
+            )?)),
+            (Some(_), None) => Some(Arc::new(highlight_spans(
+                layers,
+                &new_text,
+                0..new_text.len(),
+                cancellation_flag,
+            )?)),
+        };
+
         self.rev = new_rev;
         self.lens = lens;
         self.normal_lines = normal_lines;
         self.styles = styles;
-        self.text = new_text
+        self.changed_lines = changed_lines;
+        self.partial = false;
+        self.text = new_text;
+        Ok(())
     }
 
     pub fn update_lens_height(&mut self, line_height: usize, lens_height: usize) {
@@ -681,7 +826,12 @@ pub fn lens_from_normal_lines(
         builder.build()
     }
 
+    /// The offset of the bracket paired with the one at `offset`. Brackets in
+    /// strings, comments and character literals aren't paired with any.
     pub fn find_matching_pair(&self, offset: usize) -> Option<usize> {
+        if self.is_in_literal(offset) {
+            return None;
+        }
         let tree = self.layers.as_ref()?.try_tree()?;
         let node = tree
             .root_node()
@@ -698,6 +848,46 @@ pub fn find_matching_pair(&self, offset: usize) -> Option<usize> {
         None
     }
 
+    /// Whether `offset` is inside a string, a comment or a character literal,
+    /// where brackets don't pair up with the ones outside. The end of a
+    /// comment that doesn't end with a `/`, like a line comment, is still in
+    /// it.
+    pub fn is_in_literal(&self, offset: usize) -> bool {
+        if offset == 0 || offset > self.text.len() {
+            return false;
+        }
+        let Some(tree) = self.layers.as_ref().and_then(|layers| layers.try_tree())
+        else {
+            return false;
+        };
+        // The node of the char before `offset`, which `offset` is in if it's
+        // not at its start
+        let Some(mut node) = tree
+            .root_node()
+            .descendant_for_byte_range(offset - 1, offset)
+        else {
+            return false;
+        };
+        loop {
+            let kind = node.kind();
+            if is_literal_kind(kind) {
+                let end = node.end_byte();
+                if node.start_byte() < offset
+                    && (offset < end
+                        || (offset == end
+                            && kind.contains("comment")
+                            && self.text.byte_at(end - 1) != b'/'))
+                {
+                    return true;
+                }
+            }
+            match node.parent() {
+                Some(parent) => node = parent,
+                None => return false,
+            }
+        }
+    }
+
     pub fn parent_offset(&self, offset: usize) -> Option<usize> {
         let tree = self.layers.as_ref()?.try_tree()?;
         let node = tree
@@ -824,6 +1014,17 @@ pub fn find_enclosing_parentheses(
         }
     }
 
+    /// The brackets in the `range` of the text, in order, with how deeply they
+    /// are nested. Brackets in strings and comments are left out.
+    pub fn brackets(&self, range: Range<usize>) -> Vec<Bracket> {
+        let mut brackets = Vec::new();
+        if let Some(tree) = self.layers.as_ref().and_then(|layers| layers.try_tree())
+        {
+            collect_brackets(tree.root_node(), &range, 0, &mut brackets);
+        }
+        brackets
+    }
+
     pub fn find_enclosing_pair(&self, offset: usize) -> Option<(usize, usize)> {
         // If there is no text then the document can't have any bytes
         if self.text.is_empty() {
@@ -859,6 +1060,204 @@ pub fn find_enclosing_pair(&self, offset: usize) -> Option<(usize, usize)> {
     }
 }
 
+/// Whether the nodes of `kind` are strings, comments or character literals,
+/// whose text is taken as it is.
+fn is_literal_kind(kind: &str) -> bool {
+    kind.contains("comment")
+        || kind.contains("string")
+        || kind.contains("char_literal")
+        || kind.contains("character_literal")
+}
+
+/// Collect the brackets among the descendants of `node` that are in `range`,
+/// where `node` is nested in `depth` bracket pairs. Brackets are only paired
+/// with others among the same node's children, which is where grammars put
+/// both brackets of a pair.
+fn collect_brackets(
+    node: Node,
+    range: &Range<usize>,
+    depth: usize,
+    brackets: &mut Vec<Bracket>,
+) {
+    // The brackets opened among the children so far, with their index in
+    // `brackets` if they are in the range
+    let mut open: Vec<(char, Option<usize>)> = Vec::new();
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        let kind = child.kind();
+        if child.is_missing() || is_literal_kind(kind) {
+            continue;
+        }
+        let in_range =
+            child.start_byte() < range.end && child.end_byte() > range.start;
+        let bracket = if child.is_named() || kind.len() != 1 {
+            None
+        } else {
+            kind.chars()
+                .next()
+                .and_then(|c| Some((c, matching_pair_direction(c)?)))
+        };
+        match bracket {
+            Some((c, true)) => {
+                let index = in_range.then(|| {
+                    brackets.push(Bracket {
+                        offset: child.start_byte(),
+                        depth: Some(depth + open.len()),
+                    });
+                    brackets.len() - 1
+                });
+                open.push((c, index));
+            }
+            Some((c, false)) => {
+                let paired = matches!(
+                    open.last(),
+                    Some((open, _)) if matching_char(*open) == Some(c)
+                );
+                if paired {
+                    open.pop();
+                }
+                if in_range {
+                    brackets.push(Bracket {
+                        offset: child.start_byte(),
+                        depth: paired.then_some(depth + open.len()),
+                    });
+                }
+            }
+            None if in_range => {
+                collect_brackets(child, range, depth + open.len(), brackets)
+            }
+            None => {}
+        }
+    }
+
+    // The brackets still open have no pair
+    for (_, index) in open {
+        if let Some(index) = index {
+            brackets[index].depth = None;
+        }
+    }
+}
+
+/// The styles of `old_styles` with the `lines` highlighted anew, or of only
+/// those lines if there are no old styles.
+fn highlight_lines_over(
+    layers: &SyntaxLayers,
+    text: &Rope,
+    old_styles: Option<&Spans<Style>>,
+    lines: &[Range<usize>],
+    cancellation_flag: Option<&AtomicUsize>,
+) -> Result<Spans<Style>, Error> {
+    let mut styles = match old_styles {
+        Some(old_styles) => old_styles.clone(),
+        None => SpansBuilder::new(text.len()).build(),
+    };
+    for lines in lines {
+        let start = text.offset_of_line(lines.start);
+        let end = text.offset_of_line(lines.end);
+        styles.edit(
+            Interval::new(start, end),
+            highlight_spans(layers, text, start..end, cancellation_flag)?,
+        );
+    }
+    Ok(styles)
+}
+
+/// The highlight spans of `range` in `text`, relative to its start.
+fn highlight_spans(
+    layers: &SyntaxLayers,
+    text: &Rope,
+    range: Range<usize>,
+    cancellation_flag: Option<&AtomicUsize>,
+) -> Result<Spans<Style>, Error> {
+    let mut current_hl: Option<Highlight> = None;
+    let mut highlights: SpansBuilder<Style> = SpansBuilder::new(range.len());
+
+    // TODO: Should we be ignoring highlight errors other than cancellation?
+    for highlight in
+        layers.highlight_iter(text, Some(range.clone()), cancellation_flag)
+    {
+        let highlight = match highlight {
+            Ok(highlight) => highlight,
+            Err(Error::Cancelled) => return Err(Error::Cancelled),
+            Err(_) => continue,
+        };
+        match highlight {
+            HighlightEvent::Source { start, end } => {
+                let start = start.max(range.start);
+                let end = end.min(range.end);
+                if start >= end {
+                    continue;
+                }
+                if let Some(hl) = current_hl {
+                    if let Some(hl) = SCOPES.get(hl.0) {
+                        highlights.add_span(
+                            Interval::new(start - range.start, end - range.start),
+                            Style {
+                                fg_color: Some(hl.to_string()),
+                            },
+                        );
+                    }
+                }
+            }
+            HighlightEvent::HighlightStart(hl) => {
+                current_hl = Some(hl);
+            }
+            HighlightEvent::HighlightEnd => current_hl = None,
+        }
+    }
+
+    Ok(highlights.build())
+}
+
+/// The byte ranges of the text inserted by `edits`, in the text after all of
+/// them. Each edit is in the text left by the ones before it.
+fn edited_ranges(edits: &[&tree_sitter::InputEdit]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for edit in edits {
+        let shift = |offset: usize| {
+            (offset + edit.new_end_byte).saturating_sub(edit.old_end_byte)
+        };
+        for range in ranges.iter_mut() {
+            if range.end <= edit.start_byte {
+                continue;
+            }
+            if range.start >= edit.old_end_byte {
+                *range = shift(range.start)..shift(range.end);
+            } else {
+                let end = shift(range.end.max(edit.old_end_byte));
+                *range = range.start.min(edit.start_byte)..end;
+            }
+        }
+        ranges.push(edit.start_byte..edit.new_end_byte);
+    }
+    ranges
+}
+
+/// The sorted and merged line ranges covering the byte `ranges` of `text`.
+fn lines_of_ranges(
+    text: &Rope,
+    ranges: impl Iterator<Item = Range<usize>>,
+) -> Vec<Range<usize>> {
+    let len = text.len();
+    let lines = ranges
+        .map(|range| {
+            let start = text.line_of_offset(range.start.min(len));
+            let end = text.line_of_offset(range.end.min(len));
+            start..end + 1
+        })
+        .sorted_by_key(|lines| lines.start);
+    let mut merged: Vec<Range<usize>> = Vec::new();
+    for lines in lines {
+        match merged.last_mut() {
+            Some(last) if lines.start <= last.end => {
+                last.end = last.end.max(lines.end);
+            }
+            _ => merged.push(lines),
+        }
+    }
+    merged
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -895,4 +1294,234 @@ fn test_lens_iter() {
         assert_eq!(Some((90, 25)), iter.next());
         assert_eq!(None, iter.next());
     }
+
+    #[test]
+    fn test_edited_ranges() {
+        let text = Rope::from("abc\ndef\nghi\n");
+        // Insert "xx" on the second line, then delete the first line
+        let insert = edit::create_insert_edit(&text, 5, &Rope::from("xx"));
+        let text = Rope::from("abc\ndxxef\nghi\n");
+        let delete = edit::create_delete_edit(&text, 0, 4);
+        let ranges = edited_ranges(&[&insert, &delete]);
+        assert_eq!(ranges, vec![1..3, 0..0]);
+
+        let text = Rope::from("dxxef\nghi\n");
+        assert_eq!(lines_of_ranges(&text, ranges.into_iter()), vec![0..1]);
+    }
+
+    #[cfg(feature = "lang-rust")]
+    #[test]
+    fn test_parse_within_budget() {
+        use std::{cell::RefCell, sync::atomic::Ordering};
+
+        let text = Rope::from("fn main() {\n    let a = \"a\";\n}\n".repeat(100));
+        let cancellation_flag = AtomicUsize::new(0);
+        let partial = RefCell::new(None);
+        let mut syntax = Syntax::from_language(LapceLanguage::Rust);
+
+        // A late parse hands over the priority lines first
+        let priority = text.offset_of_line(10)..text.offset_of_line(12);
+        syntax
+            .parse_within(
+                0,
+                text.clone(),
+                None,
+                Some(ParseBudget {
+                    cancellation_flag: &cancellation_flag,
+                    deadline: Instant::now(),
+                    priority,
+                    on_partial: Box::new(|syntax| {
+                        *partial.borrow_mut() = Some(syntax);
+                    }),
+                }),
+            )
+            .unwrap();
+        let partial = partial.into_inner().unwrap();
+        assert!(partial.partial);
+        assert_eq!(partial.changed_lines, Some(vec![10..13]));
+        let partial_styles = byte_styles(&partial);
+        let full_styles = byte_styles(&syntax);
+        let start = text.offset_of_line(10);
+        let end = text.offset_of_line(13);
+        assert_eq!(partial_styles[start..end], full_styles[start..end]);
+        assert!(partial_styles[..start].iter().all(Option::is_none));
+        assert!(!syntax.partial);
+
+        // A cancelled one gives up
+        cancellation_flag.store(1, Ordering::Relaxed);
+        let mut syntax = Syntax::from_language(LapceLanguage::Rust);
+        let result = syntax.parse_within(
+            0,
+            text,
+            None,
+            Some(ParseBudget {
+                cancellation_flag: &cancellation_flag,
+                deadline: Instant::now(),
+                priority: 0..0,
+                on_partial: Box::new(|_| {}),
+            }),
+        );
+        assert_eq!(result, Err(Error::Cancelled));
+    }
+
+    /// The style of every byte of the text, so highlights can be compared
+    /// regardless of how they are split into spans.
+    #[cfg(feature = "lang-rust")]
+    fn byte_styles(syntax: &Syntax) -> Vec<Option<String>> {
+        let styles = syntax.styles.as_ref().unwrap();
+        let mut bytes = vec![None; styles.len()];
+        for (iv, style) in styles.iter() {
+            for byte in &mut bytes[iv.start..iv.end] {
+                *byte = style.fg_color.clone();
+            }
+        }
+        bytes
+    }
+
+    #[cfg(feature = "lang-rust")]
+    fn insert(syntax: &mut Syntax, text: &Rope, offset: usize, s: &str) -> Rope {
+        use lapce_xi_rope::RopeDelta;
+
+        let inserted = Rope::from(s);
+        let edit = edit::create_insert_edit(text, offset, &inserted);
+        let delta = RopeDelta::simple_edit(
+            Interval::new(offset, offset),
+            inserted,
+            text.len(),
+        );
+        let new_text = delta.apply(text);
+        // Like the document does, shift the styles before reparsing
+        if let Some(styles) = syntax.styles.as_mut() {
+            Arc::make_mut(styles).apply_shape(&delta);
+        }
+        syntax.parse(
+            syntax.rev + 1,
+            new_text.clone(),
+            Some(&[SyntaxEdit::new(vec![edit])]),
+        );
+        new_text
+    }
+
+    #[cfg(feature = "lang-rust")]
+    #[test]
+    fn test_brackets() {
+        let text = Rope::from("fn a() {\n    f([1], \"(\"); // )\n}\n)");
+        let mut syntax = Syntax::from_language(LapceLanguage::Rust);
+        syntax.parse(0, text.clone(), None);
+
+        let bracket = |offset, depth| Bracket { offset, depth };
+        assert_eq!(
+            syntax.brackets(0..text.len()),
+            vec![
+                bracket(4, Some(0)),
+                bracket(5, Some(0)),
+                bracket(7, Some(0)),
+                bracket(14, Some(1)),
+                bracket(15, Some(2)),
+                bracket(17, Some(2)),
+                bracket(23, Some(1)),
+                bracket(31, Some(0)),
+                bracket(33, None),
+            ]
+        );
+        // Only the second line, which still knows how deep it is nested
+        assert_eq!(syntax.brackets(9..31)[0], bracket(14, Some(1)));
+    }
+
+    /// Check that the brackets at `pairs` are paired with each other and that
+    /// the rest of the brackets in `source` are in literals and paired with
+    /// none.
+    #[cfg(any(feature = "lang-rust", feature = "lang-json"))]
+    fn assert_pairs(
+        language: LapceLanguage,
+        source: &str,
+        pairs: &[(usize, usize)],
+    ) {
+        let mut syntax = Syntax::from_language(language);
+        syntax.parse(0, Rope::from(source), None);
+
+        for (open, close) in pairs {
+            assert_eq!(syntax.find_matching_pair(*open), Some(*close));
+            assert_eq!(syntax.find_matching_pair(*close), Some(*open));
+            assert!(!syntax.is_in_literal(*open));
+            assert!(!syntax.is_in_literal(*close));
+        }
+        for (offset, c) in source.char_indices() {
+            if "{}[]()".contains(c)
+                && !pairs
+                    .iter()
+                    .any(|(open, close)| offset == *open || offset == *close)
+            {
+                assert!(syntax.is_in_literal(offset), "{c} at {offset}");
+                assert_eq!(syntax.find_matching_pair(offset), None);
+            }
+        }
+    }
+
+    #[cfg(feature = "lang-rust")]
+    #[test]
+    fn test_matching_pair_in_literals() {
+        let source = concat!(
+            "fn a() {\n",
+            "    /// ```\n",
+            "    /// {\n",
+            "    /// ```\n",
+            "    let s = \"{\";\n",
+            "    let r = r#\"}\"#;\n",
+            "    let c = '{';\n",
+            "    /* ( */ // }\n",
+            "}\n",
+        );
+        let open = source.find('{').unwrap();
+        let close = source.rfind('}').unwrap();
+        assert_pairs(LapceLanguage::Rust, source, &[(4, 5), (open, close)]);
+    }
+
+    #[cfg(feature = "lang-json")]
+    #[test]
+    fn test_matching_pair_in_json_strings() {
+        let source = r#"{"a": "}", "b": [1, "["], "c": "\"{"}"#;
+        let open = source.find('[').unwrap();
+        let close = source.rfind(']').unwrap();
+        assert_pairs(
+            LapceLanguage::Json,
+            source,
+            &[(0, source.len() - 1), (open, close)],
+        );
+    }
+
+    #[cfg(feature = "lang-rust")]
+    #[test]
+    fn test_incremental_highlight() {
+        let text = Rope::from("fn main() {\n    let a = \"a\";\n}\n".repeat(3400));
+        let mut syntax = Syntax::from_language(LapceLanguage::Rust);
+        syntax.parse(0, text.clone(), None);
+        assert_eq!(syntax.changed_lines, None);
+
+        // Typing in the middle of a 10k line file only rehighlights that line
+        let offset = text.offset_of_line(5002) + "    let a = \"".len();
+        let text = insert(&mut syntax, &text, offset, "b");
+        assert_eq!(syntax.changed_lines, Some(vec![5002..5003]));
+
+        let mut full = Syntax::from_language(LapceLanguage::Rust);
+        full.parse(0, text, None);
+        assert_eq!(byte_styles(&syntax), byte_styles(&full));
+    }
+
+    #[cfg(feature = "lang-rust")]
+    #[test]
+    fn test_incremental_highlight_open_comment() {
+        let text = Rope::from("fn a() {}\nfn b() {}\n// */\n");
+        let mut syntax = Syntax::from_language(LapceLanguage::Rust);
+        syntax.parse(0, text.clone(), None);
+
+        // Opening a comment turns the lines after it into comment as well
+        let text = insert(&mut syntax, &text, 0, "/*");
+        let changed_lines = syntax.changed_lines.clone().unwrap();
+        assert!(changed_lines.iter().any(|lines| lines.contains(&1)));
+
+        let mut full = Syntax::from_language(LapceLanguage::Rust);
+        full.parse(0, text, None);
+        assert_eq!(byte_styles(&syntax), byte_styles(&full));
+    }
 }
@@ -1,5 +1,6 @@
 use std::{
     borrow::Cow,
+    collections::HashSet,
     ffi::OsString,
     fs,
     fs::File,
@@ -10,10 +11,11 @@
 
 use anyhow::{anyhow, Result};
 use lapce_core::{
-    buffer::rope_text::CharIndicesJoin, encoding::offset_utf8_to_utf16,
+    buffer::rope_text::{CharIndicesJoin, RopeText, RopeTextRef},
+    encoding::offset_utf8_to_utf16,
 };
 use lapce_rpc::buffer::BufferId;
-use lapce_xi_rope::{interval::IntervalBounds, rope::Rope, RopeDelta};
+use lapce_xi_rope::{interval::IntervalBounds, rope::Rope, Interval, RopeDelta};
 use lsp_types::*;
 
 #[derive(Clone)]
@@ -64,6 +66,10 @@ pub fn save(&mut self, rev: u64) -> Result<()> {
         let bak_file_path = &path.with_extension(bak_extension);
         if !new_file {
             fs::copy(&path, bak_file_path)?;
+        } else if let Some(parent) = path.parent() {
+            // A file opened from the command line can be in a folder that
+            // doesn't exist yet
+            fs::create_dir_all(parent)?;
         }
 
         let mut f = fs::OpenOptions::new()
@@ -103,6 +109,21 @@ pub fn update(
         )
     }
 
+    /// Replace the whole text with `content`, the one of the editor at `rev`,
+    /// as an update was missed. The change is `None` if `rev` is older than
+    /// the buffer, which it gets to when a resync was already asked for.
+    pub fn resync(&mut self, content: &str, rev: u64) -> Option<RopeDelta> {
+        if rev < self.rev {
+            return None;
+        }
+        let len = self.rope.len();
+        let delta =
+            RopeDelta::simple_edit(Interval::new(0, len), Rope::from(content), len);
+        self.rope = delta.apply(&self.rope);
+        self.rev = rev;
+        Some(delta)
+    }
+
     pub fn get_document(&self) -> String {
         self.rope.to_string()
     }
@@ -314,3 +335,70 @@ pub fn get_mod_time<P: AsRef<Path>>(path: P) -> Option<SystemTime> {
         .and_then(|meta| meta.modified())
         .ok()
 }
+
+/// The words of `text` that fuzzily match `input`, offered as completions
+/// when no language server gives any. The word being typed at `position` is
+/// left out.
+pub fn word_completions(
+    text: &Rope,
+    input: &str,
+    position: Position,
+) -> Vec<CompletionItem> {
+    if input.is_empty() {
+        return Vec::new();
+    }
+    let offset = RopeTextRef::new(text).offset_of_position(&position);
+    let input: Vec<char> = input.to_lowercase().chars().collect();
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+
+    let content = text.slice_to_cow(..);
+    let mut seen = HashSet::new();
+    let mut items = Vec::new();
+    let mut word_start = None;
+    for (i, c) in content.char_indices().chain([(content.len(), ' ')]) {
+        if is_word_char(c) {
+            word_start.get_or_insert(i);
+            continue;
+        }
+        let Some(start) = word_start.take() else {
+            continue;
+        };
+        if start <= offset && offset <= i {
+            continue;
+        }
+        let word = &content[start..i];
+        let mut chars = word.chars().flat_map(char::to_lowercase);
+        let matches = input.iter().all(|c| chars.any(|w| w == *c));
+        if matches && seen.insert(word) {
+            items.push(CompletionItem {
+                label: word.to_string(),
+                kind: Some(CompletionItemKind::TEXT),
+                ..Default::default()
+            });
+        }
+    }
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_word_completions() {
+        let text = Rope::from("let foo_bar = fob;\nfoo_bar + Foo + foo_bar");
+        let labels = |input: &str, position: Position| {
+            word_completions(&text, input, position)
+                .into_iter()
+                .map(|item| item.label)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            labels("fo", Position::new(0, 0)),
+            vec!["foo_bar", "fob", "Foo"]
+        );
+        // The word at the cursor is the one being typed
+        assert_eq!(labels("fob", Position::new(0, 17)), vec!["foo_bar"]);
+        assert!(labels("", Position::new(0, 0)).is_empty());
+    }
+}
@@ -1,9 +1,9 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     fs,
     path::{Path, PathBuf},
     sync::{
-        atomic::{AtomicU64, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
         Arc,
     },
     thread,
@@ -16,33 +16,51 @@
 use git2::{build::CheckoutBuilder, DiffOptions, Repository};
 use grep_matcher::Matcher;
 use grep_regex::RegexMatcherBuilder;
-use grep_searcher::{sinks::UTF8, SearcherBuilder};
+use grep_searcher::{sinks::UTF8, BinaryDetection, SearcherBuilder};
 use indexmap::IndexMap;
+use lapce_core::{
+    buffer::rope_text::{RopeText, RopeTextRef},
+    path::renamed_path,
+    replace::apply_edits,
+    symbol::scan_symbols,
+};
 use lapce_rpc::{
     core::{CoreNotification, CoreRpcHandler},
-    file::FileNodeItem,
+    file::{FileEvent, FileNodeItem},
     proxy::{
         ProxyHandler, ProxyNotification, ProxyRequest, ProxyResponse,
         ProxyRpcHandler, SearchMatch,
     },
-    source_control::{DiffInfo, FileDiff},
+    source_control::{BlameCommit, BlameHunk, DiffInfo, FileDiff},
     style::{LineStyle, SemanticStyles},
     terminal::TermId,
     RequestId, RpcError,
 };
 use lapce_xi_rope::Rope;
-use lsp_types::{Position, Range, TextDocumentItem, Url};
+use lsp_types::{
+    DocumentSymbol, Location, Position, Range, SymbolInformation, TextDocumentItem,
+    TextEdit, Url,
+};
 use parking_lot::Mutex;
+use tracing::error;
 
 use crate::{
-    buffer::{get_mod_time, load_file, Buffer},
+    buffer::{get_mod_time, language_id_from_path, load_file, Buffer},
+    file_index::{is_left_out, mtime, FileIndex},
+    formatter::format_with_command,
+    globs::PathGlobs,
     plugin::{catalog::PluginCatalog, remove_volt, PluginCatalogRpcHandler},
+    task::Tasks,
     terminal::Terminal,
     watcher::{FileWatcher, Notify, WatchToken},
 };
 
 const OPEN_FILE_EVENT_TOKEN: WatchToken = WatchToken(1);
 const WORKSPACE_EVENT_TOKEN: WatchToken = WatchToken(2);
+/// How many workspace symbols are found by scanning the files at most
+const SCANNED_SYMBOLS_LIMIT: usize = 1000;
+/// How long the language servers have to give the edits for a rename of files
+const WILL_RENAME_TIMEOUT: Duration = Duration::from_secs(3);
 
 pub struct Dispatcher {
     workspace: Option<PathBuf>,
@@ -52,9 +70,16 @@ pub struct Dispatcher {
     buffers: HashMap<PathBuf, Buffer>,
     #[allow(deprecated)]
     terminals: HashMap<TermId, mio::channel::Sender<Msg>>,
+    tasks: Tasks,
     file_watcher: FileWatcher,
     window_id: usize,
     tab_id: usize,
+    /// The globs of `files.exclude`, kept compiled until they change
+    files_exclude: PathGlobs,
+    /// The globs of `search.exclude`, kept compiled until they change
+    search_exclude: PathGlobs,
+    /// The files the file palette lists
+    file_index: Arc<Mutex<FileIndex>>,
 }
 
 impl ProxyHandler for Dispatcher {
@@ -65,6 +90,7 @@ fn handle_notification(&mut self, rpc: ProxyNotification) {
                 workspace,
                 disabled_volts,
                 plugin_configurations,
+                language_servers,
                 window_id,
                 tab_id,
             } => {
@@ -75,10 +101,27 @@ fn handle_notification(&mut self, rpc: ProxyNotification) {
                     self.workspace.clone(),
                     self.core_rpc.clone(),
                     self.proxy_rpc.clone(),
+                    self.file_index.clone(),
                 ));
                 if let Some(workspace) = self.workspace.as_ref() {
                     self.file_watcher
                         .watch(workspace, true, WORKSPACE_EVENT_TOKEN);
+
+                    // The cached files are read right away, and the file
+                    // palette waits for them when it's opened before they are
+                    let workspace = workspace.clone();
+                    let file_index = self.file_index.clone();
+                    thread::spawn(move || {
+                        let mut file_index = file_index.lock();
+                        if file_index.walked || file_index.walking {
+                            return;
+                        }
+                        if let Some(cached) = FileIndex::cache_file(&workspace)
+                            .and_then(|path| FileIndex::load(&path))
+                        {
+                            *file_index = cached;
+                        }
+                    });
                 }
 
                 let plugin_rpc = self.catalog_rpc.clone();
@@ -88,6 +131,7 @@ fn handle_notification(&mut self, rpc: ProxyNotification) {
                         workspace,
                         disabled_volts,
                         plugin_configurations,
+                        language_servers,
                         plugin_rpc.clone(),
                     );
                     plugin_rpc.mainloop(&mut plugin);
@@ -107,6 +151,9 @@ fn handle_notification(&mut self, rpc: ProxyNotification) {
                 self.core_rpc
                     .notification(CoreNotification::OpenPaths { paths });
             }
+            FileEvent { event } => {
+                self.handle_file_event(event);
+            }
             OpenFileChanged { path } => {
                 if let Some(buffer) = self.buffers.get(&path) {
                     if get_mod_time(&buffer.path) == buffer.mod_time {
@@ -122,9 +169,23 @@ fn handle_notification(&mut self, rpc: ProxyNotification) {
                 path,
                 input,
                 position,
+                trigger_character,
             } => {
-                self.catalog_rpc
-                    .completion(request_id, &path, input, position);
+                let text = self.buffers.get(&path).map(|buffer| buffer.rope.clone());
+                self.catalog_rpc.completion(
+                    request_id,
+                    &path,
+                    input,
+                    position,
+                    trigger_character,
+                    text,
+                );
+            }
+            CancelCompletion {} => {
+                let _ = self.catalog_rpc.cancel_completions();
+            }
+            CancelWorkspaceSymbols {} => {
+                let _ = self.catalog_rpc.cancel_workspace_symbols();
             }
             SignatureHelp {
                 request_id,
@@ -139,12 +200,28 @@ fn handle_notification(&mut self, rpc: ProxyNotification) {
                     #[allow(deprecated)]
                     let _ = sender.send(Msg::Shutdown);
                 }
+                self.tasks.kill_all();
                 self.proxy_rpc.shutdown();
             }
             Update { path, delta, rev } => {
-                let buffer = self.buffers.get_mut(&path).unwrap();
+                let Some(buffer) = self.buffers.get_mut(&path) else {
+                    return;
+                };
                 let old_text = buffer.rope.clone();
-                buffer.update(&delta, rev);
+                // An edit out of order would put the servers out of sync, so
+                // the whole text is asked for instead
+                if buffer.update(&delta, rev).is_none() {
+                    self.core_rpc.log(
+                        tracing::Level::WARN,
+                        format!(
+                            "buffer update of {} out of order at rev {rev}, \
+                             resyncing",
+                            path.display()
+                        ),
+                    );
+                    self.core_rpc.buffer_out_of_sync(path);
+                    return;
+                }
                 self.catalog_rpc.did_change_text_document(
                     &path,
                     rev,
@@ -153,6 +230,50 @@ fn handle_notification(&mut self, rpc: ProxyNotification) {
                     buffer.rope.clone(),
                 );
             }
+            ResyncBuffer { path, content, rev } => {
+                let Some(buffer) = self.buffers.get_mut(&path) else {
+                    return;
+                };
+                let old_text = buffer.rope.clone();
+                // Replacing the whole text is sent to the servers as a full
+                // change
+                if let Some(delta) = buffer.resync(&content, rev) {
+                    self.catalog_rpc.did_change_text_document(
+                        &path,
+                        rev,
+                        delta,
+                        old_text,
+                        buffer.rope.clone(),
+                    );
+                }
+            }
+            CloseBuffer { path } => {
+                if self.buffers.remove(&path).is_some() {
+                    self.file_watcher.unwatch(&path, OPEN_FILE_EVENT_TOKEN);
+                    self.catalog_rpc.did_close_document(&path);
+                    self.catalog_rpc.set_language_id(&path, None);
+                }
+            }
+            SetBufferLanguage { path, language_id } => {
+                let Some(buffer) = self.buffers.get(&path) else {
+                    return;
+                };
+                let path_language_id = buffer.language_id.to_string();
+                let language_id = language_id.filter(|id| *id != path_language_id);
+                let new = language_id.clone().unwrap_or(path_language_id);
+                if self.catalog_rpc.language_id(&path) != new {
+                    // The language servers of the old language let go of the
+                    // file, and the ones of the new one take it
+                    self.catalog_rpc.did_close_document(&path);
+                    self.catalog_rpc.set_language_id(&path, language_id);
+                    self.catalog_rpc.did_open_document(
+                        &path,
+                        new,
+                        buffer.rev as i32,
+                        buffer.get_document(),
+                    );
+                }
+            }
             UpdatePluginConfigs { configs } => {
                 let _ = self.catalog_rpc.update_plugin_configs(configs);
             }
@@ -213,6 +334,18 @@ fn handle_notification(&mut self, rpc: ProxyNotification) {
                     let _ = tx.send(Msg::Shutdown);
                 }
             }
+            RunTask {
+                task_id,
+                command,
+                cwd,
+            } => {
+                let cwd = cwd.or_else(|| self.workspace.clone());
+                self.tasks
+                    .run(task_id, &command, cwd, self.core_rpc.clone());
+            }
+            KillTask { task_id } => {
+                self.tasks.kill(task_id);
+            }
             DapStart {
                 config,
                 breakpoints,
@@ -260,6 +393,12 @@ fn handle_notification(&mut self, rpc: ProxyNotification) {
             ReloadVolt { volt } => {
                 let _ = self.catalog_rpc.reload_volt(volt);
             }
+            RestartLanguageServer { volt_id } => {
+                let _ = self.catalog_rpc.restart_server(volt_id);
+            }
+            StopLanguageServer { volt_id } => {
+                let _ = self.catalog_rpc.stop_server(volt_id);
+            }
             RemoveVolt { volt } => {
                 let catalog_rpc = self.catalog_rpc.clone();
                 let _ = catalog_rpc.stop_volt(volt.info());
@@ -277,7 +416,7 @@ fn handle_notification(&mut self, rpc: ProxyNotification) {
                 if let Some(workspace) = self.workspace.as_ref() {
                     match git_commit(workspace, &message, diffs) {
                         Ok(()) => (),
-                        Err(e) => eprintln!("{e:?}"),
+                        Err(e) => error!("{e:?}"),
                     }
                 }
             }
@@ -285,7 +424,7 @@ fn handle_notification(&mut self, rpc: ProxyNotification) {
                 if let Some(workspace) = self.workspace.as_ref() {
                     match git_checkout(workspace, &reference) {
                         Ok(()) => (),
-                        Err(e) => eprintln!("{e:?}"),
+                        Err(e) => error!("{e:?}"),
                     }
                 }
             }
@@ -296,7 +435,7 @@ fn handle_notification(&mut self, rpc: ProxyNotification) {
                         files.iter().map(AsRef::as_ref),
                     ) {
                         Ok(()) => (),
-                        Err(e) => eprintln!("{e:?}"),
+                        Err(e) => error!("{e:?}"),
                     }
                 }
             }
@@ -304,7 +443,7 @@ fn handle_notification(&mut self, rpc: ProxyNotification) {
                 if let Some(workspace) = self.workspace.as_ref() {
                     match git_discard_workspace_changes(workspace) {
                         Ok(()) => (),
-                        Err(e) => eprintln!("{e:?}"),
+                        Err(e) => error!("{e:?}"),
                     }
                 }
             }
@@ -312,7 +451,7 @@ fn handle_notification(&mut self, rpc: ProxyNotification) {
                 if let Some(workspace) = self.workspace.as_ref() {
                     match git_init(workspace) {
                         Ok(()) => (),
-                        Err(e) => eprintln!("{e:?}"),
+                        Err(e) => error!("{e:?}"),
                     }
                 }
             }
@@ -365,40 +504,92 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                 case_sensitive,
                 whole_word,
                 is_regex,
+                include,
+                exclude,
             } => {
                 static WORKER_ID: AtomicU64 = AtomicU64::new(0);
                 let our_id = WORKER_ID.fetch_add(1, Ordering::SeqCst) + 1;
 
+                self.search_exclude.update(exclude);
+                let exclude = self.search_exclude.clone();
+                let include = PathGlobs::new(include);
                 let workspace = self.workspace.clone();
+                // Open files are searched as they are in the editor, which can
+                // be ahead of what's on disk
                 let buffers = self
                     .buffers
                     .iter()
-                    .map(|p| p.0)
-                    .cloned()
-                    .collect::<Vec<PathBuf>>();
+                    .map(|(path, buffer)| (path.clone(), buffer.rope.clone()))
+                    .collect::<HashMap<PathBuf, Rope>>();
                 let proxy_rpc = self.proxy_rpc.clone();
 
                 // Perform the search on another thread to avoid blocking the proxy thread
                 thread::spawn(move || {
+                    let excluded = Arc::new(AtomicBool::new(false));
+                    let paths = workspace
+                        .iter()
+                        .flat_map(|w| {
+                            walk_files(w, &exclude, &excluded).build().flatten()
+                        })
+                        .map(|p| p.into_path())
+                        .chain(buffers.keys().cloned().filter(|path| {
+                            let is_excluded =
+                                exclude.is_match(workspace.as_deref(), path);
+                            if is_excluded {
+                                excluded.store(true, Ordering::Relaxed);
+                            }
+                            !is_excluded
+                        }));
+                    let result = search_in_path(
+                        our_id,
+                        &WORKER_ID,
+                        include.filter_included(workspace.clone(), paths),
+                        &buffers,
+                        &pattern,
+                        case_sensitive,
+                        whole_word,
+                        is_regex,
+                    )
+                    .map(|response| match response {
+                        ProxyResponse::GlobalSearchResponse { matches, .. } => {
+                            ProxyResponse::GlobalSearchResponse {
+                                matches,
+                                excluded: excluded.load(Ordering::Relaxed),
+                            }
+                        }
+                        response => response,
+                    });
+                    proxy_rpc.handle_response(id, result);
+                });
+            }
+            ApplyTextEdits { files } => {
+                let revs = files
+                    .iter()
+                    .filter_map(|(path, _, _)| {
+                        let buffer = self.buffers.get(path)?;
+                        Some((path.clone(), buffer.rev))
+                    })
+                    .collect::<HashMap<PathBuf, u64>>();
+                let proxy_rpc = self.proxy_rpc.clone();
+                thread::spawn(move || {
+                    let result = apply_text_edits(files, &revs);
+                    proxy_rpc.handle_response(id, Ok(result));
+                });
+            }
+            ReadFiles { paths } => {
+                let proxy_rpc = self.proxy_rpc.clone();
+                thread::spawn(move || {
+                    let files = paths
+                        .into_iter()
+                        .map(|path| {
+                            let content =
+                                fs::read_to_string(&path).map_err(|e| e.to_string());
+                            (path, content)
+                        })
+                        .collect();
                     proxy_rpc.handle_response(
                         id,
-                        search_in_path(
-                            our_id,
-                            &WORKER_ID,
-                            workspace
-                                .iter()
-                                .flat_map(|w| ignore::Walk::new(w).flatten())
-                                .chain(
-                                    buffers.iter().flat_map(|p| {
-                                        ignore::Walk::new(p).flatten()
-                                    }),
-                                )
-                                .map(|p| p.into_path()),
-                            &pattern,
-                            case_sensitive,
-                            whole_word,
-                            is_regex,
-                        ),
+                        Ok(ProxyResponse::ReadFilesResponse { files }),
                     );
                 });
             }
@@ -455,10 +646,29 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                             id,
                             Ok(ProxyResponse::GitGetRemoteFileUrl { file_url: s }),
                         ),
-                        Err(e) => eprintln!("{e:?}"),
+                        Err(e) => error!("{e:?}"),
                     }
                 }
             }
+            GitBlame { path } => {
+                let workspace = self.workspace.clone();
+                // Unsaved changes are blamed as uncommitted
+                let content = self.buffers.get(&path).map(|b| b.rope.to_string());
+                let proxy_rpc = self.proxy_rpc.clone();
+                thread::spawn(move || {
+                    let result = workspace
+                        .ok_or_else(|| anyhow!("no workspace"))
+                        .and_then(|workspace| {
+                            git_blame(&workspace, &path, content.as_deref())
+                        })
+                        .map(|hunks| ProxyResponse::GitBlameResponse { hunks })
+                        .map_err(|e| RpcError {
+                            code: 0,
+                            message: e.to_string(),
+                        });
+                    proxy_rpc.handle_response(id, result);
+                });
+            }
             GetDefinition {
                 request_id,
                 path,
@@ -564,12 +774,14 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                 path,
                 position,
                 diagnostics,
+                only,
             } => {
                 let proxy_rpc = self.proxy_rpc.clone();
                 self.catalog_rpc.get_code_actions(
                     &path,
                     position,
                     diagnostics,
+                    only,
                     move |plugin_id, result| {
                         let result = result.map(|resp| {
                             ProxyResponse::GetCodeActionsResponse { plugin_id, resp }
@@ -578,6 +790,17 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                     },
                 );
             }
+            ExecuteCommand { plugin_id, command } => {
+                let proxy_rpc = self.proxy_rpc.clone();
+                self.catalog_rpc.execute_command(
+                    plugin_id,
+                    command,
+                    move |result| {
+                        let result = result.map(|_| ProxyResponse::Success {});
+                        proxy_rpc.handle_response(id, result);
+                    },
+                );
+            }
             GetDocumentSymbols { path } => {
                 let proxy_rpc = self.proxy_rpc.clone();
                 self.catalog_rpc
@@ -588,24 +811,113 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                     });
             }
             GetWorkspaceSymbols { query } => {
+                static WORKER_ID: AtomicU64 = AtomicU64::new(0);
+                let our_id = WORKER_ID.fetch_add(1, Ordering::SeqCst) + 1;
+
+                let (tx, rx) = crossbeam_channel::bounded(1);
+                self.catalog_rpc.get_workspace_symbols(
+                    query.clone(),
+                    move |result| {
+                        let _ = tx.send(result);
+                    },
+                );
+
+                let workspace = self.workspace.clone();
+                let buffers = self
+                    .buffers
+                    .iter()
+                    .map(|(path, buffer)| (path.clone(), buffer.rope.clone()))
+                    .collect::<HashMap<PathBuf, Rope>>();
                 let proxy_rpc = self.proxy_rpc.clone();
-                self.catalog_rpc
-                    .get_workspace_symbols(query, move |_, result| {
+                // The files of the languages no server is for are scanned for
+                // their declarations instead
+                let _ = self.catalog_rpc.served_languages(move |served| {
+                    thread::spawn(move || {
+                        let mut scanned = match (workspace, served) {
+                            (Some(workspace), Some(served)) => {
+                                scan_workspace_symbols(
+                                    our_id,
+                                    &WORKER_ID,
+                                    ignore::Walk::new(workspace)
+                                        .flatten()
+                                        .map(|p| p.into_path()),
+                                    &buffers,
+                                    &served,
+                                    &query,
+                                )
+                            }
+                            _ => Vec::new(),
+                        };
+                        let result = match rx.recv() {
+                            Ok(Ok(mut symbols)) => {
+                                symbols.append(&mut scanned);
+                                Ok(symbols)
+                            }
+                            Ok(Err(err)) if scanned.is_empty() => Err(err),
+                            _ => Ok(scanned),
+                        };
                         let result = result.map(|symbols| {
                             ProxyResponse::GetWorkspaceSymbols { symbols }
                         });
                         proxy_rpc.handle_response(id, result);
                     });
+                });
             }
-            GetDocumentFormatting { path } => {
+            GetDocumentFormatting { path, options } => {
                 let proxy_rpc = self.proxy_rpc.clone();
-                self.catalog_rpc
-                    .get_document_formatting(&path, move |_, result| {
+                self.catalog_rpc.get_document_formatting(
+                    &path,
+                    options,
+                    move |_, result| {
                         let result = result.map(|edits| {
                             ProxyResponse::GetDocumentFormatting { edits }
                         });
                         proxy_rpc.handle_response(id, result);
-                    });
+                    },
+                );
+            }
+            GetDocumentRangeFormatting {
+                path,
+                range,
+                options,
+            } => {
+                let proxy_rpc = self.proxy_rpc.clone();
+                self.catalog_rpc.get_document_range_formatting(
+                    &path,
+                    range,
+                    options,
+                    move |_, result| {
+                        let result = result.map(|edits| {
+                            ProxyResponse::GetDocumentFormatting { edits }
+                        });
+                        proxy_rpc.handle_response(id, result);
+                    },
+                );
+            }
+            FormatWithCommand { path, command } => {
+                let Some(text) = self
+                    .buffers
+                    .get(&path)
+                    .map(|buffer| buffer.rope.to_string())
+                else {
+                    self.respond_rpc(
+                        id,
+                        Err(RpcError {
+                            code: 0,
+                            message: "the document isn't open".to_string(),
+                        }),
+                    );
+                    return;
+                };
+                let proxy_rpc = self.proxy_rpc.clone();
+                thread::spawn(move || {
+                    let result = format_with_command(&command, &path, text)
+                        .map(|text| ProxyResponse::FormatWithCommandResponse {
+                            text,
+                        })
+                        .map_err(|message| RpcError { code: 0, message });
+                    proxy_rpc.handle_response(id, result);
+                });
             }
             PrepareRename { path, position } => {
                 let proxy_rpc = self.proxy_rpc.clone();
@@ -636,44 +948,82 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                     },
                 );
             }
-            GetFiles { .. } => {
-                let workspace = self.workspace.clone();
+            GetFiles { path, exclude } => {
+                self.files_exclude.update(exclude.clone());
+                let files_exclude = self.files_exclude.clone();
                 let proxy_rpc = self.proxy_rpc.clone();
+                let Some(workspace) = self.workspace.clone() else {
+                    // The folder of a file opened without a workspace isn't
+                    // indexed
+                    thread::spawn(move || {
+                        let items = path
+                            .map(|path| {
+                                walk_workspace_files(&path, &path, &files_exclude)
+                                    .into_keys()
+                                    .collect()
+                            })
+                            .unwrap_or_default();
+                        proxy_rpc.handle_response(
+                            id,
+                            Ok(ProxyResponse::GetFilesResponse { items }),
+                        );
+                    });
+                    return;
+                };
+                let file_index = self.file_index.clone();
+                let core_rpc = self.core_rpc.clone();
                 thread::spawn(move || {
-                    let result = if let Some(workspace) = workspace {
-                        let git_folder =
-                            ignore::overrides::OverrideBuilder::new(&workspace)
-                                .add("!.git/")
-                                .map(|git_folder| git_folder.build());
-
-                        let walker = match git_folder {
-                            Ok(Ok(git_folder)) => {
-                                ignore::WalkBuilder::new(&workspace)
-                                    .hidden(false)
-                                    .parents(false)
-                                    .require_git(false)
-                                    .overrides(git_folder)
-                                    .build()
-                            }
-                            _ => ignore::WalkBuilder::new(&workspace)
-                                .parents(false)
-                                .require_git(false)
-                                .build(),
-                        };
+                    let mut index = file_index.lock();
+                    if !index.is_for(&exclude) {
+                        index.reset(exclude.clone());
+                    }
+                    // The files are listed right away when they're cached, and
+                    // the ones added or removed since are sent once the
+                    // workspace is walked
+                    let listed = !index.is_empty() || index.walked || index.walking;
+                    if listed {
+                        proxy_rpc.handle_response(
+                            id,
+                            Ok(ProxyResponse::GetFilesResponse {
+                                items: index.files(),
+                            }),
+                        );
+                    }
+                    if index.walked || index.walking {
+                        return;
+                    }
+                    index.walking = true;
+                    drop(index);
 
-                        let mut items = Vec::new();
-                        for path in walker.flatten() {
-                            if let Some(file_type) = path.file_type() {
-                                if file_type.is_file() {
-                                    items.push(path.into_path());
-                                }
-                            }
+                    let walked =
+                        walk_workspace_files(&workspace, &workspace, &files_exclude);
+
+                    let mut index = file_index.lock();
+                    // The globs changed during the walk, so it's walked again
+                    if !index.is_for(&exclude) {
+                        if !listed {
+                            proxy_rpc.handle_response(
+                                id,
+                                Ok(ProxyResponse::GetFilesResponse {
+                                    items: walked.into_keys().collect(),
+                                }),
+                            );
                         }
-                        Ok(ProxyResponse::GetFilesResponse { items })
-                    } else {
-                        Ok(ProxyResponse::GetFilesResponse { items: Vec::new() })
-                    };
-                    proxy_rpc.handle_response(id, result);
+                        return;
+                    }
+                    index.walking = false;
+                    let change = index.reconcile(walked);
+                    save_file_index(&workspace, &mut index);
+                    if !listed {
+                        proxy_rpc.handle_response(
+                            id,
+                            Ok(ProxyResponse::GetFilesResponse {
+                                items: index.files(),
+                            }),
+                        );
+                    } else if !change.is_empty() {
+                        core_rpc.file_index_changed(change.added, change.removed);
+                    }
                 });
             }
             GetOpenFilesContent {} => {
@@ -682,7 +1032,7 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                     .iter()
                     .map(|(path, buffer)| TextDocumentItem {
                         uri: Url::from_file_path(path).unwrap(),
-                        language_id: buffer.language_id.to_string(),
+                        language_id: self.catalog_rpc.language_id(path),
                         version: buffer.rev as i32,
                         text: buffer.get_document(),
                     })
@@ -764,13 +1114,40 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                         std::fs::OpenOptions::new()
                             .write(true)
                             .create_new(true)
-                            .open(path)
+                            .open(&path)
                     })
                     .map(|_| ProxyResponse::Success {})
                     .map_err(|e| RpcError {
                         code: 0,
                         message: e.to_string(),
                     });
+                if result.is_ok() {
+                    self.handle_file_event(FileEvent::Created { path });
+                }
+                self.respond_rpc(id, result);
+            }
+            WriteFile {
+                path,
+                content,
+                overwrite,
+            } => {
+                let existed = path.exists();
+                let result = if existed && !overwrite {
+                    Err(RpcError {
+                        code: RpcError::ALREADY_EXISTS,
+                        message: format!("{path:?} already exists"),
+                    })
+                } else {
+                    write_atomically(&path, &content)
+                        .map(|_| ProxyResponse::Success {})
+                        .map_err(|e| RpcError {
+                            code: 0,
+                            message: e.to_string(),
+                        })
+                };
+                if result.is_ok() && !existed {
+                    self.handle_file_event(FileEvent::Created { path });
+                }
                 self.respond_rpc(id, result);
             }
             CreateDirectory { path } => {
@@ -783,14 +1160,45 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                 self.respond_rpc(id, result);
             }
             TrashPath { path } => {
-                let result = trash::delete(path)
+                let result = trash::delete(&path)
                     .map(|_| ProxyResponse::Success {})
                     .map_err(|e| RpcError {
                         code: 0,
                         message: e.to_string(),
                     });
+                if result.is_ok() {
+                    self.handle_file_event(FileEvent::Deleted { path });
+                }
+                self.respond_rpc(id, result);
+            }
+            DeletePath { path } => {
+                let result = if path.is_dir() {
+                    fs::remove_dir_all(&path)
+                } else {
+                    fs::remove_file(&path)
+                };
+                let result =
+                    result.map(|_| ProxyResponse::Success {}).map_err(|e| {
+                        RpcError {
+                            code: 0,
+                            message: e.to_string(),
+                        }
+                    });
+                if result.is_ok() {
+                    self.handle_file_event(FileEvent::Deleted { path });
+                }
                 self.respond_rpc(id, result);
             }
+            CountFiles { path } => {
+                let proxy_rpc = self.proxy_rpc.clone();
+                thread::spawn(move || {
+                    let count = count_files(&path);
+                    proxy_rpc.handle_response(
+                        id,
+                        Ok(ProxyResponse::CountFilesResponse { count }),
+                    );
+                });
+            }
             DuplicatePath {
                 existing_path,
                 new_path,
@@ -822,24 +1230,50 @@ fn handle_request(&mut self, id: RequestId, rpc: ProxyRequest) {
                 };
                 self.respond_rpc(id, result);
             }
-            RenamePath { from, to } => {
+            RenamePath {
+                from,
+                to,
+                overwrite,
+            } => {
                 // We first check if the destination already exists, because rename can overwrite it
                 // and that's not the default behavior we want for when a user renames a document.
-                let result = if to.exists() {
+                let result = if to.exists() && !overwrite {
                     Err(RpcError {
-                        code: 0,
+                        code: RpcError::ALREADY_EXISTS,
                         message: format!("{to:?} already exists"),
                     })
                 } else {
-                    std::fs::rename(from, to)
+                    std::fs::rename(&from, &to)
                         .map(|_| ProxyResponse::Success {})
                         .map_err(|e| RpcError {
                             code: 0,
                             message: e.to_string(),
                         })
                 };
+                if result.is_ok() {
+                    self.catalog_rpc.did_rename_files(&from, &to);
+                    self.handle_file_event(FileEvent::Renamed { from, to });
+                }
                 self.respond_rpc(id, result);
             }
+            WillRenameFiles { from, to } => {
+                let (tx, rx) = crossbeam_channel::bounded(1);
+                self.catalog_rpc
+                    .will_rename_files(&from, &to, move |edits| {
+                        let _ = tx.send(edits);
+                    });
+                // A server that doesn't answer doesn't hold up the rename,
+                // which goes on without its edits
+                let proxy_rpc = self.proxy_rpc.clone();
+                thread::spawn(move || {
+                    let edits =
+                        rx.recv_timeout(WILL_RENAME_TIMEOUT).unwrap_or_default();
+                    proxy_rpc.handle_response(
+                        id,
+                        Ok(ProxyResponse::WillRenameFilesResponse { edits }),
+                    );
+                });
+            }
             GetSelectionRange { positions, path } => {
                 let proxy_rpc = self.proxy_rpc.clone();
                 self.catalog_rpc.get_selection_range(
@@ -889,9 +1323,13 @@ pub fn new(core_rpc: CoreRpcHandler, proxy_rpc: ProxyRpcHandler) -> Self {
             catalog_rpc: plugin_rpc,
             buffers: HashMap::new(),
             terminals: HashMap::new(),
+            tasks: Tasks::default(),
             file_watcher,
             window_id: 1,
             tab_id: 1,
+            files_exclude: PathGlobs::default(),
+            search_exclude: PathGlobs::default(),
+            file_index: Arc::new(Mutex::new(FileIndex::default())),
         }
     }
 
@@ -906,6 +1344,7 @@ struct FileWatchNotifier {
     workspace: Option<PathBuf>,
     workspace_fs_change_handler: Arc<Mutex<Option<Sender<bool>>>>,
     last_diff: Arc<Mutex<DiffInfo>>,
+    file_index: Arc<Mutex<FileIndex>>,
 }
 
 impl Notify for FileWatchNotifier {
@@ -919,6 +1358,7 @@ fn new(
         workspace: Option<PathBuf>,
         core_rpc: CoreRpcHandler,
         proxy_rpc: ProxyRpcHandler,
+        file_index: Arc<Mutex<FileIndex>>,
     ) -> Self {
         let notifier = Self {
             workspace,
@@ -926,6 +1366,7 @@ fn new(
             proxy_rpc,
             workspace_fs_change_handler: Arc::new(Mutex::new(None)),
             last_diff: Arc::new(Mutex::new(DiffInfo::default())),
+            file_index,
         };
 
         if let Some(workspace) = notifier.workspace.clone() {
@@ -953,15 +1394,92 @@ fn handle_fs_events(&self, events: Vec<(WatchToken, notify::Event)>) {
     }
 
     fn handle_open_file_fs_event(&self, event: notify::Event) {
-        if event.kind.is_modify() {
-            for path in event.paths {
-                self.proxy_rpc
-                    .notification(ProxyNotification::OpenFileChanged { path });
+        match event.kind {
+            // Moved away, whether it was renamed is only known from the
+            // events of the workspace, which tell where it went
+            notify::EventKind::Remove(_)
+            | notify::EventKind::Modify(notify::event::ModifyKind::Name(_)) => {
+                for path in event.paths {
+                    self.proxy_rpc.notification(ProxyNotification::FileEvent {
+                        event: FileEvent::Deleted { path },
+                    });
+                }
             }
+            notify::EventKind::Modify(_) => {
+                for path in event.paths {
+                    self.proxy_rpc
+                        .notification(ProxyNotification::OpenFileChanged { path });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Have the open buffers follow a file or folder being created, renamed
+    /// or deleted, whether it's done from the file explorer or outside of
+    /// Lapce, and the UI's documents too when there are buffers to follow it.
+    fn handle_file_event(&mut self, event: FileEvent) {
+        let open = match &event {
+            FileEvent::Created { path } => {
+                let open = self.buffers.contains_key(path);
+                if open {
+                    self.file_watcher.watch(path, false, OPEN_FILE_EVENT_TOKEN);
+                }
+                open
+            }
+            FileEvent::Renamed { from, to } => {
+                let renamed = rename_buffers(&mut self.buffers, from, to);
+                for (old, new) in &renamed {
+                    self.file_watcher.unwatch(old, OPEN_FILE_EVENT_TOKEN);
+                    self.file_watcher.watch(new, false, OPEN_FILE_EVENT_TOKEN);
+                    self.catalog_rpc.did_close_document(old);
+                    self.catalog_rpc.set_language_id(old, None);
+                    if let Some(buffer) = self.buffers.get(new) {
+                        // The UI sets the language again once it has looked
+                        // at the new path
+                        self.catalog_rpc.did_open_document(
+                            new,
+                            buffer.language_id.to_string(),
+                            buffer.rev as i32,
+                            buffer.rope.to_string(),
+                        );
+                    }
+                }
+                !renamed.is_empty()
+            }
+            // The buffers are kept, so that their documents can be saved to
+            // where they were
+            FileEvent::Deleted { path } => {
+                !path.exists()
+                    && self.buffers.keys().any(|open| open.starts_with(path))
+            }
+        };
+        if open {
+            self.core_rpc.file_event(event);
         }
     }
 
     fn handle_workspace_fs_event(&self, event: notify::Event) {
+        if let notify::EventKind::Modify(notify::event::ModifyKind::Name(
+            notify::event::RenameMode::Both,
+        )) = event.kind
+        {
+            if let [from, to] = event.paths.as_slice() {
+                self.proxy_rpc.notification(ProxyNotification::FileEvent {
+                    event: FileEvent::Renamed {
+                        from: from.clone(),
+                        to: to.clone(),
+                    },
+                });
+            }
+        } else if let notify::EventKind::Create(_) = event.kind {
+            for path in &event.paths {
+                self.proxy_rpc.notification(ProxyNotification::FileEvent {
+                    event: FileEvent::Created { path: path.clone() },
+                });
+            }
+        }
+
         let explorer_change = match &event.kind {
             notify::EventKind::Create(_)
             | notify::EventKind::Remove(_)
@@ -969,6 +1487,9 @@ fn handle_workspace_fs_event(&self, event: notify::Event) {
             notify::EventKind::Modify(_) => false,
             _ => return,
         };
+        if explorer_change {
+            self.update_file_index(&event.paths);
+        }
 
         let mut handler = self.workspace_fs_change_handler.lock();
         if let Some(sender) = handler.as_mut() {
@@ -988,6 +1509,7 @@ fn handle_workspace_fs_event(&self, event: notify::Event) {
         let core_rpc = self.core_rpc.clone();
         let workspace = self.workspace.clone().unwrap();
         let last_diff = self.last_diff.clone();
+        let file_index = self.file_index.clone();
         thread::spawn(move || {
             thread::sleep(Duration::from_millis(500));
 
@@ -1005,6 +1527,7 @@ fn handle_workspace_fs_event(&self, event: notify::Event) {
             if explorer_change {
                 core_rpc.workspace_file_change();
             }
+            save_file_index(&workspace, &mut file_index.lock());
             if let Some(diff) = git_diff_new(&workspace) {
                 let mut last_diff = last_diff.lock();
                 if diff != *last_diff {
@@ -1015,6 +1538,41 @@ fn handle_workspace_fs_event(&self, event: notify::Event) {
         });
         *handler = Some(sender);
     }
+
+    /// Have the file index follow the files at `paths` being created, removed
+    /// or renamed, once the workspace has been walked or its files were
+    /// cached. The cache file is written once the events settle.
+    fn update_file_index(&self, paths: &[PathBuf]) {
+        let Some(workspace) = self.workspace.as_ref() else {
+            return;
+        };
+        let mut index = self.file_index.lock();
+        if index.is_empty() && !index.walked {
+            return;
+        }
+        let exclude = PathGlobs::new(index.exclude().to_vec());
+        let mut added = Vec::new();
+        let mut removed = Vec::new();
+        for path in paths {
+            let before = index.remove(path).into_iter().collect::<HashSet<_>>();
+            let after = workspace_files_at(workspace, &exclude, path);
+            removed.extend(
+                before
+                    .iter()
+                    .filter(|file| !after.contains_key(*file))
+                    .cloned(),
+            );
+            for (file, mtime) in after {
+                if !before.contains(&file) {
+                    added.push(file.clone());
+                }
+                index.add(file, mtime);
+            }
+        }
+        if !added.is_empty() || !removed.is_empty() {
+            self.core_rpc.file_index_changed(added, removed);
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -1058,7 +1616,13 @@ fn git_commit(
     let tree = index.write_tree()?;
     let tree = repo.find_tree(tree)?;
     let signature = repo.signature()?;
-    let parent = repo.head()?.peel_to_commit()?;
+    // The first commit of a repository has no parent
+    let parent = match repo.head() {
+        Ok(head) => Some(head.peel_to_commit()?),
+        Err(e) if e.code() == git2::ErrorCode::UnbornBranch => None,
+        Err(e) => return Err(e.into()),
+    };
+    let parents = parent.iter().collect::<Vec<_>>();
 
     repo.commit(
         Some("HEAD"),
@@ -1066,7 +1630,7 @@ fn git_commit(
         &signature,
         message,
         &tree,
-        &[&parent],
+        &parents,
     )?;
     Ok(())
 }
@@ -1145,8 +1709,7 @@ fn git_delta_format(
 
 fn git_diff_new(workspace_path: &Path) -> Option<DiffInfo> {
     let repo = Repository::discover(workspace_path).ok()?;
-    let head = repo.head().ok()?;
-    let name = head.shorthand()?.to_string();
+    let name = git_head_name(&repo)?;
 
     let mut branches = Vec::new();
     for branch in repo.branches(None).ok()? {
@@ -1177,14 +1740,14 @@ fn git_diff_new(workspace_path: &Path) -> Option<DiffInfo> {
             deltas.push(delta);
         }
     }
+    // Without any commits yet, everything in the index is compared against
+    // the empty tree
+    let head_tree = repo
+        .revparse_single("HEAD^{tree}")
+        .ok()
+        .and_then(|tree| repo.find_tree(tree.id()).ok());
     let cached_diff = repo
-        .diff_tree_to_index(
-            repo.find_tree(repo.revparse_single("HEAD^{tree}").ok()?.id())
-                .ok()
-                .as_ref(),
-            None,
-            None,
-        )
+        .diff_tree_to_index(head_tree.as_ref(), None, None)
         .ok()?;
     for delta in cached_diff.deltas() {
         if let Some(delta) = git_delta_format(workspace_path, &delta) {
@@ -1240,6 +1803,68 @@ fn git_diff_new(workspace_path: &Path) -> Option<DiffInfo> {
     })
 }
 
+/// The name shown for `HEAD`: its branch, even when that branch has no
+/// commits yet, or the short commit id when it is detached.
+fn git_head_name(repo: &Repository) -> Option<String> {
+    match repo.head() {
+        Ok(head) => {
+            if repo.head_detached().unwrap_or(false) {
+                let id = head.target()?.to_string();
+                Some(id.chars().take(7).collect())
+            } else {
+                Some(head.shorthand()?.to_string())
+            }
+        }
+        Err(_) => {
+            let head = repo.find_reference("HEAD").ok()?;
+            let target = head.symbolic_target()?;
+            Some(target.trim_start_matches("refs/heads/").to_string())
+        }
+    }
+}
+
+fn git_blame(
+    workspace_path: &Path,
+    path: &Path,
+    content: Option<&str>,
+) -> Result<Vec<BlameHunk>> {
+    let repo = Repository::discover(workspace_path)?;
+    let workdir = repo.workdir().ok_or_else(|| anyhow!("bare repository"))?;
+    let blame = repo.blame_file(path.strip_prefix(workdir)?, None)?;
+    let blame = match content {
+        Some(content) => blame.blame_buffer(content.as_bytes())?,
+        None => blame,
+    };
+
+    let mut commits: HashMap<git2::Oid, BlameCommit> = HashMap::new();
+    let mut hunks = Vec::new();
+    for hunk in blame.iter() {
+        let id = hunk.final_commit_id();
+        let commit = if id.is_zero() {
+            None
+        } else if let Some(commit) = commits.get(&id) {
+            Some(commit.clone())
+        } else {
+            let commit = repo.find_commit(id)?;
+            let commit = BlameCommit {
+                id: id.to_string(),
+                author: commit.author().name().unwrap_or("").to_string(),
+                time: commit.time().seconds(),
+                summary: commit.summary().unwrap_or("").to_string(),
+                message: commit.message().unwrap_or("").to_string(),
+            };
+            commits.insert(id, commit.clone());
+            Some(commit)
+        };
+        hunks.push(BlameHunk {
+            start_line: hunk.final_start_line().saturating_sub(1),
+            lines: hunk.lines_in_hunk(),
+            commit,
+        });
+    }
+    Ok(hunks)
+}
+
 fn file_get_head(workspace_path: &Path, path: &Path) -> Result<(String, String)> {
     let repo = Repository::discover(workspace_path)?;
     let head = repo.head()?;
@@ -1298,10 +1923,109 @@ fn git_get_remote_file_url(workspace_path: &Path, file: &Path) -> Result<String>
     Ok(url)
 }
 
+/// Move the buffers of `from`, or of the files in it if it's a folder, to
+/// where they are in `to`, returning their old and new paths.
+fn rename_buffers(
+    buffers: &mut HashMap<PathBuf, Buffer>,
+    from: &Path,
+    to: &Path,
+) -> Vec<(PathBuf, PathBuf)> {
+    let renamed = buffers
+        .keys()
+        .filter_map(|path| Some((path.clone(), renamed_path(path, from, to)?)))
+        .collect::<Vec<_>>();
+    for (old, new) in &renamed {
+        if let Some(mut buffer) = buffers.remove(old) {
+            buffer.path = new.clone();
+            buffer.language_id = language_id_from_path(new).unwrap_or("");
+            buffers.insert(new.clone(), buffer);
+        }
+    }
+    renamed
+}
+
+/// The new content is written next to the file and then moved over it, so
+/// that a failure never leaves the file half written.
+fn write_atomically(path: &Path, content: &str) -> std::io::Result<()> {
+    let file_name = path.file_name().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, "not a file")
+    })?;
+    let tmp_path =
+        path.with_file_name(format!(".{}.lapce-write", file_name.to_string_lossy()));
+    fs::write(&tmp_path, content)?;
+    if let Ok(metadata) = fs::metadata(path) {
+        let _ = fs::set_permissions(&tmp_path, metadata.permissions());
+    }
+    if let Err(err) = fs::rename(&tmp_path, path) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// Apply the edits of a language server to each file, leaving alone the files
+/// that changed since the edits were made. `revs` holds the revisions of the
+/// files that are open.
+fn apply_text_edits(
+    files: Vec<(PathBuf, Option<i32>, Vec<TextEdit>)>,
+    revs: &HashMap<PathBuf, u64>,
+) -> ProxyResponse {
+    let mut changed = 0;
+    let mut failures = Vec::new();
+    for (path, version, edits) in files {
+        let outdated = match (version, revs.get(&path)) {
+            (Some(version), Some(rev)) => *rev as i32 != version,
+            _ => false,
+        };
+        let result = if outdated {
+            Err(anyhow!("it changed since the edits were made"))
+        } else {
+            apply_text_edits_to_file(&path, &edits)
+        };
+        match result {
+            Ok(()) => changed += 1,
+            Err(err) => failures.push((path, err.to_string())),
+        }
+    }
+    ProxyResponse::ApplyTextEditsResponse {
+        files: changed,
+        failures,
+    }
+}
+
+fn apply_text_edits_to_file(path: &Path, edits: &[TextEdit]) -> Result<()> {
+    let text = fs::read_to_string(path)?;
+    let rope = Rope::from(text.as_str());
+    let rope_text = RopeTextRef::new(&rope);
+    let last_line = rope_text.last_line();
+
+    let mut edits = edits
+        .iter()
+        .map(|edit| {
+            // Positions past the end mean the file isn't what the edits
+            // were made for
+            if edit.range.end.line as usize > last_line {
+                return Err(anyhow!("it changed since the edits were made"));
+            }
+            let start = rope_text.offset_of_position(&edit.range.start);
+            let end = rope_text.offset_of_position(&edit.range.end);
+            Ok((start..end.max(start), edit.new_text.clone()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    edits.sort_by_key(|(range, _)| range.start);
+    if edits.windows(2).any(|pair| pair[0].0.end > pair[1].0.start) {
+        return Err(anyhow!("the edits overlap"));
+    }
+
+    write_atomically(path, &apply_edits(&text, &edits))?;
+    Ok(())
+}
+
 fn search_in_path(
     id: u64,
     current_id: &AtomicU64,
     paths: impl Iterator<Item = PathBuf>,
+    buffers: &HashMap<PathBuf, Rope>,
     pattern: &str,
     case_sensitive: bool,
     whole_word: bool,
@@ -1319,7 +2043,11 @@ fn search_in_path(
         code: 0,
         message: "can't build matcher".to_string(),
     })?;
-    let mut searcher = SearcherBuilder::new().build();
+    // Stop at the first NUL byte, which is taken to mean a binary file
+    let mut searcher = SearcherBuilder::new()
+        .binary_detection(BinaryDetection::quit(b'\x00'))
+        .build();
+    let mut searched = HashSet::new();
 
     for path in paths {
         if current_id.load(Ordering::SeqCst) != id {
@@ -1329,12 +2057,15 @@ fn search_in_path(
             });
         }
 
-        if path.is_file() {
+        if !searched.insert(path.clone()) {
+            continue;
+        }
+
+        let buffer = buffers.get(&path);
+        if buffer.is_some() || path.is_file() {
             let mut line_matches = Vec::new();
-            let _ = searcher.search_path(
-                &matcher,
-                path.clone(),
-                UTF8(|lnum, line| {
+            let mut on_line =
+                |lnum: u64, line: &str| -> Result<bool, std::io::Error> {
                     if current_id.load(Ordering::SeqCst) != id {
                         return Ok(false);
                     }
@@ -1369,13 +2100,438 @@ fn search_in_path(
                         line_content: line,
                     });
                     Ok(true)
-                }),
-            );
+                };
+            let _ = match buffer {
+                Some(rope) => searcher.search_slice(
+                    &matcher,
+                    rope.to_string().as_bytes(),
+                    UTF8(&mut on_line),
+                ),
+                None => searcher.search_path(&matcher, &path, UTF8(&mut on_line)),
+            };
             if !line_matches.is_empty() {
                 matches.insert(path.clone(), line_matches);
             }
         }
     }
 
-    Ok(ProxyResponse::GlobalSearchResponse { matches })
+    Ok(ProxyResponse::GlobalSearchResponse {
+        matches,
+        excluded: false,
+    })
+}
+
+/// A walk of the files in `workspace` which doesn't go into the paths
+/// matching `exclude`, setting `excluded` when it leaves one out.
+fn walk_files(
+    workspace: &Path,
+    exclude: &PathGlobs,
+    excluded: &Arc<AtomicBool>,
+) -> ignore::WalkBuilder {
+    walk_files_in(workspace, workspace, exclude, excluded)
+}
+
+/// Like [`walk_files`], for the files in `root`, which is in `workspace`.
+fn walk_files_in(
+    workspace: &Path,
+    root: &Path,
+    exclude: &PathGlobs,
+    excluded: &Arc<AtomicBool>,
+) -> ignore::WalkBuilder {
+    let mut walker = ignore::WalkBuilder::new(root);
+    if !exclude.is_empty() {
+        let workspace = workspace.to_path_buf();
+        let exclude = exclude.clone();
+        let excluded = excluded.clone();
+        walker.filter_entry(move |entry| {
+            let is_excluded =
+                exclude.is_match(Some(workspace.as_path()), entry.path());
+            if is_excluded {
+                excluded.store(true, Ordering::Relaxed);
+            }
+            !is_excluded
+        });
+    }
+    walker
+}
+
+/// The files the file palette lists in `root`, which is `workspace` or one of
+/// its folders, with their modification times: the hidden ones too, but not
+/// the ones in `.git`, ignored by git or matching `exclude`.
+fn walk_workspace_files(
+    workspace: &Path,
+    root: &Path,
+    exclude: &PathGlobs,
+) -> BTreeMap<PathBuf, u64> {
+    let git_folder = ignore::overrides::OverrideBuilder::new(workspace)
+        .add("!.git/")
+        .map(|git_folder| git_folder.build());
+
+    let excluded = Arc::new(AtomicBool::new(false));
+    let mut walker = walk_files_in(workspace, root, exclude, &excluded);
+    // The `.gitignore` files of the folders of a folder in the workspace
+    // apply to it too
+    walker.parents(root != workspace).require_git(false);
+    if let Ok(Ok(git_folder)) = git_folder {
+        walker.hidden(false).overrides(git_folder);
+    }
+
+    let mut files = BTreeMap::new();
+    for entry in walker.build().flatten() {
+        if entry
+            .file_type()
+            .map_or(false, |file_type| file_type.is_file())
+        {
+            let mtime = entry.metadata().map(|m| mtime(&m)).unwrap_or(0);
+            files.insert(entry.into_path(), mtime);
+        }
+    }
+    files
+}
+
+/// The files at `path` in `workspace`, which is a file or a folder, that a walk
+/// of the workspace finds.
+fn workspace_files_at(
+    workspace: &Path,
+    exclude: &PathGlobs,
+    path: &Path,
+) -> BTreeMap<PathBuf, u64> {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return BTreeMap::new();
+    };
+    if is_left_out(workspace, exclude, path, metadata.is_dir()) {
+        BTreeMap::new()
+    } else if metadata.is_dir() {
+        walk_workspace_files(workspace, path, exclude)
+    } else if metadata.is_file() {
+        BTreeMap::from([(path.to_path_buf(), mtime(&metadata))])
+    } else {
+        BTreeMap::new()
+    }
+}
+
+/// Write the file index of `workspace` to its cache file, if it changed since
+/// it was written. When it can't be written, the workspace is walked from
+/// scratch the next time.
+fn save_file_index(workspace: &Path, index: &mut FileIndex) {
+    if index.dirty && (index.walked || !index.is_empty()) {
+        if let Some(path) = FileIndex::cache_file(workspace) {
+            let _ = index.save(&path);
+        }
+    }
+}
+
+/// How many files there are in `path` and in its folders, or 1 when it's a
+/// file. Links are counted as files rather than followed.
+fn count_files(path: &Path) -> usize {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => fs::read_dir(path)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|entry| count_files(&entry.path()))
+                    .sum()
+            })
+            .unwrap_or(0),
+        Ok(_) => 1,
+        Err(_) => 0,
+    }
+}
+
+/// Whether the characters of the lowercase `query` appear in `name` in order.
+fn symbol_matches_query(name: &str, query: &str) -> bool {
+    let mut name = name.chars().flat_map(char::to_lowercase);
+    query.chars().all(|c| name.any(|n| n == c))
+}
+
+#[allow(deprecated)]
+fn push_scanned_symbols(
+    symbols: &mut Vec<SymbolInformation>,
+    uri: &Url,
+    container_name: Option<&str>,
+    scanned: Vec<DocumentSymbol>,
+    query: &str,
+) {
+    for symbol in scanned {
+        if symbol_matches_query(&symbol.name, query) {
+            symbols.push(SymbolInformation {
+                name: symbol.name.clone(),
+                kind: symbol.kind,
+                tags: None,
+                deprecated: None,
+                location: Location::new(uri.clone(), symbol.selection_range),
+                container_name: container_name.map(|name| name.to_string()),
+            });
+        }
+        if let Some(children) = symbol.children {
+            push_scanned_symbols(symbols, uri, Some(&symbol.name), children, query);
+        }
+    }
+}
+
+/// The declarations matching `query` in the files of the languages that none
+/// of the `served_languages` is, for the workspace symbols no server gives.
+fn scan_workspace_symbols(
+    id: u64,
+    current_id: &AtomicU64,
+    paths: impl Iterator<Item = PathBuf>,
+    buffers: &HashMap<PathBuf, Rope>,
+    served_languages: &HashSet<String>,
+    query: &str,
+) -> Vec<SymbolInformation> {
+    let query = query.to_lowercase();
+    let mut symbols = Vec::new();
+    let mut scanned = HashSet::new();
+
+    for path in paths {
+        if current_id.load(Ordering::SeqCst) != id
+            || symbols.len() >= SCANNED_SYMBOLS_LIMIT
+        {
+            break;
+        }
+        if !scanned.insert(path.clone()) {
+            continue;
+        }
+        let Some(language_id) = language_id_from_path(&path) else {
+            continue;
+        };
+        if served_languages.contains(language_id) {
+            continue;
+        }
+        let Ok(uri) = Url::from_file_path(&path) else {
+            continue;
+        };
+        let text = match buffers.get(&path) {
+            Some(text) => text.clone(),
+            None => match fs::read_to_string(&path) {
+                Ok(text) => Rope::from(text),
+                Err(_) => continue,
+            },
+        };
+        push_scanned_symbols(&mut symbols, &uri, None, scan_symbols(&text), &query);
+    }
+
+    symbols
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{HashMap, HashSet},
+        path::PathBuf,
+        sync::atomic::AtomicU64,
+    };
+
+    use git2::Repository;
+    use lapce_rpc::{
+        buffer::BufferId, proxy::ProxyResponse, source_control::FileDiff,
+    };
+    use lapce_xi_rope::Rope;
+
+    use lsp_types::{Position, Range, TextEdit};
+
+    use super::{
+        apply_text_edits, count_files, git_blame, git_commit, git_diff_new,
+        rename_buffers, scan_workspace_symbols, search_in_path,
+    };
+    use crate::buffer::Buffer;
+
+    fn text_edit(line: u32, start: u32, end: u32, new_text: &str) -> TextEdit {
+        TextEdit {
+            range: Range {
+                start: Position {
+                    line,
+                    character: start,
+                },
+                end: Position {
+                    line,
+                    character: end,
+                },
+            },
+            new_text: new_text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_search_open_buffers() {
+        let current_id = AtomicU64::new(1);
+        let text = PathBuf::from("/not/on/disk/text.txt");
+        let binary = PathBuf::from("/not/on/disk/binary.bin");
+        let buffers = HashMap::from([
+            (text.clone(), Rope::from("one\nfoo two\nthree foo\n")),
+            (binary.clone(), Rope::from("\0foo\n")),
+        ]);
+
+        let result = search_in_path(
+            1,
+            &current_id,
+            [text.clone(), binary, text.clone()].into_iter(),
+            &buffers,
+            "foo",
+            false,
+            false,
+            false,
+        );
+        let Ok(ProxyResponse::GlobalSearchResponse { matches, .. }) = result else {
+            panic!("search failed");
+        };
+        assert_eq!(matches.len(), 1);
+        let lines = matches[&text].iter().map(|m| m.line).collect::<Vec<_>>();
+        assert_eq!(lines, vec![2, 3]);
+    }
+
+    #[test]
+    fn test_rename_buffers() {
+        let dir = PathBuf::from("/not/on/disk/src");
+        let mut buffer = Buffer::new(BufferId::next(), dir.join("lib.rs"));
+        buffer.rope = Rope::from("unsaved\n");
+        let other = PathBuf::from("/not/on/disk/srcs/main.rs");
+        let mut buffers = HashMap::from([
+            (buffer.path.clone(), buffer),
+            (other.clone(), Buffer::new(BufferId::next(), other.clone())),
+        ]);
+
+        let renamed =
+            rename_buffers(&mut buffers, &dir, &PathBuf::from("/not/on/disk/crate"));
+        let new = PathBuf::from("/not/on/disk/crate/lib.rs");
+        assert_eq!(renamed, vec![(dir.join("lib.rs"), new.clone())]);
+        assert!(buffers.contains_key(&other));
+        let buffer = &buffers[&new];
+        assert_eq!(buffer.path, new);
+        assert_eq!(buffer.rope.to_string(), "unsaved\n");
+
+        let renamed = rename_buffers(&mut buffers, &new, &new.with_extension("md"));
+        assert_eq!(renamed.len(), 1);
+        assert_eq!(buffers[&new.with_extension("md")].language_id, "markdown");
+    }
+
+    #[test]
+    fn test_scan_workspace_symbols() {
+        let current_id = AtomicU64::new(1);
+        let python = PathBuf::from("/not/on/disk/a.py");
+        let rust = PathBuf::from("/not/on/disk/b.rs");
+        let buffers = HashMap::from([
+            (
+                python.clone(),
+                Rope::from("class Foo:\n    def format(self):\n        pass\n"),
+            ),
+            (rust.clone(), Rope::from("fn format() {}\n")),
+        ]);
+
+        let symbols = scan_workspace_symbols(
+            1,
+            &current_id,
+            [python, rust].into_iter(),
+            &buffers,
+            &HashSet::from(["rust".to_string()]),
+            "FO",
+        );
+        let names = symbols
+            .iter()
+            .map(|s| (s.name.as_str(), s.container_name.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec![("Foo", None), ("format", Some("Foo"))]);
+        assert_eq!(symbols[1].location.range.start, Position::new(1, 8));
+    }
+
+    #[test]
+    fn test_git_repository_without_commits() {
+        let temp = tempfile::tempdir().unwrap();
+        let workspace = temp.path();
+        let repo = Repository::init(workspace).unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "Lapce").unwrap();
+        config.set_str("user.email", "lapce@example.com").unwrap();
+        let file = workspace.join("new.txt");
+        std::fs::write(&file, "new\n").unwrap();
+
+        let diff = git_diff_new(workspace).unwrap();
+        assert!(!diff.head.is_empty());
+        assert_eq!(diff.diffs, vec![FileDiff::Added(file.clone())]);
+
+        git_commit(workspace, "first", vec![FileDiff::Added(file.clone())]).unwrap();
+        let diff = git_diff_new(workspace).unwrap();
+        assert!(diff.diffs.is_empty());
+    }
+
+    #[test]
+    fn test_git_blame_unsaved_lines() {
+        let temp = tempfile::tempdir().unwrap();
+        let workspace = temp.path();
+        let repo = Repository::init(workspace).unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "Lapce").unwrap();
+        config.set_str("user.email", "lapce@example.com").unwrap();
+        let file = workspace.join("new.txt");
+        std::fs::write(&file, "new\n").unwrap();
+        git_commit(workspace, "first", vec![FileDiff::Added(file.clone())]).unwrap();
+
+        let hunks = git_blame(workspace, &file, Some("new\nunsaved\n")).unwrap();
+        assert_eq!(hunks.len(), 2);
+        let summary = hunks[0].commit.as_ref().map(|c| c.summary.as_str());
+        assert_eq!(summary, Some("first"));
+        assert_eq!(hunks[1].start_line, 1);
+        assert_eq!(hunks[1].commit, None);
+    }
+
+    #[test]
+    fn test_apply_text_edits() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let edited = dir.join("edited.rs");
+        let changed = dir.join("changed.rs");
+        let open = dir.join("open.rs");
+        for path in [&edited, &changed, &open] {
+            std::fs::write(path, "let foo = 1;\nfoo + foo\n").unwrap();
+        }
+
+        let rename = vec![
+            text_edit(1, 6, 9, "bar"),
+            text_edit(0, 4, 7, "bar"),
+            text_edit(1, 0, 3, "bar"),
+        ];
+        let revs = HashMap::from([(open.clone(), 3)]);
+        let result = apply_text_edits(
+            vec![
+                (edited.clone(), None, rename.clone()),
+                (changed.clone(), None, vec![text_edit(5, 0, 3, "bar")]),
+                (open.clone(), Some(2), rename),
+            ],
+            &revs,
+        );
+        let ProxyResponse::ApplyTextEditsResponse { files, failures } = result
+        else {
+            panic!("unexpected response");
+        };
+        assert_eq!(files, 1);
+        assert_eq!(
+            failures.iter().map(|(path, _)| path).collect::<Vec<_>>(),
+            vec![&changed, &open]
+        );
+        assert_eq!(
+            std::fs::read_to_string(&edited).unwrap(),
+            "let bar = 1;\nbar + bar\n"
+        );
+        for path in [&changed, &open] {
+            assert_eq!(
+                std::fs::read_to_string(path).unwrap(),
+                "let foo = 1;\nfoo + foo\n"
+            );
+        }
+    }
+
+    #[test]
+    fn test_count_files() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        std::fs::create_dir_all(dir.join("nested").join("empty")).unwrap();
+        std::fs::write(dir.join("a.rs"), "").unwrap();
+        std::fs::write(dir.join("nested").join("b.rs"), "").unwrap();
+        std::fs::write(dir.join("nested").join(".hidden"), "").unwrap();
+
+        assert_eq!(count_files(dir), 3);
+        assert_eq!(count_files(&dir.join("a.rs")), 1);
+        assert_eq!(count_files(&dir.join("nested").join("empty")), 0);
+        assert_eq!(count_files(&dir.join("missing")), 0);
+    }
 }
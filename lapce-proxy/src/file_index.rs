@@ -0,0 +1,321 @@
+//! The files of the workspace that the file palette lists, kept in a cache
+//! file so that the palette lists them as soon as Lapce starts, rather than
+//! once the whole workspace has been walked.
+//!
+//! The files are read from the cache first, then the workspace is walked in
+//! the background and the files added or removed since are sent to the UI.
+//! From then on the file watcher keeps the files up to date. A cache that
+//! can't be read, or that was written by another version of the format or for
+//! other `files.exclude` globs, is walked again from scratch.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use ignore::gitignore::Gitignore;
+use lapce_core::directory::Directory;
+
+use crate::globs::PathGlobs;
+
+/// The first line of the cache file, followed by the version of its format
+const CACHE_HEADER: &str = "lapce-file-index";
+
+/// The version of the format of the cache file, which a cache of another
+/// version isn't read for
+const CACHE_VERSION: u32 = 1;
+
+#[derive(Default)]
+pub struct FileIndex {
+    /// The `files.exclude` globs the files were walked with
+    exclude: Vec<String>,
+    /// The files, with their modification times in seconds since the epoch
+    files: BTreeMap<PathBuf, u64>,
+    /// Whether the workspace was walked since Lapce started, rather than the
+    /// files only being read from the cache
+    pub walked: bool,
+    /// Whether the workspace is being walked
+    pub walking: bool,
+    /// Whether the files changed since the cache was written
+    pub dirty: bool,
+}
+
+/// The files added to and removed from the index
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct FileIndexChange {
+    pub added: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+}
+
+impl FileIndexChange {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl FileIndex {
+    /// The cache file of the index of `workspace`, which is named after a
+    /// hash of its path.
+    pub fn cache_file(workspace: &Path) -> Option<PathBuf> {
+        let dir = Directory::file_index_directory()?;
+        Some(dir.join(format!("{:016x}", fnv_hash(workspace))))
+    }
+
+    /// The index in the cache file at `path`, if it can be read.
+    pub fn load(path: &Path) -> Option<Self> {
+        let text = std::fs::read_to_string(path).ok()?;
+        Self::parse(&text)
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        let mut lines = text.lines();
+        let version = lines.next()?.strip_prefix(CACHE_HEADER)?.trim();
+        if version.parse::<u32>().ok()? != CACHE_VERSION {
+            return None;
+        }
+        let exclude = serde_json::from_str(lines.next()?).ok()?;
+        let mut files = BTreeMap::new();
+        for line in lines {
+            let (mtime, path) = line.split_once('\t')?;
+            files.insert(PathBuf::from(path), mtime.parse().ok()?);
+        }
+        Some(Self {
+            exclude,
+            files,
+            ..Default::default()
+        })
+    }
+
+    fn to_cache(&self) -> String {
+        let mut text = format!("{CACHE_HEADER} {CACHE_VERSION}\n");
+        text.push_str(
+            &serde_json::to_string(&self.exclude).unwrap_or_else(|_| "[]".into()),
+        );
+        text.push('\n');
+        for (path, mtime) in &self.files {
+            // A path that isn't unicode is found again by the next walk
+            if let Some(path) = path.to_str().filter(|path| !path.contains('\n')) {
+                let _ = writeln!(text, "{mtime}\t{path}");
+            }
+        }
+        text
+    }
+
+    /// Write the index to the cache file at `path`, by way of a temporary
+    /// file so that the cache is never half written.
+    pub fn save(&mut self, path: &Path) -> std::io::Result<()> {
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, self.to_cache())?;
+        std::fs::rename(&tmp, path)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Whether the files were walked with the `exclude` globs.
+    pub fn is_for(&self, exclude: &[String]) -> bool {
+        self.exclude == exclude
+    }
+
+    pub fn exclude(&self) -> &[String] {
+        &self.exclude
+    }
+
+    /// Start over for the `exclude` globs.
+    pub fn reset(&mut self, exclude: Vec<String>) {
+        *self = Self {
+            exclude,
+            ..Default::default()
+        };
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn files(&self) -> Vec<PathBuf> {
+        self.files.keys().cloned().collect()
+    }
+
+    /// Take the files found by a walk of the workspace as the files, and
+    /// return the ones added and removed.
+    pub fn reconcile(&mut self, walked: BTreeMap<PathBuf, u64>) -> FileIndexChange {
+        let mut change = FileIndexChange::default();
+        for path in self.files.keys() {
+            if !walked.contains_key(path) {
+                change.removed.push(path.clone());
+            }
+        }
+        for (path, mtime) in &walked {
+            match self.files.get(path) {
+                None => change.added.push(path.clone()),
+                Some(old) if old != mtime => self.dirty = true,
+                Some(_) => {}
+            }
+        }
+        if !change.is_empty() {
+            self.dirty = true;
+        }
+        self.files = walked;
+        self.walked = true;
+        change
+    }
+
+    /// Add the file at `path`, returning whether it's new.
+    pub fn add(&mut self, path: PathBuf, mtime: u64) -> bool {
+        self.dirty = true;
+        self.files.insert(path, mtime).is_none()
+    }
+
+    /// Remove the file at `path`, or the files in the folder at `path`,
+    /// returning the ones removed.
+    pub fn remove(&mut self, path: &Path) -> Vec<PathBuf> {
+        let removed = self
+            .files
+            .range(path.to_path_buf()..)
+            .map(|(file, _)| file)
+            .take_while(|file| file.starts_with(path))
+            .cloned()
+            .collect::<Vec<_>>();
+        for file in &removed {
+            self.files.remove(file);
+        }
+        if !removed.is_empty() {
+            self.dirty = true;
+        }
+        removed
+    }
+}
+
+/// The modification time of `metadata` in seconds since the epoch, or 0 when
+/// there's none.
+pub fn mtime(metadata: &std::fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|mtime| mtime.duration_since(UNIX_EPOCH).ok())
+        .map(|mtime| mtime.as_secs())
+        .unwrap_or(0)
+}
+
+/// Whether a walk of `workspace` leaves out `path`: it's in `.git`, it
+/// matches the `exclude` globs, or the `.gitignore` of one of its folders
+/// ignores it.
+pub fn is_left_out(
+    workspace: &Path,
+    exclude: &PathGlobs,
+    path: &Path,
+    is_dir: bool,
+) -> bool {
+    let Ok(relative) = path.strip_prefix(workspace) else {
+        return true;
+    };
+    if relative.components().any(|c| c.as_os_str() == ".git")
+        || exclude.is_match(Some(workspace), path)
+    {
+        return true;
+    }
+    let mut dir = workspace.to_path_buf();
+    let mut folders = relative.components();
+    folders.next_back();
+    for folder in std::iter::once(None).chain(folders.map(Some)) {
+        if let Some(folder) = folder {
+            dir.push(folder);
+        }
+        let gitignore = dir.join(".gitignore");
+        if gitignore.is_file()
+            && Gitignore::new(&gitignore)
+                .0
+                .matched_path_or_any_parents(path, is_dir)
+                .is_ignore()
+        {
+            return true;
+        }
+    }
+    false
+}
+
+/// The 64 bit FNV-1a hash of `path`, which unlike the std hasher is the same
+/// from one version of Lapce to the next.
+fn fnv_hash(path: &Path) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in path.to_string_lossy().as_bytes() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, path::PathBuf};
+
+    use super::{FileIndex, FileIndexChange};
+
+    fn index(files: &[(&str, u64)]) -> FileIndex {
+        FileIndex {
+            exclude: vec!["**/node_modules".to_string()],
+            files: files
+                .iter()
+                .map(|(path, mtime)| (PathBuf::from(path), *mtime))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_cache() {
+        let index = index(&[("/ws/src/main.rs", 10), ("/ws/a b\tc.txt", 20)]);
+        let cache = index.to_cache();
+        let loaded = FileIndex::parse(&cache).unwrap();
+        assert_eq!(loaded.exclude, index.exclude);
+        assert_eq!(loaded.files, index.files);
+        assert!(!loaded.walked);
+
+        // Another version of the format, or a broken cache, isn't read
+        assert!(FileIndex::parse(&cache.replacen(" 1\n", " 2\n", 1)).is_none());
+        assert!(FileIndex::parse(&cache.replacen("10\t", "x\t", 1)).is_none());
+        assert!(FileIndex::parse(&format!("{cache}/ws/no-mtime\n")).is_none());
+        assert!(FileIndex::parse("").is_none());
+    }
+
+    #[test]
+    fn test_reconcile() {
+        let mut index = index(&[("/ws/a", 1), ("/ws/b", 1), ("/ws/c", 1)]);
+        let walked = [("/ws/b", 1), ("/ws/c", 2), ("/ws/d", 1)]
+            .into_iter()
+            .map(|(path, mtime)| (PathBuf::from(path), mtime))
+            .collect::<BTreeMap<_, _>>();
+        let change = index.reconcile(walked.clone());
+        assert_eq!(
+            change,
+            FileIndexChange {
+                added: vec![PathBuf::from("/ws/d")],
+                removed: vec![PathBuf::from("/ws/a")],
+            }
+        );
+        assert_eq!(index.files, walked);
+        assert!(index.walked && index.dirty);
+    }
+
+    #[test]
+    fn test_add_remove() {
+        let mut index =
+            index(&[("/ws/src/a.rs", 1), ("/ws/src/b/c.rs", 1), ("/ws/srcs", 1)]);
+        assert!(index.add(PathBuf::from("/ws/d.rs"), 1));
+        assert!(!index.add(PathBuf::from("/ws/d.rs"), 2));
+        assert_eq!(
+            index.remove(&PathBuf::from("/ws/src")),
+            vec![
+                PathBuf::from("/ws/src/a.rs"),
+                PathBuf::from("/ws/src/b/c.rs")
+            ]
+        );
+        assert_eq!(
+            index.files(),
+            vec![PathBuf::from("/ws/d.rs"), PathBuf::from("/ws/srcs")]
+        );
+        assert!(index.remove(&PathBuf::from("/ws/e")).is_empty());
+    }
+}
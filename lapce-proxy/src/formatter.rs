@@ -0,0 +1,146 @@
+use std::{
+    io::{Read, Write},
+    path::Path,
+    process::Stdio,
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::task::{kill_tree, shell_command};
+
+/// How long a formatter has to finish before it's taken to be hung and killed
+const FORMAT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often a formatter is checked for having exited
+const WAIT_INTERVAL: Duration = Duration::from_millis(20);
+
+/// `command` with the `{path}` in it replaced by `path`, quoted for the shell.
+pub fn expand_command(command: &str, path: &Path) -> String {
+    let path = path.to_string_lossy();
+    #[cfg(not(target_os = "windows"))]
+    let quoted = format!("'{}'", path.replace('\'', r"'\''"));
+    #[cfg(target_os = "windows")]
+    let quoted = format!("\"{path}\"");
+    command.replace("{path}", &quoted)
+}
+
+/// Run the formatter `command` in a shell, in the folder of `path`, with
+/// `text` on its stdin, the text it prints being the formatted one. It fails
+/// with what it printed to stderr when it exits with an error, and is killed
+/// when it runs for longer than [`FORMAT_TIMEOUT`].
+pub fn format_with_command(
+    command: &str,
+    path: &Path,
+    text: String,
+) -> Result<String, String> {
+    let cwd = path.parent().map(Path::to_path_buf);
+    let mut child = shell_command(&expand_command(command, path), cwd)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("Couldn't run `{command}`: {err}"))?;
+
+    // The pipes each have a thread, so that a formatter that prints before
+    // it has read all of its input doesn't block on a full pipe
+    let empty = text.is_empty();
+    let stdin = child.stdin.take();
+    thread::spawn(move || {
+        if let Some(mut stdin) = stdin {
+            let _ = stdin.write_all(text.as_bytes());
+        }
+    });
+    let stdout = read_pipe(child.stdout.take());
+    let stderr = read_pipe(child.stderr.take());
+
+    let start = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if start.elapsed() < FORMAT_TIMEOUT => {
+                thread::sleep(WAIT_INTERVAL);
+            }
+            Ok(None) => {
+                kill_tree(&mut child);
+                let _ = child.wait();
+                return Err(format!(
+                    "`{command}` didn't finish in {} seconds, so it was stopped",
+                    FORMAT_TIMEOUT.as_secs()
+                ));
+            }
+            Err(err) => return Err(err.to_string()),
+        }
+    };
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+
+    if !status.success() {
+        let stderr = String::from_utf8_lossy(&stderr);
+        let stderr = stderr.trim();
+        return Err(match status.code() {
+            _ if !stderr.is_empty() => stderr.to_string(),
+            Some(code) => format!("`{command}` failed with exit code {code}"),
+            None => format!("`{command}` was stopped"),
+        });
+    }
+    // A formatter that rewrites the file rather than printing it would
+    // otherwise empty the document
+    if stdout.is_empty() && !empty {
+        return Err(format!("`{command}` didn't print the formatted text"));
+    }
+    String::from_utf8(stdout)
+        .map_err(|_| format!("`{command}` printed text that isn't UTF-8"))
+}
+
+/// Read all of `pipe` until it's closed.
+fn read_pipe<R: Read + Send + 'static>(pipe: Option<R>) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+#[cfg(test)]
+#[cfg(not(target_os = "windows"))]
+mod tests {
+    use std::path::Path;
+
+    use super::{expand_command, format_with_command};
+
+    #[test]
+    fn test_expand_command() {
+        assert_eq!(
+            expand_command(
+                "prettier --stdin-filepath {path}",
+                Path::new("/a b/c.ts")
+            ),
+            "prettier --stdin-filepath '/a b/c.ts'"
+        );
+        assert_eq!(
+            expand_command("fmt {path}", Path::new("/it's.md")),
+            r"fmt '/it'\''s.md'"
+        );
+        assert_eq!(expand_command("rustfmt", Path::new("/a.rs")), "rustfmt");
+    }
+
+    #[test]
+    fn test_format_with_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        assert_eq!(
+            format_with_command("tr a-z A-Z", &path, "abc\n".to_string()),
+            Ok("ABC\n".to_string())
+        );
+        assert_eq!(
+            format_with_command("echo bad >&2; exit 3", &path, "abc".to_string()),
+            Err("bad".to_string())
+        );
+        assert!(
+            format_with_command("cat > /dev/null", &path, "abc".to_string())
+                .is_err()
+        );
+    }
+}
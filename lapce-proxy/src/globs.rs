@@ -0,0 +1,129 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+
+/// Globs of paths in the workspace, like `**/node_modules` or `*.min.js`,
+/// matched against the paths relative to the workspace. They're compiled
+/// once, and cheap to clone into the threads walking the workspace.
+#[derive(Clone)]
+pub struct PathGlobs {
+    patterns: Vec<String>,
+    set: Arc<GlobSet>,
+}
+
+impl Default for PathGlobs {
+    fn default() -> Self {
+        Self {
+            patterns: Vec::new(),
+            set: Arc::new(GlobSet::empty()),
+        }
+    }
+}
+
+impl PathGlobs {
+    /// Compile `patterns`, leaving out the ones that aren't valid globs.
+    pub fn new(patterns: Vec<String>) -> Self {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in &patterns {
+            if let Ok(glob) = Glob::new(pattern) {
+                builder.add(glob);
+            }
+        }
+        let set = builder.build().unwrap_or_else(|_| GlobSet::empty());
+        Self {
+            patterns,
+            set: Arc::new(set),
+        }
+    }
+
+    /// Compile `patterns` if they aren't the ones compiled already.
+    pub fn update(&mut self, patterns: Vec<String>) {
+        if self.patterns != patterns {
+            *self = Self::new(patterns);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty()
+    }
+
+    /// Whether `path`, or one of the folders it's in, matches a glob.
+    pub fn is_match(&self, workspace: Option<&Path>, path: &Path) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+        let path = workspace
+            .and_then(|workspace| path.strip_prefix(workspace).ok())
+            .unwrap_or(path);
+        path.ancestors()
+            .filter(|path| !path.as_os_str().is_empty())
+            .any(|path| self.set.is_match(path))
+    }
+
+    /// Keep the paths that match a glob, or all of them when there are none.
+    pub fn filter_included(
+        &self,
+        workspace: Option<PathBuf>,
+        paths: impl Iterator<Item = PathBuf>,
+    ) -> impl Iterator<Item = PathBuf> {
+        let globs = self.clone();
+        paths.filter(move |path| {
+            globs.is_empty() || globs.is_match(workspace.as_deref(), path)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::PathGlobs;
+
+    #[test]
+    fn test_path_globs() {
+        let globs = PathGlobs::new(vec![
+            "**/node_modules".to_string(),
+            "*.min.js".to_string(),
+            "fixtures/**".to_string(),
+            "[invalid".to_string(),
+        ]);
+        let workspace = Some(Path::new("/ws"));
+        for (path, matched) in [
+            ("/ws/node_modules", true),
+            ("/ws/web/node_modules/react/index.js", true),
+            ("/ws/dist/app.min.js", true),
+            ("/ws/fixtures/a/b.txt", true),
+            ("/ws/src/fixtures/b.txt", false),
+            ("/ws/src/main.rs", false),
+            ("/ws/app.js", false),
+            // Outside of the workspace, the whole path is matched
+            ("/other/node_modules/a.js", true),
+        ] {
+            assert_eq!(
+                globs.is_match(workspace, Path::new(path)),
+                matched,
+                "{path}"
+            );
+        }
+
+        assert!(!PathGlobs::default().is_match(workspace, Path::new("/ws/a")));
+    }
+
+    #[test]
+    fn test_path_globs_included() {
+        let paths = ["/ws/src/a.rs", "/ws/src/b.js", "/ws/c.rs"]
+            .map(|path| Path::new(path).to_path_buf());
+        let included = |patterns: &[&str]| {
+            PathGlobs::new(patterns.iter().map(|p| p.to_string()).collect())
+                .filter_included(Some("/ws".into()), paths.clone().into_iter())
+                .count()
+        };
+        assert_eq!(included(&[]), 3);
+        assert_eq!(included(&["*.rs"]), 2);
+        assert_eq!(included(&["src"]), 2);
+        assert_eq!(included(&["src/*.js", "c.rs"]), 2);
+    }
+}
@@ -3,7 +3,11 @@
 pub mod buffer;
 pub mod cli;
 pub mod dispatch;
+pub mod file_index;
+pub mod formatter;
+pub mod globs;
 pub mod plugin;
+pub mod task;
 pub mod terminal;
 pub mod watcher;
 
@@ -1,23 +1,29 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     path::PathBuf,
     sync::{
         atomic::{AtomicUsize, Ordering},
         Arc,
     },
     thread,
+    time::{Duration, Instant},
 };
 
+use jsonrpc_lite::Id;
 use lapce_rpc::{
     dap_types::{DapId, DapServer, SetBreakpointsResponse},
-    plugin::{PluginId, VoltID, VoltMetadata},
+    plugin::{
+        LanguageServerConfig, LanguageServerStatus, PluginId, VoltID, VoltMetadata,
+    },
     proxy::ProxyResponse,
     style::LineStyle,
     RpcError,
 };
 use lapce_xi_rope::{Rope, RopeDelta};
 use lsp_types::{
-    notification::DidOpenTextDocument, DidOpenTextDocumentParams, SemanticTokens,
+    notification::{DidCloseTextDocument, DidOpenTextDocument},
+    request::{Completion, Request, WorkspaceSymbol},
+    DidCloseTextDocumentParams, DidOpenTextDocumentParams, SemanticTokens,
     TextDocumentIdentifier, TextDocumentItem, VersionedTextDocumentIdentifier,
 };
 use parking_lot::Mutex;
@@ -26,12 +32,35 @@
 
 use super::{
     dap::{DapClient, DapRpcHandler},
+    lsp::{language_server_language_id, language_server_volt_id, LspClient},
     psp::{ClonableCallback, PluginServerRpc, PluginServerRpcHandler, RpcCallback},
-    wasi::{load_all_volts, start_volt},
+    wasi::{find_all_volts, load_all_volts, start_volt},
     PluginCatalogNotification, PluginCatalogRpcHandler,
 };
 use crate::plugin::{install_volt, wasi::enable_volt};
 
+/// How long to wait before restarting a crashed language server, doubled for
+/// every crash in a row
+const RESTART_BACKOFF: Duration = Duration::from_secs(1);
+/// How many crashes in a row a language server gets restarted after
+const MAX_RESTARTS: u32 = 5;
+/// How long a language server has to run for its crashes to no longer count
+/// as in a row
+const RESTART_RESET_AFTER: Duration = Duration::from_secs(60);
+
+/// A language server from the user's settings, started when the first document
+/// of its language is opened.
+struct LanguageServer {
+    config: LanguageServerConfig,
+    /// The server, unless it failed to start or is waiting to be restarted
+    rpc: Option<PluginServerRpcHandler>,
+    /// How many times in a row the server crashed
+    restarts: u32,
+    started: Instant,
+    /// Whether the user stopped the server, so that it's not restarted
+    stopped: bool,
+}
+
 pub struct PluginCatalog {
     workspace: Option<PathBuf>,
     plugin_rpc: PluginCatalogRpcHandler,
@@ -40,6 +69,14 @@ pub struct PluginCatalog {
     plugin_configurations: HashMap<String, HashMap<String, serde_json::Value>>,
     unactivated_volts: HashMap<VoltID, VoltMetadata>,
     open_files: HashMap<PathBuf, String>,
+    /// The configured language servers by language id
+    language_server_configs: HashMap<String, LanguageServerConfig>,
+    /// The configured language servers that were started
+    language_servers: HashMap<String, LanguageServer>,
+    /// The completion requests made since completion was last cancelled
+    completion_requests: Vec<(PluginId, Id)>,
+    /// The workspace symbol requests made since they were last cancelled
+    workspace_symbol_requests: Vec<(PluginId, Id)>,
 }
 
 impl PluginCatalog {
@@ -47,6 +84,7 @@ pub fn new(
         workspace: Option<PathBuf>,
         disabled_volts: Vec<VoltID>,
         plugin_configurations: HashMap<String, HashMap<String, serde_json::Value>>,
+        language_server_configs: HashMap<String, LanguageServerConfig>,
         plugin_rpc: PluginCatalogRpcHandler,
     ) -> Self {
         {
@@ -86,6 +124,10 @@ pub fn new(
             daps: HashMap::new(),
             unactivated_volts: HashMap::new(),
             open_files: HashMap::new(),
+            language_server_configs,
+            language_servers: HashMap::new(),
+            completion_requests: Vec::new(),
+            workspace_symbol_requests: Vec::new(),
         };
 
         thread::spawn(move || {
@@ -108,7 +150,7 @@ pub fn handle_server_request(
     ) {
         if let Some(plugin_id) = plugin_id {
             if let Some(plugin) = self.plugins.get(&plugin_id) {
-                plugin.server_request_async(
+                let id = plugin.server_request_async(
                     method,
                     params,
                     language_id,
@@ -118,6 +160,11 @@ pub fn handle_server_request(
                         f(plugin_id, result);
                     },
                 );
+                if method == Completion::METHOD {
+                    self.completion_requests.push((plugin_id, id));
+                } else if method == WorkspaceSymbol::METHOD {
+                    self.workspace_symbol_requests.push((plugin_id, id));
+                }
             } else {
                 f(
                     plugin_id,
@@ -140,10 +187,9 @@ pub fn handle_server_request(
                 // make a direct callback with an "error"
                 f(
                     lapce_rpc::plugin::PluginId(0),
-                    Err(RpcError {
-                        code: 0,
-                        message: "no available plugin could make a callback, because the plugins list is empty".to_string(),
-                    }),
+                    Err(RpcError::not_capable(
+                        "no available plugin could make a callback, because the plugins list is empty",
+                    )),
                 );
                 return;
             } else {
@@ -153,7 +199,7 @@ pub fn handle_server_request(
         for (plugin_id, plugin) in self.plugins.iter() {
             let f = dyn_clone::clone_box(&*f);
             let plugin_id = *plugin_id;
-            plugin.server_request_async(
+            let id = plugin.server_request_async(
                 method,
                 params.clone(),
                 language_id.clone(),
@@ -163,9 +209,44 @@ pub fn handle_server_request(
                     f(plugin_id, result);
                 },
             );
+            if method == Completion::METHOD {
+                self.completion_requests.push((plugin_id, id));
+            } else if method == WorkspaceSymbol::METHOD {
+                self.workspace_symbol_requests.push((plugin_id, id));
+            }
+        }
+    }
+
+    /// Cancel the completion requests made for a completion that was closed
+    /// or moved elsewhere. Those that were already answered are ignored by the
+    /// servers.
+    fn cancel_completions(&mut self) {
+        for (plugin_id, id) in self.completion_requests.drain(..) {
+            if let Some(plugin) = self.plugins.get(&plugin_id) {
+                plugin.cancel_request(id);
+            }
         }
     }
 
+    /// Cancel the workspace symbol requests made for a query that changed.
+    fn cancel_workspace_symbols(&mut self) {
+        for (plugin_id, id) in self.workspace_symbol_requests.drain(..) {
+            if let Some(plugin) = self.plugins.get(&plugin_id) {
+                plugin.cancel_request(id);
+            }
+        }
+    }
+
+    /// The languages of the documents the loaded servers are for, or `None`
+    /// if one of them takes documents of any language.
+    fn served_languages(&self) -> Option<HashSet<String>> {
+        let mut languages = HashSet::new();
+        for plugin in self.plugins.values() {
+            languages.extend(plugin.languages.as_ref()?.iter().cloned());
+        }
+        Some(languages)
+    }
+
     pub fn handle_server_notification(
         &mut self,
         method: &'static str,
@@ -272,6 +353,9 @@ pub fn handle_did_open_text_document(&mut self, document: TextDocumentItem) {
             })
             .collect();
         self.start_unactivated_volts(to_be_activated);
+        if !self.language_servers.contains_key(&document.language_id) {
+            self.start_language_server(&document.language_id);
+        }
 
         let path = document.uri.to_file_path().ok();
         for (_, plugin) in self.plugins.iter() {
@@ -287,6 +371,157 @@ pub fn handle_did_open_text_document(&mut self, document: TextDocumentItem) {
         }
     }
 
+    fn start_language_server(&mut self, language_id: &str) {
+        let Some(config) = self.language_server_configs.get(language_id).cloned()
+        else {
+            return;
+        };
+        let restarts = self
+            .language_servers
+            .get(language_id)
+            .map(|server| server.restarts)
+            .unwrap_or(0);
+
+        let core_rpc = &self.plugin_rpc.core_rpc;
+        let rpc = match LspClient::start_language_server(
+            self.plugin_rpc.clone(),
+            self.workspace.clone(),
+            language_id.to_string(),
+            config.clone(),
+        ) {
+            Ok(rpc) => Some(rpc),
+            Err(err) => {
+                core_rpc.language_server_status(
+                    language_server_volt_id(language_id),
+                    config.command.clone(),
+                    LanguageServerStatus::Failed {
+                        message: err.to_string(),
+                    },
+                );
+                None
+            }
+        };
+        self.language_servers.insert(
+            language_id.to_string(),
+            LanguageServer {
+                config,
+                rpc,
+                restarts,
+                started: Instant::now(),
+                stopped: false,
+            },
+        );
+    }
+
+    /// Shut down the servers that go by `volt_id`, so that they aren't
+    /// restarted as if they crashed.
+    fn stop_server(&mut self, volt_id: &VoltID) {
+        let ids: Vec<PluginId> = self
+            .plugins
+            .iter()
+            .filter(|(_, plugin)| &plugin.volt_id == volt_id)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in ids.iter() {
+            if let Some(plugin) = self.plugins.remove(id) {
+                plugin.shutdown();
+            }
+        }
+
+        // The server may not have been loaded yet
+        if let Some(rpc) = language_server_language_id(volt_id)
+            .and_then(|language_id| self.language_servers.get_mut(language_id))
+            .and_then(|server| server.rpc.take())
+        {
+            if !ids.contains(&rpc.plugin_id) {
+                rpc.shutdown();
+            }
+        }
+    }
+
+    /// Restart the configured language server of a plugin server that stopped,
+    /// backing off when it keeps crashing.
+    fn handle_plugin_server_stopped(&mut self, plugin_id: PluginId) {
+        let plugin = self.plugins.remove(&plugin_id);
+        if let Some(plugin) = plugin.as_ref() {
+            plugin.shutdown();
+        }
+
+        let Some((language_id, server)) =
+            self.language_servers.iter_mut().find(|(_, server)| {
+                matches!(&server.rpc, Some(rpc) if rpc.plugin_id == plugin_id)
+            })
+        else {
+            // A server of a volt, which is only started again with the volt
+            if let Some(plugin) = plugin {
+                self.plugin_rpc.core_rpc.language_server_status(
+                    plugin.volt_id.clone(),
+                    plugin.volt_id.name.clone(),
+                    LanguageServerStatus::Failed {
+                        message: "the server stopped".to_string(),
+                    },
+                );
+            }
+            return;
+        };
+        if let Some(rpc) = server.rpc.take() {
+            rpc.shutdown();
+        }
+
+        if server.started.elapsed() > RESTART_RESET_AFTER {
+            server.restarts = 0;
+        }
+        let core_rpc = &self.plugin_rpc.core_rpc;
+        if server.restarts >= MAX_RESTARTS {
+            core_rpc.language_server_status(
+                language_server_volt_id(language_id),
+                server.config.command.clone(),
+                LanguageServerStatus::Failed {
+                    message: format!("crashed {MAX_RESTARTS} times in a row"),
+                },
+            );
+            return;
+        }
+
+        let delay = RESTART_BACKOFF * 2u32.pow(server.restarts);
+        server.restarts += 1;
+        core_rpc.language_server_status(
+            language_server_volt_id(language_id),
+            server.config.command.clone(),
+            LanguageServerStatus::Restarting {
+                attempt: server.restarts,
+            },
+        );
+        let plugin_rpc = self.plugin_rpc.clone();
+        let language_id = language_id.clone();
+        thread::spawn(move || {
+            thread::sleep(delay);
+            let _ = plugin_rpc.restart_language_server(language_id);
+        });
+    }
+
+    pub fn handle_did_close_text_document(
+        &mut self,
+        language_id: String,
+        document: TextDocumentIdentifier,
+    ) {
+        let path = document.uri.to_file_path().ok();
+        if let Some(path) = path.as_ref() {
+            self.open_files.remove(path);
+        }
+        for (_, plugin) in self.plugins.iter() {
+            plugin.server_notification(
+                DidCloseTextDocument::METHOD,
+                DidCloseTextDocumentParams {
+                    text_document: document.clone(),
+                },
+                Some(language_id.clone()),
+                path.clone(),
+                true,
+            );
+        }
+    }
+
     pub fn handle_did_save_text_document(
         &mut self,
         language_id: String,
@@ -380,6 +615,61 @@ pub fn handle_notification(&mut self, notification: PluginCatalogNotification) {
                 }
                 self.plugins.insert(plugin.plugin_id, plugin);
             }
+            PluginServerStopped(plugin_id) => {
+                self.handle_plugin_server_stopped(plugin_id);
+            }
+            CancelCompletions => {
+                self.cancel_completions();
+            }
+            CancelWorkspaceSymbols => {
+                self.cancel_workspace_symbols();
+            }
+            ServedLanguages(f) => {
+                f(self.served_languages());
+            }
+            RestartLanguageServer(language_id) => {
+                if matches!(
+                    self.language_servers.get(&language_id),
+                    Some(server) if server.rpc.is_none() && !server.stopped
+                ) {
+                    self.start_language_server(&language_id);
+                }
+            }
+            RestartServer(volt_id) => {
+                self.stop_server(&volt_id);
+                if let Some(language_id) = language_server_language_id(&volt_id) {
+                    if let Some(server) = self.language_servers.get_mut(language_id)
+                    {
+                        server.restarts = 0;
+                    }
+                    self.start_language_server(language_id);
+                } else {
+                    let plugin_rpc = self.plugin_rpc.clone();
+                    thread::spawn(move || {
+                        if let Some(volt) = find_all_volts()
+                            .into_iter()
+                            .find(|volt| VoltID::from(volt) == volt_id)
+                        {
+                            let _ = plugin_rpc.unactivated_volts(vec![volt]);
+                        }
+                    });
+                }
+            }
+            StopServer(volt_id) => {
+                self.stop_server(&volt_id);
+                if let Some(server) =
+                    language_server_language_id(&volt_id).and_then(|language_id| {
+                        self.language_servers.get_mut(language_id)
+                    })
+                {
+                    server.stopped = true;
+                }
+                self.plugin_rpc.core_rpc.language_server_status(
+                    volt_id.clone(),
+                    volt_id.name.clone(),
+                    LanguageServerStatus::Stopped,
+                );
+            }
             InstallVolt(volt) => {
                 let workspace = self.workspace.clone();
                 let configurations =
@@ -411,6 +701,11 @@ pub fn handle_notification(&mut self, notification: PluginCatalogNotification) {
                         plugin.shutdown();
                     }
                 }
+                self.plugin_rpc.core_rpc.language_server_status(
+                    volt_id.clone(),
+                    volt_id.name.clone(),
+                    LanguageServerStatus::Stopped,
+                );
             }
             EnableVolt(volt) => {
                 let volt_id = volt.id();
@@ -526,6 +821,14 @@ pub fn handle_notification(&mut self, notification: PluginCatalogNotification) {
                 for (_, plugin) in self.plugins.iter() {
                     plugin.shutdown();
                 }
+                // The servers that haven't finished initializing yet
+                for (_, server) in self.language_servers.drain() {
+                    if let Some(rpc) = server.rpc {
+                        if !self.plugins.contains_key(&rpc.plugin_id) {
+                            rpc.shutdown();
+                        }
+                    }
+                }
             }
         }
     }
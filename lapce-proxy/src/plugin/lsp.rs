@@ -4,29 +4,32 @@
     io::{BufRead, BufReader, BufWriter, Write},
     path::{Path, PathBuf},
     process::{self, Child, Command, Stdio},
-    sync::Arc,
     thread,
+    time::{Duration, Instant},
 };
 
 use anyhow::{anyhow, Result};
 use crossbeam_channel::Sender;
 use jsonrpc_lite::{Id, Params};
 use lapce_core::meta;
-use lapce_rpc::{plugin::VoltID, style::LineStyle, RpcError};
+use lapce_rpc::{
+    plugin::{LanguageServerConfig, LanguageServerStatus, VoltID},
+    style::LineStyle,
+    RpcError,
+};
 use lapce_xi_rope::Rope;
 use lsp_types::{
     notification::{Initialized, Notification},
     request::{Initialize, Request},
     *,
 };
-use parking_lot::Mutex;
 use serde_json::Value;
 
 use super::{
     client_capabilities,
     psp::{
-        handle_plugin_server_message, PluginHandlerNotification, PluginHostHandler,
-        PluginServerHandler, PluginServerRpcHandler, RpcCallback,
+        handle_plugin_server_message, DocumentChanges, PluginHandlerNotification,
+        PluginHostHandler, PluginServerHandler, PluginServerRpcHandler, RpcCallback,
     },
 };
 use crate::{buffer::Buffer, plugin::PluginCatalogRpcHandler};
@@ -34,6 +37,9 @@
 const HEADER_CONTENT_LENGTH: &str = "content-length";
 const HEADER_CONTENT_TYPE: &str = "content-type";
 
+/// How long a server gets to exit on its own before it's killed
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(1);
+
 pub enum LspRpc {
     Request {
         id: u64,
@@ -54,9 +60,28 @@ pub enum LspRpc {
     },
 }
 
+/// The author of the volt id made up for a language server from the settings
+const SETTINGS_AUTHOR: &str = "settings";
+
+/// The volt id the language server from the settings for `language_id` goes
+/// by.
+pub fn language_server_volt_id(language_id: &str) -> VoltID {
+    VoltID {
+        author: SETTINGS_AUTHOR.to_string(),
+        name: language_id.to_string(),
+    }
+}
+
+/// The language id of the server from the settings that goes by `volt_id`.
+pub fn language_server_language_id(volt_id: &VoltID) -> Option<&str> {
+    (volt_id.author == SETTINGS_AUTHOR).then_some(volt_id.name.as_str())
+}
+
 pub struct LspClient {
     plugin_rpc: PluginCatalogRpcHandler,
     server_rpc: PluginServerRpcHandler,
+    volt_id: VoltID,
+    volt_display_name: String,
     process: Child,
     workspace: Option<PathBuf>,
     host: PluginHostHandler,
@@ -68,6 +93,10 @@ fn method_registered(&mut self, method: &'static str) -> bool {
         self.host.method_registered(method)
     }
 
+    fn request_supported(&self, method: &'static str, params: &Params) -> bool {
+        self.host.request_supported(method, params)
+    }
+
     fn document_supported(
         &mut self,
         lanaguage_id: Option<&str>,
@@ -130,12 +159,7 @@ fn handle_did_change_text_document(
         delta: lapce_xi_rope::RopeDelta,
         text: lapce_xi_rope::Rope,
         new_text: lapce_xi_rope::Rope,
-        change: Arc<
-            Mutex<(
-                Option<TextDocumentContentChangeEvent>,
-                Option<TextDocumentContentChangeEvent>,
-            )>,
-        >,
+        change: DocumentChanges,
     ) {
         self.host.handle_did_change_text_document(
             language_id,
@@ -166,32 +190,28 @@ fn new(
         volt_id: VoltID,
         volt_display_name: String,
         pwd: Option<PathBuf>,
-        server_uri: Url,
+        server: String,
         args: Vec<String>,
         options: Option<Value>,
     ) -> Result<Self> {
-        let server = match server_uri.scheme() {
-            "file" => {
-                let path = server_uri.to_file_path().map_err(|_| anyhow!(""))?;
-                #[cfg(unix)]
-                let _ = std::process::Command::new("chmod")
-                    .arg("+x")
-                    .arg(&path)
-                    .output();
-                path.to_str().ok_or_else(|| anyhow!(""))?.to_string()
-            }
-            "urn" => server_uri.path().to_string(),
-            _ => return Err(anyhow!("uri not supported")),
-        };
-
         let mut process = Self::process(workspace.as_ref(), &server, &args)?;
+        plugin_rpc.core_rpc.language_server_status(
+            volt_id.clone(),
+            volt_display_name.clone(),
+            LanguageServerStatus::Starting,
+        );
         let stdin = process.stdin.take().unwrap();
         let stdout = process.stdout.take().unwrap();
         let stderr = process.stderr.take().unwrap();
 
         let mut writer = Box::new(BufWriter::new(stdin));
         let (io_tx, io_rx) = crossbeam_channel::unbounded();
-        let server_rpc = PluginServerRpcHandler::new(volt_id.clone(), io_tx.clone());
+        let languages = document_selector
+            .iter()
+            .map(|filter| filter.language.clone())
+            .collect();
+        let server_rpc =
+            PluginServerRpcHandler::new(volt_id.clone(), languages, io_tx.clone());
         thread::spawn(move || {
             for msg in io_rx {
                 if let Ok(msg) = serde_json::to_string(&msg) {
@@ -204,6 +224,7 @@ fn new(
         });
 
         let local_server_rpc = server_rpc.clone();
+        let local_plugin_rpc = plugin_rpc.clone();
         let core_rpc = plugin_rpc.core_rpc.clone();
         thread::spawn(move || {
             let mut reader = Box::new(BufReader::new(stdout));
@@ -222,6 +243,9 @@ fn new(
                             tracing::Level::ERROR,
                             format!("lsp server {server} stopped!"),
                         );
+                        local_server_rpc.fail_pending_requests();
+                        let _ = local_plugin_rpc
+                            .plugin_server_stopped(local_server_rpc.plugin_id);
                         return;
                     }
                 };
@@ -229,6 +253,7 @@ fn new(
         });
 
         let core_rpc = plugin_rpc.core_rpc.clone();
+        let log_volt_id = volt_id.clone();
         thread::spawn(move || {
             let mut reader = Box::new(BufReader::new(stderr));
             loop {
@@ -238,10 +263,9 @@ fn new(
                         if n == 0 {
                             return;
                         }
-                        core_rpc.log(
-                            tracing::Level::ERROR,
-                            format!("lsp server stderr: {}", line.trim_end()),
-                        );
+                        let line = line.trim_end().to_string();
+                        tracing::debug!("{log_volt_id} stderr: {line}");
+                        core_rpc.language_server_log(log_volt_id.clone(), line);
                     }
                     Err(_) => {
                         return;
@@ -253,8 +277,8 @@ fn new(
         let host = PluginHostHandler::new(
             workspace.clone(),
             pwd,
-            volt_id,
-            volt_display_name,
+            volt_id.clone(),
+            volt_display_name.clone(),
             document_selector,
             server_rpc.clone(),
             plugin_rpc.clone(),
@@ -263,6 +287,8 @@ fn new(
         Ok(Self {
             plugin_rpc,
             server_rpc,
+            volt_id,
+            volt_display_name,
             process,
             workspace,
             host,
@@ -282,24 +308,87 @@ pub fn start(
         args: Vec<String>,
         options: Option<Value>,
     ) -> Result<()> {
-        let mut lsp = Self::new(
+        let server = match server_uri.scheme() {
+            "file" => {
+                let path = server_uri.to_file_path().map_err(|_| anyhow!(""))?;
+                #[cfg(unix)]
+                let _ = std::process::Command::new("chmod")
+                    .arg("+x")
+                    .arg(&path)
+                    .output();
+                path.to_str().ok_or_else(|| anyhow!(""))?.to_string()
+            }
+            "urn" => server_uri.path().to_string(),
+            _ => return Err(anyhow!("uri not supported")),
+        };
+
+        let core_rpc = plugin_rpc.core_rpc.clone();
+        let lsp = match Self::new(
             plugin_rpc,
             document_selector,
             workspace,
-            volt_id,
-            volt_display_name,
+            volt_id.clone(),
+            volt_display_name.clone(),
             pwd,
-            server_uri,
+            server,
             args,
             options,
-        )?;
-        let rpc = lsp.server_rpc.clone();
-        thread::spawn(move || {
-            rpc.mainloop(&mut lsp);
-        });
+        ) {
+            Ok(lsp) => lsp,
+            Err(err) => {
+                core_rpc.language_server_status(
+                    volt_id,
+                    volt_display_name,
+                    LanguageServerStatus::Failed {
+                        message: err.to_string(),
+                    },
+                );
+                return Err(err);
+            }
+        };
+        lsp.run();
         Ok(())
     }
 
+    /// Start a language server from the user's settings for the documents of
+    /// `language_id`.
+    pub fn start_language_server(
+        plugin_rpc: PluginCatalogRpcHandler,
+        workspace: Option<PathBuf>,
+        language_id: String,
+        config: LanguageServerConfig,
+    ) -> Result<PluginServerRpcHandler> {
+        let document_selector = vec![lsp_types::DocumentFilter {
+            language: Some(language_id.clone()),
+            scheme: None,
+            pattern: None,
+        }];
+        let volt_id = language_server_volt_id(&language_id);
+        let lsp = Self::new(
+            plugin_rpc,
+            document_selector,
+            workspace,
+            volt_id,
+            config.command.clone(),
+            None,
+            config.command,
+            config.args,
+            config.options,
+        )?;
+        Ok(lsp.run())
+    }
+
+    fn run(mut self) -> PluginServerRpcHandler {
+        let rpc = self.server_rpc.clone();
+        {
+            let rpc = rpc.clone();
+            thread::spawn(move || {
+                rpc.mainloop(&mut self);
+            });
+        }
+        rpc
+    }
+
     fn initialize(&mut self) {
         let root_uri = self
             .workspace
@@ -325,29 +414,47 @@ fn initialize(&mut self) {
             locale: None,
             root_path: None,
         };
-        if let Ok(value) = self.server_rpc.server_request(
+        let core_rpc = &self.plugin_rpc.core_rpc;
+        match self.server_rpc.server_request(
             Initialize::METHOD,
             params,
             None,
             None,
             false,
         ) {
-            let result: InitializeResult = serde_json::from_value(value).unwrap();
-            self.host.server_capabilities = result.capabilities;
-            self.server_rpc.server_notification(
-                Initialized::METHOD,
-                InitializedParams {},
-                None,
-                None,
-                false,
-            );
-            if self
-                .plugin_rpc
-                .plugin_server_loaded(self.server_rpc.clone())
-                .is_err()
-            {
-                self.server_rpc.shutdown();
-                self.shutdown();
+            Ok(value) => {
+                let result: InitializeResult =
+                    serde_json::from_value(value).unwrap();
+                self.host.server_capabilities = result.capabilities;
+                self.server_rpc.server_notification(
+                    Initialized::METHOD,
+                    InitializedParams {},
+                    None,
+                    None,
+                    false,
+                );
+                core_rpc.language_server_status(
+                    self.volt_id.clone(),
+                    self.volt_display_name.clone(),
+                    LanguageServerStatus::Running,
+                );
+                if self
+                    .plugin_rpc
+                    .plugin_server_loaded(self.server_rpc.clone())
+                    .is_err()
+                {
+                    self.server_rpc.shutdown();
+                    self.shutdown();
+                }
+            }
+            Err(err) => {
+                core_rpc.language_server_status(
+                    self.volt_id.clone(),
+                    self.volt_display_name.clone(),
+                    LanguageServerStatus::Failed {
+                        message: err.message,
+                    },
+                );
             }
         }
         //     move |result| {
@@ -362,7 +469,37 @@ fn initialize(&mut self) {
         // );
     }
 
+    /// Ask the server to shut down and exit, and kill it if it doesn't in time.
     fn shutdown(&mut self) {
+        if matches!(self.process.try_wait(), Ok(None)) {
+            let (tx, rx) = crossbeam_channel::bounded(1);
+            self.server_rpc.server_request_async(
+                request::Shutdown::METHOD,
+                (),
+                None,
+                None,
+                false,
+                move |result| {
+                    let _ = tx.send(result);
+                },
+            );
+            if let Ok(Ok(_)) = rx.recv_timeout(SHUTDOWN_TIMEOUT) {
+                self.server_rpc.server_notification(
+                    notification::Exit::METHOD,
+                    (),
+                    None,
+                    None,
+                    false,
+                );
+                let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+                while Instant::now() < deadline {
+                    if !matches!(self.process.try_wait(), Ok(None)) {
+                        return;
+                    }
+                    thread::sleep(Duration::from_millis(10));
+                }
+            }
+        }
         let _ = self.process.kill();
         let _ = self.process.wait();
     }
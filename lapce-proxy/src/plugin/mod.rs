@@ -5,7 +5,7 @@
 pub mod wasi;
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs,
     path::{Path, PathBuf},
     sync::{
@@ -23,7 +23,7 @@
 use lapce_rpc::{
     core::CoreRpcHandler,
     dap_types::{DapId, RunDebugConfig, SourceBreakpoint, ThreadId},
-    plugin::{PluginId, VoltInfo, VoltMetadata},
+    plugin::{PluginId, VoltID, VoltInfo, VoltMetadata},
     proxy::ProxyRpcHandler,
     style::LineStyle,
     terminal::TermId,
@@ -31,40 +31,46 @@
 };
 use lapce_xi_rope::{Rope, RopeDelta};
 use lsp_types::{
+    notification::{DidRenameFiles, Notification},
     request::{
         CodeActionRequest, CodeActionResolveRequest, Completion,
-        DocumentSymbolRequest, Formatting, GotoDefinition, GotoTypeDefinition,
-        GotoTypeDefinitionParams, GotoTypeDefinitionResponse, HoverRequest,
-        InlayHintRequest, PrepareRenameRequest, References, Rename, Request,
-        ResolveCompletionItem, SelectionRangeRequest, SemanticTokensFullRequest,
-        SignatureHelpRequest, WorkspaceSymbol,
+        DocumentSymbolRequest, ExecuteCommand, Formatting, GotoDefinition,
+        GotoTypeDefinition, GotoTypeDefinitionParams, GotoTypeDefinitionResponse,
+        HoverRequest, InlayHintRequest, PrepareRenameRequest, RangeFormatting,
+        References, Rename, Request, ResolveCompletionItem, SelectionRangeRequest,
+        SemanticTokensFullRequest, SignatureHelpRequest, WillRenameFiles,
+        WorkspaceSymbol,
     },
     ClientCapabilities, CodeAction, CodeActionCapabilityResolveSupport,
     CodeActionClientCapabilities, CodeActionContext, CodeActionKind,
     CodeActionKindLiteralSupport, CodeActionLiteralSupport, CodeActionParams,
-    CodeActionResponse, CompletionClientCapabilities, CompletionItem,
-    CompletionItemCapability, CompletionItemCapabilityResolveSupport,
-    CompletionParams, CompletionResponse, Diagnostic, DocumentFormattingParams,
-    DocumentSymbolParams, DocumentSymbolResponse, FormattingOptions, GotoCapability,
+    CodeActionResponse, Command, CompletionClientCapabilities, CompletionContext,
+    CompletionItem, CompletionItemCapability,
+    CompletionItemCapabilityResolveSupport, CompletionParams, CompletionResponse,
+    CompletionTriggerKind, Diagnostic, DocumentFormattingParams,
+    DocumentRangeFormattingParams, DocumentSymbolParams, DocumentSymbolResponse,
+    ExecuteCommandParams, FileRename, FormattingOptions, GotoCapability,
     GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverClientCapabilities,
     HoverParams, InlayHint, InlayHintClientCapabilities, InlayHintParams, Location,
     MarkupKind, MessageActionItemCapabilities, ParameterInformationSettings,
     PartialResultParams, Position, PrepareRenameResponse, Range, ReferenceContext,
-    ReferenceParams, RenameParams, SelectionRange, SelectionRangeParams,
-    SemanticTokens, SemanticTokensClientCapabilities, SemanticTokensParams,
-    ShowMessageRequestClientCapabilities, SignatureHelp,
+    ReferenceParams, RenameFilesParams, RenameParams, SelectionRange,
+    SelectionRangeParams, SemanticTokens, SemanticTokensClientCapabilities,
+    SemanticTokensParams, ShowMessageRequestClientCapabilities, SignatureHelp,
     SignatureHelpClientCapabilities, SignatureHelpParams,
     SignatureInformationSettings, SymbolInformation, TextDocumentClientCapabilities,
     TextDocumentIdentifier, TextDocumentItem, TextDocumentPositionParams,
     TextDocumentSyncClientCapabilities, TextEdit, Url,
     VersionedTextDocumentIdentifier, WindowClientCapabilities,
     WorkDoneProgressParams, WorkspaceClientCapabilities, WorkspaceEdit,
-    WorkspaceSymbolClientCapabilities, WorkspaceSymbolParams,
+    WorkspaceFileOperationsClientCapabilities, WorkspaceSymbolClientCapabilities,
+    WorkspaceSymbolParams,
 };
 use parking_lot::Mutex;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value;
 use tar::Archive;
+use tracing::error;
 
 use self::{
     catalog::PluginCatalog,
@@ -72,7 +78,7 @@
     psp::{ClonableCallback, PluginServerRpcHandler, RpcCallback},
     wasi::{load_volt, start_volt},
 };
-use crate::buffer::language_id_from_path;
+use crate::buffer::{language_id_from_path, word_completions};
 
 pub type PluginName = String;
 
@@ -102,6 +108,10 @@ pub enum PluginCatalogRpc {
     DidOpenTextDocument {
         document: TextDocumentItem,
     },
+    DidCloseTextDocument {
+        language_id: String,
+        document: TextDocumentIdentifier,
+    },
     DidChangeTextDocument {
         language_id: String,
         document: VersionedTextDocumentIdentifier,
@@ -124,6 +134,15 @@ pub enum PluginCatalogNotification {
     UpdatePluginConfigs(HashMap<String, HashMap<String, serde_json::Value>>),
     UnactivatedVolts(Vec<VoltMetadata>),
     PluginServerLoaded(PluginServerRpcHandler),
+    PluginServerStopped(PluginId),
+    RestartLanguageServer(String),
+    /// Restart a server at the request of the user
+    RestartServer(VoltID),
+    StopServer(VoltID),
+    CancelCompletions,
+    CancelWorkspaceSymbols,
+    /// Get the languages the loaded servers are for
+    ServedLanguages(Box<dyn FnOnce(Option<HashSet<String>>) + Send>),
     InstallVolt(VoltInfo),
     StopVolt(VoltInfo),
     EnableVolt(VoltInfo),
@@ -175,6 +194,9 @@ pub struct PluginCatalogRpcHandler {
     id: Arc<AtomicU64>,
     #[allow(dead_code, clippy::type_complexity)]
     pending: Arc<Mutex<HashMap<u64, Sender<Result<Value, RpcError>>>>>,
+    /// The language ids of the open files whose language isn't the one of
+    /// their path, as the user chose another one
+    languages: Arc<Mutex<HashMap<PathBuf, String>>>,
 }
 
 impl PluginCatalogRpcHandler {
@@ -187,9 +209,29 @@ pub fn new(core_rpc: CoreRpcHandler, proxy_rpc: ProxyRpcHandler) -> Self {
             plugin_rx: Arc::new(Mutex::new(Some(plugin_rx))),
             id: Arc::new(AtomicU64::new(0)),
             pending: Arc::new(Mutex::new(HashMap::new())),
+            languages: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
+    /// The language id the language servers know the file at `path` by
+    pub fn language_id(&self, path: &Path) -> String {
+        self.languages
+            .lock()
+            .get(path)
+            .cloned()
+            .unwrap_or_else(|| language_id_from_path(path).unwrap_or("").to_string())
+    }
+
+    /// Have the file at `path` be of the language of `language_id` rather
+    /// than of the one of its path, or of that one again if `None`.
+    pub fn set_language_id(&self, path: &Path, language_id: Option<String>) {
+        let mut languages = self.languages.lock();
+        match language_id {
+            Some(language_id) => languages.insert(path.to_path_buf(), language_id),
+            None => languages.remove(path),
+        };
+    }
+
     #[allow(dead_code)]
     fn handle_response(&self, id: RequestId, result: Result<Value, RpcError>) {
         if let Some(chan) = { self.pending.lock().remove(&id) } {
@@ -247,6 +289,12 @@ pub fn mainloop(&self, plugin: &mut PluginCatalog) {
                 PluginCatalogRpc::DidOpenTextDocument { document } => {
                     plugin.handle_did_open_text_document(document);
                 }
+                PluginCatalogRpc::DidCloseTextDocument {
+                    language_id,
+                    document,
+                } => {
+                    plugin.handle_did_close_text_document(language_id, document);
+                }
                 PluginCatalogRpc::DidSaveTextDocument {
                     language_id,
                     path,
@@ -311,6 +359,9 @@ fn send_request_to_all_plugins<P, Resp>(
         let got_success = Arc::new(AtomicBool::new(false));
         let request_sent = Arc::new(AtomicUsize::new(0));
         let err_received = Arc::new(AtomicUsize::new(0));
+        // The error of a plugin capable of the request says more than those of
+        // the plugins that aren't
+        let capable_err = Arc::new(Mutex::new(None));
         self.send_request(
             None,
             Some(request_sent.clone()),
@@ -336,12 +387,17 @@ fn send_request_to_all_plugins<P, Resp>(
                     }
                     Err(e) => Err(e),
                 };
-                if result.is_ok() {
-                    cb(plugin_id, result)
-                } else {
-                    let rx = err_received.fetch_add(1, Ordering::Relaxed) + 1;
-                    if request_sent.load(Ordering::Acquire) == rx {
-                        cb(plugin_id, result)
+                match result {
+                    Ok(_) => cb(plugin_id, result),
+                    Err(err) => {
+                        if err.code != RpcError::NOT_CAPABLE {
+                            *capable_err.lock() = Some(err.clone());
+                        }
+                        let rx = err_received.fetch_add(1, Ordering::Relaxed) + 1;
+                        if request_sent.load(Ordering::Acquire) == rx {
+                            let err = capable_err.lock().take().unwrap_or(err);
+                            cb(plugin_id, Err(err))
+                        }
                     }
                 }
             },
@@ -372,6 +428,22 @@ fn send_request<P: Serialize>(
         let _ = self.plugin_tx.send(rpc);
     }
 
+    fn send_notification<P: Serialize>(
+        &self,
+        method: &'static str,
+        params: P,
+        language_id: Option<String>,
+        path: Option<PathBuf>,
+    ) {
+        let params = serde_json::to_value(params).unwrap();
+        let _ = self.plugin_tx.send(PluginCatalogRpc::ServerNotification {
+            method,
+            params,
+            language_id,
+            path,
+        });
+    }
+
     pub fn format_semantic_tokens(
         &self,
         plugin_id: PluginId,
@@ -390,7 +462,7 @@ pub fn format_semantic_tokens(
     pub fn did_save_text_document(&self, path: &Path, text: Rope) {
         let text_document =
             TextDocumentIdentifier::new(Url::from_file_path(path).unwrap());
-        let language_id = language_id_from_path(path).unwrap_or("").to_string();
+        let language_id = self.language_id(path);
         let _ = self.plugin_tx.send(PluginCatalogRpc::DidSaveTextDocument {
             language_id,
             text_document,
@@ -411,7 +483,7 @@ pub fn did_change_text_document(
             Url::from_file_path(path).unwrap(),
             rev as i32,
         );
-        let language_id = language_id_from_path(path).unwrap_or("").to_string();
+        let language_id = self.language_id(path);
         let _ = self
             .plugin_tx
             .send(PluginCatalogRpc::DidChangeTextDocument {
@@ -443,8 +515,7 @@ pub fn get_definition(
             partial_result_params: PartialResultParams::default(),
         };
 
-        let language_id =
-            Some(language_id_from_path(path).unwrap_or("").to_string());
+        let language_id = Some(self.language_id(path));
         self.send_request_to_all_plugins(
             method,
             params,
@@ -474,8 +545,7 @@ pub fn get_type_definition(
             partial_result_params: PartialResultParams::default(),
         };
 
-        let language_id =
-            Some(language_id_from_path(path).unwrap_or("").to_string());
+        let language_id = Some(self.language_id(path));
         self.send_request_to_all_plugins(
             method,
             params,
@@ -508,8 +578,7 @@ pub fn get_references(
             },
         };
 
-        let language_id =
-            Some(language_id_from_path(path).unwrap_or("").to_string());
+        let language_id = Some(self.language_id(path));
         self.send_request_to_all_plugins(
             method,
             params,
@@ -524,6 +593,7 @@ pub fn get_code_actions(
         path: &Path,
         position: Position,
         diagnostics: Vec<Diagnostic>,
+        only: Option<Vec<CodeActionKind>>,
         cb: impl FnOnce(PluginId, Result<CodeActionResponse, RpcError>)
             + Clone
             + Send
@@ -537,15 +607,11 @@ pub fn get_code_actions(
                 start: position,
                 end: position,
             },
-            context: CodeActionContext {
-                diagnostics,
-                only: None,
-            },
+            context: CodeActionContext { diagnostics, only },
             work_done_progress_params: WorkDoneProgressParams::default(),
             partial_result_params: PartialResultParams::default(),
         };
-        let language_id =
-            Some(language_id_from_path(path).unwrap_or("").to_string());
+        let language_id = Some(self.language_id(path));
         self.send_request_to_all_plugins(
             method,
             params,
@@ -571,8 +637,7 @@ pub fn get_inlay_hints(
             work_done_progress_params: WorkDoneProgressParams::default(),
             range,
         };
-        let language_id =
-            Some(language_id_from_path(path).unwrap_or("").to_string());
+        let language_id = Some(self.language_id(path));
         self.send_request_to_all_plugins(
             method,
             params,
@@ -597,8 +662,7 @@ pub fn get_document_symbols(
             work_done_progress_params: WorkDoneProgressParams::default(),
             partial_result_params: PartialResultParams::default(),
         };
-        let language_id =
-            Some(language_id_from_path(path).unwrap_or("").to_string());
+        let language_id = Some(self.language_id(path));
         self.send_request_to_all_plugins(
             method,
             params,
@@ -608,10 +672,12 @@ pub fn get_document_symbols(
         );
     }
 
+    /// Get the symbols matching `query` from every server, merged, once all
+    /// of them answered. It's an error only if none of them could answer.
     pub fn get_workspace_symbols(
         &self,
         query: String,
-        cb: impl FnOnce(PluginId, Result<Vec<SymbolInformation>, RpcError>)
+        cb: impl FnOnce(Result<Vec<SymbolInformation>, RpcError>)
             + Clone
             + Send
             + 'static,
@@ -622,12 +688,57 @@ pub fn get_workspace_symbols(
             work_done_progress_params: WorkDoneProgressParams::default(),
             partial_result_params: PartialResultParams::default(),
         };
-        self.send_request_to_all_plugins(method, params, None, None, cb);
+        let request_sent = Arc::new(AtomicUsize::new(0));
+        let received = Arc::new(AtomicUsize::new(0));
+        // The symbols received so far, or the error if none were
+        let merged: Arc<Mutex<Option<Result<Vec<SymbolInformation>, RpcError>>>> =
+            Arc::new(Mutex::new(None));
+        self.send_request(
+            None,
+            Some(request_sent.clone()),
+            method,
+            params,
+            None,
+            None,
+            move |_, result| {
+                let result = result.and_then(|value| {
+                    serde_json::from_value::<Option<Vec<SymbolInformation>>>(value)
+                        .map(Option::unwrap_or_default)
+                        .map_err(|_| RpcError {
+                            code: 0,
+                            message: "deserialize error".to_string(),
+                        })
+                });
+                let mut merged = merged.lock();
+                let result = match (merged.take(), result) {
+                    (Some(Ok(mut symbols)), Ok(mut new_symbols)) => {
+                        symbols.append(&mut new_symbols);
+                        Ok(symbols)
+                    }
+                    (Some(Ok(symbols)), Err(_)) => Ok(symbols),
+                    // The error of a capable server says more than the others
+                    (Some(Err(err)), Err(new_err))
+                        if err.code != RpcError::NOT_CAPABLE
+                            || new_err.code == RpcError::NOT_CAPABLE =>
+                    {
+                        Err(err)
+                    }
+                    (_, result) => result,
+                };
+                let rx = received.fetch_add(1, Ordering::Relaxed) + 1;
+                if request_sent.load(Ordering::Acquire) == rx {
+                    cb(result);
+                } else {
+                    *merged = Some(result);
+                }
+            },
+        );
     }
 
     pub fn get_document_formatting(
         &self,
         path: &Path,
+        options: FormattingOptions,
         cb: impl FnOnce(PluginId, Result<Vec<TextEdit>, RpcError>)
             + Clone
             + Send
@@ -637,15 +748,38 @@ pub fn get_document_formatting(
         let method = Formatting::METHOD;
         let params = DocumentFormattingParams {
             text_document: TextDocumentIdentifier { uri },
-            options: FormattingOptions {
-                tab_size: 4,
-                insert_spaces: true,
-                ..Default::default()
-            },
+            options,
             work_done_progress_params: WorkDoneProgressParams::default(),
         };
-        let language_id =
-            Some(language_id_from_path(path).unwrap_or("").to_string());
+        let language_id = Some(self.language_id(path));
+        self.send_request_to_all_plugins(
+            method,
+            params,
+            language_id,
+            Some(path.to_path_buf()),
+            cb,
+        );
+    }
+
+    pub fn get_document_range_formatting(
+        &self,
+        path: &Path,
+        range: Range,
+        options: FormattingOptions,
+        cb: impl FnOnce(PluginId, Result<Vec<TextEdit>, RpcError>)
+            + Clone
+            + Send
+            + 'static,
+    ) {
+        let uri = Url::from_file_path(path).unwrap();
+        let method = RangeFormatting::METHOD;
+        let params = DocumentRangeFormattingParams {
+            text_document: TextDocumentIdentifier { uri },
+            range,
+            options,
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        let language_id = Some(self.language_id(path));
         self.send_request_to_all_plugins(
             method,
             params,
@@ -670,8 +804,7 @@ pub fn prepare_rename(
             text_document: TextDocumentIdentifier { uri },
             position,
         };
-        let language_id =
-            Some(language_id_from_path(path).unwrap_or("").to_string());
+        let language_id = Some(self.language_id(path));
         self.send_request_to_all_plugins(
             method,
             params,
@@ -701,8 +834,7 @@ pub fn rename(
             new_name,
             work_done_progress_params: WorkDoneProgressParams::default(),
         };
-        let language_id =
-            Some(language_id_from_path(path).unwrap_or("").to_string());
+        let language_id = Some(self.language_id(path));
         self.send_request_to_all_plugins(
             method,
             params,
@@ -712,6 +844,53 @@ pub fn rename(
         );
     }
 
+    /// Ask the servers that watch `from` for the edits that go with it being
+    /// renamed to `to`, like fixing the imports of a moved module. The edits
+    /// of all the servers that make any are given to `cb`.
+    pub fn will_rename_files(
+        &self,
+        from: &Path,
+        to: &Path,
+        cb: impl FnOnce(Vec<WorkspaceEdit>) + Clone + Send + 'static,
+    ) {
+        let method = WillRenameFiles::METHOD;
+        let params = rename_files_params(from, to);
+        let request_sent = Arc::new(AtomicUsize::new(0));
+        let received = Arc::new(AtomicUsize::new(0));
+        let edits = Arc::new(Mutex::new(Vec::new()));
+        self.send_request(
+            None,
+            Some(request_sent.clone()),
+            method,
+            params,
+            None,
+            None,
+            move |_, result| {
+                let edit = result.ok().and_then(|value| {
+                    serde_json::from_value::<Option<WorkspaceEdit>>(value)
+                        .ok()
+                        .flatten()
+                });
+                let mut edits = edits.lock();
+                edits.extend(edit);
+                let rx = received.fetch_add(1, Ordering::Relaxed) + 1;
+                if request_sent.load(Ordering::Acquire) == rx {
+                    cb(std::mem::take(&mut *edits));
+                }
+            },
+        );
+    }
+
+    /// Tell the servers that watch `from` that it was renamed to `to`.
+    pub fn did_rename_files(&self, from: &Path, to: &Path) {
+        self.send_notification(
+            DidRenameFiles::METHOD,
+            rename_files_params(from, to),
+            None,
+            None,
+        );
+    }
+
     pub fn get_semantic_tokens(
         &self,
         path: &Path,
@@ -727,8 +906,7 @@ pub fn get_semantic_tokens(
             work_done_progress_params: WorkDoneProgressParams::default(),
             partial_result_params: PartialResultParams::default(),
         };
-        let language_id =
-            Some(language_id_from_path(path).unwrap_or("").to_string());
+        let language_id = Some(self.language_id(path));
         self.send_request_to_all_plugins(
             method,
             params,
@@ -755,8 +933,7 @@ pub fn get_selection_range(
             work_done_progress_params: WorkDoneProgressParams::default(),
             partial_result_params: Default::default(),
         };
-        let language_id =
-            Some(language_id_from_path(path).unwrap_or("").to_string());
+        let language_id = Some(self.language_id(path));
         self.send_request_to_all_plugins(
             method,
             params,
@@ -781,8 +958,7 @@ pub fn hover(
             },
             work_done_progress_params: WorkDoneProgressParams::default(),
         };
-        let language_id =
-            Some(language_id_from_path(path).unwrap_or("").to_string());
+        let language_id = Some(self.language_id(path));
 
         self.send_request_to_all_plugins(
             method,
@@ -793,15 +969,24 @@ pub fn hover(
         );
     }
 
+    /// Ask the servers for completions at `position`. Without any server to
+    /// answer, the words of `text` that match `input` are offered instead.
     pub fn completion(
         &self,
         request_id: usize,
         path: &Path,
         input: String,
         position: Position,
+        trigger_character: Option<String>,
+        text: Option<Rope>,
     ) {
         let uri = Url::from_file_path(path).unwrap();
         let method = Completion::METHOD;
+        let trigger_kind = if trigger_character.is_some() {
+            CompletionTriggerKind::TRIGGER_CHARACTER
+        } else {
+            CompletionTriggerKind::INVOKED
+        };
         let params = CompletionParams {
             text_document_position: TextDocumentPositionParams {
                 text_document: TextDocumentIdentifier { uri },
@@ -809,20 +994,22 @@ pub fn completion(
             },
             work_done_progress_params: WorkDoneProgressParams::default(),
             partial_result_params: PartialResultParams::default(),
-            context: None,
+            context: Some(CompletionContext {
+                trigger_kind,
+                trigger_character,
+            }),
         };
 
         let core_rpc = self.core_rpc.clone();
-        let language_id =
-            Some(language_id_from_path(path).unwrap_or("").to_string());
+        let language_id = Some(self.language_id(path));
 
         self.send_request_to_all_plugins(
             method,
             params,
             language_id,
             Some(path.to_path_buf()),
-            move |plugin_id, result| {
-                if let Ok(value) = result {
+            move |plugin_id, result| match result {
+                Ok(value) => {
                     if let Ok(resp) =
                         serde_json::from_value::<CompletionResponse>(value)
                     {
@@ -830,6 +1017,20 @@ pub fn completion(
                             .completion_response(request_id, input, resp, plugin_id);
                     }
                 }
+                Err(_) => {
+                    let Some(text) = text else {
+                        return;
+                    };
+                    let items = word_completions(&text, &input, position);
+                    if !items.is_empty() {
+                        core_rpc.completion_response(
+                            request_id,
+                            input,
+                            CompletionResponse::Array(items),
+                            plugin_id,
+                        );
+                    }
+                }
             },
         );
     }
@@ -889,8 +1090,7 @@ pub fn signature_help(
         };
 
         let core_rpc = self.core_rpc.clone();
-        let language_id =
-            Some(language_id_from_path(path).unwrap_or("").to_string());
+        let language_id = Some(self.language_id(path));
         self.send_request(
             None,
             None,
@@ -900,8 +1100,16 @@ pub fn signature_help(
             Some(path.to_path_buf()),
             move |plugin_id, result| {
                 if let Ok(value) = result {
-                    if let Ok(resp) = serde_json::from_value::<SignatureHelp>(value)
+                    // No signature is passed on too, for the one shown to be
+                    // dismissed
+                    if let Ok(resp) =
+                        serde_json::from_value::<Option<SignatureHelp>>(value)
                     {
+                        let resp = resp.unwrap_or(SignatureHelp {
+                            signatures: Vec::new(),
+                            active_signature: None,
+                            active_parameter: None,
+                        });
                         core_rpc
                             .signature_help_response(request_id, resp, plugin_id);
                     }
@@ -945,6 +1153,29 @@ pub fn action_resolve(
         );
     }
 
+    pub fn execute_command(
+        &self,
+        plugin_id: PluginId,
+        command: Command,
+        cb: impl FnOnce(Result<Value, RpcError>) + Send + Clone + 'static,
+    ) {
+        let method = ExecuteCommand::METHOD;
+        let params = ExecuteCommandParams {
+            command: command.command,
+            arguments: command.arguments.unwrap_or_default(),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        self.send_request(
+            Some(plugin_id),
+            None,
+            method,
+            params,
+            None,
+            None,
+            move |_, result| cb(result),
+        );
+    }
+
     pub fn did_open_document(
         &self,
         path: &Path,
@@ -962,6 +1193,16 @@ pub fn did_open_document(
         });
     }
 
+    pub fn did_close_document(&self, path: &Path) {
+        let language_id = self.language_id(path);
+        let _ = self.plugin_tx.send(PluginCatalogRpc::DidCloseTextDocument {
+            language_id,
+            document: TextDocumentIdentifier::new(
+                Url::from_file_path(path).unwrap(),
+            ),
+        });
+    }
+
     pub fn unactivated_volts(&self, volts: Vec<VoltMetadata>) -> Result<()> {
         self.catalog_notification(PluginCatalogNotification::UnactivatedVolts(volts))
     }
@@ -975,6 +1216,35 @@ pub fn plugin_server_loaded(
         ))
     }
 
+    pub fn plugin_server_stopped(&self, plugin_id: PluginId) -> Result<()> {
+        self.catalog_notification(PluginCatalogNotification::PluginServerStopped(
+            plugin_id,
+        ))
+    }
+
+    pub fn cancel_completions(&self) -> Result<()> {
+        self.catalog_notification(PluginCatalogNotification::CancelCompletions)
+    }
+
+    pub fn cancel_workspace_symbols(&self) -> Result<()> {
+        self.catalog_notification(PluginCatalogNotification::CancelWorkspaceSymbols)
+    }
+
+    pub fn served_languages(
+        &self,
+        f: impl FnOnce(Option<HashSet<String>>) + Send + 'static,
+    ) -> Result<()> {
+        self.catalog_notification(PluginCatalogNotification::ServedLanguages(
+            Box::new(f),
+        ))
+    }
+
+    pub fn restart_language_server(&self, language_id: String) -> Result<()> {
+        self.catalog_notification(PluginCatalogNotification::RestartLanguageServer(
+            language_id,
+        ))
+    }
+
     pub fn update_plugin_configs(
         &self,
         configs: HashMap<String, HashMap<String, serde_json::Value>>,
@@ -988,6 +1258,14 @@ pub fn install_volt(&self, volt: VoltInfo) -> Result<()> {
         self.catalog_notification(PluginCatalogNotification::InstallVolt(volt))
     }
 
+    pub fn restart_server(&self, volt_id: VoltID) -> Result<()> {
+        self.catalog_notification(PluginCatalogNotification::RestartServer(volt_id))
+    }
+
+    pub fn stop_server(&self, volt_id: VoltID) -> Result<()> {
+        self.catalog_notification(PluginCatalogNotification::StopServer(volt_id))
+    }
+
     pub fn stop_volt(&self, volt: VoltInfo) -> Result<()> {
         self.catalog_notification(PluginCatalogNotification::StopVolt(volt))
     }
@@ -1189,7 +1467,7 @@ pub fn remove_volt(
             anyhow::anyhow!("don't have dir")
         })?;
         if let Err(e) = std::fs::remove_dir_all(path) {
-            eprintln!("Could not delete plugin folder: {e}");
+            error!("Could not delete plugin folder: {e}");
             catalog_rpc.core_rpc.volt_removing(
                 volt.clone(),
                 "Could not remove Plugin Directory".to_string(),
@@ -1202,6 +1480,20 @@ pub fn remove_volt(
     Ok(())
 }
 
+fn rename_files_params(from: &Path, to: &Path) -> RenameFilesParams {
+    let uri = |path: &Path| {
+        Url::from_file_path(path)
+            .map(|url| url.to_string())
+            .unwrap_or_default()
+    };
+    RenameFilesParams {
+        files: vec![FileRename {
+            old_uri: uri(from),
+            new_uri: uri(to),
+        }],
+    }
+}
+
 fn client_capabilities() -> ClientCapabilities {
     ClientCapabilities {
         text_document: Some(TextDocumentClientCapabilities {
@@ -1292,10 +1584,16 @@ fn client_capabilities() -> ClientCapabilities {
             ..Default::default()
         }),
         workspace: Some(WorkspaceClientCapabilities {
+            apply_edit: Some(true),
             symbol: Some(WorkspaceSymbolClientCapabilities {
                 ..Default::default()
             }),
             configuration: Some(false),
+            file_operations: Some(WorkspaceFileOperationsClientCapabilities {
+                will_rename: Some(true),
+                did_rename: Some(true),
+                ..Default::default()
+            }),
             ..Default::default()
         }),
         ..Default::default()
@@ -21,29 +21,33 @@
     style::{LineStyle, Style},
     RpcError,
 };
-use lapce_xi_rope::{Rope, RopeDelta};
+use lapce_xi_rope::{DeltaElement, Rope, RopeDelta};
 use lsp_types::{
     notification::{
-        DidChangeTextDocument, DidOpenTextDocument, DidSaveTextDocument,
-        Initialized, LogMessage, Notification, Progress, PublishDiagnostics,
-        ShowMessage,
+        Cancel, DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+        DidRenameFiles, DidSaveTextDocument, Initialized, LogMessage, Notification,
+        Progress, PublishDiagnostics, ShowMessage,
     },
     request::{
-        CodeActionRequest, CodeActionResolveRequest, Completion,
-        DocumentSymbolRequest, Formatting, GotoDefinition, GotoTypeDefinition,
-        HoverRequest, Initialize, InlayHintRequest, PrepareRenameRequest,
-        References, RegisterCapability, Rename, ResolveCompletionItem,
-        SelectionRangeRequest, SemanticTokensFullRequest, SignatureHelpRequest,
+        ApplyWorkspaceEdit, CodeActionRequest, CodeActionResolveRequest, Completion,
+        DocumentSymbolRequest, ExecuteCommand, Formatting, GotoDefinition,
+        GotoTypeDefinition, HoverRequest, Initialize, InlayHintRequest,
+        PrepareRenameRequest, RangeFormatting, References, RegisterCapability,
+        Rename, ResolveCompletionItem, SelectionRangeRequest,
+        SemanticTokensFullRequest, SignatureHelpRequest, WillRenameFiles,
         WorkDoneProgressCreate, WorkspaceSymbol,
     },
+    ApplyWorkspaceEditParams, ApplyWorkspaceEditResponse, CancelParams,
     CodeActionProviderCapability, DidChangeTextDocumentParams,
-    DidSaveTextDocumentParams, DocumentSelector, HoverProviderCapability,
-    InitializeResult, LogMessageParams, OneOf, ProgressParams,
+    DidSaveTextDocumentParams, DocumentSelector, FileOperationPatternKind,
+    FileOperationRegistrationOptions, FileRename, HoverProviderCapability,
+    InitializeResult, LogMessageParams, NumberOrString, OneOf, ProgressParams,
     PublishDiagnosticsParams, Range, Registration, RegistrationParams,
-    SemanticTokens, SemanticTokensLegend, SemanticTokensServerCapabilities,
-    ServerCapabilities, ShowMessageParams, TextDocumentContentChangeEvent,
-    TextDocumentIdentifier, TextDocumentSaveRegistrationOptions,
-    TextDocumentSyncCapability, TextDocumentSyncKind, TextDocumentSyncSaveOptions,
+    RenameOptions, SemanticTokens, SemanticTokensLegend,
+    SemanticTokensServerCapabilities, ServerCapabilities, ShowMessageParams,
+    TextDocumentContentChangeEvent, TextDocumentIdentifier,
+    TextDocumentSaveRegistrationOptions, TextDocumentSyncCapability,
+    TextDocumentSyncKind, TextDocumentSyncSaveOptions, Url,
     VersionedTextDocumentIdentifier,
 };
 use parking_lot::Mutex;
@@ -53,6 +57,7 @@
 };
 use serde::Serialize;
 use serde_json::Value;
+use tracing::{debug, error};
 
 use super::{
     lsp::{DocumentFilter, LspClient},
@@ -97,6 +102,16 @@ fn call(self: Box<F>, result: Result<Resp, Error>) {
     }
 }
 
+/// The content changes of a `didChange` notification, for the full and the
+/// incremental sync kind, which are worked out by the first server that needs
+/// them and shared with the others.
+pub type DocumentChanges = Arc<
+    Mutex<(
+        Option<TextDocumentContentChangeEvent>,
+        Option<Vec<TextDocumentContentChangeEvent>>,
+    )>,
+>;
+
 pub enum PluginHandlerNotification {
     Initialize,
     InitializeResult(InitializeResult),
@@ -142,12 +157,7 @@ pub enum PluginServerRpc {
         delta: RopeDelta,
         text: Rope,
         new_text: Rope,
-        change: Arc<
-            Mutex<(
-                Option<TextDocumentContentChangeEvent>,
-                Option<TextDocumentContentChangeEvent>,
-            )>,
-        >,
+        change: DocumentChanges,
     },
     FormatSemanticTokens {
         tokens: SemanticTokens,
@@ -160,6 +170,9 @@ pub enum PluginServerRpc {
 pub struct PluginServerRpcHandler {
     pub plugin_id: PluginId,
     pub volt_id: VoltID,
+    /// The languages of the documents the server is for, or `None` if it
+    /// takes documents of any language
+    pub languages: Option<Vec<String>>,
     rpc_tx: Sender<PluginServerRpc>,
     rpc_rx: Receiver<PluginServerRpc>,
     io_tx: Sender<JsonRpc>,
@@ -174,6 +187,7 @@ fn document_supported(
         path: Option<&Path>,
     ) -> bool;
     fn method_registered(&mut self, method: &'static str) -> bool;
+    fn request_supported(&self, method: &'static str, params: &Params) -> bool;
     fn handle_host_notification(&mut self, method: String, params: Params);
     fn handle_host_request(
         &mut self,
@@ -200,12 +214,7 @@ fn handle_did_change_text_document(
         delta: RopeDelta,
         text: Rope,
         new_text: Rope,
-        change: Arc<
-            Mutex<(
-                Option<TextDocumentContentChangeEvent>,
-                Option<TextDocumentContentChangeEvent>,
-            )>,
-        >,
+        change: DocumentChanges,
     );
     fn format_semantic_tokens(
         &self,
@@ -216,11 +225,16 @@ fn format_semantic_tokens(
 }
 
 impl PluginServerRpcHandler {
-    pub fn new(volt_id: VoltID, io_tx: Sender<JsonRpc>) -> Self {
+    pub fn new(
+        volt_id: VoltID,
+        languages: Option<Vec<String>>,
+        io_tx: Sender<JsonRpc>,
+    ) -> Self {
         let (rpc_tx, rpc_rx) = crossbeam_channel::unbounded();
 
         let rpc = Self {
             volt_id,
+            languages,
             plugin_id: PluginId::next(),
             rpc_tx,
             rpc_rx,
@@ -320,6 +334,8 @@ pub fn server_request<P: Serialize>(
         })
     }
 
+    /// Make a request to plugin/language server, with the response going to
+    /// `f`. Returns the id of the request, which it can be cancelled by.
     pub fn server_request_async<P: Serialize>(
         &self,
         method: &'static str,
@@ -328,7 +344,7 @@ pub fn server_request_async<P: Serialize>(
         path: Option<PathBuf>,
         check: bool,
         f: impl RpcCallback<Value, RpcError> + 'static,
-    ) {
+    ) -> Id {
         self.server_request_common(
             method,
             params,
@@ -336,7 +352,7 @@ pub fn server_request_async<P: Serialize>(
             path,
             check,
             ResponseHandler::Callback(Box::new(f)),
-        );
+        )
     }
 
     fn server_request_common<P: Serialize>(
@@ -347,12 +363,12 @@ fn server_request_common<P: Serialize>(
         path: Option<PathBuf>,
         check: bool,
         rh: ResponseHandler<Value, RpcError>,
-    ) {
-        let id = self.id.fetch_add(1, Ordering::Relaxed);
+    ) -> Id {
+        let id = Id::Num(self.id.fetch_add(1, Ordering::Relaxed) as i64);
         let params = Params::from(serde_json::to_value(params).unwrap());
         if check {
             let _ = self.rpc_tx.send(PluginServerRpc::ServerRequest {
-                id: Id::Num(id as i64),
+                id: id.clone(),
                 method,
                 params,
                 language_id,
@@ -360,8 +376,26 @@ fn server_request_common<P: Serialize>(
                 rh,
             });
         } else {
-            self.send_server_request(Id::Num(id as i64), method, params, rh);
+            self.send_server_request(id.clone(), method, params, rh);
         }
+        id
+    }
+
+    /// Tell the server the response to the request `id` isn't wanted anymore.
+    /// The server still answers it, usually with an error.
+    pub fn cancel_request(&self, id: Id) {
+        let id = match id {
+            Id::Num(id) => NumberOrString::Number(id as i32),
+            Id::Str(id) => NumberOrString::String(id),
+            Id::None(_) => return,
+        };
+        self.server_notification(
+            Cancel::METHOD,
+            CancelParams { id },
+            None,
+            None,
+            false,
+        );
     }
 
     pub fn handle_server_response(&self, id: Id, result: Result<Value, RpcError>) {
@@ -370,6 +404,18 @@ pub fn handle_server_response(&self, id: Id, result: Result<Value, RpcError>) {
         }
     }
 
+    /// Fail the requests still waiting for a response from a server that
+    /// stopped, which will never answer them.
+    pub fn fail_pending_requests(&self) {
+        let pending: Vec<_> = self.server_pending.lock().drain().collect();
+        for (_, handler) in pending {
+            handler.invoke(Err(RpcError {
+                code: 0,
+                message: "server stopped".to_string(),
+            }));
+        }
+    }
+
     pub fn shutdown(&self) {
         self.handle_rpc(PluginServerRpc::Handler(
             PluginHandlerNotification::Shutdown,
@@ -394,13 +440,11 @@ pub fn mainloop<H>(&self, handler: &mut H)
                     if handler
                         .document_supported(language_id.as_deref(), path.as_deref())
                         && handler.method_registered(method)
+                        && handler.request_supported(method, &params)
                     {
                         self.send_server_request(id, method, params, rh);
                     } else {
-                        rh.invoke(Err(RpcError {
-                            code: 0,
-                            message: "server not capable".to_string(),
-                        }));
+                        rh.invoke(Err(RpcError::not_capable("server not capable")));
                     }
                 }
                 PluginServerRpc::ServerNotification {
@@ -412,6 +456,7 @@ pub fn mainloop<H>(&self, handler: &mut H)
                     if handler
                         .document_supported(language_id.as_deref(), path.as_deref())
                         && handler.method_registered(method)
+                        && handler.request_supported(method, &params)
                     {
                         self.send_server_notification(method, params);
                     }
@@ -525,7 +570,7 @@ pub fn handle_plugin_server_message(
             None
         }
         Err(err) => {
-            eprintln!("parse error {err} message {message}");
+            error!("parse error {err} message {message}");
             None
         }
     }
@@ -607,6 +652,81 @@ pub fn document_supported(
         }
     }
 
+    /// Whether the server takes a request with `params`, for the requests
+    /// where that depends on more than the method. Completions triggered by a
+    /// character only go to the servers that listed it.
+    pub fn request_supported(&self, method: &'static str, params: &Params) -> bool {
+        match method {
+            Completion::METHOD => {
+                let trigger_character = match params {
+                    Params::Map(params) => params
+                        .get("context")
+                        .and_then(|context| context.get("triggerCharacter"))
+                        .and_then(Value::as_str),
+                    _ => None,
+                };
+                let Some(trigger_character) = trigger_character else {
+                    return true;
+                };
+                self.server_capabilities
+                    .completion_provider
+                    .as_ref()
+                    .and_then(|c| c.trigger_characters.as_ref())
+                    .map(|chars| chars.iter().any(|c| c == trigger_character))
+                    .unwrap_or(false)
+            }
+            // The renames of files only go to the servers that watch them
+            WillRenameFiles::METHOD | DidRenameFiles::METHOD => {
+                let Some(options) = self.file_rename_options(method) else {
+                    return false;
+                };
+                let files = match params {
+                    Params::Map(params) => params
+                        .get("files")
+                        .cloned()
+                        .and_then(|files| {
+                            serde_json::from_value::<Vec<FileRename>>(files).ok()
+                        })
+                        .unwrap_or_default(),
+                    _ => Vec::new(),
+                };
+                // The file is where it is before it's renamed, and where it
+                // went after
+                files.iter().any(|file| {
+                    let uri = if method == WillRenameFiles::METHOD {
+                        &file.old_uri
+                    } else {
+                        &file.new_uri
+                    };
+                    Url::parse(uri)
+                        .ok()
+                        .and_then(|url| url.to_file_path().ok())
+                        .map_or(false, |path| file_operation_matches(options, &path))
+                })
+            }
+            _ => true,
+        }
+    }
+
+    /// What the server said about the renames of files, for `method`, the
+    /// request before them or the notification after.
+    fn file_rename_options(
+        &self,
+        method: &str,
+    ) -> Option<&FileOperationRegistrationOptions> {
+        let operations = self
+            .server_capabilities
+            .workspace
+            .as_ref()?
+            .file_operations
+            .as_ref()?;
+        if method == WillRenameFiles::METHOD {
+            operations.will_rename.as_ref()
+        } else {
+            operations.did_rename.as_ref()
+        }
+    }
+
     pub fn method_registered(&mut self, method: &'static str) -> bool {
         match method {
             Initialize::METHOD => true,
@@ -620,7 +740,7 @@ pub fn method_registered(&mut self, method: &'static str) -> bool {
                 .as_ref()
                 .and_then(|c| c.resolve_provider)
                 .unwrap_or(false),
-            DidOpenTextDocument::METHOD => {
+            DidOpenTextDocument::METHOD | DidCloseTextDocument::METHOD => {
                 match &self.server_capabilities.text_document_sync {
                     Some(TextDocumentSyncCapability::Kind(kind)) => {
                         kind != &TextDocumentSyncKind::NONE
@@ -699,6 +819,15 @@ pub fn method_registered(&mut self, method: &'static str) -> bool {
                     OneOf::Right(_) => true,
                 })
                 .unwrap_or(false),
+            RangeFormatting::METHOD => self
+                .server_capabilities
+                .document_range_formatting_provider
+                .as_ref()
+                .map(|f| match f {
+                    OneOf::Left(is_capable) => *is_capable,
+                    OneOf::Right(_) => true,
+                })
+                .unwrap_or(false),
             SemanticTokensFullRequest::METHOD => {
                 self.server_capabilities.semantic_tokens_provider.is_some()
             }
@@ -711,16 +840,35 @@ pub fn method_registered(&mut self, method: &'static str) -> bool {
             WorkspaceSymbol::METHOD => {
                 self.server_capabilities.workspace_symbol_provider.is_some()
             }
-            PrepareRenameRequest::METHOD => {
-                self.server_capabilities.rename_provider.is_some()
-            }
-            Rename::METHOD => self.server_capabilities.rename_provider.is_some(),
+            // Only servers that said so can check where a rename is possible
+            PrepareRenameRequest::METHOD => matches!(
+                self.server_capabilities.rename_provider,
+                Some(OneOf::Right(RenameOptions {
+                    prepare_provider: Some(true),
+                    ..
+                }))
+            ),
+            Rename::METHOD => self
+                .server_capabilities
+                .rename_provider
+                .as_ref()
+                .map(|r| match r {
+                    OneOf::Left(is_capable) => *is_capable,
+                    OneOf::Right(_) => true,
+                })
+                .unwrap_or(false),
             SelectionRangeRequest::METHOD => {
                 self.server_capabilities.selection_range_provider.is_some()
             }
             CodeActionResolveRequest::METHOD => {
                 self.server_capabilities.code_action_provider.is_some()
             }
+            ExecuteCommand::METHOD => {
+                self.server_capabilities.execute_command_provider.is_some()
+            }
+            WillRenameFiles::METHOD | DidRenameFiles::METHOD => {
+                self.file_rename_options(method).is_some()
+            }
             _ => false,
         }
     }
@@ -791,7 +939,7 @@ fn register_capability(&mut self, registration: Registration) -> Result<()> {
                 });
             }
             _ => {
-                eprintln!(
+                debug!(
                     "don't handle register capability for {}",
                     registration.method
                 );
@@ -821,6 +969,16 @@ pub fn process_request(
     ) -> Result<Value> {
         match method.as_str() {
             WorkDoneProgressCreate::METHOD => Ok(Value::Null),
+            ApplyWorkspaceEdit::METHOD => {
+                let params: ApplyWorkspaceEditParams =
+                    serde_json::from_value(serde_json::to_value(params)?)?;
+                self.catalog_rpc.core_rpc.apply_workspace_edit(params.edit);
+                Ok(serde_json::to_value(ApplyWorkspaceEditResponse {
+                    applied: true,
+                    failure_reason: None,
+                    failed_change: None,
+                })?)
+            }
             RegisterCapability::METHOD => {
                 let params: RegistrationParams =
                     serde_json::from_value(serde_json::to_value(params)?)?;
@@ -893,7 +1051,7 @@ pub fn handle_notification(
                 self.catalog_rpc.core_rpc.log_message(message);
             }
             _ => {
-                eprintln!("host notificaton {method} not handled");
+                debug!("host notificaton {method} not handled");
             }
         }
         Ok(())
@@ -935,13 +1093,13 @@ pub fn handle_did_change_text_document(
         delta: RopeDelta,
         text: Rope,
         new_text: Rope,
-        change: Arc<
-            Mutex<(
-                Option<TextDocumentContentChangeEvent>,
-                Option<TextDocumentContentChangeEvent>,
-            )>,
-        >,
+        change: DocumentChanges,
     ) {
+        let path = document.uri.to_file_path().ok();
+        if !self.document_supported(Some(&lanaguage_id), path.as_deref()) {
+            return;
+        }
+
         let kind = match &self.server_capabilities.text_document_sync {
             Some(TextDocumentSyncCapability::Kind(kind)) => *kind,
             Some(TextDocumentSyncCapability::Options(options)) => {
@@ -951,9 +1109,9 @@ pub fn handle_did_change_text_document(
         };
 
         let mut existing = change.lock();
-        let change = match kind {
+        let changes = match kind {
             TextDocumentSyncKind::FULL => {
-                if let Some(c) = existing.0.as_ref() {
+                let change = if let Some(c) = existing.0.as_ref() {
                     c.clone()
                 } else {
                     let change = TextDocumentContentChangeEvent {
@@ -963,31 +1121,28 @@ pub fn handle_did_change_text_document(
                     };
                     existing.0 = Some(change.clone());
                     change
-                }
+                };
+                vec![change]
             }
             TextDocumentSyncKind::INCREMENTAL => {
                 if let Some(c) = existing.1.as_ref() {
                     c.clone()
                 } else {
-                    let change = get_document_content_change(&text, &delta)
-                        .unwrap_or_else(|| TextDocumentContentChangeEvent {
-                            range: None,
-                            range_length: None,
-                            text: new_text.to_string(),
-                        });
-                    existing.1 = Some(change.clone());
-                    change
+                    let changes = get_document_content_changes(&text, &delta);
+                    existing.1 = Some(changes.clone());
+                    changes
                 }
             }
             TextDocumentSyncKind::NONE => return,
             _ => return,
         };
-
-        let path = document.uri.to_file_path().ok();
+        if changes.is_empty() {
+            return;
+        }
 
         let params = DidChangeTextDocumentParams {
             text_document: document,
-            content_changes: vec![change],
+            content_changes: changes,
         };
 
         self.server_rpc.server_notification(
@@ -1018,50 +1173,100 @@ pub fn format_semantic_tokens(
     }
 }
 
-fn get_document_content_change(
-    text: &Rope,
-    delta: &RopeDelta,
-) -> Option<TextDocumentContentChangeEvent> {
-    let (interval, _) = delta.summary();
-    let (start, end) = interval.start_end();
-
-    let text = RopeTextRef::new(text);
-
-    // TODO: Handle more trivial cases like typing when there's a selection or transpose
-    if let Some(node) = delta.as_simple_insert() {
-        let (start, end) = interval.start_end();
-        let start = text.offset_to_position(start);
-
-        let end = text.offset_to_position(end);
-
-        let text = String::from(node);
-        let text_document_content_change_event = TextDocumentContentChangeEvent {
-            range: Some(Range { start, end }),
-            range_length: None,
-            text,
+/// Whether `path` is one of the files or folders of the `filters` a server
+/// watches the operations on.
+fn file_operation_matches(
+    options: &FileOperationRegistrationOptions,
+    path: &Path,
+) -> bool {
+    options.filters.iter().any(|filter| {
+        if filter
+            .scheme
+            .as_deref()
+            .map_or(false, |scheme| scheme != "file")
+        {
+            return false;
+        }
+        let pattern = &filter.pattern;
+        let kind_matches = match pattern.matches {
+            Some(FileOperationPatternKind::File) => !path.is_dir(),
+            Some(FileOperationPatternKind::Folder) => path.is_dir(),
+            None => true,
         };
+        let ignore_case = pattern
+            .options
+            .as_ref()
+            .and_then(|options| options.ignore_case)
+            .unwrap_or(false);
+        kind_matches
+            && globset::GlobBuilder::new(&pattern.glob)
+                .literal_separator(true)
+                .case_insensitive(ignore_case)
+                .build()
+                .map_or(false, |glob| glob.compile_matcher().is_match(path))
+    })
+}
 
-        return Some(text_document_content_change_event);
+/// The incremental changes of `delta` to `text`, with LSP positions in UTF-16
+/// code units. The server applies them one after the other, so they are in
+/// reverse order, which keeps the positions of each in terms of `text`.
+fn get_document_content_changes(
+    text: &Rope,
+    delta: &RopeDelta,
+) -> Vec<TextDocumentContentChangeEvent> {
+    // The replaced ranges of `text`, with what replaces them
+    let mut edits: Vec<(usize, usize, String)> = Vec::new();
+    let mut pending: Option<(usize, usize, String)> = None;
+    let mut offset = 0;
+    for el in delta.els.iter() {
+        match el {
+            DeltaElement::Copy(start, end) => {
+                if *start > offset {
+                    let edit =
+                        pending.get_or_insert((offset, offset, String::new()));
+                    edit.1 = *start;
+                }
+                edits.extend(pending.take());
+                offset = *end;
+            }
+            DeltaElement::Insert(node) => {
+                let edit = pending.get_or_insert((offset, offset, String::new()));
+                edit.2.push_str(&String::from(node));
+            }
+        }
+    }
+    if delta.base_len > offset {
+        let edit = pending.get_or_insert((offset, offset, String::new()));
+        edit.1 = delta.base_len;
+    }
+    edits.extend(pending.take());
+
+    // The whole text replaced, as when the buffer is resynced, is the full
+    // text without a range
+    if let [(0, end, new_text)] = edits.as_slice() {
+        if *end == delta.base_len {
+            return vec![TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: new_text.clone(),
+            }];
+        }
     }
-    // Or a simple delete
-    else if delta.is_simple_delete() {
-        let end_position = text.offset_to_position(end);
-
-        let start = text.offset_to_position(start);
 
-        let text_document_content_change_event = TextDocumentContentChangeEvent {
+    let text = RopeTextRef::new(text);
+    edits
+        .into_iter()
+        .rev()
+        .filter(|(start, end, new_text)| start != end || !new_text.is_empty())
+        .map(|(start, end, new_text)| TextDocumentContentChangeEvent {
             range: Some(Range {
-                start,
-                end: end_position,
+                start: text.offset_to_position(start),
+                end: text.offset_to_position(end),
             }),
             range_length: None,
-            text: String::new(),
-        };
-
-        return Some(text_document_content_change_event);
-    }
-
-    None
+            text: new_text,
+        })
+        .collect()
 }
 
 fn format_semantic_styles(
@@ -1121,3 +1326,76 @@ fn semantic_tokens_legend(
         ) => &options.semantic_tokens_options.legend,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use lapce_xi_rope::{DeltaBuilder, Interval, Rope};
+    use lsp_types::Position;
+
+    use super::get_document_content_changes;
+
+    fn changes(
+        text: &str,
+        edits: &[(usize, usize, &str)],
+    ) -> Vec<((u32, u32), (u32, u32), String)> {
+        let text = Rope::from(text);
+        let mut builder = DeltaBuilder::new(text.len());
+        for (start, end, new_text) in edits {
+            builder.replace(Interval::new(*start, *end), Rope::from(*new_text));
+        }
+        let pos = |pos: Position| (pos.line, pos.character);
+        get_document_content_changes(&text, &builder.build())
+            .into_iter()
+            .map(|change| {
+                let range = change.range.unwrap();
+                (pos(range.start), pos(range.end), change.text)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_content_changes_utf16() {
+        // The emoji is four bytes in UTF-8 but two code units in UTF-16
+        let text = "a😀b\r\nc😀d";
+        assert_eq!(
+            changes(text, &[(5, 6, "")]),
+            vec![((0, 3), (0, 4), "".into())]
+        );
+        assert_eq!(
+            changes(text, &[(13, 13, "x")]),
+            vec![((1, 3), (1, 3), "x".into())]
+        );
+        // Multiple edits come last to first
+        assert_eq!(
+            changes(text, &[(1, 5, "e"), (13, 13, "x")]),
+            vec![((1, 3), (1, 3), "x".into()), ((0, 1), (0, 3), "e".into())]
+        );
+    }
+
+    #[test]
+    fn test_content_changes_whole_text() {
+        let text = Rope::from("a😀b\r\nc😀d");
+        let delta = DeltaBuilder::new(text.len()).build();
+        assert!(get_document_content_changes(&text, &delta).is_empty());
+
+        let mut builder = DeltaBuilder::new(text.len());
+        builder.replace(Interval::new(0, text.len()), Rope::from("e"));
+        let changes = get_document_content_changes(&text, &builder.build());
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].range, None);
+        assert_eq!(changes[0].text, "e");
+    }
+
+    #[test]
+    fn test_content_changes_crlf() {
+        let text = "a😀b\r\nc😀d";
+        assert_eq!(
+            changes(text, &[(6, 8, " ")]),
+            vec![((0, 4), (1, 0), " ".into())]
+        );
+        assert_eq!(
+            changes(text, &[(8, 8, "\r\n")]),
+            vec![((1, 0), (1, 0), "\r\n".into())]
+        );
+    }
+}
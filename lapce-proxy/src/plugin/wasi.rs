@@ -23,19 +23,19 @@
 use lapce_xi_rope::{Rope, RopeDelta};
 use lsp_types::{
     notification::Initialized, request::Initialize, DocumentFilter,
-    InitializeParams, InitializedParams, TextDocumentContentChangeEvent,
-    TextDocumentIdentifier, Url, VersionedTextDocumentIdentifier,
+    InitializeParams, InitializedParams, TextDocumentIdentifier, Url,
+    VersionedTextDocumentIdentifier,
 };
-use parking_lot::Mutex;
 use psp_types::{Notification, Request};
+use tracing::warn;
 use wasi_experimental_http_wasmtime::{HttpCtx, HttpState};
 use wasmtime_wasi::WasiCtxBuilder;
 
 use super::{
     client_capabilities,
     psp::{
-        handle_plugin_server_message, PluginHandlerNotification, PluginHostHandler,
-        PluginServerHandler, PluginServerRpc, RpcCallback,
+        handle_plugin_server_message, DocumentChanges, PluginHandlerNotification,
+        PluginHostHandler, PluginServerHandler, PluginServerRpc, RpcCallback,
     },
     volt_icon, PluginCatalogRpcHandler,
 };
@@ -93,6 +93,10 @@ fn method_registered(&mut self, method: &'static str) -> bool {
         self.host.method_registered(method)
     }
 
+    fn request_supported(&self, method: &'static str, params: &Params) -> bool {
+        self.host.request_supported(method, params)
+    }
+
     fn document_supported(
         &mut self,
         language_id: Option<&str>,
@@ -155,12 +159,7 @@ fn handle_did_change_text_document(
         delta: RopeDelta,
         text: Rope,
         new_text: Rope,
-        change: Arc<
-            Mutex<(
-                Option<TextDocumentContentChangeEvent>,
-                Option<TextDocumentContentChangeEvent>,
-            )>,
-        >,
+        change: DocumentChanges,
     ) {
         self.host.handle_did_change_text_document(
             language_id,
@@ -438,7 +437,8 @@ pub fn start_volt(
     let mut store = wasmtime::Store::new(&engine, wasi);
 
     let (io_tx, io_rx) = crossbeam_channel::unbounded();
-    let rpc = PluginServerRpcHandler::new(meta.id(), io_tx);
+    // The language servers of the volt are started as servers of their own
+    let rpc = PluginServerRpcHandler::new(meta.id(), Some(Vec::new()), io_tx);
 
     let local_rpc = rpc.clone();
     let local_stdin = stdin.clone();
@@ -453,7 +453,7 @@ pub fn start_volt(
     })?;
     linker.func_wrap("lapce", "host_handle_stderr", move || {
         if let Ok(msg) = wasi_read_string(&stderr) {
-            eprintln!("got stderr from plugin: {msg}");
+            warn!("got stderr from plugin: {msg}");
         }
     })?;
     linker.module(&mut store, "", &module)?;
@@ -0,0 +1,173 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Read},
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+use lapce_rpc::{
+    core::CoreRpcHandler,
+    task::{TaskExit, TaskId},
+};
+use parking_lot::Mutex;
+
+/// How often a task whose output is closed is checked for having exited
+const WAIT_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The variables that have tools color their output even though it's piped,
+/// as the colors are shown
+const COLOR_ENV: [(&str, &str); 3] = [
+    ("CLICOLOR_FORCE", "1"),
+    ("FORCE_COLOR", "1"),
+    ("CARGO_TERM_COLOR", "always"),
+];
+
+/// The processes of the tasks that are running
+#[derive(Clone, Default)]
+pub struct Tasks(Arc<Mutex<HashMap<TaskId, Arc<Mutex<Child>>>>>);
+
+impl Tasks {
+    /// Run `command` in a shell, sending each line of its output and then how
+    /// it exited to the core.
+    pub fn run(
+        &self,
+        task_id: TaskId,
+        command: &str,
+        cwd: Option<PathBuf>,
+        core_rpc: CoreRpcHandler,
+    ) {
+        let mut child = match spawn(command, cwd) {
+            Ok(child) => child,
+            Err(err) => {
+                core_rpc.task_exited(task_id, TaskExit::Error(err.to_string()));
+                return;
+            }
+        };
+        let pipes: [Option<Box<dyn Read + Send>>; 2] = [
+            child.stdout.take().map(|pipe| Box::new(pipe) as _),
+            child.stderr.take().map(|pipe| Box::new(pipe) as _),
+        ];
+        let readers = pipes
+            .into_iter()
+            .flatten()
+            .map(|pipe| {
+                let core_rpc = core_rpc.clone();
+                thread::spawn(move || read_lines(task_id, pipe, &core_rpc))
+            })
+            .collect::<Vec<_>>();
+
+        let child = Arc::new(Mutex::new(child));
+        self.0.lock().insert(task_id, child.clone());
+        let tasks = self.clone();
+        thread::spawn(move || {
+            for reader in readers {
+                let _ = reader.join();
+            }
+            // The child isn't waited on while it's locked, so that it can
+            // still be killed if it closed its output and kept running
+            let exit = loop {
+                match child.lock().try_wait() {
+                    Ok(Some(status)) => {
+                        break status.code().map_or(TaskExit::Signal, TaskExit::Code)
+                    }
+                    Ok(None) => {}
+                    Err(err) => break TaskExit::Error(err.to_string()),
+                }
+                thread::sleep(WAIT_INTERVAL);
+            };
+            tasks.0.lock().remove(&task_id);
+            core_rpc.task_exited(task_id, exit);
+        });
+    }
+
+    pub fn kill(&self, task_id: TaskId) {
+        let child = self.0.lock().get(&task_id).cloned();
+        if let Some(child) = child {
+            kill_tree(&mut child.lock());
+        }
+    }
+
+    pub fn kill_all(&self) {
+        let children = self.0.lock().values().cloned().collect::<Vec<_>>();
+        for child in children {
+            kill_tree(&mut child.lock());
+        }
+    }
+}
+
+fn spawn(command: &str, cwd: Option<PathBuf>) -> std::io::Result<Child> {
+    shell_command(command, cwd)
+        .envs(COLOR_ENV)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+}
+
+/// `command` to be run in a shell, in its own process group to be killed with
+/// [`kill_tree`].
+pub(crate) fn shell_command(command: &str, cwd: Option<PathBuf>) -> Command {
+    #[cfg(not(target_os = "windows"))]
+    let mut process = {
+        let mut process = Command::new("sh");
+        process.arg("-c").arg(command);
+        // Its own process group, for what it starts to be killed along with it
+        std::os::unix::process::CommandExt::process_group(&mut process, 0);
+        process
+    };
+    #[cfg(target_os = "windows")]
+    let mut process = {
+        let mut process = Command::new("cmd");
+        process.arg("/C").arg(command);
+        // CREATE_NO_WINDOW
+        std::os::windows::process::CommandExt::creation_flags(
+            &mut process,
+            0x08000000,
+        );
+        process
+    };
+    if let Some(cwd) = cwd {
+        process.current_dir(cwd);
+    }
+    process
+}
+
+/// Kill the process of a task and the ones it started.
+pub(crate) fn kill_tree(child: &mut Child) {
+    let pid = child.id().to_string();
+    #[cfg(not(target_os = "windows"))]
+    let _ = Command::new("kill")
+        .args(["-TERM", "--", &format!("-{pid}")])
+        .status();
+    #[cfg(target_os = "windows")]
+    let _ = Command::new("taskkill")
+        .args(["/T", "/F", "/PID", &pid])
+        .status();
+    let _ = child.kill();
+}
+
+/// Send the lines of `pipe` to the core until it's closed. Of a line that's
+/// rewritten with carriage returns, like a progress bar, only the last text
+/// is kept.
+fn read_lines(
+    task_id: TaskId,
+    pipe: Box<dyn Read + Send>,
+    core_rpc: &CoreRpcHandler,
+) {
+    let mut reader = BufReader::new(pipe);
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf) {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+        let line = String::from_utf8_lossy(&buf);
+        let line = line.trim_end_matches(['\n', '\r']);
+        let line = line.rsplit('\r').next().unwrap_or(line);
+        core_rpc.task_output(task_id, line.to_string());
+    }
+}
@@ -10,17 +10,18 @@
 use crossbeam_channel::{Receiver, Sender};
 use lsp_types::{
     CompletionResponse, LogMessageParams, ProgressParams, PublishDiagnosticsParams,
-    ShowMessageParams, SignatureHelp,
+    ShowMessageParams, SignatureHelp, WorkspaceEdit,
 };
 use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 
 use crate::{
     dap_types::{self, DapId, RunDebugConfig, StackFrame, Stopped, ThreadId},
-    file::PathObject,
-    plugin::{PluginId, VoltInfo, VoltMetadata},
+    file::{FileEvent, PathObject},
+    plugin::{LanguageServerStatus, PluginId, VoltID, VoltInfo, VoltMetadata},
     proxy::ProxyStatus,
     source_control::DiffInfo,
+    task::{TaskExit, TaskId},
     terminal::TermId,
     RequestId, RpcError, RpcMessage,
 };
@@ -42,6 +43,14 @@ pub enum CoreNotification {
         path: PathBuf,
         content: String,
     },
+    /// The proxy missed an edit of the buffer of the file, so it needs its
+    /// whole text again
+    BufferOutOfSync {
+        path: PathBuf,
+    },
+    FileEvent {
+        event: FileEvent,
+    },
     CompletionResponse {
         request_id: usize,
         input: String,
@@ -57,6 +66,11 @@ pub enum CoreNotification {
         paths: Vec<PathObject>,
     },
     WorkspaceFileChange,
+    /// Files were added to or removed from the file index of the workspace
+    FileIndexChanged {
+        added: Vec<PathBuf>,
+        removed: Vec<PathBuf>,
+    },
     PublishDiagnostics {
         diagnostics: PublishDiagnosticsParams,
     },
@@ -67,6 +81,11 @@ pub enum CoreNotification {
         title: String,
         message: ShowMessageParams,
     },
+    /// An edit a language server asked to be made, usually while running one
+    /// of its commands
+    ApplyWorkspaceEdit {
+        edit: WorkspaceEdit,
+    },
     LogMessage {
         message: LogMessageParams,
     },
@@ -123,6 +142,28 @@ pub enum CoreNotification {
         path: PathBuf,
         breakpoints: Vec<dap_types::Breakpoint>,
     },
+    LanguageServerStatus {
+        /// The volt providing the server, or the one made up for a server
+        /// from the settings
+        volt_id: VoltID,
+        /// The name to show the status under
+        server: String,
+        status: LanguageServerStatus,
+    },
+    /// A line a language server wrote to its stderr
+    LanguageServerLog {
+        volt_id: VoltID,
+        line: String,
+    },
+    /// A line a task wrote to its stdout or stderr
+    TaskOutput {
+        task_id: TaskId,
+        line: String,
+    },
+    TaskExited {
+        task_id: TaskId,
+        exit: TaskExit,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -222,6 +263,10 @@ pub fn workspace_file_change(&self) {
         self.notification(CoreNotification::WorkspaceFileChange);
     }
 
+    pub fn file_index_changed(&self, added: Vec<PathBuf>, removed: Vec<PathBuf>) {
+        self.notification(CoreNotification::FileIndexChanged { added, removed });
+    }
+
     pub fn diff_info(&self, diff: DiffInfo) {
         self.notification(CoreNotification::DiffInfo { diff });
     }
@@ -230,6 +275,14 @@ pub fn open_file_changed(&self, path: PathBuf, content: String) {
         self.notification(CoreNotification::OpenFileChanged { path, content });
     }
 
+    pub fn buffer_out_of_sync(&self, path: PathBuf) {
+        self.notification(CoreNotification::BufferOutOfSync { path });
+    }
+
+    pub fn file_event(&self, event: FileEvent) {
+        self.notification(CoreNotification::FileEvent { event });
+    }
+
     pub fn completion_response(
         &self,
         request_id: usize,
@@ -300,6 +353,10 @@ pub fn show_message(&self, title: String, message: ShowMessageParams) {
         self.notification(CoreNotification::ShowMessage { title, message });
     }
 
+    pub fn apply_workspace_edit(&self, edit: WorkspaceEdit) {
+        self.notification(CoreNotification::ApplyWorkspaceEdit { edit });
+    }
+
     pub fn log_message(&self, message: LogMessageParams) {
         self.notification(CoreNotification::LogMessage { message });
     }
@@ -332,6 +389,31 @@ pub fn dap_stopped(
         });
     }
 
+    pub fn language_server_status(
+        &self,
+        volt_id: VoltID,
+        server: String,
+        status: LanguageServerStatus,
+    ) {
+        self.notification(CoreNotification::LanguageServerStatus {
+            volt_id,
+            server,
+            status,
+        });
+    }
+
+    pub fn language_server_log(&self, volt_id: VoltID, line: String) {
+        self.notification(CoreNotification::LanguageServerLog { volt_id, line });
+    }
+
+    pub fn task_output(&self, task_id: TaskId, line: String) {
+        self.notification(CoreNotification::TaskOutput { task_id, line });
+    }
+
+    pub fn task_exited(&self, task_id: TaskId, exit: TaskExit) {
+        self.notification(CoreNotification::TaskExited { task_id, exit });
+    }
+
     pub fn dap_continued(&self, dap_id: DapId) {
         self.notification(CoreNotification::DapContinued { dap_id });
     }
@@ -1,7 +1,9 @@
 use std::{
     cmp::{Ord, Ordering, PartialOrd},
     collections::HashMap,
+    iter::Peekable,
     path::{Path, PathBuf},
+    str::Chars,
 };
 
 use serde::{Deserialize, Serialize};
@@ -15,6 +17,15 @@ pub struct LineCol {
     pub column: usize,
 }
 
+/// A file or folder of the workspace being created, renamed or deleted, from
+/// the file explorer or outside of Lapce, which the open buffers follow
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FileEvent {
+    Created { path: PathBuf },
+    Renamed { from: PathBuf, to: PathBuf },
+    Deleted { path: PathBuf },
+}
+
 #[derive(
     Default, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize,
 )]
@@ -47,6 +58,72 @@ pub fn from_path(path: PathBuf, is_dir: bool) -> PathObject {
     }
 }
 
+/// Compare names the way people order them: ignoring case, and with the
+/// numbers in them compared by their value, so that `File2.txt` comes before
+/// `file10.txt`. Names that only differ in case or in leading zeros are then
+/// compared by their characters, so that no two different names are equal.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+    loop {
+        let order = match (a_chars.peek().copied(), b_chars.peek().copied()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x = digit_run(&mut a_chars);
+                let y = digit_run(&mut b_chars);
+                let x = x.trim_start_matches('0');
+                let y = y.trim_start_matches('0');
+                x.len().cmp(&y.len()).then_with(|| x.cmp(y))
+            }
+            (Some(x), Some(y)) => {
+                a_chars.next();
+                b_chars.next();
+                x.to_lowercase().cmp(y.to_lowercase())
+            }
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+}
+
+fn digit_run(chars: &mut Peekable<Chars>) -> String {
+    let mut run = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+        run.push(c);
+    }
+    run
+}
+
+/// Compare paths by their components' names, naturally with [`natural_cmp`]
+/// or else by their characters.
+pub fn compare_file_names(a: &Path, b: &Path, natural: bool) -> Ordering {
+    if !natural {
+        return a.cmp(b);
+    }
+    let mut a = a.components();
+    let mut b = b.components();
+    loop {
+        match (a.next(), b.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) => {
+                let order = natural_cmp(
+                    &x.as_os_str().to_string_lossy(),
+                    &y.as_os_str().to_string_lossy(),
+                )
+                .then_with(|| x.cmp(&y));
+                if order != Ordering::Equal {
+                    return order;
+                }
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct FileNodeItem {
     pub path: PathBuf,
@@ -65,14 +142,7 @@ fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
             _ => {}
         }
 
-        let self_file_name = self.path.file_name()?.to_str()?;
-        let other_file_name = other.path.file_name()?.to_str()?;
-
-        // TODO(dbuga): it would be nicer if human_sort had a `eq_ignore_ascii_case` function.
-        Some(human_sort::compare(
-            &self_file_name.to_lowercase(),
-            &other_file_name.to_lowercase(),
-        ))
+        Some(compare_file_names(&self.path, &other.path, true))
     }
 }
 
@@ -208,3 +278,62 @@ pub fn update_node_count(&mut self, path: &Path) -> Option<()> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{cmp::Ordering, path::Path};
+
+    use super::{compare_file_names, natural_cmp};
+
+    #[test]
+    fn test_natural_cmp() {
+        let mut names = vec![
+            "file10.txt",
+            "File2.txt",
+            "file1.txt",
+            "file02.txt",
+            "b",
+            "A",
+            "a",
+            "zebra",
+            "file1.txt.bak",
+        ];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            names,
+            vec![
+                "A",
+                "a",
+                "b",
+                "file1.txt",
+                "file1.txt.bak",
+                "File2.txt",
+                "file02.txt",
+                "file10.txt",
+                "zebra",
+            ]
+        );
+        assert_eq!(natural_cmp("v1.10", "v1.9"), Ordering::Greater);
+        assert_eq!(
+            natural_cmp("99999999999999999999999", "100000000000000000000000"),
+            Ordering::Less
+        );
+        assert_eq!(natural_cmp("same", "same"), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_compare_file_names() {
+        let a = Path::new("src/file2.rs");
+        let b = Path::new("src/file10.rs");
+        assert_eq!(compare_file_names(a, b, true), Ordering::Less);
+        assert_eq!(compare_file_names(a, b, false), Ordering::Greater);
+        assert_eq!(
+            compare_file_names(Path::new("Src/b"), Path::new("lib/a"), true),
+            Ordering::Greater
+        );
+        assert_eq!(
+            compare_file_names(Path::new("Src/b"), Path::new("lib/a"), false),
+            Ordering::Less
+        );
+    }
+}
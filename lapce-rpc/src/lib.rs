@@ -11,6 +11,7 @@
 pub mod source_control;
 pub mod stdio;
 pub mod style;
+pub mod task;
 pub mod terminal;
 
 pub use parse::{Call, RequestId, RpcObject};
@@ -30,3 +31,19 @@ pub struct RpcError {
     pub code: i64,
     pub message: String,
 }
+
+impl RpcError {
+    /// The code of the error for a request that no plugin, or no language
+    /// server of the document, is capable of
+    pub const NOT_CAPABLE: i64 = 1;
+    /// The code of the error for a file that couldn't be made because there
+    /// is one already where it would go
+    pub const ALREADY_EXISTS: i64 = 2;
+
+    pub fn not_capable(message: impl Into<String>) -> Self {
+        Self {
+            code: Self::NOT_CAPABLE,
+            message: message.into(),
+        }
+    }
+}
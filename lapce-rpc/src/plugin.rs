@@ -140,6 +140,36 @@ fn from(volt: &VoltInfo) -> Self {
     }
 }
 
+/// A language server the user configured for a language, run without a
+/// plugin.
+#[derive(Deserialize, Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct LanguageServerConfig {
+    /// The server executable, looked up in `PATH` unless it's a path
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Sent to the server as its `initializationOptions`
+    #[serde(default)]
+    pub options: Option<Value>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum LanguageServerStatus {
+    Starting,
+    Running,
+    /// The server crashed and will be started again after a delay
+    Restarting {
+        attempt: u32,
+    },
+    /// The server couldn't be started, or crashed too often to keep trying
+    Failed {
+        message: String,
+    },
+    /// The server was stopped by the user
+    Stopped,
+}
+
 #[cfg(test)]
 mod tests {
     use super::{VoltID, VoltInfo, VoltMetadata};
@@ -11,10 +11,11 @@
 use indexmap::IndexMap;
 use lapce_xi_rope::RopeDelta;
 use lsp_types::{
-    request::GotoTypeDefinitionResponse, CodeAction, CodeActionResponse,
-    CompletionItem, Diagnostic, DocumentSymbolResponse, GotoDefinitionResponse,
-    Hover, InlayHint, Location, Position, PrepareRenameResponse, SelectionRange,
-    SymbolInformation, TextDocumentItem, TextEdit, WorkspaceEdit,
+    request::GotoTypeDefinitionResponse, CodeAction, CodeActionKind,
+    CodeActionResponse, Command, CompletionItem, Diagnostic, DocumentSymbolResponse,
+    FormattingOptions, GotoDefinitionResponse, Hover, InlayHint, Location, Position,
+    PrepareRenameResponse, Range, SelectionRange, SymbolInformation,
+    TextDocumentItem, TextEdit, WorkspaceEdit,
 };
 use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
@@ -23,10 +24,11 @@
 use crate::{
     buffer::BufferId,
     dap_types::{DapId, RunDebugConfig, SourceBreakpoint, ThreadId},
-    file::{FileNodeItem, PathObject},
-    plugin::{PluginId, VoltInfo, VoltMetadata},
-    source_control::FileDiff,
+    file::{FileEvent, FileNodeItem, PathObject},
+    plugin::{LanguageServerConfig, PluginId, VoltID, VoltInfo, VoltMetadata},
+    source_control::{BlameHunk, FileDiff},
     style::SemanticStyles,
+    task::TaskId,
     terminal::TermId,
     RequestId, RpcError, RpcMessage,
 };
@@ -69,6 +71,19 @@ pub enum ProxyRequest {
         case_sensitive: bool,
         whole_word: bool,
         is_regex: bool,
+        /// Globs of the files to search, or empty to search them all
+        include: Vec<String>,
+        /// Globs of the files and folders to leave out
+        exclude: Vec<String>,
+    },
+    /// Apply the edits of a language server to files that aren't open, each
+    /// with the version of the document the edits were made for, if known
+    ApplyTextEdits {
+        files: Vec<(PathBuf, Option<i32>, Vec<TextEdit>)>,
+    },
+    /// The contents of the files on disk, whether or not they are open
+    ReadFiles {
+        paths: Vec<PathBuf>,
     },
     CompletionResolve {
         plugin_id: PluginId,
@@ -94,6 +109,9 @@ pub enum ProxyRequest {
     GitGetRemoteFileUrl {
         file: PathBuf,
     },
+    GitBlame {
+        path: PathBuf,
+    },
     GetReferences {
         path: PathBuf,
         position: Position,
@@ -127,6 +145,13 @@ pub enum ProxyRequest {
         path: PathBuf,
         position: Position,
         diagnostics: Vec<Diagnostic>,
+        /// The kinds of actions to ask for, or all of them if `None`
+        only: Option<Vec<CodeActionKind>>,
+    },
+    /// Run a command of the language server, such as one of a code action
+    ExecuteCommand {
+        plugin_id: PluginId,
+        command: Command,
     },
     GetDocumentSymbols {
         path: PathBuf,
@@ -137,10 +162,26 @@ pub enum ProxyRequest {
     },
     GetDocumentFormatting {
         path: PathBuf,
+        options: FormattingOptions,
+    },
+    GetDocumentRangeFormatting {
+        path: PathBuf,
+        range: Range,
+        options: FormattingOptions,
+    },
+    /// Format a document with a command of the user's, rather than with a
+    /// language server
+    FormatWithCommand {
+        path: PathBuf,
+        /// Run in a shell, with `{path}` being the path of the document
+        command: String,
     },
     GetOpenFilesContent {},
     GetFiles {
-        path: String,
+        /// The folder to list the files of when there's no workspace.
+        path: Option<PathBuf>,
+        /// Globs of the files and folders to leave out
+        exclude: Vec<String>,
     },
     ReadDir {
         path: PathBuf,
@@ -158,19 +199,43 @@ pub enum ProxyRequest {
     CreateFile {
         path: PathBuf,
     },
+    /// Write `content` to `path`, failing with [`RpcError::ALREADY_EXISTS`]
+    /// when there's a file there, unless it's to be overwritten
+    WriteFile {
+        path: PathBuf,
+        content: String,
+        overwrite: bool,
+    },
     CreateDirectory {
         path: PathBuf,
     },
     TrashPath {
         path: PathBuf,
     },
+    /// Delete a path for good, without moving it to the trash
+    DeletePath {
+        path: PathBuf,
+    },
+    /// Count the files in a folder, in its folders too
+    CountFiles {
+        path: PathBuf,
+    },
     DuplicatePath {
         existing_path: PathBuf,
         new_path: PathBuf,
     },
+    /// Rename `from` to `to`, failing with [`RpcError::ALREADY_EXISTS`] when
+    /// `to` exists, unless it's to be overwritten
     RenamePath {
         from: PathBuf,
         to: PathBuf,
+        overwrite: bool,
+    },
+    /// Ask the language servers for the edits that go with `from` being
+    /// renamed to `to`, like the fixes of the imports of a moved module
+    WillRenameFiles {
+        from: PathBuf,
+        to: PathBuf,
     },
 }
 
@@ -182,12 +247,16 @@ pub enum ProxyNotification {
         workspace: Option<PathBuf>,
         disabled_volts: Vec<VoltID>,
         plugin_configurations: HashMap<String, HashMap<String, serde_json::Value>>,
+        language_servers: HashMap<String, LanguageServerConfig>,
         window_id: usize,
         tab_id: usize,
     },
     OpenFileChanged {
         path: PathBuf,
     },
+    FileEvent {
+        event: FileEvent,
+    },
     OpenPaths {
         paths: Vec<PathObject>,
     },
@@ -197,7 +266,16 @@ pub enum ProxyNotification {
         path: PathBuf,
         input: String,
         position: Position,
-    },
+        /// The character typed that made the completion show, if it wasn't
+        /// asked for or typed as part of a word
+        trigger_character: Option<String>,
+    },
+    /// Cancel the completion requests still running, the completion having
+    /// been closed or moved elsewhere
+    CancelCompletion {},
+    /// Cancel the workspace symbol requests still running, the query having
+    /// changed
+    CancelWorkspaceSymbols {},
     SignatureHelp {
         request_id: usize,
         path: PathBuf,
@@ -208,6 +286,23 @@ pub enum ProxyNotification {
         delta: RopeDelta,
         rev: u64,
     },
+    /// The whole text of the buffer at `rev`, for when the proxy missed an
+    /// update
+    ResyncBuffer {
+        path: PathBuf,
+        content: String,
+        rev: u64,
+    },
+    /// The buffer isn't shown anymore, so the language servers can forget it
+    CloseBuffer {
+        path: PathBuf,
+    },
+    /// The buffer is of the language of the id, like `toml`, which the user
+    /// chose or associated with its path, or of the one of its path if none
+    SetBufferLanguage {
+        path: PathBuf,
+        language_id: Option<String>,
+    },
     UpdatePluginConfigs {
         configs: HashMap<String, HashMap<String, serde_json::Value>>,
     },
@@ -226,6 +321,13 @@ pub enum ProxyNotification {
     ReloadVolt {
         volt: VoltMetadata,
     },
+    /// Stop a language server and start it again, even if it failed
+    RestartLanguageServer {
+        volt_id: VoltID,
+    },
+    StopLanguageServer {
+        volt_id: VoltID,
+    },
     DisableVolt {
         volt: VoltInfo,
     },
@@ -256,6 +358,16 @@ pub enum ProxyNotification {
     TerminalClose {
         term_id: TermId,
     },
+    /// Run the shell `command` of a task in `cwd`, or in the workspace
+    RunTask {
+        task_id: TaskId,
+        command: String,
+        cwd: Option<PathBuf>,
+    },
+    /// Stop a task that's still running, along with what it started
+    KillTask {
+        task_id: TaskId,
+    },
     DapStart {
         config: RunDebugConfig,
         breakpoints: HashMap<PathBuf, Vec<SourceBreakpoint>>,
@@ -297,6 +409,9 @@ pub enum ProxyResponse {
     GitGetRemoteFileUrl {
         file_url: String,
     },
+    GitBlameResponse {
+        hunks: Vec<BlameHunk>,
+    },
     NewBufferResponse {
         content: String,
     },
@@ -307,6 +422,9 @@ pub enum ProxyResponse {
     ReadDirResponse {
         items: Vec<FileNodeItem>,
     },
+    CountFilesResponse {
+        count: usize,
+    },
     CompletionResolveResponse {
         item: Box<CompletionItem>,
     },
@@ -364,6 +482,23 @@ pub enum ProxyResponse {
     },
     GlobalSearchResponse {
         matches: IndexMap<PathBuf, Vec<SearchMatch>>,
+        /// Whether files were left out by the excluded globs
+        excluded: bool,
+    },
+    ApplyTextEditsResponse {
+        files: usize,
+        failures: Vec<(PathBuf, String)>,
+    },
+    ReadFilesResponse {
+        /// The content of each file, or why it couldn't be read
+        files: Vec<(PathBuf, Result<String, String>)>,
+    },
+    FormatWithCommandResponse {
+        /// The whole text of the document, formatted
+        text: String,
+    },
+    WillRenameFilesResponse {
+        edits: Vec<WorkspaceEdit>,
     },
     Success {},
     SaveResponse {},
@@ -506,6 +641,14 @@ pub fn reload_volt(&self, volt: VoltMetadata) {
         self.notification(ProxyNotification::ReloadVolt { volt });
     }
 
+    pub fn restart_language_server(&self, volt_id: VoltID) {
+        self.notification(ProxyNotification::RestartLanguageServer { volt_id });
+    }
+
+    pub fn stop_language_server(&self, volt_id: VoltID) {
+        self.notification(ProxyNotification::StopLanguageServer { volt_id });
+    }
+
     pub fn remove_volt(&self, volt: VoltMetadata) {
         self.notification(ProxyNotification::RemoveVolt { volt });
     }
@@ -528,6 +671,7 @@ pub fn initialize(
         workspace: Option<PathBuf>,
         disabled_volts: Vec<VoltID>,
         plugin_configurations: HashMap<String, HashMap<String, serde_json::Value>>,
+        language_servers: HashMap<String, LanguageServerConfig>,
         window_id: usize,
         tab_id: usize,
     ) {
@@ -535,6 +679,7 @@ pub fn initialize(
             workspace,
             disabled_volts,
             plugin_configurations,
+            language_servers,
             window_id,
             tab_id,
         });
@@ -546,15 +691,25 @@ pub fn completion(
         path: PathBuf,
         input: String,
         position: Position,
+        trigger_character: Option<String>,
     ) {
         self.notification(ProxyNotification::Completion {
             request_id,
             path,
             input,
             position,
+            trigger_character,
         });
     }
 
+    pub fn cancel_completion(&self) {
+        self.notification(ProxyNotification::CancelCompletion {});
+    }
+
+    pub fn cancel_workspace_symbols(&self) {
+        self.notification(ProxyNotification::CancelWorkspaceSymbols {});
+    }
+
     pub fn signature_help(
         &self,
         request_id: usize,
@@ -587,6 +742,18 @@ pub fn terminal_close(&self, term_id: TermId) {
         self.notification(ProxyNotification::TerminalClose { term_id });
     }
 
+    pub fn run_task(&self, task_id: TaskId, command: String, cwd: Option<PathBuf>) {
+        self.notification(ProxyNotification::RunTask {
+            task_id,
+            command,
+            cwd,
+        });
+    }
+
+    pub fn kill_task(&self, task_id: TaskId) {
+        self.notification(ProxyNotification::KillTask { task_id });
+    }
+
     pub fn terminal_resize(&self, term_id: TermId, width: usize, height: usize) {
         self.notification(ProxyNotification::TerminalResize {
             term_id,
@@ -616,6 +783,23 @@ pub fn create_file(&self, path: PathBuf, f: impl ProxyCallback + 'static) {
         self.request_async(ProxyRequest::CreateFile { path }, f);
     }
 
+    pub fn write_file(
+        &self,
+        path: PathBuf,
+        content: String,
+        overwrite: bool,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(
+            ProxyRequest::WriteFile {
+                path,
+                content,
+                overwrite,
+            },
+            f,
+        );
+    }
+
     pub fn create_directory(&self, path: PathBuf, f: impl ProxyCallback + 'static) {
         self.request_async(ProxyRequest::CreateDirectory { path }, f);
     }
@@ -624,6 +808,14 @@ pub fn trash_path(&self, path: PathBuf, f: impl ProxyCallback + 'static) {
         self.request_async(ProxyRequest::TrashPath { path }, f);
     }
 
+    pub fn delete_path(&self, path: PathBuf, f: impl ProxyCallback + 'static) {
+        self.request_async(ProxyRequest::DeletePath { path }, f);
+    }
+
+    pub fn count_files(&self, path: PathBuf, f: impl ProxyCallback + 'static) {
+        self.request_async(ProxyRequest::CountFiles { path }, f);
+    }
+
     pub fn duplicate_path(
         &self,
         existing_path: PathBuf,
@@ -643,9 +835,26 @@ pub fn rename_path(
         &self,
         from: PathBuf,
         to: PathBuf,
+        overwrite: bool,
         f: impl ProxyCallback + 'static,
     ) {
-        self.request_async(ProxyRequest::RenamePath { from, to }, f);
+        self.request_async(
+            ProxyRequest::RenamePath {
+                from,
+                to,
+                overwrite,
+            },
+            f,
+        );
+    }
+
+    pub fn will_rename_files(
+        &self,
+        from: PathBuf,
+        to: PathBuf,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(ProxyRequest::WillRenameFiles { from, to }, f);
     }
 
     pub fn save_buffer_as(
@@ -667,12 +876,15 @@ pub fn save_buffer_as(
         );
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn global_search(
         &self,
         pattern: String,
         case_sensitive: bool,
         whole_word: bool,
         is_regex: bool,
+        include: Vec<String>,
+        exclude: Vec<String>,
         f: impl ProxyCallback + 'static,
     ) {
         self.request_async(
@@ -681,22 +893,36 @@ pub fn global_search(
                 case_sensitive,
                 whole_word,
                 is_regex,
+                include,
+                exclude,
             },
             f,
         );
     }
 
+    pub fn apply_text_edits(
+        &self,
+        files: Vec<(PathBuf, Option<i32>, Vec<TextEdit>)>,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(ProxyRequest::ApplyTextEdits { files }, f);
+    }
+
+    pub fn read_files(&self, paths: Vec<PathBuf>, f: impl ProxyCallback + 'static) {
+        self.request_async(ProxyRequest::ReadFiles { paths }, f);
+    }
+
     pub fn save(&self, rev: u64, path: PathBuf, f: impl ProxyCallback + 'static) {
         self.request_async(ProxyRequest::Save { rev, path }, f);
     }
 
-    pub fn get_files(&self, f: impl ProxyCallback + 'static) {
-        self.request_async(
-            ProxyRequest::GetFiles {
-                path: "path".into(),
-            },
-            f,
-        );
+    pub fn get_files(
+        &self,
+        path: Option<PathBuf>,
+        exclude: Vec<String>,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(ProxyRequest::GetFiles { path, exclude }, f);
     }
 
     pub fn get_open_files_content(&self) -> Result<ProxyResponse, RpcError> {
@@ -802,6 +1028,7 @@ pub fn get_code_actions(
         path: PathBuf,
         position: Position,
         diagnostics: Vec<Diagnostic>,
+        only: Option<Vec<CodeActionKind>>,
         f: impl ProxyCallback + 'static,
     ) {
         self.request_async(
@@ -809,17 +1036,54 @@ pub fn get_code_actions(
                 path,
                 position,
                 diagnostics,
+                only,
             },
             f,
         );
     }
 
+    pub fn execute_command(
+        &self,
+        plugin_id: PluginId,
+        command: Command,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(ProxyRequest::ExecuteCommand { plugin_id, command }, f);
+    }
+
     pub fn get_document_formatting(
         &self,
         path: PathBuf,
+        options: FormattingOptions,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(ProxyRequest::GetDocumentFormatting { path, options }, f);
+    }
+
+    pub fn get_document_range_formatting(
+        &self,
+        path: PathBuf,
+        range: Range,
+        options: FormattingOptions,
+        f: impl ProxyCallback + 'static,
+    ) {
+        self.request_async(
+            ProxyRequest::GetDocumentRangeFormatting {
+                path,
+                range,
+                options,
+            },
+            f,
+        );
+    }
+
+    pub fn format_with_command(
+        &self,
+        path: PathBuf,
+        command: String,
         f: impl ProxyCallback + 'static,
     ) {
-        self.request_async(ProxyRequest::GetDocumentFormatting { path }, f);
+        self.request_async(ProxyRequest::FormatWithCommand { path, command }, f);
     }
 
     pub fn get_semantic_tokens(
@@ -863,6 +1127,10 @@ pub fn git_get_remote_file_url(
         self.request_async(ProxyRequest::GitGetRemoteFileUrl { file }, f);
     }
 
+    pub fn git_blame(&self, path: PathBuf, f: impl ProxyCallback + 'static) {
+        self.request_async(ProxyRequest::GitBlame { path }, f);
+    }
+
     pub fn rename(
         &self,
         path: PathBuf,
@@ -888,6 +1156,21 @@ pub fn update(&self, path: PathBuf, delta: RopeDelta, rev: u64) {
         self.notification(ProxyNotification::Update { path, delta, rev });
     }
 
+    pub fn resync_buffer(&self, path: PathBuf, content: String, rev: u64) {
+        self.notification(ProxyNotification::ResyncBuffer { path, content, rev });
+    }
+
+    pub fn close_buffer(&self, path: PathBuf) {
+        self.notification(ProxyNotification::CloseBuffer { path });
+    }
+
+    pub fn set_buffer_language(&self, path: PathBuf, language_id: Option<String>) {
+        self.notification(ProxyNotification::SetBufferLanguage {
+            path,
+            language_id,
+        });
+    }
+
     pub fn update_plugin_configs(
         &self,
         configs: HashMap<String, HashMap<String, serde_json::Value>>,
@@ -28,3 +28,23 @@ pub fn path(&self) -> &PathBuf {
         }
     }
 }
+
+/// The lines of a file that were last changed by the same commit
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BlameHunk {
+    /// The first line of the hunk, counting from 0
+    pub start_line: usize,
+    pub lines: usize,
+    /// `None` for lines that were not committed yet
+    pub commit: Option<BlameCommit>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BlameCommit {
+    pub id: String,
+    pub author: String,
+    /// Seconds since the Unix epoch
+    pub time: i64,
+    pub summary: String,
+    pub message: String,
+}
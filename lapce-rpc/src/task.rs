@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+
+use crate::counter::Counter;
+
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct TaskId(pub u64);
+
+impl TaskId {
+    pub fn next() -> Self {
+        static TASK_ID_COUNTER: Counter = Counter::new();
+        Self(TASK_ID_COUNTER.next())
+    }
+}
+
+/// How the command of a task ended
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TaskExit {
+    /// It exited with the code
+    Code(i32),
+    /// It was ended by a signal, like when it's killed
+    Signal,
+    /// It couldn't be run
+    Error(String),
+}